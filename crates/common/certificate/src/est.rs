@@ -0,0 +1,287 @@
+//! Helpers to exchange certificates with an EST ([RFC 7030](https://datatracker.ietf.org/doc/html/rfc7030)) server,
+//! as the Cumulocity CA.
+
+/// The path prefix of all the EST operations, as defined by [RFC 7030](https://datatracker.ietf.org/doc/html/rfc7030#section-3.2.2)
+const EST_PATH_PREFIX: &str = "/.well-known/est";
+
+/// Return the URL of an EST operation (`cacerts`, `simpleenroll` or `simplereenroll`)
+///
+/// The EST server can be given either as a base URL, e.g. `https://est.example.com`,
+/// or including the EST path prefix and an optional CA label,
+/// e.g. `https://est.example.com/.well-known/est/arbitraryLabel1`.
+pub fn est_endpoint(server: &str, operation: &str) -> String {
+    let server = server.trim_end_matches('/');
+    if server.contains(EST_PATH_PREFIX) {
+        format!("{server}/{operation}")
+    } else {
+        format!("{server}{EST_PATH_PREFIX}/{operation}")
+    }
+}
+
+/// Return the content of a PEM encoded CSR in the format expected by an EST server
+///
+/// i.e. the base64 DER encoding, without the PEM header and footer.
+pub fn parse_csr_contents(csr: String) -> String {
+    let pem_header = if csr.contains("-----BEGIN NEW CERTIFICATE REQUEST-----\n") {
+        "NEW CERTIFICATE REQUEST"
+    } else {
+        "CERTIFICATE REQUEST"
+    };
+    // Don't assume that the CSR starts with the BEGIN block, as some tools
+    // like gnutls certtool add details about the CSR in plain text by default.
+    let csr = csr
+        .split(format!("-----BEGIN {pem_header}-----\n").as_str())
+        .last()
+        .unwrap_or(&csr);
+    let csr = csr
+        .split(format!("-----END {pem_header}-----\n").as_str())
+        .next()
+        .unwrap_or(csr)
+        .to_string();
+    csr
+}
+
+/// Extract the x509 certificates from a pkcs7 pem
+///
+/// All the certificates are returned as a single PEM string,
+/// i.e. the issued certificate for `simpleenroll`
+/// and the chain of CA certificates for `cacerts`.
+///
+/// EST returns certificates using
+/// [application/pkcs7-mime;smime-type=certs-only](https://datatracker.ietf.org/doc/html/rfc5273.html#page-3).
+/// Meaning the content is a:
+/// - base64-encoded
+/// - BER [SignedData object](https://datatracker.ietf.org/doc/html/rfc2315.html#section-9.1)
+pub fn pk7_to_x509(pk7_base64: String) -> Result<String, IllFormedPk7Cert> {
+    use base64::prelude::*;
+    use rasn::ber;
+    use rasn::der;
+    use rasn_cms::ContentInfo;
+    use rasn_cms::SignedData;
+
+    let pk7_ber = BASE64_STANDARD.decode(pk7_base64.replace(['\n', '\r'], ""))?;
+    let content_info = ber::decode::<ContentInfo>(&pk7_ber)?;
+    let pk7 = ber::decode::<SignedData>(content_info.content.as_bytes())?;
+    let x509_pem: Result<Vec<_>, IllFormedPk7Cert> = if let Some(certificates) = pk7.certificates {
+        certificates
+            .to_vec()
+            .iter()
+            .map(|cert| {
+                der::encode(cert)
+                    .map_err(|err| IllFormedPk7Cert::IllFormedCMS(format!("{err}")))
+                    .map(|x509_der| pem::encode(&pem::Pem::new("CERTIFICATE", x509_der)))
+            })
+            .collect()
+    } else {
+        Err(IllFormedPk7Cert::MissingCertificate)
+    };
+
+    Ok(x509_pem?.join("\r\n"))
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum IllFormedPk7Cert {
+    #[error(transparent)]
+    NotBase64(#[from] base64::DecodeError),
+
+    #[error("Invalid pkcs#7 certificate: {0}")]
+    IllFormedCMS(String),
+
+    #[error("No certificate found in pkcs#7 content")]
+    MissingCertificate,
+}
+
+impl From<rasn::error::DecodeError> for IllFormedPk7Cert {
+    fn from(value: rasn::error::DecodeError) -> Self {
+        IllFormedPk7Cert::IllFormedCMS(format!("{value}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PemCertificate;
+
+    #[test]
+    fn est_endpoints_are_derived_from_the_server_url() {
+        assert_eq!(
+            est_endpoint("https://est.example.com", "cacerts"),
+            "https://est.example.com/.well-known/est/cacerts"
+        );
+        assert_eq!(
+            est_endpoint("https://est.example.com:8443/", "simpleenroll"),
+            "https://est.example.com:8443/.well-known/est/simpleenroll"
+        );
+        assert_eq!(
+            est_endpoint("https://est.example.com/.well-known/est", "simpleenroll"),
+            "https://est.example.com/.well-known/est/simpleenroll"
+        );
+        assert_eq!(
+            est_endpoint(
+                "https://est.example.com/.well-known/est/devices/",
+                "simplereenroll"
+            ),
+            "https://est.example.com/.well-known/est/devices/simplereenroll"
+        );
+    }
+
+    #[test]
+    fn decode_certificate() {
+        let pk7 = r#"
+MIAGCSqGSIb3DQEHAqCAMIACAQExADALBgkqhkiG9w0BBwGggDCCAXkwggEgoAMC
+AQICBgGVPZIizTAKBggqhkjOPQQDAjBCMRYwFAYDVQQGEw1Vbml0ZWQgU3RhdGVz
+MRMwEQYDVQQKEwpDdW11bG9jaXR5MRMwEQYDVQQDEwptYW5hZ2VtZW50MB4XDTI1
+MDIyNTE0NDU0MloXDTI2MDIyNDA5NDE0NFowRjEaMBgGA1UEAwwRZGlkaWVyLWRl
+dmljZS0wMDExEjAQBgNVBAoMCVRoaW4gRWRnZTEUMBIGA1UECwwLVGVzdCBEZXZp
+Y2UwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATwSjNE/7AJZEtrXW2CP2LSLlcl
+wDyh4YwHmpwDhnTCm+ZxeeXBUcUARcFXOtdmxMset9CgMQl1Fjw255dISpqiMAoG
+CCqGSM49BAMCA0cAMEQCICapYBWyzrDU36IVEtyOfdlDA0bW9HE3pwHz2X9LAgl1
+AiAD0naayxieH0RVE1vJtdD3iCJHrzLNM3Eff2gNOhuzJAAAMQAAAAAAAAA=
+"#
+        .to_string();
+
+        // Computed using `openssl pkcs7 -print_certs`
+        let expected_x509 = r#"
+-----BEGIN CERTIFICATE-----
+MIIBeTCCASCgAwIBAgIGAZU9kiLNMAoGCCqGSM49BAMCMEIxFjAUBgNVBAYTDVVu
+aXRlZCBTdGF0ZXMxEzARBgNVBAoTCkN1bXVsb2NpdHkxEzARBgNVBAMTCm1hbmFn
+ZW1lbnQwHhcNMjUwMjI1MTQ0NTQyWhcNMjYwMjI0MDk0MTQ0WjBGMRowGAYDVQQD
+DBFkaWRpZXItZGV2aWNlLTAwMTESMBAGA1UECgwJVGhpbiBFZGdlMRQwEgYDVQQL
+DAtUZXN0IERldmljZTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABPBKM0T/sAlk
+S2tdbYI/YtIuVyXAPKHhjAeanAOGdMKb5nF55cFRxQBFwVc612bEyx630KAxCXUW
+PDbnl0hKmqIwCgYIKoZIzj0EAwIDRwAwRAIgJqlgFbLOsNTfohUS3I592UMDRtb0
+cTenAfPZf0sCCXUCIAPSdprLGJ4fRFUTW8m10PeIIkevMs0zcR9/aA06G7Mk
+-----END CERTIFICATE-----
+"#
+        .to_string();
+
+        let x509 = pk7_to_x509(pk7).unwrap();
+        let cert = PemCertificate::from_pem_string(&x509).unwrap();
+
+        assert_eq!(
+            x509.replace(['\n', '\r'], ""),
+            expected_x509.replace(['\n', '\r'], "")
+        );
+
+        assert_eq!(
+            cert.subject().unwrap(),
+            "CN=didier-device-001, O=Thin Edge, OU=Test Device".to_string()
+        );
+        assert_eq!(
+            cert.issuer().unwrap(),
+            "C=United States, O=Cumulocity, CN=management".to_string()
+        );
+        assert_eq!(
+            cert.not_before().unwrap(),
+            "Tue, 25 Feb 2025 14:45:42 +0000".to_string()
+        );
+        assert_eq!(
+            cert.not_after().unwrap(),
+            "Tue, 24 Feb 2026 09:41:44 +0000".to_string()
+        );
+        assert_eq!(
+            cert.thumbprint().unwrap(),
+            "9C68C7EC9A860366FB8D2697C53B2543D9EA525C".to_string()
+        );
+    }
+
+    #[test]
+    fn parse_csr_contents_from_tools() {
+        // Computed using `$ gnutls-certtool --generate-request --template cert.template --load-privkey '<pkcs_url>' --load-pubkey tedge.pub`
+        let gntls_certtool_csr = r#"
+PKCS #10 Certificate Request Information:
+	Version: 1
+	Subject: CN=test001,OU=Test Device,O=Thin Edge
+	Subject Public Key Algorithm: EC/ECDSA
+	Algorithm Security Level: High (256 bits)
+		Curve:	SECP256R1
+		X:
+			00:d3:18:d2:54:e4:1c:d8:d0:38:46:01:b3:e0:89:2c
+			39:ce:09:b3:8f:23:4c:20:f6:b8:4d:4d:e1:1a:1e:b5
+			a8
+		Y:
+			4f:84:a9:df:bf:97:f5:f6:8d:81:f4:1e:13:71:4b:6d
+			40:52:e9:40:81:ba:e5:84:b6:38:0c:e4:90:cf:b4:3f
+	Signature Algorithm: ECDSA-SHA256
+	Attributes:
+		Extensions:
+			Basic Constraints (critical):
+				Certificate Authority (CA): TRUE
+			Key Usage (critical):
+				Digital signature.
+Other Information:
+	Public Key ID:
+		sha1:8b6aa6928b774d16fcb2bf967072b09ff68cd521
+		sha256:4bae00d825d04602c4fed9fcec2887e5ae4a8b97d7f42580fbe24f9c72ef67ef
+	Public Key PIN:
+		pin-sha256:S64A2CXQRgLE/tn87CiH5a5Ki5fX9CWA++JPnHLvZ+8=
+
+Self signature: verified
+
+-----BEGIN NEW CERTIFICATE REQUEST-----
+MIIBKTCB0AIBADA8MRIwEAYDVQQKEwlUaGluIEVkZ2UxFDASBgNVBAsTC1Rlc3Qg
+RGV2aWNlMRAwDgYDVQQDEwd0ZXN0MDAxMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcD
+QgAE0xjSVOQc2NA4RgGz4IksOc4Js48jTCD2uE1N4RoetahPhKnfv5f19o2B9B4T
+cUttQFLpQIG65YS2OAzkkM+0P6AyMDAGCSqGSIb3DQEJDjEjMCEwDwYDVR0TAQH/
+BAUwAwEB/zAOBgNVHQ8BAf8EBAMCB4AwCgYIKoZIzj0EAwIDSAAwRQIhALxYCCHa
+9ZdaZCd7YhhWmVcq+/KSLPK/PUvfV83PDy5TAiAA/e9yrH6rrLGhkhEPtTbyBbBe
+yzaWmqSb64bH/x0TjQ==
+-----END NEW CERTIFICATE REQUEST-----
+"#
+        .to_string();
+
+        // Computed using `$ gnutls-certtool --generate-request --no-text --template cert.template --load-privkey '<pkcs_url>' --load-pubkey tedge.pub`
+        let gntls_certtool_csr_without_text = r#"
+-----BEGIN NEW CERTIFICATE REQUEST-----
+MIIBKTCB0AIBADA8MRIwEAYDVQQKEwlUaGluIEVkZ2UxFDASBgNVBAsTC1Rlc3Qg
+RGV2aWNlMRAwDgYDVQQDEwd0ZXN0MDAxMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcD
+QgAE0xjSVOQc2NA4RgGz4IksOc4Js48jTCD2uE1N4RoetahPhKnfv5f19o2B9B4T
+cUttQFLpQIG65YS2OAzkkM+0P6AyMDAGCSqGSIb3DQEJDjEjMCEwDwYDVR0TAQH/
+BAUwAwEB/zAOBgNVHQ8BAf8EBAMCB4AwCgYIKoZIzj0EAwIDSAAwRQIhALxYCCHa
+9ZdaZCd7YhhWmVcq+/KSLPK/PUvfV83PDy5TAiAA/e9yrH6rrLGhkhEPtTbyBbBe
+yzaWmqSb64bH/x0TjQ==
+-----END NEW CERTIFICATE REQUEST-----
+        "#
+        .to_string();
+
+        // Computed using `$ tedge cert renew --ca self-signed`
+        let tedge_csr = r#"
+-----BEGIN CERTIFICATE REQUEST-----
+MIIBKTCB0AIBADA8MRIwEAYDVQQKEwlUaGluIEVkZ2UxFDASBgNVBAsTC1Rlc3Qg
+RGV2aWNlMRAwDgYDVQQDEwd0ZXN0MDAxMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcD
+QgAE0xjSVOQc2NA4RgGz4IksOc4Js48jTCD2uE1N4RoetahPhKnfv5f19o2B9B4T
+cUttQFLpQIG65YS2OAzkkM+0P6AyMDAGCSqGSIb3DQEJDjEjMCEwDwYDVR0TAQH/
+BAUwAwEB/zAOBgNVHQ8BAf8EBAMCB4AwCgYIKoZIzj0EAwIDSAAwRQIhALxYCCHa
+9ZdaZCd7YhhWmVcq+/KSLPK/PUvfV83PDy5TAiAA/e9yrH6rrLGhkhEPtTbyBbBe
+yzaWmqSb64bH/x0TjQ==
+-----END CERTIFICATE REQUEST-----
+        "#
+        .to_string();
+
+        let expected_contents = r#"
+MIIBKTCB0AIBADA8MRIwEAYDVQQKEwlUaGluIEVkZ2UxFDASBgNVBAsTC1Rlc3Qg
+RGV2aWNlMRAwDgYDVQQDEwd0ZXN0MDAxMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcD
+QgAE0xjSVOQc2NA4RgGz4IksOc4Js48jTCD2uE1N4RoetahPhKnfv5f19o2B9B4T
+cUttQFLpQIG65YS2OAzkkM+0P6AyMDAGCSqGSIb3DQEJDjEjMCEwDwYDVR0TAQH/
+BAUwAwEB/zAOBgNVHQ8BAf8EBAMCB4AwCgYIKoZIzj0EAwIDSAAwRQIhALxYCCHa
+9ZdaZCd7YhhWmVcq+/KSLPK/PUvfV83PDy5TAiAA/e9yrH6rrLGhkhEPtTbyBbBe
+yzaWmqSb64bH/x0TjQ==
+"#
+        .to_string();
+
+        assert_eq!(
+            parse_csr_contents(gntls_certtool_csr).replace(['\n', '\r'], ""),
+            expected_contents.replace(['\n', '\r'], "")
+        );
+
+        assert_eq!(
+            parse_csr_contents(gntls_certtool_csr_without_text).replace(['\n', '\r'], ""),
+            expected_contents.replace(['\n', '\r'], "")
+        );
+
+        assert_eq!(
+            parse_csr_contents(tedge_csr).replace(['\n', '\r'], ""),
+            expected_contents.replace(['\n', '\r'], "")
+        );
+    }
+}
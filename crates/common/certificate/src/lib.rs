@@ -15,7 +15,11 @@ mod cloud_root_certificate;
 pub use cloud_root_certificate::*;
 
 pub mod device_id;
+pub mod est;
 pub mod parse_root_certificate;
+mod shift;
+mod token_key_pair;
+pub use shift::CertificateShift;
 pub struct PemCertificate {
     pem: x509_parser::pem::Pem,
 }
@@ -145,6 +149,8 @@ pub enum KeyKind {
     New,
     /// Reuse the existing PEM-encoded key pair
     Reuse { keypair_pem: String },
+    /// Reuse the key pair stored in a PKCS#11 token, the private key never leaving the token
+    Cryptoki(parse_root_certificate::CryptokiConfig),
 }
 
 pub struct KeyCertPair {
@@ -215,15 +221,24 @@ impl KeyCertPair {
         let mut params = CertificateParams::default();
         params.distinguished_name = distinguished_name;
 
-        if let KeyKind::Reuse { keypair_pem } = key_kind {
-            // Use the same signing algorithm as the existing key
-            // Failing to do so leads to an error telling the algorithm is not compatible
-            let key_pair = KeyPair::from_pem(keypair_pem)?;
-            params.alg = key_pair.algorithm();
-            params.key_pair = Some(key_pair);
-        } else {
-            // ECDSA signing using the P-256 curves and SHA-256 hashing as per RFC 5758
-            params.alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+        match key_kind {
+            KeyKind::Reuse { keypair_pem } => {
+                // Use the same signing algorithm as the existing key
+                // Failing to do so leads to an error telling the algorithm is not compatible
+                let key_pair = KeyPair::from_pem(keypair_pem)?;
+                params.alg = key_pair.algorithm();
+                params.key_pair = Some(key_pair);
+            }
+            KeyKind::Cryptoki(cryptoki_config) => {
+                let key_pair = token_key_pair::TokenKeyPair::new(cryptoki_config.clone())?;
+                let key_pair = KeyPair::from_remote(Box::new(key_pair))?;
+                params.alg = key_pair.algorithm();
+                params.key_pair = Some(key_pair);
+            }
+            KeyKind::New => {
+                // ECDSA signing using the P-256 curves and SHA-256 hashing as per RFC 5758
+                params.alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+            }
         }
 
         Ok(params)
@@ -314,7 +329,7 @@ pub enum CertificateError {
     Other(#[from] anyhow::Error),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct CsrTemplate {
     pub max_cn_size: usize,
     pub validity_period_days: u32,
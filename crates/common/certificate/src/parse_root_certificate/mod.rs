@@ -61,9 +61,56 @@ pub fn create_tls_config_cryptoki(
     Ok(config)
 }
 
+/// Create a TLS ClientConfig that uses a PKCS#11 device for client authentication,
+/// trusting the platform root certificates as well as the given ones.
+///
+/// This TLS configuration is used to reach HTTPS endpoints, as an EST server,
+/// using the device certificate for client authentication.
+pub fn client_config_for_ca_certificates_with_cryptoki<P>(
+    root_certificates: impl IntoIterator<Item = P>,
+    client_certificate: impl AsRef<Path>,
+    cryptoki_config: CryptokiConfig,
+) -> Result<ClientConfig, CertificateError>
+where
+    P: AsRef<Path>,
+{
+    use rustls::sign::CertifiedKey;
+    use std::sync::Arc;
+    use tedge_p11_server::single_cert_and_key::SingleCertAndKey;
+
+    let roots = platform_root_store(root_certificates).map_err(anyhow::Error::from)?;
+    let cert_chain = read_cert_chain(client_certificate)?;
+    let key = tedge_p11_server::signing_key(cryptoki_config)?;
+
+    let certified_key = CertifiedKey {
+        cert: cert_chain,
+        key,
+        ocsp: None,
+    };
+    let resolver: SingleCertAndKey = certified_key.into();
+
+    Ok(ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_client_cert_resolver(Arc::new(resolver)))
+}
+
 pub fn client_config_for_ca_certificates<P>(
     root_certificates: impl IntoIterator<Item = P>,
 ) -> Result<ClientConfig, std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let roots = platform_root_store(root_certificates)?;
+
+    Ok(ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Build a root store with the platform root certificates and the given ones
+fn platform_root_store<P>(
+    root_certificates: impl IntoIterator<Item = P>,
+) -> Result<RootCertStore, std::io::Error>
 where
     P: AsRef<Path>,
 {
@@ -95,9 +142,7 @@ where
         ))?
     }
 
-    Ok(ClientConfig::builder()
-        .with_root_certificates(roots)
-        .with_no_client_auth())
+    Ok(roots)
 }
 
 pub fn create_tls_config_without_client_cert(
@@ -4,7 +4,8 @@
 //!   - `"$(tedge config get device.cert_path)"` is the path to the certificate currently used to connect the cloud endpoint
 //!   - `"$(tedge config get device.cert_path).new"` is the path to a new certificate, if any, still to be validated.
 //!
-//! The command `tedge cert renew` stores the new certificate into `"$(tedge config get device.cert_path).new"`
+//! The command `tedge cert renew`, as tedge-agent when `certificate.renewal.enable` is set,
+//! stores the new certificate into `"$(tedge config get device.cert_path).new"`
 //!
 //! The promotion of a new certificate as the current certificate is done by the `tedge connect` command.
 //! If there is a new certificate, `tedge connect` uses this new certificate to connect the cloud
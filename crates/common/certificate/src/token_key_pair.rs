@@ -0,0 +1,185 @@
+//! A key pair stored in a PKCS#11 token, used by rcgen to sign certificates and CSRs.
+use crate::parse_root_certificate::CryptokiConfig;
+use crate::CertificateError;
+use anyhow::anyhow;
+use rcgen::RemoteKeyPair;
+use rcgen::SignatureAlgorithm;
+use rustls::SignatureScheme;
+use tedge_p11_server::Pkcs11Token;
+use x509_parser::prelude::FromDer;
+use x509_parser::x509::SubjectPublicKeyInfo;
+
+pub struct TokenKeyPair {
+    token: Pkcs11Token,
+    public_key: Vec<u8>,
+    scheme: SignatureScheme,
+    algorithm: &'static SignatureAlgorithm,
+}
+
+impl TokenKeyPair {
+    pub fn new(config: CryptokiConfig) -> Result<Self, CertificateError> {
+        let token = Pkcs11Token::new(config)?;
+        let response = token.get_public_key()?;
+        let (scheme, algorithm) = signature_algorithm(response.scheme.0)?;
+
+        // rcgen expects the raw public key, i.e. the content of the SubjectPublicKeyInfo bit string
+        let (_, public_key_info) = SubjectPublicKeyInfo::from_der(&response.public_key)
+            .map_err(|err| CertificateError::X509Error(err.to_string()))?;
+        let public_key = public_key_info.subject_public_key.data.to_vec();
+
+        Ok(TokenKeyPair {
+            token,
+            public_key,
+            scheme,
+            algorithm,
+        })
+    }
+}
+
+impl RemoteKeyPair for TokenKeyPair {
+    fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, rcgen::Error> {
+        self.token.sign(msg, self.scheme).map_err(|err| {
+            tracing::error!("Failed to sign using the PKCS#11 token: {err:#}");
+            rcgen::Error::RemoteKeyError
+        })
+    }
+
+    fn algorithm(&self) -> &'static SignatureAlgorithm {
+        self.algorithm
+    }
+}
+
+/// Returns the signature scheme and algorithm to be used by rcgen for a key with the given default scheme
+///
+/// RSA keys are used with PKCS#1 v1.5 signatures, rcgen not supporting RSA-PSS.
+fn signature_algorithm(
+    key_scheme: SignatureScheme,
+) -> Result<(SignatureScheme, &'static SignatureAlgorithm), CertificateError> {
+    match key_scheme {
+        SignatureScheme::ECDSA_NISTP256_SHA256 => Ok((key_scheme, &rcgen::PKCS_ECDSA_P256_SHA256)),
+        SignatureScheme::ECDSA_NISTP384_SHA384 => Ok((key_scheme, &rcgen::PKCS_ECDSA_P384_SHA384)),
+        SignatureScheme::RSA_PSS_SHA256 | SignatureScheme::RSA_PKCS1_SHA256 => {
+            Ok((SignatureScheme::RSA_PKCS1_SHA256, &rcgen::PKCS_RSA_SHA256))
+        }
+        _ => Err(
+            anyhow!("Signature scheme {key_scheme:?} is not supported to sign certificates").into(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ec_keys_are_used_with_their_default_scheme() {
+        let (scheme, algorithm) =
+            signature_algorithm(SignatureScheme::ECDSA_NISTP384_SHA384).unwrap();
+        assert_eq!(scheme, SignatureScheme::ECDSA_NISTP384_SHA384);
+        assert_eq!(algorithm, &rcgen::PKCS_ECDSA_P384_SHA384);
+    }
+
+    #[test]
+    fn rsa_keys_are_used_with_pkcs1_signatures() {
+        let (scheme, algorithm) = signature_algorithm(SignatureScheme::RSA_PSS_SHA256).unwrap();
+        assert_eq!(scheme, SignatureScheme::RSA_PKCS1_SHA256);
+        assert_eq!(algorithm, &rcgen::PKCS_RSA_SHA256);
+    }
+
+    #[test]
+    fn unsupported_curves_are_rejected() {
+        assert!(signature_algorithm(SignatureScheme::ECDSA_NISTP521_SHA512).is_err());
+    }
+
+    /// Create a key in a SoftHSM2 token and sign a CSR with it
+    ///
+    /// The test is skipped when SoftHSM2 is not installed.
+    /// The module path can be given by the `SOFTHSM2_MODULE` environment variable.
+    #[test]
+    fn csr_signed_by_a_key_created_in_softhsm() {
+        use crate::parse_root_certificate::CryptokiConfig;
+        use crate::CsrTemplate;
+        use crate::KeyCertPair;
+        use crate::KeyKind;
+        use tedge_p11_server::service::CreateKeyRequest;
+        use tedge_p11_server::service::KeyTypeRequest;
+        use tedge_p11_server::AuthPin;
+        use tedge_p11_server::CryptokiConfigDirect;
+        use x509_parser::certification_request::X509CertificationRequest;
+
+        let module_path = std::env::var("SOFTHSM2_MODULE")
+            .unwrap_or_else(|_| "/usr/lib/softhsm/libsofthsm2.so".to_string());
+        if !std::path::Path::new(&module_path).exists() {
+            eprintln!("Skipping test: SoftHSM2 module not found at {module_path}");
+            return;
+        }
+
+        // A token created from scratch in a temporary directory
+        let dir = tempfile::tempdir().unwrap();
+        let token_dir = dir.path().join("tokens");
+        std::fs::create_dir(&token_dir).unwrap();
+        let conf_path = dir.path().join("softhsm2.conf");
+        std::fs::write(
+            &conf_path,
+            format!(
+                "directories.tokendir = {}\nobjectstore.backend = file\n",
+                token_dir.display()
+            ),
+        )
+        .unwrap();
+        std::env::set_var("SOFTHSM2_CONF", &conf_path);
+        let status = std::process::Command::new("softhsm2-util")
+            .args(["--init-token", "--free", "--label", "tedge-test"])
+            .args(["--pin", "123456", "--so-pin", "123456"])
+            .status()
+            .expect("softhsm2-util is installed along the SoftHSM2 module");
+        assert!(status.success());
+
+        let cryptoki_config = |uri: &str| {
+            CryptokiConfig::Direct(CryptokiConfigDirect {
+                module_path: module_path.clone().into(),
+                pin: AuthPin::new("123456".to_string()),
+                uri: Some(uri.into()),
+            })
+        };
+
+        let token = Pkcs11Token::new(cryptoki_config("pkcs11:token=tedge-test")).unwrap();
+        let key = token
+            .create_key(CreateKeyRequest {
+                uri: None,
+                key: KeyTypeRequest::Ec { curve: 256 },
+                label: "csr-key".to_string(),
+                id: Some(vec![0x01]),
+            })
+            .unwrap();
+
+        let csr = KeyCertPair::new_certificate_sign_request(
+            &CsrTemplate::default(),
+            "my-device",
+            &KeyKind::Cryptoki(cryptoki_config(&key.uri)),
+        )
+        .unwrap()
+        .certificate_signing_request_string()
+        .unwrap();
+
+        // The CSR is for the key created in the token and is signed by this key
+        let der = pem::parse(csr).unwrap().into_contents();
+        let (_, csr) = X509CertificationRequest::from_der(&der).unwrap();
+        let csr_info = &csr.certification_request_info;
+        let (_, token_public_key) = SubjectPublicKeyInfo::from_der(&key.public_key).unwrap();
+        assert_eq!(
+            csr_info.subject_pki.subject_public_key.data,
+            token_public_key.subject_public_key.data
+        );
+        ring::signature::UnparsedPublicKey::new(
+            &ring::signature::ECDSA_P256_SHA256_ASN1,
+            &token_public_key.subject_public_key.data,
+        )
+        .verify(csr_info.raw, &csr.signature_value.data)
+        .expect("the CSR is signed by the token key");
+    }
+}
@@ -0,0 +1,329 @@
+use crate::error::DownloadError;
+use crate::error::ErrContext;
+use log::debug;
+use log::warn;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// The maximum window size accepted when applying a zstd patch, i.e. 1 GB
+///
+/// This is the largest value supported on 32-bit platforms.
+const ZSTD_WINDOW_LOG_MAX: u32 = 30;
+
+/// Describes a delta patch, from which a file can be reconstructed using a previous version
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct DeltaInfo {
+    /// The URL of the patch
+    pub url: String,
+    /// The SHA-256 checksum of the previous version the patch has to be applied to
+    pub base_sha256: String,
+    /// The format of the patch
+    #[serde(default)]
+    pub format: DeltaFormat,
+}
+
+impl DeltaInfo {
+    pub fn new(url: &str, base_sha256: &str, format: DeltaFormat) -> Self {
+        Self {
+            url: url.into(),
+            base_sha256: base_sha256.into(),
+            format,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeltaFormat {
+    /// A `BSDIFF40` patch, as produced by `bsdiff old new patch`
+    #[default]
+    Bsdiff,
+    /// A zstd frame using the previous version as reference, as produced by `zstd --patch-from=old new`
+    Zstd,
+}
+
+/// The files previously downloaded, stored by SHA-256 checksum to be used as base for delta patches
+///
+/// The most recently used files are kept, up to a total of `max_size` bytes.
+#[derive(Debug)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl ArtifactCache {
+    pub fn new(dir: impl Into<PathBuf>, max_size: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            max_size,
+        }
+    }
+
+    /// Return the path of the cached file with the given checksum, if any
+    pub async fn get(&self, sha256: &str) -> Option<PathBuf> {
+        let path = self.dir.join(cache_key(sha256)?);
+        if !path.is_file() {
+            return None;
+        }
+
+        // Mark the entry as recently used
+        let touch = File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        if let Err(err) = touch {
+            debug!("Failed to update the modification time of {path:?}: {err}");
+        }
+        Some(path)
+    }
+
+    /// Add a downloaded file to the cache, evicting the least recently used entries if full
+    ///
+    /// The file is copied into the cache, so the entry is not altered
+    /// if the downloaded file is later modified in place by its user.
+    /// The given checksum is assumed to have been checked.
+    /// A file larger than the cache itself is not stored.
+    pub async fn store(&self, file: &Path, sha256: &str) -> Result<(), DownloadError> {
+        let Some(key) = cache_key(sha256) else {
+            return Ok(());
+        };
+        let size = tokio::fs::metadata(file)
+            .await
+            .context(format!("Failed to read {file:?}"))?
+            .len();
+        if size > self.max_size {
+            debug!("Not caching {file:?}, which is larger than the artifact cache");
+            return Ok(());
+        }
+
+        tokio::fs::create_dir_all(&self.dir).await.context(format!(
+            "Failed to create the artifact cache {:?}",
+            self.dir
+        ))?;
+        let path = self.dir.join(&key);
+        let tmp_path = self.dir.join(format!("{key}.tmp"));
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        tokio::fs::copy(file, &tmp_path)
+            .await
+            .context(format!("Failed to copy {file:?} to the artifact cache"))?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .context(format!("Failed to add {path:?} to the artifact cache"))?;
+        if let Ok(file) = File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        self.evict().await
+    }
+
+    async fn evict(&self) -> Result<(), DownloadError> {
+        let context = || format!("Failed to read the artifact cache {:?}", self.dir);
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.dir).await.context(context())?;
+        while let Some(entry) = dir.next_entry().await.context(context())? {
+            let is_cache_entry = entry.file_name().to_str().and_then(cache_key).is_some();
+            if !is_cache_entry {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((modified, metadata.len(), entry.path()));
+        }
+
+        entries.sort_by(|a, b| b.0.cmp(&a.0));
+        let mut total_size = 0u64;
+        for (_, size, path) in entries {
+            total_size = total_size.saturating_add(size);
+            if total_size <= self.max_size {
+                continue;
+            }
+            debug!("Evicting {path:?} from the artifact cache");
+            if let Err(err) = tokio::fs::remove_file(&path).await {
+                warn!("Failed to evict {path:?} from the artifact cache: {err}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name of a cache entry, provided the checksum is a valid SHA-256 hexadecimal string
+fn cache_key(sha256: &str) -> Option<String> {
+    let sha256 = sha256.trim();
+    (sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| sha256.to_ascii_lowercase())
+}
+
+/// Reconstruct a file from its previous version and a delta patch
+///
+/// The previous version is memory-mapped rather than read,
+/// so large files are paged in on demand by the patch algorithms which need random access to it.
+pub(crate) fn apply_patch(
+    format: DeltaFormat,
+    base_path: &Path,
+    patch: &Path,
+    target: &mut File,
+) -> Result<(), DownloadError> {
+    let base_file = File::open(base_path).context(format!("Failed to read {base_path:?}"))?;
+    // SAFETY: the base file is an entry of the artifact cache, which is never modified in place:
+    // entries are copies of the downloaded files, only ever replaced by renaming or removed.
+    let base = unsafe { memmap2::Mmap::map(&base_file) }
+        .context(format!("Failed to read {base_path:?}"))?;
+    match format {
+        DeltaFormat::Bsdiff => {
+            let patch = std::fs::read(patch).context(format!("Failed to read {patch:?}"))?;
+            qbsdiff::Bspatch::new(&patch)
+                .and_then(|patcher| patcher.apply(&base[..], target))
+                .context("Failed to apply bsdiff patch".to_string())?;
+        }
+        DeltaFormat::Zstd => {
+            let patch = File::open(patch).context(format!("Failed to read {patch:?}"))?;
+            let mut decoder =
+                zstd::stream::read::Decoder::with_ref_prefix(BufReader::new(patch), &base[..])
+                    .context("Failed to decode zstd patch".to_string())?;
+            decoder
+                .window_log_max(ZSTD_WINDOW_LOG_MAX)
+                .context("Failed to decode zstd patch".to_string())?;
+            std::io::copy(&mut decoder, target)
+                .context("Failed to apply zstd patch".to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SHA_A: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    const SHA_B: &str = "486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7";
+    const SHA_C: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[tokio::test]
+    async fn cache_keeps_the_most_recently_used_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("artifact");
+        std::fs::write(&file, "content").unwrap();
+        let cache = ArtifactCache::new(temp_dir.path().join("cache"), 14);
+
+        cache.store(&file, SHA_A).await.unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cache.store(&file, SHA_B).await.unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(cache.get(SHA_A).await.is_some());
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cache.store(&file, SHA_C).await.unwrap();
+
+        assert!(cache.get(SHA_A).await.is_some());
+        assert!(cache.get(SHA_B).await.is_none());
+        assert!(cache.get(SHA_C).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn cache_is_bounded_by_the_total_size_of_its_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let small = temp_dir.path().join("small");
+        let large = temp_dir.path().join("large");
+        let too_large = temp_dir.path().join("too-large");
+        std::fs::write(&small, "small").unwrap();
+        std::fs::write(&large, "a larger file").unwrap();
+        std::fs::write(&too_large, "a file larger than the cache").unwrap();
+        let cache = ArtifactCache::new(temp_dir.path().join("cache"), 20);
+
+        cache.store(&small, SHA_A).await.unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cache.store(&small, SHA_B).await.unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cache.store(&large, SHA_C).await.unwrap();
+
+        // 5 + 13 bytes fit the cache, but not 5 + 5 + 13
+        assert!(cache.get(SHA_A).await.is_none());
+        assert!(cache.get(SHA_B).await.is_some());
+        assert!(cache.get(SHA_C).await.is_some());
+
+        // A file larger than the cache is not stored, nor evicts the current entries
+        cache.store(&too_large, SHA_A).await.unwrap();
+        assert!(cache.get(SHA_A).await.is_none());
+        assert!(cache.get(SHA_B).await.is_some());
+        assert!(cache.get(SHA_C).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn cache_ignores_invalid_checksums() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("artifact");
+        std::fs::write(&file, "content").unwrap();
+        let cache = ArtifactCache::new(temp_dir.path().join("cache"), 14);
+
+        cache.store(&file, "../artifact").await.unwrap();
+        assert!(cache.get("../artifact").await.is_none());
+        assert!(!temp_dir.path().join("cache").exists());
+
+        // Checksums are not case-sensitive
+        cache.store(&file, &SHA_A.to_uppercase()).await.unwrap();
+        assert!(cache.get(SHA_A).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn cache_entries_are_not_altered_by_changes_to_the_stored_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("artifact");
+        std::fs::write(&file, "content").unwrap();
+        let cache = ArtifactCache::new(temp_dir.path().join("cache"), 14);
+
+        cache.store(&file, SHA_A).await.unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&file)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, b" modified in place"))
+            .unwrap();
+
+        let entry = cache.get(SHA_A).await.unwrap();
+        assert_eq!(std::fs::read_to_string(entry).unwrap(), "content");
+    }
+
+    #[test]
+    fn apply_bsdiff_patch() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("base");
+        let patch = temp_dir.path().join("patch");
+        let target = temp_dir.path().join("target");
+
+        let old = b"firmware version 1.0.0 with some payload".repeat(100);
+        let new = b"firmware version 1.0.1 with some payload".repeat(100);
+        let mut delta = Vec::new();
+        qbsdiff::Bsdiff::new(&old, &new)
+            .compare(&mut delta)
+            .unwrap();
+        std::fs::write(&base, &old).unwrap();
+        std::fs::write(&patch, &delta).unwrap();
+
+        let mut file = File::create(&target).unwrap();
+        apply_patch(DeltaFormat::Bsdiff, &base, &patch, &mut file).unwrap();
+
+        assert_eq!(std::fs::read(&target).unwrap(), new);
+    }
+
+    #[test]
+    fn invalid_patch_is_an_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("base");
+        let patch = temp_dir.path().join("patch");
+        std::fs::write(&base, "old content").unwrap();
+        std::fs::write(&patch, "not a patch").unwrap();
+
+        for format in [DeltaFormat::Bsdiff, DeltaFormat::Zstd] {
+            let mut file = File::create(temp_dir.path().join("target")).unwrap();
+            assert!(apply_patch(format, &base, &patch, &mut file).is_err());
+        }
+    }
+}
@@ -1,6 +1,10 @@
 mod partial_response;
+use crate::delta::apply_patch;
+use crate::delta::ArtifactCache;
+use crate::delta::DeltaInfo;
 use crate::error::DownloadError;
 use crate::error::ErrContext;
+use crate::verification::TrustStore;
 use anyhow::anyhow;
 use backoff::future::retry_notify;
 use backoff::ExponentialBackoff;
@@ -23,6 +27,7 @@ use std::io::SeekFrom;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 use tedge_utils::file::FileError;
 
@@ -52,6 +57,15 @@ pub struct DownloadInfo {
     pub url: String,
     #[serde(skip)]
     pub headers: HeaderMap,
+    /// The expected SHA-256 checksum of the file, as an hexadecimal string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// A base64-encoded detached signature of the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// A delta patch from which the file can be reconstructed, instead of being fully downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<DeltaInfo>,
 }
 
 impl From<&str> for DownloadInfo {
@@ -66,6 +80,9 @@ impl DownloadInfo {
         Self {
             url: url.into(),
             headers: HeaderMap::new(),
+            sha256: None,
+            signature: None,
+            delta: None,
         }
     }
 
@@ -77,6 +94,20 @@ impl DownloadInfo {
         }
     }
 
+    /// Set the expected checksum and signature of the file
+    pub fn with_verification(self, sha256: Option<String>, signature: Option<String>) -> Self {
+        Self {
+            sha256,
+            signature,
+            ..self
+        }
+    }
+
+    /// Set the delta patch from which the file can be reconstructed
+    pub fn with_delta(self, delta: Option<DeltaInfo>) -> Self {
+        Self { delta, ..self }
+    }
+
     pub fn url(&self) -> &str {
         self.url.as_str()
     }
@@ -92,6 +123,8 @@ pub struct Downloader {
     target_filename: PathBuf,
     backoff: ExponentialBackoff,
     client: Client,
+    trust_store: Arc<TrustStore>,
+    artifact_cache: Option<Arc<ArtifactCache>>,
 }
 
 impl Downloader {
@@ -111,6 +144,24 @@ impl Downloader {
             target_filename: target_path,
             backoff: default_backoff(),
             client,
+            trust_store: Arc::new(TrustStore::default()),
+            artifact_cache: None,
+        }
+    }
+
+    /// Use the given trust store to verify the signature of the downloaded files
+    pub fn with_trust_store(self, trust_store: Arc<TrustStore>) -> Self {
+        Self {
+            trust_store,
+            ..self
+        }
+    }
+
+    /// Cache the downloaded files, so they can be used as base for delta patches
+    pub fn with_artifact_cache(self, artifact_cache: Option<Arc<ArtifactCache>>) -> Self {
+        Self {
+            artifact_cache,
+            ..self
         }
     }
 
@@ -132,7 +183,122 @@ impl Downloader {
     ///
     /// Requests partial ranges if a transient error happened while downloading
     /// and the server response included `Accept-Ranges` header.
+    ///
+    /// If a checksum or a signature is provided, the downloaded file is verified
+    /// before being moved to its final destination, and is deleted if the verification fails.
+    ///
+    /// If a delta patch is provided along a checksum, and the base version of the patch
+    /// is in the artifact cache, the file is reconstructed from the patch and the base version.
+    /// The full file is downloaded if this is not possible or if the reconstructed file
+    /// doesn't match the checksum.
     pub async fn download(&self, url: &DownloadInfo) -> Result<(), DownloadError> {
+        let mut reconstructed = false;
+        if let Some(delta) = &url.delta {
+            match self.download_delta(url, delta).await {
+                Ok(done) => reconstructed = done,
+                Err(err) => warn!(
+                    "Failed to reconstruct {} from delta patch {}: {err}. Downloading the full file",
+                    url.url, delta.url
+                ),
+            }
+        }
+        if !reconstructed {
+            self.download_full(url).await?;
+        }
+
+        if let (Some(cache), Some(sha256)) = (&self.artifact_cache, &url.sha256) {
+            if let Err(err) = cache.store(&self.target_filename, sha256).await {
+                warn!("Failed to cache {:?}: {err}", self.target_filename);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reconstruct the file from a delta patch and its base version
+    ///
+    /// Return `false` if this is not possible because the base version is not cached
+    /// or there is no checksum to check the reconstructed file.
+    async fn download_delta(
+        &self,
+        url: &DownloadInfo,
+        delta: &DeltaInfo,
+    ) -> Result<bool, DownloadError> {
+        let Some(cache) = &self.artifact_cache else {
+            return Ok(false);
+        };
+        let Some(base) = cache.get(&delta.base_sha256).await else {
+            info!(
+                "No cached base version with sha256 {} for delta patch {}",
+                delta.base_sha256, delta.url
+            );
+            return Ok(false);
+        };
+        if url.sha256.is_none() {
+            warn!(
+                "Ignoring delta patch {}: no checksum is provided for {}",
+                delta.url, url.url
+            );
+            return Ok(false);
+        }
+
+        // The patch itself is not verified, only the reconstructed file
+        let patch_path = self.temp_filename().await?.with_extension("patch");
+        let patch_downloader = Downloader {
+            target_filename: patch_path.clone(),
+            backoff: self.backoff.clone(),
+            client: self.client.clone(),
+            trust_store: Arc::new(TrustStore::default()),
+            artifact_cache: None,
+        };
+        let patch_url = DownloadInfo::new(&delta.url).with_headers(url.headers.clone());
+        let result = match patch_downloader.download_full(&patch_url).await {
+            Ok(()) => self.apply_delta(url, delta, &base, &patch_path).await,
+            Err(err) => Err(err),
+        };
+        let _ = tokio::fs::remove_file(&patch_path).await;
+        result.map(|()| true)
+    }
+
+    async fn apply_delta(
+        &self,
+        url: &DownloadInfo,
+        delta: &DeltaInfo,
+        base: &Path,
+        patch: &Path,
+    ) -> Result<(), DownloadError> {
+        let temp_dir = self
+            .target_filename
+            .parent()
+            .unwrap_or(&self.target_filename);
+        let mut file = tempfile::NamedTempFile::new_in(temp_dir)
+            .context("Could not write to temporary file".to_string())?;
+
+        info!(
+            "Reconstructing file from delta patch {} and {base:?}",
+            delta.url
+        );
+        let format = delta.format;
+        let base = base.to_owned();
+        let patch = patch.to_owned();
+        let file = tokio::task::spawn_blocking(move || {
+            apply_patch(format, &base, &patch, file.as_file_mut()).map(|()| file)
+        })
+        .await
+        .map_err(std::io::Error::other)
+        .context("Failed to apply delta patch".to_string())??;
+
+        self.trust_store
+            .verify(file.path(), url.sha256.as_deref(), url.signature.as_deref())?;
+
+        file.persist(&self.target_filename)
+            .map_err(|p| p.error)
+            .context("Could not persist temporary file".to_string())?;
+
+        Ok(())
+    }
+
+    async fn download_full(&self, url: &DownloadInfo) -> Result<(), DownloadError> {
         let tmp_target_path = self.temp_filename().await?;
         let target_file_path = self.target_filename.as_path();
 
@@ -180,6 +346,9 @@ impl Downloader {
             }
         }
 
+        self.trust_store
+            .verify(file.path(), url.sha256.as_deref(), url.signature.as_deref())?;
+
         // Move the downloaded file to the final destination
         debug!(
             "Moving downloaded file from {:?} to {:?}",
@@ -463,6 +632,7 @@ fn try_pre_allocate_space(file: &File, path: &Path, file_len: u64) -> Result<(),
 #[allow(deprecated)]
 mod tests {
     use super::*;
+    use crate::delta::DeltaFormat;
     use axum::Router;
     use hyper::header::AUTHORIZATION;
     use rustls::pki_types::pem::PemObject;
@@ -537,6 +707,148 @@ mod tests {
         assert_eq!(file_content, "hello".as_bytes());
     }
 
+    #[tokio::test]
+    async fn downloader_rejects_file_with_unexpected_checksum() {
+        let temp_dir = tempdir().unwrap();
+
+        let mut server = mockito::Server::new_async().await;
+        let _mock1 = server
+            .mock("GET", "/some_file.txt")
+            .with_status(200)
+            .with_body(b"tampered")
+            .create_async()
+            .await;
+
+        let target_path = temp_dir.path().join("downloaded_file.txt");
+        let target_url = format!("{}/some_file.txt", server.url());
+
+        // sha256 of "hello"
+        let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let url = DownloadInfo::new(&target_url).with_verification(Some(sha256.into()), None);
+
+        let downloader = Downloader::new(target_path.clone(), None, CloudHttpConfig::test_value());
+        let err = downloader.download(&url).await.unwrap_err();
+
+        assert!(matches!(err, DownloadError::ChecksumMismatch { .. }));
+        assert!(!target_path.exists());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn downloader_reconstructs_file_from_delta_patch() {
+        let temp_dir = tempdir().unwrap();
+        let old = b"image version 1.0.0".repeat(100);
+        let new = b"image version 1.0.1".repeat(100);
+        let mut patch = Vec::new();
+        qbsdiff::Bsdiff::new(&old, &new)
+            .compare(&mut patch)
+            .unwrap();
+
+        let mut server = mockito::Server::new_async().await;
+        let full_download = server
+            .mock("GET", "/image-1.0.1")
+            .with_status(200)
+            .with_body(&new)
+            .expect(0)
+            .create_async()
+            .await;
+        let _patch_download = server
+            .mock("GET", "/image-1.0.0-1.0.1.patch")
+            .with_status(200)
+            .with_body(&patch)
+            .create_async()
+            .await;
+
+        let cache = Arc::new(ArtifactCache::new(
+            temp_dir.path().join("cache"),
+            1024 * 1024,
+        ));
+        let (old_sha256, new_sha256) = (
+            sha256_of(temp_dir.path(), &old),
+            sha256_of(temp_dir.path(), &new),
+        );
+        let old_path = temp_dir.path().join("image-1.0.0");
+        std::fs::write(&old_path, &old).unwrap();
+        cache.store(&old_path, &old_sha256).await.unwrap();
+
+        let target_path = temp_dir.path().join("image");
+        let url = DownloadInfo::new(&format!("{}/image-1.0.1", server.url()))
+            .with_verification(Some(new_sha256.clone()), None)
+            .with_delta(Some(DeltaInfo::new(
+                &format!("{}/image-1.0.0-1.0.1.patch", server.url()),
+                &old_sha256,
+                DeltaFormat::Bsdiff,
+            )));
+
+        let downloader = Downloader::new(target_path.clone(), None, CloudHttpConfig::test_value())
+            .with_artifact_cache(Some(cache.clone()));
+        downloader.download(&url).await.unwrap();
+
+        assert_eq!(std::fs::read(&target_path).unwrap(), new);
+        full_download.assert_async().await;
+        // The new version can be used as base for the next patch
+        assert!(cache.get(&new_sha256).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn downloader_falls_back_to_full_download_if_delta_patch_fails() {
+        let temp_dir = tempdir().unwrap();
+        let old = b"image version 1.0.0".repeat(100);
+        let new = b"image version 1.0.1".repeat(100);
+
+        let mut server = mockito::Server::new_async().await;
+        let full_download = server
+            .mock("GET", "/image-1.0.1")
+            .with_status(200)
+            .with_body(&new)
+            .expect(1)
+            .create_async()
+            .await;
+        let _patch_download = server
+            .mock("GET", "/image-1.0.0-1.0.1.patch")
+            .with_status(200)
+            .with_body(b"corrupted patch")
+            .create_async()
+            .await;
+
+        let cache = Arc::new(ArtifactCache::new(
+            temp_dir.path().join("cache"),
+            1024 * 1024,
+        ));
+        let (old_sha256, new_sha256) = (
+            sha256_of(temp_dir.path(), &old),
+            sha256_of(temp_dir.path(), &new),
+        );
+        let old_path = temp_dir.path().join("image-1.0.0");
+        std::fs::write(&old_path, &old).unwrap();
+        cache.store(&old_path, &old_sha256).await.unwrap();
+
+        let target_path = temp_dir.path().join("image");
+        let url = DownloadInfo::new(&format!("{}/image-1.0.1", server.url()))
+            .with_verification(Some(new_sha256), None)
+            .with_delta(Some(DeltaInfo::new(
+                &format!("{}/image-1.0.0-1.0.1.patch", server.url()),
+                &old_sha256,
+                DeltaFormat::Bsdiff,
+            )));
+
+        let downloader = Downloader::new(target_path.clone(), None, CloudHttpConfig::test_value())
+            .with_artifact_cache(Some(cache));
+        downloader.download(&url).await.unwrap();
+
+        assert_eq!(std::fs::read(&target_path).unwrap(), new);
+        full_download.assert_async().await;
+        assert!(!target_path.with_extension("patch").exists());
+    }
+
+    fn sha256_of(dir: &Path, content: &[u8]) -> String {
+        let path = dir.join("checksum");
+        std::fs::write(&path, content).unwrap();
+        let sha256 = crate::verification::sha256_digest(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        sha256
+    }
+
     #[cfg(target_os = "linux")]
     #[tokio::test]
     #[ignore = "Overriding Content-Length doesn't work in mockito"]
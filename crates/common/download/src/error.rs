@@ -23,6 +23,18 @@ pub enum DownloadError {
 
     #[error("Invalid server response")]
     InvalidResponse(#[from] InvalidResponseError),
+
+    #[error("Checksum mismatch: expected sha256 {expected}, but the downloaded file has sha256 {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("The downloaded file is not signed by a trusted key")]
+    UntrustedSignature,
+
+    #[error("The downloaded file is not signed, while a signature is required")]
+    MissingSignature,
 }
 
 /// A trait for attaching context string to io-like errors.
@@ -7,6 +7,10 @@
 //! - implementing reasonable exponential backoff strategy
 //! - performing partial downloads if a portion of a file has already been
 //!   downloaded
+//! - verifying the SHA-256 checksum and the signature of the downloaded files,
+//!   when provided along the [`DownloadInfo`]
+//! - reconstructing the files from delta patches and previously downloaded versions
+//!   kept in an [`ArtifactCache`]
 //!
 //! # Usage
 //!
@@ -44,9 +48,15 @@
 //! }
 //! ```
 
+mod delta;
 mod download;
 mod error;
+mod verification;
 
+pub use crate::delta::ArtifactCache;
+pub use crate::delta::DeltaFormat;
+pub use crate::delta::DeltaInfo;
 pub use crate::download::DownloadInfo;
 pub use crate::download::Downloader;
 pub use crate::error::DownloadError;
+pub use crate::verification::TrustStore;
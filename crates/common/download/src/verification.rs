@@ -0,0 +1,326 @@
+use crate::error::DownloadError;
+use crate::error::ErrContext;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use log::info;
+use log::warn;
+use ring::digest;
+use ring::signature;
+use ring::signature::UnparsedPublicKey;
+use ring::signature::VerificationAlgorithm;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use x509_parser::prelude::FromDer;
+use x509_parser::x509::SubjectPublicKeyInfo;
+
+const OID_ED25519: &str = "1.3.101.112";
+const OID_EC_PUBLIC_KEY: &str = "1.2.840.10045.2.1";
+const OID_RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.1";
+
+/// The public keys trusted to sign the downloaded artifacts
+///
+/// The trust store is a directory of PEM files, each containing one or more `PUBLIC KEY` blocks.
+/// Ed25519, ECDSA (P-256 with SHA-256, P-384 with SHA-384) and RSA (PKCS#1 v1.5 with SHA-256) keys are supported.
+#[derive(Debug, Default)]
+pub struct TrustStore {
+    keys: Vec<TrustedKey>,
+    require_signature: bool,
+}
+
+#[derive(Debug)]
+struct TrustedKey {
+    path: PathBuf,
+    algorithm: &'static dyn VerificationAlgorithm,
+    public_key: Vec<u8>,
+}
+
+impl TrustStore {
+    /// Load all the public keys stored in the given directory
+    ///
+    /// A missing directory is not an error, but leads to an empty trust store.
+    /// The files that are not valid PEM public keys are ignored with a warning.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, DownloadError> {
+        let dir = dir.as_ref();
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(TrustStore::default())
+            }
+            Err(err) => return Err(err).context(format!("Failed to read the trust store {dir:?}")),
+        };
+
+        let mut keys = Vec::new();
+        for entry in entries {
+            let path = entry
+                .context(format!("Failed to read the trust store {dir:?}"))?
+                .path();
+            if !path.is_file() {
+                continue;
+            }
+            match Self::read_keys(&path) {
+                Ok(file_keys) if file_keys.is_empty() => {
+                    warn!("No supported public key found in {path:?}")
+                }
+                Ok(file_keys) => keys.extend(file_keys),
+                Err(err) => warn!("Ignoring {path:?} from the trust store: {err}"),
+            }
+        }
+
+        Ok(TrustStore {
+            keys,
+            require_signature: false,
+        })
+    }
+
+    /// Reject any download that is not signed by one of the trusted keys
+    pub fn with_required_signature(self, require_signature: bool) -> Self {
+        TrustStore {
+            require_signature,
+            ..self
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn read_keys(path: &Path) -> Result<Vec<TrustedKey>, String> {
+        let content = std::fs::read(path).map_err(|err| err.to_string())?;
+        let blocks = pem::parse_many(content).map_err(|err| err.to_string())?;
+
+        let mut keys = Vec::new();
+        for block in blocks.iter().filter(|block| block.tag() == "PUBLIC KEY") {
+            let (_, spki) =
+                SubjectPublicKeyInfo::from_der(block.contents()).map_err(|err| err.to_string())?;
+            let public_key = spki.subject_public_key.data.to_vec();
+            let algorithm: &'static dyn VerificationAlgorithm = match spki
+                .algorithm
+                .algorithm
+                .to_id_string()
+                .as_str()
+            {
+                OID_ED25519 => &signature::ED25519,
+                OID_EC_PUBLIC_KEY if public_key.len() == 65 => &signature::ECDSA_P256_SHA256_ASN1,
+                OID_EC_PUBLIC_KEY if public_key.len() == 97 => &signature::ECDSA_P384_SHA384_ASN1,
+                OID_RSA_ENCRYPTION => &signature::RSA_PKCS1_2048_8192_SHA256,
+                oid => {
+                    warn!("Ignoring public key with unsupported algorithm {oid} in {path:?}");
+                    continue;
+                }
+            };
+            keys.push(TrustedKey {
+                path: path.to_owned(),
+                algorithm,
+                public_key,
+            });
+        }
+        Ok(keys)
+    }
+
+    /// Check that a downloaded file has the expected SHA-256 checksum and is signed by a trusted key
+    ///
+    /// The checksum is given as an hexadecimal string and the signature is a base64-encoded
+    /// detached signature of the file content.
+    pub fn verify(
+        &self,
+        file: &Path,
+        sha256: Option<&str>,
+        signature: Option<&str>,
+    ) -> Result<(), DownloadError> {
+        if let Some(expected) = sha256 {
+            let actual = sha256_digest(file)?;
+            if !actual.eq_ignore_ascii_case(expected.trim()) {
+                return Err(DownloadError::ChecksumMismatch {
+                    expected: expected.to_string(),
+                    actual,
+                });
+            }
+        }
+
+        match signature {
+            Some(signature) => self.verify_signature(file, signature),
+            None if self.require_signature => Err(DownloadError::MissingSignature),
+            None => Ok(()),
+        }
+    }
+
+    fn verify_signature(&self, file: &Path, signature: &str) -> Result<(), DownloadError> {
+        let signature = BASE64
+            .decode(signature.trim())
+            .map_err(|err| DownloadError::InvalidSignature(err.to_string()))?;
+        // The signature algorithms require the whole message to be in memory
+        let content =
+            std::fs::read(file).context(format!("Failed to read downloaded file {file:?}"))?;
+
+        match self.keys.iter().find(|key| {
+            UnparsedPublicKey::new(key.algorithm, &key.public_key)
+                .verify(&content, &signature)
+                .is_ok()
+        }) {
+            Some(key) => {
+                info!("Download signature verified with {:?}", key.path);
+                Ok(())
+            }
+            None => Err(DownloadError::UntrustedSignature),
+        }
+    }
+}
+
+pub(crate) fn sha256_digest(file: &Path) -> Result<String, DownloadError> {
+    let context = || format!("Failed to read downloaded file {file:?}");
+    let mut file = File::open(file).context(context())?;
+    let mut digest = digest::Context::new(&digest::SHA256);
+    let mut buffer = [0; 8192];
+    loop {
+        let len = file.read(&mut buffer).context(context())?;
+        if len == 0 {
+            break;
+        }
+        digest.update(&buffer[..len]);
+    }
+    Ok(digest
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::KeyPair;
+    use rcgen::PKCS_ECDSA_P256_SHA256;
+    use ring::rand::SystemRandom;
+    use ring::signature::EcdsaKeyPair;
+    use tempfile::TempDir;
+
+    const CONTENT: &[u8] = b"some artifact content";
+    const CONTENT_SHA256: &str = "9eec0424198db2c22c28afbceac63f3bf08a94dbd31ce548ec4c3a46bc2ca573";
+
+    struct Signer {
+        key_pair: EcdsaKeyPair,
+        public_key_pem: String,
+    }
+
+    impl Signer {
+        fn new() -> Self {
+            let rng = SystemRandom::new();
+            let key_pair = KeyPair::generate(&PKCS_ECDSA_P256_SHA256).unwrap();
+            let public_key_pem = key_pair.public_key_pem();
+            let key_pair = EcdsaKeyPair::from_pkcs8(
+                &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+                &key_pair.serialize_der(),
+                &rng,
+            )
+            .unwrap();
+            Signer {
+                key_pair,
+                public_key_pem,
+            }
+        }
+
+        fn sign(&self, content: &[u8]) -> String {
+            let signature = self.key_pair.sign(&SystemRandom::new(), content).unwrap();
+            BASE64.encode(signature.as_ref())
+        }
+    }
+
+    fn setup(trusted: &[&Signer]) -> (TempDir, PathBuf, TrustStore) {
+        let dir = TempDir::new().unwrap();
+        let trust_store_dir = dir.path().join("trust-store");
+        std::fs::create_dir(&trust_store_dir).unwrap();
+        for (i, signer) in trusted.iter().enumerate() {
+            std::fs::write(
+                trust_store_dir.join(format!("key-{i}.pem")),
+                &signer.public_key_pem,
+            )
+            .unwrap();
+        }
+        std::fs::write(trust_store_dir.join("README"), "not a key").unwrap();
+
+        let file = dir.path().join("artifact");
+        std::fs::write(&file, CONTENT).unwrap();
+
+        let trust_store = TrustStore::load(&trust_store_dir).unwrap();
+        (dir, file, trust_store)
+    }
+
+    #[test]
+    fn accept_file_with_expected_checksum() {
+        let (_dir, file, trust_store) = setup(&[]);
+        trust_store
+            .verify(&file, Some(CONTENT_SHA256), None)
+            .unwrap();
+        trust_store
+            .verify(&file, Some(&CONTENT_SHA256.to_uppercase()), None)
+            .unwrap();
+    }
+
+    #[test]
+    fn reject_file_with_unexpected_checksum() {
+        let (_dir, file, trust_store) = setup(&[]);
+        let error = trust_store
+            .verify(&file, Some("0123456789abcdef"), None)
+            .unwrap_err();
+
+        assert!(
+            matches!(error, DownloadError::ChecksumMismatch { ref actual, .. } if actual == CONTENT_SHA256)
+        );
+    }
+
+    #[test]
+    fn accept_file_signed_by_a_trusted_key() {
+        let signer = Signer::new();
+        let other_signer = Signer::new();
+        let (_dir, file, trust_store) = setup(&[&other_signer, &signer]);
+
+        trust_store
+            .verify(&file, None, Some(&signer.sign(CONTENT)))
+            .unwrap();
+    }
+
+    #[test]
+    fn reject_file_signed_by_an_untrusted_key() {
+        let trusted_signer = Signer::new();
+        let untrusted_signer = Signer::new();
+        let (_dir, file, trust_store) = setup(&[&trusted_signer]);
+
+        let error = trust_store
+            .verify(&file, None, Some(&untrusted_signer.sign(CONTENT)))
+            .unwrap_err();
+        assert!(matches!(error, DownloadError::UntrustedSignature));
+    }
+
+    #[test]
+    fn reject_tampered_file() {
+        let signer = Signer::new();
+        let (_dir, file, trust_store) = setup(&[&signer]);
+
+        let error = trust_store
+            .verify(&file, None, Some(&signer.sign(b"original content")))
+            .unwrap_err();
+        assert!(matches!(error, DownloadError::UntrustedSignature));
+    }
+
+    #[test]
+    fn reject_unsigned_file_when_signature_is_required() {
+        let signer = Signer::new();
+        let (_dir, file, trust_store) = setup(&[&signer]);
+        let trust_store = trust_store.with_required_signature(true);
+
+        let error = trust_store
+            .verify(&file, Some(CONTENT_SHA256), None)
+            .unwrap_err();
+        assert!(matches!(error, DownloadError::MissingSignature));
+    }
+
+    #[test]
+    fn missing_trust_store_is_empty() {
+        let dir = TempDir::new().unwrap();
+        let trust_store = TrustStore::load(dir.path().join("does-not-exist")).unwrap();
+        assert!(trust_store.is_empty());
+    }
+}
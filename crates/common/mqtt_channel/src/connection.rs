@@ -4,6 +4,7 @@ use crate::MqttError;
 use crate::MqttMessage;
 use crate::PubChannel;
 use crate::SubChannel;
+use crate::TopicFilter;
 use futures::channel::mpsc;
 use futures::channel::oneshot;
 use futures::SinkExt;
@@ -20,6 +21,7 @@ use std::collections::HashSet;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 use tokio::sync::OwnedSemaphorePermit;
 use tokio::sync::Semaphore;
@@ -38,6 +40,48 @@ pub struct Connection {
 
     /// A channel to notify that all the published messages have been actually published.
     pub pub_done: oneshot::Receiver<()>,
+
+    /// A handle to update the subscriptions of this connection.
+    pub subscriptions: SubscriptionHandle,
+}
+
+/// A handle to subscribe to and unsubscribe from topics, once a connection is established
+///
+/// The topics subscribed this way are subscribed again when the connection is re-established
+/// without the session being persisted by the broker.
+#[derive(Clone)]
+pub struct SubscriptionHandle {
+    mqtt_client: AsyncClient,
+    subscriptions: Arc<Mutex<TopicFilter>>,
+}
+
+impl SubscriptionHandle {
+    /// Subscribe to new topics
+    pub async fn subscribe(&self, topics: TopicFilter) -> Result<(), MqttError> {
+        let filters = topics.filters();
+        if filters.is_empty() {
+            return Ok(());
+        }
+        self.subscriptions.lock().unwrap().add_all(topics);
+        Connection::subscribe_to_topics(&self.mqtt_client, filters).await
+    }
+
+    /// Unsubscribe from topics previously subscribed to
+    pub async fn unsubscribe(&self, topics: TopicFilter) -> Result<(), MqttError> {
+        for pattern in topics.patterns() {
+            self.subscriptions.lock().unwrap().remove(pattern);
+            self.mqtt_client
+                .unsubscribe(pattern)
+                .await
+                .map_err(MqttError::ClientError)?;
+        }
+        Ok(())
+    }
+
+    /// The topics subscribed to since the connection has been established
+    fn filters(&self) -> Vec<rumqttc::SubscribeFilter> {
+        self.subscriptions.lock().unwrap().filters()
+    }
 }
 
 impl Connection {
@@ -94,11 +138,16 @@ impl Connection {
 
         let (mqtt_client, event_loop) =
             Connection::open(config, received_sender.clone(), error_sender.clone()).await?;
+        let subscriptions = SubscriptionHandle {
+            mqtt_client: mqtt_client.clone(),
+            subscriptions: Arc::new(Mutex::new(TopicFilter::empty())),
+        };
         let permits = Arc::new(Semaphore::new(1));
         let permit = permits.clone().acquire_owned().await.unwrap();
         let pub_count = Arc::new(AtomicUsize::new(0));
         tokio::spawn(Connection::receiver_loop(
             mqtt_client.clone(),
+            subscriptions.clone(),
             config.clone(),
             event_loop,
             received_sender,
@@ -121,6 +170,7 @@ impl Connection {
             published: published_sender,
             errors: error_receiver,
             pub_done: pub_done_receiver,
+            subscriptions,
         })
     }
 
@@ -210,6 +260,7 @@ impl Connection {
     #[allow(clippy::too_many_arguments)]
     async fn receiver_loop(
         mqtt_client: AsyncClient,
+        dynamic_subscriptions: SubscriptionHandle,
         config: Config,
         mut event_loop: EventLoop,
         mut message_sender: mpsc::UnboundedSender<MqttMessage>,
@@ -289,7 +340,8 @@ impl Connection {
                             // If session_name is not provided or if the broker session persistence
                             // is not enabled or working, then re-subscribe
 
-                            let subscriptions = config.subscriptions.filters();
+                            let mut subscriptions = config.subscriptions.filters();
+                            subscriptions.extend(dynamic_subscriptions.filters());
                             // Need check here otherwise it will hang waiting for a SubAck, and none will come when there is no subscription.
                             if subscriptions.is_empty() {
                                 break;
@@ -109,7 +109,7 @@ impl<'de> Deserialize<'de> for DebugPayload {
     {
         struct DebugPayloadVisitor;
 
-        impl serde::de::Visitor<'_> for DebugPayloadVisitor {
+        impl<'de> serde::de::Visitor<'de> for DebugPayloadVisitor {
             type Value = DebugPayload;
 
             fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -129,6 +129,18 @@ impl<'de> Deserialize<'de> for DebugPayload {
             {
                 Ok(DebugPayload(value.to_vec()))
             }
+
+            // Non UTF-8 payloads are serialized as an array of bytes by JSON serializers
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    bytes.push(byte);
+                }
+                Ok(DebugPayload(bytes))
+            }
         }
 
         deserializer.deserialize_any(DebugPayloadVisitor)
@@ -311,4 +323,19 @@ mod tests {
             serde_json::from_value(json).expect("Deserialization failed");
         assert_eq!(deserialized, message);
     }
+
+    #[test]
+    fn non_utf8_message_serialize_deserialize() {
+        let message = MqttMessage {
+            topic: Topic::new("test").unwrap(),
+            payload: DebugPayload(vec![0xff, 0x00, 0x7f]),
+            qos: QoS::AtLeastOnce,
+            retain: false,
+        };
+
+        let json = serde_json::to_string(&message).expect("Serialization failed");
+        let deserialized: MqttMessage =
+            serde_json::from_str(&json).expect("Deserialization failed");
+        assert_eq!(deserialized, message);
+    }
 }
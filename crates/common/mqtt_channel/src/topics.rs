@@ -116,6 +116,15 @@ impl TopicFilter {
         }
     }
 
+    /// Remove a pattern from this topic filter.
+    ///
+    /// Return false if the pattern was not part of this filter.
+    pub fn remove(&mut self, pattern: &str) -> bool {
+        let len = self.patterns.len();
+        self.patterns.retain(|p| p != pattern);
+        self.patterns.len() != len
+    }
+
     /// Check if the given topic matches this filter pattern.
     pub fn accept_topic(&self, topic: &Topic) -> bool {
         self.patterns
@@ -74,6 +74,72 @@ impl FromStr for AutoLogUpload {
     }
 }
 
+/// How tedge-watchdog supervises the thin-edge services
+#[derive(
+    Debug, Display, Clone, Copy, Eq, PartialEq, doku::Document, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum WatchdogMode {
+    /// The services are restarted by systemd, using the health notifications sent by tedge-watchdog
+    Systemd,
+    /// The services are restarted by tedge-watchdog itself
+    Native,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to parse watchdog mode: {input}. Supported values are: 'systemd' or 'native'")]
+pub struct InvalidWatchdogMode {
+    input: String,
+}
+
+impl FromStr for WatchdogMode {
+    type Err = InvalidWatchdogMode;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "systemd" => Ok(WatchdogMode::Systemd),
+            "native" => Ok(WatchdogMode::Native),
+            _ => Err(InvalidWatchdogMode {
+                input: input.to_string(),
+            }),
+        }
+    }
+}
+
+/// A cloud to which thin-edge can be connected
+#[derive(
+    Debug, Display, Clone, Copy, Eq, PartialEq, doku::Document, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum CloudType {
+    C8y,
+    Az,
+    Aws,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to parse cloud: {input}. Supported values are: 'c8y', 'az' or 'aws'")]
+pub struct InvalidCloudType {
+    input: String,
+}
+
+impl FromStr for CloudType {
+    type Err = InvalidCloudType;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "c8y" => Ok(CloudType::C8y),
+            "az" => Ok(CloudType::Az),
+            "aws" => Ok(CloudType::Aws),
+            _ => Err(InvalidCloudType {
+                input: input.to_string(),
+            }),
+        }
+    }
+}
+
 pub const MQTT_MAX_PAYLOAD_SIZE: u32 = 268435455;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Document)]
@@ -11,6 +11,7 @@ use super::models::timestamp::TimeFormat;
 use super::models::AptConfig;
 use super::models::AutoFlag;
 use super::models::AutoLogUpload;
+use super::models::CloudType;
 use super::models::ConnectUrl;
 use super::models::Cryptoki;
 use super::models::HostPort;
@@ -19,6 +20,7 @@ use super::models::SecondsOrHumanTime;
 use super::models::SoftwareManagementApiFlag;
 use super::models::TemplatesSet;
 use super::models::TopicPrefix;
+use super::models::WatchdogMode;
 use super::models::HTTPS_PORT;
 use super::models::MQTT_TLS_PORT;
 use super::tedge_config_location::TEdgeConfigLocation;
@@ -30,6 +32,7 @@ use camino::Utf8PathBuf;
 use certificate::parse_root_certificate::client_config_for_ca_certificates;
 use certificate::parse_root_certificate::create_tls_config;
 use certificate::parse_root_certificate::create_tls_config_without_client_cert;
+use certificate::parse_root_certificate::CryptokiConfig;
 use certificate::read_trust_store;
 use certificate::CertificateError;
 use certificate::CloudHttpConfig;
@@ -204,11 +207,38 @@ define_tedge_config! {
             requested_duration: SecondsOrHumanTime,
 
             /// Minimum validity duration below which a new certificate should be requested
-            #[tedge_config(note = "This is an advisory setting and the renewal has to be scheduled")]
+            #[tedge_config(note = "This is an advisory setting and the renewal has to be scheduled, unless `certificate.renewal.enable` is set")]
             #[tedge_config(example = "30d", default(from_str = "30d"))]
             minimum_duration: SecondsOrHumanTime,
         },
 
+        renewal: {
+            /// Determines if tedge-agent should renew the device certificate before it expires
+            #[tedge_config(note = "The certificate is renewed using the EST server given by `certificate.est.url` or else the Cumulocity CA, then the device is reconnected with `tedge reconnect`")]
+            #[tedge_config(example = "true", default(value = false))]
+            enable: bool,
+
+            /// How often tedge-agent checks the validity of the device certificate
+            #[tedge_config(example = "1h", default(from_str = "1h"))]
+            check_interval: SecondsOrHumanTime,
+
+            /// The cloud connection using the device certificate to be renewed
+            #[tedge_config(example = "c8y", example = "az", example = "aws", default(variable = "CloudType::C8y"))]
+            cloud: CloudType,
+
+            /// The profile of the cloud connection using the device certificate to be renewed
+            #[tedge_config(note = "When not set, the default profile of `certificate.renewal.cloud` is used")]
+            #[tedge_config(example = "edge")]
+            profile: Arc<str>,
+        },
+
+        est: {
+            /// URL of the EST server used to renew the device certificate
+            #[tedge_config(note = "When not set, the certificate is renewed by the Cumulocity CA, through the local proxy of the c8y mapper")]
+            #[tedge_config(example = "https://est.example.com", example = "https://est.example.com/.well-known/est/my-ca")]
+            url: Arc<str>,
+        },
+
         /// Organization name used for certificate signing requests
         #[tedge_config(example = "ACME", default(value = "Thin Edge"))]
         organization: Arc<str>,
@@ -326,6 +356,14 @@ define_tedge_config! {
             /// Enable device_profile feature
             #[tedge_config(example = "true", default(value = true))]
             device_profile: bool,
+
+            /// Enable the c8y_Command operation, mapped to the shell command of the devices
+            #[tedge_config(example = "true", default(value = false))]
+            shell: bool,
+
+            /// Enable the c8y_FileList operation, mapped to the file_list command of the devices
+            #[tedge_config(example = "true", default(value = false))]
+            file_list: bool,
         },
 
         mapper: {
@@ -433,6 +471,14 @@ define_tedge_config! {
             #[tedge_config(example = "60m", default(from_str = "60m"))]
             interval: SecondsOrHumanTime,
         },
+
+        metrics: {
+            bind: {
+                /// The port the Cumulocity mapper serves its internal metrics on, when `metrics.enable` is set
+                #[tedge_config(example = "9101", default(value = 9101u16))]
+                port: u16,
+            },
+        },
     },
 
     #[tedge_config(deprecated_name = "azure")] // for 0.1.0 compatibility
@@ -508,8 +554,16 @@ define_tedge_config! {
 
         /// Set of MQTT topics the Azure IoT mapper should subscribe to
         #[tedge_config(example = "te/+/+/+/+/a/+,te/+/+/+/+/m/+,te/+/+/+/+/e/+")]
-        #[tedge_config(default(value = "te/+/+/+/+/m/+,te/+/+/+/+/e/+,te/+/+/+/+/a/+,te/+/+/+/+/status/health"))]
+        #[tedge_config(default(value = "te/+/+/+/+/m/+,te/+/+/+/+/e/+,te/+/+/+/+/a/+,te/+/+/+/+/status/health,te/+/+/+/+/twin/+"))]
         topics: TemplatesSet,
+
+        metrics: {
+            bind: {
+                /// The port the Azure IoT mapper serves its internal metrics on, when `metrics.enable` is set
+                #[tedge_config(example = "9102", default(value = 9102u16))]
+                port: u16,
+            },
+        },
     },
 
     #[tedge_config(multi)]
@@ -585,8 +639,16 @@ define_tedge_config! {
 
         /// Set of MQTT topics the AWS IoT mapper should subscribe to
         #[tedge_config(example = "te/+/+/+/+/a/+,te/+/+/+/+/m/+,te/+/+/+/+/e/+")]
-        #[tedge_config(default(value = "te/+/+/+/+/m/+,te/+/+/+/+/e/+,te/+/+/+/+/a/+,te/+/+/+/+/status/health"))]
+        #[tedge_config(default(value = "te/+/+/+/+/m/+,te/+/+/+/+/e/+,te/+/+/+/+/a/+,te/+/+/+/+/status/health,te/+/+/+/+/twin/+"))]
         topics: TemplatesSet,
+
+        metrics: {
+            bind: {
+                /// The port the AWS IoT mapper serves its internal metrics on, when `metrics.enable` is set
+                #[tedge_config(example = "9103", default(value = 9103u16))]
+                port: u16,
+            },
+        },
     },
 
     mqtt: {
@@ -704,6 +766,29 @@ define_tedge_config! {
                 #[tedge_config(example = "5m", default(from_str = "5m"))]
                 reset_window: SecondsOrHumanTime,
             },
+
+            queue: {
+                /// Persist the messages forwarded to the cloud by the built-in bridge till acknowledged by the cloud
+                #[tedge_config(default(value = false))]
+                #[tedge_config(example = "true")]
+                #[tedge_config(note = "The messages are stored under `<data.path>/bridge`. Only QoS 1 and 2 messages are persisted, QoS 0 messages being forwarded as is.")]
+                persistent: bool,
+
+                /// The maximum size in bytes of the messages persisted by the built-in bridge
+                #[tedge_config(note = "When the queue is full, the oldest messages with the lowest priority are dropped first")]
+                #[tedge_config(example = "104857600", default(value = 104857600u64))]
+                max_size: u64,
+
+                /// Set of cloud topics on which messages are forwarded first by the built-in bridge
+                #[tedge_config(example = "s/us,alarm/alarms/create")]
+                #[tedge_config(default(value = "s/us,s/us/#,alarm/alarms/create"))]
+                high_priority_topics: TemplatesSet,
+
+                /// Set of cloud topics on which messages are forwarded last and dropped first by the built-in bridge
+                #[tedge_config(example = "measurement/measurements/create")]
+                #[tedge_config(default(value = "measurement/measurements/create"))]
+                low_priority_topics: TemplatesSet,
+            },
         },
     },
 
@@ -754,6 +839,19 @@ define_tedge_config! {
         ca_path: AbsolutePath,
     },
 
+    metrics: {
+        /// Determines if tedge-agent and the mappers should expose their internal metrics
+        #[tedge_config(note = "The tedge-agent metrics are served on the `/metrics` endpoint of its HTTP server.")]
+        #[tedge_config(example = "true", default(value = false))]
+        enable: bool,
+
+        bind: {
+            /// The address the mappers serve their internal metrics on
+            #[tedge_config(example = "127.0.0.1", example = "0.0.0.0", default(variable = "Ipv4Addr::LOCALHOST"))]
+            address: IpAddr,
+        },
+    },
+
     agent: {
         state: {
             /// The directory where the tedge-agent persists its state across restarts
@@ -775,6 +873,38 @@ define_tedge_config! {
             /// Determines if tedge-agent should enable log_upload operation
             #[tedge_config(example = "true", default(value = true))]
             log_upload: bool,
+
+            /// Determines if tedge-agent should enable shell operation
+            #[tedge_config(example = "true", default(value = false))]
+            shell: bool,
+
+            /// Determines if tedge-agent should enable file_list operation
+            #[tedge_config(example = "true", default(value = false))]
+            file_list: bool,
+        },
+
+        shell: {
+            /// The user running the commands of the shell operation, using sudo
+            #[tedge_config(note = "When not set, shell commands are refused. The user running tedge-agent must be allowed by sudoers to run `sh` as this user.")]
+            #[tedge_config(example = "nobody")]
+            user: String,
+
+            /// The maximum duration of a command of the shell operation, also used when no timeout is given by the request
+            #[tedge_config(example = "60s", default(from_str = "60s"))]
+            timeout: SecondsOrHumanTime,
+        },
+
+        file_list: {
+            /// The directories that can be listed by the file_list operation, including their sub-directories
+            #[tedge_config(example = "/var/log,/etc/tedge", default(value = "/var/log"))]
+            allowed_paths: TemplatesSet,
+        },
+
+        file_cache: {
+            /// The maximum size in bytes of the files cached by tedge-agent for the child devices
+            #[tedge_config(note = "When the cache is full, the least recently used files not used by an ongoing operation are evicted first")]
+            #[tedge_config(example = "1073741824", default(value = 1073741824u64))]
+            max_size: u64,
         },
 
         entity_store: {
@@ -807,6 +937,10 @@ define_tedge_config! {
             /// The filtering criterion, in form of regex, that is used to filter out packages from the output list
             #[tedge_config(example = "^(glibc|lib|kernel-|iptables-module).*")]
             exclude: String,
+
+            /// Revert all the changes of a software update when one of the module updates fails
+            #[tedge_config(example = "true", default(value = false))]
+            rollback: bool,
         }
     },
 
@@ -842,6 +976,27 @@ define_tedge_config! {
         path: AbsolutePath,
     },
 
+    download: {
+        /// The directory of the PEM-encoded public keys trusted to sign the downloaded artifacts
+        #[tedge_config(example = "/etc/tedge/trust-store", default(function = "default_trust_store"))]
+        trust_store: AbsolutePath,
+
+        /// Reject the downloaded artifacts that are not signed by a trusted key
+        #[tedge_config(example = "true", default(value = false))]
+        require_signature: bool,
+
+        delta: {
+            /// Reconstruct the downloaded artifacts from delta patches, when provided along a checksum
+            #[tedge_config(example = "true", default(value = false))]
+            enable: bool,
+
+            /// The maximum size in bytes of the downloaded artifacts kept in the data directory as base versions for delta patches
+            #[tedge_config(note = "When the cache is full, the least recently used artifacts are evicted first")]
+            #[tedge_config(example = "1073741824", default(value = 1073741824u64))]
+            cache_max_size: u64,
+        },
+    },
+
     firmware: {
         child: {
             update: {
@@ -884,6 +1039,42 @@ define_tedge_config! {
         },
     },
 
+    watchdog: {
+        /// How tedge-watchdog supervises the thin-edge services
+        #[tedge_config(note = "With `systemd`, tedge-watchdog notifies systemd on behalf of the healthy services, using their `WatchdogSec`. With `native`, tedge-watchdog restarts itself the services that stop responding, e.g. on devices without systemd")]
+        #[tedge_config(example = "systemd", example = "native", default(variable = "WatchdogMode::Systemd"))]
+        mode: WatchdogMode,
+
+        /// The services supervised by tedge-watchdog
+        #[tedge_config(note = "In native mode, a service is only supervised once it has responded to a health check, i.e. once installed and started")]
+        #[tedge_config(example = "tedge-agent,tedge-mapper-c8y")]
+        #[tedge_config(default(value = "tedge-agent,tedge-mapper-c8y,tedge-mapper-az,tedge-mapper-aws,tedge-mapper-collectd,c8y-firmware-plugin"))]
+        services: TemplatesSet,
+
+        /// How often tedge-watchdog checks the health of the services in native mode
+        #[tedge_config(example = "30s", default(from_str = "30s"))]
+        check_interval: SecondsOrHumanTime,
+
+        /// How long tedge-watchdog waits for a health check response, before restarting the service in native mode
+        #[tedge_config(example = "10s", default(from_str = "10s"))]
+        response_timeout: SecondsOrHumanTime,
+
+        restart: {
+            /// The command used by tedge-watchdog to restart an unresponsive service, `{}` being replaced by the service name
+            #[tedge_config(note = "When not set, the `init.restart` command of `system.toml` is used")]
+            #[tedge_config(example = "/usr/bin/sv restart {}")]
+            command: String,
+
+            /// The number of consecutive restarts of a service after which tedge-watchdog raises an alarm
+            #[tedge_config(example = "3", default(value = 3u32))]
+            threshold: u32,
+
+            /// How long tedge-watchdog waits after restarting a service, before checking its health again
+            #[tedge_config(example = "60s", default(from_str = "60s"))]
+            grace_period: SecondsOrHumanTime,
+        },
+    },
+
     sudo: {
         /// Determines if thin-edge should use `sudo` when attempting to write to files possibly
         /// not owned by `tedge`.
@@ -1032,6 +1223,19 @@ impl TEdgeConfigReader {
         })
     }
 
+    /// The PKCS#11 configuration to use the device key for the given cloud, if the key is stored in a token
+    pub fn device_cryptoki_config<'a>(
+        &self,
+        cloud: Option<impl Into<Cloud<'a>>>,
+    ) -> anyhow::Result<Option<CryptokiConfig>> {
+        match cloud.map(<_>::into) {
+            None => self.device.default_cryptoki_config(),
+            Some(Cloud::C8y(profile)) => self.device.cryptoki_config(self.c8y.try_get(profile)?),
+            Some(Cloud::Az(profile)) => self.device.cryptoki_config(self.az.try_get(profile)?),
+            Some(Cloud::Aws(profile)) => self.device.cryptoki_config(self.aws.try_get(profile)?),
+        }
+    }
+
     pub fn device_id<'a>(&self, cloud: Option<impl Into<Cloud<'a>>>) -> Result<&str, ReadError> {
         Ok(match cloud.map(<_>::into) {
             None => self.device.id()?,
@@ -1229,6 +1433,14 @@ fn default_root_cert_path(_location: &TEdgeConfigLocation) -> AbsolutePath {
     AbsolutePath::try_new(DEFAULT_ROOT_CERT_PATH).unwrap()
 }
 
+fn default_trust_store(location: &TEdgeConfigLocation) -> AbsolutePath {
+    location
+        .tedge_config_root_path()
+        .join("trust-store")
+        .try_into()
+        .unwrap()
+}
+
 fn default_device_key(location: &TEdgeConfigLocation) -> AbsolutePath {
     location
         .tedge_config_root_path()
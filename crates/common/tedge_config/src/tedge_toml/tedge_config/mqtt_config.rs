@@ -11,6 +11,7 @@ use anyhow::Context;
 use camino::Utf8PathBuf;
 use certificate::parse_root_certificate::AuthPin;
 use certificate::CertificateError;
+use std::sync::Arc;
 use tedge_config_macros::all_or_nothing;
 
 use super::CloudConfig;
@@ -217,8 +218,20 @@ impl TEdgeConfigReaderDevice {
         &self,
         cloud: &dyn CloudConfig,
     ) -> Result<Option<CryptokiConfig>, anyhow::Error> {
-        let cryptoki = &self.cryptoki;
         let uri = cloud.key_uri().or(self.key_uri.or_none().cloned());
+        self.cryptoki_config_with_key_uri(uri)
+    }
+
+    /// The PKCS#11 configuration to use the device key, independently of any cloud profile
+    pub fn default_cryptoki_config(&self) -> Result<Option<CryptokiConfig>, anyhow::Error> {
+        self.cryptoki_config_with_key_uri(self.key_uri.or_none().cloned())
+    }
+
+    fn cryptoki_config_with_key_uri(
+        &self,
+        uri: Option<Arc<str>>,
+    ) -> Result<Option<CryptokiConfig>, anyhow::Error> {
+        let cryptoki = &self.cryptoki;
         match cryptoki.mode {
             Cryptoki::Off => Ok(None),
             Cryptoki::Module => Ok(Some(CryptokiConfig::Direct(CryptokiConfigDirect {
@@ -0,0 +1,273 @@
+//! Internal metrics of the thin-edge services, exposed using the OpenMetrics text format.
+//!
+//! The metrics are collected in a process-wide registry, which is only created
+//! once [enable] has been called by the service. Until then, all the `record_*`
+//! functions are no-ops, so the instrumented crates don't have to check the configuration.
+//!
+//! The following metrics are provided:
+//! - `tedge_mqtt_messages_total{direction, channel}`: the number of MQTT messages
+//!   received or published by the service, per thin-edge channel
+//!   (`measurement`, `event`, `alarm`, `command`, ... or `other` for non thin-edge topics)
+//! - `tedge_conversion_errors_total{mapper}`: the number of messages a mapper failed to convert
+//! - `tedge_message_box_queue_depth{actor}`: the number of messages waiting in the input queue of an actor
+//! - `tedge_bridge_reconnects_total{broker}`: the number of times a bridge connection has been re-established
+//! - `tedge_command_duration_seconds{operation}`: how long the commands take from `init` to a final state
+mod server;
+
+pub use server::*;
+
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::exponential_buckets;
+use prometheus_client::metrics::histogram::Histogram;
+use prometheus_client::registry::Registry;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::MqttSchema;
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Start collecting the metrics of this process
+///
+/// The MQTT schema is used to classify the MQTT messages per channel.
+pub fn enable(mqtt_schema: MqttSchema) {
+    METRICS.get_or_init(|| Metrics::new(mqtt_schema));
+}
+
+/// The direction of an MQTT message, from the point of view of the service
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Received,
+    Published,
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Received => "received",
+            Direction::Published => "published",
+        }
+    }
+}
+
+/// Count an MQTT message received or published on the given topic
+pub fn record_mqtt_message(direction: Direction, topic: &str) {
+    if let Some(metrics) = METRICS.get() {
+        let labels = MqttLabels {
+            direction: direction.as_str(),
+            channel: metrics.channel_kind(topic),
+        };
+        metrics.mqtt_messages.get_or_create(&labels).inc();
+    }
+}
+
+/// Count a message the given mapper failed to convert
+pub fn record_conversion_error(mapper: &str) {
+    if let Some(metrics) = METRICS.get() {
+        let labels = MapperLabels {
+            mapper: mapper.to_string(),
+        };
+        metrics.conversion_errors.get_or_create(&labels).inc();
+    }
+}
+
+/// Count a re-connection of the bridge to the given broker (`local` or `cloud`)
+pub fn record_bridge_reconnect(broker: &str) {
+    if let Some(metrics) = METRICS.get() {
+        let labels = BrokerLabels {
+            broker: broker.to_string(),
+        };
+        metrics.bridge_reconnects.get_or_create(&labels).inc();
+    }
+}
+
+/// Record the time taken by a command to reach a final state
+pub fn record_command_duration(operation: &str, duration: Duration) {
+    if let Some(metrics) = METRICS.get() {
+        let labels = OperationLabels {
+            operation: operation.to_string(),
+        };
+        metrics
+            .command_duration
+            .get_or_create(&labels)
+            .observe(duration.as_secs_f64());
+    }
+}
+
+/// Encode the current value of all the metrics using the OpenMetrics text format
+///
+/// Return `None` if the metrics are not enabled for this process.
+pub fn encode_metrics() -> Option<String> {
+    let metrics = METRICS.get()?;
+    metrics.refresh_queue_depths();
+
+    let mut buffer = String::new();
+    encode(&mut buffer, &metrics.registry).ok()?;
+    Some(buffer)
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct MqttLabels {
+    direction: &'static str,
+    channel: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct MapperLabels {
+    mapper: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ActorLabels {
+    actor: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct BrokerLabels {
+    broker: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct OperationLabels {
+    operation: String,
+}
+
+struct Metrics {
+    mqtt_schema: MqttSchema,
+    registry: Registry,
+    mqtt_messages: Family<MqttLabels, Counter>,
+    conversion_errors: Family<MapperLabels, Counter>,
+    queue_depth: Family<ActorLabels, Gauge>,
+    bridge_reconnects: Family<BrokerLabels, Counter>,
+    command_duration: Family<OperationLabels, Histogram, fn() -> Histogram>,
+}
+
+impl Metrics {
+    fn new(mqtt_schema: MqttSchema) -> Self {
+        let mqtt_messages = Family::<MqttLabels, Counter>::default();
+        let conversion_errors = Family::<MapperLabels, Counter>::default();
+        let queue_depth = Family::<ActorLabels, Gauge>::default();
+        let bridge_reconnects = Family::<BrokerLabels, Counter>::default();
+        let command_duration =
+            Family::<OperationLabels, Histogram, fn() -> Histogram>::new_with_constructor(|| {
+                // From 100 ms to about one hour
+                Histogram::new(exponential_buckets(0.1, 2.0, 16))
+            });
+
+        let mut registry = Registry::default();
+        registry.register(
+            "tedge_mqtt_messages",
+            "Number of MQTT messages received or published",
+            mqtt_messages.clone(),
+        );
+        registry.register(
+            "tedge_conversion_errors",
+            "Number of messages the mapper failed to convert",
+            conversion_errors.clone(),
+        );
+        registry.register(
+            "tedge_message_box_queue_depth",
+            "Number of messages waiting in the input queue of an actor",
+            queue_depth.clone(),
+        );
+        registry.register(
+            "tedge_bridge_reconnects",
+            "Number of times a bridge connection has been re-established",
+            bridge_reconnects.clone(),
+        );
+        registry.register(
+            "tedge_command_duration_seconds",
+            "Time taken by the commands to reach a final state",
+            command_duration.clone(),
+        );
+
+        Metrics {
+            mqtt_schema,
+            registry,
+            mqtt_messages,
+            conversion_errors,
+            queue_depth,
+            bridge_reconnects,
+            command_duration,
+        }
+    }
+
+    fn channel_kind(&self, topic: &str) -> &'static str {
+        match self.mqtt_schema.entity_channel_of(topic) {
+            Ok((_, channel)) => match channel {
+                Channel::EntityMetadata => "entity_metadata",
+                Channel::EntityTwinData { .. } => "twin",
+                Channel::Measurement { .. } => "measurement",
+                Channel::MeasurementMetadata { .. } => "measurement_metadata",
+                Channel::Event { .. } => "event",
+                Channel::EventMetadata { .. } => "event_metadata",
+                Channel::Alarm { .. } => "alarm",
+                Channel::AlarmMetadata { .. } => "alarm_metadata",
+                Channel::Command { .. } => "command",
+                Channel::CommandMetadata { .. } => "command_metadata",
+                Channel::Health => "health",
+            },
+            Err(_) => "other",
+        }
+    }
+
+    /// Update the queue depth gauges, these being only known by the message boxes
+    fn refresh_queue_depths(&self) {
+        self.queue_depth.clear();
+        for (actor, depth) in tedge_actors::queue_depth::queue_depths() {
+            self.queue_depth
+                .get_or_create(&ActorLabels { actor })
+                .set(depth as i64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_counted_per_channel() {
+        let metrics = Metrics::new(MqttSchema::new());
+
+        assert_eq!(
+            metrics.channel_kind("te/device/main///m/temperature"),
+            "measurement"
+        );
+        assert_eq!(
+            metrics.channel_kind("te/device/child01///a/high_temperature"),
+            "alarm"
+        );
+        assert_eq!(
+            metrics.channel_kind("te/device/main///cmd/restart/123"),
+            "command"
+        );
+        assert_eq!(
+            metrics.channel_kind("te/device/main/service/tedge-agent/status/health"),
+            "health"
+        );
+        assert_eq!(metrics.channel_kind("c8y/s/us"), "other");
+    }
+
+    #[test]
+    fn metrics_are_encoded_using_the_open_metrics_format() {
+        enable(MqttSchema::new());
+        record_mqtt_message(Direction::Received, "te/device/main///m/temperature");
+        record_mqtt_message(Direction::Received, "te/device/main///m/pressure");
+        record_conversion_error("tedge-mapper-c8y");
+        record_command_duration("restart", Duration::from_secs(3));
+
+        let output = encode_metrics().unwrap();
+
+        assert!(output.contains(
+            r#"tedge_mqtt_messages_total{direction="received",channel="measurement"} 2"#
+        ));
+        assert!(output.contains(r#"tedge_conversion_errors_total{mapper="tedge-mapper-c8y"} 1"#));
+        assert!(output.contains(r#"tedge_command_duration_seconds_count{operation="restart"} 1"#));
+        assert!(output.ends_with("# EOF\n"));
+    }
+}
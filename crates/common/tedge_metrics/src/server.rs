@@ -0,0 +1,36 @@
+use crate::encode_metrics;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use std::net::SocketAddr;
+
+/// The content type of the metrics, as expected by Prometheus
+pub const OPEN_METRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// A router serving the metrics of this process on `GET /metrics`
+///
+/// Responds with `404 Not Found` if the metrics are not enabled.
+pub fn metrics_router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new().route("/metrics", get(get_metrics))
+}
+
+/// Serve the metrics of this process on `http://{addr}/metrics`
+pub async fn serve_metrics(addr: SocketAddr) -> std::io::Result<()> {
+    axum_server::bind(addr)
+        .serve(metrics_router::<()>().into_make_service())
+        .await
+}
+
+async fn get_metrics() -> Response {
+    match encode_metrics() {
+        Some(metrics) => ([(CONTENT_TYPE, OPEN_METRICS_CONTENT_TYPE)], metrics).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
@@ -40,6 +40,8 @@ pub enum C8yDeviceControlOperation {
     DownloadConfigFile(C8yDownloadConfigFile),
     Firmware(C8yFirmware),
     DeviceProfile(C8yDeviceProfile),
+    Command(C8yCommand),
+    FileList(C8yFileList),
     Custom,
 }
 
@@ -71,6 +73,10 @@ impl C8yDeviceControlOperation {
             C8yDeviceControlOperation::DeviceProfile(C8yDeviceProfile::from_json_value(
                 value.clone(),
             )?)
+        } else if let Some(value) = hashmap.get("c8y_Command") {
+            C8yDeviceControlOperation::Command(C8yCommand::from_json_value(value.clone())?)
+        } else if let Some(value) = hashmap.get("c8y_FileList") {
+            C8yDeviceControlOperation::FileList(C8yFileList::from_json_value(value.clone())?)
         } else {
             C8yDeviceControlOperation::Custom
         };
@@ -228,6 +234,7 @@ impl TryFrom<C8ySoftwareUpdate> for SoftwarePayload {
                 name: module.name,
                 version,
                 url,
+                tedge_url: None,
                 action: match module.action.clone().try_into()? {
                     C8ySoftwareUpdateAction::Install => Some(SoftwareModuleAction::Install),
                     C8ySoftwareUpdateAction::Delete => Some(SoftwareModuleAction::Remove),
@@ -402,6 +409,15 @@ pub struct C8yLogfileRequest {
     #[serde(deserialize_with = "to_datetime")]
     pub date_from: OffsetDateTime,
     pub maximum_lines: usize,
+    /// The optional search fields, forwarded as is to the log manager
+    #[serde(default)]
+    pub search_regex: bool,
+    #[serde(default)]
+    pub ignore_case: bool,
+    #[serde(default)]
+    pub exclude_text: Option<String>,
+    #[serde(default)]
+    pub context_lines: Option<usize>,
 }
 
 /// Default type assigned to the upload/download operations
@@ -544,6 +560,38 @@ pub struct C8yDeviceProfile {
     pub configuration: Vec<C8yDownloadConfigFile>,
 }
 
+/// Representation of c8y_Command JSON object
+///
+/// ```rust
+/// use c8y_api::json_c8y_deserializer::C8yCommand;
+///
+/// // Example input from c8y
+/// let data = r#"{"text": "df -h"}"#;
+///
+/// // Parse the data
+/// let req: C8yCommand = serde_json::from_str(data).unwrap();
+/// ```
+#[derive(Debug, Deserialize, Eq, PartialEq)]
+pub struct C8yCommand {
+    pub text: String,
+}
+
+/// Representation of c8y_FileList JSON object
+///
+/// ```rust
+/// use c8y_api::json_c8y_deserializer::C8yFileList;
+///
+/// // Example input from c8y
+/// let data = r#"{"path": "/var/log"}"#;
+///
+/// // Parse the data
+/// let req: C8yFileList = serde_json::from_str(data).unwrap();
+/// ```
+#[derive(Debug, Deserialize, Eq, PartialEq)]
+pub struct C8yFileList {
+    pub path: String,
+}
+
 /// Error returned by C8Y REST API
 #[derive(Debug, Deserialize, Eq, PartialEq)]
 pub struct C8yAPIError {
@@ -574,6 +622,10 @@ impl C8yDeviceControlOperationHelper for C8yFirmware {}
 
 impl C8yDeviceControlOperationHelper for C8yDeviceProfile {}
 
+impl C8yDeviceControlOperationHelper for C8yCommand {}
+
+impl C8yDeviceControlOperationHelper for C8yFileList {}
+
 #[derive(thiserror::Error, Debug)]
 pub enum C8yJsonOverMqttDeserializerError {
     #[error("Parameter {parameter} is not recognized. {hint}")]
@@ -154,11 +154,17 @@ pub fn succeed_operation(
         let reason = reason.strip_prefix('"').unwrap_or(reason);
         let reason = reason.strip_suffix('"').unwrap_or(reason);
 
-        // if we'd cut across an escaped " character, move trim point 1 char back to omit it
-        if &reason[max_result_limit - 1..=max_result_limit] == r#""""# {
+        // don't cut across a multi-byte character
+        while !reason.is_char_boundary(max_result_limit) {
             max_result_limit -= 1;
         }
-        let trimmed_reason = &reason[..max_result_limit];
+        let mut trimmed_reason = &reason[..max_result_limit];
+
+        // if we'd cut across an escaped " character, i.e. a pair of quotes, omit it
+        let trailing_quotes = trimmed_reason.len() - trimmed_reason.trim_end_matches('"').len();
+        if trailing_quotes % 2 == 1 {
+            trimmed_reason = &trimmed_reason[..trimmed_reason.len() - 1];
+        }
 
         format!("{prefix},\"{trimmed_reason}{trim_indicator}\"")
     };
@@ -189,6 +195,8 @@ pub enum CumulocitySupportedOperations {
     C8yDownloadConfigFile,
     C8yFirmware,
     C8yDeviceProfile,
+    C8yCommand,
+    C8yFileList,
     C8yCustom(String),
 }
 
@@ -202,6 +210,8 @@ impl CumulocitySupportedOperations {
             CumulocitySupportedOperations::C8yDownloadConfigFile => "c8y_DownloadConfigFile",
             CumulocitySupportedOperations::C8yFirmware => "c8y_Firmware",
             CumulocitySupportedOperations::C8yDeviceProfile => "c8y_DeviceProfile",
+            CumulocitySupportedOperations::C8yCommand => "c8y_Command",
+            CumulocitySupportedOperations::C8yFileList => "c8y_FileList",
             CumulocitySupportedOperations::C8yCustom(operation) => operation.as_str(),
         }
     }
@@ -670,4 +680,18 @@ mod tests {
 
         assert_eq!(num_quotes, expected_num_quotes);
     }
+
+    /// Make sure that `reason` field is not trimmed across a multi-byte character.
+    #[test]
+    fn succeed_operation_trims_reason_field_on_char_boundaries() {
+        let reason = format!("x{}", "é\"".repeat(MAX_PAYLOAD_LIMIT_IN_BYTES));
+
+        let smartrest =
+            succeed_operation(SET_OPERATION_TO_SUCCESSFUL, "c8y_Command", reason).unwrap();
+
+        let smartrest = smartrest.as_str();
+        assert!(smartrest.len() <= MAX_PAYLOAD_LIMIT_IN_BYTES);
+        assert!(smartrest.starts_with(r#"503,c8y_Command,"xé""é"#));
+        assert!(smartrest.ends_with(r#"é""...<trimmed>""#));
+    }
 }
@@ -2,7 +2,9 @@ use async_trait::async_trait;
 use camino::Utf8Path;
 use certificate::CloudHttpConfig;
 use csv::ReaderBuilder;
+use download::ArtifactCache;
 use download::Downloader;
+use download::TrustStore;
 use regex::Regex;
 use reqwest::Identity;
 use serde::Deserialize;
@@ -53,6 +55,25 @@ pub trait Plugin {
         command_log: Option<&mut CommandLog>,
     ) -> Result<Vec<SoftwareModule>, SoftwareError>;
 
+    /// List all the installed modules, to be able to restore them after a failed update
+    ///
+    /// Contrary to `list`, the returned list must be neither filtered nor truncated.
+    async fn snapshot(
+        &self,
+        command_log: Option<&mut CommandLog>,
+    ) -> Result<Vec<SoftwareModule>, SoftwareError> {
+        self.list(command_log).await
+    }
+
+    /// Apply in one go the updates restoring the modules to their previous state
+    ///
+    /// Return `SoftwareError::RollbackNotSupported` if the updates have to be applied one by one.
+    async fn rollback_list(
+        &self,
+        updates: &[SoftwareModuleUpdate],
+        command_log: Option<&mut CommandLog>,
+    ) -> Result<(), SoftwareError>;
+
     async fn version(
         &self,
         module: &SoftwareModule,
@@ -77,6 +98,8 @@ pub trait Plugin {
                             download_path,
                             self.identity(),
                             self.cloud_root_certs().clone(),
+                            self.trust_store().clone(),
+                            self.artifact_cache().cloned(),
                         )
                         .await?
                     }
@@ -91,6 +114,8 @@ pub trait Plugin {
 
     fn identity(&self) -> Option<&Identity>;
     fn cloud_root_certs(&self) -> &CloudHttpConfig;
+    fn trust_store(&self) -> &Arc<TrustStore>;
+    fn artifact_cache(&self) -> Option<&Arc<ArtifactCache>>;
 
     async fn apply_all(
         &self,
@@ -122,6 +147,8 @@ pub trait Plugin {
                     download_path,
                     self.identity(),
                     self.cloud_root_certs().clone(),
+                    self.trust_store().clone(),
+                    self.artifact_cache().cloned(),
                 )
                 .await
                 {
@@ -169,6 +196,47 @@ pub trait Plugin {
         failed_updates
     }
 
+    /// Restore the modules touched by the given updates to the state captured by a `snapshot`
+    ///
+    /// Return the updates that have been applied to restore the modules.
+    async fn rollback(
+        &self,
+        updates: &[SoftwareModuleUpdate],
+        snapshot: &[SoftwareModule],
+        mut command_log: Option<&mut CommandLog>,
+        download_path: &Path,
+    ) -> Result<Vec<SoftwareModuleUpdate>, SoftwareError> {
+        let current = self.snapshot(command_log.as_deref_mut()).await?;
+        let restore_updates = rollback_updates(updates, snapshot, &current);
+        if restore_updates.is_empty() {
+            return Ok(restore_updates);
+        }
+
+        let outcome = self
+            .rollback_list(&restore_updates, command_log.as_deref_mut())
+            .await;
+        if let Err(err @ SoftwareError::RollbackNotSupported(_)) = outcome {
+            info!("{err}");
+            self.prepare(command_log.as_deref_mut()).await?;
+            let mut result = Ok(());
+            for update in restore_updates.iter() {
+                result = self
+                    .apply(update, command_log.as_deref_mut(), download_path)
+                    .await;
+                if result.is_err() {
+                    break;
+                }
+            }
+            let finalized = self.finalize(command_log.as_deref_mut()).await;
+            result.and(finalized)?;
+        } else {
+            outcome?;
+        }
+
+        Ok(restore_updates)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn install_from_url(
         &self,
         module: &mut SoftwareModule,
@@ -177,6 +245,8 @@ pub trait Plugin {
         download_path: &Path,
         identity: Option<&Identity>,
         cloud_root_certs: CloudHttpConfig,
+        trust_store: Arc<TrustStore>,
+        artifact_cache: Option<Arc<ArtifactCache>>,
     ) -> Result<(), SoftwareError> {
         let downloader = Self::download_from_url(
             module,
@@ -185,6 +255,8 @@ pub trait Plugin {
             download_path,
             identity,
             cloud_root_certs,
+            trust_store,
+            artifact_cache,
         )
         .await?;
         let result = self.install(module, command_log.as_deref_mut()).await;
@@ -193,6 +265,7 @@ pub trait Plugin {
         result
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn download_from_url(
         module: &mut SoftwareModule,
         url: &DownloadInfo,
@@ -200,10 +273,14 @@ pub trait Plugin {
         download_path: &Path,
         identity: Option<&Identity>,
         cloud_root_certs: CloudHttpConfig,
+        trust_store: Arc<TrustStore>,
+        artifact_cache: Option<Arc<ArtifactCache>>,
     ) -> Result<Downloader, SoftwareError> {
         let sm_path = sm_path(&module.name, &module.version, download_path);
         let downloader =
-            Downloader::new(sm_path, identity.map(|id| id.to_owned()), cloud_root_certs);
+            Downloader::new(sm_path, identity.map(|id| id.to_owned()), cloud_root_certs)
+                .with_trust_store(trust_store)
+                .with_artifact_cache(artifact_cache);
 
         if let Some(ref mut logger) = command_log {
             logger
@@ -279,6 +356,8 @@ pub struct ExternalPluginCommand {
     include: Option<String>,
     identity: Option<Identity>,
     cloud_root_certs: CloudHttpConfig,
+    trust_store: Arc<TrustStore>,
+    artifact_cache: Option<Arc<ArtifactCache>>,
     pub tmp_dir: Arc<Utf8Path>,
 }
 
@@ -304,10 +383,28 @@ impl ExternalPluginCommand {
             include,
             identity,
             cloud_root_certs,
+            trust_store: Arc::new(TrustStore::default()),
+            artifact_cache: None,
             tmp_dir,
         }
     }
 
+    /// Verify the signature of the downloaded modules against the given trust store
+    pub fn with_trust_store(self, trust_store: Arc<TrustStore>) -> Self {
+        Self {
+            trust_store,
+            ..self
+        }
+    }
+
+    /// Cache the downloaded modules, so they can be used as base for delta patches
+    pub fn with_artifact_cache(self, artifact_cache: Option<Arc<ArtifactCache>>) -> Self {
+        Self {
+            artifact_cache,
+            ..self
+        }
+    }
+
     pub fn command(
         &self,
         action: &str,
@@ -358,6 +455,54 @@ impl ExternalPluginCommand {
         }
     }
 
+    /// Run a plugin command that reads the list of updates on its stdin, one update per line
+    async fn execute_with_updates(
+        &self,
+        action: &str,
+        updates: &[SoftwareModuleUpdate],
+        command_log: Option<&mut CommandLog>,
+    ) -> Result<Output, SoftwareError> {
+        let mut command = self.command(action, None)?;
+
+        let mut child = command.spawn()?;
+        let child_stdin =
+            child
+                .inner_child
+                .stdin
+                .as_mut()
+                .ok_or_else(|| SoftwareError::IoError {
+                    reason: "Plugin stdin unavailable".into(),
+                })?;
+
+        for update in updates {
+            let action = match update {
+                SoftwareModuleUpdate::Install { module } => {
+                    format!(
+                        "install\t{}\t{}\t{}\n",
+                        module.name,
+                        module.version.clone().map_or("".into(), |v| v),
+                        module.file_path.clone().map_or("".into(), |v| v
+                            .to_str()
+                            .map_or("".into(), |u| u.to_string()))
+                    )
+                }
+
+                SoftwareModuleUpdate::Remove { module } => {
+                    format!(
+                        "remove\t{}\t{}\t\n",
+                        module.name,
+                        module.version.clone().map_or("".into(), |v| v),
+                    )
+                }
+            };
+
+            child_stdin.write_all(action.as_bytes()).await?;
+            child_stdin.flush().await?;
+        }
+
+        Ok(child.wait_with_output(command_log).await?)
+    }
+
     /// This test validates if an incoming module can be handled by it, by matching the module type with the plugin type
     pub fn check_module_type(&self, module: &SoftwareModule) -> Result<(), SoftwareError> {
         match &module.module_type {
@@ -377,6 +522,7 @@ const INSTALL: &str = "install";
 const REMOVE: &str = "remove";
 const UPDATE_LIST: &str = "update-list";
 const FINALIZE: &str = "finalize";
+const ROLLBACK: &str = "rollback";
 pub const LIST: &str = "list";
 const VERSION: &str = "version";
 
@@ -437,45 +583,9 @@ impl Plugin for ExternalPluginCommand {
         updates: &[SoftwareModuleUpdate],
         command_log: Option<&mut CommandLog>,
     ) -> Result<(), SoftwareError> {
-        let mut command = self.command(UPDATE_LIST, None)?;
-
-        let mut child = command.spawn()?;
-        let child_stdin =
-            child
-                .inner_child
-                .stdin
-                .as_mut()
-                .ok_or_else(|| SoftwareError::IoError {
-                    reason: "Plugin stdin unavailable".into(),
-                })?;
-
-        for update in updates {
-            let action = match update {
-                SoftwareModuleUpdate::Install { module } => {
-                    format!(
-                        "install\t{}\t{}\t{}\n",
-                        module.name,
-                        module.version.clone().map_or("".into(), |v| v),
-                        module.file_path.clone().map_or("".into(), |v| v
-                            .to_str()
-                            .map_or("".into(), |u| u.to_string()))
-                    )
-                }
-
-                SoftwareModuleUpdate::Remove { module } => {
-                    format!(
-                        "remove\t{}\t{}\t\n",
-                        module.name,
-                        module.version.clone().map_or("".into(), |v| v),
-                    )
-                }
-            };
-
-            child_stdin.write_all(action.as_bytes()).await?;
-            child_stdin.flush().await?;
-        }
-
-        let output = child.wait_with_output(command_log).await?;
+        let output = self
+            .execute_with_updates(UPDATE_LIST, updates, command_log)
+            .await?;
         match output.status.code() {
             Some(0) => Ok(()),
             Some(1) => Err(SoftwareError::UpdateListNotSupported(self.name.clone())),
@@ -490,6 +600,28 @@ impl Plugin for ExternalPluginCommand {
         }
     }
 
+    async fn rollback_list(
+        &self,
+        updates: &[SoftwareModuleUpdate],
+        command_log: Option<&mut CommandLog>,
+    ) -> Result<(), SoftwareError> {
+        let output = self
+            .execute_with_updates(ROLLBACK, updates, command_log)
+            .await?;
+        match output.status.code() {
+            Some(0) => Ok(()),
+            Some(1) => Err(SoftwareError::RollbackNotSupported(self.name.clone())),
+            Some(_) => Err(SoftwareError::Rollback {
+                software_type: self.name.clone(),
+                reason: self.content(output.stderr)?,
+            }),
+            None => Err(SoftwareError::Rollback {
+                software_type: self.name.clone(),
+                reason: "Interrupted".into(),
+            }),
+        }
+    }
+
     async fn finalize(&self, command_log: Option<&mut CommandLog>) -> Result<(), SoftwareError> {
         let command = self.command(FINALIZE, None)?;
         let output = self.execute(command, command_log).await?;
@@ -565,6 +697,22 @@ impl Plugin for ExternalPluginCommand {
         }
     }
 
+    async fn snapshot(
+        &self,
+        command_log: Option<&mut CommandLog>,
+    ) -> Result<Vec<SoftwareModule>, SoftwareError> {
+        let command = self.command(LIST, None)?;
+        let output = self.execute(command, command_log).await?;
+        if output.status.success() {
+            deserialize_module_info(self.name.clone(), &output.stdout[..])
+        } else {
+            Err(SoftwareError::Plugin {
+                software_type: self.name.clone(),
+                reason: self.content(output.stderr)?,
+            })
+        }
+    }
+
     async fn version(
         &self,
         module: &SoftwareModule,
@@ -595,6 +743,14 @@ impl Plugin for ExternalPluginCommand {
     fn cloud_root_certs(&self) -> &CloudHttpConfig {
         &self.cloud_root_certs
     }
+
+    fn trust_store(&self) -> &Arc<TrustStore> {
+        &self.trust_store
+    }
+
+    fn artifact_cache(&self) -> Option<&Arc<ArtifactCache>> {
+        self.artifact_cache.as_ref()
+    }
 }
 
 pub fn deserialize_module_info(
@@ -620,6 +776,38 @@ pub fn deserialize_module_info(
     Ok(software_list)
 }
 
+/// The updates restoring the modules touched by `updates` to their state in the `before` snapshot
+///
+/// The modules that are in the same state in the `after` snapshot are left untouched.
+/// The modules are restored in the reverse order of the updates.
+pub fn rollback_updates(
+    updates: &[SoftwareModuleUpdate],
+    before: &[SoftwareModule],
+    after: &[SoftwareModule],
+) -> Vec<SoftwareModuleUpdate> {
+    let find = |modules: &[SoftwareModule], name: &str| {
+        modules.iter().find(|module| module.name == name).cloned()
+    };
+
+    let mut names: Vec<&str> = Vec::new();
+    let mut restore_updates = Vec::new();
+    for update in updates.iter().rev() {
+        let name = update.module().name.as_str();
+        if names.contains(&name) {
+            continue;
+        }
+        names.push(name);
+
+        match (find(before, name), find(after, name)) {
+            (Some(previous), Some(current)) if previous.version == current.version => {}
+            (Some(previous), _) => restore_updates.push(SoftwareModuleUpdate::install(previous)),
+            (None, Some(current)) => restore_updates.push(SoftwareModuleUpdate::remove(current)),
+            (None, None) => {}
+        }
+    }
+    restore_updates
+}
+
 pub fn sm_path(name: &str, version: &Option<String>, target_dir_path: impl AsRef<Path>) -> PathBuf {
     let mut filename = name.to_string();
     if let Some(version) = version {
@@ -2,6 +2,8 @@ use crate::plugin::ExternalPluginCommand;
 use crate::plugin::Plugin;
 use crate::plugin::LIST;
 use camino::Utf8PathBuf;
+use download::ArtifactCache;
+use download::TrustStore;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fs;
@@ -10,9 +12,11 @@ use std::io::{self};
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Stdio;
+use std::sync::Arc;
 use tedge_api::commands::CommandStatus;
 use tedge_api::commands::SoftwareListCommand;
 use tedge_api::commands::SoftwareUpdateCommand;
+use tedge_api::path::DataDir;
 use tedge_api::CommandLog;
 use tedge_api::SoftwareError;
 use tedge_api::SoftwareType;
@@ -47,6 +51,7 @@ pub struct ExternalPlugins {
     default_plugin_type: Option<SoftwareType>,
     sudo: SudoCommandBuilder,
     config_dir: Utf8PathBuf,
+    rollback: bool,
 }
 
 impl Plugins for ExternalPlugins {
@@ -104,6 +109,7 @@ impl ExternalPlugins {
             default_plugin_type: default_plugin_type.clone(),
             sudo,
             config_dir,
+            rollback: false,
         };
         if let Err(e) = plugins.load().await {
             warn!(
@@ -141,6 +147,19 @@ impl ExternalPlugins {
             .await
             .map_err(|err| io::Error::other(format!("Failed to load tedge config: {}", err)))?;
 
+        let trust_store = Arc::new(
+            TrustStore::load(&config.download.trust_store)?
+                .with_required_signature(config.download.require_signature),
+        );
+        let artifact_cache = config.download.delta.enable.then(|| {
+            let data_dir = DataDir::from(config.data.path.as_path().to_owned());
+            Arc::new(ArtifactCache::new(
+                data_dir.artifact_cache_dir(),
+                config.download.delta.cache_max_size,
+            ))
+        });
+        self.rollback = config.software.plugin.rollback;
+
         for maybe_entry in fs::read_dir(&self.plugin_dir)? {
             let entry = maybe_entry?;
             let path = entry.path();
@@ -199,7 +218,9 @@ impl ExternalPlugins {
                             identity,
                             config.cloud_root_certs()?,
                             config.tmp.path.as_path().into(),
-                        );
+                        )
+                        .with_trust_store(trust_store.clone())
+                        .with_artifact_cache(artifact_cache.clone());
                         self.plugin_map.insert(plugin_name.into(), plugin);
                     }
                 }
@@ -246,13 +267,31 @@ impl ExternalPlugins {
     ) -> SoftwareUpdateCommand {
         let mut response = request.clone().with_status(CommandStatus::Executing);
         let mut error_messages = Vec::new();
+        let mut applied_updates = Vec::new();
 
         for software_type in request.modules_types() {
             let updates = request.updates_for(&software_type);
             let errors = if let Some(plugin) = self.by_software_type(&software_type) {
-                plugin
-                    .apply_all(updates, command_log.as_mut(), download_path)
-                    .await
+                if self.rollback {
+                    match plugin.snapshot(command_log.as_mut()).await {
+                        Ok(snapshot) => {
+                            applied_updates.push((
+                                software_type.clone(),
+                                plugin,
+                                updates.clone(),
+                                snapshot,
+                            ));
+                            plugin
+                                .apply_all(updates, command_log.as_mut(), download_path)
+                                .await
+                        }
+                        Err(err) => vec![err],
+                    }
+                } else {
+                    plugin
+                        .apply_all(updates, command_log.as_mut(), download_path)
+                        .await
+                }
             } else {
                 let error = SoftwareError::UnknownSoftwareType {
                     software_type: software_type.clone(),
@@ -272,6 +311,32 @@ impl ExternalPlugins {
                     .join(",");
                 error_messages.push(message);
                 response.add_errors(&software_type, errors);
+                if self.rollback {
+                    // There is no point to apply the remaining updates, as all will be reverted
+                    break;
+                }
+            }
+        }
+
+        if self.rollback && !error_messages.is_empty() {
+            // Revert the updates in the reverse order they have been applied
+            for (software_type, plugin, updates, snapshot) in applied_updates.into_iter().rev() {
+                info!("Rolling back the {software_type} updates");
+                match plugin
+                    .rollback(&updates, &snapshot, command_log.as_mut(), download_path)
+                    .await
+                {
+                    Ok(restore_updates) => {
+                        response.add_rolled_back(&software_type, restore_updates)
+                    }
+                    Err(error) => {
+                        if let Some(command_log) = &mut command_log {
+                            command_log.log_error(&error.to_string()).await;
+                        }
+                        error_messages.push(error.to_string());
+                        response.add_errors(&software_type, vec![error]);
+                    }
+                }
             }
         }
 
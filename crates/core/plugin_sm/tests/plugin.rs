@@ -3,6 +3,7 @@ mod tests {
     use camino::Utf8PathBuf;
     use certificate::CloudHttpConfig;
     use plugin_sm::plugin::deserialize_module_info;
+    use plugin_sm::plugin::rollback_updates;
     use plugin_sm::plugin::sm_path;
     use plugin_sm::plugin::ExternalPluginCommand;
     use std::path::Path;
@@ -10,6 +11,7 @@ mod tests {
     use std::sync::Arc;
     use tedge_api::SoftwareError;
     use tedge_api::SoftwareModule;
+    use tedge_api::SoftwareModuleUpdate;
     use tedge_config::SudoCommandBuilder;
     use tedge_config::TEdgeConfig;
     use test_case::test_case;
@@ -176,6 +178,47 @@ mod tests {
         assert_eq!(res, Ok(()));
     }
 
+    #[test]
+    fn rollback_restores_the_modules_touched_by_the_updates() {
+        let module = |name: &str, version: &str| SoftwareModule {
+            module_type: Some("test".into()),
+            name: name.into(),
+            version: Some(version.into()),
+            url: None,
+            file_path: None,
+        };
+
+        let updates = vec![
+            SoftwareModuleUpdate::install(module("upgraded", "2.0")),
+            SoftwareModuleUpdate::install(module("installed", "1.0")),
+            SoftwareModuleUpdate::remove(module("removed", "1.0")),
+            SoftwareModuleUpdate::install(module("failed", "2.0")),
+        ];
+        let before = vec![
+            module("upgraded", "1.0"),
+            module("removed", "1.0"),
+            module("failed", "1.0"),
+            module("untouched", "1.0"),
+        ];
+        let after = vec![
+            module("upgraded", "2.0"),
+            module("installed", "1.0"),
+            module("failed", "1.0"),
+            module("untouched", "1.0"),
+        ];
+
+        let restore_updates = rollback_updates(&updates, &before, &after);
+
+        assert_eq!(
+            restore_updates,
+            vec![
+                SoftwareModuleUpdate::install(module("removed", "1.0")),
+                SoftwareModuleUpdate::remove(module("installed", "1.0")),
+                SoftwareModuleUpdate::install(module("upgraded", "1.0")),
+            ]
+        );
+    }
+
     #[test_case("abc", &Some("1:2.3.4567-8~1234".to_string()), "/tmp", PathBuf::from("/tmp/abc_1%3a2.3.4567-8~1234") ; "with special character")]
     fn handle_special_characters_in_module_version(
         name: &str,
@@ -2,6 +2,7 @@ use crate::cli::certificate::c8y::create_device_csr;
 use crate::cli::certificate::c8y::read_csr_from_file;
 use crate::cli::certificate::c8y::store_device_cert;
 use crate::cli::certificate::show::ShowCertCmd;
+use crate::cli::certificate::PrivateKey;
 use crate::command::Command;
 use crate::error;
 use crate::get_webpki_error_from_reqwest;
@@ -83,7 +84,7 @@ impl DownloadCertCmd {
         if self.generate_csr {
             create_device_csr(
                 common_name.clone(),
-                self.key_path.clone(),
+                PrivateKey::File(self.key_path.clone()),
                 self.csr_path.clone(),
                 self.csr_template.clone(),
             )
@@ -3,6 +3,7 @@ use crate::cli::certificate::c8y::create_device_csr;
 use crate::cli::certificate::c8y::read_csr_from_file;
 use crate::cli::certificate::c8y::store_device_cert;
 use crate::cli::certificate::show::ShowCertCmd;
+use crate::cli::certificate::PrivateKey;
 use crate::command::Command;
 use crate::get_webpki_error_from_reqwest;
 use crate::log::MaybeFancy;
@@ -79,7 +80,7 @@ impl RenewCertCmd {
             let common_name = certificate_cn(&self.cert_path).await?;
             create_device_csr(
                 common_name,
-                self.key_path.clone(),
+                PrivateKey::File(self.key_path.clone()),
                 self.csr_path.clone(),
                 self.csr_template.clone(),
             )
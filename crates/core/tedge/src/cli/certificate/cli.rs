@@ -1,10 +1,15 @@
 use super::create::CreateCertCmd;
 use super::create_csr::CreateCsrCmd;
+use super::create_key_hsm::parse_curve;
+use super::create_key_hsm::parse_key_id;
+use super::create_key_hsm::CreateKeyHsmCmd;
 use super::remove::RemoveCertCmd;
 use super::renew::RenewCertCmd;
 use super::show::ShowCertCmd;
 use crate::certificate_is_self_signed;
 use crate::cli::certificate::c8y;
+use crate::cli::certificate::est;
+use crate::cli::certificate::PrivateKey;
 use crate::cli::common::Cloud;
 use crate::cli::common::CloudArg;
 use crate::command::BuildCommand;
@@ -20,6 +25,7 @@ use std::time::Duration;
 use tedge_config::tedge_toml::OptionalConfigError;
 use tedge_config::tedge_toml::ProfileName;
 use tedge_config::TEdgeConfig;
+use tedge_p11_server::service::KeyTypeRequest;
 
 #[derive(clap::Subcommand, Debug)]
 pub enum TEdgeCertCli {
@@ -34,6 +40,9 @@ pub enum TEdgeCertCli {
     },
 
     /// Create a certificate signing request
+    ///
+    /// When `device.cryptoki.mode` is set to `module` or `socket`,
+    /// the CSR is signed using the private key stored in the PKCS#11 token.
     CreateCsr {
         /// The device identifier to be used as the common name for the certificate
         #[clap(long = "device-id", global = true)]
@@ -47,6 +56,49 @@ pub enum TEdgeCertCli {
         cloud: Option<CloudArg>,
     },
 
+    /// Create a key pair in the PKCS#11 token configured with `device.cryptoki`
+    ///
+    /// The private key never leaves the token.
+    /// The URI of the new key is printed along its public key,
+    /// and can be used to configure the device key with `tedge config set device.key_uri <uri>`.
+    ///
+    /// A CSR can then be created for this key using `tedge cert create-csr`.
+    CreateKeyHsm {
+        /// The label (CKA_LABEL attribute) of the new key
+        #[clap(long)]
+        label: String,
+
+        /// The id (CKA_ID attribute) of the new key, as an hexadecimal string, e.g. `01ab`
+        #[clap(long)]
+        id: Option<String>,
+
+        /// The type of the key
+        #[clap(long = "type", default_value_t = KeyTypeArg::Ecdsa)]
+        key_type: KeyTypeArg,
+
+        /// The size of RSA keys, in bits
+        #[clap(long, default_value_t = 2048)]
+        bits: u32,
+
+        /// The NIST curve of ECDSA keys: 256 or 384
+        #[clap(long, default_value_t = 256)]
+        #[arg(value_parser = parse_curve)]
+        curve: u16,
+
+        /// The URI of the token where the key has to be created, e.g. `pkcs11:token=my-token`
+        ///
+        /// Default to the token selected by `device.key_uri` or `device.cryptoki.uri`.
+        #[clap(long)]
+        token: Option<String>,
+
+        /// Path where the public key will be stored, in place of the standard output
+        #[clap(long = "outfile-pubkey", value_hint = ValueHint::FilePath)]
+        pubkey_path: Option<Utf8PathBuf>,
+
+        #[clap(subcommand)]
+        cloud: Option<CloudArg>,
+    },
+
     /// Renew the device certificate
     ///
     /// The current certificate is left unchanged and a new certificate file is created,
@@ -78,6 +130,92 @@ pub enum TEdgeCertCli {
         #[clap(long = "ca", default_value_t = CA::C8y, global = true)]
         ca: CA,
 
+        /// URL of an EST server to be used to renew the certificate, in place of the `--ca`
+        ///
+        /// The device is authenticated by the EST server using its current certificate.
+        #[clap(long = "est", global = true, conflicts_with = "ca")]
+        est_url: Option<String>,
+
+        #[clap(subcommand)]
+        cloud: Option<CloudArg>,
+    },
+
+    /// Enroll the device to an EST server, requesting and downloading its first certificate
+    ///
+    /// - Fetch the CA certificates from the EST server and store them next to the device certificate
+    /// - Generate a private key and Certificate Signing Request (CSR) for the device, unless a CSR is provided
+    /// - Post this CSR to the EST server, authenticating the device with a one-time password or a bootstrap certificate
+    /// - Loop till the request is approved
+    /// - Store the certificate issued by the EST server
+    ///
+    /// The certificate can then be renewed using `tedge cert renew --est <url>`.
+    #[clap(verbatim_doc_comment)]
+    Enroll {
+        /// URL of the EST server, e.g. https://est.example.com or https://est.example.com/.well-known/est/<label>
+        #[clap(long = "est")]
+        est_url: String,
+
+        /// The device identifier to be used as the common name for the certificate
+        ///
+        /// Default to the configured device id.
+        /// You will be prompted for input if no value is provided
+        #[clap(long = "device-id", global = true)]
+        #[arg(env = "DEVICE_ID", hide_env_values = true)]
+        id: Option<String>,
+
+        #[clap(
+            short = 'p',
+            long = "one-time-password",
+            allow_hyphen_values = true,
+            global = true
+        )]
+        #[arg(
+            env = "DEVICE_ONE_TIME_PASSWORD",
+            hide_env_values = true,
+            hide_default_value = true,
+            default_value = ""
+        )]
+        /// The one-time password used to authenticate the device
+        ///
+        /// Not used when a bootstrap certificate is provided.
+        /// You will be prompted for input if neither a one-time password nor a bootstrap certificate is provided
+        one_time_password: String,
+
+        /// A certificate used to authenticate the device, in place of a one-time password
+        ///
+        /// This is typically a certificate installed by the manufacturer of the device.
+        #[clap(long = "bootstrap-cert", global = true, value_hint = ValueHint::FilePath)]
+        bootstrap_cert: Option<Utf8PathBuf>,
+
+        /// The private key of the bootstrap certificate
+        ///
+        /// Default to the device private key, be it stored in a file or in a PKCS#11 token.
+        #[clap(
+            long = "bootstrap-key",
+            global = true,
+            requires = "bootstrap_cert",
+            value_hint = ValueHint::FilePath
+        )]
+        bootstrap_key: Option<Utf8PathBuf>,
+
+        /// Path to a Certificate Signing Request (CSR) ready to be used
+        ///
+        /// If none is provided a CSR is generated using the device id and private key
+        /// configured for the given cloud profile.
+        #[clap(long = "csr-path", global = true, value_hint = ValueHint::FilePath)]
+        csr_path: Option<Utf8PathBuf>,
+
+        #[clap(long, default_value = "30s", global = true)]
+        #[arg(value_parser = humantime::parse_duration)]
+        /// Delay between two attempts, polling till the request is approved,
+        /// unless the EST server tells when to retry
+        retry_every: Duration,
+
+        #[clap(long, default_value = "10m", global = true)]
+        #[arg(value_parser = humantime::parse_duration)]
+        /// Maximum time waiting for the request to be approved
+        max_timeout: Duration,
+
         #[clap(subcommand)]
         cloud: Option<CloudArg>,
     },
@@ -130,6 +268,15 @@ pub enum TEdgeCertCli {
     Download(DownloadCertCli),
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq, strum_macros::Display)]
+pub enum KeyTypeArg {
+    #[strum(serialize = "ecdsa")]
+    Ecdsa,
+
+    #[strum(serialize = "rsa")]
+    Rsa,
+}
+
 #[derive(clap::ValueEnum, Clone, Debug, Eq, PartialEq, strum_macros::Display)]
 pub enum CA {
     #[strum(serialize = "self-signed")]
@@ -184,7 +331,7 @@ impl BuildCommand for TEdgeCertCli {
 
                 let cmd = CreateCsrCmd {
                     id: get_device_id(id, config, &cloud)?,
-                    key_path: config.device_key_path(cloud.as_ref())?.to_owned(),
+                    key: device_private_key(config, &cloud)?,
                     // Use output file instead of csr_path from tedge config if provided
                     csr_path: if let Some(output_path) = output_path {
                         output_path
@@ -198,6 +345,43 @@ impl BuildCommand for TEdgeCertCli {
                 cmd.into_boxed()
             }
 
+            TEdgeCertCli::CreateKeyHsm {
+                label,
+                id,
+                key_type,
+                bits,
+                curve,
+                token,
+                pubkey_path,
+                cloud,
+            } => {
+                let cloud: Option<Cloud> = cloud.map(<_>::try_into).transpose()?;
+                let Some(cryptoki_config) = config.device_cryptoki_config(cloud.as_ref())? else {
+                    return Err(anyhow!(
+                        "PKCS#11 is not enabled: `device.cryptoki.mode` has to be set to `module` or `socket`"
+                    )
+                    .into());
+                };
+                let id = id
+                    .as_deref()
+                    .map(parse_key_id)
+                    .transpose()
+                    .map_err(|err| anyhow!("Invalid key id: {err}"))?;
+                let key = match key_type {
+                    KeyTypeArg::Ecdsa => KeyTypeRequest::Ec { curve },
+                    KeyTypeArg::Rsa => KeyTypeRequest::Rsa { bits },
+                };
+                let cmd = CreateKeyHsmCmd {
+                    cryptoki_config,
+                    token_uri: token,
+                    label,
+                    id,
+                    key,
+                    pubkey_path,
+                };
+                cmd.into_boxed()
+            }
+
             TEdgeCertCli::Show {
                 cloud,
                 cert_path,
@@ -287,10 +471,82 @@ impl BuildCommand for TEdgeCertCli {
                 cmd.into_boxed()
             }
 
+            TEdgeCertCli::Enroll {
+                est_url,
+                id,
+                one_time_password,
+                bootstrap_cert,
+                bootstrap_key,
+                csr_path,
+                retry_every,
+                max_timeout,
+                cloud,
+            } => {
+                let cloud: Option<Cloud> = cloud.map(<_>::try_into).transpose()?;
+                let cert_path = config.device_cert_path(cloud.as_ref())?.to_owned();
+                let key = device_private_key(config, &cloud)?;
+                let device_id = id
+                    .or_else(|| config.device_id(cloud.as_ref()).ok().map(String::from))
+                    .unwrap_or_default();
+
+                let (csr_path, generate_csr) = match csr_path {
+                    None => (config.device_csr_path(cloud.as_ref())?.to_owned(), true),
+                    Some(csr_path) => (csr_path, false),
+                };
+                let bootstrap = bootstrap_cert.map(|cert_path| est::ClientCertificate {
+                    cert_path,
+                    key: bootstrap_key.map_or(key.clone(), PrivateKey::File),
+                });
+
+                let cmd = est::EnrollCertCmd {
+                    est_url,
+                    device_id,
+                    one_time_password,
+                    bootstrap,
+                    http_config: config.cloud_root_certs()?,
+                    ca_path: est::ca_certificates_path(&cert_path),
+                    cert_path,
+                    key,
+                    csr_path,
+                    generate_csr,
+                    retry_every,
+                    max_timeout,
+                    csr_template,
+                };
+                cmd.into_boxed()
+            }
+
+            TEdgeCertCli::Renew {
+                csr_path,
+                cloud,
+                est_url: Some(est_url),
+                ..
+            } => {
+                let cloud: Option<Cloud> = cloud.map(<_>::try_into).transpose()?;
+                let cert_path = config.device_cert_path(cloud.as_ref())?.to_owned();
+                let (csr_path, generate_csr) = match csr_path {
+                    None => (config.device_csr_path(cloud.as_ref())?.to_owned(), true),
+                    Some(csr_path) => (csr_path, false),
+                };
+                let cmd = est::RenewCertCmd {
+                    est_url,
+                    http_config: config.cloud_root_certs()?,
+                    new_cert_path: CertificateShift::new_certificate_path(&cert_path),
+                    ca_path: est::ca_certificates_path(&cert_path),
+                    key: device_private_key(config, &cloud)?,
+                    cert_path,
+                    csr_path,
+                    generate_csr,
+                    csr_template,
+                };
+                cmd.into_boxed()
+            }
+
             TEdgeCertCli::Renew {
                 csr_path,
                 cloud,
                 ca,
+                est_url: None,
             } => {
                 let cloud: Option<Cloud> = cloud.map(<_>::try_into).transpose()?;
                 let cert_path = config.device_cert_path(cloud.as_ref())?.to_owned();
@@ -395,6 +651,19 @@ pub enum UploadCertCli {
     },
 }
 
+/// Returns the device private key, be it stored in a file or in a PKCS#11 token
+fn device_private_key(
+    config: &TEdgeConfig,
+    cloud: &Option<Cloud>,
+) -> Result<PrivateKey, anyhow::Error> {
+    match config.device_cryptoki_config(cloud.as_ref())? {
+        Some(cryptoki_config) => Ok(PrivateKey::Cryptoki(cryptoki_config)),
+        None => Ok(PrivateKey::File(
+            config.device_key_path(cloud.as_ref())?.to_owned(),
+        )),
+    }
+}
+
 /// Returns the device ID from the config if no ID is provided by CLI
 fn get_device_id(
     id: Option<String>,
@@ -1,4 +1,5 @@
 use super::error::CertError;
+use super::PrivateKey;
 use crate::command::Command;
 use crate::log::MaybeFancy;
 use crate::override_public_key;
@@ -15,8 +16,10 @@ pub struct CreateCsrCmd {
     /// The device identifier
     pub id: String,
 
-    /// The path where the device private key will be stored
-    pub key_path: Utf8PathBuf,
+    /// The device private key, stored in a file, or in a PKCS#11 token
+    ///
+    /// A new key is created if no key file exists.
+    pub key: PrivateKey,
 
     /// The path where the device CSR will be stored
     pub csr_path: Utf8PathBuf,
@@ -46,16 +49,22 @@ impl CreateCsrCmd {
     pub async fn create_certificate_signing_request(&self) -> Result<(), CertError> {
         let id = &self.id;
         let csr_path = &self.csr_path;
-        let key_path = &self.key_path;
-
-        let previous_key = reuse_private_key(key_path)
-            .await
-            .map_err(|e| CertError::IoError(e).key_context(key_path.clone()))?;
+        let (previous_key, key_path) = match &self.key {
+            PrivateKey::File(key_path) => {
+                let previous_key = reuse_private_key(key_path)
+                    .await
+                    .map_err(|e| CertError::IoError(e).key_context(key_path.clone()))?;
+                (previous_key, Some(key_path))
+            }
+            PrivateKey::Cryptoki(cryptoki_config) => {
+                (KeyKind::Cryptoki(cryptoki_config.clone()), None)
+            }
+        };
 
         let cert =
             KeyCertPair::new_certificate_sign_request(&self.csr_template, id, &previous_key)?;
 
-        if let KeyKind::New = previous_key {
+        if let (KeyKind::New, Some(key_path)) = (previous_key, key_path) {
             persist_new_private_key(
                 key_path,
                 cert.private_key_pem_string()?,
@@ -89,7 +98,7 @@ mod tests {
 
         let cmd = CreateCsrCmd {
             id: id.to_string(),
-            key_path: key_path.clone(),
+            key: PrivateKey::File(key_path.clone()),
             csr_path: csr_path.clone(),
             user: "mosquitto".to_string(),
             group: "mosquitto".to_string(),
@@ -132,7 +141,7 @@ mod tests {
 
         let cmd = CreateCsrCmd {
             id: id.to_string(),
-            key_path: key_path.clone(),
+            key: PrivateKey::File(key_path.clone()),
             csr_path: csr_path.clone(),
             user: "mosquitto".to_string(),
             group: "mosquitto".to_string(),
@@ -0,0 +1,148 @@
+use crate::command::Command;
+use crate::log::MaybeFancy;
+use crate::override_public_key;
+use anyhow::anyhow;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use certificate::parse_root_certificate::CryptokiConfig;
+use tedge_config::TEdgeConfig;
+use tedge_p11_server::pkcs11::uri::Pkcs11Uri;
+use tedge_p11_server::service::CreateKeyRequest;
+use tedge_p11_server::service::KeyTypeRequest;
+use tedge_p11_server::Pkcs11Token;
+
+/// Create a key pair in a PKCS#11 token, the private key never leaving the token
+pub struct CreateKeyHsmCmd {
+    /// How to access the token
+    pub cryptoki_config: CryptokiConfig,
+
+    /// The URI of the token where the key has to be created, if not the configured one
+    pub token_uri: Option<String>,
+
+    /// The label of the new key
+    pub label: String,
+
+    /// The id of the new key, if any
+    pub id: Option<Vec<u8>>,
+
+    /// The type of the new key
+    pub key: KeyTypeRequest,
+
+    /// The path where the public key will be stored, if any
+    pub pubkey_path: Option<Utf8PathBuf>,
+}
+
+#[async_trait::async_trait]
+impl Command for CreateKeyHsmCmd {
+    fn description(&self) -> String {
+        format!("create the key pair {} in the PKCS#11 token", self.label)
+    }
+
+    async fn execute(&self, _: TEdgeConfig) -> Result<(), MaybeFancy<anyhow::Error>> {
+        let (uri, public_key) = self.create_key().await?;
+        let public_key_pem = pem::encode(&pem::Pem::new("PUBLIC KEY", public_key));
+
+        eprintln!("Key pair created successfully");
+        eprintln!("    => to use this key as the device key:");
+        eprintln!("       tedge config set device.key_uri '{uri}'\n");
+        println!("{uri}");
+        match &self.pubkey_path {
+            Some(pubkey_path) => override_public_key(pubkey_path, public_key_pem)
+                .await
+                .with_context(|| format!("Fail to store the public key {pubkey_path}"))?,
+            None => print!("{public_key_pem}"),
+        }
+        Ok(())
+    }
+}
+
+impl CreateKeyHsmCmd {
+    /// Create the key, returning its URI and its DER-encoded public key
+    async fn create_key(&self) -> Result<(String, Vec<u8>), anyhow::Error> {
+        let cryptoki_config = self.cryptoki_config.clone();
+        let request = CreateKeyRequest {
+            uri: self.token_uri.clone(),
+            key: self.key,
+            label: self.label.clone(),
+            id: self.id.clone(),
+        };
+
+        tokio::task::spawn_blocking(move || {
+            let token = Pkcs11Token::new(cryptoki_config)?;
+
+            // Labels are not required to be unique by PKCS#11, but they are used to select the keys
+            let objects = token.list_objects(request.uri.clone())?;
+            if objects
+                .iter()
+                .any(|object| has_label(object, &request.label))
+            {
+                return Err(anyhow!(
+                    "An object labelled '{}' already exists in the token",
+                    request.label
+                ));
+            }
+
+            let response = token.create_key(request)?;
+            Ok((response.uri, response.public_key))
+        })
+        .await?
+    }
+}
+
+fn has_label(object_uri: &str, label: &str) -> bool {
+    Pkcs11Uri::parse(object_uri).is_ok_and(|uri| uri.object.as_deref() == Some(label))
+}
+
+/// Parse a key id given as an hexadecimal string, e.g. `01ab`
+pub fn parse_key_id(id: &str) -> Result<Vec<u8>, String> {
+    if id.len() % 2 != 0 {
+        return Err("expected an even number of hexadecimal digits".to_string());
+    }
+    (0..id.len())
+        .step_by(2)
+        .map(|i| {
+            id.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| format!("invalid hexadecimal byte at position {i}"))
+        })
+        .collect()
+}
+
+/// Parse the size of a NIST curve supported to create ECDSA keys
+///
+/// P-521 keys are rejected, as such keys cannot be used to sign a CSR.
+pub fn parse_curve(curve: &str) -> Result<u16, String> {
+    match curve.parse() {
+        Ok(curve @ (256 | 384)) => Ok(curve),
+        _ => Err("expected one of the supported curves: 256 or 384".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_ids_are_parsed_from_hexadecimal_strings() {
+        assert_eq!(parse_key_id("01ab"), Ok(vec![0x01, 0xab]));
+        assert_eq!(parse_key_id(""), Ok(vec![]));
+        assert!(parse_key_id("1ab").is_err());
+        assert!(parse_key_id("zz").is_err());
+    }
+
+    #[test]
+    fn only_curves_supported_to_sign_csrs_are_accepted() {
+        assert_eq!(parse_curve("256"), Ok(256));
+        assert_eq!(parse_curve("384"), Ok(384));
+        assert!(parse_curve("521").is_err());
+        assert!(parse_curve("p256").is_err());
+    }
+
+    #[test]
+    fn objects_are_matched_by_label() {
+        let uri = "pkcs11:token=tedge;object=my%20key;id=%01;type=private";
+        assert!(has_label(uri, "my key"));
+        assert!(!has_label(uri, "my"));
+        assert!(!has_label("pkcs11:token=tedge;type=cert", "my key"));
+    }
+}
@@ -95,7 +95,7 @@ pub enum CertError {
     FileError(#[from] FileError),
 
     #[error(transparent)]
-    IllFormedPk7Cert(#[from] crate::cli::certificate::c8y::IllFormedPk7Cert),
+    IllFormedPk7Cert(#[from] certificate::est::IllFormedPk7Cert),
 
     #[error("Root certificate path {0} does not exist")]
     RootCertificatePathDoesNotExist(String),
@@ -0,0 +1,379 @@
+use super::ClientCertificate;
+use super::EnrollResponse;
+use super::EstAuth;
+use super::EstClient;
+use super::EstError;
+use crate::cli::certificate::c8y::create_device_csr;
+use crate::cli::certificate::c8y::read_csr_from_file;
+use crate::cli::certificate::show::ShowCertCmd;
+use crate::cli::certificate::PrivateKey;
+use crate::command::Command;
+use crate::error;
+use crate::log::MaybeFancy;
+use crate::override_public_key;
+use crate::warning;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use camino::Utf8PathBuf;
+use certificate::CloudHttpConfig;
+use certificate::CsrTemplate;
+use std::io::Write;
+use std::time::Duration;
+use tedge_config::TEdgeConfig;
+
+/// Command to enroll the device to an EST server, requesting and downloading its first certificate
+pub struct EnrollCertCmd {
+    /// The EST server
+    pub est_url: String,
+
+    /// The device identifier to be used as the common name for the certificate
+    pub device_id: String,
+
+    /// The one-time password used to authenticate the device, unless a bootstrap certificate is used
+    pub one_time_password: String,
+
+    /// A certificate used to authenticate the device, in place of a one-time password
+    pub bootstrap: Option<ClientCertificate>,
+
+    /// Root certificates used to authenticate the EST server
+    pub http_config: CloudHttpConfig,
+
+    /// The path where the device certificate will be stored
+    pub cert_path: Utf8PathBuf,
+
+    /// The device private key
+    pub key: PrivateKey,
+
+    /// The path where the device CSR file will be stored
+    pub csr_path: Utf8PathBuf,
+
+    /// Tell if the CSR has to be generated or is ready to be used
+    pub generate_csr: bool,
+
+    /// The path where the CA certificates returned by the EST server will be stored
+    pub ca_path: Utf8PathBuf,
+
+    /// Delay between two attempts, polling till the request is approved
+    pub retry_every: Duration,
+
+    /// Maximum time waiting for the request to be approved
+    pub max_timeout: Duration,
+
+    /// CSR template
+    pub csr_template: CsrTemplate,
+}
+
+#[async_trait::async_trait]
+impl Command for EnrollCertCmd {
+    fn description(&self) -> String {
+        format!(
+            "Enroll the device {} to the EST server {}",
+            self.device_id, self.est_url
+        )
+    }
+
+    async fn execute(&self, _: TEdgeConfig) -> Result<(), MaybeFancy<Error>> {
+        self.enroll_device().await?;
+        eprintln!("Certificate enrolled successfully");
+        eprintln!("    => the device can now be connected\n");
+        ShowCertCmd::show(&self.cert_path).await?;
+        Ok(())
+    }
+}
+
+impl EnrollCertCmd {
+    async fn enroll_device(&self) -> Result<(), Error> {
+        let (common_name, auth) = self.get_registration_data().await?;
+        let est = EstClient::new(&self.est_url, &self.http_config, &self.ca_path, auth)?;
+
+        let ca_certificates = est.ca_certificates().await?;
+        override_public_key(&self.ca_path, ca_certificates)
+            .await
+            .with_context(|| format!("Fail to store the EST CA certificates {}", self.ca_path))?;
+
+        if self.generate_csr {
+            create_device_csr(
+                common_name.clone(),
+                self.key.clone(),
+                self.csr_path.clone(),
+                self.csr_template.clone(),
+            )
+            .await
+            .with_context(|| format!("Fail to create the device CSR {}", self.csr_path))?;
+        }
+        let csr = read_csr_from_file(&self.csr_path).await?;
+
+        let started = std::time::Instant::now();
+        loop {
+            let retry_after = match est.simple_enroll(&csr).await {
+                Ok(EnrollResponse::Issued(cert)) => {
+                    override_public_key(&self.cert_path, cert).await?;
+                    return Ok(());
+                }
+                Ok(EnrollResponse::Pending { retry_after }) => {
+                    warning!(
+                        "The certificate request for {common_name} is waiting for approval on {}",
+                        self.est_url
+                    );
+                    retry_after
+                }
+                Err(err) => {
+                    // Only retry on connection and server errors, a rejected request being rejected again
+                    let transient = err
+                        .downcast_ref::<EstError>()
+                        .is_some_and(EstError::is_transient);
+                    if !transient {
+                        return Err(err);
+                    }
+                    error!("{err:#}");
+                    None
+                }
+            };
+            if started.elapsed() > self.max_timeout {
+                return Err(anyhow!(
+                    "Maximum timeout elapsed. No certificate has been enrolled"
+                ));
+            }
+            let delay = retry_after.unwrap_or(self.retry_every);
+            warning!("Will retry in {} seconds", delay.as_secs());
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Prompt the user for the device id and the one-time password
+    ///
+    /// - unless already set on the command line or using env variables,
+    /// - the one-time password being not used when the device owns a bootstrap certificate.
+    async fn get_registration_data(&self) -> Result<(String, EstAuth), std::io::Error> {
+        let self_device_id = self.device_id.clone();
+        let self_one_time_password = self.one_time_password.clone();
+        let bootstrap = self.bootstrap.clone();
+        tokio::task::spawn_blocking(move || {
+            let device_id = if self_device_id.is_empty() {
+                print!("Enter device id: ");
+                std::io::stdout().flush()?;
+                let mut input = String::new();
+                std::io::stdin().read_line(&mut input)?;
+                input.trim_end_matches(['\n', '\r']).to_string()
+            } else {
+                self_device_id
+            };
+
+            let auth = match bootstrap {
+                Some(certificate) => EstAuth::Certificate(certificate),
+                None => {
+                    // Read the one-time password from /dev/tty
+                    let password = if self_one_time_password.is_empty() {
+                        rpassword::read_password_from_tty(Some("Enter one-time password: "))?
+                    } else {
+                        self_one_time_password
+                    };
+                    EstAuth::OneTimePassword {
+                        username: device_id.clone(),
+                        password,
+                    }
+                }
+            };
+
+            Ok((device_id, auth))
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::certificate::est::tests::PK7_CERTIFICATE;
+    use crate::cli::certificate::test_helpers::*;
+    use certificate::KeyCertPair;
+    use certificate::KeyKind;
+    use tempfile::tempdir;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn enrollment_stores_the_ca_certificates_the_private_key_and_the_certificate() {
+        let dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/.well-known/est/cacerts")
+            .with_body(PK7_CERTIFICATE)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/.well-known/est/simpleenroll")
+            .with_body(PK7_CERTIFICATE)
+            .create_async()
+            .await;
+
+        let cmd = enroll_cmd(&dir, &server);
+
+        cmd.enroll_device().await.unwrap();
+
+        assert_eq!(parse_x509_file(&cmd.ca_path).label, "CERTIFICATE");
+        assert_eq!(parse_x509_file(&cmd.cert_path).label, "CERTIFICATE");
+        assert_eq!(
+            parse_x509_file(dir.path().join("my-device-key.pem")).label,
+            "PRIVATE KEY"
+        );
+    }
+
+    #[tokio::test]
+    async fn the_csr_is_signed_by_the_token_storing_the_private_key() {
+        let dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/.well-known/est/cacerts")
+            .with_body(PK7_CERTIFICATE)
+            .create_async()
+            .await;
+        let enroll = server
+            .mock("POST", "/.well-known/est/simpleenroll")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let cmd = EnrollCertCmd {
+            est_url: server.url(),
+            device_id: "my-device".to_string(),
+            one_time_password: String::new(),
+            bootstrap: Some(bootstrap_certificate(&dir)),
+            http_config: CloudHttpConfig::test_value(),
+            cert_path: temp_file_path(&dir, "my-device-cert.pem"),
+            key: PrivateKey::Cryptoki(
+                certificate::parse_root_certificate::CryptokiConfig::SocketService {
+                    socket_path: temp_file_path(&dir, "tedge-p11-server.sock"),
+                    uri: None,
+                },
+            ),
+            csr_path: temp_file_path(&dir, "my-device.csr"),
+            generate_csr: true,
+            ca_path: temp_file_path(&dir, "est-ca.pem"),
+            retry_every: Duration::from_secs(1),
+            max_timeout: Duration::from_secs(1),
+            csr_template: CsrTemplate::default(),
+        };
+
+        // No tedge-p11-server is listening on the socket: the CSR cannot be signed
+        let err = cmd.enroll_device().await.unwrap_err();
+
+        assert!(format!("{err:#}").contains("tedge-p11-server"));
+        enroll.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn enrollment_fails_fast_when_the_request_is_rejected() {
+        let dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/.well-known/est/cacerts")
+            .with_body(PK7_CERTIFICATE)
+            .create_async()
+            .await;
+        let enroll = server
+            .mock("POST", "/.well-known/est/simpleenroll")
+            .with_status(403)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let cmd = EnrollCertCmd {
+            retry_every: Duration::from_millis(10),
+            max_timeout: Duration::from_secs(60),
+            ..enroll_cmd(&dir, &server)
+        };
+
+        let err = cmd.enroll_device().await.unwrap_err();
+
+        assert!(err.to_string().contains("403"));
+        enroll.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn enrollment_is_retried_on_server_errors() {
+        let dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/.well-known/est/cacerts")
+            .with_body(PK7_CERTIFICATE)
+            .create_async()
+            .await;
+        let enroll = server
+            .mock("POST", "/.well-known/est/simpleenroll")
+            .with_status(503)
+            .expect_at_least(2)
+            .create_async()
+            .await;
+
+        let cmd = EnrollCertCmd {
+            retry_every: Duration::from_millis(10),
+            max_timeout: Duration::from_millis(200),
+            ..enroll_cmd(&dir, &server)
+        };
+
+        let err = cmd.enroll_device().await.unwrap_err();
+
+        assert!(err.to_string().contains("Maximum timeout elapsed"));
+        enroll.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn one_time_passwords_are_not_sent_over_plain_http() {
+        let dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let cacerts = server
+            .mock("GET", "/.well-known/est/cacerts")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let cmd = EnrollCertCmd {
+            one_time_password: "secret".to_string(),
+            bootstrap: None,
+            ..enroll_cmd(&dir, &server)
+        };
+
+        let err = cmd.enroll_device().await.unwrap_err();
+
+        assert!(err.to_string().contains("https"));
+        cacerts.assert_async().await;
+    }
+
+    /// An enrollment command using a bootstrap certificate,
+    /// as a one-time password cannot be sent to the mock server which serves plain http
+    fn enroll_cmd(dir: &TempDir, server: &mockito::ServerGuard) -> EnrollCertCmd {
+        EnrollCertCmd {
+            est_url: server.url(),
+            device_id: "my-device".to_string(),
+            one_time_password: String::new(),
+            bootstrap: Some(bootstrap_certificate(dir)),
+            http_config: CloudHttpConfig::test_value(),
+            cert_path: temp_file_path(dir, "my-device-cert.pem"),
+            key: PrivateKey::File(temp_file_path(dir, "my-device-key.pem")),
+            csr_path: temp_file_path(dir, "my-device.csr"),
+            generate_csr: true,
+            ca_path: temp_file_path(dir, "est-ca.pem"),
+            retry_every: Duration::from_secs(1),
+            max_timeout: Duration::from_secs(1),
+            csr_template: CsrTemplate::default(),
+        }
+    }
+
+    fn bootstrap_certificate(dir: &TempDir) -> ClientCertificate {
+        let cert = KeyCertPair::new_selfsigned_certificate(
+            &CsrTemplate::default(),
+            "my-bootstrap-device",
+            &KeyKind::New,
+        )
+        .unwrap();
+        let cert_path = temp_file_path(dir, "bootstrap-cert.pem");
+        let key_path = temp_file_path(dir, "bootstrap-key.pem");
+        std::fs::write(&cert_path, cert.certificate_pem_string().unwrap()).unwrap();
+        std::fs::write(&key_path, cert.private_key_pem_string().unwrap().as_bytes()).unwrap();
+        ClientCertificate {
+            cert_path,
+            key: PrivateKey::File(key_path),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use super::ClientCertificate;
+use super::EnrollResponse;
+use super::EstAuth;
+use super::EstClient;
+use crate::certificate_cn;
+use crate::cli::certificate::c8y::create_device_csr;
+use crate::cli::certificate::c8y::read_csr_from_file;
+use crate::cli::certificate::show::ShowCertCmd;
+use crate::cli::certificate::PrivateKey;
+use crate::command::Command;
+use crate::log::MaybeFancy;
+use crate::override_public_key;
+use anyhow::anyhow;
+use anyhow::Error;
+use camino::Utf8PathBuf;
+use certificate::CloudHttpConfig;
+use certificate::CsrTemplate;
+use tedge_config::TEdgeConfig;
+
+/// Command to renew the device certificate using an EST server
+///
+/// The device authenticates itself using its current certificate.
+pub struct RenewCertCmd {
+    /// The EST server from where the device got its current certificate
+    pub est_url: String,
+
+    /// Root certificates used to authenticate the EST server
+    pub http_config: CloudHttpConfig,
+
+    /// The path of the certificate to be renewed
+    pub cert_path: Utf8PathBuf,
+
+    /// The path where the new certificate will be stored
+    pub new_cert_path: Utf8PathBuf,
+
+    /// The private key to re-use
+    pub key: PrivateKey,
+
+    /// The path where the device CSR file will be stored
+    pub csr_path: Utf8PathBuf,
+
+    /// Tell if the CSR has to be generated or is ready to be used
+    pub generate_csr: bool,
+
+    /// The path where the CA certificates returned by the EST server are stored
+    pub ca_path: Utf8PathBuf,
+
+    /// CSR template
+    pub csr_template: CsrTemplate,
+}
+
+#[async_trait::async_trait]
+impl Command for RenewCertCmd {
+    fn description(&self) -> String {
+        format!(
+            "renew the device certificate via the EST server {}",
+            self.est_url
+        )
+    }
+
+    async fn execute(&self, _: TEdgeConfig) -> Result<(), MaybeFancy<Error>> {
+        self.renew_device_certificate().await?;
+        eprintln!("Certificate renewed successfully");
+        eprintln!("    For an un-interrupted service:");
+        eprintln!("    => the device has to be reconnected to the cloud\n");
+        ShowCertCmd::show(&self.new_cert_path).await?;
+        Ok(())
+    }
+}
+
+impl RenewCertCmd {
+    async fn renew_device_certificate(&self) -> Result<(), Error> {
+        if self.generate_csr {
+            let common_name = certificate_cn(&self.cert_path).await?;
+            create_device_csr(
+                common_name,
+                self.key.clone(),
+                self.csr_path.clone(),
+                self.csr_template.clone(),
+            )
+            .await?;
+        }
+        let csr = read_csr_from_file(&self.csr_path).await?;
+
+        let auth = EstAuth::Certificate(ClientCertificate {
+            cert_path: self.cert_path.clone(),
+            key: self.key.clone(),
+        });
+        let est = EstClient::new(&self.est_url, &self.http_config, &self.ca_path, auth)?;
+        match est.simple_reenroll(&csr).await? {
+            EnrollResponse::Issued(cert) => {
+                override_public_key(&self.new_cert_path, cert).await?;
+                Ok(())
+            }
+            EnrollResponse::Pending { .. } => Err(anyhow!(
+                "The certificate request is waiting for approval by {}: retry later",
+                self.est_url
+            )),
+        }
+    }
+}
@@ -1,21 +1,34 @@
 pub use self::cli::TEdgeCertCli;
 use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use certificate::parse_root_certificate::CryptokiConfig;
 use tokio::io::AsyncReadExt;
 
 mod c8y;
 mod cli;
 mod create;
 mod create_csr;
+mod create_key_hsm;
 mod error;
+mod est;
 mod remove;
 mod renew;
-mod shift;
 mod show;
 
 pub use self::cli::*;
 pub use self::create::*;
 pub use self::error::*;
-pub use self::shift::*;
+pub use certificate::CertificateShift;
+
+/// Where the private key of a certificate is stored
+#[derive(Debug, Clone)]
+pub enum PrivateKey {
+    /// A PEM file
+    File(Utf8PathBuf),
+
+    /// A PKCS#11 token, accessed directly or through `tedge-p11-server`
+    Cryptoki(CryptokiConfig),
+}
 
 pub(crate) async fn read_cert_to_string(path: impl AsRef<Utf8Path>) -> Result<String, CertError> {
     let mut file = tokio::fs::File::open(path.as_ref()).await.map_err(|err| {
@@ -528,6 +528,13 @@ fn validate_config(config: &TEdgeConfig, cloud: &MaybeBorrowedCloud<'_>) -> anyh
                 &profiles,
             )?;
             disallow_matching_configurations(config, ReadableKey::AwsBridgeTopicPrefix, &profiles)?;
+            if config.metrics.enable {
+                disallow_matching_configurations(
+                    config,
+                    ReadableKey::AwsMetricsBindPort,
+                    &profiles,
+                )?;
+            }
         }
         #[cfg(feature = "azure")]
         MaybeBorrowedCloud::Azure(_) => {
@@ -544,6 +551,13 @@ fn validate_config(config: &TEdgeConfig, cloud: &MaybeBorrowedCloud<'_>) -> anyh
                 &profiles,
             )?;
             disallow_matching_configurations(config, ReadableKey::AzBridgeTopicPrefix, &profiles)?;
+            if config.metrics.enable {
+                disallow_matching_configurations(
+                    config,
+                    ReadableKey::AzMetricsBindPort,
+                    &profiles,
+                )?;
+            }
         }
         #[cfg(feature = "c8y")]
         MaybeBorrowedCloud::C8y(_) => {
@@ -561,6 +575,13 @@ fn validate_config(config: &TEdgeConfig, cloud: &MaybeBorrowedCloud<'_>) -> anyh
             )?;
             disallow_matching_configurations(config, ReadableKey::C8yBridgeTopicPrefix, &profiles)?;
             disallow_matching_configurations(config, ReadableKey::C8yProxyBindPort, &profiles)?;
+            if config.metrics.enable {
+                disallow_matching_configurations(
+                    config,
+                    ReadableKey::C8yMetricsBindPort,
+                    &profiles,
+                )?;
+            }
         }
     }
     Ok(())
@@ -1300,6 +1321,24 @@ Each cloud profile requires either a unique URL or unique device ID, so it corre
             assert!(err.to_string().contains("c8y.profiles.new.proxy.bind.port"));
         }
 
+        #[tokio::test]
+        async fn rejects_conflicting_metrics_ports_when_metrics_are_enabled() {
+            let cloud = Cloud::C8y(None);
+            let config = TEdgeConfig::load_toml_str(
+                "metrics.enable = true
+            c8y.url = \"latest.example.com\"
+            c8y.profiles.new.url = \"example.com\"
+            c8y.profiles.new.bridge.topic_prefix = \"c8y-new\"
+            c8y.profiles.new.proxy.bind.port = 8002",
+            );
+
+            let err = validate_config(&config, &cloud).unwrap_err();
+            assert!(err.to_string().contains("c8y.metrics.bind.port"));
+            assert!(err
+                .to_string()
+                .contains("c8y.profiles.new.metrics.bind.port"));
+        }
+
         #[tokio::test]
         async fn ignores_conflicting_configs_for_other_clouds() {
             let cloud = Cloud::Azure(None);
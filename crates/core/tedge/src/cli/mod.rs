@@ -29,6 +29,7 @@ mod mqtt;
 mod reconnect;
 mod refresh_bridges;
 mod upload;
+mod workflow;
 
 #[derive(clap::Parser, Debug)]
 #[clap(
@@ -138,6 +139,10 @@ pub enum TEdgeOpt {
     #[clap(subcommand)]
     Http(http::TEdgeHttpCli),
 
+    /// Check and simulate operation workflows
+    #[clap(subcommand)]
+    Workflow(workflow::TEdgeWorkflowCli),
+
     /// Run thin-edge services and plugins
     Run(ComponentOpt),
 
@@ -208,6 +213,7 @@ impl BuildCommand for TEdgeOpt {
             TEdgeOpt::Mqtt(opt) => opt.build_command(config),
             TEdgeOpt::Http(opt) => opt.build_command(config),
             TEdgeOpt::Reconnect(opt) => opt.build_command(config),
+            TEdgeOpt::Workflow(opt) => opt.build_command(config),
             TEdgeOpt::Run(_) => {
                 // This method has to be kept in sync with tedge::redirect_if_multicall()
                 panic!("tedge mapper|agent|write commands are launched as multicall")
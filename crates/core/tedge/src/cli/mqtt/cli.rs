@@ -1,8 +1,12 @@
 use crate::cli::mqtt::publish::MqttPublishCommand;
+use crate::cli::mqtt::record::MqttRecordCommand;
+use crate::cli::mqtt::replay::parse_speed;
+use crate::cli::mqtt::replay::MqttReplayCommand;
 use crate::cli::mqtt::subscribe::MqttSubscribeCommand;
 use crate::cli::mqtt::subscribe::SimpleTopicFilter;
 use crate::command::BuildCommand;
 use crate::command::Command;
+use camino::Utf8PathBuf;
 use clap_complete::ArgValueCandidates;
 use clap_complete::CompletionCandidate;
 use mqtt_channel::Topic;
@@ -12,6 +16,8 @@ use tedge_config::TEdgeConfig;
 
 const PUB_CLIENT_PREFIX: &str = "tedge-pub";
 const SUB_CLIENT_PREFIX: &str = "tedge-sub";
+const RECORD_CLIENT_PREFIX: &str = "tedge-record";
+const REPLAY_CLIENT_PREFIX: &str = "tedge-replay";
 
 #[derive(clap::Subcommand, Debug)]
 pub enum TEdgeMqttCli {
@@ -56,6 +62,44 @@ pub enum TEdgeMqttCli {
         #[clap(long)]
         retained_only: bool,
     },
+
+    /// Record the messages received on MQTT topics into a JSON lines file.
+    ///
+    /// Each line is the JSON representation of a message, i.e. its topic, payload, qos, retain flag
+    /// and reception timestamp, the recording being suitable for `tedge mqtt replay`.
+    Record {
+        /// Topic to subscribe to, possibly repeated
+        #[clap(long, short = 't', required = true)]
+        #[arg(value_parser = SimpleTopicFilter::new)]
+        topic: Vec<SimpleTopicFilter>,
+        /// Path of the file where the messages are recorded
+        #[clap(long = "out", short = 'o')]
+        output: Utf8PathBuf,
+        /// QoS level (0, 1, 2)
+        #[clap(short, long, default_value = "0")]
+        #[arg(value_parser = parse_qos)]
+        #[arg(add = ArgValueCandidates::new(qos_completions))]
+        qos: QoS,
+        /// Disconnect and exit after the specified timeout (e.g., 60s, 1h)
+        #[clap(long, short = 'W')]
+        duration: Option<SecondsOrHumanTime>,
+        /// Disconnect and exit after recording the specified number of messages
+        #[clap(long, short = 'C')]
+        count: Option<u32>,
+    },
+
+    /// Publish the messages recorded by `tedge mqtt record`, with the recorded delays.
+    Replay {
+        /// Path of the recording
+        input: Utf8PathBuf,
+        /// Replay speed factor (e.g. 2x to replay twice as fast as recorded)
+        #[clap(long, default_value = "1x")]
+        #[arg(value_parser = parse_speed)]
+        speed: f64,
+        /// Replay the recording over and over, till interrupted
+        #[clap(long = "loop")]
+        repeat: bool,
+    },
 }
 
 impl BuildCommand for TEdgeMqttCli {
@@ -104,6 +148,42 @@ impl BuildCommand for TEdgeMqttCli {
                     retained_only,
                 }
                 .into_boxed(),
+                TEdgeMqttCli::Record {
+                    topic,
+                    output,
+                    qos,
+                    duration,
+                    count,
+                } => MqttRecordCommand {
+                    host: config.mqtt.client.host.clone(),
+                    port: config.mqtt.client.port.into(),
+                    topics: topic,
+                    qos,
+                    output,
+                    client_id: format!("{}-{}", RECORD_CLIENT_PREFIX, std::process::id()),
+                    ca_file: auth_config.ca_file,
+                    ca_dir: auth_config.ca_dir,
+                    client_auth_config: auth_config.client,
+                    duration: duration.map(|v| v.duration()),
+                    count,
+                }
+                .into_boxed(),
+                TEdgeMqttCli::Replay {
+                    input,
+                    speed,
+                    repeat,
+                } => MqttReplayCommand {
+                    host: config.mqtt.client.host.clone(),
+                    port: config.mqtt.client.port.into(),
+                    input,
+                    speed,
+                    repeat,
+                    client_id: format!("{}-{}", REPLAY_CLIENT_PREFIX, std::process::id()),
+                    ca_file: auth_config.ca_file,
+                    ca_dir: auth_config.ca_dir,
+                    client_auth_config: auth_config.client,
+                }
+                .into_boxed(),
             }
         };
 
@@ -1,7 +1,42 @@
 pub use self::cli::TEdgeMqttCli;
+use camino::Utf8PathBuf;
+use tedge_config::tedge_toml::MqttAuthClientConfig;
 
 mod cli;
 mod publish;
+mod record;
+mod replay;
 mod subscribe;
 
 const MAX_PACKET_SIZE: usize = 268435455; // 256 MB
+const DEFAULT_QUEUE_CAPACITY: usize = 10;
+
+/// The MQTT connection settings shared by all the `tedge mqtt` sub-commands
+fn mqtt_config(
+    host: &str,
+    port: u16,
+    client_id: &str,
+    ca_file: Option<&Utf8PathBuf>,
+    ca_dir: Option<&Utf8PathBuf>,
+    client_auth_config: Option<&MqttAuthClientConfig>,
+) -> Result<mqtt_channel::Config, anyhow::Error> {
+    let mut config = mqtt_channel::Config::default()
+        .with_host(host)
+        .with_port(port)
+        .with_session_name(client_id)
+        .with_clean_session(true)
+        .with_max_packet_size(MAX_PACKET_SIZE)
+        .with_queue_capacity(DEFAULT_QUEUE_CAPACITY);
+
+    if let Some(ca_file) = ca_file {
+        config.with_cafile(ca_file)?;
+    }
+    if let Some(ca_dir) = ca_dir {
+        config.with_cadir(ca_dir)?;
+    }
+    if let Some(client_auth) = client_auth_config {
+        config.with_client_auth(&client_auth.cert_file, &client_auth.key_file)?;
+    }
+
+    Ok(config)
+}
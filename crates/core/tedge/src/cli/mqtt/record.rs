@@ -0,0 +1,191 @@
+use crate::cli::mqtt::subscribe::SimpleTopicFilter;
+use crate::command::Command;
+use crate::log::MaybeFancy;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use mqtt_channel::Connection;
+use mqtt_channel::QoS;
+use mqtt_channel::StreamExt;
+use mqtt_channel::TopicFilter;
+use std::time::Duration;
+use tedge_api::store::message_log::LogEntry;
+use tedge_api::store::message_log::MessageLogWriter;
+use tedge_config::tedge_toml::MqttAuthClientConfig;
+use tedge_config::TEdgeConfig;
+use time::OffsetDateTime;
+use tracing::info;
+
+/// Record the messages received on a set of topics into a JSON lines file
+pub struct MqttRecordCommand {
+    pub host: String,
+    pub port: u16,
+    pub topics: Vec<SimpleTopicFilter>,
+    pub qos: QoS,
+    pub output: Utf8PathBuf,
+    pub client_id: String,
+    pub ca_file: Option<Utf8PathBuf>,
+    pub ca_dir: Option<Utf8PathBuf>,
+    pub client_auth_config: Option<MqttAuthClientConfig>,
+    pub duration: Option<Duration>,
+    pub count: Option<u32>,
+}
+
+#[async_trait::async_trait]
+impl Command for MqttRecordCommand {
+    fn description(&self) -> String {
+        format!(
+            "record the messages received on {:?} into {}.",
+            self.topics
+                .iter()
+                .map(SimpleTopicFilter::pattern)
+                .collect::<Vec<_>>(),
+            self.output
+        )
+    }
+
+    async fn execute(&self, _: TEdgeConfig) -> Result<(), MaybeFancy<anyhow::Error>> {
+        Ok(record(self).await?)
+    }
+}
+
+async fn record(cmd: &MqttRecordCommand) -> Result<(), anyhow::Error> {
+    let mut recording = MessageLogWriter::open_truncated(&cmd.output)
+        .with_context(|| format!("Fail to create {}", cmd.output))?;
+    let mqtt = connect(cmd).await?;
+    record_messages(cmd, mqtt, &mut recording).await
+}
+
+async fn connect(cmd: &MqttRecordCommand) -> Result<Connection, anyhow::Error> {
+    let mut topics = TopicFilter::empty();
+    for topic in cmd.topics.iter() {
+        topics.add(topic.pattern())?;
+    }
+    let topics = topics.with_qos(cmd.qos);
+
+    let config = super::mqtt_config(
+        &cmd.host,
+        cmd.port,
+        &cmd.client_id,
+        cmd.ca_file.as_ref(),
+        cmd.ca_dir.as_ref(),
+        cmd.client_auth_config.as_ref(),
+    )?
+    .with_subscriptions(topics);
+
+    Ok(Connection::new(&config).await?)
+}
+
+async fn record_messages(
+    cmd: &MqttRecordCommand,
+    mut mqtt: Connection,
+    recording: &mut MessageLogWriter,
+) -> Result<(), anyhow::Error> {
+    let mut signals = tedge_utils::signals::TermSignals::new(cmd.duration);
+    let mut n_messages = 0;
+    loop {
+        let message = match signals.might_interrupt(mqtt.received.next()).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(signal) => {
+                info!(target: "MQTT", "{signal:?}");
+                break;
+            }
+        };
+
+        let entry = LogEntry {
+            message,
+            timestamp: Some(OffsetDateTime::now_utc()),
+        };
+        recording
+            .append_entry(&entry)
+            .with_context(|| format!("Fail to write into {}", cmd.output))?;
+        n_messages += 1;
+        if matches!(cmd.count, Some(count) if count > 0 && n_messages >= count) {
+            break;
+        }
+    }
+    eprintln!("Recorded {n_messages} message/s into {}", cmd.output);
+
+    mqtt.published.close_channel();
+    mqtt.pub_done.await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::mqtt::replay::read_entries;
+    use crate::cli::mqtt::replay::replay;
+    use crate::cli::mqtt::replay::MqttReplayCommand;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn recorded_messages_are_replayed() {
+        let broker = mqtt_tests::test_mqtt_broker();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let recording = Utf8PathBuf::try_from(temp_dir.path().join("recording.jsonl")).unwrap();
+
+        // Record messages as published on the broker
+        let record_cmd = MqttRecordCommand {
+            host: "localhost".to_string(),
+            port: broker.port,
+            topics: vec![SimpleTopicFilter::new("test/record/+").unwrap()],
+            qos: QoS::AtLeastOnce,
+            output: recording.clone(),
+            client_id: "test-record".to_string(),
+            ca_file: None,
+            ca_dir: None,
+            client_auth_config: None,
+            duration: Some(TEST_TIMEOUT),
+            count: Some(3),
+        };
+        let mut writer = MessageLogWriter::open_truncated(&recording).unwrap();
+        let mqtt = connect(&record_cmd).await.unwrap();
+        let recorder =
+            tokio::spawn(async move { record_messages(&record_cmd, mqtt, &mut writer).await });
+
+        broker.publish("test/record/a", "1").await.unwrap();
+        broker.publish("test/record/b", "2").await.unwrap();
+        broker.publish("test/record/a", "3").await.unwrap();
+        recorder.await.unwrap().unwrap();
+
+        let entries = read_entries(&recording).unwrap();
+        let recorded: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.message.topic.name.as_str(),
+                    entry.message.payload_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            recorded,
+            vec![
+                ("test/record/a", "1"),
+                ("test/record/b", "2"),
+                ("test/record/a", "3")
+            ]
+        );
+        assert!(entries.iter().all(|entry| entry.timestamp.is_some()));
+
+        // Replay the recording and check the same messages are published again
+        let mut replayed = broker.messages_published_on("test/record/+").await;
+        let replay_cmd = MqttReplayCommand {
+            host: "localhost".to_string(),
+            port: broker.port,
+            input: recording,
+            speed: 10.0,
+            repeat: false,
+            client_id: "test-replay".to_string(),
+            ca_file: None,
+            ca_dir: None,
+            client_auth_config: None,
+        };
+        replay(&replay_cmd).await.unwrap();
+
+        mqtt_tests::assert_received_all_expected(&mut replayed, TEST_TIMEOUT, &["1", "2", "3"])
+            .await;
+    }
+}
@@ -0,0 +1,189 @@
+use crate::command::Command;
+use crate::log::MaybeFancy;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use mqtt_channel::PubChannel;
+use std::time::Duration;
+use tedge_api::store::message_log::LogEntry;
+use tedge_api::store::message_log::MessageLogReader;
+use tedge_config::tedge_toml::MqttAuthClientConfig;
+use tedge_config::TEdgeConfig;
+use tokio::time::Instant;
+use tracing::info;
+
+/// Publish the messages of a recording, respecting the delays between messages
+pub struct MqttReplayCommand {
+    pub host: String,
+    pub port: u16,
+    pub input: Utf8PathBuf,
+    /// Replay speed factor, 2.0 meaning twice as fast as recorded
+    pub speed: f64,
+    /// Replay the recording over and over, till interrupted
+    pub repeat: bool,
+    pub client_id: String,
+    pub ca_file: Option<Utf8PathBuf>,
+    pub ca_dir: Option<Utf8PathBuf>,
+    pub client_auth_config: Option<MqttAuthClientConfig>,
+}
+
+#[async_trait::async_trait]
+impl Command for MqttReplayCommand {
+    fn description(&self) -> String {
+        format!(
+            "replay the messages recorded in {} at speed {}x.",
+            self.input, self.speed
+        )
+    }
+
+    async fn execute(&self, _: TEdgeConfig) -> Result<(), MaybeFancy<anyhow::Error>> {
+        Ok(replay(self).await?)
+    }
+}
+
+pub(super) async fn replay(cmd: &MqttReplayCommand) -> Result<(), anyhow::Error> {
+    let entries = read_entries(&cmd.input)?;
+    let offsets = replay_offsets(&entries, cmd.speed);
+
+    let config = super::mqtt_config(
+        &cmd.host,
+        cmd.port,
+        &cmd.client_id,
+        cmd.ca_file.as_ref(),
+        cmd.ca_dir.as_ref(),
+        cmd.client_auth_config.as_ref(),
+    )?;
+
+    let mut mqtt = mqtt_channel::Connection::new(&config).await?;
+    let mut signals = tedge_utils::signals::TermSignals::new(None);
+    let mut n_messages = 0;
+
+    'replay: loop {
+        let start = Instant::now();
+        for (entry, offset) in entries.iter().zip(offsets.iter()) {
+            let publish = async {
+                tokio::time::sleep_until(start + *offset).await;
+                mqtt.published.publish(entry.message.clone()).await
+            };
+            match signals.might_interrupt(publish).await {
+                Ok(Ok(())) => n_messages += 1,
+                Ok(err) => err?,
+                Err(signal) => {
+                    info!(target: "MQTT", "{signal:?}");
+                    break 'replay;
+                }
+            }
+        }
+        if !cmd.repeat || entries.is_empty() {
+            break;
+        }
+    }
+    eprintln!("Replayed {n_messages} message/s from {}", cmd.input);
+
+    mqtt.close().await;
+    Ok(())
+}
+
+pub(super) fn read_entries(path: &Utf8PathBuf) -> Result<Vec<LogEntry>, anyhow::Error> {
+    let mut reader =
+        MessageLogReader::open(path).with_context(|| format!("Fail to open {path}"))?;
+    let mut entries = vec![];
+    while let Some(entry) = reader
+        .next_entry()
+        .with_context(|| format!("Fail to read {path}"))?
+    {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// The delay of each entry from the start of the replay, as recorded and divided by the speed factor
+///
+/// Entries without timestamp are published right after the previous entry.
+fn replay_offsets(entries: &[LogEntry], speed: f64) -> Vec<Duration> {
+    let first_timestamp = entries.iter().find_map(|entry| entry.timestamp);
+    let mut offset = Duration::ZERO;
+    entries
+        .iter()
+        .map(|entry| {
+            if let (Some(first), Some(timestamp)) = (first_timestamp, entry.timestamp) {
+                let elapsed = Duration::try_from(timestamp - first).unwrap_or_default();
+                // Entries are published in order, even if the clock went backward while recording
+                offset = offset.max(elapsed.div_f64(speed));
+            }
+            offset
+        })
+        .collect()
+}
+
+/// Parse a replay speed factor, given as `2x`, `0.5x` or simply `2`
+pub fn parse_speed(src: &str) -> Result<f64, String> {
+    let factor = src.strip_suffix('x').unwrap_or(src);
+    match factor.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        _ => Err("expected a positive speed factor, e.g. 2x or 0.5x".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mqtt_channel::MqttMessage;
+    use mqtt_channel::Topic;
+    use time::macros::datetime;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn speed_factors_are_parsed_with_or_without_suffix() {
+        assert_eq!(parse_speed("2x"), Ok(2.0));
+        assert_eq!(parse_speed("0.5x"), Ok(0.5));
+        assert_eq!(parse_speed("3"), Ok(3.0));
+        assert!(parse_speed("0x").is_err());
+        assert!(parse_speed("-1").is_err());
+        assert!(parse_speed("fast").is_err());
+    }
+
+    #[test]
+    fn messages_are_replayed_with_the_recorded_delays_divided_by_the_speed() {
+        let entries = vec![
+            entry(Some(datetime!(2026-10-18 10:00:00 UTC))),
+            entry(Some(datetime!(2026-10-18 10:00:01 UTC))),
+            entry(None),
+            entry(Some(datetime!(2026-10-18 10:00:04 UTC))),
+        ];
+
+        assert_eq!(
+            replay_offsets(&entries, 2.0),
+            vec![
+                Duration::ZERO,
+                Duration::from_millis(500),
+                Duration::from_millis(500),
+                Duration::from_secs(2)
+            ]
+        );
+    }
+
+    #[test]
+    fn messages_are_replayed_in_order_even_if_the_clock_went_backward() {
+        let entries = vec![
+            entry(Some(datetime!(2026-10-18 10:00:00 UTC))),
+            entry(Some(datetime!(2026-10-18 10:00:02 UTC))),
+            entry(Some(datetime!(2026-10-18 10:00:01 UTC))),
+        ];
+
+        assert_eq!(
+            replay_offsets(&entries, 1.0),
+            vec![
+                Duration::ZERO,
+                Duration::from_secs(2),
+                Duration::from_secs(2)
+            ]
+        );
+    }
+
+    fn entry(timestamp: Option<OffsetDateTime>) -> LogEntry {
+        LogEntry {
+            message: MqttMessage::new(&Topic::new("te/device/main///m/").unwrap(), "{}"),
+            timestamp,
+        }
+    }
+}
@@ -11,9 +11,6 @@ use tokio::io::AsyncWriteExt;
 use tracing::error;
 use tracing::info;
 
-const DEFAULT_QUEUE_CAPACITY: usize = 10;
-use super::MAX_PACKET_SIZE;
-
 pub struct MqttSubscribeCommand {
     pub host: String,
     pub port: u16,
@@ -50,24 +47,15 @@ impl Command for MqttSubscribeCommand {
 async fn subscribe(cmd: &MqttSubscribeCommand) -> Result<(), anyhow::Error> {
     let topic = TopicFilter::new(cmd.topic.pattern())?.with_qos(cmd.qos);
 
-    let mut config = mqtt_channel::Config::default()
-        .with_host(cmd.host.clone())
-        .with_port(cmd.port)
-        .with_session_name(cmd.client_id.clone())
-        .with_clean_session(true)
-        .with_subscriptions(topic)
-        .with_max_packet_size(MAX_PACKET_SIZE)
-        .with_queue_capacity(DEFAULT_QUEUE_CAPACITY);
-
-    if let Some(ca_file) = &cmd.ca_file {
-        config.with_cafile(ca_file)?;
-    }
-    if let Some(ca_dir) = &cmd.ca_dir {
-        config.with_cadir(ca_dir)?;
-    }
-    if let Some(client_auth) = cmd.client_auth_config.as_ref() {
-        config.with_client_auth(&client_auth.cert_file, &client_auth.key_file)?;
-    }
+    let config = super::mqtt_config(
+        &cmd.host,
+        cmd.port,
+        &cmd.client_id,
+        cmd.ca_file.as_ref(),
+        cmd.ca_dir.as_ref(),
+        cmd.client_auth_config.as_ref(),
+    )?
+    .with_subscriptions(topic);
 
     let mut mqtt = mqtt_channel::Connection::new(&config).await?;
     let mut signals = tedge_utils::signals::TermSignals::new(cmd.duration);
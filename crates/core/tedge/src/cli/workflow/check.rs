@@ -0,0 +1,48 @@
+use crate::command::Command;
+use crate::log::MaybeFancy;
+use anyhow::anyhow;
+use anyhow::Context;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use serde_json::Value;
+use tedge_api::workflow::OperationWorkflow;
+use tedge_config::TEdgeConfig;
+
+pub struct CheckWorkflowCommand {
+    pub file: Utf8PathBuf,
+    /// Example of command payload, used to check the `${.payload...}` expressions
+    pub payload: Option<Value>,
+}
+
+#[async_trait::async_trait]
+impl Command for CheckWorkflowCommand {
+    fn description(&self) -> String {
+        format!("check the workflow definition {}", self.file)
+    }
+
+    async fn execute(&self, _: TEdgeConfig) -> Result<(), MaybeFancy<anyhow::Error>> {
+        let workflow = read_workflow(&self.file).await?;
+        let warnings = match &self.payload {
+            None => workflow.check(),
+            Some(payload) => workflow.check_with_payload(payload),
+        };
+        if warnings.is_empty() {
+            println!("{}: no issues found", self.file);
+            return Ok(());
+        }
+
+        for warning in warnings.iter() {
+            eprintln!("{}: {warning}", self.file);
+        }
+        Err(anyhow!("{} issue(s) found in {}", warnings.len(), self.file).into())
+    }
+}
+
+/// Read and parse a workflow definition
+pub async fn read_workflow(file: &Utf8Path) -> anyhow::Result<OperationWorkflow> {
+    let content = tokio::fs::read_to_string(file)
+        .await
+        .with_context(|| format!("reading {file}"))?;
+    let workflow = toml::from_str(&content).with_context(|| format!("parsing {file}"))?;
+    Ok(workflow)
+}
@@ -0,0 +1,140 @@
+use crate::cli::workflow::check::CheckWorkflowCommand;
+use crate::cli::workflow::simulate::SimulateWorkflowCommand;
+use crate::cli::workflow::simulate::Stubs;
+use crate::command::BuildCommand;
+use crate::command::Command;
+use crate::ConfigError;
+use camino::Utf8PathBuf;
+use tedge_config::TEdgeConfig;
+
+#[derive(clap::Subcommand, Debug)]
+pub enum TEdgeWorkflowCli {
+    /// Check an operation workflow definition
+    ///
+    /// Reports parsing errors as well as likely mistakes:
+    /// target states with no definition, unreachable states
+    /// and `${...}` expressions that don't refer to a command property.
+    ///
+    /// Given an example of command payload,
+    /// also reports `${.payload...}` expressions that refer to a field
+    /// which is neither in that payload nor set by the workflow.
+    ///
+    /// Examples:
+    ///   tedge workflow check /etc/tedge/operations/firmware_update.toml
+    ///   tedge workflow check firmware_update.toml --payload '{"url":"http://example.com/fw"}'
+    #[clap(verbatim_doc_comment)]
+    Check {
+        /// Path to the workflow definition
+        file: Utf8PathBuf,
+
+        /// Example of command payload
+        #[clap(long)]
+        #[arg(value_parser = parse_json)]
+        payload: Option<serde_json::Value>,
+    },
+
+    /// Step a command through an operation workflow, with no side effect
+    ///
+    /// Scripts are not executed and sub-operations not triggered.
+    /// Their outcomes are stubbed, being successful by default.
+    /// Failed actions are retried as per their retry policy, with no actual delay,
+    /// and commands in the `scheduled` state proceed as if their schedule was reached.
+    ///
+    /// Examples:
+    ///   # Simulate a command where the `download` script fails with exit code 2
+    ///   tedge workflow simulate firmware_update.toml --payload '{"url":"http://example.com/fw"}' --exit-code download=2
+    ///
+    ///   # Simulate a command where the `download` script succeeds on its third attempt
+    ///   tedge workflow simulate firmware_update.toml --exit-code download=1,1,0
+    ///
+    ///   # Simulate a command where a script outputs the next state
+    ///   tedge workflow simulate firmware_update.toml --stdout check='{"status":"install"}'
+    #[clap(verbatim_doc_comment)]
+    Simulate {
+        /// Path to the workflow definition
+        file: Utf8PathBuf,
+
+        /// Payload of the command init state
+        #[clap(long, default_value = "{}")]
+        #[arg(value_parser = parse_json)]
+        payload: serde_json::Value,
+
+        /// Exit code returned by the script of a state, given as `<state>=<code>` (0 by default)
+        ///
+        /// When the script is retried, the exit codes of the successive attempts
+        /// can be given as `<state>=<code>,<code>,...`, the last one being repeated.
+        #[clap(long)]
+        #[arg(value_parser = parse_exit_codes)]
+        exit_code: Vec<(String, Vec<u8>)>,
+
+        /// JSON output of the script of a state, given as `<state>=<json>`
+        #[clap(long)]
+        #[arg(value_parser = parse_stdout)]
+        stdout: Vec<(String, serde_json::Value)>,
+
+        /// Make fail the sub-operation or builtin action awaited in a state
+        #[clap(long)]
+        fail: Vec<String>,
+
+        /// Maximum number of steps, to stop simulating a command looping over the same states
+        #[clap(long, default_value_t = 100)]
+        max_steps: usize,
+    },
+}
+
+impl BuildCommand for TEdgeWorkflowCli {
+    fn build_command(self, _: &TEdgeConfig) -> Result<Box<dyn Command>, ConfigError> {
+        let cmd = match self {
+            TEdgeWorkflowCli::Check { file, payload } => {
+                CheckWorkflowCommand { file, payload }.into_boxed()
+            }
+            TEdgeWorkflowCli::Simulate {
+                file,
+                payload,
+                exit_code,
+                stdout,
+                fail,
+                max_steps,
+            } => SimulateWorkflowCommand {
+                file,
+                payload,
+                stubs: Stubs {
+                    exit_codes: exit_code.into_iter().collect(),
+                    stdouts: stdout.into_iter().collect(),
+                    failures: fail.into_iter().collect(),
+                },
+                max_steps,
+            }
+            .into_boxed(),
+        };
+        Ok(cmd)
+    }
+}
+
+fn parse_json(input: &str) -> Result<serde_json::Value, String> {
+    serde_json::from_str(input).map_err(|err| format!("Invalid JSON: {err}"))
+}
+
+fn parse_key_value(input: &str) -> Result<(String, &str), String> {
+    input
+        .split_once('=')
+        .map(|(state, value)| (state.to_string(), value))
+        .ok_or_else(|| format!("Expected `<state>=<value>`, found: {input}"))
+}
+
+fn parse_exit_codes(input: &str) -> Result<(String, Vec<u8>), String> {
+    let (state, codes) = parse_key_value(input)?;
+    let codes = codes
+        .split(',')
+        .map(|code| {
+            code.parse()
+                .map_err(|_| format!("Invalid exit code for {state}: {code}"))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((state, codes))
+}
+
+fn parse_stdout(input: &str) -> Result<(String, serde_json::Value), String> {
+    let (state, stdout) = parse_key_value(input)?;
+    Ok((state, parse_json(stdout)?))
+}
@@ -0,0 +1,5 @@
+mod check;
+mod cli;
+mod simulate;
+
+pub use cli::TEdgeWorkflowCli;
@@ -0,0 +1,591 @@
+use crate::cli::workflow::check::read_workflow;
+use crate::command::Command;
+use crate::log::MaybeFancy;
+use anyhow::anyhow;
+use camino::Utf8PathBuf;
+use mqtt_channel::Topic;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::process::Output;
+use tedge_api::workflow::CommandSchedule;
+use tedge_api::workflow::GenericCommandState;
+use tedge_api::workflow::GenericStateUpdate;
+use tedge_api::workflow::OperationAction;
+use tedge_api::workflow::OperationWorkflow;
+use tedge_api::workflow::RetryPolicy;
+use tedge_api::workflow::StateName;
+use tedge_api::workflow::WorkflowExecutionError;
+use tedge_config::TEdgeConfig;
+use time::OffsetDateTime;
+
+pub struct SimulateWorkflowCommand {
+    pub file: Utf8PathBuf,
+    pub payload: Value,
+    pub stubs: Stubs,
+    pub max_steps: usize,
+}
+
+/// The stubbed outcomes of the actions, per state
+#[derive(Debug, Default)]
+pub struct Stubs {
+    /// Exit codes of the successive attempts to run the script of a state
+    ///
+    /// The last exit code is used for any further attempt, 0 being used if none is given.
+    pub exit_codes: HashMap<StateName, Vec<u8>>,
+
+    /// JSON output of the script of a state
+    pub stdouts: HashMap<StateName, Value>,
+
+    /// States where the awaited sub-operation or builtin action fails
+    pub failures: HashSet<StateName>,
+}
+
+#[async_trait::async_trait]
+impl Command for SimulateWorkflowCommand {
+    fn description(&self) -> String {
+        format!("simulate a command using the workflow {}", self.file)
+    }
+
+    async fn execute(&self, _: TEdgeConfig) -> Result<(), MaybeFancy<anyhow::Error>> {
+        let workflow = read_workflow(&self.file).await?;
+        let init_state = init_state(&workflow, self.payload.clone());
+
+        let now = OffsetDateTime::now_utc();
+        let simulation = simulate(&workflow, init_state, &self.stubs, self.max_steps, now);
+        for step in simulation.steps.iter() {
+            match &step.action {
+                Ok(action) => println!("[{}] {}", step.state.status, action),
+                Err(err) => println!("[{}] error: {}", step.state.status, err),
+            }
+            if let Some(note) = &step.note {
+                println!("  => {note}");
+            }
+        }
+        if let Some(last_state) = simulation.steps.last().map(|step| &step.state) {
+            if simulation.end == SimulationEnd::StepLimitReached {
+                eprintln!(
+                    "Simulation stopped after {} steps, in state {}",
+                    self.max_steps, last_state.status
+                );
+            }
+            println!("{}", serde_json::to_string_pretty(&last_state.payload)?);
+            if simulation.end == SimulationEnd::Failed {
+                return Err(anyhow!("Simulation aborted in state {}", last_state.status).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn init_state(workflow: &OperationWorkflow, mut payload: Value) -> GenericCommandState {
+    if let Some(payload) = payload.as_object_mut() {
+        payload.insert("status".to_string(), "init".into());
+    }
+    let topic = Topic::new_unchecked(&format!(
+        "te/device/main///cmd/{}/simulation",
+        workflow.operation.name()
+    ));
+    GenericCommandState::new(topic, "init".to_string(), payload)
+}
+
+/// A state a command goes through, along with the action taken on that state
+struct Step {
+    state: GenericCommandState,
+    /// The action to take on that state, or why none can be determined, ending the simulation
+    action: Result<OperationAction, WorkflowExecutionError>,
+    /// What happened beyond the plain action: a retry, a scheduling delay
+    note: Option<String>,
+}
+
+/// The steps of a simulated command, along with the reason why the simulation ended
+struct Simulation {
+    steps: Vec<Step>,
+    end: SimulationEnd,
+}
+
+#[derive(Debug, PartialEq)]
+enum SimulationEnd {
+    /// The command reached a terminal state and has been cleared
+    Cleared,
+
+    /// No action can be determined for the last state
+    Failed,
+
+    /// The command is still in progress after the maximum number of steps
+    StepLimitReached,
+}
+
+/// Step a command through a workflow, till the command reaches a terminal state
+///
+/// Return the sequence of states the command goes through along with the action taken on each.
+/// If no action can be determined for a state, the error is recorded as the final step.
+///
+/// As done by the agent, failed actions are retried as per their retry policy
+/// and the schedule of a command is checked when in the `scheduled` state.
+/// However, the simulation doesn't wait: delays are only reported along the steps.
+fn simulate(
+    workflow: &OperationWorkflow,
+    mut state: GenericCommandState,
+    stubs: &Stubs,
+    max_steps: usize,
+    now: OffsetDateTime,
+) -> Simulation {
+    let mut steps = Vec::new();
+    for _ in 0..max_steps {
+        let action = match workflow.get_action(&state) {
+            Ok(action) => action,
+            Err(err) => {
+                steps.push(Step {
+                    state,
+                    action: Err(err),
+                    note: None,
+                });
+                return Simulation {
+                    steps,
+                    end: SimulationEnd::Failed,
+                };
+            }
+        };
+        let mut note = None;
+
+        if state.is_scheduled() {
+            match CommandSchedule::from_payload(&state.payload) {
+                Ok(schedule) => {
+                    if let Some(delay) = schedule.and_then(|schedule| schedule.delay(now)) {
+                        note = Some(format!("postponed by {}s as scheduled", delay.as_secs()));
+                    }
+                }
+                Err(err) => {
+                    let next_state = state
+                        .clone()
+                        .update(GenericStateUpdate::failed(err.to_string()));
+                    note = Some(format!("cannot be scheduled: {err}"));
+                    steps.push(Step {
+                        state,
+                        action: Ok(action),
+                        note,
+                    });
+                    state = next_state;
+                    continue;
+                }
+            }
+        }
+
+        let next_state = match action.clone() {
+            OperationAction::Clear => {
+                steps.push(Step {
+                    state,
+                    action: Ok(action),
+                    note,
+                });
+                return Simulation {
+                    steps,
+                    end: SimulationEnd::Cleared,
+                };
+            }
+            OperationAction::MoveTo(update) => state.clone().move_to(update),
+            OperationAction::Script(script, handlers) => {
+                let output = Ok(stubs.script_output(&state));
+                let script_failed = handlers.is_failure(&output);
+                let retry = handlers.retry().cloned();
+                let new_state =
+                    state
+                        .clone()
+                        .update_with_script_output(script.command, output, handlers);
+                match retry {
+                    Some(retry) if script_failed => {
+                        retry_or_fail(new_state, &state.status, &retry, &mut note)
+                    }
+                    Some(_) => new_state.clear_retry_attempts(&state.status),
+                    None => new_state,
+                }
+            }
+            OperationAction::BgScript(_, handlers)
+            | OperationAction::Operation(_, _, _, handlers)
+            | OperationAction::BuiltInOperation(_, handlers)
+            | OperationAction::ParallelIterate(_, _, _, handlers) => {
+                state.clone().update(handlers.on_exec)
+            }
+            OperationAction::BuiltIn(exec_handlers, _) if !state.is_executing() => {
+                state.clone().update(exec_handlers.on_exec)
+            }
+            OperationAction::BuiltIn(_, handlers)
+            | OperationAction::AwaitingAgentRestart(handlers) => {
+                if stubs.failures.contains(&state.status) {
+                    state.clone().update(handlers.on_error)
+                } else {
+                    state.clone().update(handlers.on_success)
+                }
+            }
+            OperationAction::AwaitOperationCompletion(handlers, _) => {
+                let retry = workflow.sub_operation_retry_policy(&state.status);
+                if stubs.failures.contains(&state.status) {
+                    let new_state = state.clone().update(handlers.on_error);
+                    match retry {
+                        Some((retried_state, retry)) => {
+                            retry_or_fail(new_state, retried_state, retry, &mut note)
+                        }
+                        None => new_state,
+                    }
+                } else {
+                    let new_state = state.clone().update(handlers.on_success);
+                    match retry {
+                        Some((retried_state, _)) => new_state.clear_retry_attempts(retried_state),
+                        None => new_state,
+                    }
+                }
+            }
+            OperationAction::Iterate(json_path, handlers) => {
+                let on_error = handlers.on_error.clone();
+                OperationAction::process_iterate(state.clone(), &json_path, handlers)
+                    .unwrap_or_else(|_| state.clone().update(on_error))
+            }
+            OperationAction::Switch(handlers) => {
+                let update = handlers.state_update(&state);
+                state.clone().update(update)
+            }
+        };
+        steps.push(Step {
+            state,
+            action: Ok(action),
+            note,
+        });
+        state = next_state;
+    }
+    Simulation {
+        steps,
+        end: SimulationEnd::StepLimitReached,
+    }
+}
+
+/// Move a failed command back to the retried state, unless all the attempts have been exhausted
+///
+/// The delay before the new attempt is not awaited but reported in the step note.
+fn retry_or_fail(
+    failed_state: GenericCommandState,
+    retried_state: &str,
+    retry: &RetryPolicy,
+    note: &mut Option<String>,
+) -> GenericCommandState {
+    let (new_state, delay) = failed_state.retry_or_fail(retried_state, retry);
+    *note = Some(match delay {
+        None => format!(
+            "{retried_state} failed after {} attempts",
+            retry.max_attempts
+        ),
+        Some(delay) => format!(
+            "retrying {retried_state} in {}s (attempt {}/{})",
+            delay.as_secs(),
+            new_state.retry_attempts(retried_state) + 1,
+            retry.max_attempts
+        ),
+    });
+    new_state
+}
+
+impl Stubs {
+    fn script_output(&self, state: &GenericCommandState) -> Output {
+        let attempt = state.retry_attempts(&state.status) as usize;
+        let code = self
+            .exit_codes
+            .get(&state.status)
+            .and_then(|codes| codes.get(attempt).or(codes.last()))
+            .copied()
+            .unwrap_or(0);
+        let stdout = match self.stdouts.get(&state.status) {
+            None => String::new(),
+            Some(json) => format!(":::begin-tedge:::\n{json}\n:::end-tedge:::\n"),
+        };
+        Output {
+            status: ExitStatus::from_raw((code as i32) << 8),
+            stdout: stdout.into_bytes(),
+            stderr: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use time::macros::datetime;
+
+    const WORKFLOW: &str = r#"
+operation = "firmware_update"
+
+[init]
+action = "proceed"
+on_success = "download"
+
+[download]
+script = "/usr/bin/download.sh ${.payload.url}"
+on_success = "install"
+on_exit.2 = { status = "failed", reason = "not found" }
+
+[install]
+operation = "install_firmware"
+on_exec = "awaiting_install"
+
+[awaiting_install]
+action = "await-operation-completion"
+on_success = "successful"
+on_error = "failed"
+"#;
+
+    fn simulated_states(stubs: Stubs) -> Vec<String> {
+        let workflow: OperationWorkflow = toml::from_str(WORKFLOW).unwrap();
+        let init = init_state(&workflow, json!({"url": "http://example.com/fw"}));
+        simulate(
+            &workflow,
+            init,
+            &stubs,
+            100,
+            datetime!(2024-06-01 12:00 UTC),
+        )
+        .steps
+        .into_iter()
+        .map(|step| step.state.status)
+        .collect()
+    }
+
+    fn simulate_firmware_update(stubs: Stubs, max_steps: usize) -> Simulation {
+        let workflow: OperationWorkflow = toml::from_str(WORKFLOW).unwrap();
+        let init = init_state(&workflow, json!({"url": "http://example.com/fw"}));
+        simulate(
+            &workflow,
+            init,
+            &stubs,
+            max_steps,
+            datetime!(2024-06-01 12:00 UTC),
+        )
+    }
+
+    const RETRIED_WORKFLOW: &str = r#"
+operation = "firmware_update"
+
+[init]
+action = "proceed"
+on_success = "scheduled"
+
+[scheduled]
+action = "proceed"
+on_success = "download"
+
+[download]
+script = "/usr/bin/download.sh ${.payload.url}"
+retry = { max_attempts = 3, backoff = { initial_second = 5, factor = 2 } }
+on_success = "install"
+on_error = "failed"
+
+[install]
+operation = "install_firmware"
+retry = { max_attempts = 2, backoff = 60, on_exhausted = "gave_up" }
+on_exec = "awaiting_install"
+
+[awaiting_install]
+action = "await-operation-completion"
+on_success = "successful"
+on_error = "failed"
+
+[gave_up]
+action = "proceed"
+on_success = "failed"
+"#;
+
+    fn simulated_steps(payload: Value, stubs: Stubs) -> Vec<(String, Option<String>)> {
+        let workflow: OperationWorkflow = toml::from_str(RETRIED_WORKFLOW).unwrap();
+        let init = init_state(&workflow, payload);
+        simulate(
+            &workflow,
+            init,
+            &stubs,
+            100,
+            datetime!(2024-06-01 12:00 UTC),
+        )
+        .steps
+        .into_iter()
+        .map(|step| (step.state.status, step.note))
+        .collect()
+    }
+
+    fn step(status: &str, note: Option<&str>) -> (String, Option<String>) {
+        (status.to_string(), note.map(str::to_string))
+    }
+
+    #[test]
+    fn simulate_successful_command() {
+        assert_eq!(
+            simulated_states(Stubs::default()),
+            vec![
+                "init",
+                "download",
+                "install",
+                "awaiting_install",
+                "successful"
+            ]
+        );
+    }
+
+    #[test]
+    fn simulate_failing_script() {
+        let stubs = Stubs {
+            exit_codes: HashMap::from([("download".to_string(), vec![2])]),
+            ..Stubs::default()
+        };
+        assert_eq!(simulated_states(stubs), vec!["init", "download", "failed"]);
+    }
+
+    #[test]
+    fn simulate_failing_sub_operation() {
+        let stubs = Stubs {
+            failures: HashSet::from(["awaiting_install".to_string()]),
+            ..Stubs::default()
+        };
+        assert_eq!(
+            simulated_states(stubs),
+            vec!["init", "download", "install", "awaiting_install", "failed"]
+        );
+    }
+
+    #[test]
+    fn simulate_script_succeeding_on_retry() {
+        let stubs = Stubs {
+            exit_codes: HashMap::from([("download".to_string(), vec![1, 0])]),
+            ..Stubs::default()
+        };
+        assert_eq!(
+            simulated_steps(json!({}), stubs),
+            vec![
+                step("init", None),
+                step("scheduled", None),
+                step("download", Some("retrying download in 5s (attempt 2/3)")),
+                step("download", None),
+                step("install", None),
+                step("awaiting_install", None),
+                step("successful", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn simulate_script_failing_after_all_attempts() {
+        let stubs = Stubs {
+            exit_codes: HashMap::from([("download".to_string(), vec![1])]),
+            ..Stubs::default()
+        };
+        assert_eq!(
+            simulated_steps(json!({}), stubs),
+            vec![
+                step("init", None),
+                step("scheduled", None),
+                step("download", Some("retrying download in 5s (attempt 2/3)")),
+                step("download", Some("retrying download in 10s (attempt 3/3)")),
+                step("download", Some("download failed after 3 attempts")),
+                step("failed", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn simulate_sub_operation_failing_after_all_attempts() {
+        let stubs = Stubs {
+            failures: HashSet::from(["awaiting_install".to_string()]),
+            ..Stubs::default()
+        };
+        assert_eq!(
+            simulated_steps(json!({}), stubs),
+            vec![
+                step("init", None),
+                step("scheduled", None),
+                step("download", None),
+                step("install", None),
+                step(
+                    "awaiting_install",
+                    Some("retrying install in 60s (attempt 2/2)")
+                ),
+                step("install", None),
+                step("awaiting_install", Some("install failed after 2 attempts")),
+                step("gave_up", None),
+                step("failed", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn simulate_scheduled_command() {
+        let payload = json!({"scheduledAt": "2024-06-01T14:00:00Z"});
+        assert_eq!(
+            simulated_steps(payload, Stubs::default())[1],
+            step("scheduled", Some("postponed by 7200s as scheduled"))
+        );
+
+        let payload = json!({"scheduledAt": "not a date"});
+        let steps = simulated_steps(payload, Stubs::default());
+        assert_eq!(steps[1].0, "scheduled");
+        assert!(steps[1]
+            .1
+            .as_ref()
+            .is_some_and(|note| note.starts_with("cannot be scheduled")));
+        assert_eq!(steps[2], step("failed", None));
+    }
+
+    #[test]
+    fn simulation_ends_when_the_command_is_cleared() {
+        let simulation = simulate_firmware_update(Stubs::default(), 100);
+        assert_eq!(simulation.end, SimulationEnd::Cleared);
+        assert_eq!(simulation.steps.len(), 5);
+    }
+
+    #[test]
+    fn simulation_stops_when_running_out_of_steps() {
+        let simulation = simulate_firmware_update(Stubs::default(), 3);
+        assert_eq!(simulation.end, SimulationEnd::StepLimitReached);
+        assert_eq!(simulation.steps.len(), 3);
+
+        let simulation = simulate_firmware_update(Stubs::default(), 5);
+        assert_eq!(simulation.end, SimulationEnd::Cleared);
+    }
+
+    #[test]
+    fn simulation_records_a_workflow_error_as_the_final_step() {
+        let workflow: OperationWorkflow = toml::from_str(
+            r#"
+operation = "firmware_update"
+
+[init]
+action = "proceed"
+on_success = "download"
+
+[download]
+script = "/usr/bin/download.sh ${.payload.url}"
+on_success = "instal"
+"#,
+        )
+        .unwrap();
+        let init = init_state(&workflow, json!({"url": "http://example.com/fw"}));
+        let simulation = simulate(
+            &workflow,
+            init,
+            &Stubs::default(),
+            100,
+            datetime!(2024-06-01 12:00 UTC),
+        );
+        assert_eq!(simulation.end, SimulationEnd::Failed);
+
+        let states: Vec<_> = simulation
+            .steps
+            .iter()
+            .map(|step| step.state.status.as_str())
+            .collect();
+        assert_eq!(states, vec!["init", "download", "instal"]);
+
+        let last_action = &simulation.steps.last().unwrap().action;
+        assert!(matches!(
+            last_action,
+            Err(WorkflowExecutionError::UnknownStep { step, .. }) if step == "instal"
+        ));
+    }
+}
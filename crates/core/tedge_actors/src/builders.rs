@@ -61,6 +61,8 @@
 //!   using an `impl From<SourceMessage> for SinkMessage`. This flexibility allows an actor to receive
 //!   messages from several independent sources (see the [fan_in_message_type](crate::fan_in_message_type) macro).
 use crate::mpsc;
+use crate::queue_depth::QueueDepth;
+use crate::queue_depth::QueueSender;
 use crate::CloneSender;
 use crate::DynSender;
 use crate::LoggingReceiver;
@@ -378,7 +380,7 @@ pub trait RuntimeRequestSink {
 ///
 pub struct SimpleMessageBoxBuilder<I: Debug, O> {
     name: String,
-    input_sender: mpsc::Sender<I>,
+    input_sender: QueueSender<I>,
     signal_sender: mpsc::Sender<RuntimeRequest>,
     output_sender: DynSender<O>,
     input_receiver: LoggingReceiver<I>,
@@ -389,8 +391,11 @@ impl<I: Message, O: Message> SimpleMessageBoxBuilder<I, O> {
         let (input_sender, input_receiver) = mpsc::channel(capacity);
         let (signal_sender, signal_receiver) = mpsc::channel(4);
         let output_sender = NullSender.into();
+        let queue_depth = QueueDepth::register(name);
         let input_receiver =
-            LoggingReceiver::new(name.to_string(), input_receiver, signal_receiver);
+            LoggingReceiver::new(name.to_string(), input_receiver, signal_receiver)
+                .with_queue_depth(queue_depth.clone());
+        let input_sender = QueueSender::new(input_sender, queue_depth);
 
         SimpleMessageBoxBuilder {
             name: name.to_string(),
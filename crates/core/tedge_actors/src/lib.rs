@@ -306,6 +306,7 @@ pub mod converter;
 mod errors;
 pub mod message_boxes;
 mod messages;
+pub mod queue_depth;
 #[doc(hidden)]
 mod run_actor;
 pub mod runtime;
@@ -85,6 +85,7 @@
 //! TODO
 //!
 use crate::channels::Sender;
+use crate::queue_depth::QueueDepth;
 use crate::ChannelError;
 use crate::CloneSender;
 use crate::DynSender;
@@ -115,6 +116,7 @@ pub trait MessageReceiver<Input> {
 pub struct LoggingReceiver<Input: Debug> {
     name: String,
     receiver: CombinedReceiver<Input>,
+    queue_depth: Option<QueueDepth>,
 }
 
 impl<Input: Debug> LoggingReceiver<Input> {
@@ -124,7 +126,31 @@ impl<Input: Debug> LoggingReceiver<Input> {
         signal_receiver: mpsc::Receiver<RuntimeRequest>,
     ) -> Self {
         let receiver = CombinedReceiver::new(input_receiver, signal_receiver);
-        Self { name, receiver }
+        Self {
+            name,
+            receiver,
+            queue_depth: None,
+        }
+    }
+
+    /// Count down the messages received from a queue whose senders count up the messages sent
+    pub(crate) fn with_queue_depth(self, queue_depth: QueueDepth) -> Self {
+        Self {
+            queue_depth: Some(queue_depth),
+            ..self
+        }
+    }
+
+    fn dequeued(&self) {
+        if let Some(queue_depth) = &self.queue_depth {
+            queue_depth.decrement();
+        }
+    }
+
+    fn untracked(&mut self) {
+        if let Some(queue_depth) = self.queue_depth.take() {
+            queue_depth.unregister();
+        }
     }
 
     /// Splits a `LoggingReceiver` into an input receiver and a signal receiver,
@@ -138,6 +164,7 @@ impl<Input: Debug> LoggingReceiver<Input> {
         &mut mpsc::Receiver<Input>,
         &mut mpsc::Receiver<RuntimeRequest>,
     ) {
+        self.untracked();
         (
             &mut self.receiver.input_receiver,
             &mut self.receiver.signal_receiver,
@@ -149,7 +176,8 @@ impl<Input: Debug> LoggingReceiver<Input> {
     ///
     /// This method returns consumes the `LoggingReceiver` and returns owned
     /// receivers, which can then be separately moved.
-    pub fn into_split(self) -> (mpsc::Receiver<Input>, mpsc::Receiver<RuntimeRequest>) {
+    pub fn into_split(mut self) -> (mpsc::Receiver<Input>, mpsc::Receiver<RuntimeRequest>) {
+        self.untracked();
         (self.receiver.input_receiver, self.receiver.signal_receiver)
     }
 
@@ -164,12 +192,18 @@ impl<Input: Send + Debug> MessageReceiver<Input> for LoggingReceiver<Input> {
     async fn try_recv(&mut self) -> Result<Option<Input>, RuntimeRequest> {
         let message = self.receiver.try_recv().await;
         debug!(target: &self.name, "recv {:?}", message);
+        if let Ok(Some(_)) = message {
+            self.dequeued();
+        }
         message
     }
 
     async fn recv(&mut self) -> Option<Input> {
         let message = self.receiver.recv().await;
         debug!(target: &self.name, "recv {:?}", message);
+        if message.is_some() {
+            self.dequeued();
+        }
         message
     }
 
@@ -0,0 +1,139 @@
+//! Tracking of the number of messages waiting in the input queues of the message boxes.
+//!
+//! The message boxes built with a [SimpleMessageBoxBuilder](crate::SimpleMessageBoxBuilder)
+//! or a [ServerMessageBoxBuilder](crate::ServerMessageBoxBuilder) count the messages sent to their actor
+//! and not received yet. These counts are collected by name using [queue_depths].
+use crate::mpsc;
+use crate::ChannelError;
+use crate::Message;
+use crate::Sender;
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+
+static QUEUES: Mutex<Vec<(String, Weak<AtomicUsize>)>> = Mutex::new(Vec::new());
+
+/// The number of messages sent to a message box and not received yet by its actor
+#[derive(Clone, Debug)]
+pub struct QueueDepth {
+    count: Arc<AtomicUsize>,
+}
+
+impl QueueDepth {
+    /// Create a new queue depth, reported under the name of the message box
+    pub fn register(name: &str) -> Self {
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut queues = QUEUES.lock().unwrap();
+        queues.retain(|(_, queue)| queue.strong_count() > 0);
+        queues.push((name.to_string(), Arc::downgrade(&count)));
+        QueueDepth { count }
+    }
+
+    /// Stop reporting this queue depth
+    ///
+    /// This is used when the messages are no more received through the message box,
+    /// and therefore can no more be counted.
+    pub(crate) fn unregister(&self) {
+        let count = Arc::downgrade(&self.count);
+        QUEUES
+            .lock()
+            .unwrap()
+            .retain(|(_, queue)| !queue.ptr_eq(&count));
+    }
+
+    pub fn get(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn increment(&self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn decrement(&self) {
+        let _ = self
+            .count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                count.checked_sub(1)
+            });
+    }
+}
+
+/// The current depths of the input queues of all the live message boxes, summed by name
+pub fn queue_depths() -> BTreeMap<String, usize> {
+    let mut depths = BTreeMap::new();
+    let mut queues = QUEUES.lock().unwrap();
+    queues.retain(|(name, queue)| match queue.upgrade() {
+        Some(count) => {
+            *depths.entry(name.clone()).or_default() += count.load(Ordering::Relaxed);
+            true
+        }
+        None => false,
+    });
+    depths
+}
+
+/// A sender that counts the messages pushed into the queue of a message box
+pub(crate) struct QueueSender<M> {
+    sender: mpsc::Sender<M>,
+    depth: QueueDepth,
+}
+
+impl<M> QueueSender<M> {
+    pub(crate) fn new(sender: mpsc::Sender<M>, depth: QueueDepth) -> Self {
+        QueueSender { sender, depth }
+    }
+}
+
+impl<M> Clone for QueueSender<M> {
+    fn clone(&self) -> Self {
+        QueueSender {
+            sender: self.sender.clone(),
+            depth: self.depth.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl<M: Message, N: Message + Into<M>> Sender<N> for QueueSender<M> {
+    async fn send(&mut self, message: N) -> Result<(), ChannelError> {
+        // Count the message before sending it, so the receiver never decrements a count not incremented yet
+        self.depth.increment();
+        let result = self.sender.send(message).await;
+        if result.is_err() {
+            self.depth.decrement();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Builder;
+    use crate::MessageReceiver;
+    use crate::MessageSink;
+    use crate::SimpleMessageBoxBuilder;
+
+    #[tokio::test]
+    async fn counting_messages_waiting_in_a_message_box() {
+        let builder: SimpleMessageBoxBuilder<u32, u32> =
+            SimpleMessageBoxBuilder::new("queue_depth_test", 16);
+        let mut sender = builder.get_sender();
+        let mut message_box = builder.build();
+
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+        assert_eq!(queue_depths().get("queue_depth_test"), Some(&2));
+
+        message_box.recv().await.unwrap();
+        assert_eq!(queue_depths().get("queue_depth_test"), Some(&1));
+
+        drop(sender);
+        drop(message_box);
+        assert_eq!(queue_depths().get("queue_depth_test"), None);
+    }
+}
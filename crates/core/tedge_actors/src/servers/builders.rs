@@ -1,4 +1,6 @@
 use crate::mpsc;
+use crate::queue_depth::QueueDepth;
+use crate::queue_depth::QueueSender;
 use crate::Actor;
 use crate::Builder;
 use crate::CloneSender;
@@ -21,7 +23,7 @@ use std::fmt::Debug;
 /// A message box builder for request-response services
 pub struct ServerMessageBoxBuilder<Request: Debug, Response> {
     max_concurrency: usize,
-    request_sender: mpsc::Sender<RequestEnvelope<Request, Response>>,
+    request_sender: QueueSender<RequestEnvelope<Request, Response>>,
     request_receiver: LoggingReceiver<RequestEnvelope<Request, Response>>,
     signal_sender: mpsc::Sender<RuntimeRequest>,
 }
@@ -32,8 +34,11 @@ impl<Request: Message, Response: Message> ServerMessageBoxBuilder<Request, Respo
         let max_concurrency = 1;
         let (request_sender, request_receiver) = mpsc::channel(capacity);
         let (signal_sender, signal_receiver) = mpsc::channel(4);
+        let queue_depth = QueueDepth::register(server_name);
         let request_receiver =
-            LoggingReceiver::new(server_name.to_string(), request_receiver, signal_receiver);
+            LoggingReceiver::new(server_name.to_string(), request_receiver, signal_receiver)
+                .with_queue_depth(queue_depth.clone());
+        let request_sender = QueueSender::new(request_sender, queue_depth);
 
         ServerMessageBoxBuilder {
             max_concurrency,
@@ -1,7 +1,11 @@
+use crate::certificate_renewal::builder::CertificateRenewalBuilder;
+use crate::certificate_renewal::config::CertificateRenewalConfig;
 use crate::device_profile_manager::DeviceProfileManagerBuilder;
 use crate::entity_manager;
 use crate::entity_manager::server::EntityStoreRequest;
 use crate::entity_manager::server::EntityStoreServer;
+use crate::file_list_manager::builder::FileListManagerBuilder;
+use crate::file_list_manager::config::FileListManagerConfig;
 use crate::http_server::actor::HttpServerBuilder;
 use crate::http_server::actor::HttpServerConfig;
 use crate::operation_file_cache::FileCacheActorBuilder;
@@ -9,6 +13,8 @@ use crate::operation_workflows::OperationConfig;
 use crate::operation_workflows::WorkflowActorBuilder;
 use crate::restart_manager::builder::RestartManagerBuilder;
 use crate::restart_manager::config::RestartManagerConfig;
+use crate::shell_manager::builder::ShellManagerBuilder;
+use crate::shell_manager::config::ShellManagerConfig;
 use crate::software_manager::builder::SoftwareManagerBuilder;
 use crate::software_manager::config::SoftwareManagerConfig;
 use crate::state_repository::state::agent_default_state_dir;
@@ -50,7 +56,9 @@ use tedge_config::tedge_toml::TEdgeConfigReaderService;
 use tedge_config_manager::ConfigManagerBuilder;
 use tedge_config_manager::ConfigManagerConfig;
 use tedge_config_manager::ConfigManagerOptions;
+use tedge_downloader_ext::ArtifactCache;
 use tedge_downloader_ext::DownloaderActor;
+use tedge_downloader_ext::TrustStore;
 use tedge_file_system_ext::FsWatchActorBuilder;
 use tedge_health_ext::HealthMonitorBuilder;
 use tedge_log_manager::LogManagerBuilder;
@@ -61,6 +69,7 @@ use tedge_mqtt_ext::MqttConfig;
 use tedge_mqtt_ext::TopicFilter;
 use tedge_script_ext::ScriptActor;
 use tedge_signal_ext::SignalActor;
+use tedge_timer_ext::TimerActor;
 use tedge_uploader_ext::UploaderActor;
 use tedge_utils::file::create_directory_with_defaults;
 use tracing::info;
@@ -75,7 +84,10 @@ pub(crate) struct AgentConfig {
     pub http_config: HttpServerConfig,
     pub restart_config: RestartManagerConfig,
     pub sw_update_config: SoftwareManagerConfig,
+    pub shell_config: ShellManagerConfig,
+    pub file_list_config: FileListManagerConfig,
     pub operation_config: OperationConfig,
+    pub certificate_renewal_config: Option<CertificateRenewalConfig>,
     pub config_dir: Utf8PathBuf,
     pub tmp_dir: Arc<Utf8Path>,
     pub run_dir: Utf8PathBuf,
@@ -91,6 +103,10 @@ pub(crate) struct AgentConfig {
     pub service: TEdgeConfigReaderService,
     pub identity: Option<Identity>,
     pub cloud_root_certs: CloudHttpConfig,
+    pub trust_store: Arc<TrustStore>,
+    pub artifact_cache: Option<Arc<ArtifactCache>>,
+    pub file_cache_max_size: u64,
+    pub metrics_enabled: bool,
     pub fts_url: Arc<str>,
     pub is_sudo_enabled: bool,
     pub capabilities: Capabilities,
@@ -148,6 +164,10 @@ impl AgentConfig {
         // Software update config
         let sw_update_config = SoftwareManagerConfig::from_tedge_config(&tedge_config).await?;
 
+        // Shell and file list config
+        let shell_config = ShellManagerConfig::from_tedge_config(&tedge_config);
+        let file_list_config = FileListManagerConfig::from_tedge_config(&tedge_config);
+
         // Operation Workflow config
         let operation_config = OperationConfig::from_tedge_config(
             mqtt_topic_root.to_string(),
@@ -156,6 +176,17 @@ impl AgentConfig {
         )
         .await?;
 
+        // Certificate renewal config
+        let certificate_renewal_config = if tedge_config.certificate.renewal.enable {
+            Some(CertificateRenewalConfig::from_tedge_config(
+                &MqttSchema::with_root(mqtt_topic_root.to_string()),
+                &mqtt_device_topic_id,
+                &tedge_config,
+            )?)
+        } else {
+            None
+        };
+
         // For flockfile
         let run_dir = tedge_config.run.path.clone().into();
         let use_lock = tedge_config.run.lock_files;
@@ -167,6 +198,16 @@ impl AgentConfig {
 
         let identity = tedge_config.http.client.auth.identity()?;
         let cloud_root_certs = tedge_config.cloud_root_certs()?;
+        let trust_store = Arc::new(
+            TrustStore::load(&tedge_config.download.trust_store)?
+                .with_required_signature(tedge_config.download.require_signature),
+        );
+        let artifact_cache = tedge_config.download.delta.enable.then(|| {
+            Arc::new(ArtifactCache::new(
+                data_dir.artifact_cache_dir(),
+                tedge_config.download.delta.cache_max_size,
+            ))
+        });
 
         let is_sudo_enabled = tedge_config.sudo.enable;
 
@@ -174,6 +215,8 @@ impl AgentConfig {
             config_update: tedge_config.agent.enable.config_update,
             config_snapshot: tedge_config.agent.enable.config_snapshot,
             log_upload: tedge_config.agent.enable.log_upload,
+            shell: tedge_config.agent.enable.shell,
+            file_list: tedge_config.agent.enable.file_list,
         };
         let fts_url = format!(
             "{}:{}",
@@ -189,7 +232,10 @@ impl AgentConfig {
             http_config,
             restart_config,
             sw_update_config,
+            shell_config,
+            file_list_config,
             operation_config,
+            certificate_renewal_config,
             config_dir,
             run_dir,
             tmp_dir,
@@ -204,6 +250,10 @@ impl AgentConfig {
             tedge_http_host,
             identity,
             cloud_root_certs,
+            trust_store,
+            artifact_cache,
+            file_cache_max_size: tedge_config.agent.file_cache.max_size,
+            metrics_enabled: tedge_config.metrics.enable,
             fts_url,
             is_sudo_enabled,
             service: tedge_config.service.clone(),
@@ -267,6 +317,9 @@ impl Agent {
         // Script actor
         let mut script_runner: ServerActorBuilder<ScriptActor, Concurrent> = ScriptActor::builder();
 
+        // Timer actor
+        let mut timer_actor = TimerActor::builder();
+
         // Restart actor
         let mut restart_actor_builder = RestartManagerBuilder::new(self.config.restart_config);
 
@@ -282,6 +335,7 @@ impl Agent {
             &mut mqtt_actor_builder,
             &mut script_runner,
             &mut fs_watch_actor_builder,
+            &mut timer_actor,
         );
         converter_actor_builder.register_builtin_operation(&mut restart_actor_builder);
         converter_actor_builder.register_builtin_operation(&mut software_update_builder);
@@ -298,6 +352,9 @@ impl Agent {
             device_topic_id: DeviceTopicId::new(self.config.mqtt_device_topic_id.clone()),
         };
         let mqtt_schema = MqttSchema::with_root(self.config.mqtt_topic_root.to_string());
+        if self.config.metrics_enabled {
+            tedge_metrics::enable(mqtt_schema.clone());
+        }
         let health_actor = HealthMonitorBuilder::from_service_topic_id(
             service,
             &mut mqtt_actor_builder,
@@ -309,6 +366,8 @@ impl Agent {
             self.config.identity.clone(),
             self.config.cloud_root_certs.clone(),
         )
+        .with_trust_store(self.config.trust_store.clone())
+        .with_artifact_cache(self.config.artifact_cache.clone())
         .builder();
         let mut uploader_actor_builder =
             UploaderActor::new(self.config.identity, self.config.cloud_root_certs).builder();
@@ -362,6 +421,25 @@ impl Agent {
             None
         };
 
+        // Instantiate shell manager actor if the operation is enabled
+        let shell_actor_builder = if self.config.capabilities.shell {
+            let mut shell_actor =
+                ShellManagerBuilder::new(self.config.shell_config, &mut uploader_actor_builder);
+            converter_actor_builder.register_builtin_operation(&mut shell_actor);
+            Some(shell_actor)
+        } else {
+            None
+        };
+
+        // Instantiate file list manager actor if the operation is enabled
+        let file_list_actor_builder = if self.config.capabilities.file_list {
+            let mut file_list_actor = FileListManagerBuilder::new(self.config.file_list_config);
+            converter_actor_builder.register_builtin_operation(&mut file_list_actor);
+            Some(file_list_actor)
+        } else {
+            None
+        };
+
         // TODO: replace with a call to entity store when we stop assuming default MQTT schema
         let is_main_device =
             self.config.mqtt_device_topic_id == EntityTopicId::default_main_device();
@@ -413,6 +491,8 @@ impl Agent {
                 mqtt_schema,
                 self.config.fts_url.clone(),
                 self.config.data_dir,
+                self.config.mqtt_device_topic_id.clone(),
+                self.config.file_cache_max_size,
                 &mut downloader_actor_builder,
                 &mut mqtt_actor_builder,
             );
@@ -420,6 +500,12 @@ impl Agent {
             runtime.spawn(file_transfer_server_builder).await?;
             runtime.spawn(entity_store_actor_builder).await?;
             runtime.spawn(operation_file_cache_builder).await?;
+
+            if let Some(config) = self.config.certificate_renewal_config {
+                let certificate_renewal_builder =
+                    CertificateRenewalBuilder::new(config, &mut mqtt_actor_builder);
+                runtime.spawn(certificate_renewal_builder).await?;
+            }
         } else {
             info!("Running as a child device, tedge_to_te_converter and File Transfer Service disabled");
         }
@@ -436,9 +522,16 @@ impl Agent {
         if let Some(log_actor_builder) = log_actor_builder {
             runtime.spawn(log_actor_builder).await?;
         }
+        if let Some(shell_actor_builder) = shell_actor_builder {
+            runtime.spawn(shell_actor_builder).await?;
+        }
+        if let Some(file_list_actor_builder) = file_list_actor_builder {
+            runtime.spawn(file_list_actor_builder).await?;
+        }
         runtime.spawn(restart_actor_builder).await?;
         runtime.spawn(software_update_builder).await?;
         runtime.spawn(script_runner).await?;
+        runtime.spawn(timer_actor).await?;
         runtime.spawn(converter_actor_builder).await?;
         runtime.spawn(health_actor).await?;
 
@@ -0,0 +1,242 @@
+use crate::certificate_renewal::config::CertificateRenewalConfig;
+use crate::certificate_renewal::error::CertificateRenewalError;
+use async_trait::async_trait;
+use camino::Utf8PathBuf;
+use certificate::est::parse_csr_contents;
+use certificate::est::pk7_to_x509;
+use certificate::CertificateShift;
+use certificate::KeyCertPair;
+use certificate::KeyKind;
+use certificate::PemCertificate;
+use certificate::ValidityStatus;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde_json::json;
+use std::time::Duration;
+use tedge_actors::Actor;
+use tedge_actors::MessageReceiver;
+use tedge_actors::NoMessage;
+use tedge_actors::RuntimeError;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_mqtt_ext::MqttMessage;
+use tokio::time::sleep;
+use tracing::error;
+use tracing::info;
+
+pub struct CertificateRenewalActor {
+    config: CertificateRenewalConfig,
+    message_box: SimpleMessageBox<NoMessage, MqttMessage>,
+    /// Set once the renewal alarm, if any, has been cleared, and till a new alarm is raised
+    alarm_cleared: bool,
+}
+
+#[async_trait]
+impl Actor for CertificateRenewalActor {
+    fn name(&self) -> &str {
+        "CertificateRenewalActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        loop {
+            self.check_certificate().await?;
+
+            tokio::select! {
+                _ = sleep(self.config.check_interval) => (),
+                Some(RuntimeRequest::Shutdown) = self.message_box.recv_signal() => return Ok(()),
+            }
+        }
+    }
+}
+
+impl CertificateRenewalActor {
+    pub fn new(
+        config: CertificateRenewalConfig,
+        message_box: SimpleMessageBox<NoMessage, MqttMessage>,
+    ) -> Self {
+        CertificateRenewalActor {
+            config,
+            message_box,
+            alarm_cleared: false,
+        }
+    }
+
+    async fn check_certificate(&mut self) -> Result<(), RuntimeError> {
+        match self.renew_certificate_if_needed().await {
+            Ok(renewed) => {
+                if renewed {
+                    info!(
+                        "The device certificate {} has been renewed",
+                        self.config.cert_path
+                    );
+                }
+                // The certificate is valid: any alarm raised before, possibly by a previous run, is obsolete
+                self.clear_alarm().await
+            }
+            Err(err) => {
+                let reason = format!("Fail to renew the device certificate: {err}");
+                error!("{reason}");
+                self.raise_alarm(reason).await
+            }
+        }
+    }
+
+    /// Renew the device certificate if it expires soon
+    ///
+    /// Return `true` if the certificate has been renewed.
+    async fn renew_certificate_if_needed(&self) -> Result<bool, CertificateRenewalError> {
+        let cert_path = &self.config.cert_path;
+        let certificate = PemCertificate::from_pem_file(cert_path)?;
+        if !need_renewal(certificate.still_valid()?, self.config.minimum_validity) {
+            return Ok(false);
+        }
+        info!(
+            "The device certificate {cert_path} expires on {}: requesting a new one",
+            certificate.not_after()?
+        );
+
+        let csr = self
+            .certificate_signing_request(&certificate.subject_common_name()?)
+            .await?;
+        let pk7_base64 = self.request_new_certificate(csr).await?;
+        let x509_pem = pk7_to_x509(pk7_base64)?;
+        store_new_certificate(
+            &CertificateShift::new_certificate_path(cert_path),
+            &x509_pem,
+        )
+        .await?;
+
+        self.reconnect().await?;
+        Ok(true)
+    }
+
+    async fn certificate_signing_request(
+        &self,
+        common_name: &str,
+    ) -> Result<String, CertificateRenewalError> {
+        let key_path = &self.config.key_path;
+        let keypair_pem = tokio::fs::read_to_string(key_path).await.map_err(|error| {
+            CertificateRenewalError::ReadPrivateKey {
+                path: key_path.clone(),
+                error,
+            }
+        })?;
+        let csr = KeyCertPair::new_certificate_sign_request(
+            &self.config.csr_template,
+            common_name,
+            &KeyKind::Reuse { keypair_pem },
+        )?
+        .certificate_signing_request_string()?;
+        Ok(parse_csr_contents(csr))
+    }
+
+    /// Post the CSR to the EST endpoint, returning the new certificate encoded as a base64 PKCS#7
+    async fn request_new_certificate(
+        &self,
+        csr: String,
+    ) -> Result<String, CertificateRenewalError> {
+        let url = &self.config.est_url;
+        let connection_error = |error| CertificateRenewalError::Connection {
+            url: url.clone(),
+            error,
+        };
+
+        let http_builder = self.config.http_config.client_builder();
+        let http_builder = if let Some(identity) = &self.config.identity {
+            http_builder.identity(identity.clone())
+        } else {
+            http_builder
+        };
+        let http = http_builder.build().map_err(connection_error)?;
+
+        let response = http
+            .post(url)
+            .header(CONTENT_TYPE, "application/pkcs10")
+            .body(csr)
+            .send()
+            .await
+            .map_err(connection_error)?;
+
+        let status = response.status();
+        let body = response.text().await.map_err(connection_error)?;
+        if status != StatusCode::OK {
+            return Err(CertificateRenewalError::Rejected { status, body });
+        }
+        Ok(body)
+    }
+
+    /// Reconnect the device, so the new certificate is validated and promoted
+    async fn reconnect(&self) -> Result<(), CertificateRenewalError> {
+        let Some((program, args)) = self.config.reconnect_command.split_first() else {
+            return Ok(());
+        };
+        let mut command = self.config.sudo.command(program);
+        command.args(args);
+        let command_line = self.config.reconnect_command.join(" ");
+
+        let output = tokio::process::Command::from(command)
+            .output()
+            .await
+            .map_err(|err| CertificateRenewalError::Reconnect {
+                reason: format!("cannot execute `{command_line}`: {err}"),
+            })?;
+        if !output.status.success() {
+            return Err(CertificateRenewalError::Reconnect {
+                reason: format!(
+                    "`{command_line}` failed with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    async fn raise_alarm(&mut self, text: String) -> Result<(), RuntimeError> {
+        let payload = json!({
+            "text": text,
+            "severity": "major",
+        });
+        let alarm = MqttMessage::new(&self.config.alarm_topic, payload.to_string()).with_retain();
+        self.message_box.send(alarm).await?;
+        self.alarm_cleared = false;
+        Ok(())
+    }
+
+    async fn clear_alarm(&mut self) -> Result<(), RuntimeError> {
+        if self.alarm_cleared {
+            return Ok(());
+        }
+        let clear = MqttMessage::new(&self.config.alarm_topic, "").with_retain();
+        self.message_box.send(clear).await?;
+        self.alarm_cleared = true;
+        Ok(())
+    }
+}
+
+fn need_renewal(status: ValidityStatus, minimum: Duration) -> bool {
+    match status {
+        ValidityStatus::Valid { expired_in } => expired_in <= minimum,
+        ValidityStatus::Expired { .. } => true,
+        ValidityStatus::NotValidYet { .. } => false,
+    }
+}
+
+/// Atomically store the new certificate, so `tedge connect` never reads a partial file
+async fn store_new_certificate(
+    path: &Utf8PathBuf,
+    x509_pem: &str,
+) -> Result<(), CertificateRenewalError> {
+    let store_error = |error| CertificateRenewalError::StoreCertificate {
+        path: path.clone(),
+        error,
+    };
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, x509_pem)
+        .await
+        .map_err(store_error)?;
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .map_err(store_error)
+}
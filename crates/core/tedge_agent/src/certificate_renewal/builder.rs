@@ -0,0 +1,48 @@
+use crate::certificate_renewal::actor::CertificateRenewalActor;
+use crate::certificate_renewal::config::CertificateRenewalConfig;
+use tedge_actors::Builder;
+use tedge_actors::DynSender;
+use tedge_actors::LinkError;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::NoMessage;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_mqtt_ext::MqttMessage;
+
+pub struct CertificateRenewalBuilder {
+    config: CertificateRenewalConfig,
+    message_box: SimpleMessageBoxBuilder<NoMessage, MqttMessage>,
+}
+
+impl CertificateRenewalBuilder {
+    pub fn new(config: CertificateRenewalConfig, mqtt: &mut impl MessageSink<MqttMessage>) -> Self {
+        let mut message_box = SimpleMessageBoxBuilder::new("CertificateRenewal", 1);
+        message_box.connect_sink(NoConfig, mqtt);
+
+        Self {
+            config,
+            message_box,
+        }
+    }
+}
+
+impl RuntimeRequestSink for CertificateRenewalBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.message_box.get_signal_sender()
+    }
+}
+
+impl Builder<CertificateRenewalActor> for CertificateRenewalBuilder {
+    type Error = LinkError;
+
+    fn try_build(self) -> Result<CertificateRenewalActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> CertificateRenewalActor {
+        CertificateRenewalActor::new(self.config, self.message_box.build())
+    }
+}
@@ -0,0 +1,123 @@
+use camino::Utf8PathBuf;
+use certificate::est::est_endpoint;
+use certificate::CloudHttpConfig;
+use certificate::CsrTemplate;
+use reqwest::Identity;
+use std::time::Duration;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::models::CloudType;
+use tedge_config::tedge_toml::Cloud;
+use tedge_config::tedge_toml::ProfileName;
+use tedge_config::SudoCommandBuilder;
+use tedge_mqtt_ext::Topic;
+
+pub const ALARM_TYPE: &str = "certificate_renewal";
+
+#[derive(Debug, Clone)]
+pub struct CertificateRenewalConfig {
+    /// The device certificate to be renewed
+    pub cert_path: Utf8PathBuf,
+    /// The private key of the device, re-used for the new certificate
+    pub key_path: Utf8PathBuf,
+    pub csr_template: CsrTemplate,
+    /// The certificate is renewed when valid for less than this duration
+    pub minimum_validity: Duration,
+    pub check_interval: Duration,
+    /// The EST endpoint used to get a new certificate
+    pub est_url: String,
+    pub http_config: CloudHttpConfig,
+    pub identity: Option<Identity>,
+    /// The command used to reconnect the device with the new certificate
+    pub reconnect_command: Vec<String>,
+    pub sudo: SudoCommandBuilder,
+    /// The topic used to raise an alarm when the certificate cannot be renewed
+    pub alarm_topic: Topic,
+}
+
+impl CertificateRenewalConfig {
+    pub fn from_tedge_config(
+        mqtt_schema: &MqttSchema,
+        device_topic_id: &EntityTopicId,
+        tedge_config: &tedge_config::TEdgeConfig,
+    ) -> Result<CertificateRenewalConfig, anyhow::Error> {
+        let renewal_config = &tedge_config.certificate.renewal;
+        let cloud_type = renewal_config.cloud;
+        let profile = renewal_config
+            .profile
+            .or_none()
+            .map(|profile| profile.parse::<ProfileName>())
+            .transpose()?;
+        let cloud = match cloud_type {
+            CloudType::C8y => Cloud::C8y(profile.as_ref()),
+            CloudType::Az => Cloud::Az(profile.as_ref()),
+            CloudType::Aws => Cloud::Aws(profile.as_ref()),
+        };
+
+        let est_url = match (tedge_config.certificate.est.url.or_none(), &cloud) {
+            (Some(est_server), _) => est_endpoint(est_server, "simplereenroll"),
+            (None, Cloud::C8y(profile)) => {
+                // The Cumulocity CA is reached through the local proxy of the c8y mapper
+                let proxy = &tedge_config.c8y.try_get(*profile)?.proxy;
+                let protocol = proxy.cert_path.or_none().map_or("http", |_| "https");
+                let est_server = format!(
+                    "{protocol}://{}:{}/c8y",
+                    proxy.client.host, proxy.client.port
+                );
+                est_endpoint(&est_server, "simplereenroll")
+            }
+            (None, _) => {
+                anyhow::bail!(
+                    "certificate.est.url must be set to renew the device certificate used to connect {cloud_type}"
+                )
+            }
+        };
+
+        let mut reconnect_command =
+            vec!["tedge".into(), "reconnect".into(), cloud_type.to_string()];
+        if let Some(profile) = &profile {
+            reconnect_command.extend(["--profile".into(), profile.to_string()]);
+        }
+
+        let csr_template = CsrTemplate {
+            validity_period_days: (tedge_config
+                .certificate
+                .validity
+                .requested_duration
+                .duration()
+                .as_secs()
+                / (24 * 3600)) as u32,
+            organization_name: tedge_config.certificate.organization.to_string(),
+            organizational_unit_name: tedge_config.certificate.organization_unit.to_string(),
+            ..CsrTemplate::default()
+        };
+
+        let alarm_topic = mqtt_schema.topic_for(
+            device_topic_id,
+            &Channel::Alarm {
+                alarm_type: ALARM_TYPE.to_string(),
+            },
+        );
+
+        Ok(CertificateRenewalConfig {
+            cert_path: tedge_config
+                .device_cert_path(Some(cloud.clone()))?
+                .to_owned(),
+            key_path: tedge_config.device_key_path(Some(cloud))?.to_owned(),
+            csr_template,
+            minimum_validity: tedge_config
+                .certificate
+                .validity
+                .minimum_duration
+                .duration(),
+            check_interval: renewal_config.check_interval.duration(),
+            est_url,
+            http_config: tedge_config.cloud_root_certs()?,
+            identity: tedge_config.http.client.auth.identity()?,
+            reconnect_command,
+            sudo: SudoCommandBuilder::new(tedge_config),
+            alarm_topic,
+        })
+    }
+}
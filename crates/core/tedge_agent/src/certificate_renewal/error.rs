@@ -0,0 +1,34 @@
+use camino::Utf8PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CertificateRenewalError {
+    #[error(transparent)]
+    FromCertificate(#[from] certificate::CertificateError),
+
+    #[error("Could not read the private key {path}: {error}")]
+    ReadPrivateKey {
+        path: Utf8PathBuf,
+        error: std::io::Error,
+    },
+
+    #[error("Could not store the new certificate {path}: {error}")]
+    StoreCertificate {
+        path: Utf8PathBuf,
+        error: std::io::Error,
+    },
+
+    #[error("Fail to connect to the CA {url}: {error}")]
+    Connection { url: String, error: reqwest::Error },
+
+    #[error("The CA rejected the certificate request with {status}: {body}")]
+    Rejected {
+        status: reqwest::StatusCode,
+        body: String,
+    },
+
+    #[error(transparent)]
+    IllFormedCertificate(#[from] certificate::est::IllFormedPk7Cert),
+
+    #[error("Fail to reconnect the device with the new certificate: {reason}")]
+    Reconnect { reason: String },
+}
@@ -0,0 +1,19 @@
+//! Renewal of the device certificate before it expires.
+//!
+//! When `certificate.renewal.enable` is set, the agent periodically checks the validity
+//! of the device certificate used to connect `certificate.renewal.cloud` (with `certificate.renewal.profile`).
+//! When this certificate expires within `certificate.validity.minimum_duration`,
+//! a new certificate is requested to the EST server given by `certificate.est.url`, or else to the Cumulocity CA,
+//! using a CSR signed with the current private key.
+//! The new certificate is stored next to the current one, with a `.new` suffix,
+//! and the device is reconnected with `tedge reconnect`, which promotes the new certificate
+//! once it has been successfully used to connect the cloud.
+//!
+//! On failure, a `certificate_renewal` alarm is raised, and cleared as soon as the certificate is valid again.
+pub mod actor;
+pub mod builder;
+pub mod config;
+pub mod error;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,268 @@
+use crate::certificate_renewal::builder::CertificateRenewalBuilder;
+use crate::certificate_renewal::config::CertificateRenewalConfig;
+use certificate::CertificateShift;
+use certificate::CloudHttpConfig;
+use certificate::CsrTemplate;
+use certificate::KeyCertPair;
+use certificate::KeyKind;
+use certificate::PemCertificate;
+use serde_json::Value;
+use std::time::Duration;
+use tedge_actors::test_helpers::MessageReceiverExt;
+use tedge_actors::test_helpers::TimedMessageBox;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::MessageReceiver;
+use tedge_actors::NoMessage;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::SudoCommandBuilder;
+use tedge_config::TEdgeConfig;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::Topic;
+use tedge_test_utils::fs::TempTedgeDir;
+
+const TEST_TIMEOUT_MS: Duration = Duration::from_millis(3000);
+
+const ALARM_TOPIC: &str = "te/device/main///a/certificate_renewal";
+
+// A certificate issued by the Cumulocity CA, as returned by the EST endpoint
+const PK7_CERTIFICATE: &str = r#"
+MIAGCSqGSIb3DQEHAqCAMIACAQExADALBgkqhkiG9w0BBwGggDCCAXkwggEgoAMC
+AQICBgGVPZIizTAKBggqhkjOPQQDAjBCMRYwFAYDVQQGEw1Vbml0ZWQgU3RhdGVz
+MRMwEQYDVQQKEwpDdW11bG9jaXR5MRMwEQYDVQQDEwptYW5hZ2VtZW50MB4XDTI1
+MDIyNTE0NDU0MloXDTI2MDIyNDA5NDE0NFowRjEaMBgGA1UEAwwRZGlkaWVyLWRl
+dmljZS0wMDExEjAQBgNVBAoMCVRoaW4gRWRnZTEUMBIGA1UECwwLVGVzdCBEZXZp
+Y2UwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATwSjNE/7AJZEtrXW2CP2LSLlcl
+wDyh4YwHmpwDhnTCm+ZxeeXBUcUARcFXOtdmxMset9CgMQl1Fjw255dISpqiMAoG
+CCqGSM49BAMCA0cAMEQCICapYBWyzrDU36IVEtyOfdlDA0bW9HE3pwHz2X9LAgl1
+AiAD0naayxieH0RVE1vJtdD3iCJHrzLNM3Eff2gNOhuzJAAAMQAAAAAAAAA=
+"#;
+
+type MqttMessageBox = TimedMessageBox<SimpleMessageBox<MqttMessage, NoMessage>>;
+
+#[tokio::test]
+async fn certificate_still_valid_is_not_renewed() {
+    let temp_dir = TempTedgeDir::new();
+    let mut server = mockito::Server::new_async().await;
+    let est = server
+        .mock("POST", "/.well-known/est/simplereenroll")
+        .expect(0)
+        .create_async()
+        .await;
+
+    let config = renewal_config(&temp_dir, &server.url(), 365, "true");
+    let mut mqtt = spawn_certificate_renewal_actor(config);
+
+    // Any previous alarm is cleared, but only once
+    let message = mqtt.recv().await.unwrap();
+    assert_eq!(message.topic.name, ALARM_TOPIC);
+    assert_eq!(message.payload_str().unwrap(), "");
+    assert!(mqtt.recv().await.is_none());
+    est.assert_async().await;
+}
+
+#[tokio::test]
+async fn certificate_expiring_soon_is_renewed() {
+    let temp_dir = TempTedgeDir::new();
+    let mut server = mockito::Server::new_async().await;
+    let est = server
+        .mock("POST", "/.well-known/est/simplereenroll")
+        .match_header("content-type", "application/pkcs10")
+        .with_body(PK7_CERTIFICATE)
+        .create_async()
+        .await;
+
+    let config = renewal_config(&temp_dir, &server.url(), 10, "true");
+    let new_cert_path = CertificateShift::new_certificate_path(&config.cert_path);
+    let mut mqtt = spawn_certificate_renewal_actor(config);
+
+    // Any previous alarm is cleared
+    let message = mqtt.recv().await.unwrap();
+    assert_eq!(message.topic.name, ALARM_TOPIC);
+    assert_eq!(message.payload_str().unwrap(), "");
+    est.assert_async().await;
+
+    let new_cert = PemCertificate::from_pem_file(new_cert_path).unwrap();
+    assert_eq!(
+        new_cert.issuer().unwrap(),
+        "C=United States, O=Cumulocity, CN=management"
+    );
+}
+
+#[tokio::test]
+async fn an_alarm_is_raised_when_the_ca_rejects_the_request() {
+    let temp_dir = TempTedgeDir::new();
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/.well-known/est/simplereenroll")
+        .with_status(403)
+        .with_body("Forbidden")
+        .create_async()
+        .await;
+
+    let config = renewal_config(&temp_dir, &server.url(), 10, "true");
+    let new_cert_path = CertificateShift::new_certificate_path(&config.cert_path);
+    let mut mqtt = spawn_certificate_renewal_actor(config);
+
+    let message = mqtt.recv().await.unwrap();
+    assert_eq!(message.topic.name, ALARM_TOPIC);
+    assert!(message.retain);
+    let alarm: Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+    assert_eq!(alarm["severity"], "major");
+    assert!(alarm["text"].as_str().unwrap().contains("403"));
+    assert!(!new_cert_path.exists());
+}
+
+#[tokio::test]
+async fn the_alarm_is_cleared_when_the_certificate_is_valid_again() {
+    let temp_dir = TempTedgeDir::new();
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/.well-known/est/simplereenroll")
+        .with_status(503)
+        .create_async()
+        .await;
+
+    let mut config = renewal_config(&temp_dir, &server.url(), 10, "true");
+    config.check_interval = Duration::from_millis(100);
+    let cert_path = config.cert_path.clone();
+    let mut mqtt = spawn_certificate_renewal_actor(config);
+
+    let message = mqtt.recv().await.unwrap();
+    assert_eq!(message.topic.name, ALARM_TOPIC);
+    assert_ne!(message.payload_str().unwrap(), "");
+
+    // The certificate is renewed by other means
+    let csr_template = CsrTemplate::default();
+    let cert = KeyCertPair::new_selfsigned_certificate(&csr_template, "test-device", &KeyKind::New)
+        .unwrap();
+    std::fs::write(&cert_path, cert.certificate_pem_string().unwrap()).unwrap();
+
+    let message = mqtt.recv().await.unwrap();
+    assert_eq!(message.topic.name, ALARM_TOPIC);
+    assert_eq!(message.payload_str().unwrap(), "");
+}
+
+#[tokio::test]
+async fn an_alarm_is_raised_when_the_device_cannot_reconnect() {
+    let temp_dir = TempTedgeDir::new();
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/.well-known/est/simplereenroll")
+        .with_body(PK7_CERTIFICATE)
+        .create_async()
+        .await;
+
+    let config = renewal_config(&temp_dir, &server.url(), 10, "false");
+    let mut mqtt = spawn_certificate_renewal_actor(config);
+
+    let message = mqtt.recv().await.unwrap();
+    let alarm: Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+    assert!(alarm["text"]
+        .as_str()
+        .unwrap()
+        .contains("Fail to reconnect the device with the new certificate"));
+}
+
+#[test]
+fn the_certificate_of_the_default_c8y_profile_is_renewed_through_the_proxy() {
+    let tedge_config = TEdgeConfig::load_toml_str("certificate.renewal.enable = true");
+    let config = config_from_tedge_config(&tedge_config).unwrap();
+
+    assert_eq!(
+        config.est_url,
+        "http://127.0.0.1:8001/c8y/.well-known/est/simplereenroll"
+    );
+    assert_eq!(config.reconnect_command, ["tedge", "reconnect", "c8y"]);
+    assert_eq!(
+        config.cert_path,
+        "/etc/tedge/device-certs/tedge-certificate.pem"
+    );
+}
+
+#[test]
+fn the_renewal_can_target_a_cloud_profile_and_an_est_server() {
+    let tedge_config = TEdgeConfig::load_toml_str(
+        r#"
+        certificate.renewal.cloud = "az"
+        certificate.renewal.profile = "edge"
+        certificate.est.url = "https://est.example.com"
+        az.profiles.edge.device.cert_path = "/etc/tedge/device-certs/az-edge.pem"
+        "#,
+    );
+    let config = config_from_tedge_config(&tedge_config).unwrap();
+
+    assert_eq!(
+        config.est_url,
+        "https://est.example.com/.well-known/est/simplereenroll"
+    );
+    assert_eq!(
+        config.reconnect_command,
+        ["tedge", "reconnect", "az", "--profile", "edge"]
+    );
+    assert_eq!(config.cert_path, "/etc/tedge/device-certs/az-edge.pem");
+}
+
+#[test]
+fn an_est_server_is_required_to_renew_a_certificate_not_issued_by_c8y() {
+    let tedge_config = TEdgeConfig::load_toml_str(r#"certificate.renewal.cloud = "aws""#);
+    let error = config_from_tedge_config(&tedge_config).unwrap_err();
+
+    assert!(error
+        .to_string()
+        .contains("certificate.est.url must be set"));
+}
+
+fn config_from_tedge_config(
+    tedge_config: &TEdgeConfig,
+) -> Result<CertificateRenewalConfig, anyhow::Error> {
+    CertificateRenewalConfig::from_tedge_config(
+        &MqttSchema::default(),
+        &EntityTopicId::default_main_device(),
+        tedge_config,
+    )
+}
+
+fn renewal_config(
+    temp_dir: &TempTedgeDir,
+    est_server: &str,
+    validity_days: u32,
+    reconnect_command: &str,
+) -> CertificateRenewalConfig {
+    let csr_template = CsrTemplate {
+        validity_period_days: validity_days,
+        ..CsrTemplate::default()
+    };
+    let cert = KeyCertPair::new_selfsigned_certificate(&csr_template, "test-device", &KeyKind::New)
+        .unwrap();
+    let cert_path = temp_dir.utf8_path().join("tedge-certificate.pem");
+    let key_path = temp_dir.utf8_path().join("tedge-private-key.pem");
+    std::fs::write(&cert_path, cert.certificate_pem_string().unwrap()).unwrap();
+    std::fs::write(&key_path, cert.private_key_pem_string().unwrap().as_str()).unwrap();
+
+    CertificateRenewalConfig {
+        cert_path,
+        key_path,
+        csr_template,
+        minimum_validity: Duration::from_secs(30 * 24 * 3600),
+        check_interval: Duration::from_secs(3600),
+        est_url: format!("{est_server}/.well-known/est/simplereenroll"),
+        http_config: CloudHttpConfig::test_value(),
+        identity: None,
+        reconnect_command: vec![reconnect_command.to_string()],
+        sudo: SudoCommandBuilder::enabled(false),
+        alarm_topic: Topic::new_unchecked(ALARM_TOPIC),
+    }
+}
+
+fn spawn_certificate_renewal_actor(config: CertificateRenewalConfig) -> MqttMessageBox {
+    let mut mqtt_builder: SimpleMessageBoxBuilder<MqttMessage, NoMessage> =
+        SimpleMessageBoxBuilder::new("MQTT", 10);
+    let actor = CertificateRenewalBuilder::new(config, &mut mqtt_builder).build();
+    tokio::spawn(async move { actor.run().await });
+
+    mqtt_builder.build().with_timeout(TEST_TIMEOUT_MS)
+}
@@ -0,0 +1,134 @@
+use crate::file_list_manager::config::FileListManagerConfig;
+use async_trait::async_trait;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use std::fs::Metadata;
+use tedge_actors::Actor;
+use tedge_actors::MessageReceiver;
+use tedge_actors::RuntimeError;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::FileEntry;
+use tedge_api::commands::FileListCmd;
+use tedge_api::commands::FileType;
+use time::OffsetDateTime;
+use tracing::error;
+
+pub struct FileListManagerActor {
+    config: FileListManagerConfig,
+    message_box: SimpleMessageBox<FileListCmd, FileListCmd>,
+}
+
+#[async_trait]
+impl Actor for FileListManagerActor {
+    fn name(&self) -> &str {
+        "FileListManagerActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        while let Some(request) = self.message_box.recv().await {
+            if request.status() != CommandStatus::Scheduled {
+                // Only handle commands in the scheduled state
+                continue;
+            }
+            let executing_response = request.with_status(CommandStatus::Executing);
+            self.message_box.send(executing_response.clone()).await?;
+
+            let response = match self.list_entries(&executing_response.payload.path).await {
+                Ok(entries) => {
+                    let mut response = executing_response.with_status(CommandStatus::Successful);
+                    response.payload.entries = entries;
+                    response
+                }
+                Err(reason) => {
+                    error!("{reason}");
+                    executing_response.with_error(reason)
+                }
+            };
+            self.message_box.send(response).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FileListManagerActor {
+    pub fn new(
+        config: FileListManagerConfig,
+        message_box: SimpleMessageBox<FileListCmd, FileListCmd>,
+    ) -> Self {
+        Self {
+            config,
+            message_box,
+        }
+    }
+
+    /// List the entries of a directory, sorted by name
+    ///
+    /// The directory must be one of the allowed paths or one of their sub-directories,
+    /// once all symlinks and `..` components have been resolved.
+    async fn list_entries(&self, path: &str) -> Result<Vec<FileEntry>, String> {
+        let path = self.allowed_path(path).await?;
+
+        let mut dir = tokio::fs::read_dir(&path)
+            .await
+            .map_err(|err| format!("Failed to list {path}: {err}"))?;
+        let mut entries = Vec::new();
+        while let Some(entry) = dir
+            .next_entry()
+            .await
+            .map_err(|err| format!("Failed to list {path}: {err}"))?
+        {
+            // Symlinks are not followed, so they can be reported as such
+            let Ok(metadata) = tokio::fs::symlink_metadata(entry.path()).await else {
+                continue;
+            };
+            entries.push(FileEntry {
+                name: entry.file_name().to_string_lossy().to_string(),
+                file_type: file_type(&metadata),
+                size: metadata.len(),
+                modified: metadata.modified().ok().map(OffsetDateTime::from),
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(entries)
+    }
+
+    async fn allowed_path(&self, path: &str) -> Result<Utf8PathBuf, String> {
+        let canonical_path = canonicalize(Utf8Path::new(path))
+            .await
+            .map_err(|err| format!("Failed to access {path}: {err}"))?;
+
+        for allowed_path in &self.config.allowed_paths {
+            if let Ok(allowed_path) = canonicalize(allowed_path).await {
+                if canonical_path.starts_with(allowed_path) {
+                    return Ok(canonical_path);
+                }
+            }
+        }
+
+        Err(format!("Listing {path} is not allowed"))
+    }
+}
+
+async fn canonicalize(path: &Utf8Path) -> Result<Utf8PathBuf, String> {
+    let path = tokio::fs::canonicalize(path)
+        .await
+        .map_err(|err| err.to_string())?;
+    Utf8PathBuf::from_path_buf(path).map_err(|path| format!("Non UTF-8 path: {path:?}"))
+}
+
+fn file_type(metadata: &Metadata) -> FileType {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        FileType::Symlink
+    } else if file_type.is_dir() {
+        FileType::Directory
+    } else if file_type.is_file() {
+        FileType::File
+    } else {
+        FileType::Other
+    }
+}
@@ -0,0 +1,82 @@
+use crate::file_list_manager::actor::FileListManagerActor;
+use crate::file_list_manager::config::FileListManagerConfig;
+use tedge_actors::Builder;
+use tedge_actors::DynSender;
+use tedge_actors::LinkError;
+use tedge_actors::MappingSender;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::FileListCmd;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_api::workflow::GenericCommandData;
+use tedge_api::workflow::GenericCommandState;
+use tedge_api::workflow::OperationName;
+
+pub struct FileListManagerBuilder {
+    config: FileListManagerConfig,
+    message_box: SimpleMessageBoxBuilder<FileListCmd, FileListCmd>,
+}
+
+impl FileListManagerBuilder {
+    pub fn new(config: FileListManagerConfig) -> Self {
+        let message_box = SimpleMessageBoxBuilder::new("FileListManager", 10);
+
+        Self {
+            config,
+            message_box,
+        }
+    }
+}
+
+impl MessageSink<FileListCmd> for FileListManagerBuilder {
+    fn get_sender(&self) -> DynSender<FileListCmd> {
+        self.message_box.get_sender()
+    }
+}
+
+impl MessageSource<FileListCmd, NoConfig> for FileListManagerBuilder {
+    fn connect_sink(&mut self, config: NoConfig, peer: &impl MessageSink<FileListCmd>) {
+        self.message_box.connect_sink(config, peer)
+    }
+}
+
+impl MessageSource<GenericCommandData, NoConfig> for FileListManagerBuilder {
+    fn connect_sink(&mut self, config: NoConfig, peer: &impl MessageSink<GenericCommandData>) {
+        self.message_box.connect_sink(config, &peer.get_sender())
+    }
+}
+
+impl IntoIterator for &FileListManagerBuilder {
+    type Item = (OperationName, DynSender<GenericCommandState>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let sender =
+            MappingSender::new(self.message_box.get_sender(), |msg: GenericCommandState| {
+                msg.try_into().ok()
+            });
+        vec![(OperationType::FileList.to_string(), sender.into())].into_iter()
+    }
+}
+
+impl RuntimeRequestSink for FileListManagerBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.message_box.get_signal_sender()
+    }
+}
+
+impl Builder<FileListManagerActor> for FileListManagerBuilder {
+    type Error = LinkError;
+
+    fn try_build(self) -> Result<FileListManagerActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> FileListManagerActor {
+        FileListManagerActor::new(self.config, self.message_box.build())
+    }
+}
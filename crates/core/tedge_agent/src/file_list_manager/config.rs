@@ -0,0 +1,22 @@
+use camino::Utf8PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct FileListManagerConfig {
+    /// The directories which content can be listed, sub-directories included
+    pub allowed_paths: Vec<Utf8PathBuf>,
+}
+
+impl FileListManagerConfig {
+    pub fn from_tedge_config(tedge_config: &tedge_config::TEdgeConfig) -> FileListManagerConfig {
+        FileListManagerConfig {
+            allowed_paths: tedge_config
+                .agent
+                .file_list
+                .allowed_paths
+                .0
+                .iter()
+                .map(Utf8PathBuf::from)
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod actor;
+pub mod builder;
+pub mod config;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,116 @@
+use crate::file_list_manager::builder::FileListManagerBuilder;
+use crate::file_list_manager::config::FileListManagerConfig;
+use std::time::Duration;
+use tedge_actors::test_helpers::MessageReceiverExt;
+use tedge_actors::test_helpers::TimedMessageBox;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::DynError;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::FileListCmd;
+use tedge_api::commands::FileListCmdPayload;
+use tedge_api::commands::FileType;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_test_utils::fs::TempTedgeDir;
+
+const TEST_TIMEOUT_MS: Duration = Duration::from_millis(3000);
+
+#[tokio::test]
+async fn list_allowed_directory() -> Result<(), DynError> {
+    let temp_dir = TempTedgeDir::new();
+    let logs = temp_dir.dir("logs");
+    logs.file("syslog").with_raw_content("some log lines");
+    logs.dir("tedge");
+
+    let mut converter_box = spawn_file_list_manager(&temp_dir).await?;
+    converter_box
+        .send(file_list_command(logs.path().to_str().unwrap()))
+        .await?;
+
+    converter_box.skip(1).await;
+    let response = converter_box.recv().await.unwrap();
+    assert_eq!(response.status(), CommandStatus::Successful);
+
+    let entries: Vec<_> = response
+        .payload
+        .entries
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry.file_type))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![("syslog", FileType::File), ("tedge", FileType::Directory)]
+    );
+    assert_eq!(response.payload.entries[0].size, 14);
+    assert!(response.payload.entries[0].modified.is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reject_directory_outside_allowed_paths() -> Result<(), DynError> {
+    let temp_dir = TempTedgeDir::new();
+    temp_dir.dir("logs");
+    temp_dir.dir("secrets");
+
+    let mut converter_box = spawn_file_list_manager(&temp_dir).await?;
+    for path in [
+        temp_dir.path().join("secrets"),
+        temp_dir.path().join("logs/../secrets"),
+    ] {
+        let path = path.to_str().unwrap();
+        converter_box.send(file_list_command(path)).await?;
+
+        converter_box.skip(1).await;
+        let response = converter_box.recv().await.unwrap();
+        assert_eq!(
+            response.status(),
+            CommandStatus::Failed {
+                reason: format!("Listing {path} is not allowed")
+            }
+        );
+        assert!(response.payload.entries.is_empty());
+    }
+
+    Ok(())
+}
+
+fn file_list_command(path: &str) -> FileListCmd {
+    FileListCmd {
+        target: EntityTopicId::default_main_device(),
+        cmd_id: "1234".to_string(),
+        payload: FileListCmdPayload {
+            status: CommandStatus::Scheduled,
+            path: path.to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+async fn spawn_file_list_manager(
+    tmp_dir: &TempTedgeDir,
+) -> Result<TimedMessageBox<SimpleMessageBox<FileListCmd, FileListCmd>>, DynError> {
+    let mut converter_builder: SimpleMessageBoxBuilder<FileListCmd, FileListCmd> =
+        SimpleMessageBoxBuilder::new("Converter", 5);
+
+    let config = FileListManagerConfig {
+        allowed_paths: vec![tmp_dir.utf8_path_buf().join("logs")],
+    };
+
+    let mut file_list_actor_builder = FileListManagerBuilder::new(config);
+    converter_builder.connect_sink(NoConfig, &file_list_actor_builder);
+    file_list_actor_builder.connect_sink(NoConfig, &converter_builder);
+
+    let converter_box = converter_builder.build().with_timeout(TEST_TIMEOUT_MS);
+
+    let file_list_actor = file_list_actor_builder.build();
+    tokio::spawn(async move { file_list_actor.run().await });
+
+    Ok(converter_box)
+}
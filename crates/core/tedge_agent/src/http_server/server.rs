@@ -59,4 +59,5 @@ fn router(state: AgentState) -> Router {
     Router::new()
         .nest("/te", entity_store_router.merge(file_transfer_router))
         .merge(file_transfer_legacy_router)
+        .merge(tedge_metrics::metrics_router())
 }
@@ -19,12 +19,15 @@ use tedge_config::log_init;
 use tracing::log::warn;
 
 mod agent;
+mod certificate_renewal;
 mod device_profile_manager;
 mod entity_manager;
+mod file_list_manager;
 mod http_server;
 mod operation_file_cache;
 mod operation_workflows;
 mod restart_manager;
+mod shell_manager;
 mod software_manager;
 mod state_repository;
 mod tedge_to_te_converter;
@@ -83,6 +86,8 @@ pub struct Capabilities {
     config_update: bool,
     config_snapshot: bool,
     log_upload: bool,
+    shell: bool,
+    file_list: bool,
 }
 
 #[cfg(test)]
@@ -92,6 +97,8 @@ impl Default for Capabilities {
             config_update: true,
             config_snapshot: true,
             log_upload: true,
+            shell: true,
+            file_list: true,
         }
     }
 }
@@ -7,15 +7,24 @@
 //! so it makes sense to download it once and place it inside the File Transfer Service, so that
 //! child devices may download the files quickly on the local network.
 //!
-//! This actor, for all child devices, for operations that have `remoteUrl` property, tries to
-//! download the file from this URL, places it in the File Transfer Service, and inserts the URL to
-//! download the file from the FTS in the `tedgeUrl` property.
+//! This actor, for all child devices, for `config_update` and `firmware_update` operations that
+//! have `remoteUrl` property, and for the modules of `software_update` operations that have a
+//! `url`, tries to download the file from this URL, places it in the File Transfer Service, and
+//! inserts the URL to download the file from the FTS in the `tedgeUrl` property.
+//!
+//! The files are stored in a content-addressed cache, keyed by URL and checksum. A file with a
+//! checksum is downloaded only once, whatever the number of commands and child devices using it,
+//! the cache being kept under `agent.file_cache.max_size` by evicting the least recently used files.
 
 use async_trait::async_trait;
+use camino::Utf8Path;
 use camino::Utf8PathBuf;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::File;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::SystemTime;
 use tedge_actors::fan_in_message_type;
 use tedge_actors::Actor;
 use tedge_actors::Builder;
@@ -34,6 +43,10 @@ use tedge_actors::Service;
 use tedge_actors::SimpleMessageBoxBuilder;
 use tedge_api::commands::CommandPayload;
 use tedge_api::commands::ConfigUpdateCmdPayload;
+use tedge_api::commands::FirmwareUpdateCmdPayload;
+use tedge_api::commands::SoftwareModuleAction;
+use tedge_api::commands::SoftwareModuleItem;
+use tedge_api::commands::SoftwareUpdateCommandPayload;
 use tedge_api::mqtt_topics::Channel;
 use tedge_api::mqtt_topics::ChannelFilter;
 use tedge_api::mqtt_topics::EntityFilter;
@@ -42,15 +55,20 @@ use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::mqtt_topics::OperationType;
 use tedge_api::path::DataDir;
 use tedge_api::CommandStatus;
+use tedge_downloader_ext::DeltaInfo;
 use tedge_downloader_ext::DownloadRequest;
 use tedge_downloader_ext::DownloadResult;
 use tedge_mqtt_ext::MqttMessage;
 use tedge_mqtt_ext::Topic;
 use tedge_mqtt_ext::TopicFilter;
+use tracing::debug;
 use tracing::error;
 use tracing::info;
 use tracing::warn;
 
+#[cfg(test)]
+mod tests;
+
 type IdDownloadRequest = (String, DownloadRequest);
 type IdDownloadResult = (String, DownloadResult);
 
@@ -64,8 +82,22 @@ pub struct FileCacheActor {
     tedge_http_host: Arc<str>,
     mqtt_schema: MqttSchema,
     data_dir: DataDir,
+    device_topic_id: EntityTopicId,
+    max_size: u64,
+
+    /// The operations waiting for files to be downloaded into the cache, by command topic
+    pending_operations: HashMap<String, PendingOperation>,
 
-    pending_operations: HashMap<String, ConfigUpdateCmdPayload>,
+    /// The command topics waiting for each file being downloaded, by cache key
+    pending_downloads: HashMap<String, Vec<String>>,
+
+    /// The cache entries used by the ongoing operations, by command topic
+    entries_in_use: HashMap<String, Vec<String>>,
+}
+
+struct PendingOperation {
+    operation: CachedOperation,
+    missing_files: usize,
 }
 
 #[async_trait]
@@ -95,43 +127,37 @@ impl FileCacheActor {
             return Ok(());
         };
 
-        let Channel::Command {
-            operation: OperationType::ConfigUpdate,
-            cmd_id,
-        } = channel
-        else {
+        // The operations of the main device are given the remote URLs
+        if entity == self.device_topic_id {
             return Ok(());
-        };
+        }
 
-        let update_payload =
-            match serde_json::from_slice::<ConfigUpdateCmdPayload>(mqtt_message.payload.as_bytes())
-            {
-                Ok(payload) => payload,
-                Err(err) => {
-                    warn!("Received config update, but payload is malformed: {err}");
-                    return Ok(());
-                }
-            };
+        let Channel::Command { operation, cmd_id } = channel else {
+            return Ok(());
+        };
 
-        if update_payload.remote_url.is_empty() || update_payload.tedge_url.is_some() {
+        let payload = mqtt_message.payload.as_bytes();
+        if payload.is_empty() {
             return Ok(());
         }
 
-        match &update_payload.status {
-            CommandStatus::Executing => {
-                self.download_config_file_to_cache(&mqtt_message.topic, &update_payload)
-                    .await?;
+        let operation = match CachedOperation::from_payload(&operation, payload) {
+            Ok(Some(operation)) => operation,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                warn!("Received {operation} command, but payload is malformed: {err}");
+                return Ok(());
+            }
+        };
+
+        let topic = mqtt_message.topic.name;
+        match operation.status() {
+            CommandStatus::Executing if !operation.is_cached() => {
+                self.download_files_to_cache(topic, operation).await?;
+            }
+            CommandStatus::Successful | CommandStatus::Failed { .. } => {
+                self.release_files(&topic, &entity, &cmd_id, &operation)?;
             }
-            CommandStatus::Successful => self.delete_symlink_for_config_update(
-                &entity,
-                &update_payload.config_type,
-                &cmd_id,
-            )?,
-            CommandStatus::Failed { .. } => self.delete_symlink_for_config_update(
-                &entity,
-                &update_payload.config_type,
-                &cmd_id,
-            )?,
             _ => {}
         }
 
@@ -139,96 +165,172 @@ impl FileCacheActor {
     }
 
     async fn process_download(&mut self, download: IdDownloadResult) -> Result<(), RuntimeError> {
-        let (topic, result) = download;
+        let (cache_key, result) = download;
 
-        let Some(mut operation) = self.pending_operations.remove(&topic) else {
-            return Ok(());
-        };
-
-        let (entity, Channel::Command { cmd_id, .. }) = self
-            .mqtt_schema
-            .entity_channel_of(&topic)
-            .expect("only topics targeting config update command should be inserted")
-        else {
+        let Some(topics) = self.pending_downloads.remove(&cache_key) else {
             return Ok(());
         };
 
-        let download = match result {
-            // if cant download file, operation failed
-            Err(err) => {
-                let error_message = format!("tedge-agent failed downloading a file: {err}");
-                operation.failed(&error_message);
-                error!("{}", error_message);
-                let message = MqttMessage::new(
-                    &Topic::new_unchecked(&topic),
-                    serde_json::to_string(&operation).unwrap(),
-                );
-                self.mqtt_sender.send(message).await?;
-                return Ok(());
+        // if cant download file, operations waiting for that file failed
+        if let Err(err) = result {
+            let error_message = format!("tedge-agent failed downloading a file: {err}");
+            error!("{error_message}");
+            for topic in topics {
+                self.entries_in_use.remove(&topic);
+                if let Some(mut pending) = self.pending_operations.remove(&topic) {
+                    pending.operation.failed(&error_message);
+                    self.publish(&topic, &pending.operation).await?;
+                }
             }
-            Ok(download) => download,
-        };
-
-        self.create_symlink_for_config_update(
-            &entity,
-            &operation.config_type,
-            &cmd_id,
-            download.file_path,
-        )?;
-
-        let url_symlink_path = symlink_path(&entity, &operation.config_type, &cmd_id);
-
-        let tedge_url = format!(
-            "http://{}/te/v1/files/{}",
-            &self.tedge_http_host, url_symlink_path
-        );
+            return Ok(());
+        }
 
-        operation.tedge_url = Some(tedge_url);
+        self.evict_least_recently_used();
 
-        let mqtt_message = MqttMessage::new(
-            &Topic::new(&topic).unwrap(),
-            serde_json::to_string(&operation).unwrap(),
-        );
-        self.mqtt_sender.send(mqtt_message).await.unwrap();
+        for topic in topics {
+            let Some(pending) = self.pending_operations.get_mut(&topic) else {
+                continue;
+            };
+            if pending.missing_files > 1 {
+                pending.missing_files -= 1;
+                continue;
+            }
+            if let Some(pending) = self.pending_operations.remove(&topic) {
+                self.publish_cached_operation(&topic, pending.operation)
+                    .await?;
+            }
+        }
 
         Ok(())
     }
 
-    async fn download_config_file_to_cache(
+    async fn download_files_to_cache(
         &mut self,
-        config_update_topic: &Topic,
-        config_update_payload: &ConfigUpdateCmdPayload,
+        topic: String,
+        operation: CachedOperation,
     ) -> Result<(), RuntimeError> {
-        let remote_url = &config_update_payload.remote_url;
+        let files = operation.remote_files();
+        if files.is_empty() || self.pending_operations.contains_key(&topic) {
+            return Ok(());
+        }
 
-        let file_cache_key = sha256::digest(remote_url);
-        let dest_path = self.data_dir.cache_dir().join(file_cache_key);
-        let topic = config_update_topic.name.clone();
+        let mut cache_keys = Vec::new();
+        let mut missing_files = 0;
+        for file in files {
+            let file_cache_key = cache_key(&file.url, file.sha256.as_deref());
+            let cache_path = self.data_dir.cache_dir().join(&file_cache_key);
+
+            if let Some(waiting_operations) = self.pending_downloads.get_mut(&file_cache_key) {
+                waiting_operations.push(topic.clone());
+                missing_files += 1;
+            } else if file.sha256.is_some() && cache_path.is_file() {
+                // Without a checksum, there is no guarantee that the remote file has not changed
+                info!("Using the cached copy of `{}`", file.url);
+                touch(&cache_path);
+            } else {
+                info!("Downloading file from `{}` to cache", file.url);
+                let download_request = DownloadRequest::new(&file.url, cache_path.as_std_path())
+                    .with_verification(file.sha256, file.signature)
+                    .with_delta(file.delta);
+                self.pending_downloads
+                    .insert(file_cache_key.clone(), vec![topic.clone()]);
+                self.downloader_sender
+                    .send((file_cache_key.clone(), download_request))
+                    .await?;
+                missing_files += 1;
+            }
 
-        info!("Downloading config file from `{remote_url}` to cache");
+            cache_keys.push(file_cache_key);
+        }
+        self.entries_in_use.insert(topic.clone(), cache_keys);
+
+        if missing_files == 0 {
+            self.publish_cached_operation(&topic, operation).await
+        } else {
+            self.pending_operations.insert(
+                topic,
+                PendingOperation {
+                    operation,
+                    missing_files,
+                },
+            );
+            Ok(())
+        }
+    }
 
-        let download_request = DownloadRequest::new(remote_url, dest_path.as_std_path());
+    async fn publish_cached_operation(
+        &mut self,
+        topic: &str,
+        mut operation: CachedOperation,
+    ) -> Result<(), RuntimeError> {
+        let (entity, Channel::Command { cmd_id, .. }) = self
+            .mqtt_schema
+            .entity_channel_of(topic)
+            .expect("only topics targeting commands should be inserted")
+        else {
+            return Ok(());
+        };
 
-        self.pending_operations.insert(
-            config_update_topic.name.clone(),
-            config_update_payload.clone(),
-        );
+        let operation_type = operation.operation_type();
+        let mut tedge_urls = Vec::new();
+        for file in operation.remote_files() {
+            let file_cache_key = cache_key(&file.url, file.sha256.as_deref());
+            let url_symlink_path = symlink_path(&entity, &operation_type, &file.name, &cmd_id);
+            self.create_symlink(
+                &url_symlink_path,
+                self.data_dir.cache_dir().join(file_cache_key),
+            )?;
+
+            tedge_urls.push(format!(
+                "http://{}/te/v1/files/{}",
+                &self.tedge_http_host, url_symlink_path
+            ));
+        }
+        operation.set_tedge_urls(tedge_urls);
 
-        self.downloader_sender
-            .send((topic, download_request))
-            .await?;
+        self.publish(topic, &operation).await
+    }
 
+    async fn publish(
+        &mut self,
+        topic: &str,
+        operation: &CachedOperation,
+    ) -> Result<(), RuntimeError> {
+        let message =
+            MqttMessage::new(&Topic::new_unchecked(topic), operation.to_json()).with_retain();
+        self.mqtt_sender.send(message).await?;
         Ok(())
     }
 
-    fn create_symlink_for_config_update(
-        &self,
+    fn release_files(
+        &mut self,
+        topic: &str,
         entity_topic_id: &EntityTopicId,
-        config_type: &str,
         cmd_id: &str,
+        operation: &CachedOperation,
+    ) -> Result<(), RuntimeError> {
+        self.pending_operations.remove(topic);
+        self.entries_in_use.remove(topic);
+
+        let operation_type = operation.operation_type();
+        for file in operation.remote_files() {
+            self.delete_symlink(&symlink_path(
+                entity_topic_id,
+                &operation_type,
+                &file.name,
+                cmd_id,
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn create_symlink(
+        &self,
+        url_symlink_path: &Utf8Path,
         original: impl AsRef<Path>,
     ) -> Result<(), RuntimeError> {
-        let symlink_path = self.fs_file_transfer_symlink_path(entity_topic_id, config_type, cmd_id);
+        let symlink_path = self.data_dir.file_transfer_dir().join(url_symlink_path);
 
         if !symlink_path.is_symlink() {
             std::fs::create_dir_all(symlink_path.parent().unwrap())
@@ -239,13 +341,8 @@ impl FileCacheActor {
         Ok(())
     }
 
-    fn delete_symlink_for_config_update(
-        &self,
-        entity_topic_id: &EntityTopicId,
-        config_type: &str,
-        cmd_id: &str,
-    ) -> Result<(), RuntimeError> {
-        let symlink_path = self.fs_file_transfer_symlink_path(entity_topic_id, config_type, cmd_id);
+    fn delete_symlink(&self, url_symlink_path: &Utf8Path) -> Result<(), RuntimeError> {
+        let symlink_path = self.data_dir.file_transfer_dir().join(url_symlink_path);
 
         if let Err(e) = std::fs::remove_file(symlink_path) {
             if e.kind() != std::io::ErrorKind::NotFound {
@@ -257,22 +354,244 @@ impl FileCacheActor {
         Ok(())
     }
 
-    fn fs_file_transfer_symlink_path(
-        &self,
-        entity_topic_id: &EntityTopicId,
-        config_type: &str,
-        cmd_id: &str,
-    ) -> Utf8PathBuf {
-        let symlink_dir_path = self.data_dir.file_transfer_dir();
+    /// Remove the least recently used files from the cache, till its size is under the quota
+    ///
+    /// The files used by ongoing operations are never evicted.
+    fn evict_least_recently_used(&self) {
+        let cache_dir = self.data_dir.cache_dir();
+        let entries = match std::fs::read_dir(&cache_dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!("Failed to read the file cache {cache_dir}: {err}");
+                return;
+            }
+        };
+
+        let entries_in_use: HashSet<&str> = self
+            .entries_in_use
+            .values()
+            .flatten()
+            .chain(self.pending_downloads.keys())
+            .map(String::as_str)
+            .collect();
+
+        let mut cache_size = 0;
+        let mut evictable_files = Vec::new();
+        for entry in entries.flatten() {
+            let Ok(file_name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() || !is_cache_key(&file_name) {
+                continue;
+            }
+
+            cache_size += metadata.len();
+            if !entries_in_use.contains(file_name.as_str()) {
+                let last_used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                evictable_files.push((last_used, metadata.len(), entry.path()));
+            }
+        }
 
-        symlink_dir_path.join(symlink_path(entity_topic_id, config_type, cmd_id))
+        evictable_files.sort_by_key(|(last_used, _, _)| *last_used);
+        for (_, size, path) in evictable_files {
+            if cache_size <= self.max_size {
+                break;
+            }
+            debug!("Evicting {path:?} from the file cache");
+            match std::fs::remove_file(&path) {
+                Ok(()) => cache_size -= size,
+                Err(err) => warn!("Failed to evict {path:?} from the file cache: {err}"),
+            }
+        }
     }
 }
 
-fn symlink_path(entity_topic_id: &EntityTopicId, config_type: &str, cmd_id: &str) -> Utf8PathBuf {
+/// An operation with files that can be cached for a child device
+#[derive(Debug)]
+enum CachedOperation {
+    ConfigUpdate(ConfigUpdateCmdPayload),
+    FirmwareUpdate(FirmwareUpdateCmdPayload),
+    SoftwareUpdate(SoftwareUpdateCommandPayload),
+}
+
+/// A remote file to be downloaded into the cache
+struct RemoteFile {
+    url: String,
+    sha256: Option<String>,
+    signature: Option<String>,
+    /// A delta patch from which the file can be reconstructed using a previous version
+    delta: Option<DeltaInfo>,
+    /// The name used to publish the file on the File Transfer Service
+    name: String,
+}
+
+impl CachedOperation {
+    fn from_payload(
+        operation: &OperationType,
+        payload: &[u8],
+    ) -> Result<Option<Self>, serde_json::Error> {
+        let operation = match operation {
+            OperationType::ConfigUpdate => {
+                CachedOperation::ConfigUpdate(serde_json::from_slice(payload)?)
+            }
+            OperationType::FirmwareUpdate => {
+                CachedOperation::FirmwareUpdate(serde_json::from_slice(payload)?)
+            }
+            OperationType::SoftwareUpdate => {
+                CachedOperation::SoftwareUpdate(serde_json::from_slice(payload)?)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(operation))
+    }
+
+    fn operation_type(&self) -> OperationType {
+        match self {
+            CachedOperation::ConfigUpdate(_) => ConfigUpdateCmdPayload::operation_type(),
+            CachedOperation::FirmwareUpdate(_) => FirmwareUpdateCmdPayload::operation_type(),
+            CachedOperation::SoftwareUpdate(_) => SoftwareUpdateCommandPayload::operation_type(),
+        }
+    }
+
+    fn status(&self) -> CommandStatus {
+        match self {
+            CachedOperation::ConfigUpdate(payload) => payload.status(),
+            CachedOperation::FirmwareUpdate(payload) => payload.status(),
+            CachedOperation::SoftwareUpdate(payload) => payload.status(),
+        }
+    }
+
+    fn failed(&mut self, reason: &str) {
+        match self {
+            CachedOperation::ConfigUpdate(payload) => payload.failed(reason),
+            CachedOperation::FirmwareUpdate(payload) => payload.failed(reason),
+            CachedOperation::SoftwareUpdate(payload) => payload.failed(reason),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        match self {
+            CachedOperation::ConfigUpdate(payload) => serde_json::to_string(payload),
+            CachedOperation::FirmwareUpdate(payload) => serde_json::to_string(payload),
+            CachedOperation::SoftwareUpdate(payload) => serde_json::to_string(payload),
+        }
+        .unwrap()
+    }
+
+    /// The files to be cached, in the order expected by `set_tedge_urls`
+    fn remote_files(&self) -> Vec<RemoteFile> {
+        match self {
+            CachedOperation::ConfigUpdate(payload) if !payload.remote_url.is_empty() => {
+                vec![RemoteFile {
+                    url: payload.remote_url.clone(),
+                    sha256: payload.sha256.clone(),
+                    signature: payload.signature.clone(),
+                    delta: None,
+                    name: payload.config_type.clone(),
+                }]
+            }
+            CachedOperation::FirmwareUpdate(payload) if !payload.remote_url.is_empty() => {
+                vec![RemoteFile {
+                    url: payload.remote_url.clone(),
+                    sha256: payload.sha256.clone(),
+                    signature: payload.signature.clone(),
+                    delta: payload.delta.clone(),
+                    name: payload.name.clone(),
+                }]
+            }
+            CachedOperation::SoftwareUpdate(payload) => payload
+                .update_list
+                .iter()
+                .flat_map(|list| {
+                    list.modules.iter().filter_map(|module| {
+                        let info = module.url.as_ref().filter(|_| is_installed(module))?;
+                        Some(RemoteFile {
+                            url: info.url.clone(),
+                            sha256: info.sha256.clone(),
+                            signature: info.signature.clone(),
+                            delta: info.delta.clone(),
+                            name: format!("{}:{}", list.plugin_type, module.name),
+                        })
+                    })
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Check if the files have already been given URLs on the File Transfer Service
+    fn is_cached(&self) -> bool {
+        match self {
+            CachedOperation::ConfigUpdate(payload) => payload.tedge_url.is_some(),
+            CachedOperation::FirmwareUpdate(payload) => payload.tedge_url.is_some(),
+            CachedOperation::SoftwareUpdate(payload) => payload
+                .update_list
+                .iter()
+                .flat_map(|list| list.modules.iter())
+                .filter(|module| module.url.is_some() && is_installed(module))
+                .all(|module| module.tedge_url.is_some()),
+        }
+    }
+
+    /// Set the URLs of the cached files, given in the order of `remote_files`
+    fn set_tedge_urls(&mut self, tedge_urls: Vec<String>) {
+        let mut tedge_urls = tedge_urls.into_iter();
+        match self {
+            CachedOperation::ConfigUpdate(payload) => payload.tedge_url = tedge_urls.next(),
+            CachedOperation::FirmwareUpdate(payload) => payload.tedge_url = tedge_urls.next(),
+            CachedOperation::SoftwareUpdate(payload) => {
+                for module in payload
+                    .update_list
+                    .iter_mut()
+                    .flat_map(|list| list.modules.iter_mut())
+                    .filter(|module| module.url.is_some() && is_installed(module))
+                {
+                    module.tedge_url = tedge_urls.next();
+                }
+            }
+        }
+    }
+}
+
+fn is_installed(module: &SoftwareModuleItem) -> bool {
+    module.action == Some(SoftwareModuleAction::Install)
+}
+
+/// The name of the cache entry for a file, derived from its URL and expected checksum
+fn cache_key(url: &str, sha256: Option<&str>) -> String {
+    match sha256 {
+        None => sha256::digest(url),
+        Some(sha256) => sha256::digest(format!("{url}\n{}", sha256.trim().to_ascii_lowercase())),
+    }
+}
+
+fn is_cache_key(file_name: &str) -> bool {
+    file_name.len() == 64 && file_name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Mark a cache entry as recently used
+fn touch(path: &Utf8Path) {
+    let touch = File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    if let Err(err) = touch {
+        debug!("Failed to update the modification time of {path}: {err}");
+    }
+}
+
+fn symlink_path(
+    entity_topic_id: &EntityTopicId,
+    operation: &OperationType,
+    name: &str,
+    cmd_id: &str,
+) -> Utf8PathBuf {
     Utf8PathBuf::from(entity_topic_id.as_str().replace('/', "_"))
-        .join("config_update")
-        .join(format!("{}-{cmd_id}", config_type.replace('/', ":")))
+        .join(operation.to_string())
+        .join(format!("{}-{cmd_id}", name.replace('/', ":")))
 }
 
 pub struct FileCacheActorBuilder {
@@ -283,6 +602,8 @@ pub struct FileCacheActorBuilder {
     mqtt_schema: MqttSchema,
     tedge_http_host: Arc<str>,
     data_dir: DataDir,
+    device_topic_id: EntityTopicId,
+    max_size: u64,
 }
 
 impl FileCacheActorBuilder {
@@ -290,10 +611,12 @@ impl FileCacheActorBuilder {
         mqtt_schema: MqttSchema,
         tedge_http_host: Arc<str>,
         data_dir: DataDir,
+        device_topic_id: EntityTopicId,
+        max_size: u64,
         downloader_actor: &mut impl Service<IdDownloadRequest, IdDownloadResult>,
         mqtt_actor: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
     ) -> Self {
-        let message_box = SimpleMessageBoxBuilder::new("RestartManager", 10);
+        let message_box = SimpleMessageBoxBuilder::new("FileCacheActor", 10);
 
         let download_sender =
             downloader_actor.connect_client(message_box.get_sender().sender_clone());
@@ -308,14 +631,23 @@ impl FileCacheActorBuilder {
             mqtt_schema,
             tedge_http_host,
             data_dir,
+            device_topic_id,
+            max_size,
         }
     }
 
     fn subscriptions(mqtt_schema: &MqttSchema) -> TopicFilter {
-        mqtt_schema.topics(
-            EntityFilter::AnyEntity,
-            ChannelFilter::Command(OperationType::ConfigUpdate),
-        )
+        let mut topics = TopicFilter::empty();
+        for operation in [
+            OperationType::ConfigUpdate,
+            OperationType::FirmwareUpdate,
+            OperationType::SoftwareUpdate,
+        ] {
+            topics.add_all(
+                mqtt_schema.topics(EntityFilter::AnyEntity, ChannelFilter::Command(operation)),
+            );
+        }
+        topics
     }
 }
 
@@ -348,8 +680,12 @@ impl Builder<FileCacheActor> for FileCacheActorBuilder {
             tedge_http_host: self.tedge_http_host,
             mqtt_schema: self.mqtt_schema,
             data_dir: self.data_dir,
+            device_topic_id: self.device_topic_id,
+            max_size: self.max_size,
 
             pending_operations: HashMap::new(),
+            pending_downloads: HashMap::new(),
+            entries_in_use: HashMap::new(),
         }
     }
 }
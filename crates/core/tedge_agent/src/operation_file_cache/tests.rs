@@ -0,0 +1,362 @@
+use super::*;
+use certificate::CloudHttpConfig;
+use serde_json::json;
+use serde_json::Value;
+use std::time::Duration;
+use tedge_actors::test_helpers::FakeServerBox;
+use tedge_actors::test_helpers::FakeServerBoxBuilder;
+use tedge_actors::test_helpers::MessageReceiverExt;
+use tedge_actors::test_helpers::TimedMessageBox;
+use tedge_actors::SimpleMessageBox;
+use tedge_downloader_ext::ArtifactCache;
+use tedge_downloader_ext::DeltaFormat;
+use tedge_downloader_ext::DownloadResponse;
+use tedge_downloader_ext::DownloaderActor;
+use tedge_test_utils::fs::TempTedgeDir;
+
+const TEST_TIMEOUT_MS: Duration = Duration::from_millis(3000);
+
+const PACKAGE_URL: &str = "https://example.com/app.deb";
+const PACKAGE_SHA256: &str = "486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7";
+
+type MqttMessageBox = TimedMessageBox<SimpleMessageBox<MqttMessage, MqttMessage>>;
+type DownloaderMessageBox = TimedMessageBox<FakeServerBox<IdDownloadRequest, IdDownloadResult>>;
+
+#[tokio::test]
+async fn software_module_is_downloaded_once_for_all_child_devices() {
+    let temp_dir = TempTedgeDir::new();
+    let (mut mqtt, mut downloader) = spawn_file_cache_actor(&temp_dir, 1024);
+
+    for child in ["child01", "child02"] {
+        mqtt.send(software_update_request(child, "1234"))
+            .await
+            .unwrap();
+    }
+
+    // A single download is requested, for both commands
+    let (cache_key, request) = downloader.recv().await.unwrap();
+    assert_eq!(request.url, PACKAGE_URL);
+    assert_eq!(request.sha256.as_deref(), Some(PACKAGE_SHA256));
+    complete_download(&mut downloader, cache_key, request, "package").await;
+
+    for child in ["child01", "child02"] {
+        let message = mqtt.recv().await.unwrap();
+        assert_eq!(
+            message.topic.name,
+            format!("te/device/{child}///cmd/software_update/1234")
+        );
+        let tedge_url = format!(
+            "http://127.0.0.1:8000/te/v1/files/device_{child}__/software_update/apt:app-1234"
+        );
+        assert_eq!(module_tedge_url(&message), tedge_url);
+
+        let symlink = temp_dir
+            .utf8_path()
+            .join("file-transfer")
+            .join(tedge_url.trim_start_matches("http://127.0.0.1:8000/te/v1/files/"));
+        assert_eq!(std::fs::read_to_string(symlink).unwrap(), "package");
+    }
+
+    // A later command uses the cached file, without downloading it again
+    mqtt.send(software_update_request("child03", "5678"))
+        .await
+        .unwrap();
+    let message = mqtt.recv().await.unwrap();
+    assert_eq!(
+        module_tedge_url(&message),
+        "http://127.0.0.1:8000/te/v1/files/device_child03__/software_update/apt:app-5678"
+    );
+}
+
+#[tokio::test]
+async fn operations_waiting_for_a_file_fail_when_the_download_fails() {
+    let temp_dir = TempTedgeDir::new();
+    let (mut mqtt, mut downloader) = spawn_file_cache_actor(&temp_dir, 1024);
+
+    for child in ["child01", "child02"] {
+        mqtt.send(software_update_request(child, "1234"))
+            .await
+            .unwrap();
+    }
+
+    let (cache_key, _) = downloader.recv().await.unwrap();
+    let error = tedge_downloader_ext::DownloadError::InsufficientSpace;
+    downloader.send((cache_key, Err(error))).await.unwrap();
+
+    for _ in ["child01", "child02"] {
+        let message = mqtt.recv().await.unwrap();
+        let payload: Value = serde_json::from_slice(message.payload.as_bytes()).unwrap();
+        assert_eq!(payload["status"], "failed");
+        assert!(payload["reason"]
+            .as_str()
+            .unwrap()
+            .starts_with("tedge-agent failed downloading a file"));
+    }
+}
+
+#[tokio::test]
+async fn least_recently_used_files_are_evicted_when_the_cache_is_full() {
+    let temp_dir = TempTedgeDir::new();
+    let (mut mqtt, mut downloader) = spawn_file_cache_actor(&temp_dir, 10);
+
+    let first_config = config_update_request("child01", "1", "http://example.com/first", "1");
+    mqtt.send(first_config.clone()).await.unwrap();
+    let (first_key, request) = downloader.recv().await.unwrap();
+    complete_download(&mut downloader, first_key.clone(), request, "12345678").await;
+    mqtt.recv().await.unwrap();
+
+    // Once the operation is over, its file can be evicted
+    mqtt.send(with_status(first_config, "successful"))
+        .await
+        .unwrap();
+
+    let second_config = config_update_request("child01", "2", "http://example.com/second", "2");
+    mqtt.send(second_config).await.unwrap();
+    let (second_key, request) = downloader.recv().await.unwrap();
+    complete_download(&mut downloader, second_key.clone(), request, "87654321").await;
+    mqtt.recv().await.unwrap();
+
+    let cache_dir = temp_dir.utf8_path().join("cache");
+    assert!(!cache_dir.join(first_key).exists());
+    assert!(cache_dir.join(second_key).exists());
+    assert!(!temp_dir
+        .utf8_path()
+        .join("file-transfer/device_child01__/config_update/config-1")
+        .is_symlink());
+}
+
+#[tokio::test]
+async fn operations_of_the_main_device_are_ignored() {
+    let temp_dir = TempTedgeDir::new();
+    let (mut mqtt, mut downloader) = spawn_file_cache_actor(&temp_dir, 1024);
+
+    mqtt.send(software_update_request("main", "1234"))
+        .await
+        .unwrap();
+    mqtt.send(config_update_request(
+        "child01",
+        "1234",
+        "http://example.com/config",
+        "1",
+    ))
+    .await
+    .unwrap();
+
+    let (_, request) = downloader.recv().await.unwrap();
+    assert_eq!(request.url, "http://example.com/config");
+}
+
+#[tokio::test]
+async fn firmware_is_reconstructed_from_a_delta_patch_falling_back_to_a_full_download() {
+    let temp_dir = TempTedgeDir::new();
+    let old = b"firmware version 1.0.0".repeat(100);
+    let new = b"firmware version 1.0.1".repeat(100);
+    let newer = b"firmware version 1.0.2".repeat(100);
+    let mut patch = Vec::new();
+    qbsdiff::Bsdiff::new(&old, &new)
+        .compare(&mut patch)
+        .unwrap();
+
+    let mut server = mockito::Server::new_async().await;
+    let new_download = server
+        .mock("GET", "/firmware-1.0.1")
+        .with_body(&new)
+        .expect(0)
+        .create_async()
+        .await;
+    let _patch_download = server
+        .mock("GET", "/firmware-1.0.0-1.0.1.patch")
+        .with_body(&patch)
+        .create_async()
+        .await;
+    let newer_download = server
+        .mock("GET", "/firmware-1.0.2")
+        .with_body(&newer)
+        .expect(1)
+        .create_async()
+        .await;
+    let _corrupted_patch_download = server
+        .mock("GET", "/firmware-1.0.1-1.0.2.patch")
+        .with_body("corrupted patch")
+        .create_async()
+        .await;
+
+    // The previous version has been installed
+    let artifact_cache = Arc::new(ArtifactCache::new(
+        temp_dir.utf8_path().join("artifacts"),
+        1024 * 1024,
+    ));
+    let old_path = temp_dir.utf8_path().join("firmware-1.0.0");
+    std::fs::write(&old_path, &old).unwrap();
+    artifact_cache
+        .store(old_path.as_std_path(), &sha256::digest(old.as_slice()))
+        .await
+        .unwrap();
+    let mut mqtt = spawn_file_cache_actor_with_downloader(&temp_dir, artifact_cache);
+
+    // The new version is reconstructed from the patch
+    let request = firmware_update_request(&server.url(), "1.0.0", "1.0.1", &old, &new);
+    mqtt.send(request).await.unwrap();
+    let message = mqtt.recv().await.unwrap();
+    assert_eq!(cached_firmware(&temp_dir, &message), new);
+    new_download.assert_async().await;
+
+    // The newer version is fully downloaded, the patch being corrupted
+    let request = firmware_update_request(&server.url(), "1.0.1", "1.0.2", &new, &newer);
+    mqtt.send(request).await.unwrap();
+    let message = mqtt.recv().await.unwrap();
+    assert_eq!(cached_firmware(&temp_dir, &message), newer);
+    newer_download.assert_async().await;
+}
+
+fn spawn_file_cache_actor(
+    temp_dir: &TempTedgeDir,
+    max_size: u64,
+) -> (MqttMessageBox, DownloaderMessageBox) {
+    let mut mqtt_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage> =
+        SimpleMessageBoxBuilder::new("MQTT", 10);
+    let mut downloader_builder: FakeServerBoxBuilder<IdDownloadRequest, IdDownloadResult> =
+        FakeServerBoxBuilder::default();
+
+    let actor = FileCacheActorBuilder::new(
+        MqttSchema::new(),
+        "127.0.0.1:8000".into(),
+        DataDir::from(temp_dir.utf8_path_buf()),
+        EntityTopicId::default_main_device(),
+        max_size,
+        &mut downloader_builder,
+        &mut mqtt_builder,
+    )
+    .build();
+    tokio::spawn(async move { actor.run().await });
+
+    (
+        mqtt_builder.build().with_timeout(TEST_TIMEOUT_MS),
+        downloader_builder.build().with_timeout(TEST_TIMEOUT_MS),
+    )
+}
+
+fn spawn_file_cache_actor_with_downloader(
+    temp_dir: &TempTedgeDir,
+    artifact_cache: Arc<ArtifactCache>,
+) -> MqttMessageBox {
+    let mut mqtt_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage> =
+        SimpleMessageBoxBuilder::new("MQTT", 10);
+    let mut downloader_builder =
+        DownloaderActor::<String>::new(None, CloudHttpConfig::test_value())
+            .with_artifact_cache(Some(artifact_cache))
+            .builder();
+
+    let actor = FileCacheActorBuilder::new(
+        MqttSchema::new(),
+        "127.0.0.1:8000".into(),
+        DataDir::from(temp_dir.utf8_path_buf()),
+        EntityTopicId::default_main_device(),
+        1024 * 1024,
+        &mut downloader_builder,
+        &mut mqtt_builder,
+    )
+    .build();
+    let downloader = downloader_builder.build();
+    tokio::spawn(async move { downloader.run().await });
+    tokio::spawn(async move { actor.run().await });
+
+    mqtt_builder.build().with_timeout(TEST_TIMEOUT_MS)
+}
+
+async fn complete_download(
+    downloader: &mut DownloaderMessageBox,
+    cache_key: String,
+    request: DownloadRequest,
+    content: &str,
+) {
+    std::fs::create_dir_all(request.file_path.parent().unwrap()).unwrap();
+    std::fs::write(&request.file_path, content).unwrap();
+    let response = DownloadResponse::new(&request.url, &request.file_path);
+    downloader.send((cache_key, Ok(response))).await.unwrap();
+}
+
+fn software_update_request(child: &str, cmd_id: &str) -> MqttMessage {
+    let payload = json!({
+        "status": "executing",
+        "updateList": [{
+            "type": "apt",
+            "modules": [
+                { "name": "app", "version": "1.0", "url": PACKAGE_URL, "sha256": PACKAGE_SHA256, "action": "install" },
+                { "name": "old-app", "action": "remove" },
+            ]
+        }]
+    });
+    MqttMessage::new(
+        &Topic::new_unchecked(&format!("te/device/{child}///cmd/software_update/{cmd_id}")),
+        payload.to_string(),
+    )
+    .with_retain()
+}
+
+fn config_update_request(child: &str, cmd_id: &str, remote_url: &str, sha256: &str) -> MqttMessage {
+    let payload = json!({
+        "status": "executing",
+        "remoteUrl": remote_url,
+        "serverUrl": remote_url,
+        "type": "config",
+        "sha256": sha256.repeat(64),
+    });
+    MqttMessage::new(
+        &Topic::new_unchecked(&format!("te/device/{child}///cmd/config_update/{cmd_id}")),
+        payload.to_string(),
+    )
+    .with_retain()
+}
+
+fn firmware_update_request(
+    server_url: &str,
+    base_version: &str,
+    version: &str,
+    base: &[u8],
+    firmware: &[u8],
+) -> MqttMessage {
+    let payload = json!({
+        "status": "executing",
+        "remoteUrl": format!("{server_url}/firmware-{version}"),
+        "name": "firmware",
+        "version": version,
+        "sha256": sha256::digest(firmware),
+        "delta": {
+            "url": format!("{server_url}/firmware-{base_version}-{version}.patch"),
+            "baseSha256": sha256::digest(base),
+            "format": DeltaFormat::Bsdiff,
+        },
+    });
+    MqttMessage::new(
+        &Topic::new_unchecked(&format!(
+            "te/device/child01///cmd/firmware_update/{version}"
+        )),
+        payload.to_string(),
+    )
+    .with_retain()
+}
+
+fn cached_firmware(temp_dir: &TempTedgeDir, message: &MqttMessage) -> Vec<u8> {
+    let payload: Value = serde_json::from_slice(message.payload.as_bytes()).unwrap();
+    assert_eq!(payload["status"], "executing");
+    let tedge_url = payload["tedgeUrl"].as_str().unwrap();
+    let symlink = temp_dir
+        .utf8_path()
+        .join("file-transfer")
+        .join(tedge_url.trim_start_matches("http://127.0.0.1:8000/te/v1/files/"));
+    std::fs::read(symlink).unwrap()
+}
+
+fn with_status(message: MqttMessage, status: &str) -> MqttMessage {
+    let mut payload: Value = serde_json::from_slice(message.payload.as_bytes()).unwrap();
+    payload["status"] = status.into();
+    MqttMessage::new(&message.topic, payload.to_string()).with_retain()
+}
+
+fn module_tedge_url(message: &MqttMessage) -> String {
+    let payload: Value = serde_json::from_slice(message.payload.as_bytes()).unwrap();
+    let modules = &payload["updateList"][0]["modules"];
+    assert_eq!(modules[1].get("tedgeUrl"), None);
+    modules[0]["tedgeUrl"].as_str().unwrap().to_string()
+}
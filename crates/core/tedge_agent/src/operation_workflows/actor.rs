@@ -5,8 +5,11 @@ use async_trait::async_trait;
 use camino::Utf8PathBuf;
 use log::error;
 use log::info;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::process::Output;
 use std::time::Duration;
+use std::time::Instant;
 use tedge_actors::fan_in_message_type;
 use tedge_actors::Actor;
 use tedge_actors::ClientMessageBox;
@@ -22,6 +25,7 @@ use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::mqtt_topics::OperationType;
 use tedge_api::workflow::extract_json_output;
+use tedge_api::workflow::AwaitHandlers;
 use tedge_api::workflow::CommandBoard;
 use tedge_api::workflow::CommandId;
 use tedge_api::workflow::GenericCommandData;
@@ -30,12 +34,19 @@ use tedge_api::workflow::GenericCommandState;
 use tedge_api::workflow::GenericStateUpdate;
 use tedge_api::workflow::OperationAction;
 use tedge_api::workflow::OperationName;
+use tedge_api::workflow::ParallelIteration;
+use tedge_api::workflow::RetryPolicy;
+use tedge_api::workflow::StateExcerpt;
 use tedge_api::workflow::WorkflowExecutionError;
 use tedge_api::CommandLog;
 use tedge_file_system_ext::FsWatchEvent;
 use tedge_mqtt_ext::MqttMessage;
 use tedge_mqtt_ext::QoS;
+use tedge_mqtt_ext::SubscriptionRequest;
+use tedge_mqtt_ext::TopicFilter;
 use tedge_script_ext::Execute;
+use tedge_timer_ext::SetTimeout;
+use tedge_timer_ext::Timeout;
 use tokio::time::sleep;
 
 /// A generic command state that is published by the [TedgeOperationConverterActor]
@@ -43,7 +54,16 @@ use tokio::time::sleep;
 #[derive(Debug)]
 pub struct InternalCommandState(GenericCommandState);
 
-fan_in_message_type!(AgentInput[MqttMessage, InternalCommandState, GenericCommandData, FsWatchEvent] : Debug);
+/// A command which execution has been postponed by a schedule
+///
+/// Identified by its topic, the command is resumed from its current state when the timer fires.
+#[derive(Debug)]
+pub struct ScheduledCommand(pub String);
+
+pub type ScheduleCommand = SetTimeout<ScheduledCommand>;
+pub type ScheduledCommandDue = Timeout<ScheduledCommand>;
+
+fan_in_message_type!(AgentInput[MqttMessage, InternalCommandState, GenericCommandData, FsWatchEvent, ScheduledCommandDue] : Debug);
 
 pub struct WorkflowActor {
     pub(crate) mqtt_schema: MqttSchema,
@@ -55,7 +75,15 @@ pub struct WorkflowActor {
     pub(crate) builtin_command_dispatcher: CommandDispatcher,
     pub(crate) command_sender: DynSender<InternalCommandState>,
     pub(crate) mqtt_publisher: LoggingSender<MqttMessage>,
+    pub(crate) mqtt_subscriptions: DynSender<SubscriptionRequest>,
+    pub(crate) timer_sender: LoggingSender<ScheduleCommand>,
     pub(crate) script_runner: ClientMessageBox<Execute, std::io::Result<Output>>,
+    /// When the commands in progress have been initiated, indexed by command topic
+    pub(crate) command_started_at: HashMap<String, Instant>,
+    /// The finished sub-operations launched on other entities by a parallel iteration, indexed by command topic
+    pub(crate) remote_sub_commands: HashMap<String, GenericCommandState>,
+    /// The topics of the sub-operations launched on other entities, subscribed to while awaited
+    pub(crate) remote_sub_command_topics: HashSet<String>,
 }
 
 #[async_trait]
@@ -98,6 +126,11 @@ impl Actor for WorkflowActor {
                         self.mqtt_publisher.send(updated_capability).await?
                     }
                 }
+                AgentInput::ScheduledCommandDue(Timeout {
+                    event: ScheduledCommand(topic),
+                }) => {
+                    self.resume_scheduled_command(topic).await?;
+                }
             }
         }
         Ok(())
@@ -137,6 +170,14 @@ impl WorkflowActor {
     /// but also from *this* actor as all its state transitions are published over MQTT.
     /// Only the former will be actually processed with [Self::process_command_update].
     async fn process_mqtt_message(&mut self, message: MqttMessage) -> Result<(), RuntimeError> {
+        let Ok((entity, _)) = self.mqtt_schema.entity_channel_of(&message.topic.name) else {
+            log::error!("Unknown command channel: {}", &message.topic.name);
+            return Ok(());
+        };
+        if entity != self.device_topic_id {
+            return self.process_remote_sub_command_update(message).await;
+        }
+
         let Ok((operation, cmd_id)) = self.extract_command_identifiers(&message.topic.name) else {
             log::error!("Unknown command channel: {}", &message.topic.name);
             return Ok(());
@@ -159,6 +200,8 @@ impl WorkflowActor {
             Ok(Some(new_state)) => {
                 self.persist_command_board().await?;
                 if new_state.is_init() {
+                    self.command_started_at
+                        .insert(new_state.topic.name.clone(), Instant::now());
                     self.process_command_update(new_state.with_log_path(&log_file.path))
                         .await?;
                 }
@@ -175,6 +218,39 @@ impl WorkflowActor {
         Ok(())
     }
 
+    /// Process an update of a sub-operation launched by a parallel iteration on another entity
+    ///
+    /// Such a sub-operation is executed by the agent of the target entity,
+    /// this agent only awaiting its completion to resume the invoking command.
+    /// Any other command on another entity is ignored.
+    async fn process_remote_sub_command_update(
+        &mut self,
+        message: MqttMessage,
+    ) -> Result<(), RuntimeError> {
+        let Ok(state) = GenericCommandState::from_command_message(&message) else {
+            return Ok(());
+        };
+        if state.sub_command_index().is_none() || !state.is_finished() {
+            return Ok(());
+        }
+
+        let state = state.with_invoking_entity(&self.mqtt_schema, &self.device_topic_id);
+        let Some(invoking_command) = self
+            .workflow_repository
+            .invoking_command_state(&state)
+            .cloned()
+        else {
+            return Ok(());
+        };
+
+        self.remote_sub_commands
+            .insert(state.topic.name.clone(), state);
+        self.command_sender
+            .send(InternalCommandState(invoking_command))
+            .await?;
+        Ok(())
+    }
+
     /// Process a command state update taking any action as defined by the workflow
     ///
     /// A new state can be received:
@@ -191,6 +267,20 @@ impl WorkflowActor {
         };
         let mut log_file = self.open_command_log(&state, &operation, &cmd_id);
 
+        match self.workflow_repository.scheduled_delay(&state) {
+            Ok(None) => (),
+            Ok(Some(delay)) => {
+                return self
+                    .postpone_scheduled_command(state, delay, &mut log_file)
+                    .await
+            }
+            Err(err) => {
+                error!("{operation} operation cannot be scheduled: {err}");
+                let new_state = state.update(GenericStateUpdate::failed(err.to_string()));
+                return self.publish_command_state(new_state, &mut log_file).await;
+            }
+        }
+
         let action = match self.workflow_repository.get_action(&state) {
             Ok(action) => action,
             Err(WorkflowExecutionError::UnknownStep { operation, step }) => {
@@ -267,7 +357,7 @@ impl WorkflowActor {
                 self.publish_command_state(new_state, &mut log_file).await
             }
             OperationAction::Script(script, handlers) => {
-                let step = &state.status;
+                let step = state.status.clone();
                 info!("Processing {operation} operation {step} step with script: {script}");
 
                 let script_name = script.command.clone();
@@ -287,8 +377,20 @@ impl WorkflowActor {
                 let output = self.script_runner.await_response(command).await?;
                 log_file.log_script_output(&output).await;
 
+                let script_failed = handlers.is_failure(&output);
+                let retry = handlers.retry().cloned();
                 let new_state = state.update_with_script_output(script_name, output, handlers);
-                self.publish_command_state(new_state, &mut log_file).await
+                match retry {
+                    Some(retry) if script_failed => {
+                        self.retry_or_fail(new_state, &step, &retry, &mut log_file)
+                            .await
+                    }
+                    Some(_) => {
+                        let new_state = new_state.clear_retry_attempts(&step);
+                        self.publish_command_state(new_state, &mut log_file).await
+                    }
+                    None => self.publish_command_state(new_state, &mut log_file).await,
+                }
             }
             OperationAction::BgScript(script, handlers) => {
                 let next_state = &handlers.on_exec.status;
@@ -355,6 +457,20 @@ impl WorkflowActor {
                 let step = &state.status;
                 info!("{operation} operation {step}: waiting for sub-operation completion");
 
+                if let Some(iteration) = ParallelIteration::from_state(&state) {
+                    return self
+                        .await_parallel_sub_operations(
+                            state,
+                            operation,
+                            cmd_id,
+                            iteration,
+                            handlers,
+                            output_excerpt,
+                            &mut log_file,
+                        )
+                        .await;
+                }
+
                 // Get the sub-operation state and resume this command when the sub-operation is in a terminal state
                 if let Some(sub_state) = self
                     .workflow_repository
@@ -362,6 +478,7 @@ impl WorkflowActor {
                     .map(|s| s.to_owned())
                 {
                     let sub_operation = sub_state.operation().unwrap_or_default();
+                    let retry = self.workflow_repository.sub_operation_retry_policy(&state);
                     if sub_state.is_finished() {
                         let new_state = if sub_state.is_successful() {
                             log_file
@@ -370,9 +487,15 @@ impl WorkflowActor {
                                 ))
                                 .await;
                             let sub_cmd_output = output_excerpt.extract_value_from(&sub_state);
-                            state
+                            let new_state = state
                                 .update_with_json(sub_cmd_output)
-                                .update(handlers.on_success)
+                                .update(handlers.on_success);
+                            match &retry {
+                                None => new_state,
+                                Some((retried_state, _)) => {
+                                    new_state.clear_retry_attempts(retried_state)
+                                }
+                            }
                         } else {
                             log_file
                                 .log_info(&format!(
@@ -382,7 +505,18 @@ impl WorkflowActor {
                                 .await;
                             state.update(handlers.on_error)
                         };
-                        self.publish_command_state(new_state, &mut log_file).await?;
+                        match retry {
+                            Some((retried_state, retry)) if sub_state.is_failed() => {
+                                self.retry_or_fail(
+                                    new_state,
+                                    &retried_state,
+                                    &retry,
+                                    &mut log_file,
+                                )
+                                .await?;
+                            }
+                            _ => self.publish_command_state(new_state, &mut log_file).await?,
+                        }
                         self.publish_command_state(sub_state.clear(), &mut log_file)
                             .await?;
                     } else {
@@ -399,6 +533,12 @@ impl WorkflowActor {
 
                 Ok(())
             }
+            OperationAction::Switch(handlers) => {
+                let next_state = handlers.state_update(&state);
+                info!("Switching {operation} operation to state: {next_state}");
+                let new_state = state.update(next_state);
+                self.publish_command_state(new_state, &mut log_file).await
+            }
             OperationAction::Iterate(target_json_path, handlers) => {
                 match OperationAction::process_iterate(
                     state.clone(),
@@ -417,7 +557,302 @@ impl WorkflowActor {
                 }
                 Ok(())
             }
+            OperationAction::ParallelIterate(target_json_path, spec, input_excerpt, handlers) => {
+                let sub_operation = &spec.operation;
+                let next_state = &handlers.on_exec.status;
+                info!(
+                    "Triggering {sub_operation} commands in parallel, and moving {operation} operation to {next_state} state"
+                );
+
+                let mut iteration = match ParallelIteration::start(
+                    &state,
+                    &target_json_path,
+                    &spec,
+                    &input_excerpt,
+                ) {
+                    Ok(iteration) => iteration,
+                    Err(err) => {
+                        error!("Iteration failed due to: {err}");
+                        let new_state = state.update(GenericStateUpdate::failed(err.to_string()));
+                        return self.publish_command_state(new_state, &mut log_file).await;
+                    }
+                };
+
+                // Persist the progress of the iteration, before launching the first sub-operations
+                let sub_cmd_init_states =
+                    self.parallel_sub_command_init_states(&mut iteration, &operation, &cmd_id);
+                let new_state = iteration.inject_into(state).update(handlers.on_exec);
+                self.publish_command_state(new_state, &mut log_file).await?;
+
+                self.subscribe_to_remote_sub_commands(self.remote_topics(&sub_cmd_init_states))
+                    .await?;
+                for sub_cmd_init_state in sub_cmd_init_states {
+                    self.mqtt_publisher
+                        .send(sub_cmd_init_state.into_message())
+                        .await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Collect the outcome of the sub-operations launched in parallel by a command
+    ///
+    /// More sub-operations are launched as slots are freed by completed ones,
+    /// the command moving to its next step only when all the sub-operations are finished.
+    #[allow(clippy::too_many_arguments)]
+    async fn await_parallel_sub_operations(
+        &mut self,
+        state: GenericCommandState,
+        operation: OperationType,
+        cmd_id: CommandId,
+        mut iteration: ParallelIteration,
+        handlers: AwaitHandlers,
+        output_excerpt: StateExcerpt,
+        log_file: &mut CommandLog,
+    ) -> Result<(), RuntimeError> {
+        // Make sure the sub-operations still running on other entities are watched, notably after a restart
+        let running_remote_sub_commands = self.remote_sub_command_topics(
+            &iteration,
+            &operation,
+            &cmd_id,
+            iteration.running_items(),
+        );
+        self.subscribe_to_remote_sub_commands(running_remote_sub_commands)
+            .await?;
+
+        let remote_sub_states = self
+            .remote_sub_commands
+            .values()
+            .filter(|sub_state| sub_state.invoking_command_topic() == Some(state.topic.as_ref()));
+        let finished_sub_states: Vec<GenericCommandState> = self
+            .workflow_repository
+            .sub_command_states(&state)
+            .into_iter()
+            .filter(|sub_state| sub_state.is_finished())
+            .chain(remote_sub_states)
+            .cloned()
+            .collect();
+
+        let mut progress = false;
+        for sub_state in finished_sub_states.iter() {
+            let Some(index) = sub_state.sub_command_index() else {
+                continue;
+            };
+            let target = iteration
+                .target(index)
+                .unwrap_or_else(|| self.device_topic_id.clone());
+            if !self.is_command_of(&target, sub_state) {
+                continue;
+            }
+            let sub_cmd_output = output_excerpt.extract_value_from(sub_state);
+            if iteration.record(index, sub_state, sub_cmd_output) {
+                let sub_operation = &iteration.operation;
+                log_file
+                    .log_info(&format!(
+                        "=> {sub_operation} sub-operation #{index} {}",
+                        sub_state.status
+                    ))
+                    .await;
+                progress = true;
+            }
+        }
+
+        let sub_cmd_init_states =
+            self.parallel_sub_command_init_states(&mut iteration, &operation, &cmd_id);
+        if iteration.is_complete() {
+            let launched_remote_sub_commands = self.remote_sub_command_topics(
+                &iteration,
+                &operation,
+                &cmd_id,
+                0..iteration.launched,
+            );
+            self.unsubscribe_from_remote_sub_commands(launched_remote_sub_commands)
+                .await?;
+            let new_state = iteration.finalize(state, handlers);
+            self.publish_command_state(new_state, log_file).await?;
+        } else if progress || !sub_cmd_init_states.is_empty() {
+            let running = iteration.running();
+            log_file
+                .log_info(&format!(
+                    "=> {running} {} sub-operation(s) still running",
+                    iteration.operation
+                ))
+                .await;
+            let new_state = iteration.inject_into(state);
+            self.persist_command_state(new_state, log_file).await?;
+            self.subscribe_to_remote_sub_commands(self.remote_topics(&sub_cmd_init_states))
+                .await?;
+            for sub_cmd_init_state in sub_cmd_init_states {
+                self.mqtt_publisher
+                    .send(sub_cmd_init_state.into_message())
+                    .await?;
+            }
+        }
+
+        // The outcomes being persisted, the finished sub-operations can be cleared
+        for sub_state in finished_sub_states {
+            if self.is_command_of(&self.device_topic_id, &sub_state) {
+                self.publish_command_state(sub_state.clear(), log_file)
+                    .await?;
+            } else {
+                let topic = sub_state.topic.name.clone();
+                self.remote_sub_commands.remove(&topic);
+                self.mqtt_publisher
+                    .send(sub_state.clear().into_message())
+                    .await?;
+                self.unsubscribe_from_remote_sub_commands(vec![topic])
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The topics of the sub-operations launched by a parallel iteration on other entities
+    fn remote_sub_command_topics(
+        &self,
+        iteration: &ParallelIteration,
+        operation: &OperationType,
+        cmd_id: &CommandId,
+        indexes: impl IntoIterator<Item = usize>,
+    ) -> Vec<String> {
+        indexes
+            .into_iter()
+            .filter_map(|index| {
+                let target = iteration
+                    .target(index)
+                    .filter(|target| target != &self.device_topic_id)?;
+                let sub_cmd_state = GenericCommandState::parallel_sub_command_init_state(
+                    &self.mqtt_schema,
+                    &self.device_topic_id,
+                    &target,
+                    operation.clone(),
+                    cmd_id.clone(),
+                    iteration.operation.clone(),
+                    index,
+                );
+                Some(sub_cmd_state.topic.name)
+            })
+            .collect()
+    }
+
+    /// The topics of the given sub-operations which are not commands of the device
+    fn remote_topics(&self, sub_cmd_states: &[GenericCommandState]) -> Vec<String> {
+        sub_cmd_states
+            .iter()
+            .filter(|sub_state| !self.is_command_of(&self.device_topic_id, sub_state))
+            .map(|sub_state| sub_state.topic.name.clone())
+            .collect()
+    }
+
+    /// Subscribe to sub-operations launched on other entities, unless already subscribed
+    async fn subscribe_to_remote_sub_commands(
+        &mut self,
+        topics: Vec<String>,
+    ) -> Result<(), RuntimeError> {
+        let mut new_topics = TopicFilter::empty();
+        for topic in topics {
+            if self.remote_sub_command_topics.insert(topic.clone()) {
+                new_topics.add_unchecked(&topic);
+            }
+        }
+        if !new_topics.patterns().is_empty() {
+            self.mqtt_subscriptions
+                .send(SubscriptionRequest::Subscribe(new_topics))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Unsubscribe from sub-operations launched on other entities, once no more awaited
+    async fn unsubscribe_from_remote_sub_commands(
+        &mut self,
+        topics: Vec<String>,
+    ) -> Result<(), RuntimeError> {
+        let mut old_topics = TopicFilter::empty();
+        for topic in topics {
+            if self.remote_sub_command_topics.remove(&topic) {
+                old_topics.add_unchecked(&topic);
+            }
+        }
+        if !old_topics.patterns().is_empty() {
+            self.mqtt_subscriptions
+                .send(SubscriptionRequest::Unsubscribe(old_topics))
+                .await?;
         }
+        Ok(())
+    }
+
+    /// Return true if the given command is a command of the given entity
+    fn is_command_of(&self, entity: &EntityTopicId, command: &GenericCommandState) -> bool {
+        self.mqtt_schema
+            .entity_channel_of(command.topic.as_ref())
+            .is_ok_and(|(command_entity, _)| &command_entity == entity)
+    }
+
+    /// Create the init states of the next sub-operations to be launched by a parallel iteration
+    fn parallel_sub_command_init_states(
+        &self,
+        iteration: &mut ParallelIteration,
+        operation: &OperationType,
+        cmd_id: &CommandId,
+    ) -> Vec<GenericCommandState> {
+        iteration
+            .next_items()
+            .into_iter()
+            .map(|(index, sub_cmd_input)| {
+                let target = iteration
+                    .target(index)
+                    .unwrap_or_else(|| self.device_topic_id.clone());
+                GenericCommandState::parallel_sub_command_init_state(
+                    &self.mqtt_schema,
+                    &self.device_topic_id,
+                    &target,
+                    operation.clone(),
+                    cmd_id.clone(),
+                    iteration.operation.clone(),
+                    index,
+                )
+                .update_with_json(sub_cmd_input)
+                .update_with_json(GenericStateUpdate::init_payload())
+            })
+            .collect()
+    }
+
+    /// Postpone the execution of a command till the time given by its schedule
+    ///
+    /// The command is kept in its `scheduled` state, which has already been persisted.
+    /// On restart, the agent reloads the command and sets a new timer.
+    async fn postpone_scheduled_command(
+        &mut self,
+        state: GenericCommandState,
+        delay: Duration,
+        log_file: &mut CommandLog,
+    ) -> Result<(), RuntimeError> {
+        let topic = state.topic.name.clone();
+        info!("Postponing {topic} for {}s", delay.as_secs());
+        log_file
+            .log_info(&format!(
+                "=> {} postponed for {}s",
+                state.status,
+                delay.as_secs()
+            ))
+            .await;
+        self.timer_sender
+            .send(ScheduleCommand::new(delay, ScheduledCommand(topic)))
+            .await?;
+        Ok(())
+    }
+
+    /// Resume a scheduled or retried command when its timer fires
+    ///
+    /// The command is resumed from its current state, if not cleared in the meantime.
+    async fn resume_scheduled_command(&mut self, topic: String) -> Result<(), RuntimeError> {
+        let Some(state) = self.workflow_repository.get_state(&topic).cloned() else {
+            info!("Ignoring scheduled command {topic} which has been cleared");
+            return Ok(());
+        };
+        self.process_command_update(state).await
     }
 
     /// Pre-process an update received from a builtin operation actor
@@ -496,6 +931,7 @@ impl WorkflowActor {
             error!("Fail to persist workflow operation state: {err}");
         }
         self.persist_command_board().await?;
+        self.record_command_duration(&new_state);
         if !new_state.is_cleared() {
             log_file.log_next_step(&new_state.status).await;
             self.command_sender
@@ -506,6 +942,88 @@ impl WorkflowActor {
         Ok(())
     }
 
+    fn record_command_duration(&mut self, state: &GenericCommandState) {
+        if state.is_cleared() {
+            self.command_started_at.remove(&state.topic.name);
+        } else if state.is_finished() {
+            if let (Some(started_at), Some(operation)) = (
+                self.command_started_at.remove(&state.topic.name),
+                state.operation(),
+            ) {
+                tedge_metrics::record_command_duration(&operation, started_at.elapsed());
+            }
+        }
+    }
+
+    /// Persist and publish a command state, without processing it right away
+    ///
+    /// This is used when the command is waiting for some event to resume,
+    /// as a delay or the completion of sub-operations.
+    async fn persist_command_state(
+        &mut self,
+        new_state: GenericCommandState,
+        log_file: &mut CommandLog,
+    ) -> Result<(), RuntimeError> {
+        if let Err(err) = self
+            .workflow_repository
+            .apply_internal_update(new_state.clone())
+        {
+            error!("Fail to persist workflow operation state: {err}");
+        }
+        self.persist_command_board().await?;
+        log_file.log_next_step(&new_state.status).await;
+        self.mqtt_publisher.send(new_state.into_message()).await?;
+        Ok(())
+    }
+
+    /// Retry the action of the `retried_state`, unless all the attempts have been exhausted
+    ///
+    /// The command is moved back to the retried state, but that state is processed only after a delay,
+    /// when the timer fires and the command is resumed as a scheduled command.
+    /// Meanwhile, the echo of the retried state published over MQTT is ignored.
+    /// The number of attempts being persisted along the command state,
+    /// the count survives a restart of the agent which then resumes the retried state without delay.
+    async fn retry_or_fail(
+        &mut self,
+        failed_state: GenericCommandState,
+        retried_state: &str,
+        retry: &RetryPolicy,
+        log_file: &mut CommandLog,
+    ) -> Result<(), RuntimeError> {
+        let (new_state, delay) = failed_state.retry_or_fail(retried_state, retry);
+        let Some(delay) = delay else {
+            log_file
+                .log_info(&format!(
+                    "=> {retried_state} failed after {} attempts",
+                    retry.max_attempts
+                ))
+                .await;
+            return self.publish_command_state(new_state, log_file).await;
+        };
+
+        let attempt = new_state.retry_attempts(retried_state) + 1;
+        info!(
+            "Retrying {retried_state} step in {}s (attempt {attempt}/{})",
+            delay.as_secs(),
+            retry.max_attempts
+        );
+        log_file
+            .log_info(&format!(
+                "=> retrying in {}s (attempt {attempt}/{})",
+                delay.as_secs(),
+                retry.max_attempts
+            ))
+            .await;
+
+        let topic = new_state.topic.name.clone();
+        self.persist_command_state(new_state, log_file).await?;
+        self.workflow_repository.mark_pending_retry(&topic);
+        self.timer_sender
+            .send(ScheduleCommand::new(delay, ScheduledCommand(topic)))
+            .await?;
+        Ok(())
+    }
+
     /// Reload from disk the current state of the pending command requests
     async fn load_command_board(&mut self) -> Result<(), RuntimeError> {
         match self.state_repository.load().await {
@@ -1,11 +1,15 @@
 use crate::operation_workflows::actor::AgentInput;
 use crate::operation_workflows::actor::InternalCommandState;
+use crate::operation_workflows::actor::ScheduleCommand;
+use crate::operation_workflows::actor::ScheduledCommandDue;
 use crate::operation_workflows::actor::WorkflowActor;
 use crate::operation_workflows::config::OperationConfig;
 use crate::operation_workflows::message_box::CommandDispatcher;
 use crate::operation_workflows::persist::WorkflowRepository;
 use crate::state_repository::state::agent_state_dir;
 use crate::state_repository::state::AgentStateRepository;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::process::Output;
 use tedge_actors::futures::channel::mpsc;
@@ -30,7 +34,9 @@ use tedge_api::workflow::GenericCommandData;
 use tedge_api::workflow::GenericCommandState;
 use tedge_api::workflow::OperationName;
 use tedge_file_system_ext::FsWatchEvent;
+use tedge_mqtt_ext::DynSubscriptionsSource;
 use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::SubscriptionRequest;
 use tedge_mqtt_ext::TopicFilter;
 use tedge_script_ext::Execute;
 
@@ -41,6 +47,8 @@ pub struct WorkflowActorBuilder {
     command_dispatcher: CommandDispatcher,
     command_sender: DynSender<InternalCommandState>,
     mqtt_publisher: LoggingSender<MqttMessage>,
+    mqtt_subscriptions: DynSender<SubscriptionRequest>,
+    timer_sender: LoggingSender<ScheduleCommand>,
     script_runner: ClientMessageBox<Execute, std::io::Result<Output>>,
     signal_sender: mpsc::Sender<RuntimeRequest>,
 }
@@ -48,9 +56,10 @@ pub struct WorkflowActorBuilder {
 impl WorkflowActorBuilder {
     pub fn new(
         config: OperationConfig,
-        mqtt_actor: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+        mqtt_actor: &mut (impl DynSubscriptionsSource + MessageSink<MqttMessage>),
         script_runner: &mut impl Service<Execute, std::io::Result<Output>>,
         fs_notify: &mut impl MessageSource<FsWatchEvent, PathBuf>,
+        timer: &mut impl Service<ScheduleCommand, ScheduledCommandDue>,
     ) -> Self {
         let (input_sender, input_receiver) = mpsc::unbounded();
         let (signal_sender, signal_receiver) = mpsc::channel(10);
@@ -66,7 +75,7 @@ impl WorkflowActorBuilder {
         let command_sender = input_sender.sender_clone();
 
         let mqtt_publisher = mqtt_actor.get_sender();
-        mqtt_actor.connect_sink(
+        let mqtt_subscriptions = mqtt_actor.connect_dynamic_sink(
             Self::subscriptions(&config.mqtt_schema, &config.device_topic_id),
             &input_sender,
         );
@@ -76,6 +85,9 @@ impl WorkflowActorBuilder {
 
         fs_notify.connect_sink(config.operations_dir.clone().into(), &input_sender);
 
+        let timer_sender = timer.connect_client(input_sender.sender_clone());
+        let timer_sender = LoggingSender::new("Timer".into(), timer_sender);
+
         Self {
             config,
             input_sender,
@@ -83,6 +95,8 @@ impl WorkflowActorBuilder {
             command_dispatcher,
             command_sender,
             mqtt_publisher,
+            mqtt_subscriptions,
+            timer_sender,
             signal_sender,
             script_runner,
         }
@@ -102,6 +116,10 @@ impl WorkflowActorBuilder {
         }
     }
 
+    /// The commands of the device
+    ///
+    /// The sub-operations launched on other entities by parallel iterations
+    /// are subscribed to at runtime, only while awaited.
     pub fn subscriptions(mqtt_schema: &MqttSchema, device_topic_id: &EntityTopicId) -> TopicFilter {
         mqtt_schema.topics(EntityFilter::Entity(device_topic_id), AnyCommand)
     }
@@ -137,8 +155,13 @@ impl Builder<WorkflowActor> for WorkflowActorBuilder {
             input_receiver: self.input_receiver,
             builtin_command_dispatcher: self.command_dispatcher,
             mqtt_publisher: self.mqtt_publisher,
+            mqtt_subscriptions: self.mqtt_subscriptions,
+            timer_sender: self.timer_sender,
             command_sender: self.command_sender,
             script_runner: self.script_runner,
+            command_started_at: HashMap::new(),
+            remote_sub_commands: HashMap::new(),
+            remote_sub_command_topics: HashSet::new(),
         }
     }
 }
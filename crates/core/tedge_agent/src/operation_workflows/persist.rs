@@ -2,6 +2,7 @@ use anyhow::Context;
 use camino::Utf8Path;
 use camino::Utf8PathBuf;
 use std::collections::HashMap;
+use std::time::Duration;
 use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::mqtt_topics::OperationType;
@@ -13,6 +14,9 @@ use tedge_api::workflow::IllFormedOperationWorkflow;
 use tedge_api::workflow::OperationAction;
 use tedge_api::workflow::OperationName;
 use tedge_api::workflow::OperationWorkflow;
+use tedge_api::workflow::RetryPolicy;
+use tedge_api::workflow::ScheduleError;
+use tedge_api::workflow::StateName;
 use tedge_api::workflow::WorkflowExecutionError;
 use tedge_api::workflow::WorkflowSupervisor;
 use tedge_api::workflow::WorkflowVersion;
@@ -439,6 +443,10 @@ impl WorkflowRepository {
         self.workflows.apply_internal_update(new_command_state)
     }
 
+    pub fn mark_pending_retry(&mut self, command: &str) {
+        self.workflows.mark_pending_retry(command)
+    }
+
     pub fn get_action(
         &self,
         command_state: &GenericCommandState,
@@ -446,6 +454,25 @@ impl WorkflowRepository {
         self.workflows.get_action(command_state)
     }
 
+    pub fn sub_operation_retry_policy(
+        &self,
+        command_state: &GenericCommandState,
+    ) -> Option<(StateName, RetryPolicy)> {
+        self.workflows.sub_operation_retry_policy(command_state)
+    }
+
+    pub fn get_state(&self, command: &str) -> Option<&GenericCommandState> {
+        self.workflows.get_state(command)
+    }
+
+    pub fn scheduled_delay(
+        &self,
+        command_state: &GenericCommandState,
+    ) -> Result<Option<Duration>, ScheduleError> {
+        self.workflows
+            .scheduled_delay(command_state, time::OffsetDateTime::now_utc())
+    }
+
     pub fn root_invoking_command_state(
         &self,
         leaf_command: &GenericCommandState,
@@ -467,6 +494,13 @@ impl WorkflowRepository {
         self.workflows.sub_command_state(command_state)
     }
 
+    pub fn sub_command_states(
+        &self,
+        command_state: &GenericCommandState,
+    ) -> Vec<&GenericCommandState> {
+        self.workflows.sub_command_states(command_state)
+    }
+
     pub fn adapt_builtin_response(
         &self,
         command_state: GenericCommandState,
@@ -1,3 +1,5 @@
+use crate::operation_workflows::actor::ScheduleCommand;
+use crate::operation_workflows::actor::ScheduledCommandDue;
 use crate::operation_workflows::builder::WorkflowActorBuilder;
 use crate::operation_workflows::config::OperationConfig;
 use crate::software_manager::actor::SoftwareCommand;
@@ -108,6 +110,7 @@ async fn convert_incoming_software_update_request() -> Result<(), DynError> {
         version: Some("0.0.1".into()),
         action: Some(SoftwareModuleAction::Install),
         url: None,
+        tedge_url: None,
         reason: None,
     };
     let debian_list = SoftwareRequestResponseSoftwareList {
@@ -125,6 +128,7 @@ async fn convert_incoming_software_update_request() -> Result<(), DynError> {
                 status: CommandStatus::Scheduled,
                 update_list: vec![debian_list],
                 failures: vec![],
+                rolled_back: vec![],
                 log_path: Some(
                     tmp_dir
                         .path()
@@ -179,6 +183,38 @@ async fn convert_incoming_restart_request() -> Result<(), DynError> {
     Ok(())
 }
 
+#[tokio::test]
+async fn postpone_scheduled_restart_request() -> Result<(), DynError> {
+    let target_device = "device/child-foo//";
+
+    // Spawn incoming mqtt message converter
+    let TestHandler {
+        mut timer_box,
+        mut mqtt_box,
+        ..
+    } = spawn_mqtt_operation_converter(target_device).await?;
+    skip_capability_messages(&mut mqtt_box, target_device).await;
+
+    // Simulate a Restart MQTT message scheduled in the future
+    let topic = format!("te/{target_device}/cmd/restart/later");
+    let mqtt_message = MqttMessage::new(
+        &Topic::new_unchecked(&topic),
+        r#"{"status": "init", "scheduledAt": "2999-01-01T00:00:00Z"}"#,
+    );
+    mqtt_box.send(mqtt_message).await?;
+
+    // The command is moved to the scheduled state
+    assert_received_contains_str(&mut mqtt_box, [(topic.as_str(), r#""status":"scheduled""#)])
+        .await;
+
+    // But its execution is postponed
+    let RequestEnvelope { request, .. } = timer_box.recv().await.expect("a timer request");
+    assert_eq!(request.event.0, topic);
+    assert!(request.duration > Duration::from_secs(365 * 24 * 3600));
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn convert_outgoing_software_list_response() -> Result<(), DynError> {
     // Spawn outgoing mqtt message converter
@@ -345,6 +381,9 @@ struct TestHandler {
     mqtt_box: TimedMessageBox<SimpleMessageBox<MqttMessage, MqttMessage>>,
     software_box: TimedMessageBox<SimpleMessageBox<SoftwareCommand, SoftwareCommand>>,
     restart_box: TimedMessageBox<SimpleMessageBox<RestartCommand, RestartCommand>>,
+    timer_box: TimedMessageBox<
+        SimpleMessageBox<RequestEnvelope<ScheduleCommand, ScheduledCommandDue>, NoMessage>,
+    >,
 }
 
 async fn spawn_mqtt_operation_converter(device_topic_id: &str) -> Result<TestHandler, DynError> {
@@ -358,6 +397,10 @@ async fn spawn_mqtt_operation_converter(device_topic_id: &str) -> Result<TestHan
     > = SimpleMessageBoxBuilder::new("Script", 5);
     let mut inotify_builder: SimpleMessageBoxBuilder<NoMessage, FsWatchEvent> =
         SimpleMessageBoxBuilder::new("Inotify", 5);
+    let mut timer_builder: SimpleMessageBoxBuilder<
+        RequestEnvelope<ScheduleCommand, ScheduledCommandDue>,
+        NoMessage,
+    > = SimpleMessageBoxBuilder::new("Timer", 5);
 
     let tmp_dir = tempfile::TempDir::new().unwrap();
     let tmp_path = Utf8Path::from_path(tmp_dir.path()).unwrap();
@@ -374,6 +417,7 @@ async fn spawn_mqtt_operation_converter(device_topic_id: &str) -> Result<TestHan
         &mut mqtt_builder,
         &mut script_builder,
         &mut inotify_builder,
+        &mut timer_builder,
     );
     converter_actor_builder.register_builtin_operation(&mut restart_builder);
     converter_actor_builder.register_builtin_operation(&mut software_builder);
@@ -381,6 +425,7 @@ async fn spawn_mqtt_operation_converter(device_topic_id: &str) -> Result<TestHan
     let software_box = software_builder.0.build().with_timeout(TEST_TIMEOUT_MS);
     let restart_box = restart_builder.0.build().with_timeout(TEST_TIMEOUT_MS);
     let mqtt_box = mqtt_builder.build().with_timeout(TEST_TIMEOUT_MS);
+    let timer_box = timer_builder.build().with_timeout(TEST_TIMEOUT_MS);
 
     let converter_actor = converter_actor_builder.build();
     tokio::spawn(async move { converter_actor.run().await });
@@ -390,6 +435,7 @@ async fn spawn_mqtt_operation_converter(device_topic_id: &str) -> Result<TestHan
         mqtt_box,
         software_box,
         restart_box,
+        timer_box,
     })
 }
 
@@ -0,0 +1,231 @@
+use crate::shell_manager::builder::ShellUploadRequest;
+use crate::shell_manager::builder::ShellUploadResult;
+use crate::shell_manager::config::ShellManagerConfig;
+use async_trait::async_trait;
+use nix::sys::signal::killpg;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use std::process::Output;
+use std::process::Stdio;
+use std::time::Duration;
+use tedge_actors::Actor;
+use tedge_actors::ClientMessageBox;
+use tedge_actors::MessageReceiver;
+use tedge_actors::RuntimeError;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::ShellCmd;
+use tedge_uploader_ext::UploadRequest;
+use tokio::process::Command;
+use tokio::time::timeout;
+use tracing::error;
+use tracing::info;
+
+/// The maximum size of the stdout and stderr returned in the command payload
+const MAX_INLINE_OUTPUT: usize = 16 * 1024;
+
+/// The delay given to a timed-out command to exit on SIGTERM, before being sent a SIGKILL
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+pub struct ShellManagerActor {
+    config: ShellManagerConfig,
+    message_box: SimpleMessageBox<ShellCmd, ShellCmd>,
+    uploader: ClientMessageBox<ShellUploadRequest, ShellUploadResult>,
+}
+
+#[async_trait]
+impl Actor for ShellManagerActor {
+    fn name(&self) -> &str {
+        "ShellManagerActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        while let Some(request) = self.message_box.recv().await {
+            if request.status() != CommandStatus::Scheduled {
+                // Only handle commands in the scheduled state
+                continue;
+            }
+            let executing_response = request.with_status(CommandStatus::Executing);
+            self.message_box.send(executing_response.clone()).await?;
+
+            let response = self.handle_shell_operation(executing_response).await;
+            self.message_box.send(response).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ShellManagerActor {
+    pub fn new(
+        config: ShellManagerConfig,
+        message_box: SimpleMessageBox<ShellCmd, ShellCmd>,
+        uploader: ClientMessageBox<ShellUploadRequest, ShellUploadResult>,
+    ) -> Self {
+        Self {
+            config,
+            message_box,
+            uploader,
+        }
+    }
+
+    async fn handle_shell_operation(&mut self, mut command: ShellCmd) -> ShellCmd {
+        let max_duration = command
+            .payload
+            .timeout
+            .map(Duration::from_secs)
+            .map_or(self.config.timeout, |timeout| {
+                timeout.min(self.config.timeout)
+            });
+
+        info!("Running shell command: {}", command.payload.command);
+        let output = match self
+            .run_command(&command.payload.command, max_duration)
+            .await
+        {
+            Ok(output) => output,
+            Err(reason) => {
+                error!("{reason}");
+                return command.with_error(reason);
+            }
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let exit_code = output.status.code();
+        command.payload.exit_code = exit_code;
+
+        if let Some(tedge_url) = command.payload.tedge_url.clone() {
+            if let Err(err) = self
+                .upload_output(&command, &tedge_url, &stdout, &stderr)
+                .await
+            {
+                let reason = format!("Failed to upload the command output to {tedge_url}: {err}");
+                error!("{reason}");
+                return command.with_error(reason);
+            }
+        } else {
+            command.payload.stdout = Some(truncate_output(&stdout));
+            command.payload.stderr = Some(truncate_output(&stderr));
+        }
+
+        match exit_code {
+            Some(0) => command.with_status(CommandStatus::Successful),
+            Some(code) => command.with_error(format!("Command exited with status {code}")),
+            None => command.with_error("Command interrupted by a signal".to_string()),
+        }
+    }
+
+    /// Run a command line with `sh`, as the configured user
+    ///
+    /// Commands are refused when no user has been configured.
+    /// On timeout, the whole process group of the command is terminated,
+    /// with a SIGTERM and then a SIGKILL if still running after a grace period.
+    async fn run_command(
+        &self,
+        command_line: &str,
+        max_duration: Duration,
+    ) -> Result<Output, String> {
+        let Some(user) = &self.config.user else {
+            return Err(
+                "Shell commands are disabled: agent.shell.user must be set to the user running the commands"
+                    .to_string(),
+            );
+        };
+        let mut command = if is_current_user(user) {
+            let mut command = Command::new("sh");
+            command.args(["-c", command_line]);
+            command
+        } else {
+            let mut command = Command::new("sudo");
+            command.args(["-n", "-u", user, "--", "sh", "-c", command_line]);
+            command
+        };
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .kill_on_drop(true);
+
+        let child = command
+            .spawn()
+            .map_err(|err| format!("Failed to run {command_line:?}: {err}"))?;
+        let process_group = child.id().map(|pid| Pid::from_raw(pid as nix::libc::pid_t));
+        let output = child.wait_with_output();
+        tokio::pin!(output);
+        match timeout(max_duration, &mut output).await {
+            Ok(Ok(output)) => Ok(output),
+            Ok(Err(err)) => Err(format!("Failed to run {command_line:?}: {err}")),
+            Err(_) => {
+                if let Some(process_group) = process_group {
+                    let _ = killpg(process_group, Signal::SIGTERM);
+                    if timeout(KILL_GRACE_PERIOD, &mut output).await.is_err() {
+                        let _ = killpg(process_group, Signal::SIGKILL);
+                    }
+                }
+                Err(format!(
+                    "Command still running after {} seconds",
+                    max_duration.as_secs()
+                ))
+            }
+        }
+    }
+
+    async fn upload_output(
+        &mut self,
+        command: &ShellCmd,
+        tedge_url: &str,
+        stdout: &str,
+        stderr: &str,
+    ) -> Result<(), String> {
+        let exit_code = command
+            .payload
+            .exit_code
+            .map_or("none".to_string(), |code| code.to_string());
+        let content = format!(
+            "$ {}\nexit code: {exit_code}\n----- stdout -----\n{stdout}\n----- stderr -----\n{stderr}\n",
+            command.payload.command
+        );
+        let output_path = self
+            .config
+            .tmp_dir
+            .join(format!("shell-{}.log", command.cmd_id));
+        tokio::fs::write(&output_path, content)
+            .await
+            .map_err(|err| format!("Failed to write {output_path}: {err}"))?;
+
+        let upload_request = UploadRequest::new(tedge_url, &output_path);
+        let result = self
+            .uploader
+            .await_response((command.cmd_id.clone(), upload_request))
+            .await;
+        let _ = tokio::fs::remove_file(&output_path).await;
+
+        match result {
+            Ok((_, Ok(_))) => Ok(()),
+            Ok((_, Err(err))) => Err(err.to_string()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+/// Check if the given user is the one running the agent, in which case sudo is not required
+fn is_current_user(user: &str) -> bool {
+    nix::unistd::User::from_uid(nix::unistd::getuid())
+        .ok()
+        .flatten()
+        .is_some_and(|current_user| current_user.name == user)
+}
+
+/// Truncate an output too large to be returned in the command payload
+fn truncate_output(output: &str) -> String {
+    if output.len() <= MAX_INLINE_OUTPUT {
+        return output.to_string();
+    }
+    let mut end = MAX_INLINE_OUTPUT;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n[output truncated]", &output[..end])
+}
@@ -0,0 +1,95 @@
+use crate::shell_manager::actor::ShellManagerActor;
+use crate::shell_manager::config::ShellManagerConfig;
+use tedge_actors::Builder;
+use tedge_actors::ClientMessageBox;
+use tedge_actors::DynSender;
+use tedge_actors::LinkError;
+use tedge_actors::MappingSender;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Service;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::ShellCmd;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_api::workflow::GenericCommandData;
+use tedge_api::workflow::GenericCommandState;
+use tedge_api::workflow::OperationName;
+use tedge_uploader_ext::UploadRequest;
+use tedge_uploader_ext::UploadResult;
+
+pub type ShellUploadRequest = (String, UploadRequest);
+pub type ShellUploadResult = (String, UploadResult);
+
+pub struct ShellManagerBuilder {
+    config: ShellManagerConfig,
+    message_box: SimpleMessageBoxBuilder<ShellCmd, ShellCmd>,
+    uploader: ClientMessageBox<ShellUploadRequest, ShellUploadResult>,
+}
+
+impl ShellManagerBuilder {
+    pub fn new(
+        config: ShellManagerConfig,
+        uploader_actor: &mut impl Service<ShellUploadRequest, ShellUploadResult>,
+    ) -> Self {
+        let message_box = SimpleMessageBoxBuilder::new("ShellManager", 10);
+        let uploader = ClientMessageBox::new(uploader_actor);
+
+        Self {
+            config,
+            message_box,
+            uploader,
+        }
+    }
+}
+
+impl MessageSink<ShellCmd> for ShellManagerBuilder {
+    fn get_sender(&self) -> DynSender<ShellCmd> {
+        self.message_box.get_sender()
+    }
+}
+
+impl MessageSource<ShellCmd, NoConfig> for ShellManagerBuilder {
+    fn connect_sink(&mut self, config: NoConfig, peer: &impl MessageSink<ShellCmd>) {
+        self.message_box.connect_sink(config, peer)
+    }
+}
+
+impl MessageSource<GenericCommandData, NoConfig> for ShellManagerBuilder {
+    fn connect_sink(&mut self, config: NoConfig, peer: &impl MessageSink<GenericCommandData>) {
+        self.message_box.connect_sink(config, &peer.get_sender())
+    }
+}
+
+impl IntoIterator for &ShellManagerBuilder {
+    type Item = (OperationName, DynSender<GenericCommandState>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let sender =
+            MappingSender::new(self.message_box.get_sender(), |msg: GenericCommandState| {
+                msg.try_into().ok()
+            });
+        vec![(OperationType::Shell.to_string(), sender.into())].into_iter()
+    }
+}
+
+impl RuntimeRequestSink for ShellManagerBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.message_box.get_signal_sender()
+    }
+}
+
+impl Builder<ShellManagerActor> for ShellManagerBuilder {
+    type Error = LinkError;
+
+    fn try_build(self) -> Result<ShellManagerActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> ShellManagerActor {
+        ShellManagerActor::new(self.config, self.message_box.build(), self.uploader)
+    }
+}
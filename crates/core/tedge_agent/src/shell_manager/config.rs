@@ -0,0 +1,21 @@
+use camino::Utf8PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct ShellManagerConfig {
+    /// The user running the commands, when not the user running the agent
+    pub user: Option<String>,
+    /// The maximum duration of a command, also used when no timeout is given by the request
+    pub timeout: Duration,
+    pub tmp_dir: Utf8PathBuf,
+}
+
+impl ShellManagerConfig {
+    pub fn from_tedge_config(tedge_config: &tedge_config::TEdgeConfig) -> ShellManagerConfig {
+        ShellManagerConfig {
+            user: tedge_config.agent.shell.user.or_none().cloned(),
+            timeout: tedge_config.agent.shell.timeout.duration(),
+            tmp_dir: tedge_config.tmp.path.clone().into(),
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::shell_manager::builder::ShellManagerBuilder;
+use crate::shell_manager::builder::ShellUploadRequest;
+use crate::shell_manager::builder::ShellUploadResult;
+use crate::shell_manager::config::ShellManagerConfig;
+use std::time::Duration;
+use tedge_actors::test_helpers::FakeServerBox;
+use tedge_actors::test_helpers::FakeServerBoxBuilder;
+use tedge_actors::test_helpers::MessageReceiverExt;
+use tedge_actors::test_helpers::TimedMessageBox;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::DynError;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::ShellCmd;
+use tedge_api::commands::ShellCmdPayload;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_test_utils::fs::TempTedgeDir;
+use tedge_uploader_ext::UploadResponse;
+
+const TEST_TIMEOUT_MS: Duration = Duration::from_millis(3000);
+
+type ConverterBox = TimedMessageBox<SimpleMessageBox<ShellCmd, ShellCmd>>;
+type UploaderBox = TimedMessageBox<FakeServerBox<ShellUploadRequest, ShellUploadResult>>;
+
+#[tokio::test]
+async fn return_command_output_in_payload() -> Result<(), DynError> {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter_box, _uploader_box) = spawn_shell_manager(&temp_dir).await?;
+
+    converter_box
+        .send(shell_command("1234", "echo hello", None))
+        .await?;
+
+    let response = converter_box.recv().await.unwrap();
+    assert_eq!(response.status(), CommandStatus::Executing);
+
+    let response = converter_box.recv().await.unwrap();
+    assert_eq!(response.status(), CommandStatus::Successful);
+    assert_eq!(response.payload.exit_code, Some(0));
+    assert_eq!(response.payload.stdout.as_deref(), Some("hello\n"));
+    assert_eq!(response.payload.stderr.as_deref(), Some(""));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn command_exiting_with_an_error_fails() -> Result<(), DynError> {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter_box, _uploader_box) = spawn_shell_manager(&temp_dir).await?;
+
+    converter_box
+        .send(shell_command("1234", "echo oops >&2; exit 3", None))
+        .await?;
+
+    converter_box.skip(1).await;
+    let response = converter_box.recv().await.unwrap();
+    assert_eq!(
+        response.status(),
+        CommandStatus::Failed {
+            reason: "Command exited with status 3".to_string()
+        }
+    );
+    assert_eq!(response.payload.exit_code, Some(3));
+    assert_eq!(response.payload.stderr.as_deref(), Some("oops\n"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn command_running_too_long_fails() -> Result<(), DynError> {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter_box, _uploader_box) = spawn_shell_manager(&temp_dir).await?;
+
+    let mut command = shell_command("1234", "sleep 10", None);
+    command.payload.timeout = Some(1);
+    converter_box.send(command).await?;
+
+    converter_box.skip(1).await;
+    let response = converter_box.recv().await.unwrap();
+    assert_eq!(
+        response.status(),
+        CommandStatus::Failed {
+            reason: "Command still running after 1 seconds".to_string()
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn requested_timeout_is_capped_by_the_configured_maximum() -> Result<(), DynError> {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter_box, _uploader_box) =
+        spawn_shell_manager_as(&temp_dir, Some(current_user()), Duration::from_secs(1)).await?;
+
+    let mut command = shell_command("1234", "sleep 10", None);
+    command.payload.timeout = Some(3600);
+    converter_box.send(command).await?;
+
+    converter_box.skip(1).await;
+    let response = converter_box.recv().await.unwrap();
+    assert_eq!(
+        response.status(),
+        CommandStatus::Failed {
+            reason: "Command still running after 1 seconds".to_string()
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn background_processes_are_killed_on_timeout() -> Result<(), DynError> {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter_box, _uploader_box) = spawn_shell_manager(&temp_dir).await?;
+
+    let pid_file = temp_dir.utf8_path().join("sleep.pid");
+    let mut command = shell_command(
+        "1234",
+        &format!("sleep 30 & echo $! > {pid_file}; wait"),
+        None,
+    );
+    command.payload.timeout = Some(1);
+    converter_box.send(command).await?;
+
+    converter_box.skip(1).await;
+    let response = converter_box.recv().await.unwrap();
+    assert_eq!(
+        response.status(),
+        CommandStatus::Failed {
+            reason: "Command still running after 1 seconds".to_string()
+        }
+    );
+
+    // The background process, which is not a direct child of the agent, has been killed too
+    let pid = std::fs::read_to_string(&pid_file)?;
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default();
+    let state = stat
+        .rsplit(") ")
+        .next()
+        .and_then(|fields| fields.chars().next());
+    assert!(
+        matches!(state, None | Some('Z') | Some('X')),
+        "sleep process still running: {stat}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn commands_are_refused_when_no_user_is_configured() -> Result<(), DynError> {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter_box, _uploader_box) =
+        spawn_shell_manager_as(&temp_dir, None, Duration::from_secs(60)).await?;
+
+    converter_box
+        .send(shell_command("1234", "echo hello", None))
+        .await?;
+
+    converter_box.skip(1).await;
+    let response = converter_box.recv().await.unwrap();
+    assert_eq!(
+        response.status(),
+        CommandStatus::Failed {
+            reason: "Shell commands are disabled: agent.shell.user must be set to the user running the commands".to_string()
+        }
+    );
+    assert_eq!(response.payload.exit_code, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn upload_command_output_when_requested() -> Result<(), DynError> {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter_box, mut uploader_box) = spawn_shell_manager(&temp_dir).await?;
+
+    let tedge_url = "http://127.0.0.1:8000/te/v1/files/main/shell/1234";
+    converter_box
+        .send(shell_command("1234", "echo hello", Some(tedge_url)))
+        .await?;
+    converter_box.skip(1).await;
+
+    let (cmd_id, request) = uploader_box.recv().await.unwrap();
+    assert_eq!(cmd_id, "1234");
+    assert_eq!(request.url, tedge_url);
+    let content = std::fs::read_to_string(&request.file_path)?;
+    assert!(content.contains("$ echo hello\n"));
+    assert!(content.contains("----- stdout -----\nhello\n"));
+
+    uploader_box
+        .send((
+            cmd_id,
+            Ok(UploadResponse::new(tedge_url, request.file_path.clone())),
+        ))
+        .await?;
+
+    let response = converter_box.recv().await.unwrap();
+    assert_eq!(response.status(), CommandStatus::Successful);
+    assert_eq!(response.payload.stdout, None);
+    assert!(!request.file_path.exists());
+
+    Ok(())
+}
+
+fn shell_command(cmd_id: &str, command: &str, tedge_url: Option<&str>) -> ShellCmd {
+    ShellCmd {
+        target: EntityTopicId::default_main_device(),
+        cmd_id: cmd_id.to_string(),
+        payload: ShellCmdPayload {
+            status: CommandStatus::Scheduled,
+            command: command.to_string(),
+            tedge_url: tedge_url.map(str::to_string),
+            ..Default::default()
+        },
+    }
+}
+
+async fn spawn_shell_manager(
+    tmp_dir: &TempTedgeDir,
+) -> Result<(ConverterBox, UploaderBox), DynError> {
+    spawn_shell_manager_as(tmp_dir, Some(current_user()), Duration::from_secs(60)).await
+}
+
+fn current_user() -> String {
+    nix::unistd::User::from_uid(nix::unistd::getuid())
+        .unwrap()
+        .unwrap()
+        .name
+}
+
+async fn spawn_shell_manager_as(
+    tmp_dir: &TempTedgeDir,
+    user: Option<String>,
+    timeout: Duration,
+) -> Result<(ConverterBox, UploaderBox), DynError> {
+    let mut converter_builder: SimpleMessageBoxBuilder<ShellCmd, ShellCmd> =
+        SimpleMessageBoxBuilder::new("Converter", 5);
+    let mut uploader_builder: FakeServerBoxBuilder<ShellUploadRequest, ShellUploadResult> =
+        FakeServerBoxBuilder::default();
+
+    let config = ShellManagerConfig {
+        user,
+        timeout,
+        tmp_dir: tmp_dir.utf8_path_buf(),
+    };
+
+    let mut shell_actor_builder = ShellManagerBuilder::new(config, &mut uploader_builder);
+    converter_builder.connect_sink(NoConfig, &shell_actor_builder);
+    shell_actor_builder.connect_sink(NoConfig, &converter_builder);
+
+    let converter_box = converter_builder.build().with_timeout(TEST_TIMEOUT_MS);
+    let uploader_box = uploader_builder.build().with_timeout(TEST_TIMEOUT_MS);
+
+    let shell_actor = shell_actor_builder.build();
+    tokio::spawn(async move { shell_actor.run().await });
+
+    Ok((converter_box, uploader_box))
+}
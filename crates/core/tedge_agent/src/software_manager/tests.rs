@@ -82,6 +82,7 @@ async fn test_new_software_update_operation() -> Result<(), DynError> {
         version: Some("0.0.1".into()),
         action: Some(SoftwareModuleAction::Install),
         url: None,
+        tedge_url: None,
         reason: None,
     };
     let debian_list = SoftwareRequestResponseSoftwareList {
@@ -97,6 +98,7 @@ async fn test_new_software_update_operation() -> Result<(), DynError> {
             status: CommandStatus::Scheduled,
             update_list: vec![debian_list],
             failures: vec![],
+            rolled_back: vec![],
             log_path: None,
         },
     };
@@ -9,6 +9,7 @@ use crate::workflow::GenericCommandData;
 use crate::workflow::GenericCommandState;
 use camino::Utf8Path;
 use camino::Utf8PathBuf;
+use download::DeltaInfo;
 use download::DownloadInfo;
 use log::error;
 use mqtt_channel::MqttError;
@@ -426,6 +427,10 @@ pub struct SoftwareUpdateCommandPayload {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub failures: Vec<SoftwareRequestResponseSoftwareList>,
 
+    /// The modules restored to their previous state after a failed update
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rolled_back: Vec<SoftwareRequestResponseSoftwareList>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub log_path: Option<Utf8PathBuf>,
 }
@@ -510,7 +515,7 @@ impl SoftwareUpdateCommand {
                     Some(module_type.to_string()),
                     item.name.clone(),
                     item.version.clone(),
-                    item.url.clone(),
+                    item.download_info(),
                     None,
                 );
 
@@ -542,6 +547,20 @@ impl SoftwareUpdateCommand {
             })
     }
 
+    /// Record the updates applied to revert the modules of the given type to their previous state
+    pub fn add_rolled_back(&mut self, plugin_type: &str, updates: Vec<SoftwareModuleUpdate>) {
+        if updates.is_empty() {
+            return;
+        }
+        self.payload
+            .rolled_back
+            .push(SoftwareRequestResponseSoftwareList {
+                plugin_type: plugin_type.to_string(),
+                modules: updates.into_iter().map(SoftwareModuleItem::from).collect(),
+                errors: vec![],
+            })
+    }
+
     pub fn set_log_path(&mut self, path: impl AsRef<Utf8Path>) {
         self.payload.log_path = Some(path.as_ref().into())
     }
@@ -579,6 +598,10 @@ pub struct SoftwareModuleItem {
     #[serde(flatten)]
     pub url: Option<DownloadInfo>,
 
+    /// The URL of a copy of the module file, cached by the File Transfer Service for a child device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tedge_url: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub action: Option<SoftwareModuleAction>,
 
@@ -586,12 +609,26 @@ pub struct SoftwareModuleItem {
     pub reason: Option<String>,
 }
 
+impl SoftwareModuleItem {
+    /// The location of the module file, using the local copy cached by the File Transfer Service if any
+    pub fn download_info(&self) -> Option<DownloadInfo> {
+        let mut info = self.url.clone()?;
+        if let Some(tedge_url) = &self.tedge_url {
+            info.url.clone_from(tedge_url);
+            // The cached copy is local: no need to fetch a patch from the remote server
+            info.delta = None;
+        }
+        Some(info)
+    }
+}
+
 impl From<SoftwareModule> for SoftwareModuleItem {
     fn from(module: SoftwareModule) -> Self {
         SoftwareModuleItem {
             name: module.name,
             version: module.version,
             url: module.url,
+            tedge_url: None,
             action: None,
             reason: None,
         }
@@ -605,6 +642,7 @@ impl From<SoftwareModuleUpdate> for SoftwareModuleItem {
                 name: module.name,
                 version: module.version,
                 url: module.url,
+                tedge_url: None,
                 action: Some(SoftwareModuleAction::Install),
                 reason: None,
             },
@@ -612,6 +650,7 @@ impl From<SoftwareModuleUpdate> for SoftwareModuleItem {
                 name: module.name,
                 version: module.version,
                 url: module.url,
+                tedge_url: None,
                 action: Some(SoftwareModuleAction::Remove),
                 reason: None,
             },
@@ -626,6 +665,7 @@ impl From<SoftwareError> for Vec<SoftwareModuleItem> {
                 name: module.name,
                 version: module.version,
                 url: module.url,
+                tedge_url: None,
                 action: Some(SoftwareModuleAction::Install),
                 reason: Some(reason),
             }],
@@ -633,6 +673,7 @@ impl From<SoftwareError> for Vec<SoftwareModuleItem> {
                 name: module.name,
                 version: module.version,
                 url: module.url,
+                tedge_url: None,
                 action: Some(SoftwareModuleAction::Remove),
                 reason: Some(reason),
             }],
@@ -650,6 +691,7 @@ impl From<SoftwareError> for Vec<SoftwareModuleItem> {
                             name: module.name,
                             version: module.version,
                             url: module.url,
+                            tedge_url: None,
                             action: Some(action),
                             reason: Some(reason.clone()),
                         }
@@ -762,6 +804,8 @@ pub type LogUploadCmd = Command<LogUploadCmdPayload>;
 #[serde(rename_all = "camelCase")]
 pub struct LogMetadata {
     pub types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub search_options: Vec<String>,
 }
 
 impl Jsonify for LogMetadata {}
@@ -780,6 +824,18 @@ pub struct LogUploadCmdPayload {
     pub date_to: OffsetDateTime,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub search_text: Option<String>,
+    /// The `searchText` and `excludeText` are regular expressions, and not plain text
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub search_regex: bool,
+    /// The `searchText` and `excludeText` are matched ignoring case
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignore_case: bool,
+    /// The lines matching this text are excluded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_text: Option<String>,
+    /// The number of lines to include before and after each line matching the `searchText`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_lines: Option<usize>,
     pub lines: usize,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub log_path: Option<Utf8PathBuf>,
@@ -806,6 +862,10 @@ impl CommandPayload for LogUploadCmdPayload {
 pub struct LogUploadCmdMetadata {
     #[serde(default)]
     pub types: Vec<String>,
+    /// The optional search fields of the log_upload command supported by the log manager,
+    /// e.g. `searchRegex`, `ignoreCase`, `excludeText` or `contextLines`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub search_options: Vec<String>,
 }
 
 impl Jsonify for LogUploadCmdMetadata {}
@@ -882,6 +942,12 @@ pub struct ConfigUpdateCmdPayload {
     pub config_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
+    /// The expected SHA-256 checksum of the file to download, as an hexadecimal string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// A base64-encoded detached signature of the file to download
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub log_path: Option<Utf8PathBuf>,
 }
@@ -933,6 +999,15 @@ pub struct FirmwareUpdateCmdPayload {
     pub remote_url: String,
     pub name: String,
     pub version: String,
+    /// The expected SHA-256 checksum of the file to download, as an hexadecimal string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// A base64-encoded detached signature of the file to download
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// A delta patch from which the firmware can be reconstructed using a previous version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<DeltaInfo>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub log_path: Option<Utf8PathBuf>,
 }
@@ -953,6 +1028,104 @@ impl CommandPayload for FirmwareUpdateCmdPayload {
     }
 }
 
+/// Command to run a shell command on a device
+pub type ShellCmd = Command<ShellCmdPayload>;
+
+#[derive(Debug, Default, Deserialize, Serialize, Eq, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellCmdPayload {
+    #[serde(flatten)]
+    pub status: CommandStatus,
+    /// The command line, as interpreted by `sh -c`
+    pub command: String,
+    /// The maximum duration of the command, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// The URL where to upload the output of the command, rather than returning it in the payload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tedge_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_path: Option<Utf8PathBuf>,
+}
+
+impl Jsonify for ShellCmdPayload {}
+
+impl CommandPayload for ShellCmdPayload {
+    fn operation_type() -> OperationType {
+        OperationType::Shell
+    }
+
+    fn status(&self) -> CommandStatus {
+        self.status.clone()
+    }
+
+    fn set_status(&mut self, status: CommandStatus) {
+        self.status = status
+    }
+}
+
+/// Command to list the content of a directory
+pub type FileListCmd = Command<FileListCmdPayload>;
+
+#[derive(Debug, Default, Deserialize, Serialize, Eq, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileListCmdPayload {
+    #[serde(flatten)]
+    pub status: CommandStatus,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<FileEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_path: Option<Utf8PathBuf>,
+}
+
+impl Jsonify for FileListCmdPayload {}
+
+impl CommandPayload for FileListCmdPayload {
+    fn operation_type() -> OperationType {
+        OperationType::FileList
+    }
+
+    fn status(&self) -> CommandStatus {
+        self.status.clone()
+    }
+
+    fn set_status(&mut self, status: CommandStatus) {
+        self.status = status
+    }
+}
+
+/// An entry of a directory listing
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileEntry {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub file_type: FileType,
+    pub size: u64,
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub modified: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -982,6 +1155,7 @@ mod tests {
             version: Some("0.0.1".into()),
             action: Some(SoftwareModuleAction::Install),
             url: None,
+            tedge_url: None,
             reason: None,
         };
 
@@ -990,6 +1164,7 @@ mod tests {
             version: Some("0.0.2".into()),
             action: Some(SoftwareModuleAction::Install),
             url: None,
+            tedge_url: None,
             reason: None,
         };
 
@@ -1004,6 +1179,7 @@ mod tests {
             version: Some("0.0.1".into()),
             action: Some(SoftwareModuleAction::Remove),
             url: Some("test.com".into()),
+            tedge_url: None,
             reason: None,
         };
 
@@ -1017,6 +1193,7 @@ mod tests {
             status: CommandStatus::Init,
             update_list: vec![debian_list, docker_list],
             failures: vec![],
+            rolled_back: vec![],
             log_path: None,
         };
 
@@ -1030,6 +1207,96 @@ mod tests {
         assert_eq!(parsed_request, request);
     }
 
+    #[test]
+    fn serde_software_module_with_checksum_and_signature() {
+        let json = r#"{"name":"app","version":"1.0","url":"https://example.com/app.deb","sha256":"9eec0424","signature":"MEUCIQ==","action":"install"}"#;
+
+        let module: SoftwareModuleItem = serde_json::from_str(json).unwrap();
+        let url = module.url.as_ref().unwrap();
+        assert_eq!(url.url(), "https://example.com/app.deb");
+        assert_eq!(url.sha256.as_deref(), Some("9eec0424"));
+        assert_eq!(url.signature.as_deref(), Some("MEUCIQ=="));
+
+        assert_eq!(serde_json::to_string(&module).unwrap(), json);
+    }
+
+    #[test]
+    fn software_module_cached_by_the_file_transfer_service() {
+        let json = r#"{"name":"app","version":"1.0","url":"https://example.com/app.deb","sha256":"9eec0424","tedgeUrl":"http://127.0.0.1:8000/te/v1/files/device_child01__/software_update/apt:app-1234","action":"install"}"#;
+
+        let module: SoftwareModuleItem = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_string(&module).unwrap(), json);
+
+        let download_info = module.download_info().unwrap();
+        assert_eq!(
+            download_info.url(),
+            "http://127.0.0.1:8000/te/v1/files/device_child01__/software_update/apt:app-1234"
+        );
+        assert_eq!(download_info.sha256.as_deref(), Some("9eec0424"));
+    }
+
+    #[test]
+    fn serde_rolled_back_modules() {
+        let mut command =
+            SoftwareUpdateCommand::new(&EntityTopicId::default_main_device(), "1".to_string());
+        command.add_rolled_back(
+            "apt",
+            vec![
+                SoftwareModuleUpdate::install(SoftwareModule::new(
+                    Some("apt".into()),
+                    "curl".into(),
+                    Some("7.0".into()),
+                    None,
+                    None,
+                )),
+                SoftwareModuleUpdate::remove(SoftwareModule::new(
+                    Some("apt".into()),
+                    "jq".into(),
+                    None,
+                    None,
+                    None,
+                )),
+            ],
+        );
+        command.add_rolled_back("docker", vec![]);
+
+        assert_eq!(
+            command.payload.to_json(),
+            r#"{"status":"init","rolledBack":[{"type":"apt","modules":[{"name":"curl","version":"7.0","action":"install"},{"name":"jq","action":"remove"}]}]}"#
+        );
+    }
+
+    #[test]
+    fn serde_file_list_command() {
+        let json = r#"{"status":"successful","path":"/var/log","entries":[{"name":"syslog","type":"file","size":1024,"modified":"2024-06-01T10:00:00Z"},{"name":"tedge","type":"directory","size":4096}]}"#;
+
+        let payload = FileListCmdPayload::from_json(json).unwrap();
+        assert_eq!(payload.path, "/var/log");
+        assert_eq!(payload.entries[0].file_type, FileType::File);
+        assert_eq!(payload.entries[1].file_type, FileType::Directory);
+        assert_eq!(payload.entries[1].modified, None);
+
+        assert_eq!(payload.to_json(), json);
+    }
+
+    #[test]
+    fn serde_log_upload_search_options() {
+        let json = r#"{"status":"init","tedgeUrl":"http://127.0.0.1:8000/te/v1/files/main/log_upload/syslog-1","type":"syslog","dateFrom":"2024-01-01T00:00:00Z","dateTo":"2024-01-02T00:00:00Z","searchText":"error|warn","searchRegex":true,"ignoreCase":true,"excludeText":"DEBUG","contextLines":3,"lines":100}"#;
+
+        let payload = LogUploadCmdPayload::from_json(json).unwrap();
+        assert!(payload.search_regex);
+        assert!(payload.ignore_case);
+        assert_eq!(payload.exclude_text.as_deref(), Some("DEBUG"));
+        assert_eq!(payload.context_lines, Some(3));
+        assert_eq!(payload.to_json(), json);
+
+        // The search options are omitted when not used
+        let json = r#"{"status":"init","tedgeUrl":"http://127.0.0.1:8000/te/v1/files/main/log_upload/syslog-1","type":"syslog","dateFrom":"2024-01-01T00:00:00Z","dateTo":"2024-01-02T00:00:00Z","lines":100}"#;
+        let payload = LogUploadCmdPayload::from_json(json).unwrap();
+        assert!(!payload.search_regex);
+        assert_eq!(payload.to_json(), json);
+    }
+
     #[test]
     fn serde_custom_command_status() {
         let request = SoftwareListCommandPayload {
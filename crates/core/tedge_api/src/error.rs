@@ -68,6 +68,12 @@ pub enum SoftwareError {
         reason: String,
     },
 
+    #[error("Failed to rollback updates for {software_type:?}")]
+    Rollback {
+        software_type: SoftwareType,
+        reason: String,
+    },
+
     #[error("Unknown {software_type:?} module: {name:?}")]
     UnknownModule {
         software_type: SoftwareType,
@@ -99,6 +105,9 @@ pub enum SoftwareError {
     #[error("The update-list command is not supported by this: {0} plugin")]
     UpdateListNotSupported(String),
 
+    #[error("The rollback command is not supported by this: {0} plugin")]
+    RollbackNotSupported(String),
+
     #[error("I/O error: {reason:?}")]
     IoError { reason: String },
 
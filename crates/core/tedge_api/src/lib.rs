@@ -13,6 +13,7 @@ pub mod script;
 mod software;
 pub mod store;
 pub mod substitution;
+pub mod telemetry_metadata;
 pub mod workflow;
 
 pub use commands::CommandStatus;
@@ -7,11 +7,13 @@ mod data;
 mod group;
 mod parser;
 mod serialize;
+mod split;
 pub(crate) mod utils;
 
 pub use group::*;
 pub use parser::*;
 pub use serialize::*;
+pub use split::*;
 
 /// The `MeasurementVisitor` trait represents the capability to visit a series of measurements, possibly grouped.
 ///
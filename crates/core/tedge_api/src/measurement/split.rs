@@ -0,0 +1,360 @@
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+#[error("The series {series} alone is {actual_size} bytes, greater than the threshold size of {threshold}")]
+pub struct OversizedSeriesError {
+    pub series: String,
+    pub actual_size: usize,
+    pub threshold: usize,
+}
+
+/// A series of a measurement, possibly within a group
+struct Series<'a> {
+    group: Option<&'a str>,
+    name: &'a str,
+    value: &'a Value,
+}
+
+impl Series<'_> {
+    fn add_to(&self, measurement: &mut Map<String, Value>) {
+        match self.group {
+            None => {
+                measurement.insert(self.name.to_string(), self.value.clone());
+            }
+            Some(group) => {
+                if let Value::Object(group) = measurement
+                    .entry(group)
+                    .or_insert_with(|| Value::Object(Map::new()))
+                {
+                    group.insert(self.name.to_string(), self.value.clone());
+                }
+            }
+        }
+    }
+
+    /// The number of bytes added to the JSON representation of a measurement when this series is added
+    fn json_size_added_to(&self, measurement: &Map<String, Value>) -> usize {
+        let entry_size = json_string_size(self.name) + 1 + self.value.to_string().len();
+        match self.group {
+            None => entry_size + separator_size(measurement.is_empty()),
+            Some(group) => match measurement.get(group) {
+                Some(Value::Object(series)) => entry_size + separator_size(series.is_empty()),
+                _ => {
+                    json_string_size(group)
+                        + 3
+                        + entry_size
+                        + separator_size(measurement.is_empty())
+                }
+            },
+        }
+    }
+
+    fn path(&self) -> String {
+        match self.group {
+            None => self.name.to_string(),
+            Some(group) => format!("{group}.{}", self.name),
+        }
+    }
+}
+
+/// Split a measurement into several measurements, each smaller than a threshold
+///
+/// The series are packed in order into as few measurements as possible.
+/// The `time` and all the other fields that are not series (e.g. `type`) are copied to each of the measurements.
+/// A group might be split, its series being spread over several measurements.
+///
+/// The `size` function returns the size of a measurement once translated for the cloud.
+/// A measurement is accepted when this size is less or equal to the `threshold`.
+///
+/// To avoid translating ever larger measurements, `size` is only called on measurements with one or two series,
+/// to compute once the size added by each series: alone, or within a group already holding the first series of that group.
+/// The size of a measurement is then tracked as the sum of the sizes added by its series,
+/// which holds for a JSON representation as long as the common fields are not empty.
+///
+/// An error is returned if a single series alone doesn't fit under the threshold.
+pub fn split_measurement(
+    measurement: &Map<String, Value>,
+    threshold: usize,
+    mut size: impl FnMut(&Map<String, Value>) -> usize,
+) -> Result<Vec<Map<String, Value>>, OversizedSeriesError> {
+    let (common_fields, series) = partition(measurement);
+    let common_size = size(&common_fields);
+
+    // For each series: the size of the common fields along this series alone,
+    // and the size added by this series to a group already holding the first series of the group
+    let mut first_series_of_groups: HashMap<&str, (Map<String, Value>, usize)> = HashMap::new();
+    let mut series_sizes = Vec::with_capacity(series.len());
+    for series in series.iter() {
+        let mut alone = common_fields.clone();
+        series.add_to(&mut alone);
+        let alone_size = size(&alone);
+
+        let size_within_group =
+            series
+                .group
+                .and_then(|group| match first_series_of_groups.get(group) {
+                    None => {
+                        first_series_of_groups.insert(group, (alone, alone_size));
+                        None
+                    }
+                    Some((first, first_size)) => {
+                        let mut pair = first.clone();
+                        series.add_to(&mut pair);
+                        Some(size(&pair).saturating_sub(*first_size))
+                    }
+                });
+        series_sizes.push((alone_size, size_within_group));
+    }
+
+    let mut measurements = Vec::new();
+    let mut current = common_fields.clone();
+    let mut current_size = common_size;
+    let mut current_is_empty = true;
+    for (series, (alone_size, size_within_group)) in series.iter().zip(series_sizes) {
+        let added_size = match (series.group, size_within_group) {
+            (Some(group), Some(added_size)) if current.contains_key(group) => added_size,
+            _ => alone_size.saturating_sub(common_size),
+        };
+        if current_size.saturating_add(added_size) <= threshold {
+            series.add_to(&mut current);
+            current_size += added_size;
+            current_is_empty = false;
+            continue;
+        }
+
+        if alone_size > threshold {
+            return Err(OversizedSeriesError {
+                series: series.path(),
+                actual_size: alone_size,
+                threshold,
+            });
+        }
+        if !current_is_empty {
+            measurements.push(current);
+        }
+        current = common_fields.clone();
+        series.add_to(&mut current);
+        current_size = alone_size;
+        current_is_empty = false;
+    }
+    if !current_is_empty {
+        measurements.push(current);
+    }
+
+    Ok(measurements)
+}
+
+/// Split a measurement into several measurements, each with a JSON representation smaller than a threshold
+///
+/// This is the same as [split_measurement] using the size of the JSON representation,
+/// except that this size is tracked as the series are added,
+/// rather than computed again for each series.
+pub fn split_json_measurement(
+    measurement: &Map<String, Value>,
+    threshold: usize,
+) -> Result<Vec<Map<String, Value>>, OversizedSeriesError> {
+    let (common_fields, series) = partition(measurement);
+    let common_size = Value::Object(common_fields.clone()).to_string().len();
+
+    let mut measurements = Vec::new();
+    let mut current = common_fields.clone();
+    let mut current_size = common_size;
+    let mut current_is_empty = true;
+    for series in series {
+        let added_size = series.json_size_added_to(&current);
+        if current_size + added_size <= threshold {
+            series.add_to(&mut current);
+            current_size += added_size;
+            current_is_empty = false;
+            continue;
+        }
+
+        let actual_size = common_size + series.json_size_added_to(&common_fields);
+        if actual_size > threshold {
+            return Err(OversizedSeriesError {
+                series: series.path(),
+                actual_size,
+                threshold,
+            });
+        }
+        if !current_is_empty {
+            measurements.push(current);
+        }
+        current = common_fields.clone();
+        series.add_to(&mut current);
+        current_size = actual_size;
+        current_is_empty = false;
+    }
+    if !current_is_empty {
+        measurements.push(current);
+    }
+
+    Ok(measurements)
+}
+
+/// Separate the series of a measurement from the fields common to all the series
+fn partition(measurement: &Map<String, Value>) -> (Map<String, Value>, Vec<Series<'_>>) {
+    let mut common_fields = Map::new();
+    let mut series = Vec::new();
+    for (key, value) in measurement {
+        match value {
+            _ if key == "time" => {
+                common_fields.insert(key.clone(), value.clone());
+            }
+            Value::Number(_) => series.push(Series {
+                group: None,
+                name: key,
+                value,
+            }),
+            Value::Object(group) if group.values().any(Value::is_number) => {
+                series.extend(group.iter().map(|(name, value)| Series {
+                    group: Some(key),
+                    name,
+                    value,
+                }))
+            }
+            _ => {
+                common_fields.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    (common_fields, series)
+}
+
+/// The size of a string once quoted and escaped as JSON
+fn json_string_size(s: &str) -> usize {
+    Value::String(s.to_string()).to_string().len()
+}
+
+/// The size of the comma separating a new entry from the previous ones, if any
+fn separator_size(is_first_entry: bool) -> usize {
+    if is_first_entry {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn json_size(measurement: &Map<String, Value>) -> usize {
+        serde_json::to_string(measurement).unwrap().len()
+    }
+
+    fn measurement(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn small_measurements_are_not_split() {
+        let input = measurement(
+            json!({"time": "2021-04-08T00:00:00+05:00", "temperature": 23, "pressure": 220}),
+        );
+
+        let output = split_measurement(&input, 1024, json_size).unwrap();
+
+        assert_eq!(output, vec![input]);
+    }
+
+    #[test]
+    fn series_are_spread_over_measurements_sharing_common_fields() {
+        let input = measurement(json!({
+            "time": "2021-04-08T00:00:00+05:00",
+            "type": "environment",
+            "temperature": 23,
+            "location": {"latitude": 32.54, "longitude": -117.67},
+            "pressure": 98
+        }));
+
+        let output = split_measurement(&input, 90, json_size).unwrap();
+
+        assert_eq!(
+            output,
+            vec![
+                measurement(json!({
+                    "time": "2021-04-08T00:00:00+05:00",
+                    "type": "environment",
+                    "location": {"latitude": 32.54},
+                })),
+                measurement(json!({
+                    "time": "2021-04-08T00:00:00+05:00",
+                    "type": "environment",
+                    "location": {"longitude": -117.67},
+                })),
+                measurement(json!({
+                    "time": "2021-04-08T00:00:00+05:00",
+                    "type": "environment",
+                    "pressure": 98,
+                    "temperature": 23,
+                })),
+            ]
+        );
+        assert!(output.iter().all(|m| json_size(m) <= 90));
+    }
+
+    #[test]
+    fn a_series_too_large_is_an_error() {
+        let input = measurement(json!({
+            "time": 1617820200,
+            "temperature": 23,
+            "a_very_long_series_name_that_cannot_fit": 0
+        }));
+
+        let error = split_measurement(&input, 60, json_size).unwrap_err();
+
+        assert_eq!(error.series, "a_very_long_series_name_that_cannot_fit");
+        assert_eq!(error.threshold, 60);
+    }
+
+    #[test]
+    fn sizes_are_computed_once_per_series() {
+        let mut input = measurement(json!({"time": "2021-04-08T00:00:00+05:00"}));
+        let mut group = Map::new();
+        for i in 0..100 {
+            input.insert(format!("series_{i}"), json!(i));
+            group.insert(format!("series_{i}"), json!(i));
+        }
+        input.insert("group".to_string(), Value::Object(group));
+
+        let mut calls = 0;
+        let output = split_measurement(&input, 256, |m| {
+            calls += 1;
+            json_size(m)
+        })
+        .unwrap();
+
+        assert!(calls <= 2 * 200 + 1, "size computed {calls} times");
+        assert!(output.iter().all(|m| json_size(m) <= 256));
+        assert_eq!(output, split_json_measurement(&input, 256).unwrap());
+    }
+
+    #[test]
+    fn json_sizes_are_tracked_as_series_are_added() {
+        let input = measurement(json!({
+            "time": "2021-04-08T00:00:00+05:00",
+            "type": "environment",
+            "temperature": 23,
+            "location": {"latitude": 32.54, "longitude": -117.67, "altitude": 98.6},
+            "pressure": 98,
+            "with \"quotes\"": 1.5,
+            "é": {"ü": 0}
+        }));
+
+        for threshold in [90, 100, 120, 150, 200, 1024] {
+            let output = split_json_measurement(&input, threshold).unwrap();
+            assert_eq!(
+                output,
+                split_measurement(&input, threshold, json_size).unwrap()
+            );
+            assert!(output.iter().all(|m| json_size(m) <= threshold));
+        }
+
+        let error = split_json_measurement(&input, 60).unwrap_err();
+        assert_eq!(error, split_measurement(&input, 60, json_size).unwrap_err());
+    }
+}
@@ -710,6 +710,8 @@ pub enum OperationType {
     FirmwareUpdate,
     Health,
     DeviceProfile,
+    Shell,
+    FileList,
     Custom(String),
 }
 
@@ -752,6 +754,8 @@ impl<'a> From<&'a str> for OperationType {
             "config_update" => OperationType::ConfigUpdate,
             "firmware_update" => OperationType::FirmwareUpdate,
             "device_profile" => OperationType::DeviceProfile,
+            "shell" => OperationType::Shell,
+            "file_list" => OperationType::FileList,
             operation => OperationType::Custom(operation.to_string()),
         }
     }
@@ -769,6 +773,8 @@ impl Display for OperationType {
             OperationType::FirmwareUpdate => write!(f, "firmware_update"),
             OperationType::Health => write!(f, "health"),
             OperationType::DeviceProfile => write!(f, "device_profile"),
+            OperationType::Shell => write!(f, "shell"),
+            OperationType::FileList => write!(f, "file_list"),
             OperationType::Custom(operation) => write!(f, "{operation}"),
         }
     }
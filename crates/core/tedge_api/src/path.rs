@@ -93,4 +93,18 @@ impl DataDir {
     pub fn firmware_dir(&self) -> Utf8PathBuf {
         self.0.join("firmware")
     }
+
+    /// Return `Utf8PathBuf` to the cache of downloaded artifacts used as base for delta patches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use camino::Utf8PathBuf;
+    /// use tedge_api::path::DataDir;
+    ///
+    /// assert_eq!(DataDir::default().artifact_cache_dir(), Utf8PathBuf::from("/var/tedge/artifacts"));
+    /// ```
+    pub fn artifact_cache_dir(&self) -> Utf8PathBuf {
+        self.0.join("artifacts")
+    }
 }
@@ -1,7 +1,12 @@
 //! The message log is a persistent append-only log of MQTT messages.
 //! Each line is the JSON representation of that MQTT message.
 //! The underlying file is a JSON lines file.
+//!
+//! The same format is used by `tedge mqtt record` and `tedge mqtt replay`,
+//! the messages being then recorded along the time they have been received.
 use mqtt_channel::MqttMessage;
+use serde::Deserialize;
+use serde::Serialize;
 use serde_json::json;
 use std::fs::File;
 use std::fs::OpenOptions;
@@ -10,6 +15,7 @@ use std::io::BufReader;
 use std::io::BufWriter;
 use std::io::Write;
 use std::path::Path;
+use time::OffsetDateTime;
 
 const LOG_FILE_NAME: &str = "entity_store.jsonl";
 const LOG_FORMAT_VERSION: &str = "1.0";
@@ -23,38 +29,101 @@ pub enum LogEntryError {
     FromSerdeJson(#[source] serde_json::Error, String),
 }
 
+/// A log entry: an MQTT message along with the time it has been received, if known
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    #[serde(flatten)]
+    pub message: MqttMessage,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub timestamp: Option<OffsetDateTime>,
+}
+
+/// The header line written at the beginning of a log file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LogHeader {
+    version: String,
+}
+
 /// A reader to read the log file entries line by line
-pub(crate) struct MessageLogReader {
+pub struct MessageLogReader {
     reader: BufReader<File>,
+
+    /// The first line of a log file without header, to be returned as the first entry
+    pending_line: Option<String>,
 }
 
 impl MessageLogReader {
-    pub fn new<P>(log_dir: P) -> Result<MessageLogReader, std::io::Error>
+    pub(crate) fn new<P>(log_dir: P) -> Result<MessageLogReader, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        MessageLogReader::open(log_dir.as_ref().join(LOG_FILE_NAME))
+    }
+
+    /// Open the log file with the given path
+    ///
+    /// The file is expected to start with a `{"version":"1.0"}` header.
+    /// A file with no header is read from its first line,
+    /// while a file with a header for an unsupported version is rejected.
+    pub fn open<P>(path: P) -> Result<MessageLogReader, std::io::Error>
     where
         P: AsRef<Path>,
     {
-        let file = OpenOptions::new()
-            .read(true)
-            .open(log_dir.as_ref().join(LOG_FILE_NAME))?;
+        let file = OpenOptions::new().read(true).open(path)?;
         let mut reader = BufReader::new(file);
 
-        let mut version_info = String::new();
-        reader.read_line(&mut version_info)?;
-        // TODO: Validate if the read version is supported
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line)?;
+
+        let pending_line = match serde_json::from_str::<LogHeader>(&first_line) {
+            Ok(LogHeader { version }) if version == LOG_FORMAT_VERSION => None,
+            Ok(LogHeader { version }) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Unsupported message log version: {version}"),
+                ))
+            }
+            Err(_) if first_line.is_empty() => None,
+            Err(_) => Some(first_line),
+        };
 
-        Ok(MessageLogReader { reader })
+        Ok(MessageLogReader {
+            reader,
+            pending_line,
+        })
     }
 
     /// Return the next MQTT message from the log
     /// The reads start from the beginning of the file
     /// and each read advances the file pointer to the next line
     pub fn next_message(&mut self) -> Result<Option<MqttMessage>, LogEntryError> {
+        self.next_line()
+    }
+
+    /// Return the next log entry, i.e. the next MQTT message along with its timestamp if any
+    pub fn next_entry(&mut self) -> Result<Option<LogEntry>, LogEntryError> {
+        self.next_line()
+    }
+
+    fn next_line<T: serde::de::DeserializeOwned>(&mut self) -> Result<Option<T>, LogEntryError> {
+        if let Some(buffer) = self.pending_line.take() {
+            let entry: T = serde_json::from_str(&buffer)
+                .map_err(|err| LogEntryError::FromSerdeJson(err, buffer))?;
+            return Ok(Some(entry));
+        }
+
         let mut buffer = String::new();
         match self.reader.read_line(&mut buffer) {
             Ok(bytes_read) if bytes_read > 0 => {
-                let message: MqttMessage = serde_json::from_str(&buffer)
+                let entry: T = serde_json::from_str(&buffer)
                     .map_err(|err| LogEntryError::FromSerdeJson(err, buffer))?;
-                Ok(Some(message))
+                Ok(Some(entry))
             }
             Ok(_) => Ok(None), // EOF
             Err(err) => Err(LogEntryError::FromStdIo(err)),
@@ -63,19 +132,24 @@ impl MessageLogReader {
 }
 
 /// A writer to append new MQTT messages to the end of the log
-pub(crate) struct MessageLogWriter {
+pub struct MessageLogWriter {
     writer: BufWriter<File>,
 }
 
 impl MessageLogWriter {
-    pub fn new<P>(log_dir: P) -> Result<MessageLogWriter, std::io::Error>
+    pub(crate) fn new<P>(log_dir: P) -> Result<MessageLogWriter, std::io::Error>
     where
         P: AsRef<Path>,
     {
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(log_dir.as_ref().join(LOG_FILE_NAME))?;
+        MessageLogWriter::open(log_dir.as_ref().join(LOG_FILE_NAME))
+    }
+
+    /// Open the log file with the given path, creating it if it doesn't exist
+    pub fn open<P>(path: P) -> Result<MessageLogWriter, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
 
         // If the file is empty append the version information as a header
         let metadata = file.metadata()?;
@@ -91,7 +165,15 @@ impl MessageLogWriter {
         Ok(MessageLogWriter { writer })
     }
 
-    pub fn new_truncated<P>(log_dir: P) -> Result<MessageLogWriter, std::io::Error>
+    pub(crate) fn new_truncated<P>(log_dir: P) -> Result<MessageLogWriter, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        MessageLogWriter::open_truncated(log_dir.as_ref().join(LOG_FILE_NAME))
+    }
+
+    /// Open the log file with the given path, discarding any previous content
+    pub fn open_truncated<P>(path: P) -> Result<MessageLogWriter, std::io::Error>
     where
         P: AsRef<Path>,
     {
@@ -99,9 +181,9 @@ impl MessageLogWriter {
             .create(true)
             .write(true)
             .truncate(true)
-            .open(log_dir.as_ref().join(LOG_FILE_NAME))?;
+            .open(path.as_ref())?;
 
-        MessageLogWriter::new(log_dir)
+        MessageLogWriter::open(path)
     }
 
     /// Append the JSON representation of the given message to the log.
@@ -113,15 +195,28 @@ impl MessageLogWriter {
         self.writer.get_ref().sync_all()?;
         Ok(())
     }
+
+    /// Append the JSON representation of the given entry to the log.
+    ///
+    /// In contrast to [MessageLogWriter::append_message], the file is not synced after each entry.
+    pub fn append_entry(&mut self, entry: &LogEntry) -> Result<(), std::io::Error> {
+        let json_line = serde_json::to_string(entry)?;
+        writeln!(self.writer, "{}", json_line)?;
+        self.writer.flush()?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::LogEntry;
     use super::MessageLogReader;
     use super::MessageLogWriter;
     use mqtt_channel::MqttMessage;
+    use mqtt_channel::QoS;
     use mqtt_channel::Topic;
     use tempfile::tempdir;
+    use time::macros::datetime;
 
     #[test]
     fn test_append_and_retrieve() {
@@ -161,4 +256,93 @@ mod tests {
             assert_eq!(message_log_reader.next_message().unwrap(), None);
         }
     }
+
+    #[test]
+    fn entries_are_recorded_with_their_timestamp() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("recording.jsonl");
+
+        let entry = LogEntry {
+            message: MqttMessage::new(&Topic::new("te/device/main///m/").unwrap(), "{}")
+                .with_qos(QoS::AtMostOnce)
+                .with_retain(),
+            timestamp: Some(datetime!(2026-10-18 10:00:00.5 UTC)),
+        };
+        {
+            let mut recording = MessageLogWriter::open_truncated(&path).unwrap();
+            recording.append_entry(&entry).unwrap();
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            r#"{"version":"1.0"}
+{"topic":"te/device/main///m/","payload":"{}","qos":0,"retain":true,"timestamp":"2026-10-18T10:00:00.5Z"}
+"#
+        );
+
+        let mut reader = MessageLogReader::open(&path).unwrap();
+        assert_eq!(reader.next_entry().unwrap(), Some(entry));
+        assert_eq!(reader.next_entry().unwrap(), None);
+    }
+
+    #[test]
+    fn messages_logged_without_timestamp_can_be_read_as_entries() {
+        let temp_dir = tempdir().unwrap();
+        let message = MqttMessage::new(&Topic::new("topic").unwrap(), "payload");
+        {
+            let mut message_log = MessageLogWriter::new(&temp_dir).unwrap();
+            message_log.append_message(&message).unwrap();
+        }
+
+        let mut reader = MessageLogReader::new(&temp_dir).unwrap();
+        assert_eq!(
+            reader.next_entry().unwrap(),
+            Some(LogEntry {
+                message,
+                timestamp: None
+            })
+        );
+    }
+
+    #[test]
+    fn log_files_without_header_are_read_from_their_first_line() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("recording.jsonl");
+        std::fs::write(
+            &path,
+            r#"{"topic":"topic1","payload":"payload1","qos":1,"retain":false}
+{"topic":"topic2","payload":"payload2","qos":1,"retain":false}
+"#,
+        )
+        .unwrap();
+
+        let mut reader = MessageLogReader::open(&path).unwrap();
+        assert_eq!(
+            reader.next_message().unwrap(),
+            Some(MqttMessage::new(&Topic::new("topic1").unwrap(), "payload1"))
+        );
+        assert_eq!(
+            reader.next_message().unwrap(),
+            Some(MqttMessage::new(&Topic::new("topic2").unwrap(), "payload2"))
+        );
+        assert_eq!(reader.next_message().unwrap(), None);
+    }
+
+    #[test]
+    fn log_files_with_an_unsupported_version_are_rejected() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("recording.jsonl");
+        std::fs::write(
+            &path,
+            r#"{"version":"2.0"}
+{"topic":"topic1","payload":"payload1","qos":1,"retain":false}
+"#,
+        )
+        .unwrap();
+
+        let err = MessageLogReader::open(&path).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Unsupported message log version: 2.0");
+    }
 }
@@ -6,6 +6,13 @@ pub trait Record {
     /// Extract from this record the JSON value pointed by the given path
     fn extract_value(&self, path: &str) -> Option<Value>;
 
+    /// Extract from this record the JSON value pointed by the given path, only if actually defined
+    ///
+    /// Unlike [extract_value](Self::extract_value), this never returns a default value for a missing field.
+    fn extract_defined_value(&self, path: &str) -> Option<Value> {
+        self.extract_value(path)
+    }
+
     /// Inject values extracted from the record into a template string
     ///
     /// - Search the template string for path patterns `${...}`
@@ -63,6 +70,16 @@ impl Record for GenericCommandState {
     /// with the exception that the empty string is returned for an unknown path below the `.payload`,
     /// the rational being that the payload object represents a free-form value.
     fn extract_value(&self, path: &str) -> Option<Value> {
+        if let Some(value) = self.extract_defined_value(path) {
+            return Some(value);
+        }
+        (path.starts_with(".payload.") && !path.contains(['[', ']'])).then(|| String::new().into())
+    }
+
+    /// Extract the JSON value pointed by a path from this command state
+    ///
+    /// Return None if the path contains unknown fields, including below the `.payload`.
+    fn extract_defined_value(&self, path: &str) -> Option<Value> {
         match path {
             "." => Some(json!({
                 "topic": self.topic.name,
@@ -77,10 +94,7 @@ impl Record for GenericCommandState {
             path if path.contains(['[', ']']) => None,
             path => {
                 let value_path = path.strip_prefix(".payload.")?;
-                let value = json_excerpt(&self.payload, value_path)
-                    .cloned()
-                    .unwrap_or_else(|| String::new().into());
-                Some(value)
+                json_excerpt(&self.payload, value_path).cloned()
             }
         }
     }
@@ -0,0 +1,336 @@
+//! Metadata describing the measurement, event and alarm types of an entity.
+//!
+//! These metadata are published as retained messages on the `meta` sub-topics of the telemetry topics:
+//!
+//! - `te/<entity>/m/<measurement_type>/meta`: the units of the measurement series,
+//!   e.g. `{"units": {"temperature": "°C", "battery.voltage": "V"}}`
+//!   or `{"units": {"temperature": "°C", "battery": {"voltage": "V"}}}`
+//! - `te/<entity>/e/<event_type>/meta`: the default text of the events, e.g. `{"text": "Door opened"}`
+//! - `te/<entity>/a/<alarm_type>/meta`: the default severity and text of the alarms,
+//!   e.g. `{"severity": "major", "text": "Temperature is too high"}`
+//!
+//! Publishing an empty retained message on a `meta` topic clears the metadata.
+
+use crate::mqtt_topics::Channel;
+use crate::mqtt_topics::EntityTopicId;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+/// The metadata of a measurement type
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub struct MeasurementMetadata {
+    /// The units of the measurement series, indexed by series name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub units: BTreeMap<String, SeriesUnit>,
+}
+
+/// The unit of a series, or the units of all the series of a group
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum SeriesUnit {
+    Unit(String),
+    Group(BTreeMap<String, String>),
+}
+
+impl MeasurementMetadata {
+    /// The unit declared for a series, `group` being the group of the series if any
+    ///
+    /// The unit of a series within a group can be declared either
+    /// as `{"group": {"series": "unit"}}` or as `{"group.series": "unit"}`.
+    pub fn unit(&self, group: Option<&str>, series: &str) -> Option<&str> {
+        match group {
+            None => match self.units.get(series) {
+                Some(SeriesUnit::Unit(unit)) => Some(unit),
+                _ => None,
+            },
+            Some(group) => match self.units.get(group) {
+                Some(SeriesUnit::Group(units)) => units.get(series).map(String::as_str),
+                _ => match self.units.get(&format!("{group}.{series}")) {
+                    Some(SeriesUnit::Unit(unit)) => Some(unit),
+                    _ => None,
+                },
+            },
+        }
+    }
+
+    /// Add the declared units to a measurement payload, as a `units` object
+    ///
+    /// Nothing is added if no units are declared or if the payload already has a `units` field.
+    pub fn embed_units(&self, payload: &mut Map<String, Value>) {
+        if self.units.is_empty() || payload.contains_key("units") {
+            return;
+        }
+        if let Ok(units) = serde_json::to_value(&self.units) {
+            payload.insert("units".to_string(), units);
+        }
+    }
+}
+
+/// The metadata of an event type
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub struct EventMetadata {
+    /// The text used when an event of this type is published without text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl EventMetadata {
+    /// Add the default fields that are missing from an event payload
+    pub fn apply_defaults(&self, payload: &mut Map<String, Value>) {
+        insert_default(payload, "text", &self.text);
+    }
+}
+
+/// The metadata of an alarm type
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub struct AlarmMetadata {
+    /// The severity used when an alarm of this type is raised without severity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<String>,
+
+    /// The text used when an alarm of this type is raised without text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl AlarmMetadata {
+    /// Add the default fields that are missing from an alarm payload
+    pub fn apply_defaults(&self, payload: &mut Map<String, Value>) {
+        insert_default(payload, "severity", &self.severity);
+        insert_default(payload, "text", &self.text);
+    }
+}
+
+fn insert_default(payload: &mut Map<String, Value>, key: &str, default: &Option<String>) {
+    if let Some(default) = default {
+        payload
+            .entry(key)
+            .or_insert_with(|| Value::String(default.clone()));
+    }
+}
+
+/// The telemetry metadata of all the entities, as received on the `meta` topics
+#[derive(Debug, Default)]
+pub struct TelemetryMetadataCache {
+    measurements: HashMap<(EntityTopicId, String), MeasurementMetadata>,
+    events: HashMap<(EntityTopicId, String), EventMetadata>,
+    alarms: HashMap<(EntityTopicId, String), AlarmMetadata>,
+}
+
+impl TelemetryMetadataCache {
+    /// Update the cache with a message received on a metadata channel
+    ///
+    /// Return `Ok(false)` if the channel is not a telemetry metadata channel.
+    pub fn update(
+        &mut self,
+        entity: &EntityTopicId,
+        channel: &Channel,
+        payload: &str,
+    ) -> Result<bool, serde_json::Error> {
+        match channel {
+            Channel::MeasurementMetadata { measurement_type } => {
+                update_entry(&mut self.measurements, entity, measurement_type, payload)?
+            }
+            Channel::EventMetadata { event_type } => {
+                update_entry(&mut self.events, entity, event_type, payload)?
+            }
+            Channel::AlarmMetadata { alarm_type } => {
+                update_entry(&mut self.alarms, entity, alarm_type, payload)?
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn measurement(
+        &self,
+        entity: &EntityTopicId,
+        measurement_type: &str,
+    ) -> Option<&MeasurementMetadata> {
+        self.measurements
+            .get(&(entity.clone(), measurement_type.to_string()))
+    }
+
+    pub fn event(&self, entity: &EntityTopicId, event_type: &str) -> Option<&EventMetadata> {
+        self.events.get(&(entity.clone(), event_type.to_string()))
+    }
+
+    pub fn alarm(&self, entity: &EntityTopicId, alarm_type: &str) -> Option<&AlarmMetadata> {
+        self.alarms.get(&(entity.clone(), alarm_type.to_string()))
+    }
+
+    /// Embed into a telemetry payload the metadata declared for its type
+    ///
+    /// The units of a measurement are added as a `units` object,
+    /// while the missing fields of an event or an alarm are set to the declared defaults.
+    pub fn embed_metadata(
+        &self,
+        entity: &EntityTopicId,
+        channel: &Channel,
+        payload: &mut Map<String, Value>,
+    ) {
+        match channel {
+            Channel::Measurement { measurement_type } => {
+                if let Some(metadata) = self.measurement(entity, measurement_type) {
+                    metadata.embed_units(payload)
+                }
+            }
+            Channel::Event { event_type } => {
+                if let Some(metadata) = self.event(entity, event_type) {
+                    metadata.apply_defaults(payload)
+                }
+            }
+            Channel::Alarm { alarm_type } => {
+                if let Some(metadata) = self.alarm(entity, alarm_type) {
+                    metadata.apply_defaults(payload)
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Forget all the metadata of an entity
+    pub fn remove_entity(&mut self, entity: &EntityTopicId) {
+        self.measurements.retain(|(id, _), _| id != entity);
+        self.events.retain(|(id, _), _| id != entity);
+        self.alarms.retain(|(id, _), _| id != entity);
+    }
+}
+
+fn update_entry<T: DeserializeOwned>(
+    entries: &mut HashMap<(EntityTopicId, String), T>,
+    entity: &EntityTopicId,
+    telemetry_type: &str,
+    payload: &str,
+) -> Result<(), serde_json::Error> {
+    let key = (entity.clone(), telemetry_type.to_string());
+    if payload.trim().is_empty() {
+        entries.remove(&key);
+    } else {
+        entries.insert(key, serde_json::from_str(payload)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mqtt_topics::MqttSchema;
+    use serde_json::json;
+
+    fn update(cache: &mut TelemetryMetadataCache, topic: &str, payload: &str) -> bool {
+        let schema = MqttSchema::default();
+        let (entity, channel) = schema.entity_channel_of(topic).unwrap();
+        cache.update(&entity, &channel, payload).unwrap()
+    }
+
+    #[test]
+    fn units_can_be_declared_for_grouped_series() {
+        let metadata: MeasurementMetadata = serde_json::from_value(json!({
+            "units": {
+                "temperature": "°C",
+                "battery": { "voltage": "V" },
+                "engine.speed": "rpm"
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(metadata.unit(None, "temperature"), Some("°C"));
+        assert_eq!(metadata.unit(Some("battery"), "voltage"), Some("V"));
+        assert_eq!(metadata.unit(Some("engine"), "speed"), Some("rpm"));
+        assert_eq!(metadata.unit(None, "battery"), None);
+        assert_eq!(metadata.unit(Some("battery"), "current"), None);
+        assert_eq!(metadata.unit(None, "pressure"), None);
+    }
+
+    #[test]
+    fn metadata_are_cached_per_entity_and_type() {
+        let mut cache = TelemetryMetadataCache::default();
+        let main = EntityTopicId::default_main_device();
+        let child = EntityTopicId::default_child_device("child").unwrap();
+
+        assert!(update(
+            &mut cache,
+            "te/device/main///m/env/meta",
+            r#"{"units": {"temperature": "°C"}}"#
+        ));
+        assert!(update(
+            &mut cache,
+            "te/device/child///a/temp_high/meta",
+            r#"{"severity": "major"}"#
+        ));
+        assert!(!update(&mut cache, "te/device/main///m/env", "{}"));
+
+        let env = cache.measurement(&main, "env").unwrap();
+        assert_eq!(env.unit(None, "temperature"), Some("°C"));
+        assert!(cache.measurement(&child, "env").is_none());
+        assert!(cache.measurement(&main, "other").is_none());
+        assert_eq!(
+            cache
+                .alarm(&child, "temp_high")
+                .unwrap()
+                .severity
+                .as_deref(),
+            Some("major")
+        );
+
+        // An empty payload clears the metadata
+        assert!(update(&mut cache, "te/device/main///m/env/meta", ""));
+        assert!(cache.measurement(&main, "env").is_none());
+
+        cache.remove_entity(&child);
+        assert!(cache.alarm(&child, "temp_high").is_none());
+    }
+
+    #[test]
+    fn units_are_embedded_into_measurements() {
+        let mut cache = TelemetryMetadataCache::default();
+        update(
+            &mut cache,
+            "te/device/main///m/env/meta",
+            r#"{"units": {"temperature": "°C", "battery": {"voltage": "V"}}}"#,
+        );
+
+        let schema = MqttSchema::default();
+        let (entity, channel) = schema.entity_channel_of("te/device/main///m/env").unwrap();
+        let mut payload = json!({"temperature": 23, "battery": {"voltage": 3.7}})
+            .as_object()
+            .unwrap()
+            .clone();
+
+        cache.embed_metadata(&entity, &channel, &mut payload);
+
+        assert_eq!(
+            Value::Object(payload),
+            json!({
+                "temperature": 23,
+                "battery": {"voltage": 3.7},
+                "units": {"temperature": "°C", "battery": {"voltage": "V"}}
+            })
+        );
+    }
+
+    #[test]
+    fn defaults_do_not_override_payload_fields() {
+        let metadata = AlarmMetadata {
+            severity: Some("major".to_string()),
+            text: Some("Temperature is too high".to_string()),
+        };
+        let mut payload = json!({"text": "Temperature is 95°C"})
+            .as_object()
+            .unwrap()
+            .clone();
+
+        metadata.apply_defaults(&mut payload);
+
+        assert_eq!(
+            Value::Object(payload),
+            json!({"severity": "major", "text": "Temperature is 95°C"})
+        );
+    }
+}
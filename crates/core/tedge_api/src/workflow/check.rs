@@ -0,0 +1,459 @@
+use crate::substitution::Record;
+use crate::workflow::Condition;
+use crate::workflow::GenericCommandState;
+use crate::workflow::Operand;
+use crate::workflow::OperationAction;
+use crate::workflow::OperationWorkflow;
+use crate::workflow::StateExcerpt;
+use crate::workflow::StateName;
+use mqtt_channel::Topic;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// A likely mistake found in a workflow definition which is otherwise valid
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum WorkflowWarning {
+    /// A state that is the target of an action but has no definition
+    DanglingTarget { state: StateName, target: StateName },
+
+    /// A state that cannot be reached from the `init` state
+    UnreachableState { state: StateName },
+
+    /// A `${...}` expression that doesn't refer to any command state property
+    UndefinedSubstitution { state: StateName, expr: String },
+
+    /// A `${.payload...}` expression that refers to a field
+    /// which is neither in the example payload nor set by the workflow itself
+    UnknownPayloadField { state: StateName, expr: String },
+}
+
+impl Display for WorkflowWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkflowWarning::DanglingTarget { state, target } => {
+                write!(f, "{state}: the target state {target} is not defined")
+            }
+            WorkflowWarning::UnreachableState { state } => {
+                write!(
+                    f,
+                    "{state}: this state cannot be reached from the init state"
+                )
+            }
+            WorkflowWarning::UndefinedSubstitution { state, expr } => {
+                write!(f, "{state}: {expr} doesn't refer to a command property")
+            }
+            WorkflowWarning::UnknownPayloadField { state, expr } => {
+                write!(
+                    f,
+                    "{state}: {expr} is neither a field of the example payload nor set by the workflow"
+                )
+            }
+        }
+    }
+}
+
+impl OperationWorkflow {
+    /// Check this workflow for likely mistakes
+    ///
+    /// - targets states that are not defined,
+    /// - states that cannot be reached from the `init` state,
+    /// - path expressions `${...}` which don't refer to a command property.
+    pub fn check(&self) -> Vec<WorkflowWarning> {
+        self.check_warnings(None)
+    }
+
+    /// Check this workflow for likely mistakes, using an example of command payload
+    ///
+    /// On top of the [check](Self::check) warnings,
+    /// report the `${.payload...}` expressions referring to a field that is neither in the example payload
+    /// nor set by the workflow itself (as the output of a sub-operation or a field used internally).
+    /// A missing payload field being substituted by an empty string, such a mistake goes unnoticed otherwise.
+    pub fn check_with_payload(&self, payload: &Value) -> Vec<WorkflowWarning> {
+        self.check_warnings(Some(payload))
+    }
+
+    fn check_warnings(&self, payload: Option<&Value>) -> Vec<WorkflowWarning> {
+        let mut warnings = BTreeSet::new();
+        let probe = GenericCommandState::new(
+            Topic::new_unchecked(&format!(
+                "te/device/main///cmd/{}/check",
+                self.operation.name()
+            )),
+            "init".to_string(),
+            payload.cloned().unwrap_or_else(|| json!({})),
+        );
+        let known_fields = payload.map(|payload| self.known_payload_fields(payload));
+
+        for (state, action) in self.states.iter() {
+            for target in action.next_states().unwrap_or_default() {
+                if !self.states.contains_key(target) {
+                    warnings.insert(WorkflowWarning::DanglingTarget {
+                        state: state.clone(),
+                        target: target.to_string(),
+                    });
+                }
+            }
+
+            // The output excerpt of an awaited sub-operation refers to the sub-command payload
+            let on_sub_command = matches!(action, OperationAction::AwaitOperationCompletion(_, _));
+            for path in action_paths(action) {
+                let expr = format!("${{{path}}}");
+                if probe.extract_value(&path).is_none() {
+                    warnings.insert(WorkflowWarning::UndefinedSubstitution {
+                        state: state.clone(),
+                        expr,
+                    });
+                } else if let Some(field) = payload_field(&path).filter(|_| !on_sub_command) {
+                    if matches!(&known_fields, Some(known) if !known.contains(field)) {
+                        warnings.insert(WorkflowWarning::UnknownPayloadField {
+                            state: state.clone(),
+                            expr,
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(reachable) = self.reachable_states() {
+            for state in self.states.keys() {
+                if !reachable.contains(state.as_str()) && state != "successful" && state != "failed"
+                {
+                    warnings.insert(WorkflowWarning::UnreachableState {
+                        state: state.clone(),
+                    });
+                }
+            }
+        }
+
+        warnings.into_iter().collect()
+    }
+
+    /// Return the states that can be reached from the `init` state
+    ///
+    /// Return None if this cannot be determined,
+    /// because some reachable script freely defines the next state on its stdout.
+    fn reachable_states(&self) -> Option<BTreeSet<&str>> {
+        let mut reachable = BTreeSet::from(["init"]);
+        let mut pending = VecDeque::from(["init"]);
+        while let Some(state) = pending.pop_front() {
+            let Some(action) = self.states.get(state) else {
+                continue;
+            };
+            for target in action.next_states()? {
+                if reachable.insert(target) {
+                    pending.push_back(target);
+                }
+            }
+        }
+        Some(reachable)
+    }
+
+    /// Return the top-level payload fields a command can have,
+    /// given an example of payload and the fields set by this workflow
+    fn known_payload_fields(&self, payload: &Value) -> BTreeSet<String> {
+        let mut fields: BTreeSet<String> = ["status", "reason", "logPath"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        if let Some(payload) = payload.as_object() {
+            fields.extend(payload.keys().cloned());
+        }
+        for action in self.states.values() {
+            if let OperationAction::AwaitOperationCompletion(_, StateExcerpt::ExcerptMap(output)) =
+                action
+            {
+                fields.extend(output.keys().cloned());
+            }
+        }
+        fields
+    }
+}
+
+/// Return the paths of the `${...}` expressions used by an action
+fn action_paths(action: &OperationAction) -> Vec<String> {
+    let mut templates = Vec::new();
+    let mut paths = Vec::new();
+    match action.clone() {
+        OperationAction::Script(script, _) | OperationAction::BgScript(script, _) => {
+            templates.push(script.command);
+            templates.extend(script.args);
+        }
+        OperationAction::Operation(operation, input_script, input, _) => {
+            templates.push(operation);
+            if let Some(script) = input_script {
+                templates.push(script.command);
+                templates.extend(script.args);
+            }
+            excerpt_paths(&input, &mut paths);
+        }
+        OperationAction::AwaitOperationCompletion(_, output) => {
+            excerpt_paths(&output, &mut paths);
+        }
+        OperationAction::Iterate(path, _) => paths.push(path),
+        OperationAction::ParallelIterate(path, _, input, _) => {
+            paths.push(path);
+            excerpt_paths(&input, &mut paths);
+        }
+        OperationAction::Switch(handlers) => {
+            for (condition, _) in handlers.on_case {
+                condition_paths(condition, &mut paths);
+            }
+        }
+        _ => (),
+    }
+
+    let mut all_paths: Vec<String> = templates
+        .iter()
+        .map(String::as_str)
+        .flat_map(template_expressions)
+        .filter_map(|expr| {
+            expr.strip_prefix("${")
+                .and_then(|expr| expr.strip_suffix('}'))
+                .map(str::to_string)
+        })
+        .collect();
+    all_paths.extend(paths);
+    all_paths
+}
+
+/// Return the top-level payload field a path refers to, if any
+///
+/// Fields prefixed by `@` are used internally by the workflow engine and are ignored.
+fn payload_field(path: &str) -> Option<&str> {
+    let field = path.strip_prefix(".payload.")?;
+    let field = field.split('.').next().unwrap_or(field);
+    (!field.starts_with('@')).then_some(field)
+}
+
+/// Return the path expressions `${...}` found in a template
+fn template_expressions(template: &str) -> Vec<String> {
+    let mut expressions = Vec::new();
+    let mut remaining = template;
+    while let Some(start) = remaining.find("${") {
+        let Some(len) = remaining[start..].find('}') else {
+            break;
+        };
+        expressions.push(remaining[start..start + len + 1].to_string());
+        remaining = &remaining[start + len + 1..];
+    }
+    expressions
+}
+
+fn excerpt_paths(excerpt: &StateExcerpt, paths: &mut Vec<String>) {
+    match excerpt {
+        StateExcerpt::Literal(_) => (),
+        StateExcerpt::PathExpr(path) => paths.push(path.clone()),
+        StateExcerpt::ExcerptMap(excerpts) => {
+            for excerpt in excerpts.values() {
+                excerpt_paths(excerpt, paths)
+            }
+        }
+        StateExcerpt::ExcerptArray(excerpts) => {
+            for excerpt in excerpts.iter() {
+                excerpt_paths(excerpt, paths)
+            }
+        }
+    }
+}
+
+fn condition_paths(condition: Condition, paths: &mut Vec<String>) {
+    let operands = match condition {
+        Condition::IsSet(operand) | Condition::IsNotSet(operand) => vec![operand],
+        Condition::Equal(left, right) | Condition::NotEqual(left, right) => vec![left, right],
+    };
+    for operand in operands {
+        if let Operand::PathExpr(path) = operand {
+            paths.push(path)
+        }
+    }
+}
+
+impl OperationAction {
+    /// Return the states this action can move a command to
+    ///
+    /// Return None if the next state is freely given by a script on its stdout.
+    pub fn next_states(&self) -> Option<Vec<&str>> {
+        let states = match self {
+            OperationAction::MoveTo(update) => vec![update.status.as_str()],
+            OperationAction::BuiltIn(exec, await_handlers) => vec![
+                exec.on_exec.status.as_str(),
+                await_handlers.on_success.status.as_str(),
+                await_handlers.on_error.status.as_str(),
+                await_handlers.on_timeout.status.as_str(),
+            ],
+            OperationAction::AwaitingAgentRestart(handlers)
+            | OperationAction::AwaitOperationCompletion(handlers, _) => vec![
+                handlers.on_success.status.as_str(),
+                handlers.on_error.status.as_str(),
+                handlers.on_timeout.status.as_str(),
+            ],
+            OperationAction::Script(_, handlers) => handlers.next_states()?,
+            OperationAction::BgScript(_, handlers)
+            | OperationAction::BuiltInOperation(_, handlers)
+            | OperationAction::ParallelIterate(_, _, _, handlers) => {
+                vec![handlers.on_exec.status.as_str()]
+            }
+            OperationAction::Operation(_, _, _, handlers) => {
+                let mut states = vec![handlers.on_exec.status.as_str()];
+                if let Some(on_exhausted) = handlers
+                    .retry
+                    .as_ref()
+                    .and_then(|retry| retry.on_exhausted.as_ref())
+                {
+                    states.push(on_exhausted.status.as_str());
+                }
+                states
+            }
+            OperationAction::Clear => vec![],
+            OperationAction::Iterate(_, handlers) => vec![
+                handlers.on_next.status.as_str(),
+                handlers.on_success.status.as_str(),
+                handlers.on_error.status.as_str(),
+            ],
+            OperationAction::Switch(handlers) => handlers
+                .on_case
+                .iter()
+                .map(|(_, update)| update)
+                .chain(std::iter::once(&handlers.on_default))
+                .map(|update| update.status.as_str())
+                .collect(),
+        };
+        Some(states)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(file: &str) -> Vec<WorkflowWarning> {
+        let workflow: OperationWorkflow = toml::from_str(file).unwrap();
+        workflow.check()
+    }
+
+    #[test]
+    fn no_warnings_for_a_well_formed_workflow() {
+        let file = r#"
+operation = "make_it"
+
+[init]
+action = "proceed"
+on_success = "run"
+
+[run]
+script = "/some/script.sh ${.payload.x} ${.topic.cmd_id}"
+on_success = "successful"
+on_error = "failed"
+"#;
+        assert_eq!(check(file), vec![]);
+    }
+
+    #[test]
+    fn report_dangling_targets_and_unreachable_states() {
+        let file = r#"
+operation = "make_it"
+
+[init]
+action = "proceed"
+on_success = "run"
+
+[run]
+script = "/some/script.sh"
+on_success = "done"
+on_error = "failed"
+
+[orphan]
+action = "proceed"
+on_success = "successful"
+"#;
+        assert_eq!(
+            check(file),
+            vec![
+                WorkflowWarning::DanglingTarget {
+                    state: "run".to_string(),
+                    target: "done".to_string()
+                },
+                WorkflowWarning::UnreachableState {
+                    state: "orphan".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn report_undefined_substitutions() {
+        let file = r#"
+operation = "make_it"
+
+[init]
+script = "/some/script.sh ${.paylaod.x} ${.payload.y}"
+on_success = "check"
+
+[check]
+action = "switch"
+on_case = [ { when = "${.topic.id} == 42", next = "successful" } ]
+on_default = "failed"
+"#;
+        assert_eq!(
+            check(file),
+            vec![
+                WorkflowWarning::UndefinedSubstitution {
+                    state: "check".to_string(),
+                    expr: "${.topic.id}".to_string()
+                },
+                WorkflowWarning::UndefinedSubstitution {
+                    state: "init".to_string(),
+                    expr: "${.paylaod.x}".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn report_payload_fields_missing_from_the_example_payload() {
+        let file = r#"
+operation = "make_it"
+
+[init]
+script = "/some/script.sh ${.payload.url} ${.payload.uri} ${.payload.@next.item}"
+on_success = "run"
+
+[run]
+operation = "sub_make_it"
+input.target = "${.payload.target.name}"
+on_exec = "await"
+
+[await]
+action = "await-operation-completion"
+output.outcome = "${.payload.sub_outcome}"
+on_success = "check"
+
+[check]
+action = "switch"
+on_case = [ { when = '${.payload.outcome} == "ok"', next = "successful" } ]
+on_default = "failed"
+"#;
+        let workflow: OperationWorkflow = toml::from_str(file).unwrap();
+
+        // With no example payload, any payload field is assumed to be defined
+        assert_eq!(workflow.check(), vec![]);
+
+        assert_eq!(
+            workflow.check_with_payload(&json!({"url": "http://example.com"})),
+            vec![
+                WorkflowWarning::UnknownPayloadField {
+                    state: "init".to_string(),
+                    expr: "${.payload.uri}".to_string()
+                },
+                WorkflowWarning::UnknownPayloadField {
+                    state: "run".to_string(),
+                    expr: "${.payload.target.name}".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use crate::substitution::Record;
+use serde::de::Error;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde_json::Value;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// A condition evaluated over a command state to determine the next step of a workflow
+///
+/// - `${.payload.version} == "2.0"` checks that the `version` property of the payload is equal to `"2.0"`
+/// - `${.payload.version} != "2.0"` checks that this property is not equal to `"2.0"`
+/// - `${.payload.force}` checks that the `force` property is set, i.e. is neither null, false nor empty
+/// - `!${.payload.force}` checks that the `force` property is not set
+///
+/// The operands are either path expressions or JSON literals.
+/// A path expression pointing to a missing value is never equal nor different to anything:
+/// a comparison with such a value never holds. Only `!${.path}` holds for a missing value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Condition {
+    IsSet(Operand),
+    IsNotSet(Operand),
+    Equal(Operand, Operand),
+    NotEqual(Operand, Operand),
+}
+
+/// An operand of a [Condition]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Operand {
+    /// A JSON path to a value of the command state: `${.payload.x}`
+    PathExpr(String),
+
+    /// A JSON value: `"2.0"`, `42`, `true`
+    Literal(Value),
+}
+
+impl Condition {
+    /// Evaluate this condition against the given record
+    pub fn eval(&self, record: &impl Record) -> bool {
+        match self {
+            Condition::IsSet(operand) => operand.value(record).is_some_and(|v| is_set(&v)),
+            Condition::IsNotSet(operand) => !operand.value(record).is_some_and(|v| is_set(&v)),
+            Condition::Equal(left, right) => match (left.value(record), right.value(record)) {
+                (Some(left), Some(right)) => left == right,
+                _ => false,
+            },
+            Condition::NotEqual(left, right) => match (left.value(record), right.value(record)) {
+                (Some(left), Some(right)) => left != right,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Operand {
+    /// The value of this operand, if defined
+    fn value(&self, record: &impl Record) -> Option<Value> {
+        match self {
+            Operand::PathExpr(path) => record.extract_defined_value(path),
+            Operand::Literal(value) => Some(value.clone()),
+        }
+    }
+
+    /// Parse the operand at the start of the input, returning that operand and the remaining input
+    fn parse_prefix(input: &str) -> Result<(Operand, &str), ConditionError> {
+        let input = input.trim_start();
+        if let Some(expr) = input.strip_prefix("${") {
+            let Some(end) = expr.find('}') else {
+                return Err(ConditionError::InvalidPathExpression(input.to_string()));
+            };
+            let (path, remaining) = expr.split_at(end);
+            return Ok((Operand::PathExpr(path.to_string()), &remaining[1..]));
+        }
+
+        let mut literals = serde_json::Deserializer::from_str(input).into_iter::<Value>();
+        match literals.next() {
+            Some(Ok(value)) => Ok((Operand::Literal(value), &input[literals.byte_offset()..])),
+            _ => Err(ConditionError::InvalidOperand(input.to_string())),
+        }
+    }
+}
+
+fn is_set(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::String(s) => !s.is_empty(),
+        _ => true,
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let expr = input.trim();
+        if let Some(negated) = expr.strip_prefix('!') {
+            let (operand, remaining) = Operand::parse_prefix(negated)?;
+            if !remaining.trim().is_empty() {
+                return Err(ConditionError::UnexpectedInput(
+                    remaining.trim().to_string(),
+                ));
+            }
+            return Ok(Condition::IsNotSet(operand));
+        }
+
+        let (left, remaining) = Operand::parse_prefix(expr)?;
+        let remaining = remaining.trim_start();
+        if remaining.is_empty() {
+            return Ok(Condition::IsSet(left));
+        }
+
+        let (is_equal, remaining) = if let Some(remaining) = remaining.strip_prefix("==") {
+            (true, remaining)
+        } else if let Some(remaining) = remaining.strip_prefix("!=") {
+            (false, remaining)
+        } else {
+            return Err(ConditionError::UnknownOperator(remaining.to_string()));
+        };
+
+        let (right, remaining) = Operand::parse_prefix(remaining)?;
+        if !remaining.trim().is_empty() {
+            return Err(ConditionError::UnexpectedInput(
+                remaining.trim().to_string(),
+            ));
+        }
+
+        if is_equal {
+            Ok(Condition::Equal(left, right))
+        } else {
+            Ok(Condition::NotEqual(left, right))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let expr = String::deserialize(deserializer)?;
+        expr.parse()
+            .map_err(|err| D::Error::custom(format!("invalid condition: {expr}: {err}")))
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::PathExpr(path) => write!(f, "${{{path}}}"),
+            Operand::Literal(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::IsSet(operand) => write!(f, "{operand}"),
+            Condition::IsNotSet(operand) => write!(f, "!{operand}"),
+            Condition::Equal(left, right) => write!(f, "{left} == {right}"),
+            Condition::NotEqual(left, right) => write!(f, "{left} != {right}"),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+pub enum ConditionError {
+    #[error("Not a valid path expression: {0}")]
+    InvalidPathExpression(String),
+
+    #[error("Expected a path expression or a JSON literal: {0}")]
+    InvalidOperand(String),
+
+    #[error("Expected `==` or `!=`: {0}")]
+    UnknownOperator(String),
+
+    #[error("Unexpected input after the condition: {0}")]
+    UnexpectedInput(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::GenericCommandState;
+    use mqtt_channel::Topic;
+    use serde_json::json;
+
+    #[test]
+    fn parse_conditions() {
+        assert_eq!(
+            r#"${.payload.version} == "2.0""#.parse::<Condition>(),
+            Ok(Condition::Equal(
+                Operand::PathExpr(".payload.version".to_string()),
+                Operand::Literal(json!("2.0"))
+            ))
+        );
+        assert_eq!(
+            "${.payload.count}!=42".parse::<Condition>(),
+            Ok(Condition::NotEqual(
+                Operand::PathExpr(".payload.count".to_string()),
+                Operand::Literal(json!(42))
+            ))
+        );
+        assert_eq!(
+            " ${.payload.force} ".parse::<Condition>(),
+            Ok(Condition::IsSet(Operand::PathExpr(
+                ".payload.force".to_string()
+            )))
+        );
+        assert_eq!(
+            "!${.payload.force}".parse::<Condition>(),
+            Ok(Condition::IsNotSet(Operand::PathExpr(
+                ".payload.force".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn reject_ill_formed_conditions() {
+        assert_eq!(
+            "${.payload.version".parse::<Condition>(),
+            Err(ConditionError::InvalidPathExpression(
+                "${.payload.version".to_string()
+            ))
+        );
+        assert_eq!(
+            "${.payload.version} = 2".parse::<Condition>(),
+            Err(ConditionError::UnknownOperator("= 2".to_string()))
+        );
+        assert_eq!(
+            "${.payload.version} == 2.0 or more".parse::<Condition>(),
+            Err(ConditionError::UnexpectedInput("or more".to_string()))
+        );
+        assert_eq!(
+            "version == 2".parse::<Condition>(),
+            Err(ConditionError::InvalidOperand("version == 2".to_string()))
+        );
+    }
+
+    #[test]
+    fn eval_conditions() {
+        let topic = Topic::new_unchecked("te/device/main///cmd/make_it/123");
+        let state = GenericCommandState::new(
+            topic,
+            "init".to_string(),
+            json!({ "version": "2.0", "count": 3, "force": false, "name": "" }),
+        );
+
+        let eval = |expr: &str| expr.parse::<Condition>().unwrap().eval(&state);
+        assert!(eval(r#"${.payload.version} == "2.0""#));
+        assert!(!eval(r#"${.payload.version} == "1.0""#));
+        assert!(eval(r#"${.payload.version} != "1.0""#));
+        assert!(eval("${.payload.count} == 3"));
+        assert!(eval(r#"${.topic.operation} == "make_it""#));
+        assert!(eval("${.payload.version}"));
+        assert!(!eval("${.payload.force}"));
+        assert!(!eval("${.payload.name}"));
+        assert!(!eval("${.payload.unknown}"));
+        assert!(eval("!${.payload.unknown}"));
+    }
+
+    #[test]
+    fn comparisons_with_missing_values_never_hold() {
+        let topic = Topic::new_unchecked("te/device/main///cmd/make_it/123");
+        let state = GenericCommandState::new(
+            topic,
+            "init".to_string(),
+            json!({ "version": "2.0", "name": "" }),
+        );
+
+        let eval = |expr: &str| expr.parse::<Condition>().unwrap().eval(&state);
+        assert!(eval(r#"${.payload.name} == """#));
+        assert!(!eval(r#"${.payload.unknown} == """#));
+        assert!(!eval(r#"${.payload.unknown} != """#));
+        assert!(!eval(r#"${.payload.unknown} != "2.0""#));
+        assert!(!eval("${.payload.unknown} == null"));
+        assert!(!eval("${.payload.unknown} == ${.payload.missing}"));
+        assert!(!eval("${.payload.version.major} == 2"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::workflow::ConditionError;
 use serde::Deserialize;
 
 /// Error preventing a workflow to be registered
@@ -21,6 +22,24 @@ pub enum WorkflowDefinitionError {
     #[error("The provided target {0} is not a valid path expression")]
     InvalidPathExpression(String),
 
+    #[error("A switch requires at least one `on_case` handler")]
+    MissingSwitchCase,
+
+    #[error("Invalid switch condition `{condition}`: {error}")]
+    InvalidCondition {
+        condition: String,
+        error: ConditionError,
+    },
+
+    #[error("A retry policy can only be attached to a script or a user-defined sub-operation")]
+    UnsupportedRetryPolicy,
+
+    #[error("A parallel spec can only be attached to an iteration")]
+    UnsupportedParallelSpec,
+
+    #[error("The maximum number of parallel sub-operations must be at least 1")]
+    InvalidMaxConcurrency,
+
     #[error("The `builtin:{builtin_operation}` cannot be invoked from `{main_operation}`, but only from `{builtin_operation}`")]
     InvalidBuiltinOperation {
         main_operation: String,
@@ -48,6 +67,9 @@ pub enum ScriptDefinitionError {
 
     #[error("No handler is provided for 'on_success'")]
     MissingOnSuccessHandler,
+
+    #[error("The maximum number of attempts of a retry policy must be at least 1")]
+    InvalidMaxAttempts,
 }
 
 /// Error related to state excerpt definitions
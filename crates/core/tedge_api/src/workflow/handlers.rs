@@ -1,3 +1,5 @@
+use crate::substitution::Record;
+use crate::workflow::Condition;
 use crate::workflow::GenericStateUpdate;
 use crate::workflow::ScriptDefinitionError;
 use serde_json::Value;
@@ -15,6 +17,7 @@ pub struct ExitHandlers {
     on_exit: Vec<(u8, u8, GenericStateUpdate)>,
     on_stdout: Vec<String>,
     timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
 }
 
 impl ExitHandlers {
@@ -79,9 +82,43 @@ impl ExitHandlers {
             on_exit,
             on_stdout,
             timeout,
+            retry: None,
         })
     }
 
+    /// Attach a retry policy to be applied when the script fails
+    pub fn with_retry(self, retry: Option<RetryPolicy>) -> Self {
+        ExitHandlers { retry, ..self }
+    }
+
+    pub fn retry(&self) -> Option<&RetryPolicy> {
+        self.retry.as_ref()
+    }
+
+    /// Return the states a command can be moved to by the script outcome
+    ///
+    /// Return None if the next state is freely given by the script on its stdout.
+    pub fn next_states(&self) -> Option<Vec<&str>> {
+        if self.on_success.is_none() && self.on_stdout.is_empty() {
+            return None;
+        }
+
+        let mut states: Vec<&str> = self.on_stdout.iter().map(|s| s.as_str()).collect();
+        states.extend(
+            [&self.on_success, &self.on_error, &self.on_kill]
+                .into_iter()
+                .flatten()
+                .chain(self.on_exit.iter().map(|(_, _, update)| update))
+                .chain(
+                    self.retry
+                        .iter()
+                        .filter_map(|retry| retry.on_exhausted.as_ref()),
+                )
+                .map(|update| update.status.as_str()),
+        );
+        Some(states)
+    }
+
     pub fn state_update(
         &self,
         program: &str,
@@ -136,6 +173,26 @@ impl ExitHandlers {
         }
     }
 
+    /// Tell if the outcome of a script resolves to the failure branch of these handlers
+    ///
+    /// This is the case when the script cannot be launched, is killed with no `on_kill` handler,
+    /// returns an exit code with no specific `on_exit` handler
+    /// or succeeds but without giving the next state it was expected to give on its stdout.
+    ///
+    /// An outcome explicitly mapped to a state, as with `on_exit.2 = "other_state"`, is not a failure.
+    pub fn is_failure(&self, outcome: &std::io::Result<std::process::Output>) -> bool {
+        match outcome {
+            Ok(output) => match output.status.code() {
+                None => self.on_kill.is_none(),
+                Some(0) => {
+                    self.on_success.is_none() && json_stdout_excerpt(output.stdout.clone()).is_err()
+                }
+                Some(code) => self.state_update_on_error(code as u8).is_none(),
+            },
+            Err(_) => true,
+        }
+    }
+
     fn state_update_on_error(&self, code: u8) -> Option<GenericStateUpdate> {
         for (from, to, update) in self.on_exit.iter() {
             if code < *from {
@@ -246,20 +303,91 @@ fn extract_script_output(stdout: String) -> Option<String> {
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct ExecHandlers {
     pub on_exec: GenericStateUpdate,
+
+    /// How to retry a sub-operation that failed
+    pub retry: Option<RetryPolicy>,
 }
 
 impl ExecHandlers {
     pub fn try_new(on_exec: Option<GenericStateUpdate>) -> Result<Self, ScriptDefinitionError> {
         Ok(ExecHandlers {
             on_exec: on_exec.unwrap_or_else(GenericStateUpdate::successful),
+            retry: None,
         })
     }
+
+    /// Attach a retry policy to be applied when the triggered sub-operation fails
+    pub fn with_retry(self, retry: Option<RetryPolicy>) -> Self {
+        ExecHandlers { retry, ..self }
+    }
 }
 
 impl ExecHandlers {
     pub fn builtin_default() -> Self {
         ExecHandlers {
             on_exec: GenericStateUpdate::executing(),
+            retry: None,
+        }
+    }
+}
+
+/// Define how to retry an action that failed
+///
+/// The number of attempts already made is persisted in the command payload,
+/// so the count is not reset by an agent restart.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one
+    pub max_attempts: u32,
+
+    /// The delay to wait before each new attempt
+    pub backoff: Backoff,
+
+    /// The state to move to when all the attempts failed
+    ///
+    /// If none is provided, the command moves to the state given by the failure handlers of the action.
+    pub on_exhausted: Option<GenericStateUpdate>,
+}
+
+/// Delay between retries, growing exponentially with the number of failed attempts
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub factor: u32,
+    pub max: Option<Duration>,
+}
+
+impl Backoff {
+    /// A constant delay between attempts
+    pub fn fixed(delay: Duration) -> Self {
+        Backoff {
+            initial: delay,
+            factor: 1,
+            max: None,
+        }
+    }
+
+    /// The delay to wait after the given count of failed attempts (starting at 1)
+    pub fn delay(&self, failed_attempts: u32) -> Duration {
+        let exponent = failed_attempts.saturating_sub(1);
+        let factor = self.factor.max(1).saturating_pow(exponent);
+        let delay = self.initial.saturating_mul(factor);
+        match self.max {
+            Some(max) if delay > max => max,
+            _ => delay,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Return the delay to wait before a new attempt, given the count of failed attempts so far
+    ///
+    /// Return `None` when all the attempts have been exhausted.
+    pub fn next_attempt(&self, failed_attempts: u32) -> Option<Duration> {
+        if failed_attempts < self.max_attempts {
+            Some(self.backoff.delay(failed_attempts))
+        } else {
+            None
         }
     }
 }
@@ -306,6 +434,27 @@ impl IterateHandlers {
     }
 }
 
+/// Define the next state of a switch, given conditions over the command state
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwitchHandlers {
+    /// Conditions evaluated in order, the first one that holds defining the next state
+    pub on_case: Vec<(Condition, GenericStateUpdate)>,
+
+    /// Next state when none of the conditions holds
+    pub on_default: GenericStateUpdate,
+}
+
+impl SwitchHandlers {
+    /// Return the next state for the given command state
+    pub fn state_update(&self, record: &impl Record) -> GenericStateUpdate {
+        self.on_case
+            .iter()
+            .find(|(condition, _)| condition.eval(record))
+            .map(|(_, update)| update.clone())
+            .unwrap_or_else(|| self.on_default.clone())
+    }
+}
+
 /// Define default handlers for all state of an operation workflow
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DefaultHandlers {
@@ -409,6 +558,47 @@ on_kill = "killed"
         )
     }
 
+    #[test]
+    fn exit_code_mapped_to_a_state_is_not_retried() {
+        let file = r#"
+script = "sh -c 'exit 2'"
+on_exit.0 = "yeah"
+on_exit.2 = "other_state"
+on_exit._ = "oops"
+retry = { max_attempts = 3, backoff = 10 }
+        "#;
+        let (script, handlers) = script_from_toml(file);
+        assert!(handlers.retry().is_some());
+
+        let output = script.output();
+        assert!(!handlers.is_failure(&output));
+        let state_update = handlers.state_update(&script.command, output);
+        assert_eq!(
+            state_update,
+            json! ({
+                "status": "other_state"
+            })
+        )
+    }
+
+    #[test]
+    fn unmapped_exit_code_is_retried() {
+        let file = r#"
+script = "sh -c 'exit 3'"
+on_exit.0 = "yeah"
+on_exit.2 = "other_state"
+on_exit._ = "oops"
+retry = { max_attempts = 3, backoff = 10 }
+        "#;
+        let (script, handlers) = script_from_toml(file);
+        let output = script.output();
+        assert!(handlers.is_failure(&output));
+
+        let (script, handlers) = script_from_toml(&file.replace("exit 3", "exit 0"));
+        let output = script.output();
+        assert!(!handlers.is_failure(&output));
+    }
+
     #[test]
     fn stdout_determines_next_state() {
         let file = r#"
@@ -1,7 +1,11 @@
+pub mod check;
+pub mod condition;
 pub mod error;
 pub mod handlers;
 pub(crate) mod log;
 mod on_disk;
+pub mod parallel;
+pub mod schedule;
 pub mod state;
 pub mod supervisor;
 mod toml_config;
@@ -12,10 +16,14 @@ use crate::mqtt_topics::OperationType;
 use crate::script::ShellScript;
 use crate::substitution::Record;
 use ::log::info;
+pub use check::*;
+pub use condition::*;
 pub use error::*;
 pub use handlers::*;
 use mqtt_channel::MqttMessage;
 use mqtt_channel::QoS;
+pub use parallel::*;
+pub use schedule::*;
 use serde::Deserialize;
 use serde_json::json;
 pub use state::*;
@@ -153,6 +161,34 @@ pub enum OperationAction {
     /// on_error = "failed"
     /// ```
     Iterate(JsonPath, IterateHandlers),
+
+    /// Launch in parallel a sub-operation for each item of the specified target array,
+    /// moving to the next state from where the completion of all these sub-operations will be awaited.
+    ///
+    /// The init payload of each sub-operation is derived from the `input` excerpt
+    /// evaluated with the item captured into a `@next` fragment as for a sequential iteration.
+    ///
+    /// ```toml
+    /// iterate = "${.payload.devices}"
+    /// parallel = { operation = "firmware_update", max_concurrency = 5, fail_fast = true }
+    /// input.url = "${.payload.@next.item.url}"
+    /// on_exec = "awaiting_firmware_updates"
+    /// ```
+    ///
+    /// Each sub-operation can be sent to another entity, given by the `parallel.target` topic id.
+    ParallelIterate(JsonPath, ParallelSpec, StateExcerpt, ExecHandlers),
+
+    /// Move to the state given by the first condition holding on the command state
+    ///
+    /// ```toml
+    /// action = "switch"
+    /// on_case = [
+    ///     { when = '${.payload.version} == "2.0"', next = "install_v2" },
+    ///     { when = "${.payload.rollback}", next = "rollback" },
+    /// ]
+    /// on_default = "install_v1"
+    /// ```
+    Switch(SwitchHandlers),
 }
 
 impl Display for OperationAction {
@@ -180,6 +216,18 @@ impl Display for OperationAction {
             OperationAction::Iterate(json_path, _) => {
                 format!("iterate over {json_path}").to_string()
             }
+            OperationAction::ParallelIterate(json_path, spec, _, _) => format!(
+                "execute {} as parallel sub-operations over {json_path}",
+                spec.operation
+            ),
+            OperationAction::Switch(handlers) => {
+                let cases: Vec<String> = handlers
+                    .on_case
+                    .iter()
+                    .map(|(condition, _)| condition.to_string())
+                    .collect();
+                format!("switch on {}", cases.join(", "))
+            }
         };
         f.write_str(&str)
     }
@@ -314,6 +362,8 @@ impl OperationWorkflow {
             OperationType::Custom(_)
             | OperationType::Restart
             | OperationType::DeviceProfile
+            | OperationType::Shell
+            | OperationType::FileList
             | OperationType::FirmwareUpdate => {
                 let meta_topic = schema.capability_topic_for(target, self.operation.clone());
                 let payload = "{}".to_string();
@@ -347,6 +397,22 @@ impl OperationWorkflow {
             })
             .map(|action| action.inject_state(command_state))
     }
+
+    /// Return the state that triggered the sub-operation awaited in the given state,
+    /// along with the policy to retry that sub-operation, if any.
+    pub fn sub_operation_retry_policy(
+        &self,
+        await_state: &str,
+    ) -> Option<(&StateName, &RetryPolicy)> {
+        self.states.iter().find_map(|(state, action)| match action {
+            OperationAction::Operation(_, _, _, handlers)
+                if handlers.on_exec.status == await_state =>
+            {
+                handlers.retry.as_ref().map(|retry| (state, retry))
+            }
+            _ => None,
+        })
+    }
 }
 
 impl OperationAction {
@@ -504,6 +570,9 @@ pub enum IterationError {
 
     #[error("Index: {0} is out of bounds")]
     IndexOutOfBounds(usize),
+
+    #[error("Item {0} has no valid target entity topic id: {1}")]
+    InvalidItemTarget(usize, String),
 }
 
 #[cfg(test)]
@@ -0,0 +1,478 @@
+use crate::mqtt_topics::EntityTopicId;
+use crate::workflow::AwaitHandlers;
+use crate::workflow::GenericCommandState;
+use crate::workflow::IterationError;
+use crate::workflow::OperationName;
+use crate::workflow::StateExcerpt;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+
+const PARALLEL_KEY: &str = "@parallel";
+const RESULTS_KEY: &str = "@results";
+
+/// Define how to launch sub-operations in parallel, one for each item of an array
+///
+/// ```toml
+/// iterate = "${.payload.devices}"
+/// parallel = { operation = "firmware_update", max_concurrency = 5, fail_fast = true }
+/// input.url = "${.payload.@next.item.url}"
+/// on_exec = "awaiting_firmware_updates"
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct ParallelSpec {
+    /// The sub-operation launched for each item
+    pub operation: OperationName,
+
+    /// The entity topic id of the target of each sub-operation, e.g. `"${.payload.@next.item.topic_id}"`
+    ///
+    /// By default, the sub-operations are executed by the device of the invoking command.
+    #[serde(default)]
+    pub target: Option<String>,
+
+    /// The maximum number of sub-operations running at the same time
+    #[serde(default = "ParallelSpec::default_max_concurrency")]
+    pub max_concurrency: usize,
+
+    /// When true, no more sub-operations are launched after a first failure
+    #[serde(default)]
+    pub fail_fast: bool,
+}
+
+impl ParallelSpec {
+    fn default_max_concurrency() -> usize {
+        10
+    }
+}
+
+/// The progress of sub-operations launched in parallel by a command
+///
+/// This progress is persisted under the `@parallel` fragment of the command payload,
+/// so the iteration can be resumed after an agent restart.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ParallelIteration {
+    /// The sub-operation launched for each item
+    pub operation: OperationName,
+
+    /// The maximum number of sub-operations running at the same time
+    pub max_concurrency: usize,
+
+    /// When true, no more sub-operations are launched after a first failure
+    pub fail_fast: bool,
+
+    /// The init payloads of the sub-operations, one per item
+    pub inputs: Vec<Value>,
+
+    /// The entity topic ids of the targets of the sub-operations, one per item, if not the device itself
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+
+    /// The number of sub-operations launched so far
+    pub launched: usize,
+
+    /// The outcome of the sub-operations completed so far
+    pub results: Vec<Value>,
+}
+
+impl ParallelIteration {
+    /// Prepare the sub-operations to be launched for each item of the array pointed by the json path
+    ///
+    /// The init payload of each sub-operation is derived from the item,
+    /// available to the input excerpt as a `@next` fragment of the command payload,
+    /// with an `index` and an `item` field as for a sequential iteration.
+    /// When no input excerpt is provided, the item itself is used as the init payload.
+    pub fn start(
+        state: &GenericCommandState,
+        json_path: &str,
+        spec: &ParallelSpec,
+        input: &StateExcerpt,
+    ) -> Result<Self, IterationError> {
+        let Some(target) = state.extract_value(json_path) else {
+            return Err(IterationError::InvalidTarget(json_path.to_string()));
+        };
+
+        let Some(items) = target.as_array() else {
+            return Err(IterationError::TargetNotArray(json_path.to_string()));
+        };
+
+        let no_input = input == &StateExcerpt::ExcerptMap(Default::default());
+        let mut inputs = Vec::with_capacity(items.len());
+        let mut targets = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            let item_state = state.clone().update_with_json(json!({
+                "@next": {
+                    "index": index,
+                    "item": item,
+                }
+            }));
+            if let Some(target) = &spec.target {
+                let target = StateExcerpt::from(Value::from(target.as_str()))
+                    .extract_value_from(&item_state);
+                let Some(entity) = target
+                    .as_str()
+                    .and_then(|target| target.parse::<EntityTopicId>().ok())
+                else {
+                    return Err(IterationError::InvalidItemTarget(index, target.to_string()));
+                };
+                targets.push(entity.to_string());
+            }
+            if no_input {
+                inputs.push(item.clone());
+            } else {
+                inputs.push(input.extract_value_from(&item_state));
+            }
+        }
+
+        Ok(ParallelIteration {
+            operation: spec.operation.clone(),
+            max_concurrency: spec.max_concurrency.max(1),
+            fail_fast: spec.fail_fast,
+            inputs,
+            targets,
+            launched: 0,
+            results: Vec::new(),
+        })
+    }
+
+    /// Extract the progress of a parallel iteration from a command state, if any
+    pub fn from_state(state: &GenericCommandState) -> Option<Self> {
+        state
+            .payload
+            .get(PARALLEL_KEY)
+            .and_then(|progress| serde_json::from_value(progress.clone()).ok())
+    }
+
+    /// Inject the progress of this iteration into the command state
+    pub fn inject_into(&self, state: GenericCommandState) -> GenericCommandState {
+        state.update_with_json(json!({ PARALLEL_KEY: self }))
+    }
+
+    /// The entity targeted by the sub-operation launched for an item
+    ///
+    /// Return None if this sub-operation is executed by the device of the invoking command.
+    pub fn target(&self, index: usize) -> Option<EntityTopicId> {
+        self.targets
+            .get(index)
+            .and_then(|target| target.parse().ok())
+    }
+
+    /// The number of sub-operations launched and not completed yet
+    pub fn running(&self) -> usize {
+        self.launched.saturating_sub(self.results.len())
+    }
+
+    /// The indexes of the sub-operations launched and not completed yet
+    pub fn running_items(&self) -> Vec<usize> {
+        (0..self.launched)
+            .filter(|index| !self.is_recorded(*index))
+            .collect()
+    }
+
+    /// Return true if the outcome of a sub-operation has already been recorded
+    pub fn is_recorded(&self, index: usize) -> bool {
+        self.results
+            .iter()
+            .any(|result| result.get("index").and_then(|i| i.as_u64()) == Some(index as u64))
+    }
+
+    /// Return true if at least one sub-operation failed
+    pub fn has_failed(&self) -> bool {
+        self.results
+            .iter()
+            .any(|result| result.get("status").and_then(|s| s.as_str()) != Some("successful"))
+    }
+
+    /// Record the outcome of a sub-operation
+    ///
+    /// Return false if this outcome has already been recorded.
+    pub fn record(&mut self, index: usize, sub_state: &GenericCommandState, output: Value) -> bool {
+        if self.is_recorded(index) || index >= self.launched {
+            return false;
+        }
+
+        let mut result = json!({
+            "index": index,
+            "status": sub_state.status,
+        });
+        if let Some(reason) = sub_state.failure_reason() {
+            result["reason"] = reason.into();
+        }
+        if sub_state.is_successful() {
+            result["output"] = output;
+        }
+        self.results.push(result);
+        true
+    }
+
+    /// Return the index and init payload of the sub-operations that can be launched
+    /// given the number of sub-operations still running.
+    pub fn next_items(&mut self) -> Vec<(usize, Value)> {
+        if self.fail_fast && self.has_failed() {
+            return Vec::new();
+        }
+
+        let free_slots = self.max_concurrency.saturating_sub(self.running());
+        let next: Vec<(usize, Value)> = self
+            .inputs
+            .iter()
+            .enumerate()
+            .skip(self.launched)
+            .take(free_slots)
+            .map(|(index, input)| (index, input.clone()))
+            .collect();
+        self.launched += next.len();
+        next
+    }
+
+    /// Return true when there are no more sub-operations to launch or to await
+    pub fn is_complete(&self) -> bool {
+        let nothing_to_launch =
+            self.launched >= self.inputs.len() || (self.fail_fast && self.has_failed());
+        nothing_to_launch && self.running() == 0
+    }
+
+    /// Move the command state to its next step once all the sub-operations completed
+    ///
+    /// The outcome of all the sub-operations are aggregated, ordered by index, into an `@results` fragment.
+    pub fn finalize(
+        mut self,
+        mut state: GenericCommandState,
+        handlers: AwaitHandlers,
+    ) -> GenericCommandState {
+        if let Some(payload) = state.payload.as_object_mut() {
+            payload.remove(PARALLEL_KEY);
+        }
+
+        self.results
+            .sort_by_key(|result| result.get("index").and_then(|i| i.as_u64()));
+        let failures = self
+            .results
+            .iter()
+            .filter(|result| result.get("status").and_then(|s| s.as_str()) != Some("successful"))
+            .count();
+        let skipped = self.inputs.len() - self.results.len();
+        let has_failed = self.has_failed();
+        let state = state.update_with_json(json!({ RESULTS_KEY: self.results }));
+
+        if has_failed {
+            let mut on_error = handlers.on_error;
+            if on_error.reason.is_none() {
+                on_error.reason = Some(format!(
+                    "{failures} {} sub-operation(s) failed, {skipped} not launched",
+                    self.operation
+                ));
+            }
+            state.update(on_error)
+        } else {
+            state.update(handlers.on_success)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::GenericStateUpdate;
+    use mqtt_channel::Topic;
+
+    fn command(payload: Value) -> GenericCommandState {
+        GenericCommandState::new(
+            Topic::new_unchecked("te/device/main///cmd/rollout/123"),
+            "init".to_string(),
+            payload,
+        )
+    }
+
+    fn sub_command(index: usize, status: &str) -> GenericCommandState {
+        GenericCommandState::new(
+            Topic::new_unchecked(&format!(
+                "te/device/main///cmd/firmware_update/sub:rollout@{index}:123"
+            )),
+            status.to_string(),
+            json!({}),
+        )
+    }
+
+    #[test]
+    fn launch_sub_operations_with_limited_concurrency() {
+        let state = command(json!({ "targets": [{"v": 1}, {"v": 2}, {"v": 3}] }));
+        let spec = ParallelSpec {
+            operation: "firmware_update".to_string(),
+            target: None,
+            max_concurrency: 2,
+            fail_fast: false,
+        };
+        let mut iteration = ParallelIteration::start(
+            &state,
+            ".payload.targets",
+            &spec,
+            &StateExcerpt::ExcerptMap(Default::default()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            iteration.next_items(),
+            vec![(0, json!({"v": 1})), (1, json!({"v": 2}))]
+        );
+        assert_eq!(iteration.next_items(), vec![]);
+        assert!(!iteration.is_complete());
+
+        assert!(iteration.record(1, &sub_command(1, "successful"), json!({})));
+        assert!(!iteration.record(1, &sub_command(1, "successful"), json!({})));
+        assert_eq!(iteration.next_items(), vec![(2, json!({"v": 3}))]);
+
+        assert!(iteration.record(0, &sub_command(0, "successful"), json!({})));
+        assert!(iteration.record(2, &sub_command(2, "successful"), json!({})));
+        assert!(iteration.is_complete());
+
+        let handlers = AwaitHandlers::builtin_default();
+        let final_state = iteration.finalize(state, handlers);
+        assert!(final_state.is_successful());
+        assert_eq!(final_state.payload.get("@parallel"), None);
+        assert_eq!(
+            final_state.payload["@results"],
+            json!([
+                {"index": 0, "status": "successful", "output": {}},
+                {"index": 1, "status": "successful", "output": {}},
+                {"index": 2, "status": "successful", "output": {}},
+            ])
+        );
+    }
+
+    #[test]
+    fn stop_launching_sub_operations_on_first_failure() {
+        let state = command(json!({ "targets": ["a", "b", "c"] }));
+        let spec = ParallelSpec {
+            operation: "firmware_update".to_string(),
+            target: None,
+            max_concurrency: 1,
+            fail_fast: true,
+        };
+        let input: StateExcerpt = json!({"device": "${.payload.@next.item}"}).into();
+        let mut iteration =
+            ParallelIteration::start(&state, ".payload.targets", &spec, &input).unwrap();
+
+        assert_eq!(iteration.next_items(), vec![(0, json!({"device": "a"}))]);
+        let failed = sub_command(0, "failed").update(GenericStateUpdate::failed("oops".into()));
+        assert!(iteration.record(0, &failed, json!({})));
+        assert_eq!(iteration.next_items(), vec![]);
+        assert!(iteration.is_complete());
+
+        let final_state = iteration.finalize(state, AwaitHandlers::builtin_default());
+        assert!(final_state.is_failed());
+        assert_eq!(
+            final_state.failure_reason(),
+            Some("1 firmware_update sub-operation(s) failed, 2 not launched")
+        );
+    }
+
+    #[test]
+    fn stop_launching_concurrent_sub_operations_on_first_failure() {
+        let state = command(json!({ "targets": ["a", "b", "c", "d", "e"] }));
+        let spec = ParallelSpec {
+            operation: "firmware_update".to_string(),
+            target: None,
+            max_concurrency: 2,
+            fail_fast: true,
+        };
+        let mut iteration = ParallelIteration::start(
+            &state,
+            ".payload.targets",
+            &spec,
+            &StateExcerpt::ExcerptMap(Default::default()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            iteration.next_items(),
+            vec![(0, json!("a")), (1, json!("b"))]
+        );
+
+        // The first failure stops the launch of new sub-operations, even if a slot is free
+        let failed = sub_command(1, "failed").update(GenericStateUpdate::failed("oops".into()));
+        assert!(iteration.record(1, &failed, json!({})));
+        assert_eq!(iteration.next_items(), vec![]);
+        assert!(!iteration.is_complete());
+
+        // The sub-operations already running are awaited, but no new ones are launched
+        assert!(iteration.record(0, &sub_command(0, "successful"), json!({})));
+        assert_eq!(iteration.next_items(), vec![]);
+        assert!(iteration.is_complete());
+
+        let final_state = iteration.finalize(state, AwaitHandlers::builtin_default());
+        assert!(final_state.is_failed());
+        assert_eq!(
+            final_state.failure_reason(),
+            Some("1 firmware_update sub-operation(s) failed, 3 not launched")
+        );
+        assert_eq!(
+            final_state.payload["@results"],
+            json!([
+                {"index": 0, "status": "successful", "output": {}},
+                {"index": 1, "status": "failed", "reason": "oops"},
+            ])
+        );
+    }
+
+    #[test]
+    fn launch_sub_operations_on_the_entities_given_by_the_items() {
+        let state = command(json!({ "targets": [
+            {"topic_id": "device/child1//", "url": "a"},
+            {"topic_id": "device/child2//", "url": "b"},
+        ] }));
+        let spec = ParallelSpec {
+            operation: "firmware_update".to_string(),
+            target: Some("${.payload.@next.item.topic_id}".to_string()),
+            max_concurrency: 2,
+            fail_fast: false,
+        };
+        let input: StateExcerpt = json!({"url": "${.payload.@next.item.url}"}).into();
+        let mut iteration =
+            ParallelIteration::start(&state, ".payload.targets", &spec, &input).unwrap();
+
+        assert_eq!(
+            iteration.next_items(),
+            vec![(0, json!({"url": "a"})), (1, json!({"url": "b"}))]
+        );
+        assert_eq!(
+            iteration.target(0),
+            Some(EntityTopicId::default_child_device("child1").unwrap())
+        );
+        assert_eq!(
+            iteration.target(1),
+            Some(EntityTopicId::default_child_device("child2").unwrap())
+        );
+
+        // The targets are persisted along the progress of the iteration
+        let state = iteration.inject_into(state);
+        let iteration = ParallelIteration::from_state(&state).unwrap();
+        assert_eq!(
+            iteration.target(1),
+            Some(EntityTopicId::default_child_device("child2").unwrap())
+        );
+    }
+
+    #[test]
+    fn reject_items_with_no_target_entity() {
+        let state = command(json!({ "targets": [{"topic_id": "device/child1//"}, {}] }));
+        let spec = ParallelSpec {
+            operation: "firmware_update".to_string(),
+            target: Some("${.payload.@next.item.topic_id}".to_string()),
+            max_concurrency: 2,
+            fail_fast: false,
+        };
+        let error = ParallelIteration::start(
+            &state,
+            ".payload.targets",
+            &spec,
+            &StateExcerpt::ExcerptMap(Default::default()),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Item 1 has no valid target entity topic id: null"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use serde::de::Error;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde_json::Value;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::Time;
+use time::UtcOffset;
+
+/// When a command is allowed to leave its `scheduled` state
+///
+/// A schedule is given by optional properties of the command payload:
+///
+/// ```json
+/// {
+///     "status": "init",
+///     "scheduledAt": "2024-06-01T02:00:00Z",
+///     "maintenanceWindow": { "start": "01:00", "end": "05:00" }
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandSchedule {
+    /// The command is not executed before this date
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub scheduled_at: Option<OffsetDateTime>,
+
+    /// The command is only executed during this daily window
+    #[serde(default)]
+    pub maintenance_window: Option<MaintenanceWindow>,
+}
+
+/// A daily time window, in UTC, during which commands can be executed
+///
+/// The window can span midnight, as `{ "start": "22:00", "end": "04:00" }`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MaintenanceWindow {
+    pub start: Time,
+    pub end: Time,
+}
+
+impl CommandSchedule {
+    /// Extract the schedule of a command from its payload
+    ///
+    /// Return `None` if the command is not scheduled, i.e. if it has to be executed as soon as possible.
+    pub fn from_payload(payload: &Value) -> Result<Option<Self>, ScheduleError> {
+        let schedule = CommandSchedule::deserialize(payload)
+            .map_err(|err| ScheduleError::InvalidSchedule(err.to_string()))?;
+        if schedule.scheduled_at.is_none() && schedule.maintenance_window.is_none() {
+            Ok(None)
+        } else {
+            Ok(Some(schedule))
+        }
+    }
+
+    /// Return how long to wait before the command can be executed
+    ///
+    /// Return `None` if the command can be executed right away.
+    pub fn delay(&self, now: OffsetDateTime) -> Option<Duration> {
+        let not_before = match self.scheduled_at {
+            Some(scheduled_at) if scheduled_at > now => scheduled_at,
+            _ => now,
+        };
+        let start = match &self.maintenance_window {
+            None => not_before,
+            Some(window) => window.next_opening(not_before),
+        };
+        if start > now {
+            (start - now).try_into().ok()
+        } else {
+            None
+        }
+    }
+}
+
+impl MaintenanceWindow {
+    /// Check if the given time of the day is in this window
+    pub fn contains(&self, time: Time) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+
+    /// Return the first instant, from the given one, which is in this window
+    pub fn next_opening(&self, from: OffsetDateTime) -> OffsetDateTime {
+        let from = from.to_offset(UtcOffset::UTC);
+        if self.contains(from.time()) {
+            return from;
+        }
+        let opening = from.replace_time(self.start);
+        if opening > from {
+            opening
+        } else {
+            opening + time::Duration::days(1)
+        }
+    }
+}
+
+/// Parse a time of the day given as `HH:MM` or `HH:MM:SS`
+fn parse_time_of_day(input: &str) -> Result<Time, ScheduleError> {
+    let invalid = || ScheduleError::InvalidTimeOfDay(input.to_string());
+    let mut parts = input.split(':');
+    let mut next_part = |required: bool| -> Result<u8, ScheduleError> {
+        match parts.next() {
+            Some(part) => part.trim().parse().map_err(|_| invalid()),
+            None if required => Err(invalid()),
+            None => Ok(0),
+        }
+    };
+    let hour = next_part(true)?;
+    let minute = next_part(true)?;
+    let second = next_part(false)?;
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+    Time::from_hms(hour, minute, second).map_err(|_| invalid())
+}
+
+impl FromStr for MaintenanceWindow {
+    type Err = ScheduleError;
+
+    /// Parse a window given as `HH:MM-HH:MM`
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let Some((start, end)) = input.split_once('-') else {
+            return Err(ScheduleError::InvalidTimeOfDay(input.to_string()));
+        };
+        MaintenanceWindow::try_new(parse_time_of_day(start)?, parse_time_of_day(end)?)
+    }
+}
+
+impl MaintenanceWindow {
+    pub fn try_new(start: Time, end: Time) -> Result<Self, ScheduleError> {
+        if start == end {
+            return Err(ScheduleError::EmptyMaintenanceWindow);
+        }
+        Ok(MaintenanceWindow { start, end })
+    }
+}
+
+impl<'de> Deserialize<'de> for MaintenanceWindow {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RawWindow {
+            start: String,
+            end: String,
+        }
+
+        let raw = RawWindow::deserialize(deserializer)?;
+        let start = parse_time_of_day(&raw.start).map_err(D::Error::custom)?;
+        let end = parse_time_of_day(&raw.end).map_err(D::Error::custom)?;
+        MaintenanceWindow::try_new(start, end).map_err(D::Error::custom)
+    }
+}
+
+impl Display for MaintenanceWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start.hour(),
+            self.start.minute(),
+            self.end.hour(),
+            self.end.minute()
+        )
+    }
+}
+
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+pub enum ScheduleError {
+    #[error("Invalid command schedule: {0}")]
+    InvalidSchedule(String),
+
+    #[error("Expected a time of the day as HH:MM: {0}")]
+    InvalidTimeOfDay(String),
+
+    #[error("A maintenance window cannot start and end at the same time")]
+    EmptyMaintenanceWindow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use time::macros::datetime;
+    use time::macros::time;
+
+    #[test]
+    fn commands_are_not_scheduled_by_default() {
+        let payload = json!({"status": "init", "modules": []});
+        assert_eq!(CommandSchedule::from_payload(&payload), Ok(None));
+    }
+
+    #[test]
+    fn parse_command_schedule() {
+        let payload = json!({
+            "status": "init",
+            "scheduledAt": "2024-06-01T02:00:00Z",
+            "maintenanceWindow": { "start": "22:00", "end": "04:30" }
+        });
+        assert_eq!(
+            CommandSchedule::from_payload(&payload),
+            Ok(Some(CommandSchedule {
+                scheduled_at: Some(datetime!(2024-06-01 02:00:00 UTC)),
+                maintenance_window: Some(MaintenanceWindow {
+                    start: time!(22:00),
+                    end: time!(04:30),
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn reject_invalid_schedule() {
+        let payload = json!({"status": "init", "scheduledAt": "tomorrow"});
+        assert!(CommandSchedule::from_payload(&payload).is_err());
+
+        let payload = json!({"maintenanceWindow": { "start": "25:00", "end": "04:00" }});
+        assert!(CommandSchedule::from_payload(&payload).is_err());
+
+        let payload = json!({"maintenanceWindow": { "start": "04:00", "end": "04:00" }});
+        assert!(CommandSchedule::from_payload(&payload).is_err());
+    }
+
+    #[test]
+    fn delay_commands_till_scheduled_date() {
+        let schedule = CommandSchedule {
+            scheduled_at: Some(datetime!(2024-06-01 02:00:00 UTC)),
+            maintenance_window: None,
+        };
+        assert_eq!(
+            schedule.delay(datetime!(2024-06-01 01:30:00 UTC)),
+            Some(Duration::from_secs(30 * 60))
+        );
+        assert_eq!(schedule.delay(datetime!(2024-06-01 02:00:00 UTC)), None);
+        assert_eq!(schedule.delay(datetime!(2024-06-02 00:00:00 UTC)), None);
+    }
+
+    #[test]
+    fn delay_commands_till_maintenance_window() {
+        let schedule = CommandSchedule {
+            scheduled_at: None,
+            maintenance_window: Some("22:00-04:00".parse().unwrap()),
+        };
+        assert_eq!(schedule.delay(datetime!(2024-06-01 23:00:00 UTC)), None);
+        assert_eq!(schedule.delay(datetime!(2024-06-01 03:59:00 UTC)), None);
+        assert_eq!(
+            schedule.delay(datetime!(2024-06-01 04:00:00 UTC)),
+            Some(Duration::from_secs(18 * 3600))
+        );
+        assert_eq!(
+            schedule.delay(datetime!(2024-06-01 21:00:00 +02:00)),
+            Some(Duration::from_secs(3 * 3600))
+        );
+    }
+
+    #[test]
+    fn delay_commands_till_maintenance_window_after_scheduled_date() {
+        let schedule = CommandSchedule {
+            scheduled_at: Some(datetime!(2024-06-02 05:00:00 UTC)),
+            maintenance_window: Some("01:00-03:00".parse().unwrap()),
+        };
+        assert_eq!(
+            schedule.delay(datetime!(2024-06-01 02:00:00 UTC)),
+            Some(Duration::from_secs(47 * 3600))
+        );
+    }
+}
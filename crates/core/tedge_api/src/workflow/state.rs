@@ -6,6 +6,7 @@ use crate::substitution::Record;
 use crate::workflow::CommandId;
 use crate::workflow::ExitHandlers;
 use crate::workflow::OperationName;
+use crate::workflow::RetryPolicy;
 use crate::workflow::StateExcerptError;
 use crate::workflow::WorkflowExecutionError;
 use crate::CommandStatus;
@@ -20,9 +21,11 @@ use serde_json::json;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::time::Duration;
 
 const OP_LOG_PATH_KEY: &str = "logPath";
 const OP_WORKFLOW_VERSION_KEY: &str = "@version";
+const OP_RETRY_KEY: &str = "@retry";
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum GenericCommandData {
@@ -94,8 +97,54 @@ impl GenericCommandState {
         sub_operation: OperationName,
     ) -> GenericCommandState {
         let sub_cmd_id = Self::sub_command_id(&operation, &cmd_id);
-        let topic = schema.topic_for(
+        Self::init_state_with_sub_command_id(
+            schema,
+            entity,
+            entity,
+            operation,
+            cmd_id,
+            sub_operation,
+            sub_cmd_id,
+        )
+    }
+
+    /// Create an init state for one of the sub-operations launched in parallel by a command
+    ///
+    /// The index is used to distinguish the sub-operations launched by the same command,
+    /// and the sub-operation is sent to the `target` entity, which might not be the invoking `entity`.
+    pub fn parallel_sub_command_init_state(
+        schema: &MqttSchema,
+        entity: &EntityTopicId,
+        target: &EntityTopicId,
+        operation: OperationType,
+        cmd_id: CommandId,
+        sub_operation: OperationName,
+        index: usize,
+    ) -> GenericCommandState {
+        let sub_cmd_id = Self::parallel_sub_command_id(&operation, index, &cmd_id);
+        Self::init_state_with_sub_command_id(
+            schema,
             entity,
+            target,
+            operation,
+            cmd_id,
+            sub_operation,
+            sub_cmd_id,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn init_state_with_sub_command_id(
+        schema: &MqttSchema,
+        entity: &EntityTopicId,
+        target: &EntityTopicId,
+        operation: OperationType,
+        cmd_id: CommandId,
+        sub_operation: OperationName,
+        sub_cmd_id: CommandId,
+    ) -> GenericCommandState {
+        let topic = schema.topic_for(
+            target,
             &Channel::Command {
                 operation: OperationType::Custom(sub_operation),
                 cmd_id: sub_cmd_id,
@@ -231,6 +280,63 @@ impl GenericCommandState {
         self.set_key_value(OP_WORKFLOW_VERSION_KEY, version)
     }
 
+    /// Return the number of failed attempts to execute the action of the given state
+    pub fn retry_attempts(&self, state: &str) -> u32 {
+        self.payload
+            .get(OP_RETRY_KEY)
+            .and_then(|attempts| attempts.get(state))
+            .and_then(|attempts| attempts.as_u64())
+            .unwrap_or(0) as u32
+    }
+
+    /// Record the number of failed attempts to execute the action of the given state
+    pub fn with_retry_attempts(mut self, state: &str, attempts: u32) -> Self {
+        if let Some(o) = self.payload.as_object_mut() {
+            let retries = o.entry(OP_RETRY_KEY).or_insert_with(|| json!({}));
+            if let Some(retries) = retries.as_object_mut() {
+                retries.insert(state.to_string(), attempts.into());
+            }
+        }
+        self
+    }
+
+    /// Reset the number of failed attempts to execute the action of the given state
+    pub fn clear_retry_attempts(mut self, state: &str) -> Self {
+        if let Some(o) = self.payload.as_object_mut() {
+            let now_empty = match o.get_mut(OP_RETRY_KEY).and_then(|r| r.as_object_mut()) {
+                None => false,
+                Some(retries) => {
+                    retries.remove(state);
+                    retries.is_empty()
+                }
+            };
+            if now_empty {
+                o.remove(OP_RETRY_KEY);
+            }
+        }
+        self
+    }
+
+    /// Update this failed state for a new attempt to execute the action of the `retried_state`
+    ///
+    /// Return the state to retry along with the delay to wait before that new attempt,
+    /// or, when all the attempts have been exhausted, the final failed state with no delay.
+    pub fn retry_or_fail(
+        self,
+        retried_state: &str,
+        policy: &RetryPolicy,
+    ) -> (Self, Option<Duration>) {
+        let failed_attempts = self.retry_attempts(retried_state) + 1;
+        let failed_state = self.with_retry_attempts(retried_state, failed_attempts);
+        match policy.next_attempt(failed_attempts) {
+            Some(delay) => (failed_state.move_to(retried_state.into()), Some(delay)),
+            None => match &policy.on_exhausted {
+                None => (failed_state, None),
+                Some(on_exhausted) => (failed_state.update(on_exhausted.clone()), None),
+            },
+        }
+    }
+
     /// Update the command state with the outcome of a script
     pub fn update_with_script_output(
         self,
@@ -342,6 +448,22 @@ impl GenericCommandState {
         }
     }
 
+    /// Attach a sub command received from another entity to its invoking command on the given entity
+    ///
+    /// By default, the invoking command of a sub command is searched on the entity of the sub command.
+    pub fn with_invoking_entity(mut self, schema: &MqttSchema, entity: &EntityTopicId) -> Self {
+        self.invoking_command_topic = self.cmd_id().and_then(|sub_cmd_id| {
+            Self::extract_invoking_command_id(&sub_cmd_id).map(|(op, id)| {
+                let channel = Channel::Command {
+                    operation: op.into(),
+                    cmd_id: id.into(),
+                };
+                schema.topic_for(entity, &channel).as_ref().to_string()
+            })
+        });
+        self
+    }
+
     /// Build a sub command identifier from its invoking command identifier
     ///
     /// Using such a structure command id for sub commands is key
@@ -350,6 +472,15 @@ impl GenericCommandState {
         format!("sub:{operation}:{cmd_id}")
     }
 
+    /// Build the identifier of a sub command launched in parallel with others by the same invoking command
+    fn parallel_sub_command_id(
+        operation: &impl Display,
+        index: usize,
+        cmd_id: &impl Display,
+    ) -> String {
+        format!("sub:{operation}@{index}:{cmd_id}")
+    }
+
     /// Extract the invoking command identifier from a sub command identifier
     ///
     /// Return None if the given id is not a sub command identifier, i.e. if not generated with [sub_command_id].
@@ -357,6 +488,18 @@ impl GenericCommandState {
         sub_cmd_id
             .strip_prefix("sub:")
             .and_then(|op_id| op_id.split_once(':'))
+            .map(|(op, id)| (op.split_once('@').map_or(op, |(op, _index)| op), id))
+    }
+
+    /// Return the index of a sub command launched in parallel with others by the same invoking command
+    ///
+    /// Return None if this command has not been launched by a parallel iteration.
+    pub fn sub_command_index(&self) -> Option<usize> {
+        self.cmd_id()?
+            .strip_prefix("sub:")
+            .and_then(|op_id| op_id.split_once(':'))
+            .and_then(|(op, _)| op.split_once('@'))
+            .and_then(|(_, index)| index.parse().ok())
     }
 
     /// Extract the invoking operation names from a command identifier
@@ -393,6 +536,10 @@ impl GenericCommandState {
         self.status.as_str() == INIT
     }
 
+    pub fn is_scheduled(&self) -> bool {
+        self.status.as_str() == SCHEDULED
+    }
+
     pub fn is_executing(&self) -> bool {
         self.status.as_str() == EXECUTING
     }
@@ -789,4 +936,79 @@ mod tests {
         let cmd = GenericCommandState::from_command_message(&command).expect("parsing error");
         assert!(cmd.is_cleared())
     }
+
+    #[test]
+    fn retry_failed_steps_until_exhausted() {
+        let topic = Topic::new_unchecked("te/device/main///cmd/make_it/123");
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            backoff: crate::workflow::Backoff::fixed(Duration::from_secs(10)),
+            on_exhausted: Some("gave_up".into()),
+        };
+        let failed = GenericCommandState::new(topic, "download".to_string(), json!({}))
+            .update(GenericStateUpdate::failed("network down".to_string()));
+
+        let (retried, delay) = failed.retry_or_fail("download", &policy);
+        assert_eq!(delay, Some(Duration::from_secs(10)));
+        assert_eq!(retried.status, "download");
+        assert_eq!(retried.retry_attempts("download"), 1);
+
+        let failed = retried.update(GenericStateUpdate::failed("network down".to_string()));
+        let (exhausted, delay) = failed.retry_or_fail("download", &policy);
+        assert_eq!(delay, None);
+        assert_eq!(exhausted.status, "gave_up");
+        assert_eq!(exhausted.retry_attempts("download"), 2);
+
+        let cleared = exhausted.clear_retry_attempts("download");
+        assert_eq!(cleared.retry_attempts("download"), 0);
+        assert_eq!(cleared.payload.get("@retry"), None);
+    }
+
+    #[test]
+    fn retrieve_invoking_command_of_parallel_sub_command() {
+        let topic = Topic::new_unchecked("te/device/main///cmd/do_it/sub:make_it@3:456");
+        let payload = r#"{ "status":"successful" }"#;
+        let command = mqtt_channel::MqttMessage::new(&topic, payload);
+        let cmd = GenericCommandState::from_command_message(&command).expect("parsing error");
+        assert_eq!(cmd.sub_command_index(), Some(3));
+        assert_eq!(cmd.invoking_operation_names(), vec!["make_it".to_string()]);
+        assert_eq!(
+            cmd.invoking_command_topic(),
+            Some("te/device/main///cmd/make_it/456")
+        );
+    }
+
+    #[test]
+    fn retrieve_invoking_command_of_parallel_sub_command_sent_to_another_entity() {
+        let schema = MqttSchema::default();
+        let main = EntityTopicId::default_main_device();
+        let child = EntityTopicId::default_child_device("child1").unwrap();
+        let sub_cmd = GenericCommandState::parallel_sub_command_init_state(
+            &schema,
+            &main,
+            &child,
+            "make_it".parse().unwrap(),
+            "456".to_string(),
+            "do_it".to_string(),
+            3,
+        );
+        assert_eq!(
+            sub_cmd.topic.as_ref(),
+            "te/device/child1///cmd/do_it/sub:make_it@3:456"
+        );
+        assert_eq!(
+            sub_cmd.invoking_command_topic(),
+            Some("te/device/main///cmd/make_it/456")
+        );
+
+        // As received over MQTT by the agent of the main device
+        let message = mqtt_channel::MqttMessage::new(&sub_cmd.topic, r#"{"status":"successful"}"#);
+        let cmd = GenericCommandState::from_command_message(&message).expect("parsing error");
+        let cmd = cmd.with_invoking_entity(&schema, &main);
+        assert_eq!(cmd.sub_command_index(), Some(3));
+        assert_eq!(
+            cmd.invoking_command_topic(),
+            Some("te/device/main///cmd/make_it/456")
+        );
+    }
 }
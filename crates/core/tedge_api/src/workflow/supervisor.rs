@@ -3,7 +3,9 @@ use crate::workflow::*;
 use ::log::info;
 use on_disk::OnDiskCommandBoard;
 use serde::Serialize;
+use std::collections::HashSet;
 use std::string::ToString;
+use std::time::Duration;
 
 /// Dispatch actions to operation participants
 #[derive(Default)]
@@ -159,6 +161,13 @@ impl WorkflowSupervisor {
             // The command has been cleared
             self.commands.remove(&command_state.topic.name);
             Ok(Some(command_state))
+        } else if command_state.is_init()
+            && self
+                .commands
+                .is_retry_pending(command_state.command_topic())
+        {
+            // This is the echo of a command moved back to its init state to be retried after a delay
+            Ok(None)
         } else if command_state.is_init() {
             // This is a new command request
             if let Some(current_version) = workflow_versions.use_current_version() {
@@ -186,6 +195,44 @@ impl WorkflowSupervisor {
         &self,
         command_state: &GenericCommandState,
     ) -> Result<OperationAction, WorkflowExecutionError> {
+        self.get_workflow(command_state)
+            .and_then(|workflow| workflow.get_action(command_state))
+    }
+
+    /// Return the policy to retry the sub-operation awaited by a command, if any
+    ///
+    /// Along the retry policy, return the state from where the sub-operation has been triggered.
+    pub fn sub_operation_retry_policy(
+        &self,
+        command_state: &GenericCommandState,
+    ) -> Option<(StateName, RetryPolicy)> {
+        self.get_workflow(command_state)
+            .ok()?
+            .sub_operation_retry_policy(&command_state.status)
+            .map(|(state, retry)| (state.clone(), retry.clone()))
+    }
+
+    /// Return how long a command has to wait in its `scheduled` state before being executed, if at all
+    ///
+    /// The delay is derived from the `scheduledAt` and `maintenanceWindow` properties of the command payload.
+    /// Commands in any other state are never delayed.
+    pub fn scheduled_delay(
+        &self,
+        command_state: &GenericCommandState,
+        now: Timestamp,
+    ) -> Result<Option<Duration>, ScheduleError> {
+        if !command_state.is_scheduled() {
+            return Ok(None);
+        }
+        let schedule = CommandSchedule::from_payload(&command_state.payload)?;
+        Ok(schedule.and_then(|schedule| schedule.delay(now)))
+    }
+
+    /// Return the workflow version ruling a given command state
+    fn get_workflow(
+        &self,
+        command_state: &GenericCommandState,
+    ) -> Result<&OperationWorkflow, WorkflowExecutionError> {
         let Some(operation_name) = command_state.operation() else {
             return Err(WorkflowExecutionError::InvalidCmdTopic {
                 topic: command_state.topic.name.clone(),
@@ -202,7 +249,6 @@ impl WorkflowSupervisor {
                 operation: operation_name.clone(),
             })
             .and_then(|versions| versions.get(version))
-            .and_then(|workflow| workflow.get_action(command_state))
     }
 
     /// Return the current state of a command (identified by its topic)
@@ -256,6 +302,15 @@ impl WorkflowSupervisor {
             .lookup_sub_command(command_state.command_topic())
     }
 
+    /// Return all the sub commands of a command, as launched by a parallel iteration
+    pub fn sub_command_states(
+        &self,
+        command_state: &GenericCommandState,
+    ) -> Vec<&GenericCommandState> {
+        self.commands
+            .lookup_sub_commands(command_state.command_topic())
+    }
+
     /// Return the state of the root command which execution leads to the execution of a leaf-command
     ///
     /// Return None, if the given command is not a sub-command
@@ -270,6 +325,14 @@ impl WorkflowSupervisor {
         Some(root_command)
     }
 
+    /// Mark a command as waiting for a backoff delay before its current state is retried
+    ///
+    /// Till the command state is updated, the init messages received for that command are ignored,
+    /// as these are the echo of the retried state published over MQTT.
+    pub fn mark_pending_retry(&mut self, command: &str) {
+        self.commands.mark_pending_retry(command)
+    }
+
     /// Update the state of the command board on reception of new state for a command
     pub fn apply_internal_update(
         &mut self,
@@ -449,6 +512,12 @@ pub struct CommandBoard {
     /// TODO: use the timestamp to mark faulty any request making no progress
     #[serde(flatten)]
     commands: HashMap<TopicName, (Timestamp, GenericCommandState)>,
+
+    /// The commands moved back to a previous state to be retried, but waiting for a backoff delay
+    ///
+    /// This is not persisted: on restart, a command is resumed without delay.
+    #[serde(skip)]
+    pending_retries: HashSet<TopicName>,
 }
 
 pub type TopicName = String;
@@ -456,7 +525,10 @@ pub type Timestamp = time::OffsetDateTime;
 
 impl CommandBoard {
     pub fn new(commands: HashMap<TopicName, (Timestamp, GenericCommandState)>) -> Self {
-        CommandBoard { commands }
+        CommandBoard {
+            commands,
+            pending_retries: HashSet::new(),
+        }
     }
 
     pub fn get_state(&self, command: &str) -> Option<&(Timestamp, GenericCommandState)> {
@@ -472,6 +544,15 @@ impl CommandBoard {
             .map(|(_, command)| command)
     }
 
+    /// Return all the sub commands of a command, as launched by a parallel iteration
+    pub fn lookup_sub_commands(&self, command_topic: &TopicName) -> Vec<&GenericCommandState> {
+        self.commands
+            .values()
+            .filter(|(_, command)| command.invoking_command_topic() == Some(command_topic))
+            .map(|(_, command)| command)
+            .collect()
+    }
+
     /// Iterate over the pending commands
     pub fn iter(&self) -> impl Iterator<Item = &(Timestamp, GenericCommandState)> {
         self.commands.values()
@@ -514,6 +595,7 @@ impl CommandBoard {
                 topic: updated_command.topic.name,
             }),
             Some((timestamp, command_state)) => {
+                self.pending_retries.remove(&updated_command.topic.name);
                 *timestamp = time::OffsetDateTime::now_utc();
                 *command_state = updated_command;
                 Ok(())
@@ -523,8 +605,23 @@ impl CommandBoard {
 
     /// Remove from the board an operation request
     pub fn remove(&mut self, topic_name: &String) {
+        self.pending_retries.remove(topic_name);
         self.commands.remove(topic_name);
     }
+
+    /// Mark a command as waiting for a backoff delay before its current state is retried
+    ///
+    /// The mark is removed as soon as the command state is updated.
+    pub fn mark_pending_retry(&mut self, topic_name: &str) {
+        if self.commands.contains_key(topic_name) {
+            self.pending_retries.insert(topic_name.to_string());
+        }
+    }
+
+    /// Check if a command is waiting for a backoff delay before its current state is retried
+    pub fn is_retry_pending(&self, topic_name: &str) -> bool {
+        self.pending_retries.contains(topic_name)
+    }
 }
 
 #[cfg(test)]
@@ -608,4 +705,64 @@ mod tests {
             Some(&level_1_cmd)
         );
     }
+
+    #[test]
+    fn delay_scheduled_commands() {
+        let workflows = WorkflowSupervisor::default();
+        let now = time::macros::datetime!(2024-06-01 01:00:00 UTC);
+        let init_cmd = GenericCommandState::new(
+            Topic::new_unchecked("te/device/main///cmd/software_update/123"),
+            "init".to_string(),
+            serde_json::json!({ "scheduledAt": "2024-06-01T02:00:00Z" }),
+        );
+        assert_eq!(workflows.scheduled_delay(&init_cmd, now), Ok(None));
+
+        let scheduled_cmd = init_cmd.update(GenericStateUpdate::scheduled());
+        assert_eq!(
+            workflows.scheduled_delay(&scheduled_cmd, now),
+            Ok(Some(Duration::from_secs(3600)))
+        );
+
+        let later = time::macros::datetime!(2024-06-01 02:00:01 UTC);
+        assert_eq!(workflows.scheduled_delay(&scheduled_cmd, later), Ok(None));
+    }
+
+    #[test]
+    fn ignore_the_echo_of_a_command_waiting_to_be_retried() {
+        let mut workflows = WorkflowSupervisor::default();
+        let operation = OperationType::Custom("flaky".to_string());
+        workflows
+            .register_builtin_workflow(operation.clone())
+            .unwrap();
+
+        let init_cmd = GenericCommandState::from_command_message(&MqttMessage::new(
+            &Topic::new_unchecked("te/device/foo///cmd/flaky/id_1"),
+            r#"{ "@version": "builtin", "status":"init" }"#,
+        ))
+        .unwrap();
+        let init_cmd = workflows
+            .apply_external_update(&operation, init_cmd)
+            .unwrap()
+            .unwrap();
+
+        // The command is moved back to init to be retried after a delay
+        let retried_cmd = init_cmd.with_retry_attempts("init", 1);
+        workflows
+            .apply_internal_update(retried_cmd.clone())
+            .unwrap();
+        workflows.mark_pending_retry(retried_cmd.command_topic());
+
+        // The retried state is published over MQTT, and must not be processed on reception
+        assert!(matches!(
+            workflows.apply_external_update(&operation, retried_cmd.clone()),
+            Ok(None)
+        ));
+
+        // As soon as the command makes progress, init messages are no more ignored
+        let executing_cmd = retried_cmd.update(GenericStateUpdate::executing());
+        workflows.apply_internal_update(executing_cmd).unwrap();
+        assert!(!workflows
+            .pending_commands()
+            .is_retry_pending("te/device/foo///cmd/flaky/id_1"));
+    }
 }
@@ -2,6 +2,8 @@ use crate::mqtt_topics::OperationType;
 use crate::script::ShellScript;
 use crate::substitution::Record;
 use crate::workflow::AwaitHandlers;
+use crate::workflow::Backoff;
+use crate::workflow::Condition;
 use crate::workflow::DefaultHandlers;
 use crate::workflow::ExecHandlers;
 use crate::workflow::ExitHandlers;
@@ -10,7 +12,10 @@ use crate::workflow::GenericStateUpdate;
 use crate::workflow::IterateHandlers;
 use crate::workflow::OperationAction;
 use crate::workflow::OperationWorkflow;
+use crate::workflow::ParallelSpec;
+use crate::workflow::RetryPolicy;
 use crate::workflow::ScriptDefinitionError;
+use crate::workflow::SwitchHandlers;
 use crate::workflow::WorkflowDefinitionError;
 use serde::de::Error;
 use serde::Deserialize;
@@ -65,6 +70,88 @@ pub struct TomlOperationState {
     /// Values to be extracted from the sub-operation final state
     #[serde(default)]
     pub output: Option<Value>,
+
+    /// How to retry the action when it fails
+    #[serde(default)]
+    pub retry: Option<TomlRetryPolicy>,
+
+    /// How to launch sub-operations in parallel when iterating over an array
+    #[serde(default)]
+    pub parallel: Option<ParallelSpec>,
+}
+
+/// User-friendly representation of a [RetryPolicy]
+///
+/// ```toml
+/// retry = { max_attempts = 3, backoff = 10, on_exhausted = "failed" }
+/// retry = { max_attempts = 5, backoff = { initial_second = 5, factor = 2, max_second = 60 } }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct TomlRetryPolicy {
+    pub max_attempts: u32,
+
+    #[serde(default)]
+    pub backoff: TomlBackoff,
+
+    #[serde(default)]
+    pub on_exhausted: Option<TomlStateUpdate>,
+}
+
+/// User-friendly representation of a [Backoff]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum TomlBackoff {
+    /// A fixed delay in seconds between two attempts, eg.
+    /// `backoff = 10`
+    Fixed(u64),
+    /// An exponential delay, eg.
+    /// `backoff = { initial_second = 5, factor = 2, max_second = 60 }`
+    Exponential {
+        initial_second: u64,
+        #[serde(default)]
+        factor: Option<u32>,
+        #[serde(default)]
+        max_second: Option<u64>,
+    },
+}
+
+impl Default for TomlBackoff {
+    fn default() -> Self {
+        TomlBackoff::Fixed(0)
+    }
+}
+
+impl From<TomlBackoff> for Backoff {
+    fn from(value: TomlBackoff) -> Self {
+        match value {
+            TomlBackoff::Fixed(delay) => Backoff::fixed(Duration::from_secs(delay)),
+            TomlBackoff::Exponential {
+                initial_second,
+                factor,
+                max_second,
+            } => Backoff {
+                initial: Duration::from_secs(initial_second),
+                factor: factor.unwrap_or(2),
+                max: max_second.map(Duration::from_secs),
+            },
+        }
+    }
+}
+
+impl TryFrom<TomlRetryPolicy> for RetryPolicy {
+    type Error = ScriptDefinitionError;
+
+    fn try_from(value: TomlRetryPolicy) -> Result<Self, Self::Error> {
+        if value.max_attempts == 0 {
+            return Err(ScriptDefinitionError::InvalidMaxAttempts);
+        }
+
+        Ok(RetryPolicy {
+            max_attempts: value.max_attempts,
+            backoff: value.backoff.into(),
+            on_exhausted: value.on_exhausted.map(|u| u.into()),
+        })
+    }
 }
 
 /// User-friendly representation of an [OperationAction]
@@ -123,9 +210,23 @@ impl TryFrom<(TomlOperationState, DefaultHandlers)> for OperationAction {
     fn try_from(
         (input, defaults): (TomlOperationState, DefaultHandlers),
     ) -> Result<Self, Self::Error> {
+        let retry = input.retry.map(RetryPolicy::try_from).transpose()?;
+        let retry_supported = match &input.action {
+            TomlOperationAction::Script(_) => true,
+            TomlOperationAction::Operation(operation) => !operation.starts_with("builtin:"),
+            _ => false,
+        };
+        if retry.is_some() && !retry_supported {
+            return Err(WorkflowDefinitionError::UnsupportedRetryPolicy);
+        }
+        if input.parallel.is_some() && !matches!(input.action, TomlOperationAction::Iterate(_)) {
+            return Err(WorkflowDefinitionError::UnsupportedParallelSpec);
+        }
+
         match input.action {
             TomlOperationAction::Script(script) => {
-                let handlers = ExitHandlers::try_from((input.handlers, defaults))?;
+                let handlers =
+                    ExitHandlers::try_from((input.handlers, defaults))?.with_retry(retry);
                 Ok(OperationAction::Script(script, handlers))
             }
             TomlOperationAction::BackgroundScript(script) => {
@@ -134,7 +235,8 @@ impl TryFrom<(TomlOperationState, DefaultHandlers)> for OperationAction {
             }
             TomlOperationAction::Operation(operation) => match operation.strip_prefix("builtin:") {
                 None => {
-                    let handlers = ExecHandlers::try_from((input.handlers, defaults))?;
+                    let handlers =
+                        ExecHandlers::try_from((input.handlers, defaults))?.with_retry(retry);
                     let input_script = input.input_script;
                     let cmd_input = input.input.try_into()?;
                     Ok(OperationAction::Operation(
@@ -153,13 +255,30 @@ impl TryFrom<(TomlOperationState, DefaultHandlers)> for OperationAction {
                 }
             },
             TomlOperationAction::Iterate(target_json_path) => {
-                let handlers = IterateHandlers::try_from((input.handlers, defaults))?;
                 let Some(json_path) = GenericCommandState::extract_path(&target_json_path) else {
                     return Err(WorkflowDefinitionError::InvalidPathExpression(
                         target_json_path,
                     ));
                 };
-                Ok(OperationAction::Iterate(json_path.to_string(), handlers))
+                match input.parallel {
+                    None => {
+                        let handlers = IterateHandlers::try_from((input.handlers, defaults))?;
+                        Ok(OperationAction::Iterate(json_path.to_string(), handlers))
+                    }
+                    Some(spec) => {
+                        if spec.max_concurrency == 0 {
+                            return Err(WorkflowDefinitionError::InvalidMaxConcurrency);
+                        }
+                        let handlers = ExecHandlers::try_from((input.handlers, defaults))?;
+                        let cmd_input = input.input.try_into()?;
+                        Ok(OperationAction::ParallelIterate(
+                            json_path.to_string(),
+                            spec,
+                            cmd_input,
+                            handlers,
+                        ))
+                    }
+                }
             }
             TomlOperationAction::Action(command) => match command.as_str() {
                 "cleanup" => Ok(OperationAction::Clear),
@@ -182,6 +301,10 @@ impl TryFrom<(TomlOperationState, DefaultHandlers)> for OperationAction {
                         handlers, cmd_output,
                     ))
                 }
+                "switch" => {
+                    let handlers = SwitchHandlers::try_from(input.handlers)?;
+                    Ok(OperationAction::Switch(handlers))
+                }
                 "builtin" => {
                     let exec_handlers = ExecHandlers::try_from((
                         input.handlers.clone(),
@@ -257,6 +380,21 @@ pub struct TomlExitHandlers {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     on_next: Option<TomlStateUpdate>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    on_case: Vec<TomlSwitchCase>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_default: Option<TomlStateUpdate>,
+}
+
+/// User-friendly representation of a case of a [SwitchHandlers]
+///
+/// `{ when = '${.payload.version} == "2.0"', next = "install_v2" }`
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+pub struct TomlSwitchCase {
+    when: String,
+    next: TomlStateUpdate,
 }
 
 impl TryFrom<TomlExitHandlers> for ExitHandlers {
@@ -421,6 +559,36 @@ impl TryFrom<(TomlExitHandlers, DefaultHandlers)> for IterateHandlers {
     }
 }
 
+impl TryFrom<TomlExitHandlers> for SwitchHandlers {
+    type Error = WorkflowDefinitionError;
+
+    fn try_from(value: TomlExitHandlers) -> Result<Self, Self::Error> {
+        if value.on_case.is_empty() {
+            return Err(WorkflowDefinitionError::MissingSwitchCase);
+        }
+        let on_case = value
+            .on_case
+            .into_iter()
+            .map(|case| {
+                let condition = case.when.parse::<Condition>().map_err(|error| {
+                    WorkflowDefinitionError::InvalidCondition {
+                        condition: case.when,
+                        error,
+                    }
+                })?;
+                Ok((condition, case.next.into()))
+            })
+            .collect::<Result<_, WorkflowDefinitionError>>()?;
+        let on_default = value.on_default.map(|u| u.into()).unwrap_or_else(|| {
+            GenericStateUpdate::failed("None of the switch conditions holds".to_string())
+        });
+        Ok(SwitchHandlers {
+            on_case,
+            on_default,
+        })
+    }
+}
+
 impl TryFrom<TomlExitHandlers> for DefaultHandlers {
     type Error = ScriptDefinitionError;
 
@@ -500,8 +668,11 @@ impl FromStr for ExitCodes {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::workflow::ConditionError;
     use crate::workflow::GenericStateUpdate;
+    use crate::workflow::StateExcerpt;
     use assert_matches::assert_matches;
+    use serde_json::json;
     use ExitCodes::*;
 
     #[test]
@@ -546,6 +717,8 @@ on_kill = { status = "failed", reason = "killed"}         # next status when kil
                 on_stdout: Vec::new(),
                 on_exec: None,
                 on_next: None,
+                on_case: Vec::new(),
+                on_default: None,
             }
         )
     }
@@ -823,4 +996,251 @@ on_error = "failed"
         let res = OperationWorkflow::try_from(input);
         assert_matches!(res, Err(WorkflowDefinitionError::InvalidPathExpression(_)));
     }
+
+    #[test]
+    fn parse_retry_policy() {
+        let file = r#"
+operation = "custom_operation"
+
+[init]
+script = "/some/flaky/download.sh"
+on_success = "install"
+retry = { max_attempts = 3, backoff = { initial_second = 5, max_second = 12 }, on_exhausted = "gave_up" }
+
+[install]
+operation = "software_update"
+on_exec = "awaiting_install"
+retry = { max_attempts = 2, backoff = 30 }
+
+[awaiting_install]
+action = "await-operation-completion"
+on_success = "successful"
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let workflow = OperationWorkflow::try_from(input).unwrap();
+
+        let Some(OperationAction::Script(_, handlers)) = workflow.states.get("init") else {
+            panic!("Expected a script action")
+        };
+        let retry = handlers.retry().unwrap();
+        assert_eq!(retry.max_attempts, 3);
+        assert_eq!(retry.on_exhausted, Some("gave_up".into()));
+        assert_eq!(retry.next_attempt(1), Some(Duration::from_secs(5)));
+        assert_eq!(retry.next_attempt(2), Some(Duration::from_secs(10)));
+        assert_eq!(retry.next_attempt(3), None);
+
+        let (state, retry) = workflow
+            .sub_operation_retry_policy("awaiting_install")
+            .unwrap();
+        assert_eq!(state, "install");
+        assert_eq!(retry.max_attempts, 2);
+        assert_eq!(retry.backoff, Backoff::fixed(Duration::from_secs(30)));
+        assert_eq!(retry.on_exhausted, None);
+    }
+
+    #[test]
+    fn reject_retry_policy_on_a_proceed_action() {
+        let file = r#"
+operation = "custom_operation"
+
+[init]
+action = "proceed"
+on_success = "successful"
+retry = { max_attempts = 3 }
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let error = OperationWorkflow::try_from(input).unwrap_err();
+        assert_eq!(error, WorkflowDefinitionError::UnsupportedRetryPolicy);
+    }
+
+    #[test]
+    fn reject_retry_policy_with_no_attempts() {
+        let file = r#"
+operation = "custom_operation"
+
+[init]
+script = "/some/script.sh"
+retry = { max_attempts = 0 }
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let error = OperationWorkflow::try_from(input).unwrap_err();
+        assert_eq!(
+            error,
+            WorkflowDefinitionError::ScriptDefinitionError(
+                ScriptDefinitionError::InvalidMaxAttempts
+            )
+        );
+    }
+
+    #[test]
+    fn parse_switch_toml() {
+        let file = r#"
+operation = "custom_operation"
+
+[init]
+action = "switch"
+on_case = [
+    { when = '${.payload.version} == "2.0"', next = "install_v2" },
+    { when = "${.payload.rollback}", next = { status = "failed", reason = "rollback requested" } },
+]
+on_default = "install_v1"
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let workflow = OperationWorkflow::try_from(input).unwrap();
+
+        let Some(OperationAction::Switch(handlers)) = workflow.states.get("init") else {
+            panic!("Expected a switch action")
+        };
+        let state = |payload: Value| {
+            GenericCommandState::new(
+                "te/device/main///cmd/custom_operation/123"
+                    .try_into()
+                    .unwrap(),
+                "init".to_string(),
+                payload,
+            )
+        };
+        assert_eq!(
+            handlers.state_update(&state(serde_json::json!({"version": "2.0"}))),
+            "install_v2".into()
+        );
+        assert_eq!(
+            handlers.state_update(&state(
+                serde_json::json!({"version": "1.0", "rollback": true})
+            )),
+            GenericStateUpdate::failed("rollback requested".to_string())
+        );
+        assert_eq!(
+            handlers.state_update(&state(serde_json::json!({"version": "1.0"}))),
+            "install_v1".into()
+        );
+    }
+
+    #[test]
+    fn switch_parse_fails_without_cases() {
+        let file = r#"
+operation = "custom_operation"
+
+[init]
+action = "switch"
+on_default = "successful"
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let res = OperationWorkflow::try_from(input);
+        assert_matches!(res, Err(WorkflowDefinitionError::MissingSwitchCase));
+    }
+
+    #[test]
+    fn switch_parse_fails_with_invalid_condition() {
+        let file = r#"
+operation = "custom_operation"
+
+[init]
+action = "switch"
+on_case = [ { when = "${.payload.version} = 2", next = "successful" } ]
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let res = OperationWorkflow::try_from(input);
+        assert_matches!(
+            res,
+            Err(WorkflowDefinitionError::InvalidCondition { condition, error: ConditionError::UnknownOperator(_) })
+            if condition == "${.payload.version} = 2"
+        );
+    }
+
+    #[test]
+    fn parse_parallel_iterate_toml() {
+        let file = r#"
+operation = "rollout"
+
+[init]
+iterate = "${.payload.devices}"
+parallel = { operation = "firmware_update", max_concurrency = 5, fail_fast = true }
+input.url = "${.payload.@next.item.url}"
+on_exec = "awaiting_rollout"
+
+[awaiting_rollout]
+action = "await-operation-completion"
+on_success = "successful"
+on_error = "failed"
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let workflow = OperationWorkflow::try_from(input).unwrap();
+
+        match workflow.states.get("init").unwrap() {
+            OperationAction::ParallelIterate(target, spec, input, handlers) => {
+                assert_eq!(target, ".payload.devices");
+                assert_eq!(
+                    spec,
+                    &ParallelSpec {
+                        operation: "firmware_update".to_string(),
+                        target: None,
+                        max_concurrency: 5,
+                        fail_fast: true,
+                    }
+                );
+                assert_eq!(
+                    input,
+                    &StateExcerpt::from(json!({"url": "${.payload.@next.item.url}"}))
+                );
+                assert_eq!(handlers.on_exec, "awaiting_rollout".into());
+            }
+            other => panic!("Expected parallel iterate action, but got {other}"),
+        }
+    }
+
+    #[test]
+    fn parse_parallel_iterate_toml_with_target() {
+        let file = r#"
+operation = "rollout"
+
+[init]
+iterate = "${.payload.devices}"
+parallel = { operation = "firmware_update", target = "${.payload.@next.item.topic_id}" }
+on_exec = "awaiting_rollout"
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let workflow = OperationWorkflow::try_from(input).unwrap();
+
+        match workflow.states.get("init").unwrap() {
+            OperationAction::ParallelIterate(_, spec, _, _) => {
+                assert_eq!(
+                    spec.target.as_deref(),
+                    Some("${.payload.@next.item.topic_id}")
+                );
+                assert_eq!(spec.max_concurrency, 10);
+                assert!(!spec.fail_fast);
+            }
+            other => panic!("Expected parallel iterate action, but got {other}"),
+        }
+    }
+
+    #[test]
+    fn reject_parallel_spec_on_a_script() {
+        let file = r#"
+operation = "rollout"
+
+[init]
+script = "/some/script.sh"
+parallel = { operation = "firmware_update" }
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let error = OperationWorkflow::try_from(input).unwrap_err();
+        assert_eq!(error, WorkflowDefinitionError::UnsupportedParallelSpec);
+    }
+
+    #[test]
+    fn reject_parallel_spec_with_no_concurrency() {
+        let file = r#"
+operation = "rollout"
+
+[init]
+iterate = "${.payload.devices}"
+parallel = { operation = "firmware_update", max_concurrency = 0 }
+on_exec = "awaiting_rollout"
+"#;
+        let input: TomlOperationWorkflow = toml::from_str(file).unwrap();
+        let error = OperationWorkflow::try_from(input).unwrap_err();
+        assert_eq!(error, WorkflowDefinitionError::InvalidMaxConcurrency);
+    }
 }
@@ -1,4 +1,5 @@
 use crate::core::component::TEdgeComponent;
+use crate::core::mapper::aggregation_config;
 use crate::core::mapper::start_basic_actors;
 use crate::core::mqtt::configure_proxy;
 use anyhow::Context;
@@ -11,6 +12,7 @@ use tedge_actors::ConvertingActor;
 use tedge_actors::MessageSink;
 use tedge_actors::MessageSource;
 use tedge_actors::NoConfig;
+use tedge_aggregation_ext::AggregationBuilder;
 use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::service_health_topic;
@@ -33,13 +35,17 @@ impl TEdgeComponent for AwsMapper {
     async fn start(
         &self,
         tedge_config: TEdgeConfig,
-        _config_dir: &tedge_config::Path,
+        config_dir: &tedge_config::Path,
     ) -> Result<(), anyhow::Error> {
         let aws_config = tedge_config.aws.try_get(self.profile.as_deref())?;
         let prefix = &aws_config.bridge.topic_prefix;
         let aws_mapper_name = format!("tedge-mapper-{prefix}");
-        let (mut runtime, mut mqtt_actor) =
-            start_basic_actors(&aws_mapper_name, &tedge_config).await?;
+        let (mut runtime, mut mqtt_actor) = start_basic_actors(
+            &aws_mapper_name,
+            &tedge_config,
+            Some(aws_config.metrics.bind.port),
+        )
+        .await?;
 
         let mqtt_schema = MqttSchema::with_root(tedge_config.mqtt.topic_root.clone());
         if tedge_config.mqtt.bridge.built_in {
@@ -78,6 +84,13 @@ impl TEdgeComponent for AwsMapper {
         } else if tedge_config.proxy.address.or_none().is_some() {
             warn!("`proxy.address` is configured without the built-in bridge enabled. The bridge MQTT connection to the cloud will {} communicate via the configured proxy.", "not".bold())
         }
+        let mut topics = get_topic_filter(aws_config);
+        topics.add_all(AwsConverter::cloud_topics(prefix, &mqtt_schema));
+        let mut mqtt = AggregationBuilder::new(
+            aggregation_config(config_dir)?,
+            mqtt_schema.clone(),
+            &mut mqtt_actor,
+        );
         let clock = Box::new(WallClock);
         let aws_converter = AwsConverter::new(
             aws_config.mapper.timestamp,
@@ -89,8 +102,11 @@ impl TEdgeComponent for AwsMapper {
         );
         let mut aws_converting_actor = ConvertingActor::builder("AwsConverter", aws_converter);
 
-        aws_converting_actor.connect_source(get_topic_filter(aws_config), &mut mqtt_actor);
-        aws_converting_actor.connect_sink(NoConfig, &mqtt_actor);
+        aws_converting_actor.connect_source(topics, &mut mqtt);
+        aws_converting_actor.connect_sink(NoConfig, &mqtt);
+        for batcher in mqtt.into_batchers() {
+            runtime.spawn(batcher).await?;
+        }
 
         runtime.spawn(aws_converting_actor).await?;
         runtime.spawn(mqtt_actor).await?;
@@ -126,6 +142,9 @@ fn built_in_bridge_rules(
     // topic to interact with the shadow of the device
     bridge.forward_bidirectionally("shadow/#", local_prefix.clone(), things_prefix.clone())?;
 
+    // topics to get job executions and to report their status
+    bridge.forward_bidirectionally("jobs/#", local_prefix.clone(), things_prefix.clone())?;
+
     // echo topic mapping to check the connection
     bridge.forward_from_local(
         "",
@@ -1,4 +1,5 @@
 use crate::core::component::TEdgeComponent;
+use crate::core::mapper::aggregation_config;
 use crate::core::mapper::start_basic_actors;
 use crate::core::mqtt::configure_proxy;
 use anyhow::Context;
@@ -12,6 +13,7 @@ use tedge_actors::ConvertingActor;
 use tedge_actors::MessageSink;
 use tedge_actors::MessageSource;
 use tedge_actors::NoConfig;
+use tedge_aggregation_ext::AggregationBuilder;
 use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::service_health_topic;
@@ -34,13 +36,17 @@ impl TEdgeComponent for AzureMapper {
     async fn start(
         &self,
         tedge_config: TEdgeConfig,
-        _config_dir: &tedge_config::Path,
+        config_dir: &tedge_config::Path,
     ) -> Result<(), anyhow::Error> {
         let az_config = tedge_config.az.try_get(self.profile.as_deref())?;
         let prefix = &az_config.bridge.topic_prefix;
         let az_mapper_name = format!("tedge-mapper-{prefix}");
-        let (mut runtime, mut mqtt_actor) =
-            start_basic_actors(&az_mapper_name, &tedge_config).await?;
+        let (mut runtime, mut mqtt_actor) = start_basic_actors(
+            &az_mapper_name,
+            &tedge_config,
+            Some(az_config.metrics.bind.port),
+        )
+        .await?;
         let mqtt_schema = MqttSchema::with_root(tedge_config.mqtt.topic_root.clone());
 
         if tedge_config.mqtt.bridge.built_in {
@@ -88,6 +94,13 @@ impl TEdgeComponent for AzureMapper {
             warn!("`proxy.address` is configured without the built-in bridge enabled. The bridge MQTT connection to the cloud will {} communicate via the configured proxy.", "not".bold())
         }
         let mqtt_schema = MqttSchema::with_root(tedge_config.mqtt.topic_root.clone());
+        let mut topics = get_topic_filter(az_config);
+        topics.add_all(AzureConverter::cloud_topics(prefix, &mqtt_schema));
+        let mut mqtt = AggregationBuilder::new(
+            aggregation_config(config_dir)?,
+            mqtt_schema.clone(),
+            &mut mqtt_actor,
+        );
         let az_converter = AzureConverter::new(
             az_config.mapper.timestamp,
             Box::new(WallClock),
@@ -97,8 +110,11 @@ impl TEdgeComponent for AzureMapper {
             az_config.mapper.mqtt.max_payload_size.0,
         );
         let mut az_converting_actor = ConvertingActor::builder("AzConverter", az_converter);
-        az_converting_actor.connect_source(get_topic_filter(az_config), &mut mqtt_actor);
-        az_converting_actor.connect_sink(NoConfig, &mqtt_actor);
+        az_converting_actor.connect_source(topics, &mut mqtt);
+        az_converting_actor.connect_sink(NoConfig, &mqtt);
+        for batcher in mqtt.into_batchers() {
+            runtime.spawn(batcher).await?;
+        }
 
         runtime.spawn(az_converting_actor).await?;
         runtime.spawn(mqtt_actor).await?;
@@ -141,7 +157,16 @@ fn built_in_bridge_rules(
 
     // Digital twin
     bridge.forward_from_local("twin/GET/#", local_prefix.clone(), iothub_prefix)?;
-    bridge.forward_from_local("twin/PATCH/#", local_prefix.clone(), iothub_prefix)?;
+    bridge.forward_from_local(
+        "twin/PATCH/properties/reported/#",
+        local_prefix.clone(),
+        iothub_prefix,
+    )?;
+    bridge.forward_from_remote(
+        "twin/PATCH/properties/desired/#",
+        local_prefix.clone(),
+        iothub_prefix,
+    )?;
     bridge.forward_from_remote("twin/res/#", local_prefix.clone(), iothub_prefix)?;
 
     Ok(bridge)
@@ -1,4 +1,5 @@
 use crate::core::component::TEdgeComponent;
+use crate::core::mapper::aggregation_config;
 use crate::core::mapper::start_basic_actors;
 use crate::core::mqtt::configure_proxy;
 use anyhow::Context;
@@ -13,8 +14,10 @@ use c8y_mapper_ext::config::C8yMapperConfig;
 use c8y_mapper_ext::converter::CumulocityConverter;
 use mqtt_channel::Config;
 use std::borrow::Cow;
+use tedge_aggregation_ext::AggregationBuilder;
 use tedge_api::entity::EntityExternalId;
 use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
 use tedge_config::tedge_toml::ProfileName;
 use tedge_config::TEdgeConfig;
 use tedge_downloader_ext::DownloaderActor;
@@ -47,8 +50,12 @@ impl TEdgeComponent for CumulocityMapper {
         let c8y_config = tedge_config.c8y.try_get(c8y_profile)?;
         let prefix = &c8y_config.bridge.topic_prefix;
         let c8y_mapper_name = format!("tedge-mapper-{prefix}");
-        let (mut runtime, mut mqtt_actor) =
-            start_basic_actors(&c8y_mapper_name, &tedge_config).await?;
+        let (mut runtime, mut mqtt_actor) = start_basic_actors(
+            &c8y_mapper_name,
+            &tedge_config,
+            Some(c8y_config.metrics.bind.port),
+        )
+        .await?;
 
         let c8y_mapper_config =
             C8yMapperConfig::from_tedge_config(cfg_dir, &tedge_config, c8y_profile)?;
@@ -266,9 +273,14 @@ impl TEdgeComponent for CumulocityMapper {
         )?);
 
         C8yMapperBuilder::init(&c8y_mapper_config).await?;
+        let mut mqtt = AggregationBuilder::new(
+            aggregation_config(cfg_dir)?,
+            MqttSchema::with_root(tedge_config.mqtt.topic_root.clone()),
+            &mut mqtt_actor,
+        );
         let mut c8y_mapper_actor = C8yMapperBuilder::try_new(
             c8y_mapper_config,
-            &mut mqtt_actor,
+            &mut mqtt,
             &mut http_actor,
             &mut timer_actor,
             &mut uploader_actor,
@@ -276,6 +288,9 @@ impl TEdgeComponent for CumulocityMapper {
             &mut fs_watch_actor,
             &mut service_monitor_actor,
         )?;
+        for batcher in mqtt.into_batchers() {
+            runtime.spawn(batcher).await?;
+        }
 
         let c8y_prefix = &c8y_config.bridge.topic_prefix;
         // Adaptor translating commands sent on te/device/main///cmd/+/+ into requests on tedge/commands/req/+/+
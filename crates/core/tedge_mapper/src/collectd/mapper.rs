@@ -34,7 +34,7 @@ impl TEdgeComponent for CollectdMapper {
         _config_dir: &tedge_config::Path,
     ) -> Result<(), anyhow::Error> {
         let (mut runtime, mut mqtt_actor) =
-            start_basic_actors(COLLECTD_MAPPER_NAME, &tedge_config).await?;
+            start_basic_actors(COLLECTD_MAPPER_NAME, &tedge_config, None).await?;
 
         let input_topic = CollectdMapper::input_topics();
         let output_topic = CollectdMapper::output_topic();
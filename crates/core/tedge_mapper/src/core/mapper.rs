@@ -1,19 +1,31 @@
+use std::net::IpAddr;
+use std::net::SocketAddr;
 #[cfg(test)]
 use std::result::Result::Ok;
 use tedge_actors::Runtime;
+use tedge_aggregation_ext::AggregationConfig;
 use tedge_api::mqtt_topics::DeviceTopicId;
 use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::mqtt_topics::Service;
 use tedge_api::mqtt_topics::ServiceTopicId;
+use tedge_config::Path;
 use tedge_config::TEdgeConfig;
 use tedge_health_ext::HealthMonitorBuilder;
 use tedge_mqtt_ext::MqttActorBuilder;
 use tedge_signal_ext::SignalActor;
+use tracing::error;
+use tracing::info;
 
+const AGGREGATION_CONFIG_FILE: &str = "aggregation.toml";
+
+/// Start the actors shared by all the mappers
+///
+/// If `metrics.enable` is set, the internal metrics of the mapper are served on the given port.
 pub async fn start_basic_actors(
     mapper_name: &str,
     config: &TEdgeConfig,
+    metrics_port: Option<u16>,
 ) -> Result<(Runtime, MqttActorBuilder), anyhow::Error> {
     let mut runtime = Runtime::new();
 
@@ -37,11 +49,27 @@ pub async fn start_basic_actors(
     // Shutdown on SIGINT
     let signal_actor = SignalActor::builder(&runtime.get_handle());
 
+    if let Some(port) = metrics_port.filter(|_| config.metrics.enable) {
+        start_metrics_server(mapper_name, mqtt_schema, config.metrics.bind.address, port);
+    }
+
     runtime.spawn(signal_actor).await?;
     runtime.spawn(health_actor).await?;
     Ok((runtime, mqtt_actor))
 }
 
+fn start_metrics_server(mapper_name: &str, mqtt_schema: MqttSchema, address: IpAddr, port: u16) {
+    tedge_metrics::enable(mqtt_schema);
+    let addr = SocketAddr::new(address, port);
+    let mapper_name = mapper_name.to_string();
+    info!("{mapper_name} serves its metrics on http://{addr}/metrics");
+    tokio::spawn(async move {
+        if let Err(err) = tedge_metrics::serve_metrics(addr).await {
+            error!("{mapper_name} failed to serve its metrics on {addr}: {err}");
+        }
+    });
+}
+
 async fn get_mqtt_actor(
     session_name: &str,
     tedge_config: &TEdgeConfig,
@@ -52,3 +80,10 @@ async fn get_mqtt_actor(
         mqtt_config.with_session_name(session_name),
     ))
 }
+
+/// Read the measurement aggregation rules, shared by all the cloud mappers
+pub fn aggregation_config(config_dir: &Path) -> Result<AggregationConfig, anyhow::Error> {
+    Ok(AggregationConfig::from_file(
+        config_dir.join(AGGREGATION_CONFIG_FILE),
+    )?)
+}
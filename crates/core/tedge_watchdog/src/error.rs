@@ -1,6 +1,7 @@
 use mqtt_channel::MqttError;
 use tedge_config::CertificateError;
 use tedge_config::ConfigSettingError;
+use tedge_config::SystemTomlError;
 use tedge_config::TEdgeConfigError;
 use time::error::Parse;
 
@@ -12,6 +13,19 @@ pub enum WatchdogError {
     #[error("Fail to run `{cmd}`: {from}")]
     CommandExecError { cmd: String, from: std::io::Error },
 
+    #[error("Invalid restart command: {reason}")]
+    InvalidRestartCommand { reason: String },
+
+    #[error("`{cmd}` failed with {status}: {stderr}")]
+    RestartFailed {
+        cmd: String,
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+
+    #[error(transparent)]
+    FromSystemTomlError(#[from] SystemTomlError),
+
     #[error(transparent)]
     FromTedgeConfigError(#[from] TEdgeConfigError),
 
@@ -1,13 +1,17 @@
 use tedge_config::cli::CommonArgs;
 use tedge_config::log_init;
+use tedge_config::models::WatchdogMode;
+
+mod error;
+mod health;
+// without systemd, the services are restarted by the watchdog itself
+mod native_watchdog;
 
 // on linux, we use systemd
 #[cfg(target_os = "linux")]
 mod systemd_watchdog;
 #[cfg(target_os = "linux")]
 use systemd_watchdog as watchdog;
-#[cfg(target_os = "linux")]
-mod error;
 
 // on non-linux, we do nothing for now
 #[cfg(not(target_os = "linux"))]
@@ -34,5 +38,8 @@ pub async fn run(watchdog_opt: WatchdogOpt) -> Result<(), anyhow::Error> {
     )?;
 
     let tedge_config = tedge_config::TEdgeConfig::load(&watchdog_opt.common.config_dir).await?;
-    watchdog::start_watchdog(tedge_config).await
+    match tedge_config.watchdog.mode {
+        WatchdogMode::Systemd => watchdog::start_watchdog(tedge_config).await,
+        WatchdogMode::Native => native_watchdog::start_watchdog(tedge_config).await,
+    }
 }
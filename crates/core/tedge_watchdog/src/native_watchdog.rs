@@ -0,0 +1,508 @@
+//! Supervision of the thin-edge services by tedge-watchdog itself, on devices without systemd.
+//!
+//! Each service listed by `watchdog.services` is periodically sent a health check request.
+//! A service that doesn't respond in time is restarted, using either `watchdog.restart.command`
+//! or the `init.restart` command of `system.toml`, i.e. the command used by `tedge` to restart services.
+//! Only the services that have been seen up are restarted, so services that are not installed are ignored.
+//! An alarm is raised when a service has been restarted more than `watchdog.restart.threshold` times in a row,
+//! and cleared as soon as the service is healthy again.
+use anyhow::Context;
+use futures::channel::mpsc;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use mqtt_channel::MqttMessage;
+use mqtt_channel::PubChannel;
+use mqtt_channel::Topic;
+use serde_json::json;
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+use tedge_api::health::ServiceHealthTopic;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_config::SudoCommandBuilder;
+use tedge_config::SystemConfig;
+use tedge_config::TEdgeConfig;
+use time::OffsetDateTime;
+use tracing::debug;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+use crate::error::WatchdogError;
+use crate::health::get_latest_health_status_message;
+use crate::health::HealthStatusExt;
+
+const SERVICE_NAME: &str = "tedge-watchdog";
+
+/// The type of the alarm raised when a service has been restarted too many times
+pub const ALARM_TYPE: &str = "watchdog_restart";
+
+pub async fn start_watchdog(tedge_config: TEdgeConfig) -> Result<(), anyhow::Error> {
+    let restarter = ServiceRestarter {
+        command: restart_command(&tedge_config)?,
+        sudo: SudoCommandBuilder::new(&tedge_config),
+    };
+
+    let mqtt_topic_root = tedge_config.mqtt.topic_root.clone();
+    let mqtt_schema = MqttSchema::with_root(mqtt_topic_root.clone());
+    let device_topic_id: EntityTopicId = tedge_config
+        .mqtt
+        .device_topic_id
+        .parse()
+        .context("Can't parse as device topic id")?;
+
+    let watchdog_topic_id = device_topic_id
+        .default_service_for_device(SERVICE_NAME)
+        .context("Services not in default scheme unsupported")?;
+    let watchdog_health_topic = ServiceHealthTopic::from_new_topic(
+        &watchdog_topic_id.into(),
+        &mqtt_schema,
+        tedge_config.service.timestamp_format,
+    );
+
+    let watchdog_tasks = FuturesUnordered::new();
+    for name in tedge_config.watchdog.services.0.iter() {
+        let service = device_topic_id
+            .default_service_for_device(name)
+            .context("Services not in default scheme unsupported")?;
+        let health_topic = mqtt_schema.topic_for(&service, &Channel::Health);
+
+        let supervisor = ServiceSupervisor {
+            name: name.clone(),
+            health_check_topic: mqtt_schema.topic_for(
+                &service,
+                &Channel::Command {
+                    operation: OperationType::Health,
+                    cmd_id: "check".to_string(),
+                },
+            ),
+            alarm_topic: mqtt_schema.topic_for(
+                &service,
+                &Channel::Alarm {
+                    alarm_type: ALARM_TYPE.to_string(),
+                },
+            ),
+            check_interval: tedge_config.watchdog.check_interval.duration(),
+            response_timeout: tedge_config.watchdog.response_timeout.duration(),
+            restarter: restarter.clone(),
+            grace_period: tedge_config.watchdog.restart.grace_period.duration(),
+            threshold: tedge_config.watchdog.restart.threshold,
+            restarts: 0,
+            pid: None,
+        };
+
+        let up_message = watchdog_health_topic.clone();
+        let mqtt_config = tedge_config
+            .mqtt_config()?
+            .with_session_name(format!(
+                "{SERVICE_NAME}#{mqtt_topic_root}/{device_topic_id}/{name}"
+            ))
+            .with_subscriptions(health_topic.into())
+            .with_initial_message(move || up_message.up_message())
+            .with_last_will_message(watchdog_health_topic.down_message());
+
+        watchdog_tasks.push(tokio::spawn(async move {
+            let client = mqtt_channel::Connection::new(&mqtt_config).await?;
+            let mut received = client.received;
+            let mut published = client.published;
+            supervisor.run(&mut received, &mut published).await
+        }));
+    }
+
+    if watchdog_tasks.is_empty() {
+        warn!("tedge native watchdog not started because no services to monitor");
+    }
+    for result in futures::future::join_all(watchdog_tasks).await {
+        if let Ok(Err(err)) = result {
+            error!("{err}");
+        }
+    }
+    Ok(())
+}
+
+/// Monitor the health of a service, restarting it when it doesn't respond to health checks
+struct ServiceSupervisor {
+    name: String,
+    health_check_topic: Topic,
+    alarm_topic: Topic,
+    check_interval: Duration,
+    response_timeout: Duration,
+    restarter: ServiceRestarter,
+    /// Delay given to a service to start after a restart, before checking its health again
+    grace_period: Duration,
+    /// Number of restarts after which an alarm is raised
+    threshold: u32,
+    /// Number of restarts since the service last responded to a health check
+    restarts: u32,
+    /// Process id of the service, as given by its last health status
+    ///
+    /// None as long as the service has never been seen up, in which case the service is not restarted.
+    pid: Option<u32>,
+}
+
+impl ServiceSupervisor {
+    async fn run(
+        mut self,
+        received: &mut mpsc::UnboundedReceiver<MqttMessage>,
+        publisher: &mut mpsc::UnboundedSender<MqttMessage>,
+    ) -> Result<(), WatchdogError> {
+        info!("Starting native watchdog for {} service", self.name);
+
+        loop {
+            let start = Instant::now();
+            let request_timestamp = OffsetDateTime::now_utc();
+            publisher
+                .publish(MqttMessage::new(&self.health_check_topic, ""))
+                .await?;
+
+            let mut pause = self.check_interval;
+            match tokio::time::timeout(
+                self.response_timeout,
+                get_latest_health_status_message(request_timestamp, received),
+            )
+            .await
+            {
+                Ok(health_status) => {
+                    self.update_pid(health_status?);
+                    self.reset_restarts(publisher).await?;
+                }
+                Err(_) if self.pid.is_none() => {
+                    debug!(
+                        "No health check response received from {}, which has never been seen up",
+                        self.name
+                    );
+                }
+                Err(_) => {
+                    warn!(
+                        "No health check response received from {} in time",
+                        self.name
+                    );
+                    self.restart(publisher).await?;
+                    pause = self.grace_period;
+                }
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed < pause {
+                tokio::time::sleep(pause - elapsed).await;
+            }
+        }
+    }
+
+    fn update_pid(&mut self, health_status: HealthStatusExt) {
+        let Some(pid) = health_status.pid else {
+            error!(
+                "Ignoring invalid health status message from {} without a `pid` field in it",
+                self.name
+            );
+            return;
+        };
+        if let Some(previous_pid) = self.pid.replace(pid) {
+            if previous_pid != pid {
+                info!(
+                    "{} has been restarted: pid {previous_pid} replaced by {pid}",
+                    self.name
+                );
+            }
+        }
+    }
+
+    async fn restart(
+        &mut self,
+        publisher: &mut mpsc::UnboundedSender<MqttMessage>,
+    ) -> Result<(), WatchdogError> {
+        info!("Restarting {}", self.name);
+        if let Err(err) = self.restarter.restart(&self.name).await {
+            error!("Failed to restart {}: {err}", self.name);
+        }
+        self.restarts += 1;
+
+        if self.restarts > self.threshold {
+            let text = format!(
+                "{} has been restarted {} times by tedge-watchdog, not responding to health checks",
+                self.name, self.restarts
+            );
+            let payload = json!({
+                "text": text,
+                "severity": "major",
+            });
+            let alarm = MqttMessage::new(&self.alarm_topic, payload.to_string()).with_retain();
+            publisher.publish(alarm).await?;
+        }
+        Ok(())
+    }
+
+    /// Reset the restart count of a service responding again, clearing the alarm if one has been raised
+    async fn reset_restarts(
+        &mut self,
+        publisher: &mut mpsc::UnboundedSender<MqttMessage>,
+    ) -> Result<(), WatchdogError> {
+        if self.restarts > 0 {
+            info!("{} is healthy after {} restart/s", self.name, self.restarts);
+        }
+        if self.restarts > self.threshold {
+            let clear = MqttMessage::new(&self.alarm_topic, "").with_retain();
+            publisher.publish(clear).await?;
+        }
+        self.restarts = 0;
+        Ok(())
+    }
+}
+
+/// Restart services using a command, in which the `{}` placeholder is replaced by the service name
+#[derive(Clone, Debug)]
+struct ServiceRestarter {
+    command: Vec<String>,
+    sudo: SudoCommandBuilder,
+}
+
+impl ServiceRestarter {
+    fn command_line(&self, service: &str) -> Vec<String> {
+        self.command
+            .iter()
+            .map(|arg| {
+                if arg == "{}" {
+                    service.to_string()
+                } else {
+                    arg.clone()
+                }
+            })
+            .collect()
+    }
+
+    async fn restart(&self, service: &str) -> Result<(), WatchdogError> {
+        let command_line = self.command_line(service);
+        let cmd = command_line.join(" ");
+        let Some((program, args)) = command_line.split_first() else {
+            return Err(WatchdogError::InvalidRestartCommand {
+                reason: "the command is empty".to_string(),
+            });
+        };
+
+        let mut command = tokio::process::Command::from(self.sudo.command(program));
+        let output = command
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|from| WatchdogError::CommandExecError {
+                cmd: cmd.clone(),
+                from,
+            })?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(WatchdogError::RestartFailed {
+                cmd,
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            })
+        }
+    }
+}
+
+/// The configured restart command, defaulting to the `init.restart` command of `system.toml`
+fn restart_command(tedge_config: &TEdgeConfig) -> Result<Vec<String>, WatchdogError> {
+    let command = match tedge_config.watchdog.restart.command.or_none() {
+        Some(command_line) => shell_words::split(command_line).map_err(|err| {
+            WatchdogError::InvalidRestartCommand {
+                reason: err.to_string(),
+            }
+        })?,
+        None => SystemConfig::try_new(tedge_config.root_dir())?.init.restart,
+    };
+    check_restart_command(command)
+}
+
+fn check_restart_command(command: Vec<String>) -> Result<Vec<String>, WatchdogError> {
+    if command.is_empty() {
+        return Err(WatchdogError::InvalidRestartCommand {
+            reason: "the command is empty".to_string(),
+        });
+    }
+    if !command.iter().any(|arg| arg == "{}") {
+        return Err(WatchdogError::InvalidRestartCommand {
+            reason: "a placeholder '{}' is missing for the service name".to_string(),
+        });
+    }
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::SinkExt;
+    use tedge_utils::timestamp::TimeFormat;
+    use tempfile::TempDir;
+
+    #[test]
+    fn the_placeholder_is_replaced_by_the_service_name() {
+        let restarter = ServiceRestarter {
+            command: vec!["/sbin/rc-service".into(), "{}".into(), "restart".into()],
+            sudo: SudoCommandBuilder::enabled(false),
+        };
+
+        assert_eq!(
+            restarter.command_line("tedge-agent"),
+            vec!["/sbin/rc-service", "tedge-agent", "restart"]
+        );
+    }
+
+    #[test]
+    fn restart_commands_without_placeholder_are_rejected() {
+        assert!(check_restart_command(vec!["/sbin/reboot".into()]).is_err());
+        assert!(check_restart_command(vec![]).is_err());
+        assert!(
+            check_restart_command(vec!["/usr/bin/sv".into(), "restart".into(), "{}".into()])
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn unresponsive_services_are_restarted_and_an_alarm_raised_over_the_threshold() {
+        let tmp = TempDir::new().unwrap();
+        let supervisor = test_supervisor(&tmp, 2, Duration::from_millis(20), Some(1234));
+        let (_health_sender, mut received) = mpsc::unbounded::<MqttMessage>();
+        let (mut publisher, mut published) = mpsc::unbounded::<MqttMessage>();
+        tokio::spawn(async move { supervisor.run(&mut received, &mut publisher).await });
+
+        let alarm = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(message) = published.next().await {
+                if message.topic.name.ends_with("/a/watchdog_restart") {
+                    return message;
+                }
+            }
+            panic!("No alarm raised")
+        })
+        .await
+        .expect("An alarm should have been raised");
+
+        assert!(alarm.retain);
+        assert!(alarm
+            .payload_str()
+            .unwrap()
+            .contains("tedge-agent has been restarted 3 times"));
+        assert_eq!(restarts(&tmp), vec!["tedge-agent"; 3]);
+    }
+
+    #[tokio::test]
+    async fn responsive_services_are_not_restarted() {
+        let tmp = TempDir::new().unwrap();
+        let supervisor = test_supervisor(&tmp, 0, Duration::from_secs(5), None);
+        let (mut health_sender, mut received) = mpsc::unbounded::<MqttMessage>();
+        let (mut publisher, mut published) = mpsc::unbounded::<MqttMessage>();
+        tokio::spawn(async move { supervisor.run(&mut received, &mut publisher).await });
+
+        for _ in 0..5 {
+            let request = published.next().await.unwrap();
+            assert_eq!(
+                request.topic.name,
+                "te/device/main/service/tedge-agent/cmd/health/check"
+            );
+            health_sender.send(health_response()).await.unwrap();
+        }
+
+        assert!(restarts(&tmp).is_empty());
+    }
+
+    #[tokio::test]
+    async fn services_never_seen_up_are_not_restarted() {
+        let tmp = TempDir::new().unwrap();
+        let supervisor = test_supervisor(&tmp, 0, Duration::from_millis(20), None);
+        let (_health_sender, mut received) = mpsc::unbounded::<MqttMessage>();
+        let (mut publisher, mut published) = mpsc::unbounded::<MqttMessage>();
+        tokio::spawn(async move { supervisor.run(&mut received, &mut publisher).await });
+
+        for _ in 0..5 {
+            let request = published.next().await.unwrap();
+            assert_eq!(
+                request.topic.name,
+                "te/device/main/service/tedge-agent/cmd/health/check"
+            );
+        }
+
+        assert!(restarts(&tmp).is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_alarm_is_cleared_when_the_service_is_healthy_again() {
+        let tmp = TempDir::new().unwrap();
+        let supervisor = test_supervisor(&tmp, 0, Duration::from_millis(500), Some(1234));
+        let (mut health_sender, mut received) = mpsc::unbounded::<MqttMessage>();
+        let (mut publisher, mut published) = mpsc::unbounded::<MqttMessage>();
+        tokio::spawn(async move { supervisor.run(&mut received, &mut publisher).await });
+
+        // The first health check is not answered: the service is restarted and an alarm raised
+        let request = published.next().await.unwrap();
+        assert!(request.topic.name.ends_with("/cmd/health/check"));
+        let alarm = published.next().await.unwrap();
+        assert!(alarm.topic.name.ends_with("/a/watchdog_restart"));
+        assert!(!alarm.payload_bytes().is_empty());
+
+        // The service responds again after its restart: the alarm is cleared
+        let request = published.next().await.unwrap();
+        assert!(request.topic.name.ends_with("/cmd/health/check"));
+        health_sender.send(health_response()).await.unwrap();
+        let clear = published.next().await.unwrap();
+        assert!(clear.topic.name.ends_with("/a/watchdog_restart"));
+        assert!(clear.retain);
+        assert!(clear.payload_bytes().is_empty());
+
+        assert_eq!(restarts(&tmp), vec!["tedge-agent"]);
+    }
+
+    fn health_response() -> MqttMessage {
+        let health_topic =
+            Topic::new("te/device/main/service/tedge-agent/status/health").expect("Valid topic");
+        let response = json!({
+            "status": "up",
+            "pid": 1234u32,
+            "time": TimeFormat::Unix.to_json(OffsetDateTime::now_utc()).unwrap(),
+        });
+        MqttMessage::new(&health_topic, response.to_string())
+    }
+
+    fn test_supervisor(
+        tmp: &TempDir,
+        threshold: u32,
+        response_timeout: Duration,
+        pid: Option<u32>,
+    ) -> ServiceSupervisor {
+        let log = tmp.path().join("restarts.log");
+        ServiceSupervisor {
+            name: "tedge-agent".to_string(),
+            health_check_topic: Topic::new("te/device/main/service/tedge-agent/cmd/health/check")
+                .unwrap(),
+            alarm_topic: Topic::new("te/device/main/service/tedge-agent/a/watchdog_restart")
+                .unwrap(),
+            check_interval: Duration::from_millis(50),
+            response_timeout,
+            restarter: ServiceRestarter {
+                command: vec![
+                    "sh".into(),
+                    "-c".into(),
+                    format!("echo $1 >> {}", log.display()),
+                    "sh".into(),
+                    "{}".into(),
+                ],
+                sudo: SudoCommandBuilder::enabled(false),
+            },
+            grace_period: Duration::from_millis(10),
+            threshold,
+            restarts: 0,
+            pid,
+        }
+    }
+
+    fn restarts(tmp: &TempDir) -> Vec<String> {
+        std::fs::read_to_string(tmp.path().join("restarts.log"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
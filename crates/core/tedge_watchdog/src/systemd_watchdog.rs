@@ -1,15 +1,10 @@
 use anyhow::Context;
 use freedesktop_entry_parser::parse_entry;
-use futures::channel::mpsc;
 use futures::stream::FuturesUnordered;
 use futures::SinkExt;
-use futures::StreamExt;
 use mqtt_channel::MqttMessage;
 use mqtt_channel::PubChannel;
 use mqtt_channel::Topic;
-use serde::Deserialize;
-use serde::Serialize;
-use serde_json::Value as JsonValue;
 use std::process;
 use std::process::Command;
 use std::process::ExitStatus;
@@ -23,7 +18,6 @@ use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::mqtt_topics::OperationType;
 use tedge_config::TEdgeConfig;
-use tedge_utils::timestamp::IsoOrUnix;
 use time::OffsetDateTime;
 use tracing::debug;
 use tracing::error;
@@ -31,6 +25,7 @@ use tracing::info;
 use tracing::warn;
 
 use crate::error::WatchdogError;
+use crate::health::get_latest_health_status_message;
 
 const SERVICE_NAME: &str = "tedge-watchdog";
 
@@ -41,16 +36,6 @@ const SERVICE_NAME: &str = "tedge-watchdog";
 /// a timing misalignment.
 const NOTIFY_SEND_FREQ_RATIO: u64 = 4;
 
-/// A subset of fields of health status payload required by the watchdog.
-///
-/// https://thin-edge.github.io/thin-edge.io/operate/troubleshooting/monitoring-service-health/
-#[derive(Debug, Serialize, Deserialize)]
-struct HealthStatusExt {
-    /// Used for tracking service restarts
-    pub pid: Option<u32>,
-    pub time: Option<JsonValue>,
-}
-
 pub async fn start_watchdog(tedge_config: TEdgeConfig) -> Result<(), anyhow::Error> {
     // Send ready notification to systemd.
     notify_systemd(process::id(), "--ready")?;
@@ -95,7 +80,7 @@ async fn start_watchdog_for_tedge_services(tedge_config: TEdgeConfig) {
     let mqtt_topic_root = tedge_config.mqtt.topic_root.clone();
     let mqtt_schema = MqttSchema::with_root(mqtt_topic_root);
 
-    // TODO: now that we have entity registration, instead of hardcoding, the watchdog can see all
+    // TODO: now that we have entity registration, instead of using a static list, the watchdog can see all
     // running services by looking at registration messages
     let device_topic_id = tedge_config
         .mqtt
@@ -103,21 +88,17 @@ async fn start_watchdog_for_tedge_services(tedge_config: TEdgeConfig) {
         .parse::<EntityTopicId>()
         .expect("Services not in default scheme unsupported");
 
-    let tedge_services = vec![
-        "tedge-mapper-c8y",
-        "tedge-mapper-az",
-        "tedge-mapper-aws",
-        "tedge-mapper-collectd",
-        "tedge-agent",
-        "c8y-firmware-plugin",
-    ]
-    .into_iter()
-    .map(|s| {
-        device_topic_id
-            .default_service_for_device(s)
-            .expect("Services not in default scheme unsupported")
-    })
-    .collect::<Vec<_>>();
+    let tedge_services = tedge_config
+        .watchdog
+        .services
+        .0
+        .iter()
+        .map(|s| {
+            device_topic_id
+                .default_service_for_device(s)
+                .expect("Services not in default scheme unsupported")
+        })
+        .collect::<Vec<_>>();
 
     let watchdog_tasks = FuturesUnordered::new();
     let tedge_config = Arc::new(tedge_config);
@@ -246,41 +227,6 @@ async fn monitor_tedge_service(
     }
 }
 
-async fn get_latest_health_status_message(
-    request_timestamp: OffsetDateTime,
-    messages: &mut mpsc::UnboundedReceiver<MqttMessage>,
-) -> Result<HealthStatusExt, WatchdogError> {
-    while let Some(message) = messages.next().await {
-        if let Ok(message) = message.payload_str() {
-            debug!("Health response received: {message}");
-            if let Ok(health_status) = serde_json::from_str::<HealthStatusExt>(message) {
-                if health_status.time.is_none() {
-                    error!("Ignoring invalid health response: {health_status:?} without a `time` field in it");
-                    continue;
-                }
-                let Ok(datetime) = IsoOrUnix::try_from(&health_status.time.clone().unwrap()) else {
-                    error!("Ignoring invalid health response: failed to parse `time` field");
-                    continue;
-                };
-
-                // the unix timestamp can be a float or an integer
-                // if integer, we don't have a subsecond precision, and it wouldn't be economical to send health status
-                // messages more than once a second anyway, so compare with 1s precision
-                if datetime.into_inner().unix_timestamp() >= request_timestamp.unix_timestamp() {
-                    return Ok(health_status);
-                } else {
-                    debug!(
-                        "Ignoring stale health response: {health_status:?} older than request time: {request_timestamp}",
-                    );
-                }
-            } else {
-                error!("Invalid health response received: {message}");
-            }
-        }
-    }
-    Err(WatchdogError::ChannelClosed)
-}
-
 fn notify_systemd(pid: u32, status: &str) -> Result<ExitStatus, WatchdogError> {
     let pid_opt = format!("--pid={pid}");
     Command::new("systemd-notify")
@@ -312,80 +258,3 @@ fn get_watchdog_sec(service_file: &str) -> Result<u64, WatchdogError> {
         })
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use anyhow::Result;
-    use serde_json::json;
-    use tedge_utils::timestamp::TimeFormat;
-
-    use super::*;
-
-    #[tokio::test]
-    async fn test_get_latest_health_status_message() -> Result<()> {
-        let (mut sender, mut receiver) = mpsc::unbounded::<MqttMessage>();
-        let health_topic =
-            Topic::new("te/device/main/service/test-service/status/health").expect("Valid topic");
-        let base_timestamp = OffsetDateTime::now_utc();
-
-        for x in 1..5u64 {
-            let incremented_datetime = base_timestamp + Duration::from_secs(x);
-            let timestamp_str = TimeFormat::Rfc3339.to_json(incremented_datetime).unwrap();
-
-            let health_status = json!({
-                "status": "up",
-                "pid": 123u32,
-                "time": timestamp_str,
-            })
-            .to_string();
-            let health_message = MqttMessage::new(&health_topic, health_status);
-            sender.publish(health_message).await?;
-        }
-
-        let request_timestamp = base_timestamp + Duration::from_secs(3);
-        let health_status =
-            get_latest_health_status_message(request_timestamp, &mut receiver).await;
-
-        let expected_timestamp = TimeFormat::Rfc3339.to_json(request_timestamp).unwrap();
-        assert_eq!(health_status.unwrap().time, Some(expected_timestamp));
-
-        sender.close_channel();
-        let base_timestamp = base_timestamp + Duration::from_secs(5);
-        let timeout_error = tokio::time::timeout(
-            Duration::from_secs(1),
-            get_latest_health_status_message(base_timestamp, &mut receiver),
-        )
-        .await;
-        assert!(timeout_error.unwrap().is_err());
-
-        Ok(())
-    }
-
-    #[tokio::test]
-    async fn test_get_latest_health_status_message_unix() {
-        let (mut sender, mut receiver) = mpsc::unbounded::<MqttMessage>();
-        let health_topic =
-            Topic::new("te/device/main/service/test-service/status/health").expect("Valid topic");
-        let request_timestamp = OffsetDateTime::parse(
-            "2023-12-15T14:31:03.234Z",
-            &time::format_description::well_known::Rfc3339,
-        )
-        .unwrap();
-        let incremented_datetime = request_timestamp + Duration::from_secs(3);
-        let payload_timestamp = TimeFormat::Unix.to_json(incremented_datetime).unwrap();
-
-        let health_status = json!({
-            "status": "up",
-            "pid": 123u32,
-            "time": payload_timestamp,
-        })
-        .to_string();
-        let health_message = MqttMessage::new(&health_topic, health_status);
-        sender.publish(health_message).await.unwrap();
-        sender.close_channel();
-
-        let health_status =
-            get_latest_health_status_message(request_timestamp, &mut receiver).await;
-        assert_eq!(health_status.unwrap().time, Some(payload_timestamp));
-    }
-}
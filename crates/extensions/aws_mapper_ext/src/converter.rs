@@ -4,15 +4,25 @@ use serde_json::Map;
 use serde_json::Value;
 use std::convert::Infallible;
 use tedge_actors::Converter;
+use tedge_api::measurement::split_json_measurement;
 use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
 use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::IdGenerator;
 use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::telemetry_metadata::TelemetryMetadataCache;
 use tedge_config::models::TopicPrefix;
 use tedge_mqtt_ext::MqttMessage;
 use tedge_mqtt_ext::Topic;
+use tedge_mqtt_ext::TopicFilter;
 use tedge_utils::timestamp::TimeFormat;
 
 use crate::error::ConversionError;
+use crate::jobs;
+use crate::jobs::JobExecutionStatus;
+use crate::jobs::NextJobExecution;
+use crate::shadow;
 use crate::size_threshold::SizeThreshold;
 
 pub struct AwsConverter {
@@ -22,6 +32,10 @@ pub struct AwsConverter {
     pub mqtt_schema: MqttSchema,
     pub time_format: TimeFormat,
     pub topic_prefix: TopicPrefix,
+    /// Generate the ids of the commands triggered by jobs
+    pub(crate) command_id: IdGenerator,
+    /// Units and defaults declared on the `meta` topics of the measurement, event and alarm types
+    pub(crate) telemetry_metadata: TelemetryMetadataCache,
 }
 
 impl AwsConverter {
@@ -34,6 +48,7 @@ impl AwsConverter {
         max_payload_size: u32,
     ) -> Self {
         let size_threshold = SizeThreshold(max_payload_size as usize);
+        let command_id = IdGenerator::new(&format!("{topic_prefix}-mapper"));
         AwsConverter {
             add_timestamp,
             clock,
@@ -41,9 +56,35 @@ impl AwsConverter {
             mqtt_schema: mqtt_schema.clone(),
             time_format,
             topic_prefix,
+            command_id,
+            telemetry_metadata: TelemetryMetadataCache::default(),
         }
     }
 
+    /// The cloud topics the converter has to subscribe to,
+    /// in addition to the thin-edge topics configured by the user
+    ///
+    /// These are the topics of the job notifications,
+    /// the commands triggered by these jobs,
+    /// as well as the metadata of the telemetry types,
+    /// which are forgotten when their entity is deregistered.
+    pub fn cloud_topics(topic_prefix: &TopicPrefix, mqtt_schema: &MqttSchema) -> TopicFilter {
+        let mut topics = mqtt_schema.topics(
+            EntityFilter::Entity(&EntityTopicId::default_main_device()),
+            ChannelFilter::AnyCommand,
+        );
+        topics.add_all(
+            mqtt_schema.topics(EntityFilter::AnyEntity, ChannelFilter::MeasurementMetadata),
+        );
+        topics.add_all(mqtt_schema.topics(EntityFilter::AnyEntity, ChannelFilter::EventMetadata));
+        topics.add_all(mqtt_schema.topics(EntityFilter::AnyEntity, ChannelFilter::AlarmMetadata));
+        topics.add_all(mqtt_schema.topics(EntityFilter::AnyEntity, ChannelFilter::EntityMetadata));
+        topics.add_unchecked(&format!("{topic_prefix}/jobs/notify-next"));
+        topics.add_unchecked(&format!("{topic_prefix}/jobs/$next/get/accepted"));
+        topics.add_unchecked(&format!("{topic_prefix}/jobs/+/update/rejected"));
+        topics
+    }
+
     pub fn with_threshold(self, size_threshold: SizeThreshold) -> Self {
         Self {
             size_threshold,
@@ -54,7 +95,7 @@ impl AwsConverter {
     fn try_convert(&mut self, input: &MqttMessage) -> Result<Vec<MqttMessage>, ConversionError> {
         let messages = match self.mqtt_schema.entity_channel_of(&input.topic) {
             Ok((source, channel)) => self.try_convert_te_topics(source, channel, input),
-            Err(_) => Ok(vec![]),
+            Err(_) => self.try_convert_aws_topics(input),
         }?;
 
         for message in &messages {
@@ -82,7 +123,7 @@ impl AwsConverter {
             return Ok(vec![]);
         }
 
-        match channel {
+        match &channel {
             Channel::Measurement {
                 measurement_type: type_name,
             }
@@ -91,10 +132,77 @@ impl AwsConverter {
             }
             | Channel::Alarm {
                 alarm_type: type_name,
-            } => self.convert_telemetry_message(input, source, &type_name),
+            } => self.convert_telemetry_message(input, &source, &channel, type_name),
 
             Channel::Health => self.convert_health_message(&source, input),
 
+            Channel::MeasurementMetadata { .. }
+            | Channel::EventMetadata { .. }
+            | Channel::AlarmMetadata { .. } => {
+                self.telemetry_metadata
+                    .update(&source, &channel, input.payload_str()?)?;
+                Ok(vec![])
+            }
+            Channel::EntityMetadata if input.payload_bytes().is_empty() => {
+                self.telemetry_metadata.remove_entity(&source);
+                Ok(vec![])
+            }
+
+            Channel::EntityTwinData { fragment_key } => {
+                let update = shadow::reported_state_update(
+                    &self.topic_prefix,
+                    &source,
+                    fragment_key,
+                    input,
+                )?;
+                Ok(vec![update])
+            }
+
+            Channel::Command { cmd_id, .. }
+                if source.is_default_main_device() && !input.payload_bytes().is_empty() =>
+            {
+                match self.command_id.get_value(cmd_id) {
+                    Some(job_id) => jobs::command_status_update(&self.topic_prefix, job_id, input),
+                    None => Ok(vec![]),
+                }
+            }
+
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn try_convert_aws_topics(
+        &mut self,
+        input: &MqttMessage,
+    ) -> Result<Vec<MqttMessage>, ConversionError> {
+        let Some(topic) = input
+            .topic
+            .name
+            .strip_prefix(self.topic_prefix.as_str())
+            .and_then(|topic| topic.strip_prefix('/'))
+        else {
+            return Ok(vec![]);
+        };
+
+        match topic.split('/').collect::<Vec<_>>()[..] {
+            ["jobs", "notify-next"] | ["jobs", "$next", "get", "accepted"] => {
+                let next: NextJobExecution = serde_json::from_slice(input.payload_bytes())?;
+                match next.execution {
+                    // Jobs in progress are already being processed by a command
+                    Some(execution) if execution.status == JobExecutionStatus::Queued => {
+                        Ok(execution.into_command(
+                            &self.topic_prefix,
+                            &self.mqtt_schema,
+                            &self.command_id,
+                        ))
+                    }
+                    _ => Ok(vec![]),
+                }
+            }
+            ["jobs", job_id, "update", "rejected"] => Err(ConversionError::JobUpdateRejected {
+                job_id: job_id.to_string(),
+                error: input.payload_str()?.to_string(),
+            }),
             _ => Ok(vec![]),
         }
     }
@@ -107,7 +215,7 @@ impl AwsConverter {
         let topic_prefix = &self.topic_prefix;
         let source = normalize_name(source);
         let out_topic = format!("{topic_prefix}/td/{source}/status/health");
-        match self.with_timestamp(input) {
+        match self.with_timestamp(input, &source, &Channel::Health) {
             Ok(payload) => {
                 let output = MqttMessage::new(&Topic::new(&out_topic).unwrap(), payload);
                 Ok(vec![output])
@@ -122,18 +230,19 @@ impl AwsConverter {
     fn convert_telemetry_message(
         &mut self,
         input: &MqttMessage,
-        source: EntityTopicId,
+        source: &EntityTopicId,
+        channel: &Channel,
         telemetry_type: &String,
     ) -> Result<Vec<MqttMessage>, ConversionError> {
         let topic_prefix = &self.topic_prefix;
-        let payload = match self.with_timestamp(input) {
+        let payload = match self.with_timestamp(input, source, channel) {
             Ok(payload) => payload,
             Err(err) => {
                 error!("Could not add timestamp to payload: {err}. Skipping");
                 return Ok(vec![]);
             }
         };
-        let source = normalize_name(&source);
+        let source = normalize_name(source);
         // XXX: should match on `Channel` instead
         let out_topic = match input.topic.name.split('/').collect::<Vec<_>>()[..] {
             [_, _, _, _, _, "m", _] => {
@@ -148,13 +257,45 @@ impl AwsConverter {
             _ => return Ok(vec![]),
         };
 
+        if payload.len() > self.size_threshold.0 && matches!(channel, Channel::Measurement { .. }) {
+            return self.split_measurement(&out_topic, &payload);
+        }
+
         let output = MqttMessage::new(&out_topic, payload);
         self.size_threshold.validate(&output)?;
         Ok(vec![output])
     }
 
-    fn with_timestamp(&self, input: &MqttMessage) -> Result<String, ConversionError> {
+    /// Split a measurement too large to be sent in a single message
+    fn split_measurement(
+        &self,
+        out_topic: &Topic,
+        payload: &str,
+    ) -> Result<Vec<MqttMessage>, ConversionError> {
+        let measurement: Map<String, Value> = serde_json::from_str(payload)?;
+        let parts = split_json_measurement(&measurement, self.size_threshold.0).map_err(|err| {
+            ConversionError::SizeThresholdExceeded {
+                topic: out_topic.name.clone(),
+                actual_size: err.actual_size,
+                threshold: err.threshold,
+            }
+        })?;
+
+        Ok(parts
+            .into_iter()
+            .map(|part| MqttMessage::new(out_topic, Value::Object(part).to_string()))
+            .collect())
+    }
+
+    fn with_timestamp(
+        &self,
+        input: &MqttMessage,
+        source: &EntityTopicId,
+        channel: &Channel,
+    ) -> Result<String, ConversionError> {
         let mut payload: Map<String, Value> = serde_json::from_slice(input.payload.as_bytes())?;
+        self.telemetry_metadata
+            .embed_metadata(source, channel, &mut payload);
 
         let time = match payload.remove("time") {
             Some(time) => Some(self.time_format.reformat_json(time)?),
@@ -178,6 +319,7 @@ impl AwsConverter {
 
     fn new_error_message(&self, error: ConversionError) -> MqttMessage {
         error!("Mapping error: {}", error);
+        tedge_metrics::record_conversion_error("aws");
         MqttMessage::new(&self.mqtt_schema.error_topic(), error.to_string())
     }
 }
@@ -187,7 +329,7 @@ impl AwsConverter {
 //
 // Ref: https://docs.aws.amazon.com/general/latest/gr/iot-core.html -> "Maximum number of slashes in
 // topic and topic filter"
-fn normalize_name(source: &EntityTopicId) -> String {
+pub(crate) fn normalize_name(source: &EntityTopicId) -> String {
     let parts: Vec<&str> = source.as_str().split('/').collect();
     parts
         .iter()
@@ -206,6 +348,11 @@ impl Converter for AwsConverter {
         let messages_or_err = self.try_convert(input);
         Ok(self.wrap_errors(messages_or_err))
     }
+
+    /// Request the next pending job, to process the jobs queued while the mapper was down
+    fn init_messages(&mut self) -> Result<Vec<Self::Output>, Self::Error> {
+        Ok(vec![jobs::next_job_request(&self.topic_prefix)])
+    }
 }
 
 #[cfg(test)]
@@ -262,6 +409,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn try_convert_large_measurement_splits_it() {
+        let mut converter = create_test_converter(true).with_threshold(SizeThreshold(110));
+
+        let input = r#"{
+            "temperature": 23.0,
+            "pressure": 220.0,
+            "location": {"latitude": 32.54, "longitude": -117.67, "altitude": 98.6}
+        }"#;
+        let output = converter.try_convert(&new_tedge_message(input)).unwrap();
+
+        assert_eq!(output.len(), 2);
+        for message in &output {
+            assert_eq!(message.topic.name, "aws/td/device:main/m/");
+            assert!(message.payload_bytes().len() <= 110);
+        }
+        let parts: Vec<Value> = output
+            .iter()
+            .map(|message| serde_json::from_str(message.payload_str().unwrap()).unwrap())
+            .collect();
+        assert_json_eq!(
+            Value::Array(parts),
+            json!([
+                {
+                    "location": {"altitude": 98.6, "latitude": 32.54, "longitude": -117.67},
+                    "time": "2021-04-08T00:00:00+05:00"
+                },
+                {
+                    "pressure": 220.0,
+                    "temperature": 23.0,
+                    "time": "2021-04-08T00:00:00+05:00"
+                }
+            ])
+        );
+    }
+
     #[test]
     fn converting_input_without_timestamp_produces_output_without_timestamp_given_add_timestamp_is_false(
     ) {
@@ -775,6 +958,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn converting_telemetry_with_declared_metadata() {
+        let mut converter = create_test_converter(false);
+
+        for (topic, metadata) in [
+            (
+                "te/device/child///m/env/meta",
+                r#"{"units": {"temperature": "°C", "battery": {"voltage": "V"}}}"#,
+            ),
+            (
+                "te/device/child///e/login/meta",
+                r#"{"text": "Someone logged in"}"#,
+            ),
+        ] {
+            let meta_message =
+                MqttMessage::new(&Topic::new_unchecked(topic), metadata).with_retain();
+            assert!(converter.convert(&meta_message).unwrap().is_empty());
+        }
+
+        let measurement = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child///m/env"),
+            r#"{"temperature": 23.0, "battery": {"voltage": 3.7}}"#,
+        );
+        let output = converter.convert(&measurement).unwrap();
+        assert_eq!(output[0].topic.name, "aws/td/device:child/m/env");
+        assert_json_eq!(
+            serde_json::from_str::<serde_json::Value>(&extract_first_message_payload(output))
+                .unwrap(),
+            json!({
+                "temperature": 23.0,
+                "battery": {"voltage": 3.7},
+                "units": {"temperature": "°C", "battery": {"voltage": "V"}}
+            })
+        );
+
+        let event = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child///e/login"),
+            r#"{"time": "2021-04-23T19:00:00+05:00"}"#,
+        );
+        let output = converter.convert(&event).unwrap();
+        assert_json_eq!(
+            serde_json::from_str::<serde_json::Value>(&extract_first_message_payload(output))
+                .unwrap(),
+            json!({
+                "text": "Someone logged in",
+                "time": "2021-04-23T19:00:00+05:00"
+            })
+        );
+    }
+
+    #[test]
+    fn telemetry_metadata_are_forgotten_once_the_entity_is_deregistered() {
+        let mut converter = create_test_converter(false);
+
+        let meta_message = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child///m/env/meta"),
+            r#"{"units": {"temperature": "°C"}}"#,
+        )
+        .with_retain();
+        assert!(converter.convert(&meta_message).unwrap().is_empty());
+
+        let deregistration =
+            MqttMessage::new(&Topic::new_unchecked("te/device/child//"), "").with_retain();
+        assert!(converter.convert(&deregistration).unwrap().is_empty());
+
+        let measurement = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child///m/env"),
+            r#"{"temperature": 23.0}"#,
+        );
+        let output = converter.convert(&measurement).unwrap();
+        assert_json_eq!(
+            serde_json::from_str::<serde_json::Value>(&extract_first_message_payload(output))
+                .unwrap(),
+            json!({"temperature": 23.0})
+        );
+    }
+
     #[test]
     fn converting_service_health_status_up_message() {
         let mut converter = create_test_converter(false);
@@ -811,6 +1071,121 @@ mod tests {
         assert_eq!(res[0], expected_msg);
     }
 
+    #[test]
+    fn converting_twin_data_into_shadow_reported_state() {
+        let mut converter = create_test_converter(false);
+
+        let input = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///twin/firmware"),
+            r#"{"version":"1.2.3"}"#,
+        );
+        let output = converter.convert(&input).unwrap();
+        assert_eq!(output[0].topic.name, "aws/shadow/update");
+        assert_json_eq!(
+            serde_json::from_str::<Value>(output[0].payload_str().unwrap()).unwrap(),
+            json!({"state": {"reported": {"firmware": {"version": "1.2.3"}}}})
+        );
+
+        let input = MqttMessage::new(&Topic::new_unchecked("te/device/child///twin/firmware"), "");
+        let output = converter.convert(&input).unwrap();
+        assert_eq!(output[0].topic.name, "aws/shadow/name/device:child/update");
+        assert_json_eq!(
+            serde_json::from_str::<Value>(output[0].payload_str().unwrap()).unwrap(),
+            json!({"state": {"reported": {"firmware": null}}})
+        );
+    }
+
+    #[test]
+    fn converting_job_into_command() {
+        let mut converter = create_test_converter(false);
+
+        let init = converter.init_messages().unwrap();
+        assert_eq!(init[0].topic.name, "aws/jobs/$next/get");
+
+        let notification = MqttMessage::new(
+            &Topic::new_unchecked("aws/jobs/notify-next"),
+            json!({
+                "timestamp": 1700000000,
+                "execution": {
+                    "jobId": "job-1",
+                    "status": "QUEUED",
+                    "jobDocument": {
+                        "operation": "software_update",
+                        "updateList": []
+                    }
+                }
+            })
+            .to_string(),
+        );
+        let output = converter.convert(&notification).unwrap();
+        assert_eq!(
+            output[0].topic.name,
+            "te/device/main///cmd/software_update/aws-mapper-job-1"
+        );
+        assert!(output[0].retain);
+        assert_json_eq!(
+            serde_json::from_str::<Value>(output[0].payload_str().unwrap()).unwrap(),
+            json!({"status": "init", "updateList": []})
+        );
+        assert_eq!(output[1].topic.name, "aws/jobs/job-1/update");
+        assert_json_eq!(
+            serde_json::from_str::<Value>(output[1].payload_str().unwrap()).unwrap(),
+            json!({"status": "IN_PROGRESS", "statusDetails": {"status": "init"}})
+        );
+    }
+
+    #[test]
+    fn jobs_in_progress_are_not_relaunched() {
+        let mut converter = create_test_converter(false);
+
+        let notification = MqttMessage::new(
+            &Topic::new_unchecked("aws/jobs/notify-next"),
+            r#"{"execution": {"jobId": "job-1", "status": "IN_PROGRESS", "jobDocument": {"operation": "restart"}}}"#,
+        );
+        assert!(converter.convert(&notification).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reporting_job_execution_status_from_command_state() {
+        let mut converter = create_test_converter(false);
+        let command_topic =
+            Topic::new_unchecked("te/device/main///cmd/software_update/aws-mapper-job-1");
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &command_topic,
+                r#"{"status":"executing"}"#,
+            ))
+            .unwrap();
+        assert_eq!(output.len(), 1);
+        assert_json_eq!(
+            serde_json::from_str::<Value>(output[0].payload_str().unwrap()).unwrap(),
+            json!({"status": "IN_PROGRESS", "statusDetails": {"status": "executing"}})
+        );
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &command_topic,
+                r#"{"status":"failed","reason":"no such package"}"#,
+            ))
+            .unwrap();
+        assert_eq!(
+            output,
+            vec![
+                MqttMessage::new(
+                    &Topic::new_unchecked("aws/jobs/job-1/update"),
+                    r#"{"status":"FAILED","statusDetails":{"reason":"no such package","status":"failed"}}"#
+                ),
+                MqttMessage::new(&command_topic, "").with_retain(),
+            ]
+        );
+
+        let output = converter
+            .convert(&MqttMessage::new(&command_topic, ""))
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
     fn create_test_converter(add_timestamp: bool) -> AwsConverter {
         AwsConverter::new(
             add_timestamp,
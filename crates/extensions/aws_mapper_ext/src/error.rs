@@ -24,4 +24,7 @@ pub enum ConversionError {
 
     #[error(transparent)]
     MqttError(#[from] MqttError),
+
+    #[error("The update of the AWS job {job_id} has been rejected: {error}")]
+    JobUpdateRejected { job_id: String, error: String },
 }
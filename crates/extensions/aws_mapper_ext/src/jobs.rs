@@ -0,0 +1,203 @@
+//! Mapping of AWS IoT Jobs to thin-edge commands
+//!
+//! - A queued job execution, notified on `<prefix>/jobs/notify-next`
+//!   or returned on `<prefix>/jobs/$next/get/accepted`,
+//!   is published as a `te/device/main///cmd/<operation>/<prefix>-mapper-<job-id>` command.
+//!   The job document has to provide the `operation` name,
+//!   all the other fields of the job document being used as the command init payload.
+//! - The job execution status is then updated on `<prefix>/jobs/<job-id>/update`
+//!   along the state transitions of this command, which is cleared once in a final state.
+//!
+//! ```json
+//! {
+//!     "operation": "software_update",
+//!     "updateList": [ { "type": "apt", "modules": [ { "name": "nodered", "action": "install" } ] } ]
+//! }
+//! ```
+use crate::error::ConversionError;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::IdGenerator;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_config::models::TopicPrefix;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::Topic;
+
+/// The payload of `jobs/notify-next` and `jobs/$next/get/accepted` messages
+#[derive(Debug, Deserialize)]
+pub struct NextJobExecution {
+    /// None when there is no pending job
+    pub execution: Option<JobExecution>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobExecution {
+    pub job_id: String,
+    pub status: JobExecutionStatus,
+    #[serde(default)]
+    pub job_document: Map<String, Value>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum JobExecutionStatus {
+    Queued,
+    InProgress,
+    Succeeded,
+    Failed,
+    Rejected,
+    Removed,
+    Canceled,
+    TimedOut,
+}
+
+/// Build the request for the next pending job execution
+pub fn next_job_request(prefix: &TopicPrefix) -> MqttMessage {
+    let topic = Topic::new_unchecked(&format!("{prefix}/jobs/$next/get"));
+    MqttMessage::new(&topic, "{}")
+}
+
+impl JobExecution {
+    /// Translate a queued job execution into a thin-edge command for the main device
+    ///
+    /// Along the command, return the job execution update marking the job as in progress.
+    /// If the job document doesn't provide an operation name, the job execution is rejected.
+    pub fn into_command(
+        self,
+        prefix: &TopicPrefix,
+        mqtt_schema: &MqttSchema,
+        command_id: &IdGenerator,
+    ) -> Vec<MqttMessage> {
+        let mut init_payload = self.job_document;
+        let Some(Value::String(operation)) = init_payload.remove("operation") else {
+            let update = job_execution_update(
+                prefix,
+                &self.job_id,
+                "REJECTED",
+                json!({"reason": "The job document doesn't provide the operation to execute"}),
+            );
+            return vec![update];
+        };
+        init_payload.insert("status".to_string(), "init".into());
+
+        let channel = Channel::Command {
+            operation: OperationType::from(operation.as_str()),
+            cmd_id: command_id.new_id_with_str(&self.job_id),
+        };
+        let topic = mqtt_schema.topic_for(&EntityTopicId::default_main_device(), &channel);
+        let command =
+            MqttMessage::new(&topic, Value::Object(init_payload).to_string()).with_retain();
+        let update = job_execution_update(
+            prefix,
+            &self.job_id,
+            "IN_PROGRESS",
+            json!({"status": "init"}),
+        );
+        vec![command, update]
+    }
+}
+
+/// Translate the state of a command triggered by a job into a job execution update
+///
+/// Once the command is in a final state, the command is also cleared.
+pub fn command_status_update(
+    prefix: &TopicPrefix,
+    job_id: &str,
+    command: &MqttMessage,
+) -> Result<Vec<MqttMessage>, ConversionError> {
+    let payload: Value = serde_json::from_slice(command.payload_bytes())?;
+    let Some(status) = payload.get("status").and_then(Value::as_str) else {
+        return Ok(vec![]);
+    };
+
+    let mut details = Map::new();
+    details.insert("status".to_string(), status.into());
+    if let Some(reason) = payload.get("reason").and_then(Value::as_str) {
+        details.insert("reason".to_string(), reason.into());
+    }
+
+    let (execution_status, is_final) = match status {
+        // The job has already been marked in progress when the command was created
+        "init" => return Ok(vec![]),
+        "successful" => ("SUCCEEDED", true),
+        "failed" => ("FAILED", true),
+        _ => ("IN_PROGRESS", false),
+    };
+
+    let mut messages = vec![job_execution_update(
+        prefix,
+        job_id,
+        execution_status,
+        Value::Object(details),
+    )];
+    if is_final {
+        messages.push(MqttMessage::new(&command.topic, "").with_retain());
+    }
+    Ok(messages)
+}
+
+fn job_execution_update(
+    prefix: &TopicPrefix,
+    job_id: &str,
+    status: &str,
+    status_details: Value,
+) -> MqttMessage {
+    let topic = Topic::new_unchecked(&format!("{prefix}/jobs/{job_id}/update"));
+    let payload = json!({
+        "status": status,
+        "statusDetails": status_details,
+    });
+    MqttMessage::new(&topic, payload.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_next_job_execution() {
+        let notification = r#"{
+            "timestamp": 1700000000,
+            "execution": {
+                "jobId": "upgrade-42",
+                "status": "QUEUED",
+                "queuedAt": 1700000000,
+                "versionNumber": 1,
+                "executionNumber": 1,
+                "jobDocument": { "operation": "config_update", "type": "mosquitto" }
+            }
+        }"#;
+        let next: NextJobExecution = serde_json::from_str(notification).unwrap();
+        let execution = next.execution.unwrap();
+        assert_eq!(execution.job_id, "upgrade-42");
+        assert_eq!(execution.status, JobExecutionStatus::Queued);
+        assert_eq!(execution.job_document["type"], "mosquitto");
+
+        let next: NextJobExecution = serde_json::from_str(r#"{"timestamp": 1700000000}"#).unwrap();
+        assert!(next.execution.is_none());
+    }
+
+    #[test]
+    fn reject_job_with_no_operation() {
+        let execution = JobExecution {
+            job_id: "123".to_string(),
+            status: JobExecutionStatus::Queued,
+            job_document: Map::new(),
+        };
+        let messages = execution.into_command(
+            &TopicPrefix::try_from("aws").unwrap(),
+            &MqttSchema::default(),
+            &IdGenerator::new("aws-mapper"),
+        );
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].topic.name, "aws/jobs/123/update");
+        assert!(messages[0].payload_str().unwrap().contains("REJECTED"));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod converter;
 pub mod error;
+pub mod jobs;
+pub mod shadow;
 pub mod size_threshold;
@@ -0,0 +1,49 @@
+//! Mapping of the thin-edge twin data to the reported state of AWS IoT device shadows
+//!
+//! The twin data of the main device are reported on the classic shadow of the thing,
+//! i.e. on `<prefix>/shadow/update`,
+//! while the twin data of any other entity are reported on a named shadow,
+//! i.e. on `<prefix>/shadow/name/<entity>/update`,
+//! the shadow name being derived from the entity topic id, as for the telemetry topics.
+use crate::converter::normalize_name;
+use crate::error::ConversionError;
+use serde_json::json;
+use serde_json::Value;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_config::models::TopicPrefix;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::Topic;
+
+/// Build the shadow update reporting a twin fragment of an entity
+///
+/// An empty payload, used to clear a twin fragment, removes the fragment from the reported state.
+pub fn reported_state_update(
+    prefix: &TopicPrefix,
+    source: &EntityTopicId,
+    fragment_key: &str,
+    input: &MqttMessage,
+) -> Result<MqttMessage, ConversionError> {
+    let value: Value = if input.payload_bytes().is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(input.payload_bytes())?
+    };
+
+    let topic = if source.is_default_main_device() {
+        format!("{prefix}/shadow/update")
+    } else {
+        let shadow_name = normalize_name(source);
+        format!("{prefix}/shadow/name/{shadow_name}/update")
+    };
+    let payload = json!({
+        "state": {
+            "reported": {
+                fragment_key: value
+            }
+        }
+    });
+    Ok(MqttMessage::new(
+        &Topic::new_unchecked(&topic),
+        payload.to_string(),
+    ))
+}
@@ -1,23 +1,36 @@
 use crate::error::ConversionError;
+use crate::methods;
+use crate::methods::MethodRequest;
 use crate::size_threshold::SizeThreshold;
+use crate::twin;
+use crate::twin::TwinResponse;
 use clock::Clock;
 use log::error;
 use serde_json::Map;
 use serde_json::Value;
+use std::collections::HashSet;
 use std::convert::Infallible;
 use tedge_actors::Converter;
+use tedge_api::measurement::split_json_measurement;
 use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::IdGenerator;
 use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::telemetry_metadata::TelemetryMetadataCache;
 use tedge_config::models::timestamp::TimeFormat;
 use tedge_config::models::TopicPrefix;
 use tedge_mqtt_ext::MqttMessage;
 use tedge_mqtt_ext::Topic;
+use tedge_mqtt_ext::TopicFilter;
 
 #[derive(Debug)]
 pub struct MapperConfig {
     pub out_topic: Topic,
     pub errors_topic: Topic,
     pub time_format: TimeFormat,
+    pub topic_prefix: TopicPrefix,
 }
 
 pub struct AzureConverter {
@@ -26,6 +39,16 @@ pub struct AzureConverter {
     pub(crate) size_threshold: SizeThreshold,
     pub(crate) mapper_config: MapperConfig,
     pub mqtt_schema: MqttSchema,
+    /// Generate the ids of the commands triggered by direct methods
+    pub(crate) command_id: IdGenerator,
+    /// Operations registered by the main device, the only ones that can be invoked as direct methods
+    pub(crate) supported_methods: HashSet<String>,
+    /// Last request id used for a device twin request
+    pub(crate) twin_request_id: u64,
+    /// Request id of the pending request for the whole device twin, if any
+    pub(crate) pending_twin_get: Option<String>,
+    /// Units and defaults declared on the `meta` topics of the measurement, event and alarm types
+    pub(crate) telemetry_metadata: TelemetryMetadataCache,
 }
 
 impl AzureConverter {
@@ -41,17 +64,53 @@ impl AzureConverter {
             out_topic: Topic::new_unchecked(&format!("{topic_prefix}/messages/events/")),
             errors_topic: mqtt_schema.error_topic(),
             time_format,
+            topic_prefix: topic_prefix.clone(),
         };
+        let command_id = IdGenerator::new(&format!("{topic_prefix}-mapper"));
         let size_threshold = SizeThreshold(max_payload_size as usize);
         AzureConverter {
             add_timestamp,
             clock,
             size_threshold,
             mapper_config,
-            mqtt_schema: MqttSchema::default(),
+            mqtt_schema,
+            command_id,
+            supported_methods: HashSet::new(),
+            twin_request_id: 0,
+            pending_twin_get: None,
+            telemetry_metadata: TelemetryMetadataCache::default(),
         }
     }
 
+    /// The cloud topics the converter has to subscribe to,
+    /// in addition to the thin-edge topics configured by the user
+    ///
+    /// These are the topics of the device twin and direct method requests,
+    /// the operations supported by the main device and the commands triggered by direct methods,
+    /// as well as the metadata of the telemetry types,
+    /// which are forgotten when their entity is deregistered.
+    pub fn cloud_topics(topic_prefix: &TopicPrefix, mqtt_schema: &MqttSchema) -> TopicFilter {
+        let main_device = EntityTopicId::default_main_device();
+        let mut topics = mqtt_schema.topics(
+            EntityFilter::Entity(&main_device),
+            ChannelFilter::AnyCommand,
+        );
+        topics.add_all(mqtt_schema.topics(
+            EntityFilter::Entity(&main_device),
+            ChannelFilter::AnyCommandMetadata,
+        ));
+        topics.add_all(
+            mqtt_schema.topics(EntityFilter::AnyEntity, ChannelFilter::MeasurementMetadata),
+        );
+        topics.add_all(mqtt_schema.topics(EntityFilter::AnyEntity, ChannelFilter::EventMetadata));
+        topics.add_all(mqtt_schema.topics(EntityFilter::AnyEntity, ChannelFilter::AlarmMetadata));
+        topics.add_all(mqtt_schema.topics(EntityFilter::AnyEntity, ChannelFilter::EntityMetadata));
+        topics.add_unchecked(&format!("{topic_prefix}/twin/res/#"));
+        topics.add_unchecked(&format!("{topic_prefix}/twin/PATCH/properties/desired/#"));
+        topics.add_unchecked(&format!("{topic_prefix}/methods/POST/#"));
+        topics
+    }
+
     pub fn with_threshold(self, size_threshold: SizeThreshold) -> Self {
         Self {
             size_threshold,
@@ -61,8 +120,8 @@ impl AzureConverter {
 
     fn try_convert(&mut self, input: &MqttMessage) -> Result<Vec<MqttMessage>, ConversionError> {
         let messages = match self.mqtt_schema.entity_channel_of(&input.topic) {
-            Ok((_, channel)) => self.try_convert_te_topics(input, channel),
-            Err(_) => Ok(Vec::new()),
+            Ok((entity, channel)) => self.try_convert_te_topics(input, &entity, channel),
+            Err(_) => self.try_convert_az_topics(input),
         }?;
 
         for message in &messages {
@@ -79,6 +138,7 @@ impl AzureConverter {
     fn try_convert_te_topics(
         &mut self,
         input: &MqttMessage,
+        entity: &EntityTopicId,
         channel: Channel,
     ) -> Result<Vec<MqttMessage>, ConversionError> {
         // don't convert mosquitto bridge notification topic
@@ -97,7 +157,13 @@ impl AzureConverter {
             Channel::Measurement { .. }
             | Channel::Event { .. }
             | Channel::Alarm { .. }
-            | Channel::Health => match self.with_timestamp(input) {
+            | Channel::Health => match self.with_timestamp(input, entity, &channel) {
+                Ok(payload)
+                    if payload.len() > self.size_threshold.0
+                        && matches!(channel, Channel::Measurement { .. }) =>
+                {
+                    self.split_measurement(&payload)
+                }
                 Ok(payload) => {
                     let output = MqttMessage::new(&self.mapper_config.out_topic, payload);
                     Ok(vec![output])
@@ -110,13 +176,145 @@ impl AzureConverter {
                     Ok(vec![])
                 }
             },
+            // The device twin is the twin of the main device
+            Channel::EntityTwinData { fragment_key } if entity.is_default_main_device() => {
+                let request_id = self.new_twin_request_id();
+                let patch = twin::reported_property_patch(
+                    &self.mapper_config.topic_prefix,
+                    &request_id,
+                    fragment_key,
+                    input,
+                )?;
+                Ok(vec![patch])
+            }
+            Channel::MeasurementMetadata { .. }
+            | Channel::EventMetadata { .. }
+            | Channel::AlarmMetadata { .. } => {
+                self.telemetry_metadata
+                    .update(entity, &channel, input.payload_str()?)?;
+                Ok(vec![])
+            }
+            Channel::EntityMetadata if input.payload_bytes().is_empty() => {
+                self.telemetry_metadata.remove_entity(entity);
+                Ok(vec![])
+            }
+            Channel::CommandMetadata { operation } if entity.is_default_main_device() => {
+                if input.payload_bytes().is_empty() {
+                    self.supported_methods.remove(&operation.to_string());
+                } else {
+                    self.supported_methods.insert(operation.to_string());
+                }
+                Ok(vec![])
+            }
+            Channel::Command { cmd_id, .. } if !input.payload_bytes().is_empty() => {
+                match self.command_id.get_value(cmd_id) {
+                    Some(request_id) => Ok(methods::command_response(
+                        &self.mapper_config.topic_prefix,
+                        request_id,
+                        input,
+                    )
+                    .unwrap_or_default()),
+                    None => Ok(vec![]),
+                }
+            }
             _ => Ok(vec![]),
         }
     }
 
-    fn with_timestamp(&mut self, input: &MqttMessage) -> Result<String, ConversionError> {
+    fn try_convert_az_topics(
+        &mut self,
+        input: &MqttMessage,
+    ) -> Result<Vec<MqttMessage>, ConversionError> {
+        let Some(topic) = input
+            .topic
+            .name
+            .strip_prefix(self.mapper_config.topic_prefix.as_str())
+            .and_then(|topic| topic.strip_prefix('/'))
+        else {
+            return Ok(vec![]);
+        };
+
+        if let Some(response) = topic.strip_prefix("twin/res/") {
+            let Some(response) = TwinResponse::parse(response) else {
+                return Ok(vec![]);
+            };
+            if !response.is_success() {
+                return Err(ConversionError::TwinRequestFailed {
+                    request_id: response.request_id.to_string(),
+                    status: response.status,
+                });
+            }
+            if self.pending_twin_get.as_deref() != Some(response.request_id) {
+                // Response to a reported property patch
+                return Ok(vec![]);
+            }
+            self.pending_twin_get = None;
+            let twin: Value = serde_json::from_slice(input.payload_bytes())?;
+            return Ok(twin::desired_properties(
+                &self.mqtt_schema,
+                &twin["desired"],
+            ));
+        }
+
+        if topic.starts_with("twin/PATCH/properties/desired/") {
+            let desired: Value = serde_json::from_slice(input.payload_bytes())?;
+            return Ok(twin::desired_properties(&self.mqtt_schema, &desired));
+        }
+
+        if let Some(request) = topic
+            .strip_prefix("methods/POST/")
+            .and_then(MethodRequest::parse)
+        {
+            if !self.supported_methods.contains(request.method) {
+                return Ok(vec![
+                    request.unknown_method(&self.mapper_config.topic_prefix)
+                ]);
+            }
+            let command = request.into_command(
+                &self.mapper_config.topic_prefix,
+                &self.mqtt_schema,
+                &self.command_id,
+                input.payload_bytes(),
+            );
+            return Ok(vec![command]);
+        }
+
+        Ok(vec![])
+    }
+
+    /// Split a measurement too large to be sent in a single message
+    fn split_measurement(&self, payload: &str) -> Result<Vec<MqttMessage>, ConversionError> {
+        let out_topic = &self.mapper_config.out_topic;
+        let measurement: Map<String, Value> = serde_json::from_str(payload)?;
+        let parts = split_json_measurement(&measurement, self.size_threshold.0).map_err(|err| {
+            ConversionError::SizeThresholdExceeded {
+                topic: out_topic.name.clone(),
+                actual_size: err.actual_size,
+                threshold: err.threshold,
+            }
+        })?;
+
+        Ok(parts
+            .into_iter()
+            .map(|part| MqttMessage::new(out_topic, Value::Object(part).to_string()))
+            .collect())
+    }
+
+    fn new_twin_request_id(&mut self) -> String {
+        self.twin_request_id += 1;
+        self.twin_request_id.to_string()
+    }
+
+    fn with_timestamp(
+        &mut self,
+        input: &MqttMessage,
+        entity: &EntityTopicId,
+        channel: &Channel,
+    ) -> Result<String, ConversionError> {
         let time_format = self.mapper_config.time_format;
         let mut payload: Map<String, Value> = serde_json::from_slice(input.payload.as_bytes())?;
+        self.telemetry_metadata
+            .embed_metadata(entity, channel, &mut payload);
 
         let time = match payload.remove("time") {
             Some(time) => Some(time_format.reformat_json(time)?),
@@ -140,6 +338,7 @@ impl AzureConverter {
 
     fn new_error_message(&self, error: ConversionError) -> MqttMessage {
         error!("Mapping error: {}", error);
+        tedge_metrics::record_conversion_error("az");
         MqttMessage::new(&self.mapper_config.errors_topic, error.to_string())
     }
 }
@@ -154,6 +353,14 @@ impl Converter for AzureConverter {
 
         Ok(self.wrap_errors(messages_or_err))
     }
+
+    /// Request the whole device twin, to get the desired properties set while the mapper was down
+    fn init_messages(&mut self) -> Result<Vec<Self::Output>, Self::Error> {
+        let request_id = self.new_twin_request_id();
+        let request = twin::get_twin_request(&self.mapper_config.topic_prefix, &request_id);
+        self.pending_twin_get = Some(request_id);
+        Ok(vec![request])
+    }
 }
 
 #[cfg(test)]
@@ -218,6 +425,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn try_convert_large_measurement_splits_it() {
+        let mut converter = create_test_converter(true).with_threshold(SizeThreshold(110));
+
+        let input = r#"{
+            "temperature": 23.0,
+            "pressure": 220.0,
+            "location": {"latitude": 32.54, "longitude": -117.67, "altitude": 98.6}
+        }"#;
+        let output = converter.try_convert(&new_tedge_message(input)).unwrap();
+
+        assert_eq!(output.len(), 2);
+        let parts: Vec<Value> = output
+            .iter()
+            .map(|message| {
+                assert!(message.payload_bytes().len() <= 110);
+                serde_json::from_str(message.payload_str().unwrap()).unwrap()
+            })
+            .collect();
+        assert_json_eq!(
+            Value::Array(parts),
+            json!([
+                {
+                    "location": {"altitude": 98.6, "latitude": 32.54, "longitude": -117.67},
+                    "time": "2021-04-08T00:00:00+05:00"
+                },
+                {
+                    "pressure": 220.0,
+                    "temperature": 23.0,
+                    "time": "2021-04-08T00:00:00+05:00"
+                }
+            ])
+        );
+    }
+
     #[test]
     fn converting_input_without_timestamp_produces_output_without_timestamp_given_add_timestamp_is_false(
     ) {
@@ -440,6 +682,93 @@ mod tests {
         assert_eq!(output[0].topic.name, output_topic);
     }
 
+    #[test]
+    fn converting_telemetry_with_declared_metadata() {
+        let mut converter = create_test_converter(false);
+
+        for (topic, metadata) in [
+            (
+                "te/device/main///m/env/meta",
+                r#"{"units": {"temperature": "°C"}}"#,
+            ),
+            (
+                "te/device/main///a/temp_high/meta",
+                r#"{"severity": "major", "text": "Temperature is too high"}"#,
+            ),
+        ] {
+            let meta_message =
+                MqttMessage::new(&Topic::new_unchecked(topic), metadata).with_retain();
+            assert!(converter.convert(&meta_message).unwrap().is_empty());
+        }
+
+        let measurement = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///m/env"),
+            r#"{"temperature": 23.0}"#,
+        );
+        let output = converter.convert(&measurement).unwrap();
+        assert_json_eq!(
+            serde_json::from_str::<serde_json::Value>(&extract_first_message_payload(output))
+                .unwrap(),
+            json!({
+                "temperature": 23.0,
+                "units": {"temperature": "°C"}
+            })
+        );
+
+        let alarm = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///a/temp_high"),
+            r#"{"text": "Temperature is 95°C"}"#,
+        );
+        let output = converter.convert(&alarm).unwrap();
+        assert_json_eq!(
+            serde_json::from_str::<serde_json::Value>(&extract_first_message_payload(output))
+                .unwrap(),
+            json!({
+                "severity": "major",
+                "text": "Temperature is 95°C"
+            })
+        );
+
+        // The metadata of a type are not applied to other types
+        let other_measurement = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///m/other"),
+            r#"{"temperature": 23.0}"#,
+        );
+        let output = converter.convert(&other_measurement).unwrap();
+        assert_json_eq!(
+            serde_json::from_str::<serde_json::Value>(&extract_first_message_payload(output))
+                .unwrap(),
+            json!({"temperature": 23.0})
+        );
+    }
+
+    #[test]
+    fn telemetry_metadata_are_forgotten_once_the_entity_is_deregistered() {
+        let mut converter = create_test_converter(false);
+
+        let meta_message = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child///m/env/meta"),
+            r#"{"units": {"temperature": "°C"}}"#,
+        )
+        .with_retain();
+        assert!(converter.convert(&meta_message).unwrap().is_empty());
+
+        let deregistration =
+            MqttMessage::new(&Topic::new_unchecked("te/device/child//"), "").with_retain();
+        assert!(converter.convert(&deregistration).unwrap().is_empty());
+
+        let measurement = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child///m/env"),
+            r#"{"temperature": 23.0}"#,
+        );
+        let output = converter.convert(&measurement).unwrap();
+        assert_json_eq!(
+            serde_json::from_str::<serde_json::Value>(&extract_first_message_payload(output))
+                .unwrap(),
+            json!({"temperature": 23.0})
+        );
+    }
+
     #[test]
     fn skip_converting_bridge_health_status() {
         let mut converter = create_test_converter(false);
@@ -503,6 +832,212 @@ mod tests {
         assert_eq!(res[0], expected_msg);
     }
 
+    #[test]
+    fn converting_twin_data_into_reported_properties() {
+        let mut converter = create_test_converter(false);
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("te/device/main///twin/firmware"),
+                r#"{"version":"1.2.3"}"#,
+            ))
+            .unwrap();
+        assert_eq!(
+            output,
+            vec![MqttMessage::new(
+                &Topic::new_unchecked("az/twin/PATCH/properties/reported/?$rid=1"),
+                r#"{"firmware":{"version":"1.2.3"}}"#
+            )]
+        );
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("te/device/main///twin/firmware"),
+                "",
+            ))
+            .unwrap();
+        assert_eq!(output[0].payload_str().unwrap(), r#"{"firmware":null}"#);
+    }
+
+    #[test]
+    fn twin_data_of_child_devices_are_not_reported() {
+        let mut converter = create_test_converter(false);
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("te/device/child///twin/firmware"),
+                r#"{"version":"1.2.3"}"#,
+            ))
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn converting_desired_properties_into_twin_data() {
+        let mut converter = create_test_converter(false);
+
+        let init = converter.init_messages().unwrap();
+        assert_eq!(init[0].topic.name, "az/twin/GET/?$rid=1");
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("az/twin/res/200/?$rid=1"),
+                r#"{"desired":{"interval":5,"$version":2},"reported":{"$version":1}}"#,
+            ))
+            .unwrap();
+        assert_eq!(
+            output,
+            vec![
+                MqttMessage::new(&Topic::new_unchecked("te/device/main///twin/interval"), "5")
+                    .with_retain()
+            ]
+        );
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("az/twin/PATCH/properties/desired/?$version=3"),
+                r#"{"interval":10,"$version":3}"#,
+            ))
+            .unwrap();
+        assert_eq!(
+            output,
+            vec![MqttMessage::new(
+                &Topic::new_unchecked("te/device/main///twin/interval"),
+                "10"
+            )
+            .with_retain()]
+        );
+    }
+
+    #[test]
+    fn failed_twin_request_is_reported_as_an_error() {
+        let mut converter = create_test_converter(false);
+
+        let result = converter.try_convert(&MqttMessage::new(
+            &Topic::new_unchecked("az/twin/res/400/?$rid=4"),
+            "",
+        ));
+        assert_matches!(
+            result,
+            Err(ConversionError::TwinRequestFailed { status: 400, .. })
+        );
+    }
+
+    #[test]
+    fn converting_direct_method_into_command() {
+        let mut converter = create_test_converter(false);
+        let output = converter
+            .convert(
+                &MqttMessage::new(&Topic::new_unchecked("te/device/main///cmd/restart"), "{}")
+                    .with_retain(),
+            )
+            .unwrap();
+        assert!(output.is_empty());
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("az/methods/POST/restart/?$rid=42"),
+                r#"{"delay":5}"#,
+            ))
+            .unwrap();
+        assert_eq!(
+            output[0].topic.name,
+            "te/device/main///cmd/restart/az-mapper-42"
+        );
+        assert!(output[0].retain);
+        assert_json_eq!(
+            serde_json::from_str::<Value>(output[0].payload_str().unwrap()).unwrap(),
+            json!({"status": "init", "delay": 5})
+        );
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("te/device/main///cmd/restart/az-mapper-42"),
+                r#"{"status":"executing","delay":5}"#,
+            ))
+            .unwrap();
+        assert!(output.is_empty());
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("te/device/main///cmd/restart/az-mapper-42"),
+                r#"{"status":"successful","delay":5}"#,
+            ))
+            .unwrap();
+        assert_eq!(
+            output,
+            vec![
+                MqttMessage::new(
+                    &Topic::new_unchecked("az/methods/res/200/?$rid=42"),
+                    r#"{"delay":5,"status":"successful"}"#
+                ),
+                MqttMessage::new(
+                    &Topic::new_unchecked("te/device/main///cmd/restart/az-mapper-42"),
+                    ""
+                )
+                .with_retain(),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_direct_methods_are_rejected() {
+        let mut converter = create_test_converter(false);
+        let restart_capability = Topic::new_unchecked("te/device/main///cmd/restart");
+        converter
+            .convert(&MqttMessage::new(&restart_capability, "{}").with_retain())
+            .unwrap();
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("az/methods/POST/reboot/?$rid=7"),
+                "{}",
+            ))
+            .unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].topic.name, "az/methods/res/404/?$rid=7");
+
+        // Operations registered by a child device cannot be invoked on the main device
+        converter
+            .convert(
+                &MqttMessage::new(&Topic::new_unchecked("te/device/child///cmd/reboot"), "{}")
+                    .with_retain(),
+            )
+            .unwrap();
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("az/methods/POST/reboot/?$rid=8"),
+                "{}",
+            ))
+            .unwrap();
+        assert_eq!(output[0].topic.name, "az/methods/res/404/?$rid=8");
+
+        // A method is no more supported once deregistered
+        converter
+            .convert(&MqttMessage::new(&restart_capability, "").with_retain())
+            .unwrap();
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("az/methods/POST/restart/?$rid=9"),
+                "{}",
+            ))
+            .unwrap();
+        assert_eq!(output[0].topic.name, "az/methods/res/404/?$rid=9");
+    }
+
+    #[test]
+    fn commands_not_triggered_by_a_direct_method_are_ignored() {
+        let mut converter = create_test_converter(false);
+
+        let output = converter
+            .convert(&MqttMessage::new(
+                &Topic::new_unchecked("te/device/main///cmd/restart/c8y-mapper-42"),
+                r#"{"status":"successful"}"#,
+            ))
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
     fn create_test_converter(add_timestamp: bool) -> AzureConverter {
         AzureConverter::new(
             add_timestamp,
@@ -9,6 +9,9 @@ pub enum ConversionError {
         threshold: usize,
     },
 
+    #[error("Azure device twin request {request_id} failed with status {status}")]
+    TwinRequestFailed { request_id: String, status: u16 },
+
     #[error(transparent)]
     MqttError(#[from] MqttError),
 
@@ -1,3 +1,5 @@
 pub mod converter;
 pub mod error;
+pub mod methods;
 pub mod size_threshold;
+pub mod twin;
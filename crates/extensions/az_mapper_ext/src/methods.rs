@@ -0,0 +1,155 @@
+//! Mapping of Azure IoT Hub direct methods to thin-edge commands
+//!
+//! - A direct method request received on `<prefix>/methods/POST/<method>/?$rid=<request-id>`
+//!   is published as a `te/device/main///cmd/<method>/<prefix>-mapper-<request-id>` command,
+//!   the method payload being used as the command init payload.
+//! - Only the operations registered by the main device on `te/device/main///cmd/<operation>` can be invoked,
+//!   a request for any other method being rejected with a 404 response.
+//! - When this command reaches a final state, `successful` or `failed`,
+//!   the command payload is sent back as the method response on `<prefix>/methods/res/<status>/?$rid=<request-id>`,
+//!   and the command is cleared.
+use crate::twin::query_param;
+use serde_json::json;
+use serde_json::Value;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::IdGenerator;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_config::models::TopicPrefix;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::Topic;
+
+/// A direct method request, received on `<prefix>/methods/POST/<method>/?$rid=<request-id>`
+#[derive(Debug, Eq, PartialEq)]
+pub struct MethodRequest<'a> {
+    pub method: &'a str,
+    pub request_id: &'a str,
+}
+
+impl<'a> MethodRequest<'a> {
+    /// Parse the topic suffix of a method request, i.e. the topic stripped from `<prefix>/methods/POST/`
+    pub fn parse(topic_suffix: &'a str) -> Option<Self> {
+        let (method, params) = topic_suffix.split_once("/?")?;
+        let request_id = query_param(params, "$rid")?;
+        if method.is_empty() || method.contains('/') {
+            return None;
+        }
+        Some(MethodRequest { method, request_id })
+    }
+
+    /// Translate the method request into a thin-edge command for the main device
+    ///
+    /// If the method payload is not a JSON object, the request is rejected with a 400 response.
+    pub fn into_command(
+        self,
+        prefix: &TopicPrefix,
+        mqtt_schema: &MqttSchema,
+        command_id: &IdGenerator,
+        payload: &[u8],
+    ) -> MqttMessage {
+        let mut init_payload = match serde_json::from_slice(payload) {
+            Ok(Value::Object(init_payload)) => init_payload,
+            Ok(Value::Null) => Default::default(),
+            Err(_) if payload.is_empty() => Default::default(),
+            _ => {
+                let reason = format!(
+                    "The payload of the {} method must be a JSON object",
+                    self.method
+                );
+                return method_response(prefix, 400, self.request_id, &json!({"reason": reason}));
+            }
+        };
+        init_payload.insert("status".to_string(), "init".into());
+
+        let channel = Channel::Command {
+            operation: OperationType::from(self.method),
+            cmd_id: command_id.new_id_with_str(self.request_id),
+        };
+        let topic = mqtt_schema.topic_for(&EntityTopicId::default_main_device(), &channel);
+        MqttMessage::new(&topic, Value::Object(init_payload).to_string()).with_retain()
+    }
+}
+
+impl MethodRequest<'_> {
+    /// The response to a request for a method that is not supported by the device
+    pub fn unknown_method(&self, prefix: &TopicPrefix) -> MqttMessage {
+        let reason = format!("Unknown method: {}", self.method);
+        method_response(prefix, 404, self.request_id, &json!({"reason": reason}))
+    }
+}
+
+/// Translate the state of a command triggered by a direct method into a method response
+///
+/// Return `None` if the command is not in a final state.
+/// Otherwise, return the method response along with the message clearing the command.
+pub fn command_response(
+    prefix: &TopicPrefix,
+    request_id: &str,
+    command: &MqttMessage,
+) -> Option<Vec<MqttMessage>> {
+    let payload: Value = serde_json::from_slice(command.payload_bytes()).ok()?;
+    let status = match payload.get("status")?.as_str()? {
+        "successful" => 200,
+        "failed" => 500,
+        _ => return None,
+    };
+
+    let response = method_response(prefix, status, request_id, &payload);
+    let clear_command = MqttMessage::new(&command.topic, "").with_retain();
+    Some(vec![response, clear_command])
+}
+
+fn method_response(
+    prefix: &TopicPrefix,
+    status: u16,
+    request_id: &str,
+    payload: &Value,
+) -> MqttMessage {
+    let topic = Topic::new_unchecked(&format!("{prefix}/methods/res/{status}/?$rid={request_id}"));
+    MqttMessage::new(&topic, payload.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefix() -> TopicPrefix {
+        TopicPrefix::try_from("az").unwrap()
+    }
+
+    #[test]
+    fn parse_method_requests() {
+        assert_eq!(
+            MethodRequest::parse("restart/?$rid=1"),
+            Some(MethodRequest {
+                method: "restart",
+                request_id: "1"
+            })
+        );
+        assert_eq!(MethodRequest::parse("restart"), None);
+        assert_eq!(MethodRequest::parse("a/b/?$rid=1"), None);
+    }
+
+    #[test]
+    fn reject_method_with_non_object_payload() {
+        let request = MethodRequest::parse("restart/?$rid=3").unwrap();
+        let message = request.into_command(
+            &prefix(),
+            &MqttSchema::default(),
+            &IdGenerator::new("az-mapper"),
+            b"[1,2,3]",
+        );
+
+        assert_eq!(message.topic.name, "az/methods/res/400/?$rid=3");
+    }
+
+    #[test]
+    fn ignore_command_not_in_final_state() {
+        let command = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/restart/az-mapper-3"),
+            r#"{"status":"executing"}"#,
+        );
+        assert_eq!(command_response(&prefix(), "3", &command), None);
+    }
+}
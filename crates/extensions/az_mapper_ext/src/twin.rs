@@ -0,0 +1,151 @@
+//! Mapping of the thin-edge twin data to and from the Azure IoT Hub device twin
+//!
+//! - `te/device/main///twin/<fragment>` messages are published as reported properties,
+//!   on `<prefix>/twin/PATCH/properties/reported/?$rid=<request-id>`.
+//! - Desired properties, received either as a patch on `<prefix>/twin/PATCH/properties/desired/?$version=<version>`
+//!   or along the whole twin document on `<prefix>/twin/res/200/?$rid=<request-id>`,
+//!   are published as retained `te/device/main///twin/<property>` messages.
+use crate::error::ConversionError;
+use serde_json::Map;
+use serde_json::Value;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::models::TopicPrefix;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::Topic;
+
+/// Build the request to get the whole device twin
+pub fn get_twin_request(prefix: &TopicPrefix, request_id: &str) -> MqttMessage {
+    let topic = Topic::new_unchecked(&format!("{prefix}/twin/GET/?$rid={request_id}"));
+    MqttMessage::new(&topic, "")
+}
+
+/// Build the reported property patch for a twin fragment
+///
+/// An empty payload, used to clear a twin fragment, removes the reported property.
+pub fn reported_property_patch(
+    prefix: &TopicPrefix,
+    request_id: &str,
+    fragment_key: &str,
+    input: &MqttMessage,
+) -> Result<MqttMessage, ConversionError> {
+    let value: Value = if input.payload_bytes().is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(input.payload_bytes())?
+    };
+
+    let mut patch = Map::new();
+    patch.insert(fragment_key.to_string(), value);
+    let topic = Topic::new_unchecked(&format!(
+        "{prefix}/twin/PATCH/properties/reported/?$rid={request_id}"
+    ));
+    Ok(MqttMessage::new(&topic, Value::Object(patch).to_string()))
+}
+
+/// Translate desired properties into retained twin messages of the main device
+///
+/// The Azure metadata, i.e. the properties starting with a `$` such as `$version`, are ignored.
+/// A property set to `null` is removed, clearing the retained twin message.
+pub fn desired_properties(mqtt_schema: &MqttSchema, desired: &Value) -> Vec<MqttMessage> {
+    let Some(properties) = desired.as_object() else {
+        return vec![];
+    };
+
+    let main_device = EntityTopicId::default_main_device();
+    properties
+        .iter()
+        .filter(|(key, _)| !key.starts_with('$'))
+        .map(|(key, value)| {
+            let channel = Channel::EntityTwinData {
+                fragment_key: key.to_string(),
+            };
+            let topic = mqtt_schema.topic_for(&main_device, &channel);
+            let payload = match value {
+                Value::Null => String::new(),
+                value => value.to_string(),
+            };
+            MqttMessage::new(&topic, payload).with_retain()
+        })
+        .collect()
+}
+
+/// A response from the device twin service, received on `<prefix>/twin/res/<status>/?$rid=<request-id>`
+#[derive(Debug, Eq, PartialEq)]
+pub struct TwinResponse<'a> {
+    pub status: u16,
+    pub request_id: &'a str,
+}
+
+impl<'a> TwinResponse<'a> {
+    /// Parse the topic suffix of a twin response, i.e. the topic stripped from `<prefix>/twin/res/`
+    pub fn parse(topic_suffix: &'a str) -> Option<Self> {
+        let (status, params) = topic_suffix.split_once("/?")?;
+        let status = status.parse().ok()?;
+        let request_id = query_param(params, "$rid")?;
+        Some(TwinResponse { status, request_id })
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Extract a parameter from the query string of an IoT Hub topic
+pub(crate) fn query_param<'a>(params: &'a str, name: &str) -> Option<&'a str> {
+    params
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_twin_responses() {
+        assert_eq!(
+            TwinResponse::parse("200/?$rid=1"),
+            Some(TwinResponse {
+                status: 200,
+                request_id: "1"
+            })
+        );
+        assert_eq!(
+            TwinResponse::parse("204/?$rid=42&$version=3"),
+            Some(TwinResponse {
+                status: 204,
+                request_id: "42"
+            })
+        );
+        assert_eq!(TwinResponse::parse("200"), None);
+        assert_eq!(TwinResponse::parse("ok/?$rid=1"), None);
+    }
+
+    #[test]
+    fn desired_properties_are_published_as_retained_twin_messages() {
+        let desired = json!({
+            "config": { "interval": 5 },
+            "location": null,
+            "$version": 7,
+        });
+        let messages = desired_properties(&MqttSchema::default(), &desired);
+
+        assert_eq!(
+            messages,
+            vec![
+                MqttMessage::new(
+                    &Topic::new_unchecked("te/device/main///twin/config"),
+                    r#"{"interval":5}"#
+                )
+                .with_retain(),
+                MqttMessage::new(&Topic::new_unchecked("te/device/main///twin/location"), "")
+                    .with_retain(),
+            ]
+        );
+    }
+}
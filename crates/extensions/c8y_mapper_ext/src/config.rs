@@ -12,8 +12,11 @@ use serde_json::Value;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use tedge_api::mqtt_topics::ChannelFilter::AlarmMetadata;
 use tedge_api::mqtt_topics::ChannelFilter::AnyCommand;
 use tedge_api::mqtt_topics::ChannelFilter::AnyCommandMetadata;
+use tedge_api::mqtt_topics::ChannelFilter::EventMetadata;
+use tedge_api::mqtt_topics::ChannelFilter::MeasurementMetadata;
 use tedge_api::mqtt_topics::EntityFilter::AnyEntity;
 use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::IdGenerator;
@@ -194,6 +197,8 @@ impl C8yMapperConfig {
             config_update: c8y_config.enable.config_update,
             firmware_update: c8y_config.enable.firmware_update,
             device_profile: c8y_config.enable.device_profile,
+            shell: c8y_config.enable.shell,
+            file_list: c8y_config.enable.file_list,
         };
         let bridge_config = BridgeConfig {
             c8y_prefix: c8y_config.bridge.topic_prefix.clone(),
@@ -217,6 +222,11 @@ impl C8yMapperConfig {
         topics.add_all(mqtt_schema.topics(AnyEntity, AnyCommand));
         topics.add_all(mqtt_schema.topics(AnyEntity, AnyCommandMetadata));
 
+        // Add telemetry metadata topics
+        topics.add_all(mqtt_schema.topics(AnyEntity, MeasurementMetadata));
+        topics.add_all(mqtt_schema.topics(AnyEntity, EventMetadata));
+        topics.add_all(mqtt_schema.topics(AnyEntity, AlarmMetadata));
+
         // Add user configurable external topic filters
         for topic in c8y_config.topics.0.clone() {
             if topics.add(&topic).is_err() {
@@ -72,12 +72,15 @@ use tedge_api::entity::EntityType;
 use tedge_api::entity_store::EntityRegistrationMessage;
 use tedge_api::event::error::ThinEdgeJsonDeserializerError;
 use tedge_api::event::ThinEdgeEvent;
+use tedge_api::event::ThinEdgeEventData;
+use tedge_api::measurement::split_measurement;
 use tedge_api::mqtt_topics::Channel;
 use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::IdGenerator;
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::mqtt_topics::OperationType;
 use tedge_api::script::ShellScript;
+use tedge_api::telemetry_metadata::TelemetryMetadataCache;
 use tedge_api::workflow::GenericCommandState;
 use tedge_api::CommandLog;
 use tedge_api::DownloadInfo;
@@ -92,6 +95,7 @@ use tedge_uploader_ext::UploadResult;
 use tedge_utils::file::FileError;
 use tedge_utils::size_threshold::SizeThreshold;
 use thiserror::Error;
+use time::OffsetDateTime;
 use tokio::time::Duration;
 use tokio::time::Instant;
 use tracing::debug;
@@ -142,6 +146,7 @@ impl CumulocityConverter {
 
     pub fn new_error_message(&self, error: impl std::error::Error) -> MqttMessage {
         error!("Mapping error: {}", error);
+        tedge_metrics::record_conversion_error("c8y");
         MqttMessage::new(&self.get_mapper_config().errors_topic, error.to_string())
     }
 
@@ -152,6 +157,7 @@ impl CumulocityConverter {
             Ok(messages) => messages,
             Err(error) => {
                 error!("Mapping error: {}", error);
+                tedge_metrics::record_conversion_error("c8y");
                 vec![MqttMessage::new(
                     &self.get_mapper_config().errors_topic,
                     error.to_string(),
@@ -184,6 +190,7 @@ pub struct CumulocityConverter {
     pub service_type: String,
     pub mqtt_schema: MqttSchema,
     pub(crate) entity_cache: EntityCache,
+    telemetry_metadata: TelemetryMetadataCache,
 
     pub command_id: IdGenerator,
     // Keep active command IDs to avoid creation of multiple commands for an operation
@@ -278,6 +285,7 @@ impl CumulocityConverter {
             service_type,
             mqtt_schema: mqtt_schema.clone(),
             entity_cache,
+            telemetry_metadata: TelemetryMetadataCache::default(),
             command_id,
             active_commands: HashMap::new(),
             active_commands_last_cleared: Instant::now(),
@@ -302,8 +310,9 @@ impl CumulocityConverter {
         let (topic_id, channel) = self.mqtt_schema.entity_channel_of(&message.topic).unwrap();
         assert!(channel == Channel::EntityMetadata);
         if message.payload().is_empty() {
-            // Clear cached entity
+            // Clear cached entity along with its telemetry metadata
             self.entity_cache.delete(&topic_id);
+            self.telemetry_metadata.remove_entity(&topic_id);
             return Ok(UpdateOutcome::Deleted);
         }
 
@@ -474,22 +483,59 @@ impl CumulocityConverter {
         let mut mqtt_messages: Vec<MqttMessage> = Vec::new();
 
         if let Some(entity) = self.entity_cache.get(source) {
+            let metadata = self
+                .telemetry_metadata
+                .measurement(source, measurement_type);
+            let payload = input.payload_str()?;
+
             // Need to check if the input Thin Edge JSON is valid before adding a child ID to list
             let c8y_json_payload =
-                json::from_thin_edge_json(input.payload_str()?, entity, measurement_type)?;
+                json::from_thin_edge_json(payload, entity, measurement_type, metadata)?;
 
             if c8y_json_payload.len() < self.size_threshold.0 {
                 mqtt_messages.push(MqttMessage::new(
                     &self.mapper_config.out_topic,
                     c8y_json_payload,
                 ));
-            } else {
-                return Err(ConversionError::TranslatedSizeExceededThreshold {
-                    payload: input.payload_str()?.chars().take(50).collect(),
+                return Ok(mqtt_messages);
+            }
+
+            // Too large to be sent in one message: split the measurement,
+            // making sure all the parts are sent with the same timestamp
+            let mut measurement: serde_json::Map<String, Value> = serde_json::from_str(payload)?;
+            if !measurement.contains_key("time") {
+                let now = OffsetDateTime::now_utc()
+                    .format(&time::format_description::well_known::Rfc3339)?;
+                measurement.insert("time".to_string(), Value::String(now));
+            }
+            let translated_size = |part: &serde_json::Map<String, Value>| {
+                json::from_thin_edge_json(
+                    &Value::Object(part.clone()).to_string(),
+                    entity,
+                    measurement_type,
+                    metadata,
+                )
+                .map_or(usize::MAX, |c8y_json| c8y_json.len())
+            };
+            let parts = split_measurement(&measurement, self.size_threshold.0 - 1, translated_size)
+                .map_err(|err| ConversionError::TranslatedSizeExceededThreshold {
+                    payload: payload.chars().take(50).collect(),
                     topic: input.topic.name.clone(),
-                    actual_size: c8y_json_payload.len(),
+                    actual_size: err.actual_size,
                     threshold: self.size_threshold.0,
-                });
+                })?;
+
+            for part in parts {
+                let c8y_json_payload = json::from_thin_edge_json(
+                    &Value::Object(part).to_string(),
+                    entity,
+                    measurement_type,
+                    metadata,
+                )?;
+                mqtt_messages.push(MqttMessage::new(
+                    &self.mapper_config.out_topic,
+                    c8y_json_payload,
+                ));
             }
         }
         Ok(mqtt_messages)
@@ -517,7 +563,7 @@ impl CumulocityConverter {
                 }
             })?;
 
-            let tedge_event = ThinEdgeEvent::try_from(
+            let mut tedge_event = ThinEdgeEvent::try_from(
                 event_type,
                 &entity.metadata.r#type,
                 &entity.external_id,
@@ -531,6 +577,19 @@ impl CumulocityConverter {
                 },
             )?;
 
+            if let Some(default_text) = self
+                .telemetry_metadata
+                .event(source, event_type)
+                .and_then(|metadata| metadata.text.as_ref())
+            {
+                let event_data = tedge_event.data.get_or_insert_with(|| ThinEdgeEventData {
+                    text: None,
+                    time: None,
+                    extras: HashMap::new(),
+                });
+                event_data.text.get_or_insert_with(|| default_text.clone());
+            }
+
             let c8y_event = C8yCreateEvent::from(tedge_event);
 
             // If the message doesn't contain any fields other than `text` and `time`, convert to SmartREST
@@ -577,11 +636,25 @@ impl CumulocityConverter {
         self.size_threshold.validate(input)?;
         let entity = self.entity_cache.try_get(source)?;
 
+        // Complete the alarm with the default severity and text declared for its type.
+        // Invalid payloads are left untouched, to be rejected by the alarm converter.
+        let with_defaults = self
+            .telemetry_metadata
+            .alarm(source, alarm_type)
+            .filter(|_| !input.payload_bytes().is_empty())
+            .and_then(|metadata| {
+                let mut payload: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_slice(input.payload_bytes()).ok()?;
+                metadata.apply_defaults(&mut payload);
+                let payload = serde_json::to_string(&payload).ok()?;
+                Some(MqttMessage::new(&input.topic, payload).with_retain_flag(input.retain))
+            });
+
         let mqtt_messages = self.alarm_converter.try_convert_alarm(
             source,
             &entity.external_id,
             &entity.metadata.r#type,
-            input,
+            with_defaults.as_ref().unwrap_or(input),
             alarm_type,
             &self.config.bridge_config.c8y_prefix,
         )?;
@@ -716,6 +789,24 @@ impl CumulocityConverter {
                     vec![]
                 }
             }
+            C8yDeviceControlOperation::Command(request) => {
+                if self.config.capabilities.shell {
+                    self.convert_shell_request(device_xid, cmd_id, request)?
+                } else {
+                    warn!("Received a c8y_Command operation, however, shell feature is disabled");
+                    vec![]
+                }
+            }
+            C8yDeviceControlOperation::FileList(request) => {
+                if self.config.capabilities.file_list {
+                    self.convert_file_list_request(device_xid, cmd_id, request)?
+                } else {
+                    warn!(
+                        "Received a c8y_FileList operation, however, file_list feature is disabled"
+                    );
+                    vec![]
+                }
+            }
             C8yDeviceControlOperation::Custom => {
                 return self
                     .process_json_custom_operation(
@@ -1318,6 +1409,8 @@ impl CumulocityConverter {
                     OperationType::DeviceProfile => {
                         self.register_device_profile_operation(&source).await
                     }
+                    OperationType::Shell => self.register_shell_operation(&source).await,
+                    OperationType::FileList => self.register_file_list_operation(&source).await,
                     OperationType::Custom(command_name) => {
                         self.register_custom_operation(&source, command_name).await
                     }
@@ -1345,6 +1438,14 @@ impl CumulocityConverter {
 
             Channel::Health => self.process_health_status_message(&source, message).await,
 
+            Channel::MeasurementMetadata { .. }
+            | Channel::EventMetadata { .. }
+            | Channel::AlarmMetadata { .. } => {
+                self.telemetry_metadata
+                    .update(&source, &channel, message.payload_str()?)?;
+                Ok(vec![])
+            }
+
             _ => Ok(vec![]),
         }
     }
@@ -2180,6 +2281,143 @@ pub(crate) mod tests {
         assert_eq!(out_first_messages, vec![expected_c8y_json_message.clone()]);
     }
 
+    #[tokio::test]
+    async fn convert_measurement_with_units_declared_by_metadata() {
+        let tmp_dir = TempTedgeDir::new();
+        let (mut converter, _http_proxy) = create_c8y_converter(&tmp_dir);
+
+        let meta_topic = "te/device/main///m/environment/meta";
+        let meta_payload = r#"{"units": {"temp": "°C", "battery": {"voltage": "V"}}}"#;
+        let meta_message =
+            MqttMessage::new(&Topic::new_unchecked(meta_topic), meta_payload).with_retain();
+
+        let in_topic = "te/device/main///m/environment";
+        let in_payload = r#"{"temp": 1, "battery": {"voltage": 3.7}, "time": "2021-11-16T17:45:40.571760714+01:00"}"#;
+        let in_message = MqttMessage::new(&Topic::new_unchecked(in_topic), in_payload);
+
+        register_source_entities(in_topic, &mut converter).await;
+        assert!(converter.convert(&meta_message).await.is_empty());
+
+        let expected_c8y_json_message = MqttMessage::new(
+            &Topic::new_unchecked("c8y/measurement/measurements/create"),
+            r#"{"temp":{"temp":{"value":1.0,"unit":"°C"}},"battery":{"voltage":{"value":3.7,"unit":"V"}},"time":"2021-11-16T17:45:40.571760714+01:00","type":"environment"}"#,
+        );
+        let out_messages: Vec<_> = converter
+            .convert(&in_message)
+            .await
+            .into_iter()
+            .filter(|m| m.topic.name.starts_with("c8y"))
+            .collect();
+        assert_eq!(out_messages, vec![expected_c8y_json_message]);
+
+        // Once the metadata are cleared, no units are added
+        let clear_message = MqttMessage::new(&Topic::new_unchecked(meta_topic), "").with_retain();
+        assert!(converter.convert(&clear_message).await.is_empty());
+
+        let out_messages: Vec<_> = converter
+            .convert(&in_message)
+            .await
+            .into_iter()
+            .filter(|m| m.topic.name.starts_with("c8y"))
+            .collect();
+        assert_eq!(
+            out_messages[0].payload_str().unwrap(),
+            r#"{"temp":{"temp":{"value":1.0}},"battery":{"voltage":{"value":3.7}},"time":"2021-11-16T17:45:40.571760714+01:00","type":"environment"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn telemetry_metadata_are_forgotten_once_the_entity_is_deregistered() {
+        let tmp_dir = TempTedgeDir::new();
+        let (mut converter, _http_proxy) = create_c8y_converter(&tmp_dir);
+
+        let in_topic = "te/device/child1///m/environment";
+        register_source_entities(in_topic, &mut converter).await;
+
+        let meta_message = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child1///m/environment/meta"),
+            r#"{"units": {"temp": "°C"}}"#,
+        )
+        .with_retain();
+        assert!(converter.convert(&meta_message).await.is_empty());
+
+        let child = EntityTopicId::default_child_device("child1").unwrap();
+        assert!(converter
+            .telemetry_metadata
+            .measurement(&child, "environment")
+            .is_some());
+
+        let deregistration =
+            MqttMessage::new(&Topic::new_unchecked("te/device/child1//"), "").with_retain();
+        converter
+            .process_entity_metadata_message(&deregistration)
+            .await
+            .unwrap();
+        assert!(converter
+            .telemetry_metadata
+            .measurement(&child, "environment")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn convert_event_with_default_text_declared_by_metadata() {
+        let tmp_dir = TempTedgeDir::new();
+        let (mut converter, _http_proxy) = create_c8y_converter(&tmp_dir);
+
+        let meta_topic = "te/device/main///e/door/meta";
+        let meta_payload = r#"{"text": "Door opened"}"#;
+        let meta_message =
+            MqttMessage::new(&Topic::new_unchecked(meta_topic), meta_payload).with_retain();
+        assert!(converter.convert(&meta_message).await.is_empty());
+
+        let event_topic = "te/device/main///e/door";
+        let event_payload = r#"{ "time": "2020-02-02T01:02:03+05:30" }"#;
+        let event_message = MqttMessage::new(&Topic::new_unchecked(event_topic), event_payload);
+
+        let converted_events = converter.convert(&event_message).await;
+        assert_eq!(converted_events.len(), 1);
+        assert_eq!(
+            converted_events[0].payload_str().unwrap(),
+            r#"400,door,"Door opened",2020-02-02T01:02:03+05:30"#
+        );
+
+        // The text given by the event itself takes precedence
+        let event_payload =
+            r#"{ "text": "Back door opened", "time": "2020-02-02T01:02:03+05:30" }"#;
+        let event_message = MqttMessage::new(&Topic::new_unchecked(event_topic), event_payload);
+
+        let converted_events = converter.convert(&event_message).await;
+        assert_eq!(
+            converted_events[0].payload_str().unwrap(),
+            r#"400,door,"Back door opened",2020-02-02T01:02:03+05:30"#
+        );
+    }
+
+    #[tokio::test]
+    async fn convert_alarm_with_defaults_declared_by_metadata() {
+        let tmp_dir = TempTedgeDir::new();
+        let (mut converter, _http_proxy) = create_c8y_converter(&tmp_dir);
+        converter.sync_messages();
+
+        let meta_topic = "te/device/main///a/temperature_high/meta";
+        let meta_payload = r#"{"severity": "major", "text": "Temperature is too high"}"#;
+        let meta_message =
+            MqttMessage::new(&Topic::new_unchecked(meta_topic), meta_payload).with_retain();
+        assert!(converter.convert(&meta_message).await.is_empty());
+
+        let alarm_topic = "te/device/main///a/temperature_high";
+        let alarm_payload = r#"{ "time": "2020-02-02T01:02:03+05:30" }"#;
+        let alarm_message =
+            MqttMessage::new(&Topic::new_unchecked(alarm_topic), alarm_payload).with_retain();
+
+        let converted_alarms = converter.convert(&alarm_message).await;
+        assert_eq!(converted_alarms[0].topic.name, "c8y/s/us");
+        assert_eq!(
+            converted_alarms[0].payload_str().unwrap(),
+            "302,temperature_high,Temperature is too high,2020-02-02T01:02:03+05:30"
+        );
+    }
+
     #[tokio::test]
     async fn convert_measurement_with_main_id_with_measurement_type_in_payload() {
         let tmp_dir = TempTedgeDir::new();
@@ -2454,9 +2692,28 @@ pub(crate) mod tests {
         );
         let result = converter.convert(&big_measurement_message).await;
 
+        // The measurement is split into several messages, all under the threshold
+        assert!(result.len() > 1);
+        assert_split_measurement(&result, 10 * 1024 / 16);
+    }
+
+    #[tokio::test]
+    async fn test_convert_measurement_with_oversized_series() {
+        let tmp_dir = TempTedgeDir::new();
+        let (mut converter, _http_proxy) = create_c8y_converter(&tmp_dir);
+        let measurement_topic = "te/device/main///m/";
+        let series_name = "x".repeat(10 * 1024); // A single series > size_threshold after converting to c8y json
+        let measurement_payload = json!({"temperature": 25, series_name: 42}).to_string();
+
+        let measurement_message = MqttMessage::new(
+            &Topic::new_unchecked(measurement_topic),
+            measurement_payload,
+        );
+        let result = converter.convert(&measurement_message).await;
+
         let payload = result[0].payload_str().unwrap();
         assert!(payload.contains(
-            r#"The payload {"temperature0":0,"temperature1":1,"temperature10" received on te/device/main///m/ after translation is"#
+            r#"The payload {"temperature":25,"xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx received on te/device/main///m/ after translation is"#
         ));
         assert!(payload.ends_with("greater than the threshold size of 16184."));
     }
@@ -2501,12 +2758,39 @@ pub(crate) mod tests {
 
         let result = converter.convert(&big_measurement_message).await;
 
-        // Skipping the first two auto-registration messages and validating the third mapped message
-        let payload = result[0].payload_str().unwrap();
-        assert!(payload.contains(
-            r#"The payload {"temperature0":0,"temperature1":1,"temperature10" received on te/device/child1///m/ after translation is"#
-        ));
-        assert!(payload.ends_with("greater than the threshold size of 16184."));
+        assert!(result.len() > 1);
+        assert_split_measurement(&result, 10 * 1024 / 16);
+        for message in result {
+            let c8y_json: Value = serde_json::from_str(message.payload_str().unwrap()).unwrap();
+            assert_eq!(
+                c8y_json["externalSource"]["externalId"],
+                "test-device:device:child1"
+            );
+        }
+    }
+
+    /// Check that a measurement has been split into messages sharing the same time and type,
+    /// each under the size threshold, with no series lost
+    fn assert_split_measurement(messages: &[MqttMessage], series_count: usize) {
+        let mut time = None;
+        let mut series = 0;
+        for message in messages {
+            assert_eq!(message.topic.name, "c8y/measurement/measurements/create");
+            assert!(message.payload_bytes().len() < 16184);
+
+            let c8y_json: Value = serde_json::from_str(message.payload_str().unwrap()).unwrap();
+            let c8y_json = c8y_json.as_object().unwrap();
+            assert_eq!(c8y_json["type"], "ThinEdgeMeasurement");
+            match &time {
+                None => time = Some(c8y_json["time"].clone()),
+                Some(time) => assert_eq!(&c8y_json["time"], time),
+            }
+            series += c8y_json
+                .keys()
+                .filter(|key| key.starts_with("temperature"))
+                .count();
+        }
+        assert_eq!(series, series_count);
     }
 
     #[tokio::test]
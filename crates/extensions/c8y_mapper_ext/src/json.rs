@@ -13,7 +13,7 @@
 //!        "pressure": 220
 //!     }"#;
 //! let entity = CloudEntityMetadata::new("foo".into(), EntityMetadata::main_device(None));
-//! let output = from_thin_edge_json(single_value_thin_edge_json, &entity,"");
+//! let output = from_thin_edge_json(single_value_thin_edge_json, &entity, "", None);
 //! ```
 
 use crate::entity_cache::CloudEntityMetadata;
@@ -21,6 +21,7 @@ use crate::serializer;
 use clock::Clock;
 use clock::WallClock;
 use tedge_api::measurement::*;
+use tedge_api::telemetry_metadata::MeasurementMetadata;
 use time::OffsetDateTime;
 use time::{self};
 
@@ -34,13 +35,16 @@ pub enum CumulocityJsonError {
 }
 
 /// Converts from thin-edge measurement JSON to C8Y measurement JSON
+///
+/// The units declared by the measurement type metadata, if any, are added to the series.
 pub fn from_thin_edge_json(
     input: &str,
     entity: &CloudEntityMetadata,
     m_type: &str,
+    metadata: Option<&MeasurementMetadata>,
 ) -> Result<String, CumulocityJsonError> {
     let timestamp = WallClock.now();
-    let c8y_vec = from_thin_edge_json_with_timestamp(input, timestamp, entity, m_type)?;
+    let c8y_vec = from_thin_edge_json_with_timestamp(input, timestamp, entity, m_type, metadata)?;
     Ok(c8y_vec)
 }
 
@@ -49,8 +53,10 @@ fn from_thin_edge_json_with_timestamp(
     timestamp: OffsetDateTime,
     entity: &CloudEntityMetadata,
     m_type: &str,
+    metadata: Option<&MeasurementMetadata>,
 ) -> Result<String, CumulocityJsonError> {
-    let mut serializer = serializer::C8yJsonSerializer::new(timestamp, entity, m_type);
+    let mut serializer =
+        serializer::C8yJsonSerializer::new(timestamp, entity, m_type).with_metadata(metadata);
     parse_str(input, &mut serializer)?;
     Ok(serializer.into_string()?)
 }
@@ -77,8 +83,13 @@ mod tests {
         let timestamp = datetime!(2021-04-08 0:00:0 +05:00);
 
         let entity = CloudEntityMetadata::new("foo".into(), EntityMetadata::main_device(None));
-        let output =
-            from_thin_edge_json_with_timestamp(single_value_thin_edge_json, timestamp, &entity, "");
+        let output = from_thin_edge_json_with_timestamp(
+            single_value_thin_edge_json,
+            timestamp,
+            &entity,
+            "",
+            None,
+        );
 
         let expected_output = json!({
             "time": timestamp
@@ -114,8 +125,13 @@ mod tests {
         let timestamp = datetime!(2021-04-08 0:00:0 +05:00);
 
         let entity = CloudEntityMetadata::new("foo".into(), EntityMetadata::main_device(None));
-        let output =
-            from_thin_edge_json_with_timestamp(single_value_thin_edge_json, timestamp, &entity, "");
+        let output = from_thin_edge_json_with_timestamp(
+            single_value_thin_edge_json,
+            timestamp,
+            &entity,
+            "",
+            None,
+        );
 
         let expected_output = json!({
             "time": timestamp
@@ -160,7 +176,7 @@ mod tests {
                   }"#;
 
         let entity = CloudEntityMetadata::new("foo".into(), EntityMetadata::main_device(None));
-        let output = from_thin_edge_json(single_value_thin_edge_json, &entity, "");
+        let output = from_thin_edge_json(single_value_thin_edge_json, &entity, "", None);
 
         assert_eq!(
             expected_output.split_whitespace().collect::<String>(),
@@ -183,8 +199,13 @@ mod tests {
         let timestamp = datetime!(2021-04-08 0:00:0 +05:00);
 
         let entity = CloudEntityMetadata::new("foo".into(), EntityMetadata::main_device(None));
-        let output =
-            from_thin_edge_json_with_timestamp(multi_value_thin_edge_json, timestamp, &entity, "");
+        let output = from_thin_edge_json_with_timestamp(
+            multi_value_thin_edge_json,
+            timestamp,
+            &entity,
+            "",
+            None,
+        );
 
         let expected_output = json!({
             "time": timestamp
@@ -221,6 +242,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn check_translation_with_declared_units() {
+        let thin_edge_json = r#"{
+            "temperature": 25.0,
+            "battery": {
+                  "voltage": 3.7,
+                  "level": 80
+              }
+        }"#;
+        let metadata: MeasurementMetadata = serde_json::from_value(json!({
+            "units": {
+                "temperature": "°C",
+                "battery": { "voltage": "V" }
+            }
+        }))
+        .unwrap();
+
+        let timestamp = datetime!(2021-04-08 0:00:0 +05:00);
+
+        let entity = CloudEntityMetadata::new("foo".into(), EntityMetadata::main_device(None));
+        let output = from_thin_edge_json_with_timestamp(
+            thin_edge_json,
+            timestamp,
+            &entity,
+            "environment",
+            Some(&metadata),
+        );
+
+        let expected_output = json!({
+            "time": "2021-04-08T00:00:00+05:00",
+            "temperature": {
+                "temperature": {
+                    "value": 25.0,
+                    "unit": "°C"
+                 }
+            },
+            "battery": {
+                "voltage": {
+                   "value": 3.7,
+                   "unit": "V"
+                 },
+                "level": {
+                  "value": 80.0
+                }
+            },
+            "type": "environment"
+        });
+
+        assert_json_eq!(
+            serde_json::from_str::<serde_json::Value>(output.unwrap().as_str()).unwrap(),
+            expected_output
+        );
+    }
+
     #[test]
     fn thin_edge_json_round_tiny_number() {
         let input = r#"{
@@ -239,7 +314,7 @@ mod tests {
         }"#;
 
         let entity = CloudEntityMetadata::new("foo".into(), EntityMetadata::main_device(None));
-        let output = from_thin_edge_json(input, &entity, "");
+        let output = from_thin_edge_json(input, &entity, "", None);
 
         let actual_output = output.unwrap().split_whitespace().collect::<String>();
 
@@ -272,7 +347,7 @@ mod tests {
                 }}"#, time, measurement, measurement);
 
         let entity = CloudEntityMetadata::new("foo".into(), EntityMetadata::main_device(None));
-        let output = from_thin_edge_json(input.as_str(), &entity, "").unwrap();
+        let output = from_thin_edge_json(input.as_str(), &entity, "", None).unwrap();
         assert_eq!(
             expected_output.split_whitespace().collect::<String>(),
             output
@@ -323,7 +398,8 @@ mod tests {
             child_id.into(),
             EntityMetadata::child_device(child_id.to_string()).unwrap(),
         );
-        let output = from_thin_edge_json_with_timestamp(thin_edge_json, timestamp, &entity, "");
+        let output =
+            from_thin_edge_json_with_timestamp(thin_edge_json, timestamp, &entity, "", None);
         assert_json_eq!(
             serde_json::from_str::<serde_json::Value>(output.unwrap().as_str()).unwrap(),
             expected_output
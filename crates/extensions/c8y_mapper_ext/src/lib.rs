@@ -25,6 +25,8 @@ pub struct Capabilities {
     pub config_update: bool,
     pub firmware_update: bool,
     pub device_profile: bool,
+    pub shell: bool,
+    pub file_list: bool,
 }
 
 #[cfg(test)]
@@ -36,6 +38,8 @@ impl Default for Capabilities {
             config_update: true,
             firmware_update: true,
             device_profile: true,
+            shell: true,
+            file_list: true,
         }
     }
 }
@@ -1,7 +1,9 @@
 //! Converting Cumulocity Smartrest operation messages into local thin-edge operation messages.
 use crate::supported_operations::operation::Operation;
+use c8y_api::json_c8y_deserializer::C8yCommand;
 use c8y_api::json_c8y_deserializer::C8yDeviceProfile;
 use c8y_api::json_c8y_deserializer::C8yDownloadConfigFile;
+use c8y_api::json_c8y_deserializer::C8yFileList;
 use c8y_api::json_c8y_deserializer::C8yFirmware;
 use c8y_api::json_c8y_deserializer::C8yLogfileRequest;
 use c8y_api::json_c8y_deserializer::C8yUploadConfigFile;
@@ -14,9 +16,11 @@ use tedge_api::commands::CommandStatus;
 use tedge_api::commands::ConfigMetadata;
 use tedge_api::commands::ConfigSnapshotCmdPayload;
 use tedge_api::commands::ConfigUpdateCmdPayload;
+use tedge_api::commands::FileListCmdPayload;
 use tedge_api::commands::FirmwareUpdateCmdPayload;
 use tedge_api::commands::LogMetadata;
 use tedge_api::commands::LogUploadCmdPayload;
+use tedge_api::commands::ShellCmdPayload;
 use tedge_api::device_profile::ConfigPayload;
 use tedge_api::device_profile::DeviceProfileCmdPayload;
 use tedge_api::entity::EntityExternalId;
@@ -101,6 +105,8 @@ impl CumulocityConverter {
             tedge_url: Some(tedge_url),
             config_type: config_upload_request.config_type,
             path: None,
+            sha256: None,
+            signature: None,
             log_path: None,
         };
 
@@ -144,6 +150,10 @@ impl CumulocityConverter {
             date_from: log_request.date_from,
             date_to: log_request.date_to,
             search_text: Some(log_request.search_text).filter(|s| !s.is_empty()),
+            search_regex: log_request.search_regex,
+            ignore_case: log_request.ignore_case,
+            exclude_text: log_request.exclude_text.filter(|s| !s.is_empty()),
+            context_lines: log_request.context_lines,
             lines: log_request.maximum_lines,
             log_path: None,
         };
@@ -224,6 +234,9 @@ impl CumulocityConverter {
             remote_url: firmware_request.url,
             name: firmware_request.name,
             version: firmware_request.version,
+            sha256: None,
+            signature: None,
+            delta: None,
             log_path: None,
         };
 
@@ -297,6 +310,8 @@ impl CumulocityConverter {
             server_url: config_download_request.url.clone(),
             config_type: config_download_request.config_type.clone(),
             path: None,
+            sha256: None,
+            signature: None,
             log_path: None,
         };
 
@@ -419,6 +434,104 @@ impl CumulocityConverter {
         }
     }
 
+    /// Convert c8y_Command JSON over MQTT operation to ThinEdge shell command.
+    pub fn convert_shell_request(
+        &self,
+        device_xid: String,
+        cmd_id: String,
+        command_request: C8yCommand,
+    ) -> Result<Vec<MqttMessage>, CumulocityMapperError> {
+        let entity_xid: EntityExternalId = device_xid.into();
+
+        let target = self.entity_cache.try_get_by_external_id(&entity_xid)?;
+
+        let channel = Channel::Command {
+            operation: OperationType::Shell,
+            cmd_id,
+        };
+        let topic = self
+            .mqtt_schema
+            .topic_for(&target.metadata.topic_id, &channel);
+
+        let request = ShellCmdPayload {
+            status: CommandStatus::Init,
+            command: command_request.text,
+            ..Default::default()
+        };
+
+        // Command messages must be retained
+        Ok(vec![
+            MqttMessage::new(&topic, request.to_json()).with_retain()
+        ])
+    }
+
+    pub async fn register_shell_operation(
+        &mut self,
+        topic_id: &EntityTopicId,
+    ) -> Result<Vec<MqttMessage>, ConversionError> {
+        if !self.config.capabilities.shell {
+            warn!("Received shell metadata, however, shell feature is disabled");
+            return Ok(vec![]);
+        }
+
+        match self.register_operation(topic_id, "c8y_Command").await {
+            Err(err) => {
+                error!("Failed to register `c8y_Command` operation for {topic_id} due to: {err}");
+                Ok(vec![])
+            }
+            Ok(messages) => Ok(messages),
+        }
+    }
+
+    /// Convert c8y_FileList JSON over MQTT operation to ThinEdge file_list command.
+    pub fn convert_file_list_request(
+        &self,
+        device_xid: String,
+        cmd_id: String,
+        file_list_request: C8yFileList,
+    ) -> Result<Vec<MqttMessage>, CumulocityMapperError> {
+        let entity_xid: EntityExternalId = device_xid.into();
+
+        let target = self.entity_cache.try_get_by_external_id(&entity_xid)?;
+
+        let channel = Channel::Command {
+            operation: OperationType::FileList,
+            cmd_id,
+        };
+        let topic = self
+            .mqtt_schema
+            .topic_for(&target.metadata.topic_id, &channel);
+
+        let request = FileListCmdPayload {
+            status: CommandStatus::Init,
+            path: file_list_request.path,
+            ..Default::default()
+        };
+
+        // Command messages must be retained
+        Ok(vec![
+            MqttMessage::new(&topic, request.to_json()).with_retain()
+        ])
+    }
+
+    pub async fn register_file_list_operation(
+        &mut self,
+        topic_id: &EntityTopicId,
+    ) -> Result<Vec<MqttMessage>, ConversionError> {
+        if !self.config.capabilities.file_list {
+            warn!("Received file_list metadata, however, file_list feature is disabled");
+            return Ok(vec![]);
+        }
+
+        match self.register_operation(topic_id, "c8y_FileList").await {
+            Err(err) => {
+                error!("Failed to register `c8y_FileList` operation for {topic_id} due to: {err}");
+                Ok(vec![])
+            }
+            Ok(messages) => Ok(messages),
+        }
+    }
+
     pub fn convert_custom_operation_request(
         &self,
         device_xid: String,
@@ -252,6 +252,20 @@ impl OperationHandler {
             ]);
         }
 
+        if capabilities.shell {
+            topics.extend([
+                (AnyEntity, Command(OperationType::Shell)),
+                (AnyEntity, CommandMetadata(OperationType::Shell)),
+            ]);
+        }
+
+        if capabilities.file_list {
+            topics.extend([
+                (AnyEntity, Command(OperationType::FileList)),
+                (AnyEntity, CommandMetadata(OperationType::FileList)),
+            ]);
+        }
+
         topics
     }
 }
@@ -0,0 +1,190 @@
+use super::error::OperationError;
+use super::EntityTarget;
+use super::OperationContext;
+use super::OperationOutcome;
+use anyhow::Context;
+use c8y_api::smartrest::smartrest_serializer::CumulocitySupportedOperations;
+use c8y_api::smartrest::smartrest_serializer::TextOrCsv;
+use tedge_api::commands::FileEntry;
+use tedge_api::commands::FileListCmd;
+use tedge_api::CommandStatus;
+use tedge_mqtt_ext::MqttMessage;
+use time::format_description::well_known::Rfc3339;
+use tracing::warn;
+
+impl OperationContext {
+    /// Address a received ThinEdge file_list command. If its status is
+    /// - "executing", it converts the message to SmartREST "Executing".
+    /// - "successful", it converts the message to SmartREST "Successful" with the directory listing as result.
+    /// - "failed", it converts the message to SmartREST "Failed".
+    pub async fn handle_file_list_state_change(
+        &self,
+        target: &EntityTarget,
+        cmd_id: &str,
+        message: &MqttMessage,
+    ) -> Result<OperationOutcome, OperationError> {
+        if !self.capabilities.file_list {
+            warn!("Received a file_list command, however, file_list feature is disabled");
+            return Ok(OperationOutcome::Ignored);
+        }
+
+        let command = match FileListCmd::try_from_bytes(
+            target.topic_id.clone(),
+            cmd_id.into(),
+            message.payload_bytes(),
+        )
+        .context("Could not parse command as a file list command")?
+        {
+            Some(command) => command,
+            None => {
+                // The command has been fully processed
+                return Ok(OperationOutcome::Ignored);
+            }
+        };
+
+        let sm_topic = &target.smartrest_publish_topic;
+
+        match command.status() {
+            CommandStatus::Executing => Ok(OperationOutcome::Executing {
+                extra_messages: vec![],
+            }),
+            CommandStatus::Successful => {
+                let listing = command
+                    .payload
+                    .entries
+                    .iter()
+                    .map(format_entry)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let smartrest_operation_status = self
+                    .try_get_smartrest_successful_status_payload_with_args(
+                        CumulocitySupportedOperations::C8yFileList,
+                        cmd_id,
+                        TextOrCsv::Text(listing),
+                    );
+                let c8y_notification = MqttMessage::new(sm_topic, smartrest_operation_status);
+
+                Ok(OperationOutcome::Finished {
+                    messages: vec![c8y_notification],
+                })
+            }
+            CommandStatus::Failed { reason } => Err(anyhow::anyhow!(reason).into()),
+            _ => Ok(OperationOutcome::Ignored),
+        }
+    }
+}
+
+/// Format a directory entry as a line of the listing returned to Cumulocity
+///
+/// e.g. `directory 4096 2024-06-01T10:00:00Z tedge`
+fn format_entry(entry: &FileEntry) -> String {
+    let file_type = serde_json::to_value(entry.file_type)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let modified = entry
+        .modified
+        .and_then(|modified| modified.format(&Rfc3339).ok())
+        .unwrap_or_else(|| "-".to_string());
+    format!("{file_type} {} {modified} {}", entry.size, entry.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+    use c8y_api::json_c8y_deserializer::C8yDeviceControlTopic;
+    use serde_json::json;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::Sender;
+    use tedge_mqtt_ext::test_helpers::assert_received_contains_str;
+    use tedge_mqtt_ext::test_helpers::assert_received_includes_json;
+    use tedge_mqtt_ext::MqttMessage;
+    use tedge_mqtt_ext::Topic;
+    use tedge_test_utils::fs::TempTedgeDir;
+
+    const TEST_TIMEOUT_MS: Duration = Duration::from_millis(3000);
+
+    #[tokio::test]
+    async fn mapper_converts_file_list_op_to_file_list_cmd_for_main_device() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle { mqtt, .. } = test_handle;
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+
+        skip_init_messages(&mut mqtt).await;
+
+        // Simulate c8y_FileList operation delivered via JSON over MQTT
+        mqtt.send(MqttMessage::new(
+            &C8yDeviceControlTopic::topic(&"c8y".try_into().unwrap()),
+            json!({
+                "id": "123456",
+                "c8y_FileList": {
+                    "path": "/var/log"
+                },
+                "externalSource": {
+                    "externalId": "test-device",
+                    "type": "c8y_Serial"
+                }
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        assert_received_includes_json(
+            &mut mqtt,
+            [(
+                "te/device/main///cmd/file_list/+",
+                json!({
+                    "status": "init",
+                    "path": "/var/log"
+                }),
+            )],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn handle_file_list_executing_and_failed_cmd_for_main_device() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle { mqtt, .. } = test_handle;
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+
+        skip_init_messages(&mut mqtt).await;
+
+        // Simulate file_list command with "executing" state
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/file_list/c8y-mapper-1234"),
+            json!({
+                "status": "executing",
+                "path": "/etc",
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        assert_received_contains_str(&mut mqtt, [("c8y/s/us", "501,c8y_FileList")]).await;
+
+        // Simulate file_list command with "failed" state
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/file_list/c8y-mapper-1234"),
+            json!({
+                "status": "failed",
+                "path": "/etc",
+                "reason": "Listing /etc is not allowed"
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        assert_received_contains_str(
+            &mut mqtt,
+            [("c8y/s/us", "502,c8y_FileList,Listing /etc is not allowed")],
+        )
+        .await;
+    }
+}
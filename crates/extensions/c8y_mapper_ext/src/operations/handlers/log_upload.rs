@@ -190,6 +190,61 @@ mod tests {
         ).await;
     }
 
+    #[tokio::test]
+    async fn mapper_forwards_logfile_req_search_options_to_log_upload_cmd() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+
+        let TestHandle { mqtt, .. } = test_handle;
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+
+        skip_init_messages(&mut mqtt).await;
+
+        // Simulate c8y_LogfileRequest JSON over MQTT request with search options
+        mqtt.send(MqttMessage::new(
+            &C8yDeviceControlTopic::topic(&"c8y".try_into().unwrap()),
+            json!({
+                "id": "123456",
+                "c8y_LogfileRequest": {
+                    "searchText": "error|warn",
+                    "searchRegex": true,
+                    "ignoreCase": true,
+                    "excludeText": "DEBUG",
+                    "contextLines": 3,
+                    "logFile": "logfileA",
+                    "dateTo": "2023-11-29T16:33:50+0100",
+                    "dateFrom": "2023-11-28T16:33:50+0100",
+                    "maximumLines": 1000
+                },
+                "externalSource": {
+                    "externalId": "test-device",
+                    "type": "c8y_Serial"
+                 }
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        assert_received_includes_json(
+            &mut mqtt,
+            [(
+                "te/device/main///cmd/log_upload/c8y-mapper-123456",
+                json!({
+                    "status": "init",
+                    "type": "logfileA",
+                    "searchText": "error|warn",
+                    "searchRegex": true,
+                    "ignoreCase": true,
+                    "excludeText": "DEBUG",
+                    "contextLines": 3,
+                    "lines": 1000
+                }),
+            )],
+        )
+        .await;
+    }
+
     #[tokio::test]
     async fn mapper_converts_smartrest_logfile_req_to_log_upload_cmd_for_child_device() {
         let ttd = TempTedgeDir::new();
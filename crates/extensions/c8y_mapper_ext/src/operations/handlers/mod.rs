@@ -4,9 +4,11 @@ mod config_snapshot;
 mod config_update;
 mod custom_operation;
 mod device_profile;
+mod file_list;
 mod firmware_update;
 mod log_upload;
 mod restart;
+mod shell;
 mod software_list;
 mod software_update;
 
@@ -148,6 +150,14 @@ impl OperationContext {
                 self.handle_device_profile_state_change(&entity, &cmd_id, &message)
                     .await
             }
+            OperationType::Shell => {
+                self.handle_shell_state_change(&entity, &cmd_id, &message)
+                    .await
+            }
+            OperationType::FileList => {
+                self.handle_file_list_state_change(&entity, &cmd_id, &message)
+                    .await
+            }
             OperationType::Custom(_) => {
                 let (outcome, maybe_c8y_operation) = self
                     .handle_custom_operation_state_change(&entity, &cmd_id, &message)
@@ -333,6 +343,8 @@ fn to_c8y_operation(operation_type: &OperationType) -> Option<CumulocitySupporte
         OperationType::FirmwareUpdate => Some(CumulocitySupportedOperations::C8yFirmware),
         OperationType::SoftwareUpdate => Some(CumulocitySupportedOperations::C8ySoftwareUpdate),
         OperationType::DeviceProfile => Some(CumulocitySupportedOperations::C8yDeviceProfile),
+        OperationType::Shell => Some(CumulocitySupportedOperations::C8yCommand),
+        OperationType::FileList => Some(CumulocitySupportedOperations::C8yFileList),
         // Cannot convert custom operation name systematically
         OperationType::Custom(_) => None,
         // software list is not an c8y, only a fragment, but is a local operation that is spawned as
@@ -0,0 +1,217 @@
+use super::error::OperationError;
+use super::EntityTarget;
+use super::OperationContext;
+use super::OperationOutcome;
+use anyhow::Context;
+use c8y_api::smartrest::smartrest_serializer::CumulocitySupportedOperations;
+use c8y_api::smartrest::smartrest_serializer::TextOrCsv;
+use tedge_api::commands::ShellCmd;
+use tedge_api::CommandStatus;
+use tedge_mqtt_ext::MqttMessage;
+use tracing::warn;
+
+impl OperationContext {
+    /// Address a received ThinEdge shell command. If its status is
+    /// - "executing", it converts the message to SmartREST "Executing".
+    /// - "successful", it converts the message to SmartREST "Successful" with the command output as result,
+    ///   trimmed so the whole SmartREST message fits the size limit of Cumulocity.
+    /// - "failed", it converts the message to SmartREST "Failed".
+    pub async fn handle_shell_state_change(
+        &self,
+        target: &EntityTarget,
+        cmd_id: &str,
+        message: &MqttMessage,
+    ) -> Result<OperationOutcome, OperationError> {
+        if !self.capabilities.shell {
+            warn!("Received a shell command, however, shell feature is disabled");
+            return Ok(OperationOutcome::Ignored);
+        }
+
+        let command = match ShellCmd::try_from_bytes(
+            target.topic_id.clone(),
+            cmd_id.into(),
+            message.payload_bytes(),
+        )
+        .context("Could not parse command as a shell command")?
+        {
+            Some(command) => command,
+            None => {
+                // The command has been fully processed
+                return Ok(OperationOutcome::Ignored);
+            }
+        };
+
+        let sm_topic = &target.smartrest_publish_topic;
+
+        match command.status() {
+            CommandStatus::Executing => Ok(OperationOutcome::Executing {
+                extra_messages: vec![],
+            }),
+            CommandStatus::Successful => {
+                let output = command.payload.stdout.unwrap_or_default();
+                let smartrest_operation_status = self
+                    .try_get_smartrest_successful_status_payload_with_args(
+                        CumulocitySupportedOperations::C8yCommand,
+                        cmd_id,
+                        TextOrCsv::Text(output),
+                    );
+                let c8y_notification = MqttMessage::new(sm_topic, smartrest_operation_status);
+
+                Ok(OperationOutcome::Finished {
+                    messages: vec![c8y_notification],
+                })
+            }
+            CommandStatus::Failed { reason } => Err(anyhow::anyhow!(reason).into()),
+            _ => Ok(OperationOutcome::Ignored),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+    use c8y_api::json_c8y_deserializer::C8yDeviceControlTopic;
+    use c8y_api::smartrest::message::MAX_PAYLOAD_LIMIT_IN_BYTES;
+    use serde_json::json;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::Sender;
+    use tedge_mqtt_ext::test_helpers::assert_received_contains_str;
+    use tedge_mqtt_ext::test_helpers::assert_received_includes_json;
+    use tedge_mqtt_ext::MqttMessage;
+    use tedge_mqtt_ext::Topic;
+    use tedge_test_utils::fs::TempTedgeDir;
+
+    const TEST_TIMEOUT_MS: Duration = Duration::from_millis(3000);
+
+    #[tokio::test]
+    async fn create_command_operation_file_for_main_device() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle { mqtt, .. } = test_handle;
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+
+        skip_init_messages(&mut mqtt).await;
+
+        // Simulate shell cmd metadata message
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/shell"),
+            "{}",
+        ))
+        .await
+        .expect("Send failed");
+
+        assert_received_contains_str(&mut mqtt, [("c8y/s/us", "114,c8y_Command")]).await;
+
+        // Validate if the supported operation file is created
+        assert!(ttd.path().join("operations/c8y/c8y_Command").exists());
+    }
+
+    #[tokio::test]
+    async fn mapper_converts_command_op_to_shell_cmd_for_main_device() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle { mqtt, .. } = test_handle;
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+
+        skip_init_messages(&mut mqtt).await;
+
+        // Simulate c8y_Command operation delivered via JSON over MQTT
+        mqtt.send(MqttMessage::new(
+            &C8yDeviceControlTopic::topic(&"c8y".try_into().unwrap()),
+            json!({
+                "id": "123456",
+                "c8y_Command": {
+                    "text": "df -h"
+                },
+                "externalSource": {
+                    "externalId": "test-device",
+                    "type": "c8y_Serial"
+                }
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        assert_received_includes_json(
+            &mut mqtt,
+            [(
+                "te/device/main///cmd/shell/+",
+                json!({
+                    "status": "init",
+                    "command": "df -h"
+                }),
+            )],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn handle_shell_successful_cmd_for_main_device() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle { mqtt, .. } = test_handle;
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+
+        skip_init_messages(&mut mqtt).await;
+
+        // Simulate shell command with "successful" state
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/shell/c8y-mapper-1234"),
+            json!({
+                "status": "successful",
+                "command": "echo hello",
+                "exitCode": 0,
+                "stdout": "hello",
+                "stderr": ""
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        assert_received_contains_str(
+            &mut mqtt,
+            [
+                ("c8y/s/us", "503,c8y_Command,\"hello\""),
+                ("te/device/main///cmd/shell/c8y-mapper-1234", ""),
+            ],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn handle_shell_successful_cmd_with_an_output_too_large_for_smartrest() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle { mqtt, .. } = test_handle;
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+
+        skip_init_messages(&mut mqtt).await;
+
+        // An output larger than the 16184 bytes accepted by Cumulocity, with characters to be escaped
+        let stdout = "a line with \"quotes\" and ünïcödé\n".repeat(1000);
+        assert!(stdout.len() > 16184);
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/shell/c8y-mapper-1234"),
+            json!({
+                "status": "successful",
+                "command": "cat large-file",
+                "exitCode": 0,
+                "stdout": stdout,
+                "stderr": ""
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        let message = mqtt.recv().await.unwrap();
+        assert_eq!(message.topic.name, "c8y/s/us");
+        let smartrest = message.payload_str().unwrap();
+        assert!(smartrest.len() <= MAX_PAYLOAD_LIMIT_IN_BYTES);
+        assert!(smartrest.starts_with("503,c8y_Command,\"a line with \"\"quotes\"\""));
+        assert!(smartrest.ends_with("...<trimmed>\""));
+    }
+}
@@ -3,12 +3,15 @@ use json_writer::JsonWriter;
 use json_writer::JsonWriterError;
 use tedge_api::entity::EntityType;
 use tedge_api::measurement::MeasurementVisitor;
+use tedge_api::telemetry_metadata::MeasurementMetadata;
 use time::format_description;
 use time::OffsetDateTime;
 
 pub struct C8yJsonSerializer {
     json: JsonWriter,
     is_within_group: bool,
+    current_group: Option<String>,
+    metadata: Option<MeasurementMetadata>,
     timestamp_present: bool,
     default_timestamp: OffsetDateTime,
     type_present: bool,
@@ -82,6 +85,8 @@ impl C8yJsonSerializer {
         Self {
             json,
             is_within_group: false,
+            current_group: None,
+            metadata: None,
             timestamp_present: false,
             default_timestamp,
             type_present: false,
@@ -89,6 +94,14 @@ impl C8yJsonSerializer {
         }
     }
 
+    /// Add to each series the unit declared by the metadata of the measurement type
+    pub fn with_metadata(self, metadata: Option<&MeasurementMetadata>) -> Self {
+        Self {
+            metadata: metadata.cloned(),
+            ..self
+        }
+    }
+
     fn end(&mut self) -> Result<(), C8yJsonSerializationError> {
         if self.is_within_group {
             return Err(MeasurementStreamError::UnexpectedEndOfData.into());
@@ -108,10 +121,18 @@ impl C8yJsonSerializer {
         Ok(())
     }
 
-    fn write_value_obj(&mut self, value: f64) -> Result<(), C8yJsonSerializationError> {
+    fn write_value_obj(&mut self, key: &str, value: f64) -> Result<(), C8yJsonSerializationError> {
         self.json.write_open_obj();
         self.json.write_key("value")?;
         self.json.write_f64(value)?;
+        if let Some(unit) = self
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.unit(self.current_group.as_deref(), key))
+        {
+            self.json.write_key("unit")?;
+            self.json.write_str(unit)?;
+        }
         self.json.write_close_obj();
         Ok(())
     }
@@ -181,11 +202,11 @@ impl MeasurementVisitor for C8yJsonSerializer {
                 self.json.write_key(key)?;
 
                 if self.is_within_group {
-                    self.write_value_obj(value)?;
+                    self.write_value_obj(key, value)?;
                 } else {
                     self.json.write_open_obj();
                     self.json.write_key(key)?;
-                    self.write_value_obj(value)?;
+                    self.write_value_obj(key, value)?;
                     self.json.write_close_obj();
                 }
             }
@@ -201,6 +222,7 @@ impl MeasurementVisitor for C8yJsonSerializer {
         self.json.write_key(group)?;
         self.json.write_open_obj();
         self.is_within_group = true;
+        self.current_group = Some(group.to_string());
         Ok(())
     }
 
@@ -211,6 +233,7 @@ impl MeasurementVisitor for C8yJsonSerializer {
 
         self.json.write_close_obj();
         self.is_within_group = false;
+        self.current_group = None;
         Ok(())
     }
 }
@@ -9,7 +9,13 @@ use tracing::trace;
 
 use super::connection::Frame1;
 use super::service::ChooseSchemeRequest;
+use super::service::CreateKeyRequest;
+use super::service::CreateKeyResponse;
+use super::service::GetPublicKeyRequest;
+use super::service::GetPublicKeyResponse;
+use super::service::ListObjectsRequest;
 use super::service::SignRequest;
+use super::service::SignRequestWithSigScheme;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TedgeP11Client {
@@ -149,4 +155,72 @@ impl TedgeP11Client {
 
         Ok(response.0)
     }
+
+    /// Sign a message using the given signature scheme, in place of the default one of the key.
+    pub fn sign_with_scheme(
+        &self,
+        message: &[u8],
+        sigscheme: Option<rustls::SignatureScheme>,
+        uri: Option<String>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let request = Frame1::SignRequestWithSigScheme(SignRequestWithSigScheme {
+            to_sign: message.to_vec(),
+            sigscheme: sigscheme.map(super::service::SignatureScheme),
+            uri,
+        });
+        let Frame1::SignResponse(response) = self.request(request)? else {
+            bail!("protocol error: bad response, expected sign");
+        };
+
+        debug!("Sign complete");
+
+        Ok(response.0)
+    }
+
+    pub fn get_public_key(&self, uri: Option<String>) -> anyhow::Result<GetPublicKeyResponse> {
+        let request = Frame1::GetPublicKeyRequest(GetPublicKeyRequest { uri });
+        let Frame1::GetPublicKeyResponse(response) = self.request(request)? else {
+            bail!("protocol error: bad response, expected public key");
+        };
+
+        Ok(response)
+    }
+
+    pub fn create_key(&self, request: CreateKeyRequest) -> anyhow::Result<CreateKeyResponse> {
+        let request = Frame1::CreateKeyRequest(request);
+        let Frame1::CreateKeyResponse(response) = self.request(request)? else {
+            bail!("protocol error: bad response, expected create key");
+        };
+
+        Ok(response)
+    }
+
+    pub fn list_objects(&self, uri: Option<String>) -> anyhow::Result<Vec<String>> {
+        let request = Frame1::ListObjectsRequest(ListObjectsRequest { uri });
+        let Frame1::ListObjectsResponse(response) = self.request(request)? else {
+            bail!("protocol error: bad response, expected list objects");
+        };
+
+        Ok(response.objects)
+    }
+
+    /// Send a request and return the response, turning an error response into an error.
+    fn request(&self, request: Frame1) -> anyhow::Result<Frame1> {
+        let stream = UnixStream::connect(&self.socket_path).with_context(|| {
+            format!(
+                "Failed to connect to tedge-p11-server UNIX socket at '{}'",
+                self.socket_path.display()
+            )
+        })?;
+        let mut connection = crate::connection::Connection::new(stream);
+        debug!("Connected to socket");
+
+        trace!(?request);
+        connection.write_frame(&request)?;
+
+        match connection.read_frame()? {
+            Frame1::Error(error) => bail!("tedge-p11-server: {}", error.0),
+            response => Ok(response),
+        }
+    }
 }
@@ -15,7 +15,14 @@ use tracing::warn;
 
 use crate::service::ChooseSchemeRequest;
 use crate::service::ChooseSchemeResponse;
+use crate::service::CreateKeyRequest;
+use crate::service::CreateKeyResponse;
+use crate::service::GetPublicKeyRequest;
+use crate::service::GetPublicKeyResponse;
+use crate::service::ListObjectsRequest;
+use crate::service::ListObjectsResponse;
 use crate::service::SignRequest;
+use crate::service::SignRequestWithSigScheme;
 use crate::service::SignResponse;
 
 pub struct Connection {
@@ -89,6 +96,14 @@ pub enum Frame1 {
     SignRequest(SignRequest),
     ChooseSchemeResponse(ChooseSchemeResponse),
     SignResponse(SignResponse),
+    // new variants are added at the end, so the frames of older clients and servers are still understood
+    SignRequestWithSigScheme(SignRequestWithSigScheme),
+    GetPublicKeyRequest(GetPublicKeyRequest),
+    GetPublicKeyResponse(GetPublicKeyResponse),
+    CreateKeyRequest(CreateKeyRequest),
+    CreateKeyResponse(CreateKeyResponse),
+    ListObjectsRequest(ListObjectsRequest),
+    ListObjectsResponse(ListObjectsResponse),
 }
 
 /// An error that can be returned to the client by the server.
@@ -13,6 +13,10 @@ mod signer;
 pub use signer::signing_key;
 pub use signer::CryptokiConfig;
 
+/// A PKCS#11 token used to create keys and sign CSRs, connecting to the server or calling cryptoki module directly.
+mod token;
+pub use token::Pkcs11Token;
+
 /// A client that connects to the UNIX server, used by the signer.
 pub mod client;
 
@@ -20,9 +20,14 @@ use cryptoki::mechanism::MechanismType;
 use cryptoki::object::Attribute;
 use cryptoki::object::AttributeType;
 use cryptoki::object::KeyType;
+use cryptoki::object::ObjectClass;
 use cryptoki::object::ObjectHandle;
 use cryptoki::session::Session;
 use cryptoki::session::UserType;
+use cryptoki::slot::Slot;
+use cryptoki::slot::TokenInfo;
+use percent_encoding::AsciiSet;
+use percent_encoding::NON_ALPHANUMERIC;
 use rustls::sign::Signer;
 use rustls::sign::SigningKey;
 use rustls::SignatureAlgorithm;
@@ -31,6 +36,9 @@ use tracing::debug;
 use tracing::trace;
 use tracing::warn;
 
+use crate::service::CreateKeyRequest;
+use crate::service::KeyTypeRequest;
+
 use std::fmt::Debug;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -41,6 +49,24 @@ const SECP256R1_OID: &str = "1.2.840.10045.3.1.7";
 const SECP384R1_OID: &str = "1.3.132.0.34";
 const SECP521R1_OID: &str = "1.3.132.0.35";
 
+// DER encoding of the above curve oIDs, as expected by the EC_PARAMS attribute
+const SECP256R1_DER: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const SECP384R1_DER: &[u8] = &[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22];
+
+// AlgorithmIdentifier contents for public keys: https://datatracker.ietf.org/doc/html/rfc5480#section-2.1.1
+// and https://datatracker.ietf.org/doc/html/rfc3279#section-2.3.1
+const EC_PUBLIC_KEY_OID_DER: &[u8] = &[0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const RSA_ENCRYPTION_DER: &[u8] = &[
+    0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01, 0x05, 0x00,
+];
+
+// 65537
+const RSA_PUBLIC_EXPONENT: &[u8] = &[0x01, 0x00, 0x01];
+
+// characters percent-encoded in the attribute values of the PKCS #11 URIs built by the server
+const URI_ATTRIBUTE_ENCODE_SET: &AsciiSet =
+    &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.');
+
 #[derive(Clone)]
 pub struct CryptokiConfigDirect {
     pub module_path: Utf8PathBuf,
@@ -88,56 +114,9 @@ impl Cryptoki {
     }
 
     pub fn signing_key(&self, uri: Option<&str>) -> anyhow::Result<Pkcs11SigningKey> {
-        let mut config_uri = self
-            .config
-            .uri
-            .as_deref()
-            .map(|u| uri::Pkcs11Uri::parse(u).context("Failed to parse config PKCS#11 URI"))
-            .transpose()?
-            .unwrap_or_default();
-
-        let request_uri = uri
-            .map(|uri| uri::Pkcs11Uri::parse(uri).context("Failed to parse PKCS #11 URI"))
-            .transpose()?
-            .unwrap_or_default();
-
-        config_uri.append_attributes(request_uri);
-        let uri_attributes = config_uri;
-
-        let wanted_label = uri_attributes.token.as_ref();
-        let wanted_serial = uri_attributes.serial.as_ref();
-
-        let slots_with_tokens = self.context.get_slots_with_token()?;
-        let tokens: Result<Vec<_>, _> = slots_with_tokens
-            .iter()
-            .map(|s| {
-                self.context
-                    .get_token_info(*s)
-                    .context("Failed to get slot info")
-            })
-            .collect();
-        let tokens = tokens?;
-
-        // if token/serial attributes are passed, find a token that has these attributes, otherwise any token will do
-        let mut tokens = slots_with_tokens
-            .into_iter()
-            .zip(tokens)
-            .filter(|(_, t)| wanted_label.is_none() || wanted_label.is_some_and(|l| t.label() == l))
-            .filter(|(_, t)| {
-                wanted_serial.is_none() || wanted_serial.is_some_and(|s| t.serial_number() == s)
-            });
-        let (slot, _) = tokens
-            .next()
-            .context("Didn't find a slot to use. The device may be disconnected.")?;
-
-        let slot_info = self.context.get_slot_info(slot)?;
-        let token_info = self.context.get_token_info(slot)?;
-        debug!(?slot_info, ?token_info, "Selected slot");
-
-        let session = self.context.open_ro_session(slot)?;
-        session.login(UserType::User, Some(&self.config.pin))?;
-        let session_info = session.get_session_info()?;
-        debug!(?session_info, "Opened a readonly session");
+        let uri_attributes = self.request_uri(uri)?;
+        let (slot, _) = self.find_slot(&uri_attributes)?;
+        let session = self.login(slot, false)?;
 
         // get the signing key
         let key = Self::find_key_by_attributes(&uri_attributes, &session)?;
@@ -192,6 +171,139 @@ impl Cryptoki {
         Ok(key)
     }
 
+    /// Create a new key pair in the token, returning the URI and the public key of the new key.
+    pub fn create_key(&self, request: &CreateKeyRequest) -> anyhow::Result<(String, Vec<u8>)> {
+        let uri_attributes = self.request_uri(request.uri.as_deref())?;
+        let (slot, token_info) = self.find_slot(&uri_attributes)?;
+        let session = self.login(slot, true)?;
+
+        let label = request.label.as_bytes().to_vec();
+        let mut public_template = vec![
+            Attribute::Token(true),
+            Attribute::Private(false),
+            Attribute::Verify(true),
+            Attribute::Label(label.clone()),
+        ];
+        let mut private_template = vec![
+            Attribute::Token(true),
+            Attribute::Private(true),
+            Attribute::Sensitive(true),
+            Attribute::Extractable(false),
+            Attribute::Sign(true),
+            Attribute::Label(label),
+        ];
+        if let Some(id) = &request.id {
+            public_template.push(Attribute::Id(id.clone()));
+            private_template.push(Attribute::Id(id.clone()));
+        }
+
+        let (mechanism, key_type) = match request.key {
+            KeyTypeRequest::Ec { curve } => {
+                let ec_params = match curve {
+                    256 => SECP256R1_DER,
+                    384 => SECP384R1_DER,
+                    _ => anyhow::bail!("Unsupported EC curve: P-{curve}"),
+                };
+                public_template.push(Attribute::EcParams(ec_params.to_vec()));
+                (Mechanism::EccKeyPairGen, KeyType::EC)
+            }
+            KeyTypeRequest::Rsa { bits } => {
+                public_template.push(Attribute::ModulusBits(u64::from(bits).into()));
+                public_template.push(Attribute::PublicExponent(RSA_PUBLIC_EXPONENT.to_vec()));
+                (Mechanism::RsaPkcsKeyPairGen, KeyType::RSA)
+            }
+        };
+
+        debug!(?mechanism, label = %request.label, "Generating a key pair");
+        let (public_key, private_key) = session
+            .generate_key_pair(&mechanism, &public_template, &private_template)
+            .context("Failed to generate a key pair")?;
+
+        let public_key_der = public_key_der(&session, public_key, key_type)?;
+        let uri = object_uri(&session, &token_info, private_key)?;
+
+        Ok((uri, public_key_der))
+    }
+
+    /// List the objects of the token, returning their URIs.
+    pub fn list_objects(&self, uri: Option<&str>) -> anyhow::Result<Vec<String>> {
+        let uri_attributes = self.request_uri(uri)?;
+        let (slot, token_info) = self.find_slot(&uri_attributes)?;
+        let session = self.login(slot, false)?;
+
+        session
+            .find_objects(&[])
+            .context("Failed to find objects")?
+            .into_iter()
+            .map(|object| object_uri(&session, &token_info, object))
+            .collect()
+    }
+
+    /// Merge the attributes of the request URI with those of the configured URI.
+    fn request_uri<'a>(&'a self, uri: Option<&'a str>) -> anyhow::Result<uri::Pkcs11Uri<'a>> {
+        let mut config_uri = self
+            .config
+            .uri
+            .as_deref()
+            .map(|u| uri::Pkcs11Uri::parse(u).context("Failed to parse config PKCS#11 URI"))
+            .transpose()?
+            .unwrap_or_default();
+
+        let request_uri = uri
+            .map(|uri| uri::Pkcs11Uri::parse(uri).context("Failed to parse PKCS #11 URI"))
+            .transpose()?
+            .unwrap_or_default();
+
+        config_uri.append_attributes(request_uri);
+        Ok(config_uri)
+    }
+
+    fn find_slot(&self, uri_attributes: &uri::Pkcs11Uri) -> anyhow::Result<(Slot, TokenInfo)> {
+        let wanted_label = uri_attributes.token.as_ref();
+        let wanted_serial = uri_attributes.serial.as_ref();
+
+        let slots_with_tokens = self.context.get_slots_with_token()?;
+        let tokens: Result<Vec<_>, _> = slots_with_tokens
+            .iter()
+            .map(|s| {
+                self.context
+                    .get_token_info(*s)
+                    .context("Failed to get slot info")
+            })
+            .collect();
+        let tokens = tokens?;
+
+        // if token/serial attributes are passed, find a token that has these attributes, otherwise any token will do
+        let mut tokens = slots_with_tokens
+            .into_iter()
+            .zip(tokens)
+            .filter(|(_, t)| wanted_label.is_none() || wanted_label.is_some_and(|l| t.label() == l))
+            .filter(|(_, t)| {
+                wanted_serial.is_none() || wanted_serial.is_some_and(|s| t.serial_number() == s)
+            });
+        let (slot, token_info) = tokens
+            .next()
+            .context("Didn't find a slot to use. The device may be disconnected.")?;
+
+        let slot_info = self.context.get_slot_info(slot)?;
+        debug!(?slot_info, ?token_info, "Selected slot");
+
+        Ok((slot, token_info))
+    }
+
+    fn login(&self, slot: Slot, read_write: bool) -> anyhow::Result<Session> {
+        let session = if read_write {
+            self.context.open_rw_session(slot)?
+        } else {
+            self.context.open_ro_session(slot)?
+        };
+        session.login(UserType::User, Some(&self.config.pin))?;
+        let session_info = session.get_session_info()?;
+        debug!(?session_info, "Opened a session");
+
+        Ok(session)
+    }
+
     fn find_key_by_attributes(
         uri: &uri::Pkcs11Uri,
         session: &Session,
@@ -231,6 +343,41 @@ pub struct Pkcs11SigningKey {
     sigscheme: SigScheme,
 }
 
+impl Pkcs11SigningKey {
+    /// The signature scheme used by default with this key
+    pub fn scheme(&self) -> SignatureScheme {
+        self.sigscheme.into()
+    }
+
+    /// Use the given signature scheme in place of the default one of the key.
+    ///
+    /// An RSA key can be used with any RSA scheme, but an EC key only with the scheme matching its curve.
+    pub fn with_scheme(self, scheme: SignatureScheme) -> anyhow::Result<Self> {
+        let sigscheme = SigScheme::try_from(scheme)?;
+        let compatible = sigscheme == self.sigscheme
+            || (SignatureAlgorithm::from(sigscheme) == SignatureAlgorithm::RSA
+                && self.algorithm() == SignatureAlgorithm::RSA);
+        if !compatible {
+            anyhow::bail!(
+                "Signature scheme {scheme:?} can't be used with a key of type {:?}",
+                self.algorithm()
+            );
+        }
+        Ok(Self { sigscheme, ..self })
+    }
+
+    /// Returns the DER-encoded SubjectPublicKeyInfo of the key.
+    pub fn public_key(&self) -> anyhow::Result<Vec<u8>> {
+        let session = self.session.session.lock().unwrap();
+        let key_type = match self.algorithm() {
+            SignatureAlgorithm::RSA => KeyType::RSA,
+            _ => KeyType::EC,
+        };
+        let public_key = find_public_key(&session, self.handle, key_type)?;
+        public_key_der(&session, public_key, key_type)
+    }
+}
+
 impl SigningKey for Pkcs11SigningKey {
     fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
         debug!("Offered signature schemes. offered={:?}", offered);
@@ -281,10 +428,11 @@ impl PkcsSigner {
             Mechanism::EcdsaSha256 => (Mechanism::Ecdsa, Some(Mechanism::Sha256)),
             Mechanism::EcdsaSha384 => (Mechanism::Ecdsa, Some(Mechanism::Sha384)),
             Mechanism::EcdsaSha512 => (Mechanism::Ecdsa, Some(Mechanism::Sha512)),
-            Mechanism::Sha1RsaPkcs => (Mechanism::RsaPkcs, Some(Mechanism::Sha1)),
-            Mechanism::Sha256RsaPkcs => (Mechanism::RsaPkcs, Some(Mechanism::Sha256)),
-            Mechanism::Sha384RsaPkcs => (Mechanism::RsaPkcs, Some(Mechanism::Sha384)),
-            Mechanism::Sha512RsaPkcs => (Mechanism::RsaPkcs, Some(Mechanism::Sha512)),
+            // the hash-and-sign mechanisms add the DigestInfo prefix required by PKCS #1 v1.5
+            Mechanism::Sha1RsaPkcs => (Mechanism::Sha1RsaPkcs, None),
+            Mechanism::Sha256RsaPkcs => (Mechanism::Sha256RsaPkcs, None),
+            Mechanism::Sha384RsaPkcs => (Mechanism::Sha384RsaPkcs, None),
+            Mechanism::Sha512RsaPkcs => (Mechanism::Sha512RsaPkcs, None),
             Mechanism::Sha256RsaPkcsPss(p) => (Mechanism::Sha256RsaPkcsPss(p), None),
             Mechanism::Sha384RsaPkcsPss(p) => (Mechanism::Sha384RsaPkcsPss(p), None),
             Mechanism::Sha512RsaPkcsPss(p) => (Mechanism::Sha512RsaPkcsPss(p), None),
@@ -343,6 +491,22 @@ enum SigScheme {
     EcdsaNistp384Sha384,
     EcdsaNistp521Sha512,
     RsaPssSha256,
+    RsaPkcs1Sha256,
+}
+
+impl TryFrom<rustls::SignatureScheme> for SigScheme {
+    type Error = anyhow::Error;
+
+    fn try_from(value: rustls::SignatureScheme) -> Result<Self, Self::Error> {
+        match value {
+            rustls::SignatureScheme::ECDSA_NISTP256_SHA256 => Ok(Self::EcdsaNistp256Sha256),
+            rustls::SignatureScheme::ECDSA_NISTP384_SHA384 => Ok(Self::EcdsaNistp384Sha384),
+            rustls::SignatureScheme::ECDSA_NISTP521_SHA512 => Ok(Self::EcdsaNistp521Sha512),
+            rustls::SignatureScheme::RSA_PSS_SHA256 => Ok(Self::RsaPssSha256),
+            rustls::SignatureScheme::RSA_PKCS1_SHA256 => Ok(Self::RsaPkcs1Sha256),
+            _ => anyhow::bail!("Unsupported signature scheme: {value:?}"),
+        }
+    }
 }
 
 impl From<SigScheme> for rustls::SignatureScheme {
@@ -352,6 +516,7 @@ impl From<SigScheme> for rustls::SignatureScheme {
             SigScheme::EcdsaNistp384Sha384 => Self::ECDSA_NISTP384_SHA384,
             SigScheme::EcdsaNistp521Sha512 => Self::ECDSA_NISTP521_SHA512,
             SigScheme::RsaPssSha256 => Self::RSA_PSS_SHA256,
+            SigScheme::RsaPkcs1Sha256 => Self::RSA_PKCS1_SHA256,
         }
     }
 }
@@ -362,7 +527,7 @@ impl From<SigScheme> for rustls::SignatureAlgorithm {
             SigScheme::EcdsaNistp256Sha256
             | SigScheme::EcdsaNistp384Sha384
             | SigScheme::EcdsaNistp521Sha512 => Self::ECDSA,
-            SigScheme::RsaPssSha256 => Self::RSA,
+            SigScheme::RsaPssSha256 | SigScheme::RsaPkcs1Sha256 => Self::RSA,
         }
     }
 }
@@ -381,6 +546,7 @@ impl From<SigScheme> for Mechanism<'_> {
                 // SHA256: 256 bits = 32 bytes
                 s_len: 32.into(),
             }),
+            SigScheme::RsaPkcs1Sha256 => Self::Sha256RsaPkcs,
         }
     }
 }
@@ -449,6 +615,176 @@ fn get_ec_mechanism(session: &Session, key: ObjectHandle) -> anyhow::Result<SigS
     }
 }
 
+/// Find the public key object matching a private key, i.e. with the same key type, id and label.
+fn find_public_key(
+    session: &Session,
+    private_key: ObjectHandle,
+    key_type: KeyType,
+) -> anyhow::Result<ObjectHandle> {
+    let attrs = session
+        .get_attributes(private_key, &[AttributeType::Id, AttributeType::Label])
+        .context("Failed to get private key attributes")?;
+
+    let mut template = vec![
+        Attribute::Class(ObjectClass::PUBLIC_KEY),
+        Attribute::KeyType(key_type),
+    ];
+    for attr in attrs {
+        match attr {
+            Attribute::Id(id) if !id.is_empty() => template.push(Attribute::Id(id)),
+            Attribute::Label(label) if !label.is_empty() => template.push(Attribute::Label(label)),
+            _ => {}
+        }
+    }
+
+    trace!(?template, "Finding the public key");
+    session
+        .find_objects(&template)
+        .context("Failed to find public key objects")?
+        .into_iter()
+        .next()
+        .context("Failed to find the public key matching the private key")
+}
+
+/// Encode a public key object as a DER SubjectPublicKeyInfo.
+///
+/// https://datatracker.ietf.org/doc/html/rfc5280#section-4.1.2.7
+fn public_key_der(
+    session: &Session,
+    public_key: ObjectHandle,
+    key_type: KeyType,
+) -> anyhow::Result<Vec<u8>> {
+    let (algorithm, subject_public_key) = match key_type {
+        KeyType::EC => {
+            let attrs = session
+                .get_attributes(
+                    public_key,
+                    &[AttributeType::EcParams, AttributeType::EcPoint],
+                )
+                .context("Failed to get EC public key attributes")?;
+            let mut ec_params = None;
+            let mut ec_point = None;
+            for attr in attrs {
+                match attr {
+                    Attribute::EcParams(value) => ec_params = Some(value),
+                    Attribute::EcPoint(value) => ec_point = Some(value),
+                    _ => {}
+                }
+            }
+            let ec_params = ec_params.context("Failed to get EcParams attribute")?;
+            let ec_point = ec_point.context("Failed to get EcPoint attribute")?;
+
+            // EC_POINT should be a DER-encoded OCTET STRING, but some modules return the raw point
+            let ec_point = match asn1_rs::OctetString::from_der(&ec_point) {
+                Ok((rem, point)) if rem.is_empty() => point.as_cow().to_vec(),
+                _ => ec_point,
+            };
+
+            ([EC_PUBLIC_KEY_OID_DER, &ec_params].concat(), ec_point)
+        }
+        KeyType::RSA => {
+            let attrs = session
+                .get_attributes(
+                    public_key,
+                    &[AttributeType::Modulus, AttributeType::PublicExponent],
+                )
+                .context("Failed to get RSA public key attributes")?;
+            let mut modulus = None;
+            let mut exponent = None;
+            for attr in attrs {
+                match attr {
+                    Attribute::Modulus(value) => modulus = Some(value),
+                    Attribute::PublicExponent(value) => exponent = Some(value),
+                    _ => {}
+                }
+            }
+            let modulus = modulus.context("Failed to get Modulus attribute")?;
+            let exponent = exponent.context("Failed to get PublicExponent attribute")?;
+
+            let mut rsa_public_key = Vec::new();
+            write_asn1_integer(&mut rsa_public_key, &modulus);
+            write_asn1_integer(&mut rsa_public_key, &exponent);
+
+            (RSA_ENCRYPTION_DER.to_vec(), der_tlv(0x30, &rsa_public_key))
+        }
+        _ => anyhow::bail!("unsupported key type"),
+    };
+
+    // the public key is a BIT STRING, with no unused bits
+    let subject_public_key = [&[0x00], subject_public_key.as_slice()].concat();
+    let spki = [
+        der_tlv(0x30, &algorithm),
+        der_tlv(0x03, &subject_public_key),
+    ]
+    .concat();
+    Ok(der_tlv(0x30, &spki))
+}
+
+/// Encode a DER tag-length-value, the length being encoded in the short or long form as required.
+fn der_tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    let len = value.len();
+    if len < 0x80 {
+        der.push(len as u8);
+    } else {
+        let len_bytes = len.to_be_bytes();
+        let len_bytes: Vec<u8> = len_bytes.into_iter().skip_while(|b| *b == 0).collect();
+        der.push(0x80 | len_bytes.len() as u8);
+        der.extend_from_slice(&len_bytes);
+    }
+    der.extend_from_slice(value);
+    der
+}
+
+/// Build the PKCS #11 URI of an object, from its token, class, label and id.
+///
+/// https://www.rfc-editor.org/rfc/rfc7512.html
+fn object_uri(
+    session: &Session,
+    token_info: &TokenInfo,
+    object: ObjectHandle,
+) -> anyhow::Result<String> {
+    let attrs = session
+        .get_attributes(
+            object,
+            &[
+                AttributeType::Class,
+                AttributeType::Label,
+                AttributeType::Id,
+            ],
+        )
+        .context("Failed to get object attributes")?;
+
+    let token = percent_encoding::utf8_percent_encode(token_info.label(), URI_ATTRIBUTE_ENCODE_SET);
+    let mut uri = format!("pkcs11:token={token}");
+    for attr in attrs {
+        match attr {
+            Attribute::Label(label) if !label.is_empty() => {
+                let label = percent_encoding::percent_encode(&label, URI_ATTRIBUTE_ENCODE_SET);
+                uri.push_str(&format!(";object={label}"));
+            }
+            Attribute::Id(id) if !id.is_empty() => {
+                let id: String = id.iter().map(|b| format!("%{b:02x}")).collect();
+                uri.push_str(&format!(";id={id}"));
+            }
+            Attribute::Class(class) => {
+                let object_type = match class {
+                    ObjectClass::PRIVATE_KEY => "private",
+                    ObjectClass::PUBLIC_KEY => "public",
+                    ObjectClass::SECRET_KEY => "secret-key",
+                    ObjectClass::CERTIFICATE => "cert",
+                    ObjectClass::DATA => "data",
+                    _ => continue,
+                };
+                uri.push_str(&format!(";type={object_type}"));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(uri)
+}
+
 pub mod uri {
     use std::borrow::Cow;
     use std::collections::HashMap;
@@ -642,3 +978,39 @@ pub mod uri {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_short_der_lengths() {
+        assert_eq!(
+            der_tlv(0x04, &[0xab; 3]),
+            vec![0x04, 0x03, 0xab, 0xab, 0xab]
+        );
+    }
+
+    #[test]
+    fn encodes_long_der_lengths() {
+        let der = der_tlv(0x30, &[0u8; 300]);
+        assert_eq!(&der[..4], &[0x30, 0x82, 0x01, 0x2c]);
+        assert_eq!(der.len(), 304);
+    }
+
+    #[test]
+    fn rsa_keys_can_be_used_with_pkcs1_signatures() {
+        let sigscheme = SigScheme::try_from(rustls::SignatureScheme::RSA_PKCS1_SHA256).unwrap();
+        assert_eq!(sigscheme, SigScheme::RsaPkcs1Sha256);
+        assert_eq!(SignatureAlgorithm::from(sigscheme), SignatureAlgorithm::RSA);
+        assert!(matches!(
+            Mechanism::from(sigscheme),
+            Mechanism::Sha256RsaPkcs
+        ));
+    }
+
+    #[test]
+    fn unsupported_signature_schemes_are_rejected() {
+        assert!(SigScheme::try_from(rustls::SignatureScheme::ED25519).is_err());
+    }
+}
@@ -51,36 +51,44 @@ impl TedgeP11Server {
         let response = match request {
             Frame1::Error(_)
             | Frame1::ChooseSchemeResponse { .. }
-            | Frame1::SignResponse { .. } => {
+            | Frame1::SignResponse { .. }
+            | Frame1::GetPublicKeyResponse { .. }
+            | Frame1::CreateKeyResponse { .. }
+            | Frame1::ListObjectsResponse { .. } => {
                 let error = ProtocolError("invalid request".to_string());
                 let _ = connection.write_frame(&Frame1::Error(error));
                 anyhow::bail!("protocol error: invalid request")
             }
-            Frame1::ChooseSchemeRequest(request) => {
-                let response = self.service.choose_scheme(request);
-                match response {
-                    Ok(response) => Frame1::ChooseSchemeResponse(response),
-                    Err(err) => {
-                        let response = Frame1::Error(ProtocolError(format!(
-                            "PKCS #11 service failed: {err:#}"
-                        )));
-                        connection.write_frame(&response)?;
-                        anyhow::bail!(err);
-                    }
-                }
-            }
-            Frame1::SignRequest(request) => {
-                let response = self.service.sign(request);
-                match response {
-                    Ok(response) => Frame1::SignResponse(response),
-                    Err(err) => {
-                        let response = Frame1::Error(ProtocolError(format!(
-                            "PKCS #11 service failed: {err:#}"
-                        )));
-                        connection.write_frame(&response)?;
-                        anyhow::bail!(err);
-                    }
-                }
+            Frame1::ChooseSchemeRequest(request) => self
+                .service
+                .choose_scheme(request)
+                .map(Frame1::ChooseSchemeResponse),
+            Frame1::SignRequest(request) => self.service.sign(request).map(Frame1::SignResponse),
+            Frame1::SignRequestWithSigScheme(request) => self
+                .service
+                .sign_with_scheme(request)
+                .map(Frame1::SignResponse),
+            Frame1::GetPublicKeyRequest(request) => self
+                .service
+                .get_public_key(request)
+                .map(Frame1::GetPublicKeyResponse),
+            Frame1::CreateKeyRequest(request) => self
+                .service
+                .create_key(request)
+                .map(Frame1::CreateKeyResponse),
+            Frame1::ListObjectsRequest(request) => self
+                .service
+                .list_objects(request)
+                .map(Frame1::ListObjectsResponse),
+        };
+
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                let response =
+                    Frame1::Error(ProtocolError(format!("PKCS #11 service failed: {err:#}")));
+                connection.write_frame(&response)?;
+                anyhow::bail!(err);
             }
         };
 
@@ -102,6 +110,7 @@ mod tests {
 
     const SCHEME: rustls::SignatureScheme = rustls::SignatureScheme::ECDSA_NISTP256_SHA256;
     const SIGNATURE: [u8; 2] = [0x21, 0x37];
+    const PUBLIC_KEY: [u8; 2] = [0x30, 0x00];
 
     struct TestSigningService;
 
@@ -119,6 +128,44 @@ mod tests {
         fn sign(&self, _request: SignRequest) -> anyhow::Result<SignResponse> {
             Ok(SignResponse(SIGNATURE.to_vec()))
         }
+
+        fn sign_with_scheme(
+            &self,
+            request: SignRequestWithSigScheme,
+        ) -> anyhow::Result<SignResponse> {
+            match request.sigscheme {
+                Some(scheme) if scheme.0 != SCHEME => {
+                    anyhow::bail!("unsupported scheme: {scheme:?}")
+                }
+                _ => Ok(SignResponse(SIGNATURE.to_vec())),
+            }
+        }
+
+        fn get_public_key(
+            &self,
+            _request: GetPublicKeyRequest,
+        ) -> anyhow::Result<GetPublicKeyResponse> {
+            Ok(GetPublicKeyResponse {
+                public_key: PUBLIC_KEY.to_vec(),
+                scheme: SignatureScheme(SCHEME),
+            })
+        }
+
+        fn create_key(&self, request: CreateKeyRequest) -> anyhow::Result<CreateKeyResponse> {
+            Ok(CreateKeyResponse {
+                uri: format!("pkcs11:token=test;object={};type=private", request.label),
+                public_key: PUBLIC_KEY.to_vec(),
+            })
+        }
+
+        fn list_objects(
+            &self,
+            _request: ListObjectsRequest,
+        ) -> anyhow::Result<ListObjectsResponse> {
+            Ok(ListObjectsResponse {
+                objects: vec!["pkcs11:token=test;object=tedge;type=private".to_string()],
+            })
+        }
     }
 
     /// Check that client successfully receives responses from the server about the requests. Tests the
@@ -139,9 +186,53 @@ mod tests {
             let client = TedgeP11Client::with_ready_check(socket_path.into());
             assert_eq!(client.choose_scheme(&[], None).unwrap().unwrap(), SCHEME);
             assert_eq!(&client.sign(&[], None).unwrap(), &SIGNATURE[..]);
+            assert_eq!(
+                &client.sign_with_scheme(&[], Some(SCHEME), None).unwrap(),
+                &SIGNATURE[..]
+            );
+            assert_eq!(client.get_public_key(None).unwrap().public_key, PUBLIC_KEY);
+            assert_eq!(
+                client
+                    .create_key(CreateKeyRequest {
+                        uri: None,
+                        key: KeyTypeRequest::Ec { curve: 256 },
+                        label: "new-key".to_string(),
+                        id: None,
+                    })
+                    .unwrap()
+                    .uri,
+                "pkcs11:token=test;object=new-key;type=private"
+            );
+            assert_eq!(
+                client.list_objects(None).unwrap(),
+                vec!["pkcs11:token=test;object=tedge;type=private".to_string()]
+            );
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn service_errors_are_returned_to_the_client() {
+        let service = TestSigningService;
+        let server = TedgeP11Server::new(service).unwrap();
+        let tmpdir = tempfile::tempdir().unwrap();
+        let socket_path = tmpdir.path().join("test_socket.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        tokio::spawn(async move { server.serve(listener).await });
+        // wait until the server calls accept()
+        tokio::time::sleep(Duration::from_millis(2)).await;
+
+        let err = tokio::task::spawn_blocking(move || {
+            let client = TedgeP11Client::with_ready_check(socket_path.into());
+            client
+                .sign_with_scheme(&[], Some(rustls::SignatureScheme::RSA_PSS_SHA256), None)
+                .unwrap_err()
         })
         .await
         .unwrap();
+        assert!(format!("{err:#}").contains("unsupported scheme"));
     }
 
     #[tokio::test]
@@ -13,6 +13,10 @@ use tracing::warn;
 pub trait SigningService {
     fn choose_scheme(&self, request: ChooseSchemeRequest) -> anyhow::Result<ChooseSchemeResponse>;
     fn sign(&self, request: SignRequest) -> anyhow::Result<SignResponse>;
+    fn sign_with_scheme(&self, request: SignRequestWithSigScheme) -> anyhow::Result<SignResponse>;
+    fn get_public_key(&self, request: GetPublicKeyRequest) -> anyhow::Result<GetPublicKeyResponse>;
+    fn create_key(&self, request: CreateKeyRequest) -> anyhow::Result<CreateKeyResponse>;
+    fn list_objects(&self, request: ListObjectsRequest) -> anyhow::Result<ListObjectsResponse>;
 }
 
 #[derive(Debug)]
@@ -77,6 +81,61 @@ impl SigningService for TedgeP11Service {
             .context("Failed to sign using PKCS #11")?;
         Ok(SignResponse(signature))
     }
+
+    #[instrument(skip_all)]
+    fn sign_with_scheme(&self, request: SignRequestWithSigScheme) -> anyhow::Result<SignResponse> {
+        trace!(?request);
+        let uri = request.uri;
+        let mut signing_key = self
+            .cryptoki
+            .signing_key(uri.as_deref())
+            .context("Failed to find a signing key")?;
+        if let Some(sigscheme) = request.sigscheme {
+            signing_key = signing_key.with_scheme(sigscheme.0)?;
+        }
+
+        let signer = PkcsSigner::from_key(signing_key);
+        let signature = signer
+            .sign(&request.to_sign)
+            .context("Failed to sign using PKCS #11")?;
+        Ok(SignResponse(signature))
+    }
+
+    #[instrument(skip_all)]
+    fn get_public_key(&self, request: GetPublicKeyRequest) -> anyhow::Result<GetPublicKeyResponse> {
+        trace!(?request);
+        let uri = request.uri;
+        let signing_key = self
+            .cryptoki
+            .signing_key(uri.as_deref())
+            .context("Failed to find a signing key")?;
+
+        let public_key = signing_key
+            .public_key()
+            .context("Failed to get the public key")?;
+        let scheme = SignatureScheme(signing_key.scheme());
+        Ok(GetPublicKeyResponse { public_key, scheme })
+    }
+
+    #[instrument(skip_all)]
+    fn create_key(&self, request: CreateKeyRequest) -> anyhow::Result<CreateKeyResponse> {
+        trace!(?request);
+        let (uri, public_key) = self
+            .cryptoki
+            .create_key(&request)
+            .context("Failed to create a key")?;
+        Ok(CreateKeyResponse { uri, public_key })
+    }
+
+    #[instrument(skip_all)]
+    fn list_objects(&self, request: ListObjectsRequest) -> anyhow::Result<ListObjectsResponse> {
+        trace!(?request);
+        let objects = self
+            .cryptoki
+            .list_objects(request.uri.as_deref())
+            .context("Failed to list objects")?;
+        Ok(ListObjectsResponse { objects })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -100,6 +159,74 @@ pub struct SignRequest {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SignResponse(pub Vec<u8>);
 
+/// A request to sign a message using a given signature scheme, in place of the default one of the key.
+///
+/// Used to sign CSRs, the schemes supported for certificates and TLS being not the same.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignRequestWithSigScheme {
+    pub to_sign: Vec<u8>,
+    pub sigscheme: Option<SignatureScheme>,
+    pub uri: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GetPublicKeyRequest {
+    pub uri: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GetPublicKeyResponse {
+    /// The DER-encoded SubjectPublicKeyInfo of the key
+    pub public_key: Vec<u8>,
+
+    /// The signature scheme used by default with the key
+    pub scheme: SignatureScheme,
+}
+
+/// A request to generate a new key pair in the token selected by the URI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreateKeyRequest {
+    pub uri: Option<String>,
+    pub key: KeyTypeRequest,
+
+    /// The label (CKA_LABEL) of the new key objects
+    pub label: String,
+
+    /// The id (CKA_ID) of the new key objects, if any
+    pub id: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyTypeRequest {
+    /// An EC key on the NIST curve with the given size: 256 or 384
+    ///
+    /// P-521 keys are not supported, as they cannot be used to sign certificates and CSRs.
+    Ec { curve: u16 },
+
+    /// An RSA key with the given modulus size
+    Rsa { bits: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreateKeyResponse {
+    /// The PKCS #11 URI of the new private key
+    pub uri: String,
+
+    /// The DER-encoded SubjectPublicKeyInfo of the new key
+    pub public_key: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListObjectsRequest {
+    pub uri: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListObjectsResponse {
+    /// The PKCS #11 URIs of the objects of the token
+    pub objects: Vec<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SignatureScheme(pub rustls::SignatureScheme);
 
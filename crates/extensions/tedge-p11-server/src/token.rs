@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::client::TedgeP11Client;
+use crate::service::CreateKeyRequest;
+use crate::service::CreateKeyResponse;
+use crate::service::GetPublicKeyRequest;
+use crate::service::GetPublicKeyResponse;
+use crate::service::ListObjectsRequest;
+use crate::service::SignRequestWithSigScheme;
+use crate::service::SignatureScheme;
+use crate::service::SigningService;
+use crate::service::TedgeP11Service;
+use crate::signer::CryptokiConfig;
+
+/// A PKCS #11 token used to create keys and sign CSRs, the private keys never leaving the token.
+///
+/// Depending on the config, requests are either sent to tedge-p11-server or handled by calling the
+/// cryptoki module directly.
+pub struct Pkcs11Token {
+    backend: Backend,
+    uri: Option<Arc<str>>,
+}
+
+enum Backend {
+    Direct(TedgeP11Service),
+    SocketService(TedgeP11Client),
+}
+
+impl Pkcs11Token {
+    pub fn new(config: CryptokiConfig) -> anyhow::Result<Self> {
+        let token = match config {
+            // the configured URI is used by the service itself
+            CryptokiConfig::Direct(config_direct) => Pkcs11Token {
+                backend: Backend::Direct(
+                    TedgeP11Service::new(config_direct)
+                        .context("Failed to create the signing service")?,
+                ),
+                uri: None,
+            },
+            CryptokiConfig::SocketService { socket_path, uri } => Pkcs11Token {
+                backend: Backend::SocketService(TedgeP11Client::with_ready_check(
+                    socket_path.into(),
+                )),
+                uri,
+            },
+        };
+
+        Ok(token)
+    }
+
+    /// Returns the public key of the configured key, along with its default signature scheme.
+    pub fn get_public_key(&self) -> anyhow::Result<GetPublicKeyResponse> {
+        match &self.backend {
+            Backend::Direct(service) => service.get_public_key(GetPublicKeyRequest { uri: None }),
+            Backend::SocketService(client) => client.get_public_key(self.uri()),
+        }
+    }
+
+    /// Sign a message with the configured key, using the given signature scheme.
+    pub fn sign(&self, message: &[u8], scheme: rustls::SignatureScheme) -> anyhow::Result<Vec<u8>> {
+        match &self.backend {
+            Backend::Direct(service) => service
+                .sign_with_scheme(SignRequestWithSigScheme {
+                    to_sign: message.to_vec(),
+                    sigscheme: Some(SignatureScheme(scheme)),
+                    uri: None,
+                })
+                .map(|response| response.0),
+            Backend::SocketService(client) => {
+                client.sign_with_scheme(message, Some(scheme), self.uri())
+            }
+        }
+    }
+
+    /// Create a new key pair in the token.
+    ///
+    /// The token is selected using the URI of the request, completed by the configured URI.
+    pub fn create_key(&self, mut request: CreateKeyRequest) -> anyhow::Result<CreateKeyResponse> {
+        match &self.backend {
+            Backend::Direct(service) => service.create_key(request),
+            Backend::SocketService(client) => {
+                request.uri = request.uri.or_else(|| self.uri());
+                client.create_key(request)
+            }
+        }
+    }
+
+    /// List the objects of the token selected by the given URI, completed by the configured URI.
+    pub fn list_objects(&self, uri: Option<String>) -> anyhow::Result<Vec<String>> {
+        match &self.backend {
+            Backend::Direct(service) => service
+                .list_objects(ListObjectsRequest { uri })
+                .map(|response| response.objects),
+            Backend::SocketService(client) => client.list_objects(uri.or_else(|| self.uri())),
+        }
+    }
+
+    fn uri(&self) -> Option<String> {
+        self.uri.as_ref().map(|uri| uri.to_string())
+    }
+}
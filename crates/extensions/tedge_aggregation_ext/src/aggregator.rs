@@ -0,0 +1,353 @@
+use crate::config::Statistic;
+use batcher::BatchDriverOutput;
+use batcher::Batchable;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use tedge_api::measurement::parse_str;
+use tedge_api::measurement::MeasurementVisitor;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::Topic;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// A single value of a measurement series, as received on `te/+/+/+/+/m/<type>`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeasurementSample {
+    /// The topic of the measurement
+    pub topic: Topic,
+
+    /// The group of the series, if any
+    pub group: Option<String>,
+
+    /// The name of the series
+    pub name: String,
+
+    pub value: f64,
+
+    /// The timestamp given by the measurement, if any
+    pub time: Option<OffsetDateTime>,
+
+    /// When the measurement has been received, this time being used to batch the samples
+    pub received_at: OffsetDateTime,
+}
+
+impl Batchable for MeasurementSample {
+    // The samples of a series received at different times must go in the same batch,
+    // hence the reception time is part of the key
+    type Key = (String, Option<String>, String, OffsetDateTime);
+
+    fn key(&self) -> Self::Key {
+        (
+            self.topic.name.clone(),
+            self.group.clone(),
+            self.name.clone(),
+            self.received_at,
+        )
+    }
+
+    fn event_time(&self) -> OffsetDateTime {
+        self.received_at
+    }
+}
+
+/// Extract the samples of all the series of a measurement message
+///
+/// An invalid measurement is ignored, with a warning.
+pub fn measurement_samples(
+    message: &MqttMessage,
+    received_at: OffsetDateTime,
+) -> Vec<MeasurementSample> {
+    let mut collector = SampleCollector {
+        topic: message.topic.clone(),
+        received_at,
+        group: None,
+        time: None,
+        samples: vec![],
+    };
+    let Ok(payload) = message.payload_str() else {
+        warn!("Ignoring non UTF-8 measurement on {}", message.topic.name);
+        return vec![];
+    };
+    if let Err(err) = parse_str(payload, &mut collector) {
+        warn!(
+            "Ignoring invalid measurement on {}: {err}",
+            message.topic.name
+        );
+        return vec![];
+    }
+
+    let time = collector.time;
+    collector
+        .samples
+        .into_iter()
+        .map(|sample| MeasurementSample { time, ..sample })
+        .collect()
+}
+
+struct SampleCollector {
+    topic: Topic,
+    received_at: OffsetDateTime,
+    group: Option<String>,
+    time: Option<OffsetDateTime>,
+    samples: Vec<MeasurementSample>,
+}
+
+impl MeasurementVisitor for SampleCollector {
+    type Error = Infallible;
+
+    fn visit_timestamp(&mut self, value: OffsetDateTime) -> Result<(), Self::Error> {
+        self.time = Some(value);
+        Ok(())
+    }
+
+    fn visit_measurement(&mut self, name: &str, value: f64) -> Result<(), Self::Error> {
+        self.samples.push(MeasurementSample {
+            topic: self.topic.clone(),
+            group: self.group.clone(),
+            name: name.to_string(),
+            value,
+            time: None,
+            received_at: self.received_at,
+        });
+        Ok(())
+    }
+
+    fn visit_text_property(&mut self, _name: &str, _value: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_start_group(&mut self, group: &str) -> Result<(), Self::Error> {
+        self.group = Some(group.to_string());
+        Ok(())
+    }
+
+    fn visit_end_group(&mut self) -> Result<(), Self::Error> {
+        self.group = None;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct SeriesStatistics {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+    last: f64,
+}
+
+impl SeriesStatistics {
+    fn new(value: f64) -> Self {
+        SeriesStatistics {
+            min: value,
+            max: value,
+            sum: value,
+            count: 1,
+            last: value,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.count += 1;
+        self.last = value;
+    }
+
+    fn get(&self, statistic: Statistic) -> Value {
+        match statistic {
+            Statistic::Min => json!(self.min),
+            Statistic::Max => json!(self.max),
+            Statistic::Avg => json!(self.sum / self.count as f64),
+            Statistic::Count => json!(self.count),
+            Statistic::Last => json!(self.last),
+        }
+    }
+}
+
+#[derive(Default)]
+struct TopicStatistics {
+    time: Option<OffsetDateTime>,
+    series: BTreeMap<(Option<String>, String), SeriesStatistics>,
+}
+
+/// Aggregate a batch of samples into one measurement message per topic
+///
+/// Each series `<name>` is replaced by a group `<name>: { <statistic>: <value> }`,
+/// while the series of a group `<group>.<name>` are replaced by `<group>.<name>_<statistic>` series.
+/// The timestamp of the aggregated measurement is the last timestamp received on the topic, if any.
+pub fn aggregate(
+    statistics: &[Statistic],
+    batch: BatchDriverOutput<MeasurementSample>,
+) -> Vec<MqttMessage> {
+    let mut samples: Vec<MeasurementSample> = batch.into();
+    samples.sort_by_key(|sample| sample.received_at);
+
+    let mut topics: BTreeMap<String, (Topic, TopicStatistics)> = BTreeMap::new();
+    for sample in samples {
+        let (_, topic_stats) = topics
+            .entry(sample.topic.name.clone())
+            .or_insert_with(|| (sample.topic.clone(), TopicStatistics::default()));
+        if sample.time.is_some() {
+            topic_stats.time = sample.time;
+        }
+        topic_stats
+            .series
+            .entry((sample.group, sample.name))
+            .and_modify(|series| series.add(sample.value))
+            .or_insert_with(|| SeriesStatistics::new(sample.value));
+    }
+
+    topics
+        .into_values()
+        .map(|(topic, topic_stats)| {
+            let mut payload = Map::new();
+            if let Some(time) = topic_stats.time.and_then(|time| time.format(&Rfc3339).ok()) {
+                payload.insert("time".to_string(), time.into());
+            }
+            for ((group, name), series) in topic_stats.series {
+                let (group, prefix) = match group {
+                    None => (name, String::new()),
+                    Some(group) => (group, format!("{name}_")),
+                };
+                let Value::Object(fragment) = payload
+                    .entry(group)
+                    .or_insert_with(|| Value::Object(Map::new()))
+                else {
+                    continue;
+                };
+                for statistic in statistics {
+                    fragment.insert(
+                        format!("{prefix}{}", statistic.name()),
+                        series.get(*statistic),
+                    );
+                }
+            }
+            MqttMessage::new(&topic, Value::Object(payload).to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+    use time::Duration;
+
+    fn message(topic: &str, payload: &str) -> MqttMessage {
+        MqttMessage::new(&Topic::new_unchecked(topic), payload)
+    }
+
+    #[test]
+    fn extract_samples_from_measurement() {
+        let received_at = datetime!(2024-01-01 12:00:00 UTC);
+        let samples = measurement_samples(
+            &message(
+                "te/device/main///m/environment",
+                r#"{"time": "2024-01-01T11:59:59Z", "temperature": 21.5, "location": {"x": 1, "y": 2}}"#,
+            ),
+            received_at,
+        );
+
+        let series: Vec<_> = samples
+            .iter()
+            .map(|s| (s.group.as_deref(), s.name.as_str(), s.value))
+            .collect();
+        assert_eq!(
+            series,
+            vec![
+                (None, "temperature", 21.5),
+                (Some("location"), "x", 1.0),
+                (Some("location"), "y", 2.0),
+            ]
+        );
+        assert!(samples
+            .iter()
+            .all(|s| s.time == Some(datetime!(2024-01-01 11:59:59 UTC))));
+    }
+
+    #[test]
+    fn ignore_invalid_measurements() {
+        let samples = measurement_samples(
+            &message(
+                "te/device/main///m/environment",
+                r#"{"temperature": "hot"}"#,
+            ),
+            OffsetDateTime::now_utc(),
+        );
+        assert!(samples.is_empty());
+    }
+
+    #[test]
+    fn aggregate_samples_per_topic_and_series() {
+        let start = datetime!(2024-01-01 12:00:00 UTC);
+        let mut batch = vec![];
+        for (i, (temperature, x)) in [(20.0, 1.0), (23.0, 2.0), (22.0, 6.0)].iter().enumerate() {
+            let payload = format!(r#"{{"temperature": {temperature}, "location": {{"x": {x}}}}}"#);
+            batch.extend(measurement_samples(
+                &message("te/device/main///m/environment", &payload),
+                start + Duration::seconds(i as i64),
+            ));
+        }
+        batch.extend(measurement_samples(
+            &message(
+                "te/device/child///m/environment",
+                r#"{"time": "2024-01-01T12:00:00Z", "temperature": 18}"#,
+            ),
+            start,
+        ));
+
+        let messages = aggregate(
+            &[
+                Statistic::Min,
+                Statistic::Max,
+                Statistic::Avg,
+                Statistic::Count,
+                Statistic::Last,
+            ],
+            BatchDriverOutput::Batch(batch),
+        );
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].topic.name, "te/device/child///m/environment");
+        let payload: Value = serde_json::from_slice(messages[0].payload_bytes()).unwrap();
+        assert_eq!(
+            payload,
+            json!({
+                "time": "2024-01-01T12:00:00Z",
+                "temperature": {"min": 18.0, "max": 18.0, "avg": 18.0, "count": 1, "last": 18.0}
+            })
+        );
+
+        assert_eq!(messages[1].topic.name, "te/device/main///m/environment");
+        let payload: Value = serde_json::from_slice(messages[1].payload_bytes()).unwrap();
+        assert_eq!(
+            payload,
+            json!({
+                "temperature": {"min": 20.0, "max": 23.0, "avg": 21.666666666666668, "count": 3, "last": 22.0},
+                "location": {"x_min": 1.0, "x_max": 6.0, "x_avg": 3.0, "x_count": 3, "x_last": 6.0}
+            })
+        );
+    }
+
+    #[test]
+    fn aggregate_only_the_selected_statistics() {
+        let batch = measurement_samples(
+            &message("te/device/main///m/environment", r#"{"temperature": 20}"#),
+            OffsetDateTime::now_utc(),
+        );
+
+        let messages = aggregate(
+            &[Statistic::Avg, Statistic::Max],
+            BatchDriverOutput::Batch(batch),
+        );
+
+        let payload: Value = serde_json::from_slice(messages[0].payload_bytes()).unwrap();
+        assert_eq!(payload, json!({"temperature": {"avg": 20.0, "max": 20.0}}));
+    }
+}
@@ -0,0 +1,185 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use tedge_config::models::SecondsOrHumanTime;
+
+/// The aggregation rules, as read from `aggregation.toml`
+///
+/// ```toml
+/// [[aggregate]]
+/// type = "environment"
+/// window = "1m"
+/// statistics = ["min", "max", "avg"]
+///
+/// [[aggregate]]
+/// type = "vibration"
+/// window = "10s"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub struct AggregationConfig {
+    #[serde(default, rename = "aggregate")]
+    pub rules: Vec<AggregationRule>,
+}
+
+/// Aggregate all the measurements of a given type over a time window
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct AggregationRule {
+    /// The measurement type, i.e. the `<type>` of `te/+/+/+/+/m/<type>`
+    #[serde(rename = "type")]
+    pub measurement_type: String,
+
+    /// The duration of the aggregation window
+    pub window: SecondsOrHumanTime,
+
+    /// The statistics computed for each series over a window
+    #[serde(default = "Statistic::all")]
+    pub statistics: Vec<Statistic>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Statistic {
+    Min,
+    Max,
+    Avg,
+    Count,
+    Last,
+}
+
+impl Statistic {
+    fn all() -> Vec<Statistic> {
+        vec![
+            Statistic::Min,
+            Statistic::Max,
+            Statistic::Avg,
+            Statistic::Count,
+            Statistic::Last,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Statistic::Min => "min",
+            Statistic::Max => "max",
+            Statistic::Avg => "avg",
+            Statistic::Count => "count",
+            Statistic::Last => "last",
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AggregationConfigError {
+    #[error("Failed to read aggregation rules from {path}: {error}")]
+    FromIo { path: String, error: std::io::Error },
+
+    #[error("Invalid aggregation rules in {path}: {error}")]
+    FromToml {
+        path: String,
+        error: toml::de::Error,
+    },
+
+    #[error("Duplicate aggregation rule for measurement type: {0:?}")]
+    DuplicateRule(String),
+}
+
+impl AggregationConfig {
+    /// Read the aggregation rules from a TOML file
+    ///
+    /// Return an empty set of rules, i.e. no aggregation, if there is no such file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AggregationConfigError> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content, &path.display().to_string()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(AggregationConfigError::FromIo {
+                path: path.display().to_string(),
+                error,
+            }),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, AggregationConfigError> {
+        Self::parse(content, "<input>")
+    }
+
+    fn parse(content: &str, path: &str) -> Result<Self, AggregationConfigError> {
+        let config: AggregationConfig =
+            toml::from_str(content).map_err(|error| AggregationConfigError::FromToml {
+                path: path.to_string(),
+                error,
+            })?;
+
+        let mut types = HashSet::new();
+        for rule in config.rules.iter() {
+            if !types.insert(rule.measurement_type.as_str()) {
+                return Err(AggregationConfigError::DuplicateRule(
+                    rule.measurement_type.clone(),
+                ));
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_aggregation_rules() {
+        let config = AggregationConfig::from_toml(
+            r#"
+            [[aggregate]]
+            type = "environment"
+            window = "1m"
+            statistics = ["min", "max", "avg"]
+
+            [[aggregate]]
+            type = "vibration"
+            window = 10
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[0].measurement_type, "environment");
+        assert_eq!(config.rules[0].window.duration(), Duration::from_secs(60));
+        assert_eq!(
+            config.rules[0].statistics,
+            vec![Statistic::Min, Statistic::Max, Statistic::Avg]
+        );
+        assert_eq!(config.rules[1].window.duration(), Duration::from_secs(10));
+        assert_eq!(config.rules[1].statistics, Statistic::all());
+    }
+
+    #[test]
+    fn reject_duplicate_rules() {
+        let error = AggregationConfig::from_toml(
+            r#"
+            [[aggregate]]
+            type = "environment"
+            window = "1m"
+
+            [[aggregate]]
+            type = "environment"
+            window = "10s"
+        "#,
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, AggregationConfigError::DuplicateRule(t) if t == "environment"));
+    }
+
+    #[test]
+    fn no_aggregation_when_there_is_no_config_file() {
+        let ttd = tedge_test_utils::fs::TempTedgeDir::new();
+        let config = AggregationConfig::from_file(ttd.path().join("aggregation.toml")).unwrap();
+        assert!(config.is_empty());
+    }
+}
@@ -0,0 +1,134 @@
+//! Aggregate measurements over time windows, before they are forwarded to the cloud
+//!
+//! For each measurement type with an aggregation rule,
+//! the measurements published on `te/+/+/+/+/m/<type>` are not forwarded as is to the cloud mapper.
+//! These measurements are batched over the rule window,
+//! and only a summary of each series over this window (min, max, avg, count, last) is forwarded.
+mod aggregator;
+mod config;
+
+pub use aggregator::*;
+pub use config::*;
+
+use batcher::BatchDriverInput;
+use batcher::BatchingActorBuilder;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tedge_actors::DynSender;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::TopicFilter;
+use time::OffsetDateTime;
+
+/// A proxy to the MQTT actor diverting the measurements to be aggregated
+///
+/// This proxy is used in place of the MQTT actor by a cloud mapper:
+/// - the measurements with an aggregation rule are sent to batching actors,
+///   the mapper receiving the aggregated measurements instead of the raw ones,
+/// - all the other messages are forwarded unchanged,
+/// - the messages published by the mapper are sent to the MQTT actor.
+///
+/// Only one peer, the cloud mapper, is expected to consume the aggregated measurements.
+pub struct AggregationBuilder<'a, Mqtt> {
+    mqtt: &'a mut Mqtt,
+    aggregated: Arc<AggregatedTypes>,
+    batchers: Vec<(Vec<Statistic>, BatchingActorBuilder<MeasurementSample>)>,
+}
+
+struct AggregatedTypes {
+    mqtt_schema: MqttSchema,
+    types: HashSet<String>,
+}
+
+impl AggregatedTypes {
+    fn is_aggregated(&self, message: &MqttMessage) -> bool {
+        match self.mqtt_schema.entity_channel_of(&message.topic.name) {
+            Ok((_, Channel::Measurement { measurement_type })) => {
+                self.types.contains(&measurement_type)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a, Mqtt> AggregationBuilder<'a, Mqtt>
+where
+    Mqtt: MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>,
+{
+    pub fn new(config: AggregationConfig, mqtt_schema: MqttSchema, mqtt: &'a mut Mqtt) -> Self {
+        let types = config
+            .rules
+            .iter()
+            .map(|rule| rule.measurement_type.clone())
+            .collect();
+
+        let mut batchers = Vec::new();
+        for rule in config.rules {
+            let window = rule.window.duration().as_millis().min(u32::MAX as u128) as u32;
+            let batcher = BatchingActorBuilder::default().with_batching_window(window);
+            let topics = TopicFilter::new_unchecked(&format!(
+                "{}/+/+/+/+/m/{}",
+                mqtt_schema.root, rule.measurement_type
+            ));
+            batcher.connect_mapped_source(topics, mqtt, |message: MqttMessage| {
+                measurement_samples(&message, OffsetDateTime::now_utc())
+                    .into_iter()
+                    .map(BatchDriverInput::Event)
+            });
+            batchers.push((rule.statistics, batcher));
+        }
+
+        AggregationBuilder {
+            mqtt,
+            aggregated: Arc::new(AggregatedTypes { mqtt_schema, types }),
+            batchers,
+        }
+    }
+
+    /// Release the MQTT actor, returning the batching actors to be spawned
+    pub fn into_batchers(self) -> Vec<BatchingActorBuilder<MeasurementSample>> {
+        self.batchers
+            .into_iter()
+            .map(|(_, batcher)| batcher)
+            .collect()
+    }
+}
+
+impl<Mqtt> MessageSource<MqttMessage, TopicFilter> for AggregationBuilder<'_, Mqtt>
+where
+    Mqtt: MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>,
+{
+    fn connect_sink(&mut self, topics: TopicFilter, peer: &impl MessageSink<MqttMessage>) {
+        let sender: DynSender<MqttMessage> = peer.get_sender();
+
+        let aggregated = self.aggregated.clone();
+        self.mqtt
+            .connect_mapped_sink(topics.clone(), &sender, move |message| {
+                (!aggregated.is_aggregated(&message)).then_some(message)
+            });
+
+        for (statistics, batcher) in self.batchers.iter_mut() {
+            let statistics = statistics.clone();
+            let topics = topics.clone();
+            batcher.connect_mapped_sink(NoConfig, &sender, move |batch| {
+                aggregate(&statistics, batch)
+                    .into_iter()
+                    .filter(|message| topics.accept(message))
+                    .collect::<Vec<_>>()
+            });
+        }
+    }
+}
+
+impl<Mqtt> MessageSink<MqttMessage> for AggregationBuilder<'_, Mqtt>
+where
+    Mqtt: MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>,
+{
+    fn get_sender(&self) -> DynSender<MqttMessage> {
+        self.mqtt.get_sender()
+    }
+}
@@ -335,7 +335,8 @@ impl ConfigManagerWorker {
             return Err(anyhow::anyhow!("tedge_url not present in config update payload").into());
         };
 
-        let download_request = DownloadRequest::new(tedge_url, temp_path.as_std_path());
+        let download_request = DownloadRequest::new(tedge_url, temp_path.as_std_path())
+            .with_verification(request.sha256.clone(), request.signature.clone());
 
         info!(
             "Awaiting download for config type: {} from url: {}",
@@ -1,14 +1,18 @@
 use async_trait::async_trait;
 use certificate::CloudHttpConfig;
+use download::ArtifactCache;
+use download::DeltaInfo;
 use download::DownloadError;
 use download::DownloadInfo;
 use download::Downloader;
+use download::TrustStore;
 use log::info;
 use reqwest::header::HeaderMap;
 use reqwest::Identity;
 use std::marker::PhantomData;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tedge_actors::Message;
 use tedge_actors::Sequential;
 use tedge_actors::Server;
@@ -22,6 +26,9 @@ pub struct DownloadRequest {
     pub file_path: PathBuf,
     pub headers: HeaderMap,
     pub permission: Option<PermissionEntry>,
+    pub sha256: Option<String>,
+    pub signature: Option<String>,
+    pub delta: Option<DeltaInfo>,
 }
 
 impl DownloadRequest {
@@ -31,6 +38,9 @@ impl DownloadRequest {
             file_path: file_path.into(),
             headers: HeaderMap::new(),
             permission: None,
+            sha256: None,
+            signature: None,
+            delta: None,
         }
     }
 
@@ -40,6 +50,20 @@ impl DownloadRequest {
             ..self
         }
     }
+
+    /// Set the expected checksum and signature of the downloaded file
+    pub fn with_verification(self, sha256: Option<String>, signature: Option<String>) -> Self {
+        Self {
+            sha256,
+            signature,
+            ..self
+        }
+    }
+
+    /// Set the delta patch from which the file can be reconstructed
+    pub fn with_delta(self, delta: Option<DeltaInfo>) -> Self {
+        Self { delta, ..self }
+    }
 }
 
 pub type DownloadResult = Result<DownloadResponse, DownloadError>;
@@ -65,6 +89,8 @@ pub struct DownloaderActor<T> {
     key: std::marker::PhantomData<T>,
     identity: Option<Identity>,
     cloud_root_certs: CloudHttpConfig,
+    trust_store: Arc<TrustStore>,
+    artifact_cache: Option<Arc<ArtifactCache>>,
 }
 
 impl<T> Clone for DownloaderActor<T> {
@@ -74,6 +100,8 @@ impl<T> Clone for DownloaderActor<T> {
             key: self.key,
             identity: self.identity.clone(),
             cloud_root_certs: self.cloud_root_certs.clone(),
+            trust_store: self.trust_store.clone(),
+            artifact_cache: self.artifact_cache.clone(),
         }
     }
 }
@@ -85,6 +113,24 @@ impl<T: Message + Default> DownloaderActor<T> {
             key: PhantomData,
             identity,
             cloud_root_certs,
+            trust_store: Arc::new(TrustStore::default()),
+            artifact_cache: None,
+        }
+    }
+
+    /// Verify the signature of the downloaded files against the given trust store
+    pub fn with_trust_store(self, trust_store: Arc<TrustStore>) -> Self {
+        Self {
+            trust_store,
+            ..self
+        }
+    }
+
+    /// Cache the downloaded files, so they can be used as base for delta patches
+    pub fn with_artifact_cache(self, artifact_cache: Option<Arc<ArtifactCache>>) -> Self {
+        Self {
+            artifact_cache,
+            ..self
         }
     }
 
@@ -112,13 +158,18 @@ impl<T: Message> Server for DownloaderActor<T> {
     async fn handle(&mut self, id_request: Self::Request) -> Self::Response {
         let (id, request) = id_request;
 
-        let download_info = DownloadInfo::new(&request.url).with_headers(request.headers);
+        let download_info = DownloadInfo::new(&request.url)
+            .with_headers(request.headers)
+            .with_verification(request.sha256, request.signature)
+            .with_delta(request.delta);
 
         let downloader = Downloader::new(
             request.file_path.clone(),
             self.identity.clone(),
             self.cloud_root_certs.clone(),
-        );
+        )
+        .with_trust_store(self.trust_store.clone())
+        .with_artifact_cache(self.artifact_cache.clone());
 
         info!(
             "Downloading from url {} to location {}",
@@ -3,3 +3,8 @@ mod actor;
 mod tests;
 
 pub use actor::*;
+pub use download::ArtifactCache;
+pub use download::DeltaFormat;
+pub use download::DeltaInfo;
+pub use download::DownloadError;
+pub use download::TrustStore;
@@ -1,5 +1,6 @@
 use super::*;
 use certificate::CloudHttpConfig;
+use download::DownloadError;
 use reqwest::header::HeaderMap;
 use reqwest::header::AUTHORIZATION;
 use std::time::Duration;
@@ -75,6 +76,40 @@ async fn download_with_auth() {
     assert_eq!(response.as_ref().unwrap().url, server_url);
 }
 
+#[tokio::test]
+async fn reject_download_with_unexpected_checksum() {
+    let ttd = TempTedgeDir::new();
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("GET", "/")
+        .with_status(200)
+        .with_header("content-type", "text/plain")
+        .with_body("tampered content")
+        .create_async()
+        .await;
+
+    let target_path = ttd.path().join("downloaded_file");
+    let server_url = server.url();
+    let download_request = DownloadRequest::new(&server_url, &target_path)
+        .with_verification(Some("0123456789abcdef".to_string()), None);
+
+    let mut requester = spawn_downloader_actor().await;
+
+    let (_, response) = timeout(
+        TEST_TIMEOUT,
+        requester.await_response(("id".to_string(), download_request)),
+    )
+    .await
+    .expect("timeout")
+    .expect("channel error");
+
+    assert!(matches!(
+        response,
+        Err(DownloadError::ChecksumMismatch { .. })
+    ));
+    assert!(!target_path.exists());
+}
+
 async fn spawn_downloader_actor(
 ) -> ClientMessageBox<(String, DownloadRequest), (String, DownloadResult)> {
     let mut downloader_actor_builder =
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
 use crate::manager::LogPluginConfig;
+use crate::manager::LogSearch;
+use crate::manager::SEARCH_OPTIONS;
 use async_trait::async_trait;
 use camino::Utf8Path;
 use log::debug;
@@ -156,14 +158,17 @@ impl LogManagerActor {
     ) -> Result<(), LogManagementError> {
         let topic = request.topic(&self.config.mqtt_schema).as_ref().to_string();
         let request = &request.payload;
+        let search = LogSearch::from_request(request)?;
         let log_path = crate::manager::new_read_logs(
-            &self.plugin_config.files,
+            &self.plugin_config,
             &request.log_type,
             request.date_from,
+            request.date_to,
             request.lines.to_owned(),
-            &request.search_text,
+            &search,
             &self.config.tmp_dir,
-        )?;
+        )
+        .await?;
 
         let upload_request = UploadRequest::new(
             &request.tedge_url,
@@ -260,7 +265,10 @@ impl LogManagerActor {
     async fn publish_supported_log_types(&mut self) -> Result<(), ChannelError> {
         let mut types = self.plugin_config.get_all_file_types();
         types.sort();
-        let metadata = LogUploadCmdMetadata { types };
+        let metadata = LogUploadCmdMetadata {
+            types,
+            search_options: SEARCH_OPTIONS.map(str::to_string).to_vec(),
+        };
         self.messages
             .send(LogOutput::LogUploadCmdMetadata(metadata))
             .await
@@ -0,0 +1,294 @@
+use super::config::JournaldEntry;
+use super::config::PluginEntry;
+use super::error::LogRetrievalError;
+use super::log_utils::LineFilter;
+use std::collections::VecDeque;
+use std::process::Stdio;
+use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncReadExt;
+use tokio::io::BufReader;
+use tokio::process::Command;
+
+const JOURNALCTL: &str = "journalctl";
+const GET: &str = "get";
+
+/// The maximum duration of a command retrieving logs, after which the command is killed
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Read the last `max_lines` selected lines of the journald logs of a systemd unit
+pub(crate) async fn read_journald_lines(
+    entry: &JournaldEntry,
+    max_lines: usize,
+    line_filter: &LineFilter<'_>,
+) -> Result<VecDeque<String>, LogRetrievalError> {
+    read_command_lines(
+        journalctl_command(entry, line_filter),
+        max_lines,
+        line_filter,
+        COMMAND_TIMEOUT,
+    )
+    .await
+}
+
+/// Read the last `max_lines` selected lines returned by a log plugin
+pub(crate) async fn read_plugin_lines(
+    entry: &PluginEntry,
+    log_type: &str,
+    max_lines: usize,
+    line_filter: &LineFilter<'_>,
+) -> Result<VecDeque<String>, LogRetrievalError> {
+    read_command_lines(
+        plugin_command(entry, log_type, line_filter),
+        max_lines,
+        line_filter,
+        COMMAND_TIMEOUT,
+    )
+    .await
+}
+
+fn journalctl_command(entry: &JournaldEntry, line_filter: &LineFilter) -> Command {
+    let mut command = Command::new(JOURNALCTL);
+    command
+        .arg("--no-pager")
+        .arg("--quiet")
+        .arg("--utc")
+        .arg("--output=short-iso")
+        .arg(format!("--unit={}", entry.unit))
+        .arg(format!(
+            "--since=@{}",
+            line_filter.date_from.unix_timestamp()
+        ))
+        .arg(format!("--until=@{}", line_filter.date_to.unix_timestamp()));
+    command
+}
+
+fn plugin_command(entry: &PluginEntry, log_type: &str, line_filter: &LineFilter) -> Command {
+    let mut command = Command::new(&entry.path);
+    command
+        .arg(GET)
+        .arg(log_type)
+        .arg("--since")
+        .arg(rfc3339(line_filter.date_from))
+        .arg("--until")
+        .arg(rfc3339(line_filter.date_to));
+    command
+}
+
+fn rfc3339(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| timestamp.unix_timestamp().to_string())
+}
+
+/// Run a command and keep the last `max_lines` selected lines of its output
+///
+/// The command is expected to print the log lines on its stdout, the oldest first,
+/// and to exit with a zero status code.
+/// The output is processed line by line as produced, only the selected lines being kept,
+/// and the command is killed if still running after the given timeout.
+async fn read_command_lines(
+    mut command: Command,
+    max_lines: usize,
+    line_filter: &LineFilter<'_>,
+    timeout: Duration,
+) -> Result<VecDeque<String>, LogRetrievalError> {
+    let program = command.as_std().get_program().to_string_lossy().to_string();
+    let command_failed = |reason: String| LogRetrievalError::CommandFailed {
+        command: program.clone(),
+        reason,
+    };
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| command_failed(err.to_string()))?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+
+    let mut selector = line_filter.selector();
+    let mut selected_lines = VecDeque::new();
+    let read_stdout = async {
+        let mut stdout = BufReader::new(stdout);
+        let mut line = Vec::new();
+        while stdout.read_until(b'\n', &mut line).await? > 0 {
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\n', '\r']);
+            selector.keep_last(&mut selected_lines, text, max_lines);
+            line.clear();
+        }
+        Ok::<(), std::io::Error>(())
+    };
+    let mut error_output = Vec::new();
+    let read_stderr = stderr.read_to_end(&mut error_output);
+
+    let status = match tokio::time::timeout(timeout, async {
+        let (stdout, stderr) = tokio::join!(read_stdout, read_stderr);
+        stdout?;
+        stderr?;
+        child.wait().await
+    })
+    .await
+    {
+        Ok(Ok(status)) => status,
+        Ok(Err(err)) => return Err(command_failed(err.to_string())),
+        Err(_) => return Err(command_failed(format!("still running after {timeout:?}"))),
+    };
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&error_output);
+        return Err(command_failed(match stderr.trim() {
+            "" => status.to_string(),
+            stderr => stderr.to_string(),
+        }));
+    }
+    Ok(selected_lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::LogSearch;
+    use std::os::unix::fs::PermissionsExt;
+    use tedge_test_utils::fs::TempTedgeDir;
+    use time::macros::datetime;
+
+    fn line_filter(search: &LogSearch) -> LineFilter {
+        LineFilter {
+            date_from: datetime!(2024-01-01 12:00:00 +00:00),
+            date_to: datetime!(2024-01-01 13:00:00 +00:00),
+            search,
+        }
+    }
+
+    fn search_text(text: &str) -> LogSearch {
+        LogSearch::new(Some(text), None, false, false, 0).unwrap()
+    }
+
+    fn plugin(tempdir: &TempTedgeDir, script: &str) -> PluginEntry {
+        let path = tempdir.path().join("log-plugin");
+        std::fs::write(&path, format!("#!/bin/sh\n{script}")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        PluginEntry {
+            path: path.to_str().unwrap().to_string(),
+            config_type: "containers".to_string(),
+        }
+    }
+
+    #[test]
+    fn journalctl_is_called_for_the_unit_and_date_range() {
+        let entry = JournaldEntry {
+            unit: "mosquitto.service".to_string(),
+            config_type: "mosquitto".to_string(),
+        };
+
+        let command = journalctl_command(&entry, &line_filter(&LogSearch::default()));
+        let command = command.as_std();
+
+        assert_eq!(command.get_program(), JOURNALCTL);
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .collect();
+        assert!(args.contains(&"--unit=mosquitto.service"));
+        assert!(args.contains(&"--since=@1704110400"));
+        assert!(args.contains(&"--until=@1704114000"));
+    }
+
+    #[tokio::test]
+    async fn plugin_output_is_filtered() {
+        let tempdir = TempTedgeDir::new();
+        let entry = plugin(
+            &tempdir,
+            r#"echo "args: $*"
+echo "2024-01-01T11:00:00Z ERROR too old"
+echo "2024-01-01T12:10:00Z ERROR first"
+echo "2024-01-01T12:20:00Z INFO second"
+echo "2024-01-01T12:30:00Z ERROR third"
+echo "2024-01-01T12:40:00Z ERROR fourth"
+"#,
+        );
+
+        let search = search_text("ERROR");
+        let lines = read_plugin_lines(&entry, "containers", 2, &line_filter(&search))
+            .await
+            .unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "2024-01-01T12:30:00Z ERROR third",
+                "2024-01-01T12:40:00Z ERROR fourth"
+            ]
+        );
+
+        let lines = read_plugin_lines(&entry, "containers", 1, &line_filter(&LogSearch::default()))
+            .await
+            .unwrap();
+        assert_eq!(lines, vec!["2024-01-01T12:40:00Z ERROR fourth"]);
+
+        let search = search_text("args");
+        let lines = read_plugin_lines(&entry, "containers", 10, &line_filter(&search))
+            .await
+            .unwrap();
+        assert_eq!(
+            lines,
+            vec!["args: get containers --since 2024-01-01T12:00:00Z --until 2024-01-01T13:00:00Z"]
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_plugin_is_an_error() {
+        let tempdir = TempTedgeDir::new();
+        let entry = plugin(&tempdir, "echo 'unknown container' >&2\nexit 1\n");
+
+        let error = read_plugin_lines(
+            &entry,
+            "containers",
+            10,
+            &line_filter(&LogSearch::default()),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(error, LogRetrievalError::CommandFailed { ref reason, .. } if reason == "unknown container"),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn plugin_running_too_long_is_killed() {
+        let tempdir = TempTedgeDir::new();
+        let entry = plugin(&tempdir, "echo 'first line'\nsleep 10\n");
+        let search = LogSearch::default();
+
+        let error = read_command_lines(
+            plugin_command(&entry, "containers", &line_filter(&search)),
+            10,
+            &line_filter(&search),
+            Duration::from_millis(200),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(error, LogRetrievalError::CommandFailed { ref reason, .. } if reason == "still running after 200ms"),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn only_the_last_lines_of_a_large_output_are_kept() {
+        let tempdir = TempTedgeDir::new();
+        let entry = plugin(&tempdir, "seq 1 100000\n");
+        let search = LogSearch::default();
+
+        let lines = read_plugin_lines(&entry, "containers", 3, &line_filter(&search))
+            .await
+            .unwrap();
+        assert_eq!(lines, vec!["99998", "99999", "100000"]);
+    }
+}
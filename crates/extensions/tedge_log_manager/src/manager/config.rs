@@ -7,7 +7,12 @@ use std::path::Path;
 
 #[derive(Clone, Deserialize, Debug, Eq, PartialEq, Default)]
 pub struct LogPluginConfig {
+    #[serde(default)]
     pub files: Vec<FileEntry>,
+    #[serde(default)]
+    pub journald: Vec<JournaldEntry>,
+    #[serde(default)]
+    pub plugins: Vec<PluginEntry>,
 }
 
 #[derive(Deserialize, Debug, Eq, Default, Clone)]
@@ -23,6 +28,25 @@ impl PartialEq for FileEntry {
     }
 }
 
+/// The logs of a systemd unit, as stored by journald
+#[derive(Deserialize, Debug, Eq, PartialEq, Default, Clone)]
+pub struct JournaldEntry {
+    pub(crate) unit: String,
+    #[serde(rename = "type")]
+    pub config_type: String,
+}
+
+/// An executable returning the logs of a given type
+///
+/// The plugin is invoked as `<path> get <type> --since <date_from> --until <date_to>`,
+/// and must print the log lines on its standard output, the oldest first.
+#[derive(Deserialize, Debug, Eq, PartialEq, Default, Clone)]
+pub struct PluginEntry {
+    pub(crate) path: String,
+    #[serde(rename = "type")]
+    pub config_type: String,
+}
+
 impl LogPluginConfig {
     pub fn new(config_file_path: &Path) -> Self {
         Self::read_config(config_file_path)
@@ -53,6 +77,8 @@ impl LogPluginConfig {
         self.files
             .iter()
             .map(|x| x.config_type.to_string())
+            .chain(self.journald.iter().map(|x| x.config_type.to_string()))
+            .chain(self.plugins.iter().map(|x| x.config_type.to_string()))
             .collect::<HashSet<_>>()
             .iter()
             .map(|x| x.to_string())
@@ -72,9 +98,38 @@ fn test_no_duplicated_file_types() {
             config_type: "type_one".to_string(),
         },
     ];
-    let logs_config = LogPluginConfig { files };
+    let logs_config = LogPluginConfig {
+        files,
+        ..Default::default()
+    };
     assert_eq!(
         logs_config.get_all_file_types(),
         vec!["type_one".to_string()]
     );
 }
+
+#[test]
+fn test_journald_and_plugin_log_sources() {
+    let logs_config: LogPluginConfig = toml::from_str(
+        r#"
+        journald = [
+            { type = "mosquitto", unit = "mosquitto.service" },
+        ]
+        plugins = [
+            { type = "containers", path = "/usr/share/tedge/log-plugins/docker" },
+        ]
+        "#,
+    )
+    .unwrap();
+
+    assert!(logs_config.files.is_empty());
+    assert_eq!(logs_config.journald[0].unit, "mosquitto.service");
+    assert_eq!(
+        logs_config.plugins[0].path,
+        "/usr/share/tedge/log-plugins/docker"
+    );
+
+    let mut types = logs_config.get_all_file_types();
+    types.sort();
+    assert_eq!(types, vec!["containers", "mosquitto"]);
+}
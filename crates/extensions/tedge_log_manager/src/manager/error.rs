@@ -20,6 +20,12 @@ pub enum LogRetrievalError {
     #[error("Log file has maximum number of lines.")]
     MaxLines,
 
+    #[error("Failed to read logs with {command}: {reason}")]
+    CommandFailed { command: String, reason: String },
+
+    #[error("Invalid search pattern {pattern:?}: {reason}")]
+    InvalidSearchPattern { pattern: String, reason: String },
+
     #[error("No logs found for log type {log_type:?}")]
     NoLogsAvailableForType { log_type: String },
 }
@@ -1,34 +1,78 @@
+use super::command_logs::read_journald_lines;
+use super::command_logs::read_plugin_lines;
 use super::config::FileEntry;
+use super::config::LogPluginConfig;
 use super::error::LogRetrievalError;
+use super::search::LogSearch;
 use easy_reader::EasyReader;
+use flate2::read::MultiGzDecoder;
 use glob::glob;
 use regex::Regex;
 use std::cmp::Reverse;
 use std::collections::VecDeque;
 use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
 use time::OffsetDateTime;
+use time::PrimitiveDateTime;
 
 /// read any log file coming from `obj.log.log_type`
-pub fn new_read_logs(
-    files: &[FileEntry],
+///
+/// Rotated log files compressed with gzip or zstd are transparently decompressed.
+/// Only the lines with a timestamp in the `date_from..=date_to` range are returned,
+/// the lines with no recognizable timestamp being kept.
+/// Among these, only the lines matching the `search` criteria are returned,
+/// along with their context lines.
+///
+/// The log files are read first, the most recent first,
+/// then the journald units and the log plugins configured for that type.
+pub async fn new_read_logs(
+    plugin_config: &LogPluginConfig,
     log_type: &str,
     date_from: OffsetDateTime,
+    date_to: OffsetDateTime,
     lines: usize,
-    search_text: &Option<String>,
+    search: &LogSearch,
     tmp_dir: &Path,
 ) -> Result<PathBuf, LogRetrievalError> {
+    let journald_units: Vec<_> = plugin_config
+        .journald
+        .iter()
+        .filter(|entry| entry.config_type == log_type)
+        .collect();
+    let plugins: Vec<_> = plugin_config
+        .plugins
+        .iter()
+        .filter(|entry| entry.config_type == log_type)
+        .collect();
+
     //filter logs on type and date
-    let logfiles_to_read = filter_logs(files, log_type, date_from)?;
+    let logfiles_to_read = match filter_logs(&plugin_config.files, log_type, date_from) {
+        Err(LogRetrievalError::NoLogsAvailableForType { .. })
+            if !journald_units.is_empty() || !plugins.is_empty() =>
+        {
+            vec![]
+        }
+        result => result?,
+    };
 
     let temp_path = tmp_dir.join(format!("{log_type}-{}", rand::random::<u128>()));
     let mut temp_file = File::create(&temp_path)?;
 
+    let line_filter = LineFilter {
+        date_from,
+        date_to,
+        search,
+    };
     let mut line_counter = 0usize;
     for logfile in logfiles_to_read {
-        match read_log_content(logfile.as_path(), line_counter, lines, search_text) {
+        match read_log_content(logfile.as_path(), line_counter, lines, &line_filter) {
             Ok((lines, file_content)) => {
                 line_counter = lines;
                 temp_file.write_all(file_content.as_bytes())?;
@@ -43,58 +87,287 @@ pub fn new_read_logs(
         };
     }
 
+    for entry in journald_units {
+        if line_counter >= lines {
+            break;
+        }
+        let unit_lines = read_journald_lines(entry, lines - line_counter, &line_filter).await?;
+        line_counter += unit_lines.len();
+        let header = format!("journald: {}", entry.unit);
+        temp_file.write_all(format_log_content(&header, &unit_lines).as_bytes())?;
+    }
+
+    for entry in plugins {
+        if line_counter >= lines {
+            break;
+        }
+        let plugin_lines =
+            read_plugin_lines(entry, log_type, lines - line_counter, &line_filter).await?;
+        line_counter += plugin_lines.len();
+        let header = format!("plugin: {}", entry.path);
+        temp_file.write_all(format_log_content(&header, &plugin_lines).as_bytes())?;
+    }
+
     temp_file.flush()?;
 
     Ok(temp_path)
 }
 
+/// The criteria used to select the log lines to be uploaded
+pub(crate) struct LineFilter<'a> {
+    pub(crate) date_from: OffsetDateTime,
+    pub(crate) date_to: OffsetDateTime,
+    pub(crate) search: &'a LogSearch,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum LineMatch {
+    /// The line matches the search criteria
+    Selected,
+    /// The line doesn't match the search text, but can be kept as context of a selected line
+    Unmatched,
+    Rejected,
+    /// The line is older than `date_from`, and so are all the previous lines of the file
+    TooOld,
+}
+
+impl LineFilter<'_> {
+    pub(crate) fn check(&self, line: &str) -> LineMatch {
+        if let Some(timestamp) = line_timestamp(line, self.date_to) {
+            if timestamp < self.date_from {
+                return LineMatch::TooOld;
+            }
+            if timestamp > self.date_to {
+                return LineMatch::Rejected;
+            }
+        }
+        if self.search.is_excluded(line) {
+            LineMatch::Rejected
+        } else if self.search.is_hit(line) {
+            LineMatch::Selected
+        } else {
+            LineMatch::Unmatched
+        }
+    }
+
+    pub(crate) fn selector(&self) -> LineSelector<'_> {
+        LineSelector {
+            filter: self,
+            before: VecDeque::new(),
+            after: 0,
+        }
+    }
+}
+
+/// Select the lines matching a [LineFilter], along with their context lines
+///
+/// The context being symmetric, the lines can be read either forward or backward:
+/// the selected lines are returned in reading order.
+pub(crate) struct LineSelector<'a> {
+    filter: &'a LineFilter<'a>,
+    /// The last unmatched lines, to be kept if the next line is selected
+    before: VecDeque<String>,
+    /// The number of unmatched lines still to be kept after the last selected line
+    after: usize,
+}
+
+impl LineSelector<'_> {
+    /// Return the lines to be kept now that this line has been read
+    pub(crate) fn select(&mut self, line: &str) -> Result<Vec<String>, LineMatch> {
+        let context_lines = self.filter.search.context_lines();
+        match self.filter.check(line) {
+            LineMatch::Selected => {
+                self.after = context_lines;
+                let mut lines: Vec<String> = self.before.drain(..).collect();
+                lines.push(line.to_string());
+                Ok(lines)
+            }
+            LineMatch::Unmatched if self.after > 0 => {
+                self.after -= 1;
+                Ok(vec![line.to_string()])
+            }
+            LineMatch::Unmatched => {
+                if context_lines > 0 {
+                    if self.before.len() == context_lines {
+                        self.before.pop_front();
+                    }
+                    self.before.push_back(line.to_string());
+                }
+                Ok(vec![])
+            }
+            outcome => Err(outcome),
+        }
+    }
+
+    /// Read a line forward, keeping the last `max_lines` selected lines
+    pub(crate) fn keep_last(
+        &mut self,
+        selected_lines: &mut VecDeque<String>,
+        line: &str,
+        max_lines: usize,
+    ) {
+        if let Ok(lines) = self.select(line) {
+            for line in lines {
+                if selected_lines.len() == max_lines {
+                    selected_lines.pop_front();
+                }
+                selected_lines.push_back(line);
+            }
+        }
+    }
+}
+
+/// Parse the timestamp at the beginning of a log line, if any
+///
+/// Two formats are recognized:
+/// - RFC 3339, possibly between square brackets, e.g. `2024-01-01T12:00:00Z` or `[2024-01-01T12:00:00.123+01:00]`
+/// - the traditional syslog format, e.g. `Jan  1 12:00:00`, which is interpreted as UTC.
+///   As there is no year, this is the year of the `reference` time, or the previous one
+///   if this gives a time after the `reference`.
+fn line_timestamp(line: &str, reference: OffsetDateTime) -> Option<OffsetDateTime> {
+    let first_word = line.split_whitespace().next()?;
+    let rfc3339 = first_word.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(timestamp) = OffsetDateTime::parse(rfc3339, &Rfc3339) {
+        return Some(timestamp);
+    }
+
+    let syslog_date = line.get(..15)?;
+    let parse_with_year = |year: i32| {
+        PrimitiveDateTime::parse(
+            &format!("{year} {syslog_date}"),
+            format_description!(
+                "[year] [month repr:short] [day padding:space] [hour]:[minute]:[second]"
+            ),
+        )
+        .ok()
+        .map(PrimitiveDateTime::assume_utc)
+    };
+    match parse_with_year(reference.year())? {
+        timestamp if timestamp > reference => parse_with_year(reference.year() - 1),
+        timestamp => Some(timestamp),
+    }
+}
+
+/// The compression of a rotated log file, as detected from the first bytes of the file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn detect(logfile: &Path) -> Result<Option<Compression>, LogRetrievalError> {
+        let mut magic = Vec::with_capacity(4);
+        File::open(logfile)?.take(4).read_to_end(&mut magic)?;
+        match magic.as_slice() {
+            [0x1f, 0x8b, ..] => Ok(Some(Compression::Gzip)),
+            [0x28, 0xb5, 0x2f, 0xfd] => Ok(Some(Compression::Zstd)),
+            _ => Ok(None),
+        }
+    }
+}
+
 fn read_log_content(
     logfile: &Path,
-    mut line_counter: usize,
+    line_counter: usize,
     max_lines: usize,
-    search_text: &Option<String>,
+    line_filter: &LineFilter,
 ) -> Result<(usize, String), LogRetrievalError> {
     if line_counter >= max_lines {
-        Err(LogRetrievalError::MaxLines)
-    } else {
-        let mut file_content_as_vec = VecDeque::new();
-        let file = std::fs::File::open(logfile)?;
-        let file_name = format!(
-            "filename: {}\n",
-            logfile.file_name().unwrap().to_str().unwrap() // never fails because we check file exists
-        );
-        let reader = EasyReader::new(file);
-        match reader {
-            Ok(mut reader) => {
-                reader.eof();
-                while line_counter < max_lines {
-                    if let Some(haystack) = reader.prev_line()? {
-                        if let Some(needle) = &search_text {
-                            if haystack.contains(needle) {
-                                file_content_as_vec.push_front(format!("{}\n", haystack));
-                                line_counter += 1;
-                            }
-                        } else {
-                            file_content_as_vec.push_front(format!("{}\n", haystack));
-                            line_counter += 1;
-                        }
-                    } else {
-                        // there are no more lines.prev_line()
-                        break;
-                    }
-                }
+        return Err(LogRetrievalError::MaxLines);
+    }
+
+    let remaining_lines = max_lines - line_counter;
+    let file_lines = match Compression::detect(logfile)? {
+        None => read_plain_log_lines(logfile, remaining_lines, line_filter)?,
+        Some(compression) => Some(read_compressed_log_lines(
+            logfile,
+            compression,
+            remaining_lines,
+            line_filter,
+        )?),
+    };
+    let Some(file_lines) = file_lines else {
+        return Ok((line_counter, String::new()));
+    };
 
-                file_content_as_vec.push_front(file_name);
+    let header = format!(
+        "filename: {}",
+        logfile.file_name().unwrap().to_str().unwrap() // never fails because we check file exists
+    );
+    let file_content = format_log_content(&header, &file_lines);
+    Ok((line_counter + file_lines.len(), file_content))
+}
 
-                let file_content = file_content_as_vec
-                    .iter()
-                    .map(|x| x.to_string())
-                    .collect::<String>();
-                Ok((line_counter, file_content))
+/// Format the lines read from a log source, prefixed by a header line naming this source
+fn format_log_content(header: &str, lines: &VecDeque<String>) -> String {
+    let mut content = format!("{header}\n");
+    for line in lines.iter() {
+        content.push_str(line);
+        content.push('\n');
+    }
+    content
+}
+
+/// Read the last `max_lines` selected lines of a plain text file, reading the file backward
+///
+/// Return `None` if the file cannot be read backward, e.g. when empty.
+fn read_plain_log_lines(
+    logfile: &Path,
+    max_lines: usize,
+    line_filter: &LineFilter,
+) -> Result<Option<VecDeque<String>>, LogRetrievalError> {
+    let file = File::open(logfile)?;
+    let Ok(mut reader) = EasyReader::new(file) else {
+        return Ok(None);
+    };
+
+    let mut selector = line_filter.selector();
+    let mut file_lines = VecDeque::new();
+    reader.eof();
+    while file_lines.len() < max_lines {
+        let Some(line) = reader.prev_line()? else {
+            // there are no more lines
+            break;
+        };
+        match selector.select(&line) {
+            Ok(lines) => {
+                for line in lines.into_iter().take(max_lines - file_lines.len()) {
+                    file_lines.push_front(line)
+                }
             }
-            Err(_err) => Ok((line_counter, String::new())),
+            Err(LineMatch::TooOld) => break,
+            Err(_) => (),
         }
     }
+    Ok(Some(file_lines))
+}
+
+/// Read the last `max_lines` selected lines of a compressed file
+///
+/// A compressed file cannot be read backward, hence the whole file is decompressed,
+/// keeping only the last selected lines.
+fn read_compressed_log_lines(
+    logfile: &Path,
+    compression: Compression,
+    max_lines: usize,
+    line_filter: &LineFilter,
+) -> Result<VecDeque<String>, LogRetrievalError> {
+    let file = File::open(logfile)?;
+    let decoder: Box<dyn Read> = match compression {
+        Compression::Gzip => Box::new(MultiGzDecoder::new(file)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(file)?),
+    };
+
+    let mut selector = line_filter.selector();
+    let mut file_lines = VecDeque::new();
+    for line in BufReader::new(decoder).split(b'\n') {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches('\r');
+        selector.keep_last(&mut file_lines, line, max_lines);
+    }
+    Ok(file_lines)
 }
 
 fn filter_logs(
@@ -336,16 +609,20 @@ mod tests {
 
         let line_counter = 0;
         let max_lines = 4;
-        let filter_text = None;
+        let line_filter = LineFilter {
+            date_from: OffsetDateTime::UNIX_EPOCH,
+            date_to: OffsetDateTime::now_utc(),
+            search: &LogSearch::default(),
+        };
 
         let (line_counter, result) =
-            read_log_content(Path::new(file_path), line_counter, max_lines, &filter_text).unwrap();
+            read_log_content(Path::new(file_path), line_counter, max_lines, &line_filter).unwrap();
 
         assert_eq!(line_counter, max_lines);
         assert_eq!(result, "filename: file_a_one\nthis is the second line.\nthis is the third line.\nthis is the forth line.\nthis is the fifth line.\n");
     }
 
-    #[test]
+    #[tokio::test]
     /// Inserting 5 lines of logs for each log file { file_a, ..., file_d }.
     /// Each line contains the text: "this is the { line_number } line of { file_name }
     /// where line_number { first, second, third, forth, fifth }
@@ -364,7 +641,7 @@ mod tests {
     ///
     /// - all logs from file_d (5)
     /// - last two logs from file_b (2)
-    fn test_read_log_content_multiple_files() {
+    async fn test_read_log_content_multiple_files() {
         let (tempdir, files) = prepare();
         let tempdir_path = tempdir.path().to_str().unwrap();
 
@@ -390,14 +667,20 @@ mod tests {
             let new_mtime = FileTime::from_unix_time(m_time, 0);
             set_file_mtime(file_path, new_mtime).unwrap();
         }
+        let plugin_config = LogPluginConfig {
+            files,
+            ..Default::default()
+        };
         let temp_path = new_read_logs(
-            &files,
+            &plugin_config,
             "type_one",
             datetime!(1970-01-01 00:00:03 +00:00),
+            OffsetDateTime::now_utc(),
             7,
-            &None,
+            &LogSearch::default(),
             tempdir.path(),
         )
+        .await
         .unwrap();
 
         assert_eq!(temp_path.parent().unwrap(), tempdir.path());
@@ -405,4 +688,169 @@ mod tests {
         let result = std::fs::read_to_string(temp_path).unwrap();
         assert_eq!(result, String::from("filename: file_d_one\nthis is the first line of file_d_one.\nthis is the second line of file_d_one.\nthis is the third line of file_d_one.\nthis is the forth line of file_d_one.\nthis is the fifth line of file_d_one.\nfilename: file_b_one\nthis is the forth line of file_b_one.\nthis is the fifth line of file_b_one.\n"))
     }
+
+    #[tokio::test]
+    /// Rotated log files are decompressed, whatever their name.
+    ///
+    /// Requesting 4 lines out of:
+    /// - file_d_one (plain, most recent): 2 lines
+    /// - file_b_one (zstd): 2 lines
+    /// - file_a_one (gzip, oldest): 2 lines
+    ///
+    /// gives all the lines of file_d_one and file_b_one.
+    async fn test_read_compressed_log_files() {
+        let (tempdir, files) = prepare();
+        let tempdir_path = tempdir.path().to_str().unwrap();
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(b"first line of file_a_one\nsecond line of file_a_one\n")
+            .unwrap();
+        let zstd = zstd::encode_all(
+            &b"first line of file_b_one\nsecond line of file_b_one\n"[..],
+            0,
+        )
+        .unwrap();
+
+        for (file_name, content, m_time) in [
+            ("file_a_one", gzip.finish().unwrap(), 4),
+            ("file_b_one", zstd, 5),
+            (
+                "file_d_one",
+                b"first line of file_d_one\nsecond line of file_d_one".to_vec(),
+                6,
+            ),
+        ] {
+            let file_path = format!("{tempdir_path}/{file_name}");
+            std::fs::write(&file_path, content).unwrap();
+            set_file_mtime(&file_path, FileTime::from_unix_time(m_time, 0)).unwrap();
+        }
+
+        let plugin_config = LogPluginConfig {
+            files,
+            ..Default::default()
+        };
+        let temp_path = new_read_logs(
+            &plugin_config,
+            "type_one",
+            OffsetDateTime::UNIX_EPOCH,
+            OffsetDateTime::now_utc(),
+            4,
+            &LogSearch::default(),
+            tempdir.path(),
+        )
+        .await
+        .unwrap();
+
+        let result = std::fs::read_to_string(temp_path).unwrap();
+        assert_eq!(result, "filename: file_d_one\nfirst line of file_d_one\nsecond line of file_d_one\nfilename: file_b_one\nfirst line of file_b_one\nsecond line of file_b_one\n");
+    }
+
+    #[test]
+    /// Only the lines in the requested date range are read,
+    /// the lines with no timestamp being kept.
+    fn test_filter_log_lines_on_timestamp() {
+        let (tempdir, _) = prepare();
+        let tempdir_path = tempdir.path().to_str().unwrap();
+        let plain_file = format!("{tempdir_path}/file_a_one");
+        let gzip_file = format!("{tempdir_path}/file_b_one");
+
+        let data = "2024-01-01T11:00:00Z too old\n\
+            [2024-01-01T12:00:00+00:00] in range\n\
+            no timestamp\n\
+            Jan  1 12:30:00 host syslog: in range\n\
+            2024-01-01T14:00:00Z too recent";
+        std::fs::write(&plain_file, data).unwrap();
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(data.as_bytes()).unwrap();
+        std::fs::write(&gzip_file, gzip.finish().unwrap()).unwrap();
+
+        let line_filter = LineFilter {
+            date_from: datetime!(2024-01-01 12:00:00 +00:00),
+            date_to: datetime!(2024-01-01 13:00:00 +00:00),
+            search: &LogSearch::default(),
+        };
+
+        for file in [plain_file, gzip_file] {
+            let (line_counter, result) =
+                read_log_content(Path::new(&file), 0, 100, &line_filter).unwrap();
+            assert_eq!(line_counter, 3);
+            assert!(result.ends_with("[2024-01-01T12:00:00+00:00] in range\nno timestamp\nJan  1 12:30:00 host syslog: in range\n"), "{result}");
+        }
+    }
+
+    #[test]
+    /// The lines around each hit are kept, the excluded lines being ignored,
+    /// whether the file is read backward (plain) or forward (compressed).
+    fn test_search_log_lines_with_context() {
+        let (tempdir, _) = prepare();
+        let tempdir_path = tempdir.path().to_str().unwrap();
+        let plain_file = format!("{tempdir_path}/file_a_one");
+        let gzip_file = format!("{tempdir_path}/file_b_one");
+
+        let data = "line 1\n\
+            line 2\n\
+            line 3 DEBUG\n\
+            line 4\n\
+            line 5 Error\n\
+            line 6\n\
+            line 7\n\
+            line 8\n\
+            line 9\n\
+            line 10 ERROR\n\
+            line 11 error\n\
+            line 12";
+        std::fs::write(&plain_file, data).unwrap();
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(data.as_bytes()).unwrap();
+        std::fs::write(&gzip_file, gzip.finish().unwrap()).unwrap();
+
+        let search = LogSearch::new(Some("error"), Some("debug"), false, true, 2).unwrap();
+        let line_filter = LineFilter {
+            date_from: OffsetDateTime::UNIX_EPOCH,
+            date_to: OffsetDateTime::now_utc(),
+            search: &search,
+        };
+
+        for file in [&plain_file, &gzip_file] {
+            let (line_counter, result) =
+                read_log_content(Path::new(file), 0, 100, &line_filter).unwrap();
+            assert_eq!(line_counter, 10);
+            assert!(
+                result.ends_with(
+                    "\nline 2\nline 4\nline 5 Error\nline 6\nline 7\n\
+                    line 8\nline 9\nline 10 ERROR\nline 11 error\nline 12\n"
+                ) && !result.contains("DEBUG"),
+                "{result}"
+            );
+        }
+
+        // Only the last lines are kept
+        for file in [&plain_file, &gzip_file] {
+            let (_, result) = read_log_content(Path::new(file), 0, 4, &line_filter).unwrap();
+            assert!(
+                result.ends_with("\nline 9\nline 10 ERROR\nline 11 error\nline 12\n"),
+                "{result}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_line_timestamp() {
+        let reference = datetime!(2024-01-10 00:00:00 +00:00);
+        assert_eq!(
+            line_timestamp("2023-12-31T23:00:00.5+01:00 message", reference),
+            Some(datetime!(2023-12-31 23:00:00.5 +01:00))
+        );
+        assert_eq!(
+            line_timestamp("Jan  9 08:15:00 host daemon[42]: message", reference),
+            Some(datetime!(2024-01-09 08:15:00 +00:00))
+        );
+        // A syslog date after the reference is from the previous year
+        assert_eq!(
+            line_timestamp("Dec 31 23:59:59 host daemon[42]: message", reference),
+            Some(datetime!(2023-12-31 23:59:59 +00:00))
+        );
+        assert_eq!(line_timestamp("message with no timestamp", reference), None);
+        assert_eq!(line_timestamp("", reference), None);
+    }
 }
@@ -1,7 +1,10 @@
+mod command_logs;
 mod config;
 mod error;
 mod log_utils;
+mod search;
 
 pub use config::*;
 pub use error::*;
 pub use log_utils::*;
+pub use search::*;
@@ -0,0 +1,120 @@
+use super::error::LogRetrievalError;
+use regex::Regex;
+use regex::RegexBuilder;
+use tedge_api::commands::LogUploadCmdPayload;
+
+/// The optional search fields of a `log_upload` command supported by the log manager
+pub const SEARCH_OPTIONS: [&str; 4] = ["searchRegex", "ignoreCase", "excludeText", "contextLines"];
+
+/// The text search criteria of a `log_upload` command
+///
+/// With no search text, all the lines are hits.
+#[derive(Debug, Default)]
+pub struct LogSearch {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+    context_lines: usize,
+}
+
+impl LogSearch {
+    /// Build the search criteria for a log request
+    ///
+    /// Unless `search_regex` is set, the search and exclude texts are plain text,
+    /// any regex meta-character being matched literally.
+    pub fn new(
+        search_text: Option<&str>,
+        exclude_text: Option<&str>,
+        search_regex: bool,
+        ignore_case: bool,
+        context_lines: usize,
+    ) -> Result<Self, LogRetrievalError> {
+        let pattern = |text: &str| {
+            let pattern = if search_regex {
+                text.to_string()
+            } else {
+                regex::escape(text)
+            };
+            RegexBuilder::new(&pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|err| LogRetrievalError::InvalidSearchPattern {
+                    pattern: text.to_string(),
+                    reason: err.to_string(),
+                })
+        };
+
+        Ok(LogSearch {
+            include: search_text.map(pattern).transpose()?,
+            exclude: exclude_text.map(pattern).transpose()?,
+            context_lines,
+        })
+    }
+
+    pub fn from_request(request: &LogUploadCmdPayload) -> Result<Self, LogRetrievalError> {
+        LogSearch::new(
+            request.search_text.as_deref(),
+            request.exclude_text.as_deref(),
+            request.search_regex,
+            request.ignore_case,
+            request.context_lines.unwrap_or_default(),
+        )
+    }
+
+    /// The number of lines to be kept before and after each hit
+    pub fn context_lines(&self) -> usize {
+        self.context_lines
+    }
+
+    pub fn is_hit(&self, line: &str) -> bool {
+        self.include
+            .as_ref()
+            .map_or(true, |include| include.is_match(line))
+    }
+
+    pub fn is_excluded(&self, line: &str) -> bool {
+        self.exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_match(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_search() {
+        let search = LogSearch::new(Some("error (code"), None, false, false, 0).unwrap();
+        assert!(search.is_hit("an error (code 42)"));
+        assert!(!search.is_hit("an ERROR (code 42)"));
+        assert!(!search.is_hit("an error code 42"));
+    }
+
+    #[test]
+    fn regex_search_ignoring_case() {
+        let search = LogSearch::new(Some(r"error|warn(ing)?"), None, true, true, 0).unwrap();
+        assert!(search.is_hit("[ERROR] disk full"));
+        assert!(search.is_hit("Warning: low memory"));
+        assert!(!search.is_hit("INFO: all good"));
+    }
+
+    #[test]
+    fn exclude_text() {
+        let search = LogSearch::new(None, Some("DEBUG"), false, false, 0).unwrap();
+        assert!(search.is_hit("DEBUG: any line is a hit"));
+        assert!(search.is_excluded("DEBUG: but this one is excluded"));
+        assert!(!search.is_excluded("INFO: kept"));
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        let error = LogSearch::new(Some("error("), None, true, false, 0).unwrap_err();
+        assert!(
+            matches!(error, LogRetrievalError::InvalidSearchPattern { ref pattern, .. } if pattern == "error("),
+            "{error:?}"
+        );
+
+        // The same pattern is valid as plain text
+        assert!(LogSearch::new(Some("error("), None, false, false, 0).is_ok());
+    }
+}
@@ -174,7 +174,7 @@ async fn log_manager_reloads_log_types() -> Result<(), anyhow::Error> {
         Some(
             MqttMessage::new(
                 &log_reload_topic,
-                r#"{"types":["type_one","type_three","type_two"]}"#
+                r#"{"types":["type_one","type_three","type_two"],"searchOptions":["searchRegex","ignoreCase","excludeText","contextLines"]}"#
             )
             .with_retain()
         )
@@ -71,6 +71,7 @@ pub struct BridgeHealth {
     name: &'static str,
     tx_health: mpsc::Sender<(&'static str, Status)>,
     last_err: Option<String>,
+    connected_once: bool,
 }
 
 impl BridgeHealth {
@@ -79,6 +80,7 @@ impl BridgeHealth {
             name,
             tx_health,
             last_err: Some("dummy error".into()),
+            connected_once: false,
         }
     }
 
@@ -87,7 +89,11 @@ impl BridgeHealth {
         let err = match result {
             Ok(event) => {
                 if let Event::Incoming(Incoming::ConnAck(_)) = event {
-                    info!("MQTT bridge connected to {name} broker")
+                    info!("MQTT bridge connected to {name} broker");
+                    if self.connected_once {
+                        tedge_metrics::record_bridge_reconnect(name);
+                    }
+                    self.connected_once = true;
                 }
                 None
             }
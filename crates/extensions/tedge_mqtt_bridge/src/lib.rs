@@ -1,6 +1,7 @@
 mod backoff;
 mod config;
 mod health;
+mod queue;
 #[cfg(test)]
 mod test_helpers;
 mod topics;
@@ -41,12 +42,17 @@ use tedge_actors::RuntimeRequest;
 use tedge_actors::RuntimeRequestSink;
 use tokio::sync::mpsc;
 use tracing::debug;
+use tracing::error;
 use tracing::info;
 
 pub type MqttConfig = mqtt_channel::Config;
 
 use crate::health::BridgeHealth;
 use crate::health::BridgeHealthMonitor;
+use crate::queue::BridgeQueue;
+use crate::queue::PersistentQueue;
+use crate::queue::QueueConfig;
+use crate::queue::QueueingClient;
 pub use mqtt_channel::DebugPayload;
 pub use mqtt_channel::MqttError;
 pub use mqtt_channel::MqttMessage;
@@ -122,8 +128,17 @@ impl MqttBridgeActorBuilder {
             .map(|t| SubscribeFilter::new(t.to_owned(), QoS::AtLeastOnce))
             .collect();
 
-        let [cloud_target, local_target] =
-            bidirectional_channel(cloud_client.clone(), local_client.clone(), in_flight.into());
+        let (tx_first, rx_first) = mpsc::channel(in_flight.into());
+        let (tx_second, rx_second) = mpsc::channel(in_flight.into());
+        let cloud_target = BridgeAsyncClient::new(cloud_client.clone(), tx_first, rx_second);
+        let queue = open_queue(tedge_config, service_name, in_flight.into())
+            .await
+            .map(|queue| BridgeQueue::new(queue, cloud_target.clone_sender()));
+        let local_target = BridgeAsyncClient::new(
+            QueueingClient::new(local_client.clone(), queue.clone()),
+            tx_second,
+            rx_first,
+        );
         let [(convert_local, bidir_local), (convert_cloud, bidir_cloud)] =
             rules.converters_and_bidirectional_topic_filters();
         let (tx_status, monitor) =
@@ -139,6 +154,7 @@ impl MqttBridgeActorBuilder {
             "local",
             local_topics,
             reconnect_policy.clone(),
+            queue.clone().map(QueueRole::Persist),
         ));
         tokio::spawn(half_bridge(
             cloud_event_loop,
@@ -150,6 +166,7 @@ impl MqttBridgeActorBuilder {
             "cloud",
             cloud_topics,
             reconnect_policy,
+            queue.map(QueueRole::Resend),
         ));
 
         Self {}
@@ -160,17 +177,45 @@ impl MqttBridgeActorBuilder {
     }
 }
 
-fn bidirectional_channel<Client: MqttClient + 'static>(
-    cloud_client: Client,
-    local_client: Client,
-    buffer: usize,
-) -> [BridgeAsyncClient<Client>; 2] {
-    let (tx_first, rx_first) = mpsc::channel(buffer);
-    let (tx_second, rx_second) = mpsc::channel(buffer);
-    [
-        BridgeAsyncClient::new(cloud_client, tx_first, rx_second),
-        BridgeAsyncClient::new(local_client, tx_second, rx_first),
-    ]
+/// Open the queue persisting the messages forwarded to the cloud, if enabled
+///
+/// If the queue cannot be opened, the bridge runs with no persistence.
+async fn open_queue(
+    tedge_config: &TEdgeConfig,
+    service_name: &str,
+    max_in_flight: usize,
+) -> Option<PersistentQueue> {
+    let queue_config = &tedge_config.mqtt.bridge.queue;
+    if !queue_config.persistent {
+        return None;
+    }
+
+    let config = QueueConfig {
+        dir: tedge_config.data.path.join("bridge").join(service_name),
+        max_size: queue_config.max_size,
+        max_in_flight,
+        high_priority_topics: queue_config.high_priority_topics.0.clone(),
+        low_priority_topics: queue_config.low_priority_topics.0.clone(),
+    };
+    let queue = tokio::task::spawn_blocking(move || PersistentQueue::open(config))
+        .await
+        .unwrap_or_else(|err| Err(std::io::Error::other(err)));
+    match queue {
+        Ok(queue) => Some(queue),
+        Err(err) => {
+            error!("Failed to open the bridge queue, messages will not be persisted: {err}");
+            None
+        }
+    }
+}
+
+/// The role of an half bridge regarding the persistent queue
+enum QueueRole {
+    /// Persist the received messages, which are then published by the queue
+    Persist(BridgeQueue),
+
+    /// Resend the in-flight messages of the queue when the connection is re-established
+    Resend(BridgeQueue),
 }
 
 enum BridgeMessage {
@@ -419,6 +464,17 @@ impl BridgeMessageSender {
 /// - The `half_bridge(cloud_event_loop,local_client)` receives cloud messages and publishes these message locally.
 /// - The `half_bridge(local_event_loop,cloud_client)` handles the acknowledgements: waiting for messages be acknowledged locally, before sending acks for the original messages.
 ///
+/// ## Persistent queue
+///
+/// When a `queue` is provided to the `half_bridge(local_event_loop,cloud_client)` with the [QueueRole::Persist] role,
+/// the local messages are persisted and acknowledged as soon as stored in the queue.
+/// The queue publishes these messages on the cloud, by priority and with a bounded number of messages in-flight,
+/// and removes them once acknowledged by the cloud, the local client being wrapped by a [QueueingClient].
+/// QoS 0 messages are not persisted but forwarded as is.
+///
+/// The same queue is provided to the `half_bridge(cloud_event_loop,local_client)` with the [QueueRole::Resend] role,
+/// so the in-flight messages are sent again when the cloud connection is re-established.
+///
 /// # Health topics
 /// The bridge will publish health information to `health_topic` (if supplied) on `target` to enable
 /// other components to establish bridge health. This is intended to be used the half with cloud
@@ -437,6 +493,7 @@ async fn half_bridge(
     name: &'static str,
     topics: Vec<SubscribeFilter>,
     reconnect_policy: TEdgeConfigReaderMqttBridgeReconnectPolicy,
+    queue: Option<QueueRole>,
 ) {
     let mut backoff = CustomBackoff::new(
         ::backoff::SystemClock {},
@@ -452,6 +509,7 @@ async fn half_bridge(
     let mut received = 0; // Count of messages received by this half-bridge
     let mut published = 0; // Count of messages published (by the companion)
     let mut acknowledged = 0; // Count of messages acknowledged (by the MQTT end-point of the companion)
+    let mut connected_before = false;
 
     loop {
         let res = recv_event_loop.poll().await;
@@ -480,6 +538,13 @@ async fn half_bridge(
 
         match notification {
             Event::Incoming(Incoming::ConnAck(_)) => {
+                // On reconnect, the messages sent by the queue but not acknowledged might have been lost
+                if connected_before {
+                    if let Some(QueueRole::Resend(queue)) = &queue {
+                        queue.reconnected();
+                    }
+                }
+                connected_before = true;
                 info!("Bridge {name} connection subscribing to {topics:?}");
                 let recv_client = recv_client.clone();
                 let topics = topics.clone();
@@ -493,7 +558,24 @@ async fn half_bridge(
                 if let Some(publish) = loop_breaker.ensure_not_looped(publish).await {
                     if let Some(topic) = transformer.convert_topic(&publish.topic) {
                         received += 1;
-                        target.publish(topic.to_string(), publish);
+                        match &queue {
+                            // Persisted messages are acknowledged as soon as stored,
+                            // unless persistence fails, the message being then forwarded as is
+                            Some(QueueRole::Persist(queue)) if publish.qos != QoS::AtMostOnce => {
+                                match queue.forward(topic.to_string(), publish.clone()).await {
+                                    Ok(()) => {
+                                        if let Err(err) = recv_client.ack(&publish).await {
+                                            error!("Bridge {name} connection failed to acknowledge persisted message: {err}");
+                                        }
+                                    }
+                                    Err(err) => {
+                                        error!("Bridge {name} connection failed to persist message: {err}");
+                                        target.publish(topic.to_string(), publish);
+                                    }
+                                }
+                            }
+                            _ => target.publish(topic.to_string(), publish),
+                        }
                     } else {
                         // Being not forwarded to this bridge target
                         // The message has to be acknowledged
@@ -1379,6 +1461,7 @@ mod tests {
                     "local",
                     self.subscription_topics.clone(),
                     TEdgeConfigReaderMqttBridgeReconnectPolicy::test_value(),
+                    None,
                 ));
                 let cloud_task = tokio::spawn(half_bridge(
                     self.cloud_events.clone(),
@@ -1390,6 +1473,7 @@ mod tests {
                     "cloud",
                     self.subscription_topics,
                     TEdgeConfigReaderMqttBridgeReconnectPolicy::test_value(),
+                    None,
                 ));
 
                 tokio::time::timeout(Duration::from_secs(5), self.local_events.all_processed())
@@ -0,0 +1,657 @@
+//! A disk-backed queue of the messages forwarded to the cloud
+//!
+//! When enabled, the messages received from the local broker are persisted in this queue
+//! and acknowledged to the local broker right away.
+//! They are removed from the queue only once acknowledged by the cloud,
+//! so they are neither lost when the cloud connection is down for a long time, nor on restart.
+//!
+//! Only the messages published with QoS 1 or 2 are persisted:
+//! QoS 0 messages are forwarded as is, with no delivery guarantee, as requested by their publisher.
+//!
+//! Each message is stored in its own file, named after a sequence number that gives the message order.
+//! On reconnect to the cloud, the messages sent but not acknowledged yet are sent again.
+//! The messages are sent by priority, then in order, with a bounded number of messages in-flight.
+//! When the queue is full, the oldest messages with the lowest priority are dropped first.
+use crate::have_same_content;
+use crate::topics::matches_ignore_dollar_prefix;
+use crate::BridgeMessageSender;
+use crate::MqttAck;
+use crate::MqttClient;
+use bytes::Bytes;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use rumqttc::ClientError;
+use rumqttc::Publish;
+use rumqttc::QoS;
+use rumqttc::SubscribeFilter;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::io;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tracing::error;
+use tracing::warn;
+
+const FILE_EXTENSION: &str = "msg";
+
+/// The priority of a message, derived from its target topic
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+/// Define the location, the size limit and the priorities of a persistent queue
+#[derive(Clone, Debug)]
+pub struct QueueConfig {
+    /// The directory where the messages are persisted
+    pub dir: Utf8PathBuf,
+
+    /// The maximum size in bytes of the persisted messages
+    pub max_size: u64,
+
+    /// The maximum number of messages sent and not acknowledged yet
+    pub max_in_flight: usize,
+
+    /// Topic filters of the messages to be sent first
+    pub high_priority_topics: Vec<String>,
+
+    /// Topic filters of the messages to be sent last and dropped first
+    pub low_priority_topics: Vec<String>,
+}
+
+impl QueueConfig {
+    fn priority(&self, target_topic: &str) -> Priority {
+        let matches = |filters: &Vec<String>| {
+            filters
+                .iter()
+                .any(|filter| matches_ignore_dollar_prefix(target_topic, filter))
+        };
+        if matches(&self.high_priority_topics) {
+            Priority::High
+        } else if matches(&self.low_priority_topics) {
+            Priority::Low
+        } else {
+            Priority::Normal
+        }
+    }
+}
+
+/// A message persisted in the queue
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuedMessage {
+    /// The topic on which the message has to be published
+    pub target_topic: String,
+
+    /// The message as received from the source
+    pub publish: Publish,
+}
+
+impl QueuedMessage {
+    fn size(&self) -> u64 {
+        (self.target_topic.len() + self.publish.topic.len() + self.publish.payload.len() + 6) as u64
+    }
+
+    /// Encode the message as: qos, retain, target topic, source topic, payload
+    ///
+    /// The topics are prefixed with their length on two bytes.
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.size() as usize);
+        bytes.push(self.publish.qos as u8);
+        bytes.push(self.publish.retain as u8);
+        for topic in [&self.target_topic, &self.publish.topic] {
+            bytes.extend_from_slice(&(topic.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(topic.as_bytes());
+        }
+        bytes.extend_from_slice(&self.publish.payload);
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let (&[qos, retain], bytes) = bytes.split_first_chunk::<2>()?;
+        let qos = match qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            _ => return None,
+        };
+        let (target_topic, bytes) = decode_string(bytes)?;
+        let (source_topic, payload) = decode_string(bytes)?;
+        let mut publish = Publish::new(source_topic, qos, payload.to_vec());
+        publish.retain = retain != 0;
+        Some(QueuedMessage {
+            target_topic,
+            publish,
+        })
+    }
+}
+
+fn decode_string(bytes: &[u8]) -> Option<(String, &[u8])> {
+    let (len, bytes) = bytes.split_first_chunk::<2>()?;
+    let len = u16::from_be_bytes(*len) as usize;
+    if bytes.len() < len {
+        return None;
+    }
+    let (string, bytes) = bytes.split_at(len);
+    Some((String::from_utf8(string.to_vec()).ok()?, bytes))
+}
+
+#[derive(Debug)]
+struct Entry {
+    message: QueuedMessage,
+    in_flight: bool,
+}
+
+/// A persistent queue of messages, ordered by priority and sequence number
+///
+/// This struct only maintains the index of the persisted messages:
+/// the files are written and removed by the [BridgeQueue], out of any lock.
+pub struct PersistentQueue {
+    config: QueueConfig,
+    entries: BTreeMap<(Priority, u64), Entry>,
+    in_flight: BTreeSet<(Priority, u64)>,
+    next_seq: u64,
+    size: u64,
+}
+
+impl PersistentQueue {
+    /// Open the queue, loading the messages persisted by a previous run
+    ///
+    /// All these messages are considered as not sent yet.
+    /// This function does blocking I/O and is expected to be called with [tokio::task::spawn_blocking].
+    pub fn open(config: QueueConfig) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        let mut queue = PersistentQueue {
+            config,
+            entries: BTreeMap::new(),
+            in_flight: BTreeSet::new(),
+            next_seq: 0,
+            size: 0,
+        };
+
+        for file in std::fs::read_dir(&queue.config.dir)? {
+            let path = file?.path();
+            let Some(seq) = path
+                .extension()
+                .filter(|ext| *ext == FILE_EXTENSION)
+                .and_then(|_| path.file_stem())
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            match std::fs::read(&path)
+                .ok()
+                .and_then(|bytes| QueuedMessage::decode(&bytes))
+            {
+                Some(message) => queue.insert(seq, message),
+                None => {
+                    warn!("Removing corrupted message from the bridge queue: {path:?}");
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+            queue.next_seq = queue.next_seq.max(seq + 1);
+        }
+
+        Ok(queue)
+    }
+
+    /// Allocate the sequence number of a new message
+    fn next_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    /// Index a message already persisted under the given sequence number,
+    /// dropping the oldest messages with the lowest priority if the queue is full
+    ///
+    /// Return the sequence numbers of the messages to be removed from disk:
+    /// the dropped messages, including the new one if there is no room for it.
+    fn push(&mut self, seq: u64, message: QueuedMessage) -> Vec<u64> {
+        let priority = self.config.priority(&message.target_topic);
+        let size = message.size();
+        let mut dropped = Vec::new();
+        while self.size + size > self.config.max_size {
+            let Some(evicted) = self.oldest_evictable(priority) else {
+                warn!(
+                    "Bridge queue is full: dropping message published on {}",
+                    message.publish.topic
+                );
+                dropped.push(seq);
+                return dropped;
+            };
+            if let Some(entry) = self.remove_entry(evicted) {
+                warn!(
+                    "Bridge queue is full: dropping older message published on {}",
+                    entry.message.publish.topic
+                );
+                dropped.push(evicted.1);
+            }
+        }
+
+        self.insert(seq, message);
+        dropped
+    }
+
+    /// Mark as in-flight and return the next messages to be sent
+    ///
+    /// Messages are returned by priority, then in order,
+    /// up to the maximum number of messages in-flight.
+    pub fn next_to_send(&mut self) -> Vec<QueuedMessage> {
+        let free_slots = self
+            .config
+            .max_in_flight
+            .saturating_sub(self.in_flight.len());
+        let mut next = Vec::new();
+        for (key, entry) in self.entries.iter_mut() {
+            if next.len() >= free_slots {
+                break;
+            }
+            if !entry.in_flight {
+                entry.in_flight = true;
+                self.in_flight.insert(*key);
+                next.push(entry.message.clone());
+            }
+        }
+        next
+    }
+
+    /// Remove an in-flight message that has been acknowledged
+    ///
+    /// The message is identified by its content, the first matching message being removed.
+    /// Return the sequence number of the removed message, if there is such an in-flight message.
+    fn acknowledge(&mut self, publish: &Publish) -> Option<u64> {
+        let acknowledged = self.in_flight.iter().copied().find(|key| {
+            self.entries
+                .get(key)
+                .is_some_and(|entry| have_same_content(&entry.message.publish, publish))
+        })?;
+        self.remove_entry(acknowledged);
+        Some(acknowledged.1)
+    }
+
+    /// Mark all the in-flight messages as to be sent again
+    ///
+    /// Used on reconnect, as the messages sent but not acknowledged might have been lost.
+    fn requeue_in_flight(&mut self) {
+        for key in std::mem::take(&mut self.in_flight) {
+            if let Some(entry) = self.entries.get_mut(&key) {
+                entry.in_flight = false;
+            }
+        }
+    }
+
+    fn insert(&mut self, seq: u64, message: QueuedMessage) {
+        let priority = self.config.priority(&message.target_topic);
+        self.size += message.size();
+        self.entries.insert(
+            (priority, seq),
+            Entry {
+                message,
+                in_flight: false,
+            },
+        );
+    }
+
+    /// The oldest message not in-flight and with a priority lower or equal to the given one
+    fn oldest_evictable(&self, priority: Priority) -> Option<(Priority, u64)> {
+        [Priority::Low, Priority::Normal, Priority::High]
+            .into_iter()
+            .filter(|p| *p >= priority)
+            .find_map(|p| {
+                self.entries
+                    .range((p, 0)..=(p, u64::MAX))
+                    .find(|(_, entry)| !entry.in_flight)
+                    .map(|(key, _)| *key)
+            })
+    }
+
+    fn remove_entry(&mut self, key: (Priority, u64)) -> Option<Entry> {
+        let entry = self.entries.remove(&key)?;
+        self.size -= entry.message.size();
+        self.in_flight.remove(&key);
+        Some(entry)
+    }
+}
+
+fn file_path(dir: &Utf8Path, seq: u64) -> Utf8PathBuf {
+    dir.join(format!("{seq:020}.{FILE_EXTENSION}"))
+}
+
+/// Persist a message, making sure the content is on disk before the file is renamed as a queue entry
+async fn write_message(dir: Utf8PathBuf, seq: u64, bytes: Vec<u8>) -> io::Result<()> {
+    blocking(move || {
+        let path = file_path(&dir, seq);
+        let tmp_path = path.with_extension("tmp");
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_data()?;
+        std::fs::rename(&tmp_path, &path)
+    })
+    .await
+}
+
+/// Remove the persisted messages with the given sequence numbers
+async fn remove_messages(dir: Utf8PathBuf, seqs: Vec<u64>) -> io::Result<()> {
+    if seqs.is_empty() {
+        return Ok(());
+    }
+    blocking(move || {
+        for seq in seqs {
+            match std::fs::remove_file(file_path(&dir, seq)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => (),
+            }
+        }
+        Ok(())
+    })
+    .await
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> io::Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// A persistent queue shared by the two bridge halves
+///
+/// - The local half pushes the messages to be forwarded to the cloud.
+/// - The cloud half acknowledges the messages, via the [QueueingClient] used as its target,
+///   and requeues the in-flight messages when the cloud connection is re-established.
+///
+/// Each time a message is pushed or acknowledged, the next pending messages are sent to the cloud.
+/// The lock on the queue index is never held while reading or writing files.
+#[derive(Clone)]
+pub(crate) struct BridgeQueue {
+    queue: Arc<Mutex<PersistentQueue>>,
+    dir: Utf8PathBuf,
+    cloud: BridgeMessageSender,
+}
+
+impl BridgeQueue {
+    /// Create the shared queue, sending to the cloud the messages persisted by a previous run
+    pub fn new(queue: PersistentQueue, cloud: BridgeMessageSender) -> Self {
+        let bridge_queue = BridgeQueue {
+            dir: queue.config.dir.clone(),
+            queue: Arc::new(Mutex::new(queue)),
+            cloud,
+        };
+        bridge_queue.send_next(&mut bridge_queue.queue.lock().unwrap());
+        bridge_queue
+    }
+
+    /// Persist a message to be published to the cloud on the given topic
+    pub async fn forward(&self, target_topic: String, publish: Publish) -> io::Result<()> {
+        let message = QueuedMessage {
+            target_topic,
+            publish,
+        };
+        let seq = self.queue.lock().unwrap().next_seq();
+        write_message(self.dir.clone(), seq, message.encode()).await?;
+
+        let dropped = {
+            let mut queue = self.queue.lock().unwrap();
+            let dropped = queue.push(seq, message);
+            self.send_next(&mut queue);
+            dropped
+        };
+        remove_messages(self.dir.clone(), dropped).await
+    }
+
+    /// Remove a message acknowledged by the cloud
+    ///
+    /// Return false if this message is not in the queue.
+    pub async fn acknowledge(&self, publish: &Publish) -> bool {
+        let acknowledged = {
+            let mut queue = self.queue.lock().unwrap();
+            let acknowledged = queue.acknowledge(publish);
+            self.send_next(&mut queue);
+            acknowledged
+        };
+        let Some(seq) = acknowledged else {
+            return false;
+        };
+        if let Err(err) = remove_messages(self.dir.clone(), vec![seq]).await {
+            error!("Failed to remove acknowledged message from the bridge queue: {err}");
+        }
+        true
+    }
+
+    /// Send again the in-flight messages, as the cloud connection has been re-established
+    pub fn reconnected(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.requeue_in_flight();
+        self.send_next(&mut queue);
+    }
+
+    fn send_next(&self, queue: &mut PersistentQueue) {
+        let mut cloud = self.cloud.clone();
+        for message in queue.next_to_send() {
+            cloud.publish(message.target_topic, message.publish)
+        }
+    }
+}
+
+/// The client of the local broker, acknowledging the queued messages on the queue
+///
+/// The messages which are not queued, because not persisted, are acknowledged on the local broker.
+#[derive(Clone)]
+pub(crate) struct QueueingClient<Client> {
+    client: Client,
+    queue: Option<BridgeQueue>,
+}
+
+impl<Client> QueueingClient<Client> {
+    pub fn new(client: Client, queue: Option<BridgeQueue>) -> Self {
+        QueueingClient { client, queue }
+    }
+}
+
+#[async_trait::async_trait]
+impl<Client: MqttClient> MqttAck for QueueingClient<Client> {
+    async fn ack(&self, publish: &Publish) -> Result<(), ClientError> {
+        match &self.queue {
+            Some(queue) if queue.acknowledge(publish).await => Ok(()),
+            _ => self.client.ack(publish).await,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<Client: MqttClient> MqttClient for QueueingClient<Client> {
+    async fn subscribe_many(&self, topics: Vec<SubscribeFilter>) -> Result<(), ClientError> {
+        self.client.subscribe_many(topics).await
+    }
+
+    async fn publish(
+        &self,
+        topic: String,
+        qos: QoS,
+        retain: bool,
+        payload: Bytes,
+    ) -> Result<(), ClientError> {
+        self.client.publish(topic, qos, retain, payload).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BridgeMessage;
+    use tedge_test_utils::fs::TempTedgeDir;
+    use tokio::sync::mpsc;
+
+    fn config(dir: &TempTedgeDir, max_size: u64) -> QueueConfig {
+        QueueConfig {
+            dir: dir.utf8_path().join("queue"),
+            max_size,
+            max_in_flight: 2,
+            high_priority_topics: vec!["alarm/#".to_string()],
+            low_priority_topics: vec!["measurement/#".to_string()],
+        }
+    }
+
+    fn publish(topic: &str, payload: &str) -> Publish {
+        Publish::new(topic, QoS::AtLeastOnce, payload)
+    }
+
+    /// Index a message, returning false if dropped
+    fn push(queue: &mut PersistentQueue, target_topic: &str, publish: Publish) -> bool {
+        let seq = queue.next_seq();
+        let message = QueuedMessage {
+            target_topic: target_topic.to_string(),
+            publish,
+        };
+        !queue.push(seq, message).contains(&seq)
+    }
+
+    fn sent_topics(queue: &mut PersistentQueue) -> Vec<String> {
+        queue
+            .next_to_send()
+            .into_iter()
+            .map(|message| message.target_topic)
+            .collect()
+    }
+
+    fn bridge_queue(config: QueueConfig) -> (BridgeQueue, mpsc::UnboundedReceiver<BridgeMessage>) {
+        let (unbounded_tx, unbounded_rx) = mpsc::unbounded_channel();
+        let queue = PersistentQueue::open(config).unwrap();
+        let queue = BridgeQueue::new(queue, BridgeMessageSender { unbounded_tx });
+        (queue, unbounded_rx)
+    }
+
+    fn published_topics(cloud: &mut mpsc::UnboundedReceiver<BridgeMessage>) -> Vec<String> {
+        let mut topics = Vec::new();
+        while let Ok(message) = cloud.try_recv() {
+            if let BridgeMessage::BridgePub { target_topic, .. } = message {
+                topics.push(target_topic);
+            }
+        }
+        topics
+    }
+
+    fn persisted_files(dir: &TempTedgeDir) -> usize {
+        std::fs::read_dir(dir.utf8_path().join("queue"))
+            .unwrap()
+            .count()
+    }
+
+    #[test]
+    fn messages_are_sent_by_priority_then_in_order() {
+        let ttd = TempTedgeDir::new();
+        let mut queue = PersistentQueue::open(config(&ttd, 1024)).unwrap();
+
+        push(&mut queue, "measurement/1", publish("c8y/m/1", "m1"));
+        push(&mut queue, "event/1", publish("c8y/e/1", "e1"));
+        push(&mut queue, "alarm/1", publish("c8y/a/1", "a1"));
+        push(&mut queue, "alarm/2", publish("c8y/a/2", "a2"));
+
+        assert_eq!(sent_topics(&mut queue), vec!["alarm/1", "alarm/2"]);
+        assert_eq!(sent_topics(&mut queue), Vec::<String>::new());
+
+        assert!(queue.acknowledge(&publish("c8y/a/1", "a1")).is_some());
+        assert!(queue.acknowledge(&publish("c8y/a/1", "a1")).is_none());
+        assert_eq!(sent_topics(&mut queue), vec!["event/1"]);
+
+        assert!(queue.acknowledge(&publish("c8y/e/1", "e1")).is_some());
+        assert_eq!(sent_topics(&mut queue), vec!["measurement/1"]);
+    }
+
+    #[tokio::test]
+    async fn messages_survive_restarts() {
+        let ttd = TempTedgeDir::new();
+        let (queue, mut cloud) = bridge_queue(config(&ttd, 1024));
+        queue
+            .forward("event/1".into(), publish("c8y/e/1", "e1"))
+            .await
+            .unwrap();
+        queue
+            .forward("event/2".into(), publish("c8y/e/2", "e2"))
+            .await
+            .unwrap();
+        assert_eq!(published_topics(&mut cloud), vec!["event/1", "event/2"]);
+        assert!(queue.acknowledge(&publish("c8y/e/1", "e1")).await);
+        assert_eq!(persisted_files(&ttd), 1);
+        drop(queue);
+
+        // The message sent but not acknowledged is sent again
+        let mut queue = PersistentQueue::open(config(&ttd, 1024)).unwrap();
+        assert_eq!(
+            queue.next_to_send(),
+            vec![QueuedMessage {
+                target_topic: "event/2".to_string(),
+                publish: publish("c8y/e/2", "e2")
+            }]
+        );
+
+        push(&mut queue, "event/3", publish("c8y/e/3", "e3"));
+        assert_eq!(sent_topics(&mut queue), vec!["event/3"]);
+    }
+
+    #[tokio::test]
+    async fn in_flight_messages_are_sent_again_on_reconnect() {
+        let ttd = TempTedgeDir::new();
+        let (queue, mut cloud) = bridge_queue(config(&ttd, 1024));
+        for i in 1..=3 {
+            queue
+                .forward(format!("event/{i}"), publish(&format!("c8y/e/{i}"), "e"))
+                .await
+                .unwrap();
+        }
+        assert_eq!(published_topics(&mut cloud), vec!["event/1", "event/2"]);
+
+        queue.reconnected();
+        assert_eq!(published_topics(&mut cloud), vec!["event/1", "event/2"]);
+
+        assert!(queue.acknowledge(&publish("c8y/e/1", "e")).await);
+        assert_eq!(published_topics(&mut cloud), vec!["event/3"]);
+    }
+
+    #[tokio::test]
+    async fn dropped_messages_are_removed_from_disk() {
+        // An event takes 22 bytes
+        let ttd = TempTedgeDir::new();
+        let (queue, _cloud) = bridge_queue(QueueConfig {
+            max_in_flight: 0,
+            ..config(&ttd, 50)
+        });
+        for i in 1..=3 {
+            queue
+                .forward(format!("event/{i}"), publish(&format!("c8y/e/{i}"), "e1"))
+                .await
+                .unwrap();
+        }
+        assert_eq!(persisted_files(&ttd), 2);
+    }
+
+    #[test]
+    fn oldest_messages_with_lowest_priority_are_dropped_first() {
+        // An alarm takes 22 bytes, a measurement 28
+        let ttd = TempTedgeDir::new();
+        let mut queue = PersistentQueue::open(config(&ttd, 78)).unwrap();
+
+        assert!(push(&mut queue, "alarm/1", publish("c8y/a/1", "a1")));
+        assert!(push(&mut queue, "measurement/1", publish("c8y/m/1", "m1")));
+        assert!(push(&mut queue, "measurement/2", publish("c8y/m/2", "m2")));
+
+        // Room is made by dropping the oldest measurement
+        assert!(push(&mut queue, "alarm/2", publish("c8y/a/2", "a2")));
+        assert!(push(&mut queue, "measurement/3", publish("c8y/m/3", "m3")));
+        assert!(push(&mut queue, "alarm/3", publish("c8y/a/3", "a3")));
+
+        // Alarms are never dropped to make room for a measurement
+        assert!(!push(&mut queue, "measurement/4", publish("c8y/m/4", "m4")));
+
+        queue.config.max_in_flight = 10;
+        assert_eq!(
+            sent_topics(&mut queue),
+            vec!["alarm/1", "alarm/2", "alarm/3"]
+        );
+    }
+}
@@ -13,6 +13,7 @@ use tedge_actors::Builder;
 use tedge_actors::ChannelError;
 use tedge_actors::CombinedReceiver;
 use tedge_actors::DynSender;
+use tedge_actors::MappingSender;
 use tedge_actors::MessageReceiver;
 use tedge_actors::MessageSink;
 use tedge_actors::MessageSource;
@@ -20,20 +21,45 @@ use tedge_actors::RuntimeError;
 use tedge_actors::RuntimeRequest;
 use tedge_actors::RuntimeRequestSink;
 use tedge_actors::Sender;
+use tedge_metrics::Direction;
 
 pub type MqttConfig = mqtt_channel::Config;
 pub use mqtt_channel::DebugPayload;
 pub use mqtt_channel::MqttError;
 pub use mqtt_channel::MqttMessage;
 pub use mqtt_channel::QoS;
+pub use mqtt_channel::SubscriptionHandle;
 pub use mqtt_channel::Topic;
 pub use mqtt_channel::TopicFilter;
 
+/// A request sent by a peer of the MQTT actor to update its subscriptions at runtime
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SubscriptionRequest {
+    Subscribe(TopicFilter),
+    Unsubscribe(TopicFilter),
+}
+
+/// A source of MQTT messages whose peers can update their subscriptions at runtime
+pub trait DynSubscriptionsSource: MessageSource<MqttMessage, TopicFilter> {
+    /// Connect a peer with an initial set of subscriptions,
+    /// returning a sender to subscribe to or unsubscribe from topics at runtime
+    fn connect_dynamic_sink(
+        &mut self,
+        subscriptions: TopicFilter,
+        peer: &impl MessageSink<MqttMessage>,
+    ) -> DynSender<SubscriptionRequest>;
+}
+
+/// A subscription request along with the index of the requesting peer
+type PeerSubscriptionRequest = (usize, SubscriptionRequest);
+
 pub struct MqttActorBuilder {
     mqtt_config: mqtt_channel::Config,
     input_receiver: CombinedReceiver<MqttMessage>,
     publish_sender: mpsc::Sender<MqttMessage>,
     pub subscriber_addresses: Vec<(TopicFilter, DynSender<MqttMessage>)>,
+    subscription_sender: mpsc::UnboundedSender<PeerSubscriptionRequest>,
+    subscription_receiver: mpsc::UnboundedReceiver<PeerSubscriptionRequest>,
     signal_sender: mpsc::Sender<RuntimeRequest>,
 }
 
@@ -42,12 +68,15 @@ impl MqttActorBuilder {
         let (publish_sender, publish_receiver) = mpsc::channel(10);
         let (signal_sender, signal_receiver) = mpsc::channel(10);
         let input_receiver = CombinedReceiver::new(publish_receiver, signal_receiver);
+        let (subscription_sender, subscription_receiver) = mpsc::unbounded();
 
         MqttActorBuilder {
             mqtt_config: config,
             input_receiver,
             publish_sender,
             subscriber_addresses: Vec::new(),
+            subscription_sender,
+            subscription_receiver,
             signal_sender,
         }
     }
@@ -67,7 +96,12 @@ impl MqttActorBuilder {
         }
 
         let mqtt_config = self.mqtt_config.with_subscriptions(combined_topic_filter);
-        MqttActor::new(mqtt_config, self.input_receiver, self.subscriber_addresses)
+        MqttActor::new(
+            mqtt_config,
+            self.input_receiver,
+            self.subscriber_addresses,
+            self.subscription_receiver,
+        )
     }
 }
 
@@ -84,6 +118,21 @@ impl MessageSource<MqttMessage, TopicFilter> for MqttActorBuilder {
     }
 }
 
+impl DynSubscriptionsSource for MqttActorBuilder {
+    fn connect_dynamic_sink(
+        &mut self,
+        subscriptions: TopicFilter,
+        peer: &impl MessageSink<MqttMessage>,
+    ) -> DynSender<SubscriptionRequest> {
+        let index = self.subscriber_addresses.len();
+        self.connect_sink(subscriptions, peer);
+        MappingSender::new(self.subscription_sender.clone().into(), move |request| {
+            Some((index, request))
+        })
+        .into()
+    }
+}
+
 impl MessageSink<MqttMessage> for MqttActorBuilder {
     fn get_sender(&self) -> DynSender<MqttMessage> {
         self.publish_sender.clone().into()
@@ -114,6 +163,7 @@ pub struct FromPeers {
 
 pub struct ToPeers {
     peer_senders: Vec<(TopicFilter, DynSender<MqttMessage>)>,
+    subscription_requests: mpsc::UnboundedReceiver<PeerSubscriptionRequest>,
 }
 
 impl FromPeers {
@@ -123,6 +173,7 @@ impl FromPeers {
     ) -> Result<(), RuntimeError> {
         while let Ok(Some(message)) = self.try_recv().await {
             tracing::debug!(target: "MQTT pub", "{message}");
+            tedge_metrics::record_mqtt_message(Direction::Published, &message.topic.name);
             SinkExt::send(outgoing_mqtt, message)
                 .await
                 .map_err(Box::new)?;
@@ -133,6 +184,7 @@ impl FromPeers {
 
         // Then, publish all the messages awaiting to be sent over MQTT
         while let Some(message) = self.recv().await {
+            tedge_metrics::record_mqtt_message(Direction::Published, &message.topic.name);
             SinkExt::send(outgoing_mqtt, message)
                 .await
                 .map_err(Box::new)?;
@@ -141,18 +193,86 @@ impl FromPeers {
     }
 }
 
+/// What is relayed to the peers: either a message received from MQTT or a subscription update
+enum PeerInput {
+    Message(MqttMessage),
+    SubscriptionRequest(usize, SubscriptionRequest),
+}
+
 impl ToPeers {
     async fn relay_messages_from(
         mut self,
         incoming_mqtt: &mut mpsc::UnboundedReceiver<MqttMessage>,
+        subscriptions: SubscriptionHandle,
     ) -> Result<(), RuntimeError> {
-        while let Some(message) = incoming_mqtt.next().await {
-            tracing::debug!(target: "MQTT recv", "{message}");
-            self.send(message).await?;
+        loop {
+            let input = tokio::select! {
+                message = incoming_mqtt.next() => match message {
+                    Some(message) => PeerInput::Message(message),
+                    None => break,
+                },
+                Some((peer, request)) = self.subscription_requests.next() => {
+                    PeerInput::SubscriptionRequest(peer, request)
+                }
+            };
+            match input {
+                PeerInput::Message(message) => {
+                    tracing::debug!(target: "MQTT recv", "{message}");
+                    tedge_metrics::record_mqtt_message(Direction::Received, &message.topic.name);
+                    self.send(message).await?;
+                }
+                PeerInput::SubscriptionRequest(peer, request) => {
+                    self.update_subscriptions(&subscriptions, peer, request)
+                        .await;
+                }
+            }
         }
         Ok(())
     }
 
+    /// Update the subscriptions of a peer, subscribing to or unsubscribing from the broker accordingly
+    ///
+    /// A topic pattern is only unsubscribed from the broker when no other peer is still subscribed to it.
+    async fn update_subscriptions(
+        &mut self,
+        subscriptions: &SubscriptionHandle,
+        peer: usize,
+        request: SubscriptionRequest,
+    ) {
+        let Some((peer_filter, _)) = self.peer_senders.get_mut(peer) else {
+            return;
+        };
+        let result = match request {
+            SubscriptionRequest::Subscribe(topics) => {
+                for pattern in topics.patterns() {
+                    tracing::info!(target: "MQTT sub", "{pattern}");
+                }
+                peer_filter.add_all(topics.clone());
+                subscriptions.subscribe(topics).await
+            }
+            SubscriptionRequest::Unsubscribe(topics) => {
+                for pattern in topics.patterns() {
+                    peer_filter.remove(pattern);
+                }
+                let mut unused = TopicFilter::empty();
+                for pattern in topics.patterns() {
+                    let still_used = self
+                        .peer_senders
+                        .iter()
+                        .any(|(filter, _)| filter.patterns().contains(pattern));
+                    if !still_used {
+                        tracing::info!(target: "MQTT unsub", "{pattern}");
+                        unused.add_unchecked(pattern);
+                    }
+                }
+                subscriptions.unsubscribe(unused).await
+            }
+        };
+        if let Err(err) = result {
+            tracing::error!(target: "MQTT", "Failed to update subscriptions: {err}");
+        }
+    }
+
     async fn send(&mut self, message: MqttMessage) -> Result<(), ChannelError> {
         for (topic_filter, peer_sender) in self.peer_senders.iter_mut() {
             if topic_filter.accept(&message) {
@@ -189,11 +309,15 @@ impl MqttActor {
         mqtt_config: mqtt_channel::Config,
         input_receiver: CombinedReceiver<MqttMessage>,
         peer_senders: Vec<(TopicFilter, DynSender<MqttMessage>)>,
+        subscription_requests: mpsc::UnboundedReceiver<PeerSubscriptionRequest>,
     ) -> Self {
         MqttActor {
             mqtt_config,
             from_peers: FromPeers { input_receiver },
-            to_peers: ToPeers { peer_senders },
+            to_peers: ToPeers {
+                peer_senders,
+                subscription_requests,
+            },
         }
     }
 }
@@ -218,7 +342,8 @@ impl Actor for MqttActor {
         tedge_utils::futures::select(
             self.from_peers
                 .relay_messages_to(&mut mqtt_client.published),
-            self.to_peers.relay_messages_from(&mut mqtt_client.received),
+            self.to_peers
+                .relay_messages_from(&mut mqtt_client.received, mqtt_client.subscriptions.clone()),
         )
         .await
     }
@@ -1,8 +1,15 @@
+use crate::DynSubscriptionsSource;
+use crate::SubscriptionRequest;
 use assert_json_diff::assert_json_include;
 use mqtt_channel::MqttMessage;
 use mqtt_channel::TopicFilter;
 use std::fmt::Debug;
+use tedge_actors::DynSender;
 use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NullSender;
+use tedge_actors::SimpleMessageBoxBuilder;
 
 pub async fn assert_received_contains_str<'a, M, I>(
     messages: &mut dyn MessageReceiver<M>,
@@ -148,3 +155,15 @@ where
         }
     }
 }
+
+/// A test double for the MQTT actor, ignoring the subscription updates
+impl DynSubscriptionsSource for SimpleMessageBoxBuilder<MqttMessage, MqttMessage> {
+    fn connect_dynamic_sink(
+        &mut self,
+        subscriptions: TopicFilter,
+        peer: &impl MessageSink<MqttMessage>,
+    ) -> DynSender<SubscriptionRequest> {
+        self.connect_sink(subscriptions, peer);
+        NullSender.into()
+    }
+}
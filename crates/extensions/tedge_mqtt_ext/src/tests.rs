@@ -180,6 +180,81 @@ async fn communicate_over_mqtt() {
     assert_eq!(messages, vec!["1", "2", "3", "A", "B", "C"])
 }
 
+#[tokio::test]
+async fn subscriptions_can_be_updated_at_runtime() {
+    let broker = mqtt_tests::test_mqtt_broker();
+    let mqtt_config = MqttConfig::default().with_port(broker.port);
+    let mut mqtt = MqttActorBuilder::new(mqtt_config);
+
+    let mut alice: MqttClient = MqttClientBuilder::new("Alice", &TopicFilter::empty())
+        .with_connection(&mut mqtt)
+        .build();
+
+    let carol_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage> =
+        SimpleMessageBoxBuilder::new("Carol", 16);
+    let mut carol_subscriptions = mqtt.connect_dynamic_sink(TopicFilter::empty(), &carol_builder);
+    let mut carol = carol_builder.build();
+
+    tokio::spawn(mqtt_actor(mqtt));
+
+    let first_topic = Topic::new_unchecked("dynamic/subscriptions/first");
+    let second_topic = Topic::new_unchecked("dynamic/subscriptions/second");
+    for (topic, payload) in [
+        (&first_topic, "first retained"),
+        (&second_topic, "second retained"),
+    ] {
+        broker
+            .publish_with_opts(&topic.name, payload, QoS::AtLeastOnce, true)
+            .await
+            .unwrap();
+    }
+
+    // Once subscribed, the retained message is received
+    carol_subscriptions
+        .send(SubscriptionRequest::Subscribe(first_topic.filter()))
+        .await
+        .unwrap();
+    assert_eq!(
+        carol.recv().await,
+        Some(MqttMessage::new(&first_topic, "first retained").with_retain())
+    );
+
+    // Once unsubscribed, no more messages are received on that topic
+    carol_subscriptions
+        .send(SubscriptionRequest::Unsubscribe(first_topic.filter()))
+        .await
+        .unwrap();
+    carol_subscriptions
+        .send(SubscriptionRequest::Subscribe(second_topic.filter()))
+        .await
+        .unwrap();
+    assert_eq!(
+        carol.recv().await,
+        Some(MqttMessage::new(&second_topic, "second retained").with_retain())
+    );
+
+    alice
+        .send(MqttMessage::new(&first_topic, "ignored"))
+        .await
+        .unwrap();
+    alice
+        .send(MqttMessage::new(&second_topic, "received"))
+        .await
+        .unwrap();
+    assert_eq!(
+        carol.recv().await,
+        Some(MqttMessage::new(&second_topic, "received"))
+    );
+
+    // Clear the retained messages
+    for topic in [first_topic, second_topic] {
+        broker
+            .publish_with_opts(&topic.name, "", QoS::AtLeastOnce, true)
+            .await
+            .unwrap();
+    }
+}
+
 async fn mqtt_actor(builder: MqttActorBuilder) {
     let mqtt_actor = builder.build();
     mqtt_actor.run().await.unwrap()
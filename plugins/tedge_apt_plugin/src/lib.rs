@@ -64,6 +64,9 @@ pub enum PluginOp {
     /// Install or remove multiple modules at once
     UpdateList,
 
+    /// Restore the previous versions of the modules after a failed update
+    Rollback,
+
     /// Prepare a sequences of install/remove commands
     Prepare,
 
@@ -158,7 +161,7 @@ fn run_op(apt: AptCli, tedge_config: Option<TEdgeConfig>) -> Result<ExitStatus,
             AptGetCmd::Remove(package).run()?
         }
 
-        PluginOp::UpdateList => {
+        PluginOp::UpdateList | PluginOp::Rollback => {
             let mut updates: Vec<SoftwareModuleUpdate> = Vec::new();
             let mut rdr = csv::ReaderBuilder::new()
                 .has_headers(false)